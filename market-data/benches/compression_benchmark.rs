@@ -0,0 +1,68 @@
+//! Benchmarks for IPC payload serialization and compression
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use orp_flow_market_data::config::Compression;
+use orp_flow_market_data::event::{BookSnapshot, PriceLevel, Venue};
+use orp_flow_market_data::orderbook::{OrderBook, OrderBookState};
+use orp_flow_market_data::publisher::compression;
+use rust_decimal::Decimal;
+
+fn create_state(levels: usize) -> OrderBookState {
+    // Varied quantities so the payload isn't trivially compressible
+    let level = |price: Decimal, i: usize| PriceLevel {
+        price,
+        quantity: Decimal::new((i as i64 * 7919) % 100_000 + 1, 4),
+    };
+    let snapshot = BookSnapshot {
+        venue: Venue::Binance,
+        symbol: "BTCUSDT".to_string(),
+        last_update_id: 1000,
+        bids: (0..levels)
+            .map(|i| level(Decimal::new(5_000_000 - i as i64, 2), i))
+            .collect(),
+        asks: (0..levels)
+            .map(|i| level(Decimal::new(5_000_001 + i as i64, 2), i + levels))
+            .collect(),
+    };
+
+    let mut book = OrderBook::new("BTCUSDT", levels);
+    book.init_snapshot(&snapshot);
+    book.state()
+}
+
+fn benchmark_serialize_compress(c: &mut Criterion) {
+    let codecs = [
+        ("none", Compression::None),
+        ("snappy", Compression::Snappy),
+        ("lz4", Compression::Lz4),
+    ];
+
+    for levels in [20, 500] {
+        let state = create_state(levels);
+        let raw = rmp_serde::to_vec(&state).unwrap();
+
+        let mut group = c.benchmark_group(format!("serialize_compress_{}_levels", levels));
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        for (name, codec) in codecs {
+            let size = compression::compress(codec, &raw).unwrap().len();
+            println!(
+                "{} levels, {}: {} -> {} bytes",
+                levels,
+                name,
+                raw.len(),
+                size
+            );
+
+            group.bench_with_input(BenchmarkId::from_parameter(name), &state, |b, state| {
+                b.iter(|| {
+                    let payload = rmp_serde::to_vec(black_box(state)).unwrap();
+                    black_box(compression::compress(codec, &payload).unwrap())
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, benchmark_serialize_compress);
+criterion_main!(benches);
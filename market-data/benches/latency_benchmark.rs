@@ -0,0 +1,166 @@
+//! End-to-end latency of the hot path over a recorded feed
+//!
+//! Replays a recording through parse, apply and serialize, the stages a
+//! message goes through between the socket and the IPC frame, and prints
+//! p50/p99/max per stage over several passes, so performance changes are
+//! measured against the same corpus:
+//!
+//! ```text
+//! cargo bench --bench latency_benchmark
+//! LATENCY_BENCH_RECORDING=session.jsonl cargo bench --bench latency_benchmark
+//! ```
+//!
+//! The bundled corpus, `benches/corpus/btcusdt.jsonl`, is 45 seconds of
+//! BTCUSDT in the recording format: a 200-level snapshot, then
+//! `@depth@100ms` diffs and trades synthesized to the exchange's message
+//! shape. Point `LATENCY_BENCH_RECORDING` at a capture
+//! (`orp-flow-market-data record`) to measure on live traffic.
+//! `LATENCY_BENCH_PASSES` sets the timed passes (20 by default), which
+//! follow one warm-up pass; under `cargo test` a single pass runs.
+
+use orp_flow_market_data::event::{BookSnapshot, MarketEvent};
+use orp_flow_market_data::orderbook::OrderBookManager;
+use orp_flow_market_data::parser;
+use orp_flow_market_data::recording::Recorded;
+use orp_flow_market_data::Config;
+use std::hint::black_box;
+use std::time::Instant;
+
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/corpus/btcusdt.jsonl");
+const DEFAULT_PASSES: usize = 20;
+
+/// Snapshots and raw frames of a recording, in order
+struct Corpus {
+    snapshots: Vec<BookSnapshot>,
+    messages: Vec<String>,
+}
+
+impl Corpus {
+    fn load(path: &str) -> Self {
+        let recording = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        let mut corpus = Corpus {
+            snapshots: Vec::new(),
+            messages: Vec::new(),
+        };
+        for line in recording.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(Recorded::Snapshot { snapshot, .. }) => corpus.snapshots.push(snapshot),
+                Ok(Recorded::Message { raw, .. }) => corpus.messages.push(raw),
+                Err(e) => panic!("{} is not a recording: {}", path, e),
+            }
+        }
+        assert!(!corpus.snapshots.is_empty(), "{} has no snapshots", path);
+        corpus
+    }
+}
+
+/// Per-message durations of each stage (nanoseconds)
+#[derive(Default)]
+struct Samples {
+    parse: Vec<u64>,
+    apply: Vec<u64>,
+    serialize: Vec<u64>,
+    total: Vec<u64>,
+    diffs: usize,
+    trades: usize,
+}
+
+/// Replay `corpus` once into fresh books built as the feed builds them,
+/// recording into `samples`
+fn replay(corpus: &Corpus, config: &Config, samples: &mut Samples) {
+    let mut manager = OrderBookManager::with_depth(config.depth_levels)
+        .with_overflow_levels(config.overflow_levels)
+        .with_crossed_policy(config.crossed_book_policy);
+    for snapshot in &corpus.snapshots {
+        manager.init_book(snapshot);
+    }
+    for raw in &corpus.messages {
+        let started = Instant::now();
+        let event = parser::parse_event(black_box(raw)).expect("unparseable frame");
+        let parsed = Instant::now();
+        samples.parse.push(nanos(started, parsed));
+
+        let update = match event {
+            Some(MarketEvent::DepthDelta(update)) => update,
+            Some(MarketEvent::Trade(_)) => {
+                samples.trades += 1;
+                continue;
+            }
+            _ => continue,
+        };
+        if !manager.apply_update(&update).expect("recorded diff failed") {
+            continue;
+        }
+        let state = manager.get_state(&update.symbol).expect("book missing");
+        let applied = Instant::now();
+        let frame = rmp_serde::to_vec(&state).expect("state serializes");
+        let serialized = Instant::now();
+        black_box(frame);
+
+        samples.apply.push(nanos(parsed, applied));
+        samples.serialize.push(nanos(applied, serialized));
+        samples.total.push(nanos(started, serialized));
+        samples.diffs += 1;
+    }
+}
+
+fn nanos(from: Instant, to: Instant) -> u64 {
+    to.duration_since(from).as_nanos() as u64
+}
+
+/// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[u64], pct: f64) -> u64 {
+    let rank = (sorted.len() as f64 * pct / 100.0).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn main() {
+    let path = std::env::var("LATENCY_BENCH_RECORDING").unwrap_or_else(|_| CORPUS.to_string());
+    let benching = std::env::args().any(|arg| arg == "--bench");
+    let passes = match std::env::var("LATENCY_BENCH_PASSES") {
+        Ok(passes) => passes
+            .parse()
+            .expect("LATENCY_BENCH_PASSES is not a number"),
+        Err(_) if benching => DEFAULT_PASSES,
+        Err(_) => 1,
+    };
+    let corpus = Corpus::load(&path);
+    let config = Config::default();
+
+    replay(&corpus, &config, &mut Samples::default());
+    let mut samples = Samples::default();
+    for _ in 0..passes {
+        replay(&corpus, &config, &mut samples);
+    }
+
+    println!(
+        "{}: {} messages, {} depth diffs applied and {} trades per pass, {} passes",
+        path,
+        corpus.messages.len(),
+        samples.diffs / passes.max(1),
+        samples.trades / passes.max(1),
+        passes
+    );
+    println!(
+        "{:<10} {:>10} {:>10} {:>10}",
+        "stage", "p50 ns", "p99 ns", "max ns"
+    );
+    for (stage, durations) in [
+        ("parse", &mut samples.parse),
+        ("apply", &mut samples.apply),
+        ("serialize", &mut samples.serialize),
+        ("total", &mut samples.total),
+    ] {
+        if durations.is_empty() {
+            continue;
+        }
+        durations.sort_unstable();
+        println!(
+            "{:<10} {:>10} {:>10} {:>10}",
+            stage,
+            percentile(durations, 50.0),
+            percentile(durations, 99.0),
+            durations[durations.len() - 1]
+        );
+    }
+}
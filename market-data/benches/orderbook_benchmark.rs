@@ -1,12 +1,12 @@
 //! Benchmarks for order book operations
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use market_data::orderbook::OrderBook;
-use market_data::parser::{DepthUpdate, OrderBookSnapshot, PriceLevel};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use orp_flow_market_data::event::{BookSnapshot, DepthDelta, PriceLevel, Venue};
+use orp_flow_market_data::orderbook::{Metric, MetricsConfig, OrderBook, Side, TickScale};
 use rust_decimal::Decimal;
 use std::str::FromStr;
 
-fn create_snapshot(levels: usize) -> OrderBookSnapshot {
+fn create_snapshot(levels: usize) -> BookSnapshot {
     let bids: Vec<PriceLevel> = (0..levels)
         .map(|i| PriceLevel {
             price: Decimal::from(50000 - i),
@@ -21,16 +21,18 @@ fn create_snapshot(levels: usize) -> OrderBookSnapshot {
         })
         .collect();
 
-    OrderBookSnapshot {
+    BookSnapshot {
+        venue: Venue::Binance,
+        symbol: "BTCUSDT".to_string(),
         last_update_id: 1000,
         bids,
         asks,
     }
 }
 
-fn create_update(base_id: u64) -> DepthUpdate {
-    DepthUpdate {
-        event_type: "depthUpdate".to_string(),
+fn create_update(base_id: u64) -> DepthDelta {
+    DepthDelta {
+        venue: Venue::Binance,
         event_time: 1672531200000,
         symbol: "BTCUSDT".to_string(),
         first_update_id: base_id,
@@ -46,6 +48,27 @@ fn create_update(base_id: u64) -> DepthUpdate {
     }
 }
 
+/// Updates moving levels around the top of a `create_snapshot` book,
+/// deleting every third
+fn create_update_stream(count: u64) -> Vec<DepthDelta> {
+    (0..count)
+        .map(|i| {
+            let mut update = create_update(1001 + 2 * i);
+            let offset = Decimal::from(i % 8);
+            let quantity = if i % 3 == 0 {
+                Decimal::ZERO
+            } else {
+                Decimal::from_str("0.25").unwrap()
+            };
+            update.bids[0].price -= offset;
+            update.bids[0].quantity = quantity;
+            update.asks[0].price += offset;
+            update.asks[0].quantity = quantity;
+            update
+        })
+        .collect()
+}
+
 fn benchmark_init_snapshot(c: &mut Criterion) {
     let snapshot = create_snapshot(100);
 
@@ -66,7 +89,7 @@ fn benchmark_apply_update(c: &mut Criterion) {
 
     c.bench_function("apply_update", |b| {
         b.iter(|| {
-            book.apply_update(black_box(&update));
+            let _ = book.apply_update(black_box(&update));
         })
     });
 }
@@ -95,10 +118,117 @@ fn benchmark_metrics_calculation(c: &mut Criterion) {
     });
 }
 
+/// Metric emission from running totals against walking a deep book for the
+/// same depth and imbalance figures
+fn benchmark_metric_emission(c: &mut Criterion) {
+    let snapshot = create_snapshot(1000);
+    let config = MetricsConfig {
+        // Slope walks the whole book by design
+        disabled: [Metric::Slope].into(),
+        ..MetricsConfig::default()
+    };
+    let mut book = OrderBook::new("BTCUSDT", 1000).with_metrics_config(config.clone());
+    book.init_snapshot(&snapshot);
+    let mut update_id = 1001;
+
+    let mut group = c.benchmark_group("metric_emission_1000_levels");
+    group.bench_function("running_totals", |b| {
+        b.iter(|| {
+            update_id += 2;
+            let _ = book.apply_update(black_box(&create_update(update_id)));
+            black_box(book.metrics());
+        })
+    });
+    group.bench_function("recomputed", |b| {
+        b.iter(|| {
+            update_id += 2;
+            let _ = book.apply_update(black_box(&create_update(update_id)));
+            black_box((
+                book.imbalance(config.imbalance_levels),
+                book.weighted_imbalance(
+                    config.weighted_imbalance_levels,
+                    config.weighted_imbalance_decay,
+                ),
+                book.depth_within_bps(Side::Bid, Decimal::MAX),
+                book.depth_within_bps(Side::Ask, Decimal::MAX),
+            ));
+        })
+    });
+    group.finish();
+}
+
+/// Decimal against fixed-point level storage: a stream of updates moving
+/// levels around a 100-level book retaining 200
+fn benchmark_representations(c: &mut Criterion) {
+    let snapshot = create_snapshot(200);
+    let updates = create_update_stream(64);
+    let scale = TickScale::from_sizes(
+        Decimal::from_str("0.01").unwrap(),
+        Decimal::from_str("0.00001").unwrap(),
+    );
+
+    let mut group = c.benchmark_group("representation_apply_64_updates");
+    for (name, scale) in [("decimal", None), ("fixed_point", Some(scale))] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let mut book = OrderBook::new("BTCUSDT", 100).with_overflow_levels(100);
+                    if let Some(scale) = scale {
+                        book = book.with_tick_scale(scale);
+                    }
+                    book.init_snapshot(&snapshot);
+                    book
+                },
+                |mut book| {
+                    for update in &updates {
+                        let _ = book.apply_update(black_box(update));
+                    }
+                    book
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// `BTreeMap` against sorted-`Vec` level storage for a 20-level book with
+/// a 20-level overflow, fed from a deeper snapshot
+fn benchmark_compact_storage(c: &mut Criterion) {
+    let snapshot = create_snapshot(100);
+    let updates = create_update_stream(64);
+
+    let mut group = c.benchmark_group("storage_apply_64_updates_depth_20");
+    for (name, compact) in [("btree_map", false), ("sorted_vec", true)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || {
+                    let mut book = OrderBook::new("BTCUSDT", 20)
+                        .with_overflow_levels(20)
+                        .with_compact_levels(compact);
+                    book.init_snapshot(&snapshot);
+                    book
+                },
+                |mut book| {
+                    for update in &updates {
+                        let _ = book.apply_update(black_box(update));
+                    }
+                    book
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_init_snapshot,
     benchmark_apply_update,
-    benchmark_metrics_calculation
+    benchmark_metrics_calculation,
+    benchmark_metric_emission,
+    benchmark_representations,
+    benchmark_compact_storage
 );
 criterion_main!(benches);
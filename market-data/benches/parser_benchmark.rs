@@ -0,0 +1,156 @@
+//! Benchmarks for WebSocket message parsing
+//!
+//! Counts heap allocations per message as well as time: a global allocator
+//! wrapper tallies every allocation, and each variant's count is printed
+//! before it is timed.
+//!
+//! `parse_recorded` measures throughput over the depth messages of a feed
+//! recording (`orp-flow-market-data record`) named by
+//! `PARSER_BENCH_RECORDING`, or over generated messages without one. To
+//! compare the parser backends, save a baseline and rerun with the feature:
+//!
+//! ```text
+//! cargo bench --bench parser_benchmark -- --save-baseline serde_json
+//! cargo bench --bench parser_benchmark --features simd-json -- --baseline serde_json
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use orp_flow_market_data::event::MarketEvent;
+use orp_flow_market_data::parser::{self, DepthUpdate, ParsedMessage};
+use orp_flow_market_data::recording::Recorded;
+use serde_json::Value;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Combined-stream depth update with `levels` bids and asks
+fn depth_message(levels: usize) -> String {
+    let side = |base: u64, step: i64| {
+        (0..levels as i64)
+            .map(|i| {
+                format!(
+                    r#"["{}.{:02}","{}.{:05}"]"#,
+                    base as i64 + step * i,
+                    i % 100,
+                    i % 7,
+                    i * 131 % 100_000
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    format!(
+        r#"{{"stream":"btcusdt@depth@100ms","data":{{"e":"depthUpdate","E":1672531200000,"s":"BTCUSDT","U":157,"u":160,"b":[{}],"a":[{}]}}}}"#,
+        side(50_000, -1),
+        side(50_001, 1)
+    )
+}
+
+/// Parse through a `Value` tree, as the parser used to
+fn parse_via_value(raw: &str) -> DepthUpdate {
+    let message: Value = serde_json::from_str(raw).unwrap();
+    serde_json::from_value(message["data"].clone()).unwrap()
+}
+
+/// Allocations made by one call of `f`, after a first call has set up any
+/// reused buffers
+fn allocations<T>(f: impl Fn() -> T) -> usize {
+    black_box(f());
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn benchmark_parse_depth(c: &mut Criterion) {
+    for levels in [10, 100] {
+        let raw = depth_message(levels);
+        println!(
+            "{} levels, allocations per message: value {}, parse {}, parse_event {}",
+            levels,
+            allocations(|| parse_via_value(&raw)),
+            allocations(|| ParsedMessage::parse(&raw).unwrap()),
+            allocations(|| parser::parse_event(&raw).unwrap()),
+        );
+
+        let mut group = c.benchmark_group(format!("parse_depth_{}_levels", levels));
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_function("value", |b| b.iter(|| parse_via_value(black_box(&raw))));
+        group.bench_function("parse", |b| {
+            b.iter(|| ParsedMessage::parse(black_box(&raw)).unwrap())
+        });
+        group.bench_function("parse_event", |b| {
+            b.iter(|| parser::parse_event(black_box(&raw)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+/// Depth messages of the recording at `PARSER_BENCH_RECORDING`, or
+/// generated ones of varying depth
+fn recorded_depth_messages() -> Vec<String> {
+    let Ok(path) = std::env::var("PARSER_BENCH_RECORDING") else {
+        return (0..1_000).map(|i| depth_message(1 + i % 50)).collect();
+    };
+    let recording = std::fs::read_to_string(&path).expect("PARSER_BENCH_RECORDING is unreadable");
+    let messages: Vec<String> = recording
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(Recorded::Message { raw, .. }) => Some(raw),
+            _ => None,
+        })
+        .filter(|raw| {
+            matches!(
+                parser::parse_event(raw),
+                Ok(Some(MarketEvent::DepthDelta(_)))
+            )
+        })
+        .collect();
+    assert!(!messages.is_empty(), "{} has no depth messages", path);
+    messages
+}
+
+fn benchmark_parse_recorded(c: &mut Criterion) {
+    let messages = recorded_depth_messages();
+    let bytes: usize = messages.iter().map(String::len).sum();
+    println!(
+        "{} depth messages, {} bytes on average",
+        messages.len(),
+        bytes / messages.len()
+    );
+
+    let mut group = c.benchmark_group("parse_recorded");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("parse_event", |b| {
+        b.iter(|| {
+            for raw in &messages {
+                black_box(parser::parse_event(black_box(raw)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_parse_depth, benchmark_parse_recorded);
+criterion_main!(benches);
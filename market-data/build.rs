@@ -0,0 +1,18 @@
+//! Generates protobuf bindings when the `protobuf` feature is enabled, plus
+//! the gRPC service with the `grpc` feature
+
+fn main() {
+    #[cfg(feature = "protobuf")]
+    {
+        // Use a vendored protoc so builds don't depend on a system install
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+        std::env::set_var("PROTOC", protoc);
+
+        tonic_build::configure()
+            .build_server(cfg!(feature = "grpc"))
+            .build_client(cfg!(feature = "grpc"))
+            .compile_protos(&["proto/market_data.proto"], &["proto"])
+            .expect("Failed to compile protos");
+    }
+    println!("cargo:rerun-if-changed=proto");
+}
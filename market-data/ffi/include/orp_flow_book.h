@@ -0,0 +1,110 @@
+#ifndef ORP_FLOW_BOOK_H
+#define ORP_FLOW_BOOK_H
+
+/* Generated by cbindgen from market-data/ffi/src/lib.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Outcome of a call
+typedef enum OrpStatus {
+  // Applied
+  ORP_STATUS_OK = 0,
+  // Not applied: the update is stale, or the book awaits a snapshot
+  ORP_STATUS_IGNORED = 1,
+  // A null pointer, a string that isn't UTF-8, or an update for another
+  // symbol
+  ORP_STATUS_INVALID_ARGUMENT = -1,
+  // The JSON isn't a depth snapshot or update
+  ORP_STATUS_PARSE_ERROR = -2,
+  // The update skipped ahead of the book, which now awaits a snapshot
+  ORP_STATUS_SEQUENCE_GAP = -3,
+  // The book failed otherwise and awaits a snapshot
+  ORP_STATUS_ERROR = -4,
+} OrpStatus;
+
+// Order book of one symbol
+typedef struct OrpBook OrpBook;
+
+// A price level
+typedef struct OrpLevel {
+  double price;
+  double quantity;
+} OrpLevel;
+
+// Metrics of a book; NaN where undefined (one side empty) or disabled
+typedef struct OrpMetrics {
+  uint64_t last_update_id;
+  double mid_price;
+  double microprice;
+  double spread_bps;
+  double imbalance;
+  double weighted_imbalance;
+  double bid_depth;
+  double ask_depth;
+  uintptr_t bid_levels;
+  uintptr_t ask_levels;
+} OrpMetrics;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Message of the last failed call on this thread, or null; valid until the
+// thread's next failing call
+const char *orp_last_error(void);
+
+// Create an empty book keeping up to `max_depth` levels per side; null if
+// `symbol` is invalid. Free it with `orp_book_free`.
+//
+// # Safety
+// `symbol` is null or a valid NUL-terminated string.
+struct OrpBook *orp_book_new(const char *symbol, uintptr_t max_depth);
+
+// Free a book
+//
+// # Safety
+// `book` is null or came from `orp_book_new` and wasn't freed yet.
+void orp_book_free(struct OrpBook *book);
+
+// (Re)initialize the book from a REST depth snapshot
+// (`{"lastUpdateId":..,"bids":[["price","qty"],..],"asks":[..]}`)
+//
+// # Safety
+// `book` came from `orp_book_new`; `json` is null or a valid
+// NUL-terminated string.
+enum OrpStatus orp_book_apply_snapshot(struct OrpBook *book, const char *json);
+
+// Apply a diff depth update, as received on a `<symbol>@depth` stream
+// (raw or wrapped in a combined-stream message)
+//
+// # Safety
+// `book` came from `orp_book_new`; `json` is null or a valid
+// NUL-terminated string.
+enum OrpStatus orp_book_apply_update(struct OrpBook *book, const char *json);
+
+// Write the best bid to `out`; false if the bid side is empty
+//
+// # Safety
+// `book` came from `orp_book_new`; `out` is null or valid for writes.
+bool orp_book_best_bid(const struct OrpBook *book, struct OrpLevel *out);
+
+// Write the best ask to `out`; false if the ask side is empty
+//
+// # Safety
+// `book` came from `orp_book_new`; `out` is null or valid for writes.
+bool orp_book_best_ask(const struct OrpBook *book, struct OrpLevel *out);
+
+// Write the book's metrics to `out`
+//
+// # Safety
+// `book` came from `orp_book_new`; `out` is null or valid for writes.
+enum OrpStatus orp_book_metrics(const struct OrpBook *book, struct OrpMetrics *out);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* ORP_FLOW_BOOK_H */
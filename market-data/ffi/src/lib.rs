@@ -0,0 +1,350 @@
+//! C ABI for embedding the order book engine
+//!
+//! Lets C and C++ trading systems keep books with the same logic as the
+//! market data handler, fed the exchange's own JSON:
+//!
+//! ```c
+//! OrpBook *book = orp_book_new("BTCUSDT", 1000);
+//! orp_book_apply_snapshot(book, rest_depth_json);   // GET /api/v3/depth
+//! orp_book_apply_update(book, ws_depth_update_json); // <symbol>@depth
+//! OrpLevel bid;
+//! if (orp_book_best_bid(book, &bid)) { ... }
+//! orp_book_free(book);
+//! ```
+//!
+//! Calls returning an `OrpStatus` below `ORP_STATUS_OK` leave a message for
+//! `orp_last_error`. A book is not thread-safe; callers serialize access to
+//! each one. The header, `include/orp_flow_book.h`, is regenerated by
+//! cbindgen on every build.
+//!
+//! Builds a shared (`liborp_flow_book.so`) and a static library of the
+//! parser and order book alone, without the core crate's `runtime`; build
+//! it on its own (`cargo build -p orp-flow-market-data-ffi --release`),
+//! since a workspace build unifies the core crate's default features in.
+
+use orp_flow_market_data::error::MarketDataError;
+use orp_flow_market_data::event::MarketEvent;
+use orp_flow_market_data::orderbook::{Level, OrderBook};
+use orp_flow_market_data::parser::{self, OrderBookSnapshot};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// Order book of one symbol
+pub struct OrpBook {
+    book: OrderBook,
+    symbol: String,
+}
+
+/// Outcome of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrpStatus {
+    /// Applied
+    Ok = 0,
+    /// Not applied: the update is stale, or the book awaits a snapshot
+    Ignored = 1,
+    /// A null pointer, a string that isn't UTF-8, or an update for another
+    /// symbol
+    InvalidArgument = -1,
+    /// The JSON isn't a depth snapshot or update
+    ParseError = -2,
+    /// The update skipped ahead of the book, which now awaits a snapshot
+    SequenceGap = -3,
+    /// The book failed otherwise and awaits a snapshot
+    Error = -4,
+}
+
+/// A price level
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrpLevel {
+    pub price: f64,
+    pub quantity: f64,
+}
+
+/// Metrics of a book; NaN where undefined (one side empty) or disabled
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrpMetrics {
+    pub last_update_id: u64,
+    pub mid_price: f64,
+    pub microprice: f64,
+    pub spread_bps: f64,
+    pub imbalance: f64,
+    pub weighted_imbalance: f64,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: OrpStatus, message: impl ToString) -> OrpStatus {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+fn float(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// Write `level` to `out`, if there is one and somewhere to write it
+///
+/// # Safety
+/// `out` is null or valid for writes.
+unsafe fn level(level: Option<Level>, out: *mut OrpLevel) -> bool {
+    match (level, out.is_null()) {
+        (Some(level), false) => {
+            *out = OrpLevel {
+                price: float(level.price),
+                quantity: float(level.quantity),
+            };
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Borrow a C string as UTF-8
+///
+/// # Safety
+/// `s` is null or a valid NUL-terminated string.
+unsafe fn text<'a>(s: *const c_char) -> Result<&'a str, OrpStatus> {
+    if s.is_null() {
+        return Err(fail(OrpStatus::InvalidArgument, "Null string"));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| fail(OrpStatus::InvalidArgument, e))
+}
+
+/// Message of the last failed call on this thread, or null; valid until the
+/// thread's next failing call
+#[no_mangle]
+pub extern "C" fn orp_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Create an empty book keeping up to `max_depth` levels per side; null if
+/// `symbol` is invalid. Free it with `orp_book_free`.
+///
+/// # Safety
+/// `symbol` is null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_new(symbol: *const c_char, max_depth: usize) -> *mut OrpBook {
+    match text(symbol) {
+        Ok(symbol) => Box::into_raw(Box::new(OrpBook {
+            book: OrderBook::new(symbol, max_depth),
+            symbol: symbol.to_string(),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Free a book
+///
+/// # Safety
+/// `book` is null or came from `orp_book_new` and wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_free(book: *mut OrpBook) {
+    if !book.is_null() {
+        drop(Box::from_raw(book));
+    }
+}
+
+/// (Re)initialize the book from a REST depth snapshot
+/// (`{"lastUpdateId":..,"bids":[["price","qty"],..],"asks":[..]}`)
+///
+/// # Safety
+/// `book` came from `orp_book_new`; `json` is null or a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_apply_snapshot(
+    book: *mut OrpBook,
+    json: *const c_char,
+) -> OrpStatus {
+    let Some(book) = book.as_mut() else {
+        return fail(OrpStatus::InvalidArgument, "Null book");
+    };
+    let json = match text(json) {
+        Ok(json) => json,
+        Err(status) => return status,
+    };
+    match serde_json::from_str::<OrderBookSnapshot>(json) {
+        Ok(snapshot) => {
+            book.book.init_snapshot(&snapshot.into_event(&book.symbol));
+            OrpStatus::Ok
+        }
+        Err(e) => fail(OrpStatus::ParseError, e),
+    }
+}
+
+/// Apply a diff depth update, as received on a `<symbol>@depth` stream
+/// (raw or wrapped in a combined-stream message)
+///
+/// # Safety
+/// `book` came from `orp_book_new`; `json` is null or a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_apply_update(
+    book: *mut OrpBook,
+    json: *const c_char,
+) -> OrpStatus {
+    let Some(book) = book.as_mut() else {
+        return fail(OrpStatus::InvalidArgument, "Null book");
+    };
+    let json = match text(json) {
+        Ok(json) => json,
+        Err(status) => return status,
+    };
+    let delta = match parser::parse_event(json) {
+        Ok(Some(MarketEvent::DepthDelta(delta))) => delta,
+        Ok(_) => return fail(OrpStatus::ParseError, "Not a depth update"),
+        Err(e) => return fail(OrpStatus::ParseError, e),
+    };
+    if !delta.symbol.eq_ignore_ascii_case(&book.symbol) {
+        return fail(
+            OrpStatus::InvalidArgument,
+            format!("Update for {} applied to {}", delta.symbol, book.symbol),
+        );
+    }
+    match book.book.apply_update(&delta) {
+        Ok(true) => OrpStatus::Ok,
+        Ok(false) => OrpStatus::Ignored,
+        Err(e @ MarketDataError::SequenceMismatch { .. }) => fail(OrpStatus::SequenceGap, e),
+        Err(e) => fail(OrpStatus::Error, e),
+    }
+}
+
+/// Write the best bid to `out`; false if the bid side is empty
+///
+/// # Safety
+/// `book` came from `orp_book_new`; `out` is null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_best_bid(book: *const OrpBook, out: *mut OrpLevel) -> bool {
+    book.as_ref()
+        .is_some_and(|book| level(book.book.bbo().best_bid, out))
+}
+
+/// Write the best ask to `out`; false if the ask side is empty
+///
+/// # Safety
+/// `book` came from `orp_book_new`; `out` is null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_best_ask(book: *const OrpBook, out: *mut OrpLevel) -> bool {
+    book.as_ref()
+        .is_some_and(|book| level(book.book.bbo().best_ask, out))
+}
+
+/// Write the book's metrics to `out`
+///
+/// # Safety
+/// `book` came from `orp_book_new`; `out` is null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn orp_book_metrics(book: *const OrpBook, out: *mut OrpMetrics) -> OrpStatus {
+    let (Some(book), false) = (book.as_ref(), out.is_null()) else {
+        return fail(OrpStatus::InvalidArgument, "Null book or output");
+    };
+    let metrics = book.book.metrics();
+    let optional = |value: Option<Decimal>| value.map_or(f64::NAN, float);
+    *out = OrpMetrics {
+        last_update_id: book.book.bbo().last_update_id,
+        mid_price: optional(metrics.mid_price),
+        microprice: optional(metrics.microprice),
+        spread_bps: optional(metrics.spread_bps),
+        imbalance: optional(metrics.imbalance),
+        weighted_imbalance: optional(metrics.weighted_imbalance),
+        bid_depth: optional(metrics.bid_depth),
+        ask_depth: optional(metrics.ask_depth),
+        bid_levels: metrics.bid_levels,
+        ask_levels: metrics.ask_levels,
+    };
+    OrpStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_from_json_through_the_c_abi() {
+        let c = |s: &str| CString::new(s).unwrap();
+        unsafe {
+            let book = orp_book_new(c("BTCUSDT").as_ptr(), 100);
+            let snapshot =
+                c(r#"{"lastUpdateId":100,"bids":[["100.0","1.0"]],"asks":[["101.0","2.0"]]}"#);
+            assert_eq!(
+                orp_book_apply_snapshot(book, snapshot.as_ptr()),
+                OrpStatus::Ok
+            );
+
+            let update = c(
+                r#"{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":101,"u":102,"b":[["100.5","3.0"]],"a":[]}"#,
+            );
+            assert_eq!(orp_book_apply_update(book, update.as_ptr()), OrpStatus::Ok);
+            assert_eq!(
+                orp_book_apply_update(book, update.as_ptr()),
+                OrpStatus::Ignored
+            );
+
+            let mut bid = OrpLevel::default();
+            assert!(orp_book_best_bid(book, &mut bid));
+            assert_eq!((bid.price, bid.quantity), (100.5, 3.0));
+            let mut metrics = OrpMetrics::default();
+            assert_eq!(orp_book_metrics(book, &mut metrics), OrpStatus::Ok);
+            assert_eq!(metrics.last_update_id, 102);
+            assert_eq!(metrics.mid_price, 100.75);
+            assert_eq!(metrics.bid_levels, 2);
+
+            let gap = c(r#"{"e":"depthUpdate","E":2,"s":"BTCUSDT","U":110,"u":111,"b":[],"a":[]}"#);
+            assert_eq!(
+                orp_book_apply_update(book, gap.as_ptr()),
+                OrpStatus::SequenceGap
+            );
+            assert!(!orp_last_error().is_null());
+            assert_eq!(
+                orp_book_apply_update(book, update.as_ptr()),
+                OrpStatus::Ignored
+            );
+            orp_book_free(book);
+        }
+    }
+
+    #[test]
+    fn test_quantities_overflowing_decimal_sums_dont_panic() {
+        let c = |s: &str| CString::new(s).unwrap();
+        let max = Decimal::MAX.to_string();
+        unsafe {
+            let book = orp_book_new(c("BTCUSDT").as_ptr(), 100);
+            let snapshot = c(&format!(
+                r#"{{"lastUpdateId":100,"bids":[["100.0","{max}"],["99.0","{max}"]],"asks":[["101.0","{max}"]]}}"#
+            ));
+            assert_eq!(
+                orp_book_apply_snapshot(book, snapshot.as_ptr()),
+                OrpStatus::Ok
+            );
+            let update = c(&format!(
+                r#"{{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":101,"u":101,"b":[["98.0","{max}"]],"a":[["102.0","{max}"]]}}"#
+            ));
+            assert_eq!(orp_book_apply_update(book, update.as_ptr()), OrpStatus::Ok);
+
+            let mut metrics = OrpMetrics::default();
+            assert_eq!(orp_book_metrics(book, &mut metrics), OrpStatus::Ok);
+            assert_eq!(metrics.bid_levels, 3);
+            assert_eq!(metrics.bid_depth, float(Decimal::MAX));
+            assert!(metrics.microprice.is_nan());
+            orp_book_free(book);
+        }
+    }
+}
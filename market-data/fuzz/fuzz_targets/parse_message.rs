@@ -0,0 +1,15 @@
+//! Stream messages as the WebSocket client hands them to the parser
+//!
+//! Binary frames are decoded lossily, so any bytes can reach the parser.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orp_flow_market_data::parser::{self, ParsedMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let raw = String::from_utf8_lossy(data);
+    let _ = parser::parse_event(&raw);
+    let _ = ParsedMessage::parse(&raw);
+    let _ = ParsedMessage::parse_control(&raw);
+});
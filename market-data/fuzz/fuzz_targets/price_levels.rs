@@ -0,0 +1,19 @@
+//! Price levels, as the sides of a REST snapshot and of a depth update
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orp_flow_market_data::parser::{self, OrderBookSnapshot};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(levels) = std::str::from_utf8(data) else {
+        return;
+    };
+    let snapshot = format!(r#"{{"lastUpdateId":1,"bids":{},"asks":[]}}"#, levels);
+    let _ = serde_json::from_str::<OrderBookSnapshot>(&snapshot);
+    let update = format!(
+        r#"{{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":1,"u":1,"b":[],"a":{}}}"#,
+        levels
+    );
+    let _ = parser::parse_event(&update);
+});
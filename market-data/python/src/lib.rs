@@ -0,0 +1,340 @@
+//! Python bindings for the market data subscriber
+//!
+//! Research consumers are mostly Python, so the `market_data_py` extension
+//! module exposes the Rust [`subscriber`](orp_flow_market_data::subscriber)
+//! instead of every notebook reimplementing the IPC framing:
+//!
+//! ```python
+//! from market_data_py import Subscriber
+//!
+//! for message in Subscriber("/tmp/orp-flow.sock", wire_format="msgpack"):
+//!     if message.kind == "book":
+//!         print(message.book.symbol, message.book.metrics.mid_price)
+//!     elif message.kind == "gap":
+//!         print("missed", message.expected, "to", message.received)
+//! ```
+//!
+//! Book states arrive as `OrderBook` objects with their `BookMetrics`;
+//! prices, quantities and metrics are converted to floats. Every other
+//! payload is handed over as a dict in its JSON shape (`Message.data`).
+//! Waiting for a message releases the GIL and stays interruptible.
+//!
+//! Built with maturin (`maturin develop` in this directory).
+
+use futures_util::StreamExt;
+use orp_flow_market_data::config::WireFormat;
+use orp_flow_market_data::orderbook::{Level, OrderBookMetrics, OrderBookState};
+use orp_flow_market_data::subscriber::{self, Payload, PublishedMessage};
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+/// How often a blocked `recv` checks for Python signals (Ctrl-C)
+const SIGNAL_CHECK: Duration = Duration::from_millis(100);
+
+fn float(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+fn levels(levels: &[Level]) -> Vec<(f64, f64)> {
+    levels
+        .iter()
+        .map(|level| (float(level.price), float(level.quantity)))
+        .collect()
+}
+
+/// Metrics of a book state
+#[pyclass(module = "market_data_py", frozen, get_all)]
+#[derive(Debug, Clone)]
+struct BookMetrics {
+    mid_price: Option<f64>,
+    microprice: Option<f64>,
+    spread_bps: Option<f64>,
+    realized_vol: Option<f64>,
+    imbalance: Option<f64>,
+    weighted_imbalance: Option<f64>,
+    bid_depth: Option<f64>,
+    ask_depth: Option<f64>,
+    bid_levels: usize,
+    ask_levels: usize,
+    bid_slope: Option<f64>,
+    ask_slope: Option<f64>,
+    /// `(quantity, buy_bps, sell_bps)` per reference size
+    price_impact: Vec<(f64, Option<f64>, Option<f64>)>,
+    /// `(bps, bid_depth, ask_depth)` per distance from mid
+    depth_bands: Vec<(f64, f64, f64)>,
+}
+
+impl From<&OrderBookMetrics> for BookMetrics {
+    fn from(metrics: &OrderBookMetrics) -> Self {
+        Self {
+            mid_price: metrics.mid_price.map(float),
+            microprice: metrics.microprice.map(float),
+            spread_bps: metrics.spread_bps.map(float),
+            realized_vol: metrics.realized_vol.map(float),
+            imbalance: metrics.imbalance.map(float),
+            weighted_imbalance: metrics.weighted_imbalance.map(float),
+            bid_depth: metrics.bid_depth.map(float),
+            ask_depth: metrics.ask_depth.map(float),
+            bid_levels: metrics.bid_levels,
+            ask_levels: metrics.ask_levels,
+            bid_slope: metrics.bid_slope.map(float),
+            ask_slope: metrics.ask_slope.map(float),
+            price_impact: metrics
+                .price_impact
+                .iter()
+                .map(|impact| {
+                    (
+                        float(impact.quantity),
+                        impact.buy_bps.map(float),
+                        impact.sell_bps.map(float),
+                    )
+                })
+                .collect(),
+            depth_bands: metrics
+                .depth_bands
+                .iter()
+                .map(|band| {
+                    (
+                        float(band.bps),
+                        float(band.bid_depth),
+                        float(band.ask_depth),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl BookMetrics {
+    fn __repr__(&self) -> String {
+        format!(
+            "BookMetrics(mid_price={:?}, spread_bps={:?}, imbalance={:?})",
+            self.mid_price, self.spread_bps, self.imbalance
+        )
+    }
+}
+
+/// Published state of one symbol's book
+#[pyclass(module = "market_data_py", frozen, get_all)]
+#[derive(Debug, Clone)]
+struct OrderBook {
+    symbol: String,
+    /// Milliseconds since the epoch
+    timestamp: u64,
+    last_update_id: u64,
+    /// `(price, quantity)` from the best bid down
+    bids: Vec<(f64, f64)>,
+    /// `(price, quantity)` from the best ask up
+    asks: Vec<(f64, f64)>,
+    metrics: BookMetrics,
+}
+
+impl From<&OrderBookState> for OrderBook {
+    fn from(state: &OrderBookState) -> Self {
+        Self {
+            symbol: state.symbol.clone(),
+            timestamp: state.timestamp,
+            last_update_id: state.last_update_id,
+            bids: levels(&state.bids),
+            asks: levels(&state.asks),
+            metrics: BookMetrics::from(&state.metrics),
+        }
+    }
+}
+
+#[pymethods]
+impl OrderBook {
+    fn __repr__(&self) -> String {
+        format!(
+            "OrderBook(symbol={:?}, last_update_id={}, bids={}, asks={})",
+            self.symbol,
+            self.last_update_id,
+            self.bids.len(),
+            self.asks.len()
+        )
+    }
+}
+
+/// Something that arrived from the publisher
+///
+/// `kind` is `connected`, `disconnected`, `gap`, or the payload kind of a
+/// frame: `book`, `delta`, `bootstrap`, `volume_profile`, `anomaly`, `bbo`,
+/// `control`, `order`, `account`, `status`, `heartbeat`, or `encoded` for
+/// book states in the Protobuf and FlatBuffers formats
+#[pyclass(module = "market_data_py", frozen, get_all)]
+struct Message {
+    kind: &'static str,
+    /// Envelope fields, `None` outside frames
+    seq: Option<u64>,
+    sent_at_us: Option<u64>,
+    symbol: Option<String>,
+    /// The book state of a `book` frame
+    book: Option<Py<OrderBook>>,
+    /// The payload of other frames, in its JSON shape
+    data: Option<PyObject>,
+    /// The still-encoded payload of an `encoded` frame
+    raw: Option<Py<PyBytes>>,
+    /// First missed sequence number of a `gap`
+    expected: Option<u64>,
+    /// Sequence number of the frame after a `gap`
+    received: Option<u64>,
+}
+
+#[pymethods]
+impl Message {
+    fn __repr__(&self) -> String {
+        match (self.seq, &self.symbol) {
+            (Some(seq), Some(symbol)) => {
+                format!(
+                    "Message(kind={:?}, seq={}, symbol={:?})",
+                    self.kind, seq, symbol
+                )
+            }
+            _ => format!("Message(kind={:?})", self.kind),
+        }
+    }
+}
+
+impl Message {
+    fn empty(kind: &'static str) -> Self {
+        Self {
+            kind,
+            seq: None,
+            sent_at_us: None,
+            symbol: None,
+            book: None,
+            data: None,
+            raw: None,
+            expected: None,
+            received: None,
+        }
+    }
+
+    fn from_published(py: Python<'_>, message: PublishedMessage) -> PyResult<Self> {
+        let (envelope, payload) = match message {
+            PublishedMessage::Connected => return Ok(Self::empty("connected")),
+            PublishedMessage::Disconnected => return Ok(Self::empty("disconnected")),
+            PublishedMessage::Gap { expected, received } => {
+                return Ok(Self {
+                    expected: Some(expected),
+                    received: Some(received),
+                    ..Self::empty("gap")
+                })
+            }
+            PublishedMessage::Frame { envelope, payload } => (envelope, payload),
+        };
+
+        let mut message = Self {
+            seq: Some(envelope.seq),
+            sent_at_us: Some(envelope.sent_at_us),
+            symbol: Some(envelope.symbol),
+            ..Self::empty("book")
+        };
+        let (kind, json) = match payload {
+            Payload::Book(state) => {
+                message.book = Some(Py::new(py, OrderBook::from(state.as_ref()))?);
+                return Ok(message);
+            }
+            Payload::Encoded(raw) => {
+                message.kind = "encoded";
+                message.raw = Some(PyBytes::new(py, &raw).unbind());
+                return Ok(message);
+            }
+            Payload::Delta(delta) => ("delta", serde_json::to_string(&delta)),
+            Payload::Bootstrap(frame) => ("bootstrap", serde_json::to_string(&frame)),
+            Payload::VolumeProfile(profile) => ("volume_profile", serde_json::to_string(&profile)),
+            Payload::Anomaly(anomaly) => ("anomaly", serde_json::to_string(&anomaly)),
+            Payload::Bbo(bbo) => ("bbo", serde_json::to_string(&bbo)),
+            Payload::Control(control) => ("control", serde_json::to_string(&control)),
+            Payload::Order(order) => ("order", serde_json::to_string(&order)),
+            Payload::Account(account) => ("account", serde_json::to_string(&account)),
+            Payload::Status(status) => ("status", serde_json::to_string(&status)),
+            Payload::Heartbeat(heartbeat) => ("heartbeat", serde_json::to_string(&heartbeat)),
+        };
+        let json = json.map_err(|e| PyValueError::new_err(e.to_string()))?;
+        message.kind = kind;
+        message.data = Some(py.import("json")?.call_method1("loads", (json,))?.unbind());
+        Ok(message)
+    }
+}
+
+/// Listens on the IPC socket the publisher connects to
+///
+/// Iterating blocks until the next message; `recv` takes a timeout.
+#[pyclass(module = "market_data_py")]
+struct Subscriber {
+    runtime: tokio::runtime::Runtime,
+    inner: subscriber::Subscriber,
+}
+
+#[pymethods]
+impl Subscriber {
+    /// Listen on `path` (the publisher's `IPC_SOCKET_PATH`), decoding
+    /// payloads as `wire_format` (its `WIRE_FORMAT`)
+    #[new]
+    #[pyo3(signature = (path, wire_format = "msgpack"))]
+    fn new(path: &str, wire_format: &str) -> PyResult<Self> {
+        let wire_format: WireFormat = wire_format.parse().map_err(PyValueError::new_err)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let inner = {
+            let _guard = runtime.enter();
+            subscriber::Subscriber::bind(path, wire_format)
+                .map_err(|e| PyOSError::new_err(e.to_string()))?
+        };
+        Ok(Self { runtime, inner })
+    }
+
+    /// The next message, or `None` if none arrives within `timeout`
+    /// seconds; waits indefinitely without one
+    #[pyo3(signature = (timeout = None))]
+    fn recv(&mut self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Option<Message>> {
+        let deadline =
+            timeout.map(|secs| std::time::Instant::now() + Duration::from_secs_f64(secs));
+        loop {
+            let wait = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(std::time::Instant::now());
+                    if left.is_zero() {
+                        return Ok(None);
+                    }
+                    left.min(SIGNAL_CHECK)
+                }
+                None => SIGNAL_CHECK,
+            };
+            let Self { runtime, inner } = self;
+            let next = py.allow_threads(|| {
+                runtime.block_on(async { tokio::time::timeout(wait, inner.next()).await })
+            });
+            match next {
+                Ok(Some(message)) => return Message::from_published(py, message).map(Some),
+                Ok(None) => return Err(PyOSError::new_err("Subscriber stopped")),
+                Err(_) => py.check_signals()?,
+            }
+        }
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Message>> {
+        self.recv(py, None)
+    }
+}
+
+#[pymodule]
+fn market_data_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Subscriber>()?;
+    m.add_class::<Message>()?;
+    m.add_class::<OrderBook>()?;
+    m.add_class::<BookMetrics>()?;
+    Ok(())
+}
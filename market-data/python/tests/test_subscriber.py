@@ -0,0 +1,63 @@
+"""Tests for the market_data_py subscriber bindings."""
+
+import json
+import socket
+import struct
+
+from market_data_py import Subscriber
+
+BOOK = 1
+STATUS = 10
+
+
+def frame(seq: int, message_type: int, symbol: str, payload: dict) -> bytes:
+    """Encode a JSON payload in the publisher's uncompressed envelope."""
+    body = json.dumps(payload).encode()
+    symbol_bytes = symbol.encode()
+    header = struct.pack(">QQBBB", seq, 0, message_type, 0, len(symbol_bytes))
+    data = header + symbol_bytes + body
+    return struct.pack(">I", len(data)) + data
+
+
+def test_book_frames_and_gaps(tmp_path):
+    path = str(tmp_path / "ipc.sock")
+    subscriber = Subscriber(path, wire_format="json")
+
+    book = {
+        "symbol": "BTCUSDT",
+        "timestamp": 1_700_000_000_000,
+        "last_update_id": 100,
+        "bids": [{"price": "100.5", "quantity": "2"}],
+        "asks": [{"price": "101", "quantity": "1"}],
+        "metrics": {
+            "mid_price": "100.75",
+            "bid_depth": "2",
+            "ask_depth": "1",
+            "bid_levels": 1,
+            "ask_levels": 1,
+        },
+    }
+    status = {"Resynced": {"symbol": "BTCUSDT", "last_update_id": 100, "timestamp": 0}}
+    with socket.socket(socket.AF_UNIX, socket.SOCK_STREAM) as publisher:
+        publisher.connect(path)
+        publisher.sendall(frame(1, BOOK, "BTCUSDT", book))
+        publisher.sendall(frame(3, STATUS, "BTCUSDT", status))
+
+        assert subscriber.recv(timeout=5).kind == "connected"
+
+        message = subscriber.recv(timeout=5)
+        assert message.kind == "book"
+        assert message.seq == 1
+        assert message.book.bids == [(100.5, 2.0)]
+        assert message.book.metrics.mid_price == 100.75
+        assert message.book.metrics.spread_bps is None
+
+        gap = subscriber.recv(timeout=5)
+        assert (gap.kind, gap.expected, gap.received) == ("gap", 2, 3)
+
+        message = subscriber.recv(timeout=5)
+        assert message.kind == "status"
+        assert message.data["Resynced"]["last_update_id"] == 100
+
+    assert subscriber.recv(timeout=5).kind == "disconnected"
+    assert subscriber.recv(timeout=0.1) is None
@@ -0,0 +1,226 @@
+//! Day-anchored trade analytics
+//!
+//! Accumulates per-symbol statistics over the current UTC day from the
+//! trade stream: VWAP accumulators, open/high/low/last, base and quote
+//! volume, and cumulative volume delta (taker buy minus taker sell volume).
+//! Statistics reset at the first trade of a new UTC day, so they cover the
+//! day since 00:00 UTC rather than a rolling 24 hours. Trades are counted
+//! once by trade ID, so a replay after a restart adds nothing.
+//!
+//! Downstream risk checks depend on these numbers, so the state can be
+//! persisted to a JSON file periodically and restored on startup; a restart
+//! then resumes the day's accumulators instead of zeroing them.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::Side;
+
+const MS_PER_DAY: u64 = 86_400_000;
+
+/// Statistics for one symbol since 00:00 UTC of one day
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UtcDayStats {
+    /// Days since the Unix epoch (UTC)
+    pub day: u64,
+    pub open: Option<Decimal>,
+    pub high: Option<Decimal>,
+    pub low: Option<Decimal>,
+    pub last: Option<Decimal>,
+    /// Base volume
+    pub volume: Decimal,
+    /// Quote volume (sum of price × quantity), the VWAP numerator
+    pub quote_volume: Decimal,
+    /// Volume of trades where the buyer was the taker
+    pub taker_buy_volume: Decimal,
+    pub trades: u64,
+    /// Time of the last trade included (milliseconds)
+    pub last_trade_time: u64,
+    /// ID of the last trade included; trades up to it are already counted
+    #[serde(default)]
+    pub last_trade_id: u64,
+    /// Times trades went uncounted because analytics were shed under
+    /// load; the totals are short of the day's activity when nonzero
+    #[serde(default)]
+    pub gaps: u32,
+}
+
+impl UtcDayStats {
+    fn new(day: u64) -> Self {
+        Self {
+            day,
+            ..Default::default()
+        }
+    }
+
+    /// Volume-weighted average price
+    pub fn vwap(&self) -> Option<Decimal> {
+        (!self.volume.is_zero()).then(|| self.quote_volume / self.volume)
+    }
+
+    /// Cumulative volume delta: taker buy volume minus taker sell volume
+    pub fn cvd(&self) -> Decimal {
+        self.taker_buy_volume - (self.volume - self.taker_buy_volume)
+    }
+
+    fn apply(&mut self, trade: &Trade) {
+        let price = trade.price;
+        self.open.get_or_insert(price);
+        self.high = Some(self.high.map_or(price, |high| high.max(price)));
+        self.low = Some(self.low.map_or(price, |low| low.min(price)));
+        self.last = Some(price);
+        self.volume += trade.quantity;
+        self.quote_volume += price * trade.quantity;
+        // Bid-side takers are buyers lifting the ask
+        if trade.taker_side == Side::Bid {
+            self.taker_buy_volume += trade.quantity;
+        }
+        self.trades += 1;
+        self.last_trade_time = trade.trade_time;
+        self.last_trade_id = trade.trade_id;
+    }
+}
+
+/// Day-anchored statistics for all symbols
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TradeAnalytics {
+    symbols: BTreeMap<String, UtcDayStats>,
+    /// Analytics sheds seen
+    #[serde(skip)]
+    sheds_seen: u64,
+}
+
+impl TradeAnalytics {
+    /// Fold a trade into its symbol's statistics
+    pub fn on_trade(&mut self, trade: &Trade) {
+        let day = trade.trade_time / MS_PER_DAY;
+        let stats = self
+            .symbols
+            .entry(trade.symbol.clone())
+            .or_insert_with(|| UtcDayStats::new(day));
+
+        if day > stats.day {
+            *stats = UtcDayStats::new(day);
+        } else if day < stats.day || (stats.trades > 0 && trade.trade_id <= stats.last_trade_id) {
+            // Already counted before a restart, or from a previous day
+            return;
+        }
+        stats.apply(trade);
+    }
+
+    /// Catch up with `sheds`, the times analytics have been shed so far;
+    /// if it grew, trades before `now_ms` went uncounted and every
+    /// symbol's statistics for the day of `now_ms` count a gap
+    pub fn note_sheds(&mut self, sheds: u64, now_ms: u64) {
+        if sheds == self.sheds_seen {
+            return;
+        }
+        self.sheds_seen = sheds;
+        let day = now_ms / MS_PER_DAY;
+        for stats in self.symbols.values_mut() {
+            if day > stats.day {
+                *stats = UtcDayStats::new(day);
+            }
+            stats.gaps += 1;
+        }
+    }
+
+    /// Statistics for a symbol
+    pub fn get(&self, symbol: &str) -> Option<&UtcDayStats> {
+        self.symbols.get(symbol)
+    }
+
+    /// Statistics for all symbols
+    pub fn all(&self) -> &BTreeMap<String, UtcDayStats> {
+        &self.symbols
+    }
+
+    /// Write the state to `path`, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec(self).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize analytics: {}", e))
+        })?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read state saved by `save`; a missing file yields empty state
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use rust_decimal_macros::dec;
+
+    fn trade(trade_time: u64, price: Decimal, quantity: Decimal, taker_side: Side) -> Trade {
+        Trade {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            event_time: trade_time,
+            trade_id: trade_time,
+            price,
+            quantity,
+            taker_side,
+            trade_time,
+        }
+    }
+
+    #[test]
+    fn test_day_anchored_stats_survive_restart() {
+        let mut analytics = TradeAnalytics::default();
+        analytics.on_trade(&trade(1_000, dec!(100), dec!(1), Side::Bid));
+        analytics.on_trade(&trade(2_000, dec!(110), dec!(3), Side::Ask));
+
+        let stats = analytics.get("BTCUSDT").unwrap();
+        assert_eq!(stats.vwap(), Some(dec!(107.5)));
+        assert_eq!(stats.cvd(), dec!(-2));
+        assert_eq!(stats.high, Some(dec!(110)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("analytics.json");
+        analytics.save(&path).unwrap();
+        let mut restored = TradeAnalytics::load(&path).unwrap();
+        assert_eq!(restored.get("BTCUSDT"), analytics.get("BTCUSDT"));
+
+        // A replayed trade is not counted twice, while another trade in
+        // the same millisecond as the last one is
+        restored.on_trade(&trade(2_000, dec!(110), dec!(3), Side::Ask));
+        restored.on_trade(&trade(1_500, dec!(90), dec!(1), Side::Bid));
+        assert_eq!(restored.get("BTCUSDT").unwrap().trades, 2);
+        let mut same_ms = trade(2_000, dec!(110), dec!(1), Side::Bid);
+        same_ms.trade_id = 2_001;
+        restored.on_trade(&same_ms);
+        assert_eq!(restored.get("BTCUSDT").unwrap().trades, 3);
+
+        // First trade of the next day resets
+        restored.on_trade(&trade(MS_PER_DAY + 1, dec!(120), dec!(2), Side::Bid));
+        let stats = restored.get("BTCUSDT").unwrap();
+        assert_eq!(stats.day, 1);
+        assert_eq!(stats.open, Some(dec!(120)));
+        assert_eq!(stats.cvd(), dec!(2));
+
+        // Trades missed while analytics were shed are flagged, and a day
+        // that started during the gap is flagged from its start
+        restored.note_sheds(1, MS_PER_DAY + 10);
+        restored.note_sheds(1, MS_PER_DAY + 20);
+        assert_eq!(restored.get("BTCUSDT").unwrap().gaps, 1);
+        restored.note_sheds(2, 2 * MS_PER_DAY + 10);
+        let stats = restored.get("BTCUSDT").unwrap();
+        assert_eq!((stats.day, stats.gaps, stats.trades), (2, 1, 0));
+    }
+}
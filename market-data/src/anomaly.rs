@@ -0,0 +1,380 @@
+//! Book anomaly detection
+//!
+//! Flags book behaviour that usually means manipulation or bad data:
+//!
+//! - flash liquidity: a level far larger than usual that is pulled within
+//!   `flash_window` of appearing (typical of spoofing);
+//! - update bursts: a second with far more depth updates than the symbol's
+//!   recent baseline (quote stuffing);
+//! - crossed books: best bid at or above best ask in a published state,
+//!   flagged once per crossing rather than on every state until it clears.
+//!
+//! Books already enforce `CROSSED_BOOK_POLICY` on each update, and every
+//! policy (including the default `trim`) leaves the published state
+//! uncrossed, so those crossings are counted in
+//! `orderbook_crossed_updates_total` and never reach this detector. The
+//! crossed book check is a backstop for states that get past the policy.
+//!
+//! Detections are returned as `MarketAnomaly` events for the publisher,
+//! logged, and counted in `market_anomalies_total` by symbol and kind.
+//! Exchange event time is used throughout so replays behave like live data.
+
+use prometheus::{IntCounterVec, Opts};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::event::DepthDelta;
+use crate::orderbook::{OrderBookState, Side};
+
+static ANOMALIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "market_anomalies_total",
+            "Suspicious book behaviour detected",
+        ),
+        &["symbol", "kind"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Length of an update rate bucket
+const RATE_BUCKET_MS: u64 = 1000;
+
+/// Rate buckets observed before bursts are flagged
+const RATE_WARMUP_BUCKETS: u32 = 10;
+
+/// Level quantities observed before large levels are tracked
+const SIZE_WARMUP_LEVELS: u32 = 100;
+
+/// Smoothing of the rate and level size baselines
+const BASELINE_ALPHA: f64 = 0.05;
+
+/// Detection thresholds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyPolicy {
+    /// Large levels pulled within this long of appearing are flagged
+    pub flash_window: Duration,
+    /// A level is large at this multiple of the average level quantity
+    pub large_level_multiple: f64,
+    /// A second is a burst at this multiple of the baseline update rate
+    pub update_rate_multiple: f64,
+}
+
+impl Default for AnomalyPolicy {
+    fn default() -> Self {
+        Self {
+            flash_window: Duration::from_millis(500),
+            large_level_multiple: 10.0,
+            update_rate_multiple: 5.0,
+        }
+    }
+}
+
+/// What was detected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// A large level appeared and was pulled within the flash window
+    FlashLiquidity {
+        side: Side,
+        price: Decimal,
+        quantity: Decimal,
+        lifetime_ms: u64,
+    },
+    /// Updates in one second far above the baseline rate
+    UpdateBurst { updates: u64, baseline: f64 },
+    /// Best bid at or above best ask
+    CrossedBook {
+        best_bid: Decimal,
+        best_ask: Decimal,
+    },
+}
+
+impl AnomalyKind {
+    /// Metric label
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnomalyKind::FlashLiquidity { .. } => "flash_liquidity",
+            AnomalyKind::UpdateBurst { .. } => "update_burst",
+            AnomalyKind::CrossedBook { .. } => "crossed_book",
+        }
+    }
+}
+
+/// Suspicious book behaviour on one symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketAnomaly {
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub time: u64,
+    pub kind: AnomalyKind,
+}
+
+#[derive(Debug, Default)]
+struct SymbolActivity {
+    /// Running average of non-zero level quantities in updates
+    avg_level: f64,
+    levels_seen: u32,
+    /// Large levels by side and price: (appeared at, quantity)
+    large: HashMap<(Side, Decimal), (u64, Decimal)>,
+    bucket_start: u64,
+    bucket_updates: u64,
+    /// Running average of updates per bucket
+    baseline_rate: f64,
+    buckets_seen: u32,
+    /// Last state checked was crossed, so the crossing was already flagged
+    crossed: bool,
+}
+
+/// Per-symbol anomaly detection
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    policy: AnomalyPolicy,
+    symbols: HashMap<String, SymbolActivity>,
+}
+
+impl AnomalyDetector {
+    pub fn new(policy: AnomalyPolicy) -> Self {
+        Self {
+            policy,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Check a depth update for flash liquidity and update bursts
+    pub fn on_delta(&mut self, delta: &DepthDelta) -> Vec<MarketAnomaly> {
+        let policy = self.policy;
+        let flash_window_ms = policy.flash_window.as_millis() as u64;
+        let time = delta.event_time;
+        let activity = self.symbols.entry(delta.symbol.clone()).or_default();
+        let mut kinds = Vec::new();
+
+        // Update rate, judged per completed bucket
+        let bucket_start = time - time % RATE_BUCKET_MS;
+        if bucket_start > activity.bucket_start && activity.bucket_updates > 0 {
+            let updates = activity.bucket_updates;
+            if activity.buckets_seen >= RATE_WARMUP_BUCKETS
+                && updates as f64 > policy.update_rate_multiple * activity.baseline_rate.max(1.0)
+            {
+                kinds.push(AnomalyKind::UpdateBurst {
+                    updates,
+                    baseline: activity.baseline_rate,
+                });
+            }
+            if activity.buckets_seen > 0 {
+                activity.baseline_rate +=
+                    BASELINE_ALPHA * (updates as f64 - activity.baseline_rate);
+            } else {
+                activity.baseline_rate = updates as f64;
+            }
+            activity.buckets_seen = activity.buckets_seen.saturating_add(1);
+            activity.bucket_updates = 0;
+            // Forget large levels that outlived the window
+            activity
+                .large
+                .retain(|_, (appeared, _)| time <= *appeared + flash_window_ms);
+        }
+        activity.bucket_start = activity.bucket_start.max(bucket_start);
+        activity.bucket_updates += 1;
+
+        let levels = delta
+            .bids
+            .iter()
+            .map(|l| (Side::Bid, l))
+            .chain(delta.asks.iter().map(|l| (Side::Ask, l)));
+        for (side, level) in levels {
+            let key = (side, level.price);
+            if level.quantity.is_zero() {
+                if let Some((appeared, quantity)) = activity.large.remove(&key) {
+                    let lifetime_ms = time.saturating_sub(appeared);
+                    if lifetime_ms <= flash_window_ms {
+                        kinds.push(AnomalyKind::FlashLiquidity {
+                            side,
+                            price: level.price,
+                            quantity,
+                            lifetime_ms,
+                        });
+                    }
+                }
+                continue;
+            }
+
+            let quantity = level.quantity.to_f64().unwrap_or_default();
+            if activity.levels_seen >= SIZE_WARMUP_LEVELS
+                && quantity >= policy.large_level_multiple * activity.avg_level
+            {
+                activity.large.entry(key).or_insert((time, level.quantity));
+            }
+            if activity.levels_seen > 0 {
+                activity.avg_level += BASELINE_ALPHA * (quantity - activity.avg_level);
+            } else {
+                activity.avg_level = quantity;
+            }
+            activity.levels_seen = activity.levels_seen.saturating_add(1);
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| record(&delta.symbol, time, kind))
+            .collect()
+    }
+
+    /// Check a state about to be published for a crossed book
+    ///
+    /// A crossing is flagged when it appears; later states stay quiet until
+    /// the book uncrosses.
+    pub fn on_state(&mut self, state: &OrderBookState) -> Option<MarketAnomaly> {
+        let activity = self.symbols.entry(state.symbol.clone()).or_default();
+        let crossing = match (state.bids.first(), state.asks.first()) {
+            (Some(bid), Some(ask)) if bid.price >= ask.price => Some((bid.price, ask.price)),
+            _ => None,
+        };
+        let was_crossed = std::mem::replace(&mut activity.crossed, crossing.is_some());
+        let (best_bid, best_ask) = crossing.filter(|_| !was_crossed)?;
+        Some(record(
+            &state.symbol,
+            state.timestamp,
+            AnomalyKind::CrossedBook { best_bid, best_ask },
+        ))
+    }
+}
+
+/// Log and count a detection
+fn record(symbol: &str, time: u64, kind: AnomalyKind) -> MarketAnomaly {
+    warn!(symbol, anomaly = ?kind, "Market anomaly detected");
+    ANOMALIES.with_label_values(&[symbol, kind.label()]).inc();
+    MarketAnomaly {
+        symbol: symbol.to_string(),
+        time,
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{BookSnapshot, PriceLevel, Venue};
+    use crate::orderbook::{CrossedBookPolicy, Level, OrderBook};
+    use rust_decimal_macros::dec;
+
+    fn delta(event_time: u64, bids: Vec<(Decimal, Decimal)>) -> DepthDelta {
+        DepthDelta {
+            venue: Venue::Binance,
+            event_time,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: event_time,
+            final_update_id: event_time,
+            bids: bids
+                .into_iter()
+                .map(|(price, quantity)| PriceLevel { price, quantity })
+                .collect(),
+            asks: vec![],
+        }
+    }
+
+    #[test]
+    fn test_flash_liquidity_and_bursts() {
+        let mut detector = AnomalyDetector::new(AnomalyPolicy::default());
+
+        // Baseline: one update of a 1.0 level every 100ms for 20s
+        for t in (0..200).map(|i| i * 100) {
+            assert!(detector
+                .on_delta(&delta(t, vec![(dec!(100), dec!(1))]))
+                .is_empty());
+        }
+
+        // A 50x level pulled after 200ms
+        detector.on_delta(&delta(20_000, vec![(dec!(99), dec!(50))]));
+        let found = detector.on_delta(&delta(20_200, vec![(dec!(99), dec!(0))]));
+        assert!(matches!(
+            found[..],
+            [MarketAnomaly {
+                kind: AnomalyKind::FlashLiquidity {
+                    lifetime_ms: 200,
+                    ..
+                },
+                ..
+            }]
+        ));
+
+        // 100 updates in one second against a baseline of about 10
+        for t in 21_000..21_100 {
+            detector.on_delta(&delta(t, vec![]));
+        }
+        let found = detector.on_delta(&delta(22_000, vec![]));
+        assert!(matches!(
+            found[..],
+            [MarketAnomaly {
+                kind: AnomalyKind::UpdateBurst { updates: 100, .. },
+                ..
+            }]
+        ));
+    }
+
+    fn state(bid: Decimal, ask: Decimal) -> OrderBookState {
+        let level = |price| Level {
+            price,
+            quantity: dec!(1),
+        };
+        OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            last_update_id: 1,
+            bids: vec![level(bid)],
+            asks: vec![level(ask)],
+            metrics: Default::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_crossed_book_flagged_once_per_crossing() {
+        let mut detector = AnomalyDetector::new(AnomalyPolicy::default());
+
+        assert!(detector.on_state(&state(dec!(100), dec!(101))).is_none());
+        assert!(matches!(
+            detector.on_state(&state(dec!(101), dec!(100))),
+            Some(MarketAnomaly {
+                kind: AnomalyKind::CrossedBook { .. },
+                ..
+            })
+        ));
+        // Still crossed: already flagged
+        assert!(detector.on_state(&state(dec!(102), dec!(100))).is_none());
+        // Uncrossed, then crossed again
+        assert!(detector.on_state(&state(dec!(99), dec!(100))).is_none());
+        assert!(detector.on_state(&state(dec!(100), dec!(100))).is_some());
+
+        // Under the default trim policy a crossing update never reaches
+        // the detector: the book drops the stale levels before publishing
+        assert_eq!(CrossedBookPolicy::default(), CrossedBookPolicy::Trim);
+        let level = |price| PriceLevel {
+            price,
+            quantity: dec!(1),
+        };
+        let mut book = OrderBook::new("BTCUSDT", 10);
+        book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 1,
+            bids: vec![level(dec!(99))],
+            asks: vec![level(dec!(100)), level(dec!(101))],
+        });
+        let mut crossing = delta(2_000, vec![(dec!(100.5), dec!(1))]);
+        crossing.first_update_id = 2;
+        crossing.final_update_id = 2;
+        assert!(book.apply_update(&crossing).unwrap());
+        let mut detector = AnomalyDetector::new(AnomalyPolicy::default());
+        assert!(detector.on_state(&book.state()).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! Parquet archive
+//!
+//! Persists trades and periodic book snapshots from the live feed to
+//! Parquet files partitioned by symbol and UTC date, laid out Hive style
+//! so dataset readers (pyarrow, polars, DuckDB) discover the partitions:
+//!
+//! ```text
+//! <dir>/trades/symbol=BTCUSDT/date=2024-05-01/part-<flush ms>.parquet
+//! <dir>/books/symbol=BTCUSDT/date=2024-05-01/part-<flush ms>.parquet
+//! ```
+//!
+//! Every flush writes one new file per partition that received rows.
+//! Book snapshots use the Arrow export's columns (top of book + metrics)
+//! and are sampled: the latest state of each symbol that changed since the
+//! previous sample.
+
+mod parquet;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+use crate::publisher::arrow::{Batch, Row};
+use crate::publisher::LiveFeed;
+
+/// Written to the `created_by` field of every file
+const CREATED_BY: &str = concat!("orp-flow-market-data ", env!("CARGO_PKG_VERSION"));
+
+/// Buffered rows per `(symbol, date)` partition of one kind
+struct Partitions<R> {
+    kind: &'static str,
+    batches: BTreeMap<(String, String), Batch<R>>,
+}
+
+impl<R: Row> Partitions<R> {
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            batches: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, symbol: &str, time_ms: u64, row: &R) {
+        let date = chrono::DateTime::from_timestamp_millis(time_ms as i64)
+            .unwrap_or_default()
+            .format("%Y-%m-%d")
+            .to_string();
+        self.batches
+            .entry((symbol.to_string(), date))
+            .or_default()
+            .push(row);
+    }
+
+    /// Write a file per buffered partition
+    async fn flush(&mut self, dir: &Path) {
+        let stamp = chrono::Utc::now().timestamp_millis();
+        for ((symbol, date), mut batch) in std::mem::take(&mut self.batches) {
+            let partition = dir
+                .join(self.kind)
+                .join(format!("symbol={}", symbol))
+                .join(format!("date={}", date));
+            let path = partition.join(format!("part-{}.parquet", stamp));
+            let rows = batch.len();
+            let result = async {
+                let file = parquet::encode(&batch.take()?, CREATED_BY)?;
+                tokio::fs::create_dir_all(&partition).await?;
+                tokio::fs::write(&path, file).await?;
+                Ok::<_, MarketDataError>(())
+            }
+            .await;
+            match result {
+                Ok(()) => debug!(path = %path.display(), rows, "Archived partition"),
+                Err(e) => {
+                    warn!(error = %e, path = %path.display(), rows, "Failed to write archive file")
+                }
+            }
+        }
+    }
+}
+
+/// Archive the live feed under `dir`, sampling books every
+/// `snapshot_interval` and writing files every `flush_interval`
+pub async fn run(
+    dir: PathBuf,
+    flush_interval: Duration,
+    snapshot_interval: Duration,
+    feed: LiveFeed,
+) -> Result<()> {
+    tokio::fs::create_dir_all(&dir).await?;
+    info!(dir = %dir.display(), "Parquet archive enabled");
+
+    let mut trades = Partitions::<Trade>::new("trades");
+    let mut books = Partitions::<OrderBookState>::new("books");
+    let mut latest: HashMap<String, Arc<OrderBookState>> = HashMap::new();
+
+    let mut book_rx = feed.subscribe_books();
+    let mut trade_rx = feed.subscribe_trades();
+    let mut snapshot_ticker = tokio::time::interval(snapshot_interval);
+    let mut flush_ticker = tokio::time::interval(flush_interval);
+    flush_ticker.tick().await;
+    loop {
+        tokio::select! {
+            state = book_rx.recv() => match state {
+                Ok(state) => {
+                    latest.insert(state.symbol.clone(), state);
+                }
+                // Only the latest state per symbol is kept anyway
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            trade = trade_rx.recv() => match trade {
+                Ok(trade) => trades.push(&trade.symbol, trade.trade_time, &trade),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Archive lagging, skipped trades");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = snapshot_ticker.tick() => {
+                for (symbol, state) in latest.drain() {
+                    books.push(&symbol, state.timestamp, &state);
+                }
+            }
+            _ = flush_ticker.tick() => {
+                trades.flush(&dir).await;
+                books.flush(&dir).await;
+            }
+        }
+    }
+
+    trades.flush(&dir).await;
+    books.flush(&dir).await;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! Parquet file encoding
+//!
+//! Writes a batch as a single-row-group Parquet file with the `parquet`
+//! crate's Arrow writer. Strings, unsigned integers and timestamps carry
+//! their logical types, and the Arrow schema is embedded in the footer so
+//! Arrow readers get the exported column types back.
+
+use arrow_array::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+
+use crate::error::Result;
+
+/// Encode a record batch as a complete Parquet file
+pub fn encode(batch: &RecordBatch, created_by: &str) -> Result<Vec<u8>> {
+    let properties = WriterProperties::builder()
+        .set_created_by(created_by.to_string())
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(properties))?;
+    writer.write(batch)?;
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{Level, OrderBookMetrics, OrderBookState};
+    use crate::publisher::arrow::Batch;
+    use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_file_reads_back_with_parquet_reader() {
+        let mut batch = Batch::<OrderBookState>::new();
+        let mut state = OrderBookState {
+            symbol: "ETHUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            last_update_id: 1,
+            bids: vec![Level {
+                price: dec!(2000),
+                quantity: dec!(1),
+            }],
+            asks: vec![Level {
+                price: dec!(2000.5),
+                quantity: dec!(3),
+            }],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        };
+        batch.push(&state);
+        state.last_update_id = 2;
+        state.asks.clear();
+        batch.push(&state);
+
+        let file = encode(&batch.take().unwrap(), "test").unwrap();
+        let reader = SerializedFileReader::new(bytes::Bytes::from(file)).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+        assert_eq!(metadata.created_by(), Some("test"));
+
+        let schema = metadata.schema_descr();
+        assert_eq!(schema.num_columns(), 13);
+        let column = |i: usize| schema.column(i);
+        assert_eq!(column(0).name(), "symbol");
+        assert_eq!(column(0).physical_type(), PhysicalType::BYTE_ARRAY);
+        assert_eq!(column(0).logical_type(), Some(LogicalType::String));
+        assert_eq!(
+            column(0).self_type().get_basic_info().repetition(),
+            Repetition::REQUIRED
+        );
+        assert!(matches!(
+            column(1).logical_type(),
+            Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            column(2).logical_type(),
+            Some(LogicalType::Integer {
+                bit_width: 64,
+                is_signed: false
+            })
+        ));
+        assert_eq!(column(5).name(), "ask_price");
+        assert_eq!(column(5).physical_type(), PhysicalType::DOUBLE);
+        assert_eq!(
+            column(5).self_type().get_basic_info().repetition(),
+            Repetition::OPTIONAL
+        );
+
+        let rows: Vec<Vec<Field>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(_, field)| field.clone())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows[0][0], Field::Str("ETHUSDT".to_string()));
+        assert_eq!(rows[0][1], Field::TimestampMillis(1_700_000_000_000));
+        assert_eq!(rows[1][2], Field::ULong(2));
+        assert_eq!(rows[0][3], Field::Double(2000.0));
+        assert_eq!(rows[0][5], Field::Double(2000.5));
+        // The second book has no asks, and no book has depth metrics
+        assert_eq!(rows[1][5], Field::Null);
+        assert_eq!(rows[1][6], Field::Null);
+        assert_eq!(rows[0][11], Field::Null);
+    }
+}
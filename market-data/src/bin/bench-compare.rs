@@ -0,0 +1,389 @@
+//! Benchmark regression gate
+//!
+//! Runs the criterion suites, compares mean latencies against a baseline
+//! file and exits non-zero with a JSON report when any benchmark regressed
+//! beyond its threshold.
+//!
+//! Usage:
+//!
+//! ```text
+//! bench-compare [--baseline PATH] [--criterion-dir DIR] [--threshold PCT]
+//!               [--skip-run] [--save]
+//! ```
+//!
+//! `--save` records the current results as the new baseline instead of
+//! comparing.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+const DEFAULT_BASELINE: &str = "benches/baseline.json";
+const DEFAULT_CRITERION_DIR: &str = "target/criterion";
+const DEFAULT_THRESHOLD_PCT: f64 = 10.0;
+
+/// Baseline file contents
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Baseline {
+    /// Allowed slowdown in percent for benchmarks without their own threshold
+    default_threshold_pct: f64,
+    benchmarks: BTreeMap<String, BaselineEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineEntry {
+    mean_ns: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Improved,
+    Regressed,
+    /// In the baseline but not produced by this run
+    Missing,
+    /// Produced by this run but not in the baseline
+    New,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchResult {
+    name: String,
+    status: Status,
+    baseline_ns: Option<f64>,
+    current_ns: Option<f64>,
+    change_pct: Option<f64>,
+    threshold_pct: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    passed: bool,
+    regressions: usize,
+    results: Vec<BenchResult>,
+}
+
+/// Criterion `estimates.json` subset
+#[derive(Debug, Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Debug, Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+struct Args {
+    baseline: PathBuf,
+    criterion_dir: PathBuf,
+    threshold_pct: Option<f64>,
+    skip_run: bool,
+    save: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        baseline: PathBuf::from(DEFAULT_BASELINE),
+        criterion_dir: PathBuf::from(DEFAULT_CRITERION_DIR),
+        threshold_pct: None,
+        skip_run: false,
+        save: false,
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--baseline" => args.baseline = value()?.into(),
+            "--criterion-dir" => args.criterion_dir = value()?.into(),
+            "--threshold" => {
+                let raw = value()?;
+                args.threshold_pct = Some(
+                    raw.parse()
+                        .map_err(|_| format!("Invalid threshold: {}", raw))?,
+                );
+            }
+            "--skip-run" => args.skip_run = true,
+            "--save" => args.save = true,
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    Ok(args)
+}
+
+/// Run `cargo bench` so the criterion directory holds fresh estimates
+fn run_benches() -> Result<(), String> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args(["bench", "--benches", "--", "--noplot"])
+        .status()
+        .map_err(|e| format!("Failed to run cargo bench: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("cargo bench failed: {}", status))
+    }
+}
+
+/// Collect mean estimates (ns) for every benchmark under `dir`
+///
+/// Criterion stores `<id>/new/estimates.json`, where `<id>` may contain
+/// slashes for grouped benchmarks.
+fn collect_estimates(dir: &Path) -> Result<BTreeMap<String, f64>, String> {
+    let mut estimates = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .map_err(|e| format!("Failed to read {}: {}", current.display(), e))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+
+            let file = path.join("new").join("estimates.json");
+            if file.is_file() {
+                let raw = fs::read_to_string(&file)
+                    .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+                let parsed: Estimates = serde_json::from_str(&raw)
+                    .map_err(|e| format!("Invalid {}: {}", file.display(), e))?;
+                let name = path
+                    .strip_prefix(dir)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                estimates.insert(name, parsed.mean.point_estimate);
+            } else if path.file_name().is_some_and(|n| n != "report") {
+                pending.push(path);
+            }
+        }
+    }
+
+    Ok(estimates)
+}
+
+/// Compare current estimates against the baseline
+fn compare(
+    baseline: &Baseline,
+    current: &BTreeMap<String, f64>,
+    threshold_override: Option<f64>,
+) -> Report {
+    let mut results = Vec::new();
+
+    for (name, entry) in &baseline.benchmarks {
+        let threshold_pct = threshold_override
+            .or(entry.threshold_pct)
+            .unwrap_or(baseline.default_threshold_pct);
+
+        let result = match current.get(name) {
+            Some(&current_ns) => {
+                let change_pct = (current_ns - entry.mean_ns) / entry.mean_ns * 100.0;
+                let status = if change_pct > threshold_pct {
+                    Status::Regressed
+                } else if change_pct < -threshold_pct {
+                    Status::Improved
+                } else {
+                    Status::Ok
+                };
+                BenchResult {
+                    name: name.clone(),
+                    status,
+                    baseline_ns: Some(entry.mean_ns),
+                    current_ns: Some(current_ns),
+                    change_pct: Some(change_pct),
+                    threshold_pct,
+                }
+            }
+            None => BenchResult {
+                name: name.clone(),
+                status: Status::Missing,
+                baseline_ns: Some(entry.mean_ns),
+                current_ns: None,
+                change_pct: None,
+                threshold_pct,
+            },
+        };
+        results.push(result);
+    }
+
+    for (name, &current_ns) in current {
+        if !baseline.benchmarks.contains_key(name) {
+            results.push(BenchResult {
+                name: name.clone(),
+                status: Status::New,
+                baseline_ns: None,
+                current_ns: Some(current_ns),
+                change_pct: None,
+                threshold_pct: threshold_override.unwrap_or(baseline.default_threshold_pct),
+            });
+        }
+    }
+
+    // A benchmark silently disappearing must not pass the gate
+    let regressions = results
+        .iter()
+        .filter(|r| matches!(r.status, Status::Regressed | Status::Missing))
+        .count();
+
+    Report {
+        passed: regressions == 0,
+        regressions,
+        results,
+    }
+}
+
+fn run() -> Result<bool, String> {
+    let args = parse_args()?;
+
+    if !args.skip_run {
+        run_benches()?;
+    }
+
+    let current = collect_estimates(&args.criterion_dir)?;
+    if current.is_empty() {
+        return Err(format!(
+            "No criterion results found in {}",
+            args.criterion_dir.display()
+        ));
+    }
+
+    if args.save {
+        let baseline = Baseline {
+            default_threshold_pct: args.threshold_pct.unwrap_or(DEFAULT_THRESHOLD_PCT),
+            benchmarks: current
+                .into_iter()
+                .map(|(name, mean_ns)| {
+                    (
+                        name,
+                        BaselineEntry {
+                            mean_ns,
+                            threshold_pct: None,
+                        },
+                    )
+                })
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&baseline).map_err(|e| e.to_string())?;
+        fs::write(&args.baseline, json + "\n")
+            .map_err(|e| format!("Failed to write {}: {}", args.baseline.display(), e))?;
+        eprintln!("Saved baseline to {}", args.baseline.display());
+        return Ok(true);
+    }
+
+    let raw = fs::read_to_string(&args.baseline).map_err(|e| {
+        format!(
+            "Failed to read baseline {} (create one with --save): {}",
+            args.baseline.display(),
+            e
+        )
+    })?;
+    let baseline: Baseline = serde_json::from_str(&raw)
+        .map_err(|e| format!("Invalid baseline {}: {}", args.baseline.display(), e))?;
+
+    let report = compare(&baseline, &current, args.threshold_pct);
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    println!("{}", json);
+
+    Ok(report.passed)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("Benchmark regression detected");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("bench-compare: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline() -> Baseline {
+        let mut benchmarks = BTreeMap::new();
+        benchmarks.insert(
+            "apply_update".to_string(),
+            BaselineEntry {
+                mean_ns: 100.0,
+                threshold_pct: None,
+            },
+        );
+        benchmarks.insert(
+            "get_state".to_string(),
+            BaselineEntry {
+                mean_ns: 200.0,
+                threshold_pct: Some(50.0),
+            },
+        );
+        Baseline {
+            default_threshold_pct: 10.0,
+            benchmarks,
+        }
+    }
+
+    #[test]
+    fn test_compare_flags_regressions_per_threshold() {
+        let current = BTreeMap::from([
+            ("apply_update".to_string(), 115.0),
+            ("get_state".to_string(), 260.0),
+            ("calculate_imbalance".to_string(), 5.0),
+        ]);
+
+        let report = compare(&baseline(), &current, None);
+        assert!(!report.passed);
+        assert_eq!(report.regressions, 1);
+
+        let status = |name: &str| {
+            report
+                .results
+                .iter()
+                .find(|r| r.name == name)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status("apply_update"), Status::Regressed);
+        assert_eq!(status("get_state"), Status::Ok);
+        assert_eq!(status("calculate_imbalance"), Status::New);
+    }
+
+    #[test]
+    fn test_missing_benchmark_fails_gate() {
+        let current = BTreeMap::from([("apply_update".to_string(), 80.0)]);
+        let report = compare(&baseline(), &current, None);
+        assert!(!report.passed);
+        assert_eq!(report.results[0].status, Status::Improved);
+        assert_eq!(report.results[1].status, Status::Missing);
+    }
+
+    #[test]
+    fn test_collect_estimates_walks_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let estimate = r#"{"mean":{"point_estimate":42.5}}"#;
+        for id in ["apply_update", "group/case"] {
+            let path = dir.path().join(id).join("new");
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("estimates.json"), estimate).unwrap();
+        }
+        fs::create_dir_all(dir.path().join("report")).unwrap();
+
+        let estimates = collect_estimates(dir.path()).unwrap();
+        assert_eq!(estimates.len(), 2);
+        assert_eq!(estimates["group/case"], 42.5);
+    }
+}
@@ -0,0 +1,211 @@
+//! Offset of the exchange's clock from ours
+//!
+//! Exchange latency is measured from the exchange's event time to our
+//! receive time, so it reads the network delay plus however far the two
+//! clocks disagree. Polling `GET /time` every `CLOCK_SYNC_INTERVAL_SECS`
+//! estimates that offset: each poll gives the server time read at some
+//! point of the request's round trip, the sample with the shortest round
+//! trip of the last few is the most accurate, and the spread of the rest
+//! around it is the jitter. The estimate corrects event times before stage
+//! latencies are recorded and timestamps signed REST requests.
+
+use prometheus::IntGauge;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+/// Polls the estimate is taken over
+const MAX_SAMPLES: usize = 8;
+
+/// Offset change worth logging (microseconds)
+const LOG_THRESHOLD_US: i64 = 5_000;
+
+static OFFSET: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "exchange_clock_offset_microseconds",
+        "Estimated exchange clock minus local clock, subtracted from event times before measuring latency",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static JITTER: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "exchange_clock_jitter_microseconds",
+        "Standard deviation of the recent clock offset samples",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static ROUND_TRIP: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "exchange_clock_round_trip_microseconds",
+        "Round trip of the server time request the offset estimate comes from",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+/// One reading of the exchange's clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Exchange clock minus ours (microseconds)
+    pub offset_us: i64,
+    /// Round trip of the request (microseconds); the offset is off by at
+    /// most half of it
+    pub round_trip_us: u64,
+}
+
+impl ClockSample {
+    /// Sample of a server time read between `sent_at_us` and
+    /// `received_at_us` on our clock
+    pub fn new(server_time_us: u64, sent_at_us: u64, received_at_us: u64) -> Self {
+        // The exchange read its clock about halfway through the round trip
+        let midpoint_us = sent_at_us + received_at_us.saturating_sub(sent_at_us) / 2;
+        Self {
+            offset_us: server_time_us as i64 - midpoint_us as i64,
+            round_trip_us: received_at_us.saturating_sub(sent_at_us),
+        }
+    }
+}
+
+/// Current estimate of the exchange clock offset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockEstimate {
+    pub offset_us: i64,
+    pub jitter_us: u64,
+    pub round_trip_us: u64,
+}
+
+/// Rolling estimate of the exchange clock offset from recent samples
+#[derive(Debug, Default)]
+pub struct ClockSkew {
+    samples: Mutex<VecDeque<ClockSample>>,
+    /// Estimated offset, read on the hot path
+    offset_us: AtomicI64,
+}
+
+impl ClockSkew {
+    /// Add a sample and return the new estimate
+    pub fn observe(&self, sample: ClockSample) -> ClockEstimate {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+
+        let best = samples
+            .iter()
+            .min_by_key(|s| s.round_trip_us)
+            .copied()
+            .unwrap_or(sample);
+        let variance = samples
+            .iter()
+            .map(|s| ((s.offset_us - best.offset_us) as f64).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64;
+        self.offset_us.store(best.offset_us, Ordering::Relaxed);
+        ClockEstimate {
+            offset_us: best.offset_us,
+            jitter_us: variance.sqrt() as u64,
+            round_trip_us: best.round_trip_us,
+        }
+    }
+
+    /// Estimated exchange clock minus ours (microseconds); zero before the
+    /// first sample
+    pub fn offset_us(&self) -> i64 {
+        self.offset_us.load(Ordering::Relaxed)
+    }
+
+    /// Exchange timestamp `exchange_us` on our clock
+    pub fn to_local_us(&self, exchange_us: u64) -> u64 {
+        exchange_us.saturating_add_signed(-self.offset_us())
+    }
+}
+
+/// Poll the exchange's clock every `interval`, update the estimate and
+/// align signed request timestamps with it
+pub async fn run(state: Arc<AppState>, interval: Duration) {
+    LazyLock::force(&OFFSET);
+    LazyLock::force(&JITTER);
+    LazyLock::force(&ROUND_TRIP);
+    let mut ticker = tokio::time::interval(interval);
+    let mut logged_offset_us = None;
+    loop {
+        ticker.tick().await;
+        let sample = match state.rest.measure_clock().await {
+            Ok(sample) => sample,
+            Err(e) => {
+                warn!(error = %e, "Failed to read the exchange clock");
+                continue;
+            }
+        };
+        let estimate = state.clock.observe(sample);
+        state.rest.set_clock_offset(estimate.offset_us / 1000);
+        OFFSET.set(estimate.offset_us);
+        JITTER.set(estimate.jitter_us as i64);
+        ROUND_TRIP.set(estimate.round_trip_us as i64);
+
+        if logged_offset_us
+            .is_none_or(|logged: i64| (estimate.offset_us - logged).abs() >= LOG_THRESHOLD_US)
+        {
+            info!(
+                offset_us = estimate.offset_us,
+                jitter_us = estimate.jitter_us,
+                round_trip_us = estimate.round_trip_us,
+                "Exchange clock offset estimated"
+            );
+            logged_offset_us = Some(estimate.offset_us);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_follows_shortest_round_trip() {
+        let sample = ClockSample::new(1_000_500, 999_000, 1_000_000);
+        assert_eq!(sample.offset_us, 1_000);
+        assert_eq!(sample.round_trip_us, 1_000);
+
+        let clock = ClockSkew::default();
+        assert_eq!(clock.to_local_us(5_000), 5_000);
+
+        clock.observe(ClockSample {
+            offset_us: 3_000,
+            round_trip_us: 40_000,
+        });
+        let estimate = clock.observe(ClockSample {
+            offset_us: 1_000,
+            round_trip_us: 2_000,
+        });
+        assert_eq!(estimate.offset_us, 1_000);
+        assert_eq!(estimate.round_trip_us, 2_000);
+        // sqrt((2000² + 0²) / 2)
+        assert_eq!(estimate.jitter_us, 1_414);
+
+        // Event times are moved onto our clock
+        assert_eq!(clock.to_local_us(5_000), 4_000);
+        assert_eq!(clock.to_local_us(500), 0);
+
+        // Once the precise sample ages out, the next best takes over
+        for _ in 0..MAX_SAMPLES {
+            clock.observe(ClockSample {
+                offset_us: -2_000,
+                round_trip_us: 10_000,
+            });
+        }
+        assert_eq!(clock.offset_us(), -2_000);
+    }
+}
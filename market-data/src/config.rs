@@ -1,7 +1,336 @@
 //! Configuration module for the market data handler
+//!
+//! Settings come from environment variables, optionally layered over a
+//! config file (`CONFIG_PATH` or `--config`). File keys are the
+//! environment variable names in lower case, and `[symbol.<SYMBOL>]`
+//! sections override depth, update speed, throttling and metrics settings
+//! per symbol:
+//!
+//! ```toml
+//! symbols = ["BTCUSDT", "ETHUSDT"]
+//! depth_levels = 20
+//! publish_mode = "delta"
+//!
+//! [symbol.ETHUSDT]
+//! depth_levels = 50
+//! depth_update_speed = "1000ms"
+//! metrics_disabled = ["slope"]
+//! ```
+//!
+//! An environment variable always wins over the file. Values that don't
+//! parse, unknown keys and settings for symbols that aren't subscribed are
+//! reported as errors instead of falling back to defaults.
 
+use anyhow::{anyhow, bail, Context};
+use rust_decimal::Decimal;
 use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::anomaly::AnomalyPolicy;
+use crate::dead_man::DeadManPolicy;
+use crate::degradation::DegradationPolicy;
+use crate::exchange_info::InstrumentInfo;
+pub use crate::orderbook::CrossedBookPolicy;
+use crate::orderbook::{Metric, MetricsConfig, TickScale, WarmupPolicy};
+use crate::websocket::subscription::MAX_STREAMS_PER_CONNECTION;
+
+/// Depth stream update speed offered by Binance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum DepthUpdateSpeed {
+    /// `@depth@100ms` - lowest latency, highest bandwidth
+    #[default]
+    #[serde(rename = "100ms")]
+    Ms100,
+    /// Plain `@depth` - one diff per second
+    #[serde(rename = "1000ms")]
+    Ms1000,
+}
+
+impl DepthUpdateSpeed {
+    /// Stream name suffix for this speed (appended to `<symbol>`)
+    pub fn stream_suffix(&self) -> &'static str {
+        match self {
+            DepthUpdateSpeed::Ms100 => "@depth@100ms",
+            DepthUpdateSpeed::Ms1000 => "@depth",
+        }
+    }
+}
+
+impl FromStr for DepthUpdateSpeed {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "100ms" | "100" => Ok(DepthUpdateSpeed::Ms100),
+            "1000ms" | "1000" | "1s" => Ok(DepthUpdateSpeed::Ms1000),
+            other => Err(format!("Invalid depth update speed: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for DepthUpdateSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DepthUpdateSpeed::Ms100 => write!(f, "100ms"),
+            DepthUpdateSpeed::Ms1000 => write!(f, "1000ms"),
+        }
+    }
+}
+
+/// How order book states are published over IPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishMode {
+    /// Every message carries the full published book
+    #[default]
+    Full,
+    /// A full snapshot first, then only changed levels
+    Delta,
+}
+
+impl FromStr for PublishMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(PublishMode::Full),
+            "delta" => Ok(PublishMode::Delta),
+            other => Err(format!("Invalid publish mode: {}", other)),
+        }
+    }
+}
+
+/// Payload encoding of IPC socket frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    MessagePack,
+    Json,
+    /// `proto/market_data.proto` messages; full states only, requires the
+    /// `protobuf` feature
+    Protobuf,
+    /// `schema/order_book.fbs` tables for zero-copy reads; full states only
+    FlatBuffers,
+}
+
+impl FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "msgpack" | "messagepack" => Ok(WireFormat::MessagePack),
+            "json" => Ok(WireFormat::Json),
+            "protobuf" | "proto" => Ok(WireFormat::Protobuf),
+            "flatbuffers" | "flatbuf" => Ok(WireFormat::FlatBuffers),
+            other => Err(format!("Invalid wire format: {}", other)),
+        }
+    }
+}
+
+/// Codec applied to IPC frame payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Compression {
+    /// Payloads sent as encoded
+    #[default]
+    None = 0,
+    /// Snappy raw format
+    Snappy = 1,
+    /// LZ4 block format, prefixed with the uncompressed size (u32 LE)
+    Lz4 = 2,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "none" | "" => Ok(Compression::None),
+            "snappy" | "snap" => Ok(Compression::Snappy),
+            "lz4" => Ok(Compression::Lz4),
+            other => Err(format!("Invalid compression: {}", other)),
+        }
+    }
+}
+
+/// What the IPC send queue does with a state when it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Discard the oldest queued state
+    #[default]
+    DropOldest,
+    /// Discard the new state
+    DropNewest,
+    /// Wait for the writer to make room
+    Block,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop_newest" => Ok(OverflowPolicy::DropNewest),
+            "block" => Ok(OverflowPolicy::Block),
+            other => Err(format!("Invalid overflow policy: {}", other)),
+        }
+    }
+}
+
+/// What the WebSocket intake queue does with a frame when it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntakePolicy {
+    /// Wait for processing to make room
+    #[default]
+    Block,
+    /// Replace a queued depth update of the same stream with the new one
+    KeepLatest,
+}
+
+impl FromStr for IntakePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "block" => Ok(IntakePolicy::Block),
+            "keep_latest" => Ok(IntakePolicy::KeepLatest),
+            other => Err(format!("Invalid intake policy: {}", other)),
+        }
+    }
+}
+
+/// WebSocket endpoint the market streams are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEndpoint {
+    /// `/stream`: payloads wrapped as `{"stream": ..., "data": ...}`, with
+    /// streams named in the URL or subscribed after connecting
+    #[default]
+    Combined,
+    /// `/ws`: bare payloads, every stream subscribed after connecting
+    Raw,
+}
+
+impl FromStr for StreamEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "combined" => Ok(StreamEndpoint::Combined),
+            "raw" => Ok(StreamEndpoint::Raw),
+            other => Err(format!("Invalid stream endpoint: {}", other)),
+        }
+    }
+}
+
+/// Where the raw message tap writes
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TapSink {
+    /// Append to a file (`file:<path>`)
+    File(PathBuf),
+    /// Stream to a TCP listener (`tcp:<host>:<port>`)
+    Tcp(String),
+    /// Stream to a Unix socket listener (`unix:<path>`)
+    Unix(PathBuf),
+}
+
+impl FromStr for TapSink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(TapSink::File(path.into())),
+            Some(("tcp", addr)) if addr.contains(':') => Ok(TapSink::Tcp(addr.to_string())),
+            Some(("unix", path)) if !path.is_empty() => Ok(TapSink::Unix(path.into())),
+            _ => Err(format!(
+                "Invalid tap sink: {} (expected file:<path>, tcp:<host>:<port> or unix:<path>)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for TapSink {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// How books store price levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookRepresentation {
+    /// `Decimal` prices and quantities
+    #[default]
+    Decimal,
+    /// `i64` multiples of each symbol's tick and step size
+    FixedPoint,
+}
+
+impl FromStr for BookRepresentation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "decimal" => Ok(BookRepresentation::Decimal),
+            "fixed_point" | "fixed" => Ok(BookRepresentation::FixedPoint),
+            other => Err(format!("Invalid book representation: {}", other)),
+        }
+    }
+}
+
+/// Which Redis commands the Redis backend issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisMode {
+    /// PUBLISH for live fan-out only
+    #[default]
+    Publish,
+    /// XADD to capped streams only
+    Stream,
+    /// Both PUBLISH and XADD
+    Both,
+}
+
+impl RedisMode {
+    /// Whether messages are sent with PUBLISH
+    pub fn publishes(&self) -> bool {
+        matches!(self, RedisMode::Publish | RedisMode::Both)
+    }
+
+    /// Whether messages are appended with XADD
+    pub fn streams(&self) -> bool {
+        matches!(self, RedisMode::Stream | RedisMode::Both)
+    }
+}
+
+impl FromStr for RedisMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "publish" | "pubsub" => Ok(RedisMode::Publish),
+            "stream" | "streams" => Ok(RedisMode::Stream),
+            "both" => Ok(RedisMode::Both),
+            other => Err(format!("Invalid Redis mode: {}", other)),
+        }
+    }
+}
 
 /// Application configuration
 #[derive(Debug, Clone, Deserialize)]
@@ -15,12 +344,473 @@ pub struct Config {
     /// REST API endpoint for snapshots
     pub rest_endpoint: String,
 
+    /// WebSocket endpoints to fail over to, in order
+    pub ws_fallback_endpoints: Vec<String>,
+
+    /// REST API endpoints to fail over to, in order
+    pub rest_fallback_endpoints: Vec<String>,
+
+    /// Consecutive failures of an endpoint that move to the next one
+    pub endpoint_failover_after: u32,
+
+    /// Probe endpoint latencies this often to prefer the fastest (0 = off)
+    pub endpoint_probe_interval_secs: u64,
+
+    /// PEM bundle of the only root certificates WebSocket connections trust
+    pub tls_ca_file: Option<String>,
+
+    /// Longest a WebSocket connect (TCP, TLS handshake and upgrade) may take
+    pub tls_handshake_timeout_ms: u64,
+
+    /// Disable Nagle's algorithm on the WebSocket TCP connections
+    pub tcp_nodelay: bool,
+
+    /// Idle seconds before TCP keepalive probes on the WebSocket
+    /// connections (0 = keepalive off)
+    pub tcp_keepalive_secs: u64,
+
+    /// SO_SNDBUF of the WebSocket TCP connections (kernel default when unset)
+    pub tcp_send_buffer_bytes: Option<usize>,
+
+    /// SO_RCVBUF of the WebSocket TCP connections (kernel default when unset)
+    pub tcp_recv_buffer_bytes: Option<usize>,
+
+    /// Ping the server this often regardless of traffic (unset = the
+    /// venue's default, 0 = never)
+    pub ws_ping_interval_secs: Option<u64>,
+
+    /// Send an unsolicited pong this often (unset = the venue's default,
+    /// 0 = never)
+    pub ws_pong_interval_secs: Option<u64>,
+
+    /// Ping to probe the connection after this many seconds without a frame
+    /// (unset = the venue's default, 0 = never)
+    pub ws_idle_ping_secs: Option<u64>,
+
+    /// Frames queued per connection between the socket and processing
+    pub ws_intake_capacity: usize,
+
+    /// What happens to a frame when the intake queue is full
+    pub ws_intake_policy: IntakePolicy,
+
+    /// Fold depth diffs of a symbol that are queued together into one
+    /// book update and publish
+    pub coalesce_depth_updates: bool,
+
+    /// Forward every raw WebSocket text frame, with its receive time, to
+    /// this sink
+    pub raw_tap: Option<TapSink>,
+
+    /// Frames buffered for the raw tap before new ones are dropped
+    pub raw_tap_capacity: usize,
+
+    /// Replace the WebSocket connection with a warmed-up new one after this
+    /// many seconds, ahead of Binance's 24-hour limit (0 = off)
+    pub ws_rotate_after_secs: u64,
+
+    /// Second WebSocket endpoint streaming the same symbols, arbitrated by
+    /// update ID against the primary connection
+    pub redundant_ws_endpoint: Option<String>,
+
     /// IPC socket path for publishing data
     pub ipc_socket_path: String,
 
-    /// Order book depth levels to maintain
+    /// Answer consumer bootstrap requests with gap fills or snapshots
+    pub ipc_bootstrap: bool,
+
+    /// States kept per symbol for bootstrap gap fills
+    pub ipc_replay_depth: usize,
+
+    /// How long to wait for a consumer's bootstrap request after connecting
+    pub ipc_bootstrap_timeout_ms: u64,
+
+    /// States queued per symbol for the IPC socket writers
+    pub ipc_queue_capacity: usize,
+
+    /// What happens to a state when the IPC send queue is full
+    pub ipc_queue_policy: OverflowPolicy,
+
+    /// SO_SNDBUF of the IPC socket (kernel default when unset)
+    pub ipc_send_buffer_bytes: Option<usize>,
+
+    /// Send every symbol's heartbeat on the IPC socket this often (0 = off)
+    pub ipc_heartbeat_interval_ms: u64,
+
+    /// First delay between IPC reconnect attempts, doubling per failure
+    pub ipc_reconnect_delay_ms: u64,
+
+    /// Longest delay between IPC reconnect attempts
+    pub ipc_reconnect_max_delay_ms: u64,
+
+    /// Book states held while the IPC consumer is away and written on
+    /// reconnect (0 = drop them; bootstrap gap-fills instead when enabled)
+    pub ipc_backlog_capacity: usize,
+
+    /// Directory of the write-ahead journal of IPC frames (disabled when
+    /// unset)
+    pub journal_dir: Option<String>,
+
+    /// Bytes after which a journal segment is closed
+    pub journal_segment_bytes: u64,
+
+    /// Journal segments kept; the oldest are deleted beyond this
+    pub journal_max_segments: usize,
+
+    /// Shared-memory ring file for co-located readers (disabled when unset)
+    pub shm_path: Option<String>,
+
+    /// Bytes per shm ring slot (max encoded state size plus 16)
+    pub shm_slot_size: usize,
+
+    /// Number of slots in the shm ring
+    pub shm_slot_count: usize,
+
+    /// UDP multicast group as "addr:port" (disabled when unset)
+    pub multicast_group: Option<String>,
+
+    /// Path MTU used to size multicast fragments
+    pub multicast_mtu: usize,
+
+    /// Multicast TTL (1 keeps traffic on the local subnet)
+    pub multicast_ttl: u32,
+
+    /// Local interface address to send multicast from
+    pub multicast_interface: Option<Ipv4Addr>,
+
+    /// SO_SNDBUF of the multicast socket (kernel default when unset)
+    pub multicast_send_buffer_bytes: Option<usize>,
+
+    /// NATS server URL (disabled when unset; requires the `nats` feature)
+    pub nats_url: Option<String>,
+
+    /// Subject prefix for NATS messages (`<prefix>.book.<SYMBOL>`)
+    pub nats_subject_prefix: String,
+
+    /// JetStream stream to persist NATS messages in (core NATS when unset)
+    pub nats_jetstream_stream: Option<String>,
+
+    /// Kafka bootstrap servers (disabled when unset; requires the `kafka` feature)
+    pub kafka_brokers: Option<String>,
+
+    /// Kafka topic for book states; `{symbol}` is replaced per symbol
+    pub kafka_book_topic: String,
+
+    /// Kafka topic for trades; `{symbol}` is replaced per symbol
+    pub kafka_trade_topic: String,
+
+    /// Explicit partition per symbol (otherwise partitioned by symbol key)
+    pub kafka_symbol_partitions: HashMap<String, i32>,
+
+    /// Kafka compression codec (none, gzip, snappy, lz4, zstd)
+    pub kafka_compression: String,
+
+    /// Producer batching delay
+    pub kafka_linger_ms: u64,
+
+    /// Redis URL (disabled when unset; requires the `redis` feature)
+    pub redis_url: Option<String>,
+
+    /// PUBLISH, XADD or both
+    pub redis_mode: RedisMode,
+
+    /// Channel / stream key prefix (`<prefix>:book:<SYMBOL>`)
+    pub redis_key_prefix: String,
+
+    /// Approximate maximum entries kept per Redis stream
+    pub redis_stream_maxlen: usize,
+
+    /// gRPC server listen address (disabled when unset; requires the `grpc` feature)
+    pub grpc_addr: Option<SocketAddr>,
+
+    /// OTLP/gRPC collector to export spans to (disabled when unset;
+    /// requires the `otel` feature)
+    pub otel_endpoint: Option<String>,
+
+    /// `service.name` of exported spans
+    pub otel_service_name: String,
+
+    /// Messages buffered per live-feed subscriber (`/ws`, gRPC) before it
+    /// starts skipping
+    pub live_feed_buffer: usize,
+
+    /// Directory for Arrow IPC stream files (disabled when unset)
+    pub arrow_export_dir: Option<String>,
+
+    /// How often buffered rows are written as an Arrow record batch
+    pub arrow_batch_interval_ms: u64,
+
+    /// ClickHouse HTTP interface URL, e.g. `http://localhost:8123` (disabled when unset)
+    pub clickhouse_url: Option<String>,
+
+    /// Database holding the `trades` and `book_metrics` tables
+    pub clickhouse_database: String,
+
+    /// ClickHouse user (no authentication when unset)
+    pub clickhouse_user: Option<String>,
+
+    /// ClickHouse password
+    pub clickhouse_password: Option<String>,
+
+    /// Rows per table that trigger an insert before the flush interval
+    pub clickhouse_batch_size: usize,
+
+    /// Maximum delay before buffered rows are inserted
+    pub clickhouse_flush_interval_ms: u64,
+
+    /// Rows queued for the writer before new rows are rejected
+    pub clickhouse_queue_capacity: usize,
+
+    /// Root directory of the Parquet archive (disabled when unset)
+    pub archive_dir: Option<String>,
+
+    /// How often buffered archive rows are written to Parquet files
+    pub archive_flush_interval_secs: u64,
+
+    /// How often the latest book of each symbol is sampled into the archive
+    pub archive_snapshot_interval_secs: u64,
+
+    /// Streams per SUBSCRIBE request (0 puts all streams in the connect URL)
+    pub subscribe_batch_size: usize,
+
+    /// Delay between SUBSCRIBE requests
+    pub subscribe_interval_ms: u64,
+
+    /// Read streams from the combined `/stream` or the bare `/ws` endpoint
+    pub ws_stream_endpoint: StreamEndpoint,
+
+    /// File persisting day-anchored trade analytics across restarts (unset = off)
+    pub analytics_state_path: Option<String>,
+
+    /// Interval between analytics state saves
+    pub analytics_persist_interval_secs: u64,
+
+    /// File persisting book levels and update IDs for warm restarts
+    /// (unset = off)
+    pub book_state_path: Option<String>,
+
+    /// Interval between book state saves
+    pub book_persist_interval_secs: u64,
+
+    /// Oldest saved books resumed on startup instead of fetching snapshots
+    pub book_resume_max_age_secs: u64,
+
+    /// Time allowed for saving state and flushing outputs on shutdown
+    pub shutdown_timeout_secs: u64,
+
+    /// Resubscribe and resync a book that has had no update for this long
+    /// while others keep updating (0 disables the watchdog)
+    pub stale_book_secs: u64,
+
+    /// `/livez` fails once the WebSocket loop has been stuck this long
+    pub liveness_timeout_secs: u64,
+
+    /// `/readyz` fails while a book's last update is older than this
+    /// (0 = no staleness check)
+    pub readiness_max_book_age_secs: u64,
+
+    /// `/readyz` requires an IPC consumer to be connected
+    pub readiness_require_ipc: bool,
+
+    /// Window of the rolling trade metrics attached to published states
+    pub trade_metrics_window_secs: u64,
+
+    /// Per-symbol trade metrics window overrides
+    pub symbol_trade_metrics_windows: HashMap<String, u64>,
+
+    /// Window of the realized volatility estimate (0 = off)
+    pub realized_vol_window_secs: u64,
+
+    /// Per-symbol realized volatility window overrides (0 = off)
+    pub symbol_realized_vol_windows: HashMap<String, u64>,
+
+    /// Mid-price sampling interval of the realized volatility estimate
+    pub realized_vol_sample_ms: u64,
+
+    /// Publish `BboChanged` messages when the best bid or ask moves
+    pub bbo_events_enabled: bool,
+
+    /// Flag flash liquidity, update bursts and crossed books
+    pub anomaly_detection_enabled: bool,
+
+    /// Large levels pulled within this many ms of appearing are flagged
+    pub anomaly_flash_window_ms: u64,
+
+    /// Multiple of the average level quantity that makes a level large
+    pub anomaly_large_level_multiple: f64,
+
+    /// Multiple of the baseline update rate that makes a second a burst
+    pub anomaly_update_rate_multiple: f64,
+
+    /// Track volume profiles (traded volume and resting liquidity by price)
+    pub volume_profile_enabled: bool,
+
+    /// Length of each volume profile window
+    pub volume_profile_window_secs: u64,
+
+    /// Price bucket width, in basis points of price
+    pub volume_profile_bucket_bps: Decimal,
+
+    /// Interval between IPC publications of in-progress profiles
+    pub volume_profile_publish_interval_secs: u64,
+
+    /// Shed auxiliary subsystems automatically under overload
+    pub degradation_enabled: bool,
+
+    /// Release → publish latency budget per depth update
+    pub degradation_latency_budget_us: u64,
+
+    /// Percent of updates over budget that makes a window unhealthy
+    pub degradation_max_over_budget_pct: u64,
+
+    /// Auxiliary sink failures per window that make it unhealthy
+    pub degradation_max_failures: u64,
+
+    /// Degradation evaluation window
+    pub degradation_window_secs: u64,
+
+    /// Healthy windows before a shed tier is restored
+    pub degradation_recover_windows: u32,
+
+    /// Publish `FeedDegraded` and flip `feed_degraded` when the feed can no
+    /// longer vouch for its books
+    pub dead_man_enabled: bool,
+
+    /// Silence of every book that degrades the feed (0 = not checked)
+    pub dead_man_no_update_secs: u64,
+
+    /// Resyncs of one book within the window tolerated (0 = not checked)
+    pub dead_man_max_resyncs: u32,
+
+    /// Window over which resyncs are counted
+    pub dead_man_resync_window_secs: u64,
+
+    /// Time without an IPC consumer that degrades the feed (0 = not checked)
+    pub dead_man_disconnected_secs: u64,
+
+    /// Window over which `/debug/latency` aggregates stage latencies
+    pub latency_window_secs: u64,
+
+    /// Minimum interval between published states per symbol (0 disables conflation)
+    pub publish_throttle_ms: u64,
+
+    /// Per-symbol throttle overrides (`PUBLISH_THROTTLE_MS_SYMBOLS=BTCUSDT=0,ETHUSDT=100`)
+    pub publish_throttle_ms_symbols: HashMap<String, u64>,
+
+    /// Publish immediately when best bid/ask changes, bypassing the throttle
+    pub publish_on_bbo_change: bool,
+
+    /// Full-state or incremental publishing
+    pub publish_mode: PublishMode,
+
+    /// Payload encoding of IPC socket frames
+    pub wire_format: WireFormat,
+
+    /// Codec for IPC socket frame payloads
+    pub ipc_compression: Compression,
+
+    /// Interval between full snapshots in delta mode
+    pub full_refresh_interval_ms: u64,
+
+    /// Order book depth levels to maintain and publish
     pub depth_levels: usize,
 
+    /// Per-symbol depth level overrides
+    pub symbol_depth_levels: HashMap<String, usize>,
+
+    /// Levels retained beyond depth_levels per side to refill a thinning
+    /// book; depth_levels + overflow_levels is the retention depth
+    pub overflow_levels: usize,
+
+    /// Handling of depth updates that cross or lock the book
+    pub crossed_book_policy: CrossedBookPolicy,
+
+    /// How books store price levels
+    pub book_representation: BookRepresentation,
+
+    /// Tick and step size per symbol, for fixed-point books
+    pub tick_sizes: HashMap<String, (Decimal, Decimal)>,
+
+    /// Fetch tick size, lot size and minimum notional from `exchangeInfo`
+    pub exchange_info_enabled: bool,
+
+    /// Refetch instrument metadata this often (0 = only at startup)
+    pub exchange_info_refresh_secs: u64,
+
+    /// Poll the exchange's clock this often to estimate its offset (0 = off)
+    pub clock_sync_interval_secs: u64,
+
+    /// REST request weight per minute to stay under
+    pub rest_weight_limit: u32,
+
+    /// Minimum delay between REST requests
+    pub rest_min_interval_ms: u64,
+
+    /// Retries of a REST request after rate limiting, server errors or
+    /// transport failures
+    pub rest_max_retries: u32,
+
+    /// Delay before the first REST retry, doubled for each further one
+    pub rest_retry_base_ms: u64,
+
+    /// Binance API key; follows the account's user data stream when set
+    pub binance_api_key: Option<String>,
+
+    /// Binance secret key signing `SIGNED` requests (account snapshots)
+    pub binance_api_secret: Option<String>,
+
+    /// Milliseconds after its timestamp a signed request stays valid
+    pub rest_recv_window_ms: u64,
+
+    /// Seconds between listenKey keepalives (the key expires after 60
+    /// minutes without one)
+    pub user_data_keepalive_secs: u64,
+
+    /// Snapshots fetched at once on connect
+    pub snapshot_concurrency: usize,
+
+    /// Default depth stream update speed
+    pub depth_update_speed: DepthUpdateSpeed,
+
+    /// Per-symbol depth update speed overrides
+    pub symbol_update_speeds: HashMap<String, DepthUpdateSpeed>,
+
+    /// Order sizes (base asset) whose price impact is published in metrics
+    pub impact_sizes: Vec<Decimal>,
+
+    /// Per-symbol impact size overrides
+    pub symbol_impact_sizes: HashMap<String, Vec<Decimal>>,
+
+    /// Distances from mid (bps) within which cumulative depth is published
+    pub depth_bands_bps: Vec<Decimal>,
+
+    /// Levels per side summed for the imbalance metric
+    pub imbalance_levels: usize,
+
+    /// Per-symbol imbalance level overrides
+    pub symbol_imbalance_levels: HashMap<String, usize>,
+
+    /// Levels per side weighted for the weighted imbalance metric
+    pub weighted_imbalance_levels: usize,
+
+    /// Weight multiplier per level for the weighted imbalance metric
+    pub weighted_imbalance_decay: Decimal,
+
+    /// Metrics not computed for any symbol
+    pub metrics_disabled: Vec<Metric>,
+
+    /// Per-symbol disabled metric overrides
+    pub symbol_metrics_disabled: HashMap<String, Vec<Metric>>,
+
+    /// Seconds a book must be live after a snapshot before it is published
+    pub warmup_secs: u64,
+
+    /// Diffs a book must apply after a snapshot before it is published
+    pub warmup_updates: u64,
+
+    /// Hold trades and depth diffs this long to release them in event-time
+    /// order (0 disables alignment)
+    pub alignment_max_delay_ms: u64,
+
     /// Reconnection settings
     pub reconnect_delay_ms: u64,
     pub max_reconnect_attempts: u32,
@@ -30,41 +820,785 @@ pub struct Config {
 }
 
 impl Config {
-    /// Load configuration from environment variables
+    /// Load configuration from environment variables, layered over the
+    /// config file at `CONFIG_PATH` if set
     pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(None)
+    }
+
+    /// Load configuration from environment variables, layered over the
+    /// config file at `path` (or `CONFIG_PATH`)
+    ///
+    /// The file format (TOML, YAML or JSON) follows its extension. With the
+    /// `daemon` feature, a `.env` file in the working directory is read
+    /// into the environment first.
+    pub fn load_from(path: Option<&Path>) -> anyhow::Result<Self> {
+        #[cfg(feature = "daemon")]
         dotenvy::dotenv().ok();
 
-        let symbols: Vec<String> = env::var("SYMBOLS")
-            .unwrap_or_else(|_| "BTCUSDT,ETHUSDT".to_string())
+        let path = path.map(Path::to_path_buf).or_else(|| {
+            env::var_os("CONFIG_PATH")
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+        });
+        let file = match &path {
+            Some(path) => ConfigFile::read(path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?,
+            None => ConfigFile::default(),
+        };
+
+        let config = Self::from_file(file)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Build configuration from a config file and environment overrides
+    fn from_file(file: ConfigFile) -> anyhow::Result<Self> {
+        let settings = Settings::new(file.values);
+
+        let symbols: Vec<String> = settings
+            .get("SYMBOLS")
+            .unwrap_or_else(|| "BTCUSDT,ETHUSDT".to_string())
             .split(',')
             .map(|s| s.trim().to_uppercase())
+            .filter(|s| !s.is_empty())
             .collect();
 
-        Ok(Self {
+        let mut config = Self {
             symbols,
-            ws_endpoint: env::var("WS_ENDPOINT")
-                .unwrap_or_else(|_| "wss://stream.binance.com:9443/ws".to_string()),
-            rest_endpoint: env::var("REST_ENDPOINT")
-                .unwrap_or_else(|_| "https://api.binance.com/api/v3".to_string()),
-            ipc_socket_path: env::var("IPC_SOCKET_PATH")
-                .unwrap_or_else(|_| "/tmp/quantumflow.sock".to_string()),
-            depth_levels: env::var("DEPTH_LEVELS")
-                .unwrap_or_else(|_| "20".to_string())
-                .parse()
-                .unwrap_or(20),
-            reconnect_delay_ms: env::var("RECONNECT_DELAY_MS")
-                .unwrap_or_else(|_| "1000".to_string())
-                .parse()
-                .unwrap_or(1000),
-            max_reconnect_attempts: env::var("MAX_RECONNECT_ATTEMPTS")
-                .unwrap_or_else(|_| "10".to_string())
-                .parse()
-                .unwrap_or(10),
-            health_check_interval_secs: env::var("HEALTH_CHECK_INTERVAL_SECS")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .unwrap_or(30),
+            ws_endpoint: settings
+                .get("WS_ENDPOINT")
+                .unwrap_or_else(|| "wss://stream.binance.com:9443/ws".to_string()),
+            rest_endpoint: settings
+                .get("REST_ENDPOINT")
+                .unwrap_or_else(|| "https://api.binance.com/api/v3".to_string()),
+            ws_fallback_endpoints: settings
+                .parse_with("WS_FALLBACK_ENDPOINTS", |s| parse_list(s, ','))?
+                .unwrap_or_default(),
+            rest_fallback_endpoints: settings
+                .parse_with("REST_FALLBACK_ENDPOINTS", |s| parse_list(s, ','))?
+                .unwrap_or_default(),
+            endpoint_failover_after: settings.parse("ENDPOINT_FAILOVER_AFTER", 3)?,
+            endpoint_probe_interval_secs: settings.parse("ENDPOINT_PROBE_INTERVAL_SECS", 300)?,
+            tls_ca_file: settings.get("TLS_CA_FILE").filter(|p| !p.is_empty()),
+            tls_handshake_timeout_ms: settings.parse("TLS_HANDSHAKE_TIMEOUT_MS", 10_000)?,
+            tcp_nodelay: settings.parse("TCP_NODELAY", true)?,
+            tcp_keepalive_secs: settings.parse("TCP_KEEPALIVE_SECS", 0)?,
+            tcp_send_buffer_bytes: settings.parse_opt("TCP_SEND_BUFFER_BYTES")?,
+            tcp_recv_buffer_bytes: settings.parse_opt("TCP_RECV_BUFFER_BYTES")?,
+            ws_ping_interval_secs: settings.parse_opt("WS_PING_INTERVAL_SECS")?,
+            ws_pong_interval_secs: settings.parse_opt("WS_PONG_INTERVAL_SECS")?,
+            ws_idle_ping_secs: settings.parse_opt("WS_IDLE_PING_SECS")?,
+            ws_intake_capacity: settings.parse("WS_INTAKE_CAPACITY", 4096)?,
+            ws_intake_policy: settings.parse_opt("WS_INTAKE_POLICY")?.unwrap_or_default(),
+            coalesce_depth_updates: settings.parse("COALESCE_DEPTH_UPDATES", true)?,
+            raw_tap: settings.parse_opt("RAW_TAP")?,
+            raw_tap_capacity: settings.parse("RAW_TAP_CAPACITY", 65_536)?,
+            ws_rotate_after_secs: settings.parse("WS_ROTATE_AFTER_SECS", 82_800)?,
+            redundant_ws_endpoint: settings
+                .get("REDUNDANT_WS_ENDPOINT")
+                .filter(|e| !e.is_empty()),
+            ipc_socket_path: settings
+                .get("IPC_SOCKET_PATH")
+                .unwrap_or_else(|| "/tmp/quantumflow.sock".to_string()),
+            ipc_bootstrap: settings.parse("IPC_BOOTSTRAP", false)?,
+            ipc_replay_depth: settings.parse("IPC_REPLAY_DEPTH", 1000)?,
+            ipc_bootstrap_timeout_ms: settings.parse("IPC_BOOTSTRAP_TIMEOUT_MS", 500)?,
+            ipc_queue_capacity: settings.parse("IPC_QUEUE_CAPACITY", 1024)?,
+            ipc_queue_policy: settings.parse_opt("IPC_QUEUE_POLICY")?.unwrap_or_default(),
+            ipc_send_buffer_bytes: settings.parse_opt("IPC_SEND_BUFFER_BYTES")?,
+            ipc_heartbeat_interval_ms: settings.parse("IPC_HEARTBEAT_INTERVAL_MS", 1000)?,
+            ipc_reconnect_delay_ms: settings.parse("IPC_RECONNECT_DELAY_MS", 100)?,
+            ipc_reconnect_max_delay_ms: settings.parse("IPC_RECONNECT_MAX_DELAY_MS", 5000)?,
+            ipc_backlog_capacity: settings.parse("IPC_BACKLOG_CAPACITY", 0)?,
+            journal_dir: settings.get("JOURNAL_DIR").filter(|d| !d.is_empty()),
+            journal_segment_bytes: settings.parse("JOURNAL_SEGMENT_BYTES", 64 * 1024 * 1024)?,
+            journal_max_segments: settings.parse("JOURNAL_MAX_SEGMENTS", 16)?,
+            shm_path: settings.get("SHM_PATH").filter(|p| !p.is_empty()),
+            shm_slot_size: settings.parse("SHM_SLOT_SIZE", 4096)?,
+            shm_slot_count: settings.parse("SHM_SLOT_COUNT", 1024)?,
+            multicast_group: settings.get("MULTICAST_GROUP").filter(|g| !g.is_empty()),
+            multicast_mtu: settings.parse("MULTICAST_MTU", 1500)?,
+            multicast_ttl: settings.parse("MULTICAST_TTL", 1)?,
+            multicast_interface: settings.parse_opt("MULTICAST_INTERFACE")?,
+            multicast_send_buffer_bytes: settings.parse_opt("MULTICAST_SEND_BUFFER_BYTES")?,
+            nats_url: settings.get("NATS_URL").filter(|u| !u.is_empty()),
+            nats_subject_prefix: settings
+                .get("NATS_SUBJECT_PREFIX")
+                .unwrap_or_else(|| "md".to_string()),
+            nats_jetstream_stream: settings
+                .get("NATS_JETSTREAM_STREAM")
+                .filter(|s| !s.is_empty()),
+            kafka_brokers: settings.get("KAFKA_BROKERS").filter(|b| !b.is_empty()),
+            kafka_book_topic: settings
+                .get("KAFKA_BOOK_TOPIC")
+                .unwrap_or_else(|| "md.book.{symbol}".to_string()),
+            kafka_trade_topic: settings
+                .get("KAFKA_TRADE_TOPIC")
+                .unwrap_or_else(|| "md.trade.{symbol}".to_string()),
+            kafka_symbol_partitions: settings
+                .parse_with("KAFKA_SYMBOL_PARTITIONS", parse_symbol_map)?
+                .unwrap_or_default(),
+            kafka_compression: settings
+                .get("KAFKA_COMPRESSION")
+                .unwrap_or_else(|| "lz4".to_string()),
+            kafka_linger_ms: settings.parse("KAFKA_LINGER_MS", 5)?,
+            redis_url: settings.get("REDIS_URL").filter(|u| !u.is_empty()),
+            redis_mode: settings.parse_opt("REDIS_MODE")?.unwrap_or_default(),
+            redis_key_prefix: settings
+                .get("REDIS_KEY_PREFIX")
+                .unwrap_or_else(|| "md".to_string()),
+            redis_stream_maxlen: settings.parse("REDIS_STREAM_MAXLEN", 100_000)?,
+            grpc_addr: settings.parse_opt("GRPC_ADDR")?,
+            otel_endpoint: settings
+                .get("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .filter(|s| !s.is_empty()),
+            otel_service_name: settings
+                .get("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|| "market-data".to_string()),
+            live_feed_buffer: settings.parse("LIVE_FEED_BUFFER", 1024)?,
+            arrow_export_dir: settings.get("ARROW_EXPORT_DIR").filter(|s| !s.is_empty()),
+            arrow_batch_interval_ms: settings.parse("ARROW_BATCH_INTERVAL_MS", 1000)?,
+            clickhouse_url: settings.get("CLICKHOUSE_URL").filter(|s| !s.is_empty()),
+            clickhouse_database: settings
+                .get("CLICKHOUSE_DATABASE")
+                .unwrap_or_else(|| "default".to_string()),
+            clickhouse_user: settings.get("CLICKHOUSE_USER").filter(|s| !s.is_empty()),
+            clickhouse_password: settings
+                .get("CLICKHOUSE_PASSWORD")
+                .filter(|s| !s.is_empty()),
+            clickhouse_batch_size: settings.parse("CLICKHOUSE_BATCH_SIZE", 10_000)?,
+            clickhouse_flush_interval_ms: settings.parse("CLICKHOUSE_FLUSH_INTERVAL_MS", 1000)?,
+            clickhouse_queue_capacity: settings.parse("CLICKHOUSE_QUEUE_CAPACITY", 100_000)?,
+            archive_dir: settings.get("ARCHIVE_DIR").filter(|s| !s.is_empty()),
+            archive_flush_interval_secs: settings.parse("ARCHIVE_FLUSH_INTERVAL_SECS", 60)?,
+            archive_snapshot_interval_secs: settings.parse("ARCHIVE_SNAPSHOT_INTERVAL_SECS", 10)?,
+            subscribe_batch_size: settings.parse("SUBSCRIBE_BATCH_SIZE", 0)?,
+            subscribe_interval_ms: settings.parse("SUBSCRIBE_INTERVAL_MS", 250)?,
+            ws_stream_endpoint: settings
+                .parse_opt("WS_STREAM_ENDPOINT")?
+                .unwrap_or_default(),
+            analytics_state_path: settings
+                .get("ANALYTICS_STATE_PATH")
+                .filter(|p| !p.is_empty()),
+            analytics_persist_interval_secs: settings
+                .parse("ANALYTICS_PERSIST_INTERVAL_SECS", 30)?,
+            book_state_path: settings.get("BOOK_STATE_PATH").filter(|p| !p.is_empty()),
+            book_persist_interval_secs: settings.parse("BOOK_PERSIST_INTERVAL_SECS", 30)?,
+            book_resume_max_age_secs: settings.parse("BOOK_RESUME_MAX_AGE_SECS", 60)?,
+            shutdown_timeout_secs: settings.parse("SHUTDOWN_TIMEOUT_SECS", 10)?,
+            stale_book_secs: settings.parse("STALE_BOOK_SECS", 30)?,
+            liveness_timeout_secs: settings.parse("LIVENESS_TIMEOUT_SECS", 120)?,
+            readiness_max_book_age_secs: settings.parse("READINESS_MAX_BOOK_AGE_SECS", 60)?,
+            readiness_require_ipc: settings.parse("READINESS_REQUIRE_IPC", true)?,
+            trade_metrics_window_secs: settings.parse("TRADE_METRICS_WINDOW_SECS", 60)?,
+            symbol_trade_metrics_windows: settings
+                .parse_with("TRADE_METRICS_WINDOW_SECS_SYMBOLS", parse_symbol_map)?
+                .unwrap_or_default(),
+            realized_vol_window_secs: settings.parse("REALIZED_VOL_WINDOW_SECS", 300)?,
+            symbol_realized_vol_windows: settings
+                .parse_with("REALIZED_VOL_WINDOW_SECS_SYMBOLS", parse_symbol_map)?
+                .unwrap_or_default(),
+            realized_vol_sample_ms: settings.parse("REALIZED_VOL_SAMPLE_MS", 1000)?,
+            bbo_events_enabled: settings.parse("BBO_EVENTS_ENABLED", false)?,
+            anomaly_detection_enabled: settings.parse("ANOMALY_DETECTION_ENABLED", false)?,
+            anomaly_flash_window_ms: settings.parse("ANOMALY_FLASH_WINDOW_MS", 500)?,
+            anomaly_large_level_multiple: settings.parse("ANOMALY_LARGE_LEVEL_MULTIPLE", 10.0)?,
+            anomaly_update_rate_multiple: settings.parse("ANOMALY_UPDATE_RATE_MULTIPLE", 5.0)?,
+            volume_profile_enabled: settings.parse("VOLUME_PROFILE_ENABLED", false)?,
+            volume_profile_window_secs: settings.parse("VOLUME_PROFILE_WINDOW_SECS", 300)?,
+            volume_profile_bucket_bps: settings
+                .parse("VOLUME_PROFILE_BUCKET_BPS", Decimal::from(5))?,
+            volume_profile_publish_interval_secs: settings
+                .parse("VOLUME_PROFILE_PUBLISH_INTERVAL_SECS", 10)?,
+            degradation_enabled: settings.parse("DEGRADATION_ENABLED", false)?,
+            degradation_latency_budget_us: settings.parse("DEGRADATION_LATENCY_BUDGET_US", 1000)?,
+            degradation_max_over_budget_pct: settings
+                .parse("DEGRADATION_MAX_OVER_BUDGET_PCT", 10)?,
+            degradation_max_failures: settings.parse("DEGRADATION_MAX_FAILURES", 50)?,
+            degradation_window_secs: settings.parse("DEGRADATION_WINDOW_SECS", 5)?,
+            degradation_recover_windows: settings.parse("DEGRADATION_RECOVER_WINDOWS", 3)?,
+            dead_man_enabled: settings.parse("DEADMAN_ENABLED", false)?,
+            dead_man_no_update_secs: settings.parse("DEADMAN_NO_UPDATE_SECS", 10)?,
+            dead_man_max_resyncs: settings.parse("DEADMAN_MAX_RESYNCS", 3)?,
+            dead_man_resync_window_secs: settings.parse("DEADMAN_RESYNC_WINDOW_SECS", 60)?,
+            dead_man_disconnected_secs: settings.parse("DEADMAN_DISCONNECTED_SECS", 5)?,
+            latency_window_secs: settings.parse("LATENCY_WINDOW_SECS", 5)?,
+            publish_throttle_ms: settings.parse("PUBLISH_THROTTLE_MS", 0)?,
+            publish_throttle_ms_symbols: settings
+                .parse_with("PUBLISH_THROTTLE_MS_SYMBOLS", parse_symbol_map)?
+                .unwrap_or_default(),
+            publish_on_bbo_change: settings.parse("PUBLISH_ON_BBO_CHANGE", true)?,
+            publish_mode: settings.parse_opt("PUBLISH_MODE")?.unwrap_or_default(),
+            wire_format: settings.parse_opt("WIRE_FORMAT")?.unwrap_or_default(),
+            ipc_compression: settings.parse_opt("IPC_COMPRESSION")?.unwrap_or_default(),
+            full_refresh_interval_ms: settings.parse("FULL_REFRESH_INTERVAL_MS", 5000)?,
+            depth_levels: settings.parse("DEPTH_LEVELS", 20)?,
+            symbol_depth_levels: settings
+                .parse_with("DEPTH_LEVELS_SYMBOLS", parse_symbol_map)?
+                .unwrap_or_default(),
+            overflow_levels: settings.parse("OVERFLOW_LEVELS", 480)?,
+            crossed_book_policy: settings
+                .parse_opt("CROSSED_BOOK_POLICY")?
+                .unwrap_or_default(),
+            book_representation: settings
+                .parse_opt("BOOK_REPRESENTATION")?
+                .unwrap_or_default(),
+            tick_sizes: settings
+                .parse_with("TICK_SIZES", parse_tick_sizes)?
+                .unwrap_or_default(),
+            exchange_info_enabled: settings.parse("EXCHANGE_INFO_ENABLED", true)?,
+            exchange_info_refresh_secs: settings.parse("EXCHANGE_INFO_REFRESH_SECS", 3600)?,
+            clock_sync_interval_secs: settings.parse("CLOCK_SYNC_INTERVAL_SECS", 60)?,
+            rest_weight_limit: settings.parse("REST_WEIGHT_LIMIT", 5000)?,
+            rest_min_interval_ms: settings.parse("REST_MIN_INTERVAL_MS", 100)?,
+            rest_max_retries: settings.parse("REST_MAX_RETRIES", 5)?,
+            rest_retry_base_ms: settings.parse("REST_RETRY_BASE_MS", 500)?,
+            binance_api_key: settings.get("BINANCE_API_KEY").filter(|s| !s.is_empty()),
+            binance_api_secret: settings.get("BINANCE_API_SECRET").filter(|s| !s.is_empty()),
+            rest_recv_window_ms: settings.parse("REST_RECV_WINDOW_MS", 5000)?,
+            user_data_keepalive_secs: settings.parse("USER_DATA_KEEPALIVE_SECS", 1800)?,
+            snapshot_concurrency: settings.parse("SNAPSHOT_CONCURRENCY", 4)?,
+            depth_update_speed: settings
+                .parse_opt("DEPTH_UPDATE_SPEED")?
+                .unwrap_or_default(),
+            symbol_update_speeds: settings
+                .parse_with("SYMBOL_UPDATE_SPEEDS", parse_symbol_map)?
+                .unwrap_or_default(),
+            impact_sizes: settings
+                .parse_with("IMPACT_REFERENCE_SIZES", |s| parse_decimals(s, ','))?
+                .unwrap_or_default(),
+            symbol_impact_sizes: settings
+                .parse_with("IMPACT_REFERENCE_SIZES_SYMBOLS", parse_symbol_sizes)?
+                .unwrap_or_default(),
+            depth_bands_bps: settings
+                .parse_with("DEPTH_BANDS_BPS", |s| parse_decimals(s, ','))?
+                .unwrap_or_else(|| vec![Decimal::from(10), Decimal::from(50)]),
+            imbalance_levels: settings.parse("IMBALANCE_LEVELS", 5)?,
+            symbol_imbalance_levels: settings
+                .parse_with("IMBALANCE_LEVELS_SYMBOLS", parse_symbol_map)?
+                .unwrap_or_default(),
+            weighted_imbalance_levels: settings.parse("WEIGHTED_IMBALANCE_LEVELS", 10)?,
+            weighted_imbalance_decay: settings
+                .parse("WEIGHTED_IMBALANCE_DECAY", Decimal::new(9, 1))?,
+            metrics_disabled: settings
+                .parse_with("METRICS_DISABLED", |s| parse_list(s, ','))?
+                .unwrap_or_default(),
+            symbol_metrics_disabled: settings
+                .parse_with("METRICS_DISABLED_SYMBOLS", parse_symbol_lists)?
+                .unwrap_or_default(),
+            warmup_secs: settings.parse("WARMUP_SECS", 0)?,
+            warmup_updates: settings.parse("WARMUP_UPDATES", 0)?,
+            alignment_max_delay_ms: settings.parse("ALIGNMENT_MAX_DELAY_MS", 0)?,
+            reconnect_delay_ms: settings.parse("RECONNECT_DELAY_MS", 1000)?,
+            max_reconnect_attempts: settings.parse("MAX_RECONNECT_ATTEMPTS", 10)?,
+            health_check_interval_secs: settings.parse("HEALTH_CHECK_INTERVAL_SECS", 30)?,
+        };
+
+        let unknown = settings.unknown_keys();
+        if !unknown.is_empty() {
+            bail!("Unknown config keys: {}", unknown.join(", "));
+        }
+        config.apply_sections(file.symbols);
+        Ok(config)
+    }
+
+    /// Fill per-symbol settings from config file sections; per-symbol
+    /// environment variables take precedence
+    fn apply_sections(&mut self, sections: HashMap<String, SymbolSection>) {
+        for (symbol, section) in sections {
+            let symbol = symbol.to_uppercase();
+            if let Some(levels) = section.depth_levels {
+                self.symbol_depth_levels
+                    .entry(symbol.clone())
+                    .or_insert(levels);
+            }
+            if let Some(speed) = section.depth_update_speed {
+                self.symbol_update_speeds
+                    .entry(symbol.clone())
+                    .or_insert(speed);
+            }
+            if let Some(throttle) = section.publish_throttle_ms {
+                self.publish_throttle_ms_symbols
+                    .entry(symbol.clone())
+                    .or_insert(throttle);
+            }
+            if let Some(sizes) = section.impact_reference_sizes {
+                self.symbol_impact_sizes
+                    .entry(symbol.clone())
+                    .or_insert(sizes);
+            }
+            if let Some(levels) = section.imbalance_levels {
+                self.symbol_imbalance_levels
+                    .entry(symbol.clone())
+                    .or_insert(levels);
+            }
+            if let Some(metrics) = section.metrics_disabled {
+                self.symbol_metrics_disabled
+                    .entry(symbol.clone())
+                    .or_insert(metrics);
+            }
+            if let Some(window) = section.trade_metrics_window_secs {
+                self.symbol_trade_metrics_windows
+                    .entry(symbol.clone())
+                    .or_insert(window);
+            }
+            if let Some(window) = section.realized_vol_window_secs {
+                self.symbol_realized_vol_windows
+                    .entry(symbol)
+                    .or_insert(window);
+            }
+        }
+    }
+
+    /// Reject settings the handler can't run with
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.symbols.is_empty() {
+            bail!("SYMBOLS must name at least one symbol");
+        }
+        // A depth and a trade stream per symbol on one connection
+        if self.symbols.len() * 2 > MAX_STREAMS_PER_CONNECTION {
+            bail!(
+                "SYMBOLS names {} symbols; one connection carries at most {}",
+                self.symbols.len(),
+                MAX_STREAMS_PER_CONNECTION / 2
+            );
+        }
+        if self.depth_levels == 0 {
+            bail!("DEPTH_LEVELS must be at least 1");
+        }
+        if self.tls_handshake_timeout_ms == 0 {
+            bail!("TLS_HANDSHAKE_TIMEOUT_MS must be at least 1");
+        }
+        if self.ws_intake_capacity == 0 {
+            bail!("WS_INTAKE_CAPACITY must be at least 1");
+        }
+        if self.ipc_reconnect_delay_ms == 0 {
+            bail!("IPC_RECONNECT_DELAY_MS must be at least 1");
+        }
+        if self.clickhouse_database.is_empty()
+            || !self
+                .clickhouse_database
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            bail!(
+                "CLICKHOUSE_DATABASE must be a plain identifier (letters, digits, _), got {:?}",
+                self.clickhouse_database
+            );
+        }
+        if self.journal_segment_bytes == 0 || self.journal_max_segments == 0 {
+            bail!("JOURNAL_SEGMENT_BYTES and JOURNAL_MAX_SEGMENTS must be at least 1");
+        }
+        if self.raw_tap_capacity == 0 {
+            bail!("RAW_TAP_CAPACITY must be at least 1");
+        }
+        if self.endpoint_failover_after == 0 {
+            bail!("ENDPOINT_FAILOVER_AFTER must be at least 1");
+        }
+        if let Some(symbol) = self.symbol_depth_levels.iter().find(|(_, l)| **l == 0) {
+            bail!("Depth levels of {} must be at least 1", symbol.0);
+        }
+        if self.weighted_imbalance_decay <= Decimal::ZERO
+            || self.weighted_imbalance_decay > Decimal::ONE
+        {
+            bail!("WEIGHTED_IMBALANCE_DECAY must be in (0, 1]");
+        }
+        if self.binance_api_secret.is_some() && self.binance_api_key.is_none() {
+            bail!("BINANCE_API_SECRET is set without BINANCE_API_KEY");
+        }
+        if self.rest_recv_window_ms == 0 || self.rest_recv_window_ms > 60_000 {
+            bail!("REST_RECV_WINDOW_MS must be in [1, 60000]");
+        }
+
+        let overridden = self
+            .symbol_depth_levels
+            .keys()
+            .chain(self.symbol_update_speeds.keys())
+            .chain(self.publish_throttle_ms_symbols.keys())
+            .chain(self.symbol_impact_sizes.keys())
+            .chain(self.symbol_imbalance_levels.keys())
+            .chain(self.symbol_metrics_disabled.keys())
+            .chain(self.symbol_trade_metrics_windows.keys())
+            .chain(self.symbol_realized_vol_windows.keys());
+        for symbol in overridden {
+            if !self.symbols.contains(symbol) {
+                bail!("Settings given for {}, which is not in SYMBOLS", symbol);
+            }
+        }
+        Ok(())
+    }
+
+    /// Warm-up requirement applied after each book snapshot
+    pub fn warmup_policy(&self) -> WarmupPolicy {
+        WarmupPolicy {
+            min_duration: Duration::from_secs(self.warmup_secs),
+            min_updates: self.warmup_updates,
+        }
+    }
+
+    /// Anomaly detection thresholds
+    pub fn anomaly_policy(&self) -> AnomalyPolicy {
+        AnomalyPolicy {
+            flash_window: Duration::from_millis(self.anomaly_flash_window_ms),
+            large_level_multiple: self.anomaly_large_level_multiple,
+            update_rate_multiple: self.anomaly_update_rate_multiple,
+        }
+    }
+
+    /// Degradation tier shedding thresholds
+    pub fn degradation_policy(&self) -> DegradationPolicy {
+        DegradationPolicy {
+            enabled: self.degradation_enabled,
+            latency_budget: Duration::from_micros(self.degradation_latency_budget_us),
+            max_over_budget_pct: self.degradation_max_over_budget_pct,
+            max_failures: self.degradation_max_failures,
+            window: Duration::from_secs(self.degradation_window_secs.max(1)),
+            recover_windows: self.degradation_recover_windows.max(1),
+        }
+    }
+
+    /// When the feed is declared degraded
+    pub fn dead_man_policy(&self) -> DeadManPolicy {
+        DeadManPolicy {
+            enabled: self.dead_man_enabled,
+            no_updates: Duration::from_secs(self.dead_man_no_update_secs),
+            max_resyncs: self.dead_man_max_resyncs,
+            resync_window: Duration::from_secs(self.dead_man_resync_window_secs.max(1)),
+            disconnected: Duration::from_secs(self.dead_man_disconnected_secs),
+        }
+    }
+
+    /// WebSocket endpoints, the primary first
+    pub fn ws_endpoints(&self) -> Vec<String> {
+        std::iter::once(self.ws_endpoint.clone())
+            .chain(self.ws_fallback_endpoints.iter().cloned())
+            .collect()
+    }
+
+    /// REST API endpoints, the primary first
+    pub fn rest_endpoints(&self) -> Vec<String> {
+        std::iter::once(self.rest_endpoint.clone())
+            .chain(self.rest_fallback_endpoints.iter().cloned())
+            .collect()
+    }
+
+    /// Depth levels maintained for a symbol
+    pub fn depth_levels_for(&self, symbol: &str) -> usize {
+        self.symbol_depth_levels
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.depth_levels)
+    }
+
+    /// Depth update speed for a symbol, falling back to the global default
+    pub fn depth_update_speed_for(&self, symbol: &str) -> DepthUpdateSpeed {
+        self.symbol_update_speeds
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.depth_update_speed)
+    }
+
+    /// Publish throttle for a symbol (0 = every update)
+    pub fn publish_throttle_ms_for(&self, symbol: &str) -> u64 {
+        self.publish_throttle_ms_symbols
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.publish_throttle_ms)
+    }
+
+    /// Rolling trade metrics window for a symbol
+    pub fn trade_metrics_window_secs_for(&self, symbol: &str) -> u64 {
+        self.symbol_trade_metrics_windows
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.trade_metrics_window_secs)
+    }
+
+    /// Realized volatility window for a symbol (0 = off)
+    pub fn realized_vol_window_secs_for(&self, symbol: &str) -> u64 {
+        self.symbol_realized_vol_windows
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.realized_vol_window_secs)
+    }
+
+    /// Price impact reference sizes for a symbol
+    pub fn impact_sizes_for(&self, symbol: &str) -> Vec<Decimal> {
+        self.symbol_impact_sizes
+            .get(symbol)
+            .unwrap_or(&self.impact_sizes)
+            .clone()
+    }
+
+    /// Fixed-point scales of the symbols whose books store fixed-point
+    /// levels: all symbols with known tick sizes when so configured, taken
+    /// from `tick_sizes` or else from the fetched instruments
+    pub fn tick_scales(
+        &self,
+        instruments: &HashMap<String, InstrumentInfo>,
+    ) -> HashMap<String, TickScale> {
+        if self.book_representation != BookRepresentation::FixedPoint {
+            return HashMap::new();
+        }
+        let fetched = instruments
+            .iter()
+            .filter(|(_, info)| !info.tick_size.is_zero() && !info.step_size.is_zero())
+            .map(|(symbol, info)| (symbol, (info.tick_size, info.step_size)));
+        let configured = self
+            .tick_sizes
+            .iter()
+            .map(|(symbol, sizes)| (symbol, *sizes));
+        fetched
+            .chain(configured)
+            .map(|(symbol, (tick, step))| (symbol.clone(), TickScale::from_sizes(tick, step)))
+            .collect()
+    }
+
+    /// Metrics computed for a symbol and their parameters
+    pub fn metrics_config_for(&self, symbol: &str) -> MetricsConfig {
+        MetricsConfig {
+            imbalance_levels: self
+                .symbol_imbalance_levels
+                .get(symbol)
+                .copied()
+                .unwrap_or(self.imbalance_levels),
+            weighted_imbalance_levels: self.weighted_imbalance_levels,
+            weighted_imbalance_decay: self.weighted_imbalance_decay,
+            disabled: self
+                .symbol_metrics_disabled
+                .get(symbol)
+                .unwrap_or(&self.metrics_disabled)
+                .iter()
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// Parse `SYMBOL=value` pairs, e.g. "BTCUSDT=100ms,DOGEUSDT=1000ms"
+fn parse_symbol_map<T: FromStr>(raw: &str) -> Result<HashMap<String, T>, String>
+where
+    T::Err: fmt::Display,
+{
+    entries(raw, ',')
+        .map(|pair| {
+            let (symbol, value) = split_pair(pair)?;
+            let value = value.trim().parse().map_err(|e| bad_entry(pair, e))?;
+            Ok((symbol, value))
+        })
+        .collect()
+}
+
+/// Parse a list of positive decimals, e.g. "0.1,1,10"
+fn parse_decimals(raw: &str, separator: char) -> Result<Vec<Decimal>, String> {
+    entries(raw, separator)
+        .map(|size| match size.parse::<Decimal>() {
+            Ok(size) if size > Decimal::ZERO => Ok(size),
+            Ok(_) => Err(bad_entry(size, "must be positive")),
+            Err(e) => Err(bad_entry(size, e)),
         })
+        .collect()
+}
+
+/// Parse per-symbol size lists, e.g. "BTCUSDT=0.1|1|10,DOGEUSDT=10000"
+fn parse_symbol_sizes(raw: &str) -> Result<HashMap<String, Vec<Decimal>>, String> {
+    entries(raw, ',')
+        .map(|pair| {
+            let (symbol, sizes) = split_pair(pair)?;
+            Ok((symbol, parse_decimals(sizes, '|')?))
+        })
+        .collect()
+}
+
+/// Parse per-symbol tick and step sizes, e.g. "BTCUSDT=0.01|0.00001"
+fn parse_tick_sizes(raw: &str) -> Result<HashMap<String, (Decimal, Decimal)>, String> {
+    entries(raw, ',')
+        .map(|pair| {
+            let (symbol, sizes) = split_pair(pair)?;
+            match parse_decimals(sizes, '|')?[..] {
+                [tick, step] => Ok((symbol, (tick, step))),
+                _ => Err(bad_entry(pair, "expected SYMBOL=TICK|STEP")),
+            }
+        })
+        .collect()
+}
+
+/// Parse a `separator`-delimited list
+fn parse_list<T: FromStr>(raw: &str, separator: char) -> Result<Vec<T>, String>
+where
+    T::Err: fmt::Display,
+{
+    entries(raw, separator)
+        .map(|item| item.parse().map_err(|e| bad_entry(item, e)))
+        .collect()
+}
+
+/// Parse `SYMBOL=a|b|c` lists, e.g. "BTCUSDT=slope,ETHUSDT=slope|depth"
+fn parse_symbol_lists<T: FromStr>(raw: &str) -> Result<HashMap<String, Vec<T>>, String>
+where
+    T::Err: fmt::Display,
+{
+    entries(raw, ',')
+        .map(|pair| {
+            let (symbol, items) = split_pair(pair)?;
+            Ok((symbol, parse_list(items, '|')?))
+        })
+        .collect()
+}
+
+/// Non-empty entries of a `separator`-delimited list, trimmed
+fn entries(raw: &str, separator: char) -> impl Iterator<Item = &str> {
+    raw.split(separator)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// Split `SYMBOL=value` into the upper-cased symbol and the value
+fn split_pair(pair: &str) -> Result<(String, &str), String> {
+    let (symbol, value) = pair
+        .split_once('=')
+        .ok_or_else(|| bad_entry(pair, "expected SYMBOL=VALUE"))?;
+    Ok((symbol.trim().to_uppercase(), value))
+}
+
+fn bad_entry(entry: &str, error: impl fmt::Display) -> String {
+    format!("entry {:?}: {}", entry, error)
+}
+
+/// Per-symbol section of a config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SymbolSection {
+    depth_levels: Option<usize>,
+    depth_update_speed: Option<DepthUpdateSpeed>,
+    publish_throttle_ms: Option<u64>,
+    impact_reference_sizes: Option<Vec<Decimal>>,
+    imbalance_levels: Option<usize>,
+    metrics_disabled: Option<Vec<Metric>>,
+    trade_metrics_window_secs: Option<u64>,
+    realized_vol_window_secs: Option<u64>,
+}
+
+/// Settings read from a config file
+#[derive(Debug, Default)]
+struct ConfigFile {
+    /// Top-level values by environment variable name; lists are joined
+    /// with commas
+    values: HashMap<String, String>,
+    /// `[symbol.<SYMBOL>]` sections
+    symbols: HashMap<String, SymbolSection>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        Self::parse(::config::File::from(path))
+    }
+
+    fn parse<S>(source: S) -> anyhow::Result<Self>
+    where
+        S: ::config::Source + Send + Sync + 'static,
+    {
+        let mut table: HashMap<String, ::config::Value> = ::config::Config::builder()
+            .add_source(source)
+            .build()?
+            .try_deserialize()?;
+        let symbols = match table.remove("symbol") {
+            Some(sections) => sections
+                .try_deserialize()
+                .context("Invalid [symbol] section")?,
+            None => HashMap::new(),
+        };
+        let values = table
+            .into_iter()
+            .map(|(key, value)| {
+                let value = setting_string(value).with_context(|| format!("Invalid {}", key))?;
+                Ok((key.to_uppercase(), value))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { values, symbols })
+    }
+}
+
+/// A file value as the environment would spell it
+fn setting_string(value: ::config::Value) -> anyhow::Result<String> {
+    match value.kind {
+        ::config::ValueKind::Array(items) => Ok(items
+            .into_iter()
+            .map(::config::Value::into_string)
+            .collect::<Result<Vec<_>, _>>()?
+            .join(",")),
+        _ => Ok(value.into_string()?),
+    }
+}
+
+/// Setting lookup by environment variable name: the environment first,
+/// then the config file
+struct Settings {
+    file: HashMap<String, String>,
+    /// Names looked up so far, to find unknown file keys
+    read: RefCell<HashSet<String>>,
+}
+
+impl Settings {
+    fn new(file: HashMap<String, String>) -> Self {
+        Self {
+            file,
+            read: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.read.borrow_mut().insert(key.to_string());
+        env::var(key).ok().or_else(|| self.file.get(key).cloned())
+    }
+
+    /// Parse a setting, or return `default` when it is unset or empty
+    fn parse<T: FromStr>(&self, key: &str, default: T) -> anyhow::Result<T>
+    where
+        T::Err: fmt::Display,
+    {
+        Ok(self.parse_opt(key)?.unwrap_or(default))
+    }
+
+    /// Parse a setting if it is set and not empty
+    fn parse_opt<T: FromStr>(&self, key: &str) -> anyhow::Result<Option<T>>
+    where
+        T::Err: fmt::Display,
+    {
+        match self.get(key) {
+            Some(raw) if !raw.trim().is_empty() => raw
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|e| anyhow!("Invalid {} {:?}: {}", key, raw, e)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse a setting with `parse` if it is set, naming the key and the
+    /// bad entry when it doesn't parse
+    fn parse_with<T>(
+        &self,
+        key: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> anyhow::Result<Option<T>> {
+        self.get(key)
+            .map(|raw| parse(&raw).map_err(|e| anyhow!("Invalid {} {:?}: {}", key, raw, e)))
+            .transpose()
+    }
+
+    /// File keys that no setting reads
+    fn unknown_keys(&self) -> Vec<String> {
+        let read = self.read.borrow();
+        let mut unknown: Vec<String> = self
+            .file
+            .keys()
+            .filter(|key| !read.contains(*key))
+            .map(|key| key.to_lowercase())
+            .collect();
+        unknown.sort();
+        unknown
     }
 }
 
@@ -74,11 +1608,305 @@ impl Default for Config {
             symbols: vec!["BTCUSDT".to_string()],
             ws_endpoint: "wss://stream.binance.com:9443/ws".to_string(),
             rest_endpoint: "https://api.binance.com/api/v3".to_string(),
+            ws_fallback_endpoints: Vec::new(),
+            rest_fallback_endpoints: Vec::new(),
+            endpoint_failover_after: 3,
+            endpoint_probe_interval_secs: 300,
+            tls_ca_file: None,
+            tls_handshake_timeout_ms: 10_000,
+            tcp_nodelay: true,
+            tcp_keepalive_secs: 0,
+            tcp_send_buffer_bytes: None,
+            tcp_recv_buffer_bytes: None,
+            ws_ping_interval_secs: None,
+            ws_pong_interval_secs: None,
+            ws_idle_ping_secs: None,
+            ws_intake_capacity: 4096,
+            ws_intake_policy: IntakePolicy::default(),
+            coalesce_depth_updates: true,
+            raw_tap: None,
+            raw_tap_capacity: 65_536,
+            ws_rotate_after_secs: 82_800,
+            redundant_ws_endpoint: None,
             ipc_socket_path: "/tmp/quantumflow.sock".to_string(),
+            ipc_bootstrap: false,
+            ipc_replay_depth: 1000,
+            ipc_bootstrap_timeout_ms: 500,
+            ipc_queue_capacity: 1024,
+            ipc_queue_policy: OverflowPolicy::default(),
+            ipc_send_buffer_bytes: None,
+            ipc_heartbeat_interval_ms: 1000,
+            ipc_reconnect_delay_ms: 100,
+            ipc_reconnect_max_delay_ms: 5000,
+            ipc_backlog_capacity: 0,
+            journal_dir: None,
+            journal_segment_bytes: 64 * 1024 * 1024,
+            journal_max_segments: 16,
+            shm_path: None,
+            shm_slot_size: 4096,
+            shm_slot_count: 1024,
+            multicast_group: None,
+            multicast_mtu: 1500,
+            multicast_ttl: 1,
+            multicast_interface: None,
+            multicast_send_buffer_bytes: None,
+            nats_url: None,
+            nats_subject_prefix: "md".to_string(),
+            nats_jetstream_stream: None,
+            kafka_brokers: None,
+            kafka_book_topic: "md.book.{symbol}".to_string(),
+            kafka_trade_topic: "md.trade.{symbol}".to_string(),
+            kafka_symbol_partitions: HashMap::new(),
+            kafka_compression: "lz4".to_string(),
+            kafka_linger_ms: 5,
+            redis_url: None,
+            redis_mode: RedisMode::default(),
+            redis_key_prefix: "md".to_string(),
+            redis_stream_maxlen: 100_000,
+            grpc_addr: None,
+            otel_endpoint: None,
+            otel_service_name: "market-data".to_string(),
+            live_feed_buffer: 1024,
+            arrow_export_dir: None,
+            arrow_batch_interval_ms: 1000,
+            clickhouse_url: None,
+            clickhouse_database: "default".to_string(),
+            clickhouse_user: None,
+            clickhouse_password: None,
+            clickhouse_batch_size: 10_000,
+            clickhouse_flush_interval_ms: 1000,
+            clickhouse_queue_capacity: 100_000,
+            archive_dir: None,
+            archive_flush_interval_secs: 60,
+            archive_snapshot_interval_secs: 10,
+            subscribe_batch_size: 0,
+            subscribe_interval_ms: 250,
+            ws_stream_endpoint: StreamEndpoint::default(),
+            analytics_state_path: None,
+            analytics_persist_interval_secs: 30,
+            book_state_path: None,
+            book_persist_interval_secs: 30,
+            book_resume_max_age_secs: 60,
+            shutdown_timeout_secs: 10,
+            stale_book_secs: 30,
+            liveness_timeout_secs: 120,
+            readiness_max_book_age_secs: 60,
+            readiness_require_ipc: true,
+            trade_metrics_window_secs: 60,
+            symbol_trade_metrics_windows: HashMap::new(),
+            realized_vol_window_secs: 300,
+            symbol_realized_vol_windows: HashMap::new(),
+            realized_vol_sample_ms: 1000,
+            bbo_events_enabled: false,
+            anomaly_detection_enabled: false,
+            anomaly_flash_window_ms: 500,
+            anomaly_large_level_multiple: 10.0,
+            anomaly_update_rate_multiple: 5.0,
+            volume_profile_enabled: false,
+            volume_profile_window_secs: 300,
+            volume_profile_bucket_bps: Decimal::from(5),
+            volume_profile_publish_interval_secs: 10,
+            degradation_enabled: false,
+            degradation_latency_budget_us: 1000,
+            degradation_max_over_budget_pct: 10,
+            degradation_max_failures: 50,
+            degradation_window_secs: 5,
+            degradation_recover_windows: 3,
+            dead_man_enabled: false,
+            dead_man_no_update_secs: 10,
+            dead_man_max_resyncs: 3,
+            dead_man_resync_window_secs: 60,
+            dead_man_disconnected_secs: 5,
+            latency_window_secs: 5,
+            publish_throttle_ms: 0,
+            publish_throttle_ms_symbols: HashMap::new(),
+            publish_on_bbo_change: true,
+            publish_mode: PublishMode::default(),
+            wire_format: WireFormat::default(),
+            ipc_compression: Compression::default(),
+            full_refresh_interval_ms: 5000,
             depth_levels: 20,
+            symbol_depth_levels: HashMap::new(),
+            overflow_levels: 480,
+            crossed_book_policy: CrossedBookPolicy::default(),
+            book_representation: BookRepresentation::default(),
+            tick_sizes: HashMap::new(),
+            exchange_info_enabled: true,
+            exchange_info_refresh_secs: 3600,
+            clock_sync_interval_secs: 60,
+            rest_weight_limit: 5000,
+            rest_min_interval_ms: 100,
+            rest_max_retries: 5,
+            rest_retry_base_ms: 500,
+            binance_api_key: None,
+            binance_api_secret: None,
+            rest_recv_window_ms: 5000,
+            user_data_keepalive_secs: 1800,
+            snapshot_concurrency: 4,
+            depth_update_speed: DepthUpdateSpeed::default(),
+            symbol_update_speeds: HashMap::new(),
+            impact_sizes: Vec::new(),
+            symbol_impact_sizes: HashMap::new(),
+            depth_bands_bps: vec![Decimal::from(10), Decimal::from(50)],
+            imbalance_levels: 5,
+            symbol_imbalance_levels: HashMap::new(),
+            weighted_imbalance_levels: 10,
+            weighted_imbalance_decay: Decimal::new(9, 1),
+            metrics_disabled: Vec::new(),
+            symbol_metrics_disabled: HashMap::new(),
+            warmup_secs: 0,
+            warmup_updates: 0,
+            alignment_max_delay_ms: 0,
             reconnect_delay_ms: 1000,
             max_reconnect_attempts: 10,
             health_check_interval_secs: 30,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_symbol_update_speeds() {
+        let speeds: HashMap<String, DepthUpdateSpeed> =
+            parse_symbol_map("btcusdt=100ms, DOGEUSDT=1000ms,").unwrap();
+        assert_eq!(speeds.get("BTCUSDT"), Some(&DepthUpdateSpeed::Ms100));
+        assert_eq!(speeds.get("DOGEUSDT"), Some(&DepthUpdateSpeed::Ms1000));
+
+        // A bad entry fails the whole setting, naming the entry
+        let error = parse_symbol_map::<DepthUpdateSpeed>("BTCUSDT=100ms,BAD=5ms").unwrap_err();
+        assert!(error.contains("BAD=5ms"));
+        assert!(parse_symbol_map::<i32>("BTCUSDT=0,ETHUSDT=x")
+            .unwrap_err()
+            .contains("ETHUSDT=x"));
+        assert!(parse_symbol_map::<i32>("BTCUSDT").is_err());
+        assert!(parse_decimals("1,-2", ',').unwrap_err().contains("-2"));
+        assert!(parse_tick_sizes("BTCUSDT=0.01").is_err());
+    }
+
+    #[test]
+    fn test_depth_update_speed_for_falls_back_to_default() {
+        let mut config = Config {
+            depth_update_speed: DepthUpdateSpeed::Ms1000,
+            ..Config::default()
+        };
+        config
+            .symbol_update_speeds
+            .insert("BTCUSDT".to_string(), DepthUpdateSpeed::Ms100);
+
+        assert_eq!(
+            config.depth_update_speed_for("BTCUSDT"),
+            DepthUpdateSpeed::Ms100
+        );
+        assert_eq!(
+            config.depth_update_speed_for("ETHUSDT"),
+            DepthUpdateSpeed::Ms1000
+        );
+        assert_eq!(DepthUpdateSpeed::Ms1000.stream_suffix(), "@depth");
+    }
+
+    #[test]
+    fn test_metrics_config_for_symbol() {
+        let config = Config {
+            metrics_disabled: parse_list("slope", ',').unwrap(),
+            symbol_metrics_disabled: parse_symbol_lists("ethusdt=depth|weighted-imbalance")
+                .unwrap(),
+            symbol_imbalance_levels: parse_symbol_map("ETHUSDT=3").unwrap(),
+            ..Config::default()
+        };
+
+        let btc = config.metrics_config_for("BTCUSDT");
+        assert_eq!(btc.imbalance_levels, 5);
+        assert!(!btc.is_enabled(Metric::Slope));
+        assert!(btc.is_enabled(Metric::Depth));
+
+        let eth = config.metrics_config_for("ETHUSDT");
+        assert_eq!(eth.imbalance_levels, 3);
+        assert!(eth.is_enabled(Metric::Slope));
+        assert!(!eth.is_enabled(Metric::Depth));
+        assert!(!eth.is_enabled(Metric::WeightedImbalance));
+    }
+
+    fn from_str(contents: &str, format: ::config::FileFormat) -> anyhow::Result<Config> {
+        let config = Config::from_file(ConfigFile::parse(::config::File::from_str(
+            contents, format,
+        ))?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_config_file_with_symbol_sections() {
+        let config = from_str(
+            r#"
+            symbols = ["BTCUSDT", "ETHUSDT"]
+            depth_levels = 25
+            publish_mode = "delta"
+            depth_bands_bps = [5, 25]
+
+            [symbol.ETHUSDT]
+            depth_levels = 50
+            depth_update_speed = "1000ms"
+            impact_reference_sizes = [1, 10]
+            metrics_disabled = ["slope"]
+            realized_vol_window_secs = 0
+
+            [symbol.BTCUSDT]
+            publish_throttle_ms = 50
+            trade_metrics_window_secs = 10
+            "#,
+            ::config::FileFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(config.symbols, ["BTCUSDT", "ETHUSDT"]);
+        assert_eq!(config.publish_mode, PublishMode::Delta);
+        assert_eq!(
+            config.depth_bands_bps,
+            [Decimal::from(5), Decimal::from(25)]
+        );
+        assert_eq!(config.depth_levels_for("BTCUSDT"), 25);
+        assert_eq!(config.depth_levels_for("ETHUSDT"), 50);
+        assert_eq!(
+            config.depth_update_speed_for("ETHUSDT"),
+            DepthUpdateSpeed::Ms1000
+        );
+        assert_eq!(config.impact_sizes_for("ETHUSDT").len(), 2);
+        assert!(!config
+            .metrics_config_for("ETHUSDT")
+            .is_enabled(Metric::Slope));
+        assert_eq!(config.publish_throttle_ms_for("BTCUSDT"), 50);
+        assert_eq!(config.publish_throttle_ms_for("ETHUSDT"), 0);
+        assert_eq!(config.trade_metrics_window_secs_for("BTCUSDT"), 10);
+        assert_eq!(config.trade_metrics_window_secs_for("ETHUSDT"), 60);
+        assert_eq!(config.realized_vol_window_secs_for("BTCUSDT"), 300);
+        assert_eq!(config.realized_vol_window_secs_for("ETHUSDT"), 0);
+
+        let yaml = from_str(
+            "symbols: [BTCUSDT]\nsymbol:\n  BTCUSDT:\n    imbalance_levels: 3\n",
+            ::config::FileFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(yaml.metrics_config_for("BTCUSDT").imbalance_levels, 3);
+    }
+
+    #[test]
+    fn test_config_file_errors() {
+        let toml = ::config::FileFormat::Toml;
+        let error = |contents: &str| from_str(contents, toml).unwrap_err().to_string();
+
+        assert!(error("depht_levels = 10").contains("depht_levels"));
+        assert!(error("depth_levels = \"many\"").contains("DEPTH_LEVELS"));
+        assert!(error("depth_levels = 0").contains("DEPTH_LEVELS"));
+        assert!(error("publish_mode = \"sometimes\"").contains("PUBLISH_MODE"));
+        assert!(error("clickhouse_database = \"db; DROP\"").contains("CLICKHOUSE_DATABASE"));
+        let partitions = error("kafka_symbol_partitions = \"BTCUSDT=0,ETHUSDT=x\"");
+        assert!(partitions.contains("KAFKA_SYMBOL_PARTITIONS") && partitions.contains("ETHUSDT=x"));
+        assert!(
+            error("symbols = [\"BTCUSDT\"]\n[symbol.ETHUSDT]\ndepth_levels = 5")
+                .contains("ETHUSDT")
+        );
+        assert!(from_str("[symbol.BTCUSDT]\ndepth = 5", toml).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+//! Dead-man's switch for strategies
+//!
+//! With `DEADMAN_ENABLED` the feed checks itself every second and declares
+//! itself degraded when it can no longer vouch for its books:
+//!
+//! - no book has updated for `DEADMAN_NO_UPDATE_SECS`
+//! - a book resynced more than `DEADMAN_MAX_RESYNCS` times within
+//!   `DEADMAN_RESYNC_WINDOW_SECS`
+//! - no IPC consumer has been connected for `DEADMAN_DISCONNECTED_SECS`
+//!
+//! While degraded, a `FeedDegraded` status listing the reasons is published
+//! every check, so a consumer connecting mid-outage learns of it at once,
+//! and `feed_degraded` reads 1 for alerting; `FeedRecovered` follows once
+//! every condition clears. Strategies flatten on the first and may resume
+//! on the second.
+
+use prometheus::{IntGauge, IntGaugeVec, Opts};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::pipeline;
+use crate::publisher::StatusEvent;
+use crate::AppState;
+
+/// How often the switch checks the feed
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static DEGRADED: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "feed_degraded",
+        "1 while the feed is degraded and strategies should not trade on it",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static REASONS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "feed_degraded_reason",
+            "1 for each condition currently degrading the feed",
+        ),
+        &["reason"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+/// Why the feed is degraded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DegradedReason {
+    /// No book has updated for this long
+    NoUpdates { silent_ms: u64 },
+    /// A book resynced this many times within the window
+    Resyncs { symbol: String, count: u32 },
+    /// No IPC consumer has been connected for this long
+    PublisherDisconnected { disconnected_ms: u64 },
+}
+
+impl DegradedReason {
+    /// Label of the reason on `feed_degraded_reason`
+    pub fn label(&self) -> &'static str {
+        match self {
+            DegradedReason::NoUpdates { .. } => "no_updates",
+            DegradedReason::Resyncs { .. } => "resyncs",
+            DegradedReason::PublisherDisconnected { .. } => "publisher_disconnected",
+        }
+    }
+}
+
+const LABELS: [&str; 3] = ["no_updates", "resyncs", "publisher_disconnected"];
+
+/// When the feed counts as degraded; a zero threshold turns its check off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadManPolicy {
+    pub enabled: bool,
+    /// Silence of every book that degrades the feed
+    pub no_updates: Duration,
+    /// Resyncs of one book within `resync_window` tolerated
+    pub max_resyncs: u32,
+    pub resync_window: Duration,
+    /// Time without an IPC consumer that degrades the feed
+    pub disconnected: Duration,
+}
+
+impl Default for DeadManPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            no_updates: Duration::from_secs(10),
+            max_resyncs: 3,
+            resync_window: Duration::from_secs(60),
+            disconnected: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Watch {
+    /// Newest book update seen, or when checking started (milliseconds)
+    last_progress_ms: Option<u64>,
+    disconnected_since_ms: Option<u64>,
+    /// Resync times of each book within the window (milliseconds)
+    resyncs: HashMap<String, VecDeque<u64>>,
+}
+
+/// Decides whether the feed is degraded
+#[derive(Debug, Default)]
+pub struct DeadManSwitch {
+    policy: DeadManPolicy,
+    watch: Mutex<Watch>,
+}
+
+impl DeadManSwitch {
+    pub fn new(policy: DeadManPolicy) -> Self {
+        Self {
+            policy,
+            watch: Mutex::default(),
+        }
+    }
+
+    /// Record a book (re)initialized from a snapshot at `now_ms`
+    pub fn record_resync(&self, symbol: &str, now_ms: u64) {
+        if !self.policy.enabled || self.policy.max_resyncs == 0 {
+            return;
+        }
+        let mut watch = self.watch.lock().unwrap();
+        watch
+            .resyncs
+            .entry(symbol.to_string())
+            .or_default()
+            .push_back(now_ms);
+    }
+
+    /// Reasons the feed is degraded at `now_ms`, given its newest book
+    /// update and whether an IPC consumer is connected; empty when healthy
+    pub fn check(
+        &self,
+        newest_update_ms: Option<u64>,
+        connected: bool,
+        now_ms: u64,
+    ) -> Vec<DegradedReason> {
+        let policy = &self.policy;
+        let mut watch = self.watch.lock().unwrap();
+        let mut reasons = Vec::new();
+
+        let progress = watch
+            .last_progress_ms
+            .unwrap_or(now_ms)
+            .max(newest_update_ms.unwrap_or(0));
+        watch.last_progress_ms = Some(progress);
+        let silent_ms = now_ms.saturating_sub(progress);
+        if !policy.no_updates.is_zero() && silent_ms >= policy.no_updates.as_millis() as u64 {
+            reasons.push(DegradedReason::NoUpdates { silent_ms });
+        }
+
+        let window_ms = policy.resync_window.as_millis() as u64;
+        watch.resyncs.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|&at| now_ms.saturating_sub(at) > window_ms)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        if policy.max_resyncs > 0 {
+            let mut resyncing: Vec<_> = watch
+                .resyncs
+                .iter()
+                .filter(|(_, times)| times.len() > policy.max_resyncs as usize)
+                .map(|(symbol, times)| (symbol.clone(), times.len() as u32))
+                .collect();
+            resyncing.sort_unstable();
+            reasons.extend(
+                resyncing
+                    .into_iter()
+                    .map(|(symbol, count)| DegradedReason::Resyncs { symbol, count }),
+            );
+        }
+
+        if connected {
+            watch.disconnected_since_ms = None;
+        } else {
+            let since = *watch.disconnected_since_ms.get_or_insert(now_ms);
+            let disconnected_ms = now_ms.saturating_sub(since);
+            if !policy.disconnected.is_zero()
+                && disconnected_ms >= policy.disconnected.as_millis() as u64
+            {
+                reasons.push(DegradedReason::PublisherDisconnected { disconnected_ms });
+            }
+        }
+        reasons
+    }
+}
+
+/// Check the feed every second, publishing `FeedDegraded` while it is
+/// degraded and `FeedRecovered` once it no longer is
+pub async fn run(state: Arc<AppState>) {
+    LazyLock::force(&DEGRADED);
+    for label in LABELS {
+        REASONS.with_label_values(&[label]).set(0);
+    }
+    let mut degraded = false;
+    loop {
+        state.time.sleep(CHECK_INTERVAL).await;
+        let now_ms = state.time.now_millis();
+        let newest_update_ms = state
+            .books
+            .update_times()
+            .into_iter()
+            .map(|(_, updated_at)| updated_at)
+            .max();
+        let reasons =
+            state
+                .dead_man
+                .check(newest_update_ms, state.publisher.is_connected(), now_ms);
+
+        DEGRADED.set(!reasons.is_empty() as i64);
+        for label in LABELS {
+            let active = reasons.iter().any(|reason| reason.label() == label);
+            REASONS.with_label_values(&[label]).set(active as i64);
+        }
+
+        if !reasons.is_empty() {
+            if !degraded {
+                error!(reasons = ?reasons, "Feed degraded");
+            }
+            let status = StatusEvent::FeedDegraded {
+                reasons,
+                timestamp: now_ms,
+            };
+            pipeline::publish_status(&state, status).await;
+            degraded = true;
+        } else if degraded {
+            info!("Feed recovered");
+            let status = StatusEvent::FeedRecovered { timestamp: now_ms };
+            pipeline::publish_status(&state, status).await;
+            degraded = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degrades_on_silence_resyncs_and_disconnection() {
+        let switch = DeadManSwitch::new(DeadManPolicy {
+            enabled: true,
+            max_resyncs: 2,
+            ..Default::default()
+        });
+        let start = 1_700_000_000_000;
+
+        // Updates keep flowing to a connected consumer
+        assert!(switch.check(Some(start), true, start).is_empty());
+        assert!(switch
+            .check(Some(start + 9_000), true, start + 10_000)
+            .is_empty());
+
+        // Every book goes quiet
+        assert_eq!(
+            switch.check(Some(start + 9_000), true, start + 19_000),
+            [DegradedReason::NoUpdates { silent_ms: 10_000 }]
+        );
+        assert!(switch
+            .check(Some(start + 19_500), true, start + 20_000)
+            .is_empty());
+
+        // A third resync within the window, then the consumer drops
+        for at in [20_000, 40_000, 60_000] {
+            switch.record_resync("BTCUSDT", start + at);
+        }
+        switch.record_resync("ETHUSDT", start + 60_000);
+        assert!(switch
+            .check(Some(start + 60_000), false, start + 60_000)
+            .contains(&DegradedReason::Resyncs {
+                symbol: "BTCUSDT".to_string(),
+                count: 3
+            }));
+        assert_eq!(
+            switch.check(Some(start + 84_000), false, start + 85_000),
+            [DegradedReason::PublisherDisconnected {
+                disconnected_ms: 25_000
+            }]
+        );
+
+        // The first resync left the window; the consumer is back
+        assert!(switch
+            .check(Some(start + 85_000), true, start + 85_000)
+            .is_empty());
+    }
+}
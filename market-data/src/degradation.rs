@@ -0,0 +1,297 @@
+//! Graceful degradation under overload
+//!
+//! The book + IPC publish path is the product; everything else is shed
+//! before it is allowed to slow that path down. Subsystems are grouped into
+//! tiers that are switched off in order:
+//!
+//! 1. `analytics_off`: trade analytics and the latency heat map
+//! 2. `aux_sinks_off`: also the live feed (`/ws`, gRPC) and the Kafka,
+//!    NATS and Redis backends
+//!
+//! Each evaluation window the controller checks the share of depth updates
+//! whose release → publish latency exceeded the budget (measured from
+//! when alignment hands the update to its book, so updates held for a
+//! snapshot don't count as overload), and the number of
+//! auxiliary sink failures. An unhealthy window sheds one more tier; a run
+//! of healthy windows restores one. Tier changes are logged as alerts and
+//! reported on `/health`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// Degradation tier, in shedding order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    Normal = 0,
+    AnalyticsOff = 1,
+    AuxSinksOff = 2,
+}
+
+impl Tier {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Tier::Normal,
+            1 => Tier::AnalyticsOff,
+            _ => Tier::AuxSinksOff,
+        }
+    }
+
+    fn shed(self) -> Option<Self> {
+        match self {
+            Tier::Normal => Some(Tier::AnalyticsOff),
+            Tier::AnalyticsOff => Some(Tier::AuxSinksOff),
+            Tier::AuxSinksOff => None,
+        }
+    }
+
+    fn restore(self) -> Option<Self> {
+        match self {
+            Tier::Normal => None,
+            Tier::AnalyticsOff => Some(Tier::Normal),
+            Tier::AuxSinksOff => Some(Tier::AnalyticsOff),
+        }
+    }
+}
+
+/// When to shed and restore tiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DegradationPolicy {
+    /// Automatic shedding; when off the tier stays `Normal`
+    pub enabled: bool,
+    /// Release → publish latency budget per depth update
+    pub latency_budget: Duration,
+    /// Share of updates over budget (percent) that makes a window unhealthy
+    pub max_over_budget_pct: u64,
+    /// Auxiliary sink failures that make a window unhealthy
+    pub max_failures: u64,
+    /// Evaluation window
+    pub window: Duration,
+    /// Healthy windows required before restoring a tier
+    pub recover_windows: u32,
+}
+
+impl Default for DegradationPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency_budget: Duration::from_millis(1),
+            max_over_budget_pct: 10,
+            max_failures: 50,
+            window: Duration::from_secs(5),
+            recover_windows: 3,
+        }
+    }
+}
+
+/// Degradation state for `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct DegradationStatus {
+    pub enabled: bool,
+    pub tier: Tier,
+    /// Why the current tier was entered
+    pub reason: Option<String>,
+    /// When the current tier was entered (RFC 3339)
+    pub since: Option<String>,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    samples: u64,
+    over_budget: u64,
+    failures: u64,
+    healthy_windows: u32,
+    reason: Option<String>,
+    since: Option<String>,
+}
+
+/// Tracks load and sheds or restores tiers
+#[derive(Debug)]
+pub struct Degradation {
+    policy: DegradationPolicy,
+    tier: AtomicU8,
+    /// Times analytics have been shed
+    analytics_sheds: AtomicU64,
+    window: Mutex<Window>,
+}
+
+impl Degradation {
+    /// Create a controller starting at `Normal`
+    pub fn new(policy: DegradationPolicy) -> Self {
+        Self {
+            policy,
+            tier: AtomicU8::new(Tier::Normal as u8),
+            analytics_sheds: AtomicU64::new(0),
+            window: Mutex::new(Window {
+                started: Instant::now(),
+                samples: 0,
+                over_budget: 0,
+                failures: 0,
+                healthy_windows: 0,
+                reason: None,
+                since: None,
+            }),
+        }
+    }
+
+    /// Current tier
+    pub fn tier(&self) -> Tier {
+        Tier::from_u8(self.tier.load(Ordering::Relaxed))
+    }
+
+    /// Whether trade analytics and latency tracking should run
+    pub fn analytics_enabled(&self) -> bool {
+        self.tier() < Tier::AnalyticsOff
+    }
+
+    /// Times analytics have been shed; rolling statistics that saw a
+    /// lower count missed the trades in between
+    pub fn analytics_sheds(&self) -> u64 {
+        self.analytics_sheds.load(Ordering::Relaxed)
+    }
+
+    /// Whether the live feed and external sinks should be fed
+    pub fn aux_sinks_enabled(&self) -> bool {
+        self.tier() < Tier::AuxSinksOff
+    }
+
+    /// Record the release → publish latency of one depth update
+    pub fn observe(&self, latency: Duration) {
+        self.observe_at(latency, Instant::now());
+    }
+
+    /// Record an auxiliary sink failure
+    pub fn record_failure(&self) {
+        if self.policy.enabled {
+            self.window.lock().unwrap().failures += 1;
+        }
+    }
+
+    /// Current state
+    pub fn status(&self) -> DegradationStatus {
+        let window = self.window.lock().unwrap();
+        DegradationStatus {
+            enabled: self.policy.enabled,
+            tier: self.tier(),
+            reason: window.reason.clone(),
+            since: window.since.clone(),
+        }
+    }
+
+    fn observe_at(&self, latency: Duration, now: Instant) {
+        if !self.policy.enabled {
+            return;
+        }
+
+        let mut window = self.window.lock().unwrap();
+        window.samples += 1;
+        if latency > self.policy.latency_budget {
+            window.over_budget += 1;
+        }
+
+        if now.duration_since(window.started) >= self.policy.window {
+            self.evaluate(&mut window);
+            window.started = now;
+        }
+    }
+
+    /// Close a window, shedding or restoring one tier
+    fn evaluate(&self, window: &mut Window) {
+        let over_budget_pct = window.over_budget * 100 / window.samples.max(1);
+        let unhealthy = if over_budget_pct > self.policy.max_over_budget_pct {
+            Some(format!(
+                "{}% of updates over the {:?} latency budget",
+                over_budget_pct, self.policy.latency_budget
+            ))
+        } else if window.failures > self.policy.max_failures {
+            Some(format!("{} auxiliary sink failures", window.failures))
+        } else {
+            None
+        };
+        window.samples = 0;
+        window.over_budget = 0;
+        window.failures = 0;
+
+        let current = self.tier();
+        let next = match unhealthy {
+            Some(reason) => {
+                window.healthy_windows = 0;
+                let next = current.shed();
+                if let Some(tier) = next {
+                    error!(tier = ?tier, reason = %reason, "Shedding degradation tier");
+                    window.reason = Some(reason);
+                }
+                next
+            }
+            None => {
+                window.healthy_windows += 1;
+                let next = (window.healthy_windows >= self.policy.recover_windows)
+                    .then(|| current.restore())
+                    .flatten();
+                if let Some(tier) = next {
+                    info!(tier = ?tier, "Restoring degradation tier");
+                    window.healthy_windows = 0;
+                    window.reason = (tier != Tier::Normal).then(|| "recovering".to_string());
+                }
+                next
+            }
+        };
+
+        if let Some(tier) = next {
+            if tier == Tier::AnalyticsOff && current == Tier::Normal {
+                self.analytics_sheds.fetch_add(1, Ordering::Relaxed);
+            }
+            self.tier.store(tier as u8, Ordering::Relaxed);
+            window.since = Some(chrono::Utc::now().to_rfc3339());
+        }
+    }
+}
+
+impl Default for Degradation {
+    fn default() -> Self {
+        Self::new(DegradationPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheds_under_load_and_recovers() {
+        let policy = DegradationPolicy {
+            enabled: true,
+            recover_windows: 2,
+            ..Default::default()
+        };
+        let degradation = Degradation::new(policy);
+        let start = Instant::now();
+        let slow = Duration::from_millis(5);
+        let fast = Duration::from_micros(100);
+
+        // Two overloaded windows shed both tiers
+        for i in 1..=2 {
+            degradation.observe_at(slow, start + policy.window * i);
+        }
+        assert_eq!(degradation.tier(), Tier::AuxSinksOff);
+        assert!(!degradation.analytics_enabled());
+        assert!(!degradation.aux_sinks_enabled());
+
+        // Each run of two healthy windows restores one tier
+        for i in 3..=4 {
+            degradation.observe_at(fast, start + policy.window * i);
+        }
+        assert_eq!(degradation.tier(), Tier::AnalyticsOff);
+        assert!(degradation.aux_sinks_enabled());
+        for i in 5..=6 {
+            degradation.observe_at(fast, start + policy.window * i);
+        }
+        assert_eq!(degradation.tier(), Tier::Normal);
+        assert!(degradation.status().reason.is_none());
+        assert_eq!(degradation.analytics_sheds(), 1);
+    }
+}
@@ -0,0 +1,298 @@
+//! Endpoint failover for the WebSocket feed and the REST API
+//!
+//! Each of the two has a primary endpoint and optional fallbacks
+//! (`WS_FALLBACK_ENDPOINTS`, `REST_FALLBACK_ENDPOINTS`), e.g. Binance's
+//! `stream.binance.com:9443`, `:443` and `data-stream.binance.vision`.
+//! After `ENDPOINT_FAILOVER_AFTER` consecutive failures the next endpoint
+//! takes over. Every `ENDPOINT_PROBE_INTERVAL_SECS` each endpoint's TCP
+//! connect time is probed, and the fastest reachable one is preferred when
+//! it beats the current one by `PREFER_MARGIN_PCT`; the WebSocket feed
+//! moves to it on its next connect, REST requests right away.
+
+use prometheus::{IntCounterVec, IntGaugeVec, Opts};
+use reqwest::Url;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+use crate::error::{MarketDataError, Result};
+use crate::AppState;
+
+/// How much faster (percent) a probed endpoint must be to take over
+const PREFER_MARGIN_PCT: u128 = 20;
+
+/// Longest wait for a probe's TCP connect
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static SWITCHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "endpoint_switches_total",
+            "Switches to another endpoint, by kind (ws or rest) and reason (failover or latency)",
+        ),
+        &["kind", "reason"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static PROBE_LATENCY: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "endpoint_probe_latency_microseconds",
+            "TCP connect time of each endpoint's last probe (-1 when unreachable)",
+        ),
+        &["kind", "endpoint"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+#[derive(Debug)]
+struct Inner {
+    current: usize,
+    /// Consecutive failures of the current endpoint
+    failures: u32,
+    /// Connect time of each endpoint's last probe; `None` when unreachable
+    /// or not probed yet
+    latencies: Vec<Option<Duration>>,
+}
+
+/// Endpoints of one kind, in order of preference, with the one in use
+#[derive(Debug)]
+pub struct Endpoints {
+    /// `ws` or `rest`, for logs and metrics
+    kind: &'static str,
+    urls: Vec<String>,
+    /// Consecutive failures that move to the next endpoint
+    failover_after: u32,
+    inner: Mutex<Inner>,
+}
+
+impl Endpoints {
+    /// Endpoints of `kind` starting with `urls[0]`, failing over after
+    /// `failover_after` consecutive failures
+    ///
+    /// # Panics
+    ///
+    /// When `urls` is empty.
+    pub fn new(kind: &'static str, urls: Vec<String>, failover_after: u32) -> Self {
+        assert!(!urls.is_empty(), "at least one {} endpoint", kind);
+        let latencies = vec![None; urls.len()];
+        Self {
+            kind,
+            urls,
+            failover_after: failover_after.max(1),
+            inner: Mutex::new(Inner {
+                current: 0,
+                failures: 0,
+                latencies,
+            }),
+        }
+    }
+
+    /// A single endpoint, never switched
+    pub fn single(kind: &'static str, url: impl Into<String>) -> Self {
+        Self::new(kind, vec![url.into()], 1)
+    }
+
+    /// Endpoint to use now
+    pub fn current(&self) -> &str {
+        &self.urls[self.inner.lock().unwrap().current]
+    }
+
+    /// Every endpoint, in order of preference
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// The current endpoint worked
+    pub fn succeeded(&self) {
+        self.inner.lock().unwrap().failures = 0;
+    }
+
+    /// The current endpoint failed; after enough consecutive failures the
+    /// next one takes over. Returns the endpoint switched to, if any
+    pub fn failed(&self) -> Option<&str> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.failures += 1;
+        if inner.failures < self.failover_after || self.urls.len() < 2 {
+            return None;
+        }
+        let from = inner.current;
+        inner.current = (from + 1) % self.urls.len();
+        inner.failures = 0;
+        // Its last probe no longer speaks for it
+        inner.latencies[from] = None;
+        SWITCHES.with_label_values(&[self.kind, "failover"]).inc();
+        warn!(
+            kind = self.kind,
+            from = %self.urls[from],
+            to = %self.urls[inner.current],
+            "Endpoint failing, switching to the next one"
+        );
+        Some(&self.urls[inner.current])
+    }
+
+    /// Record probed connect times, in `urls` order, and switch to the
+    /// fastest reachable endpoint if it clearly beats the current one.
+    /// Returns the endpoint switched to, if any
+    pub fn probed(&self, latencies: Vec<Option<Duration>>) -> Option<&str> {
+        let mut inner = self.inner.lock().unwrap();
+        for (url, latency) in self.urls.iter().zip(&latencies) {
+            let micros = latency.map_or(-1, |l| l.as_micros() as i64);
+            PROBE_LATENCY
+                .with_label_values(&[self.kind, url])
+                .set(micros);
+        }
+        inner.latencies = latencies;
+
+        let (fastest, fastest_latency) = inner
+            .latencies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, latency)| Some((i, (*latency)?)))
+            .min_by_key(|(_, latency)| *latency)?;
+        if fastest == inner.current {
+            return None;
+        }
+        if let Some(current_latency) = inner.latencies[inner.current] {
+            let margin = current_latency.as_micros() * PREFER_MARGIN_PCT / 100;
+            if fastest_latency.as_micros() + margin > current_latency.as_micros() {
+                return None;
+            }
+        }
+        let from = inner.current;
+        inner.current = fastest;
+        inner.failures = 0;
+        SWITCHES.with_label_values(&[self.kind, "latency"]).inc();
+        info!(
+            kind = self.kind,
+            from = %self.urls[from],
+            to = %self.urls[fastest],
+            latency_us = fastest_latency.as_micros() as u64,
+            "Preferring the lowest-latency endpoint"
+        );
+        Some(&self.urls[fastest])
+    }
+}
+
+/// TCP connect time to the host and port of `url`
+pub async fn probe(url: &str) -> Result<Duration> {
+    let parsed = Url::parse(url)
+        .map_err(|e| MarketDataError::ConfigError(format!("Invalid endpoint {}: {}", url, e)))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| MarketDataError::ConfigError(format!("Endpoint {} has no host", url)))?;
+    let port = match (parsed.port(), parsed.scheme()) {
+        (Some(port), _) => port,
+        (None, "wss" | "https") => 443,
+        (None, _) => 80,
+    };
+
+    let started = Instant::now();
+    tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host, port)))
+        .await
+        .map_err(|_| MarketDataError::ConnectionTimeout)?
+        .map_err(|e| {
+            MarketDataError::WebSocketConnection(format!(
+                "Failed to reach {}:{}: {}",
+                host, port, e
+            ))
+        })?;
+    Ok(started.elapsed())
+}
+
+/// Probe every endpoint of `endpoints`, unreachable ones as `None`
+async fn probe_all(endpoints: &Endpoints) -> Vec<Option<Duration>> {
+    let mut latencies = Vec::with_capacity(endpoints.urls().len());
+    for url in endpoints.urls() {
+        match probe(url).await {
+            Ok(latency) => latencies.push(Some(latency)),
+            Err(e) => {
+                warn!(error = %e, endpoint = %url, "Endpoint probe failed");
+                latencies.push(None);
+            }
+        }
+    }
+    latencies
+}
+
+/// Probe the WebSocket and REST endpoints every `interval` and prefer the
+/// fastest of each
+pub async fn run_probes(state: Arc<AppState>, interval: Duration) {
+    LazyLock::force(&SWITCHES);
+    LazyLock::force(&PROBE_LATENCY);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for endpoints in [&state.ws_endpoints, state.rest.endpoints()] {
+            if endpoints.urls().len() > 1 {
+                let latencies = probe_all(endpoints).await;
+                endpoints.probed(latencies);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Endpoints {
+        Endpoints::new(
+            "ws",
+            vec![
+                "wss://stream.binance.com:9443/ws".to_string(),
+                "wss://stream.binance.com:443/ws".to_string(),
+                "wss://data-stream.binance.vision/ws".to_string(),
+            ],
+            2,
+        )
+    }
+
+    #[test]
+    fn test_failover_after_consecutive_failures() {
+        let endpoints = endpoints();
+        assert_eq!(endpoints.current(), "wss://stream.binance.com:9443/ws");
+
+        // A success in between resets the count
+        assert_eq!(endpoints.failed(), None);
+        endpoints.succeeded();
+        assert_eq!(endpoints.failed(), None);
+        assert_eq!(endpoints.failed(), Some("wss://stream.binance.com:443/ws"));
+
+        endpoints.failed();
+        endpoints.failed();
+        endpoints.failed();
+        assert_eq!(endpoints.failed(), Some("wss://stream.binance.com:9443/ws"));
+
+        let single = Endpoints::single("rest", "https://api.binance.com/api/v3");
+        assert_eq!(single.failed(), None);
+        assert_eq!(single.current(), "https://api.binance.com/api/v3");
+    }
+
+    #[test]
+    fn test_probes_prefer_clearly_faster_endpoint() {
+        let endpoints = endpoints();
+        let ms = |ms| Some(Duration::from_millis(ms));
+
+        // Not enough faster to be worth a switch
+        assert_eq!(endpoints.probed(vec![ms(10), ms(9), None]), None);
+
+        assert_eq!(
+            endpoints.probed(vec![ms(10), ms(12), ms(5)]),
+            Some("wss://data-stream.binance.vision/ws")
+        );
+
+        // An unreachable current endpoint gives way to any reachable one
+        assert_eq!(
+            endpoints.probed(vec![ms(10), None, None]),
+            Some("wss://stream.binance.com:9443/ws")
+        );
+        assert_eq!(endpoints.probed(vec![None, None, None]), None);
+    }
+}
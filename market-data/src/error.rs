@@ -1,5 +1,6 @@
 //! Error types for the market data handler
 
+use rust_decimal::Decimal;
 use thiserror::Error;
 
 /// Market data handler errors
@@ -29,6 +30,16 @@ pub enum MarketDataError {
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
+    #[error("Crossed book on {symbol}: best bid {best_bid} >= best ask {best_ask}")]
+    CrossedBook {
+        symbol: String,
+        best_bid: Decimal,
+        best_ask: Decimal,
+    },
+
+    #[error("Order book {symbol} no longer holds enough known levels to fill its depth")]
+    DepthWindowExhausted { symbol: String },
+
     #[error("Sequence number mismatch: expected {expected}, got {got}")]
     SequenceMismatch { expected: u64, got: u64 },
 
@@ -39,6 +50,7 @@ pub enum MarketDataError {
     MaxReconnectAttemptsExceeded,
 }
 
+#[cfg(feature = "runtime")]
 impl From<tokio_tungstenite::tungstenite::Error> for MarketDataError {
     fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
         MarketDataError::WebSocketConnection(err.to_string())
@@ -51,12 +63,27 @@ impl From<serde_json::Error> for MarketDataError {
     }
 }
 
+#[cfg(feature = "runtime")]
 impl From<reqwest::Error> for MarketDataError {
     fn from(err: reqwest::Error) -> Self {
         MarketDataError::RestApiError(err.to_string())
     }
 }
 
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for MarketDataError {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        MarketDataError::SerializationError(err.to_string())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for MarketDataError {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        MarketDataError::SerializationError(err.to_string())
+    }
+}
+
 impl From<std::io::Error> for MarketDataError {
     fn from(err: std::io::Error) -> Self {
         MarketDataError::IpcError(err.to_string())
@@ -0,0 +1,272 @@
+//! Normalized market data model
+//!
+//! `MarketEvent` is the single internal currency between exchange
+//! connectors, order books, analytics and sinks. Connectors translate wire
+//! messages (e.g. Binance's `ParsedMessage`) into these venue-tagged events
+//! at the edge; everything downstream is exchange-agnostic.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::orderbook::Side;
+
+/// Exchange that produced an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Venue {
+    #[default]
+    Binance,
+}
+
+impl Venue {
+    /// Lowercase venue tag
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Venue::Binance => "binance",
+        }
+    }
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Price level (price, quantity pair); zero quantity removes the level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Incremental order book update covering `first_update_id..=final_update_id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthDelta {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    pub first_update_id: u64,
+    pub final_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Full order book image as of `last_update_id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    pub venue: Venue,
+    pub symbol: String,
+    pub last_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Kind of change to a resting order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderUpdateKind {
+    /// New order resting on the book
+    Add,
+    /// Order re-priced or resized (partial fill, amend)
+    Modify,
+    /// Order filled or canceled
+    Delete,
+}
+
+/// Change to a single resting order, from per-order (L3) feeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    /// Feed sequence number; consecutive updates differ by one
+    pub sequence: u64,
+    pub kind: OrderUpdateKind,
+    pub order_id: String,
+    pub side: Side,
+    /// New price; ignored for deletes
+    pub price: Decimal,
+    /// New remaining quantity; ignored for deletes
+    pub quantity: Decimal,
+}
+
+/// Order resting on a per-order book
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestingOrder {
+    pub order_id: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Full per-order book image as of `sequence`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderSnapshot {
+    pub venue: Venue,
+    pub symbol: String,
+    pub sequence: u64,
+    pub orders: Vec<RestingOrder>,
+}
+
+/// Executed trade
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    pub trade_id: u64,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    /// Side of the aggressing (taker) order
+    pub taker_side: Side,
+    /// Match time (milliseconds)
+    pub trade_time: u64,
+}
+
+/// Best bid and offer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bbo {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds), when the venue provides one
+    pub event_time: Option<u64>,
+    pub update_id: u64,
+    pub bid_price: Decimal,
+    pub bid_quantity: Decimal,
+    pub ask_price: Decimal,
+    pub ask_quantity: Decimal,
+}
+
+/// Candlestick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Kline {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    /// Interval label, e.g. "1m"
+    pub interval: String,
+    pub open_time: u64,
+    pub close_time: u64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    /// Whether the candle is final
+    pub closed: bool,
+}
+
+/// Perpetual funding / mark price update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Funding {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    pub mark_price: Decimal,
+    pub funding_rate: Decimal,
+    pub next_funding_time: u64,
+}
+
+/// Forced liquidation order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Liquidation {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    /// Side of the liquidation order
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// Kind of feed status change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    Connected,
+    Disconnected,
+}
+
+/// Feed status change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub venue: Venue,
+    /// Affected symbol, or `None` for the whole connection
+    pub symbol: Option<String>,
+    pub kind: StatusKind,
+    /// Local time (milliseconds since epoch)
+    pub timestamp: u64,
+}
+
+/// Normalized market data event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MarketEvent {
+    DepthDelta(DepthDelta),
+    BookSnapshot(BookSnapshot),
+    OrderUpdate(OrderUpdate),
+    OrderSnapshot(OrderSnapshot),
+    Trade(Trade),
+    Bbo(Bbo),
+    Kline(Kline),
+    Funding(Funding),
+    Liquidation(Liquidation),
+    Status(StatusEvent),
+}
+
+impl MarketEvent {
+    /// Venue that produced the event
+    pub fn venue(&self) -> Venue {
+        match self {
+            MarketEvent::DepthDelta(e) => e.venue,
+            MarketEvent::BookSnapshot(e) => e.venue,
+            MarketEvent::OrderUpdate(e) => e.venue,
+            MarketEvent::OrderSnapshot(e) => e.venue,
+            MarketEvent::Trade(e) => e.venue,
+            MarketEvent::Bbo(e) => e.venue,
+            MarketEvent::Kline(e) => e.venue,
+            MarketEvent::Funding(e) => e.venue,
+            MarketEvent::Liquidation(e) => e.venue,
+            MarketEvent::Status(e) => e.venue,
+        }
+    }
+
+    /// Symbol the event refers to, if any
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            MarketEvent::DepthDelta(e) => Some(&e.symbol),
+            MarketEvent::BookSnapshot(e) => Some(&e.symbol),
+            MarketEvent::OrderUpdate(e) => Some(&e.symbol),
+            MarketEvent::OrderSnapshot(e) => Some(&e.symbol),
+            MarketEvent::Trade(e) => Some(&e.symbol),
+            MarketEvent::Bbo(e) => Some(&e.symbol),
+            MarketEvent::Kline(e) => Some(&e.symbol),
+            MarketEvent::Funding(e) => Some(&e.symbol),
+            MarketEvent::Liquidation(e) => Some(&e.symbol),
+            MarketEvent::Status(e) => e.symbol.as_deref(),
+        }
+    }
+
+    /// Exchange event time in milliseconds, if the event carries one
+    pub fn event_time(&self) -> Option<u64> {
+        match self {
+            MarketEvent::DepthDelta(e) => Some(e.event_time),
+            MarketEvent::OrderUpdate(e) => Some(e.event_time),
+            MarketEvent::Trade(e) => Some(e.event_time),
+            MarketEvent::Bbo(e) => e.event_time,
+            MarketEvent::Kline(e) => Some(e.event_time),
+            MarketEvent::Funding(e) => Some(e.event_time),
+            MarketEvent::Liquidation(e) => Some(e.event_time),
+            MarketEvent::BookSnapshot(_)
+            | MarketEvent::OrderSnapshot(_)
+            | MarketEvent::Status(_) => None,
+        }
+    }
+}
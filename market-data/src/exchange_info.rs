@@ -0,0 +1,177 @@
+//! Instrument metadata from Binance `exchangeInfo`
+//!
+//! Tick size, lot step size and minimum notional per symbol, fetched at
+//! startup and refreshed periodically. Books validate incoming prices
+//! against the tick size and carry the metadata in their published states.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+#[cfg(feature = "runtime")]
+use std::time::Duration;
+#[cfg(feature = "runtime")]
+use tracing::{info, warn};
+
+use crate::error::Result;
+#[cfg(feature = "runtime")]
+use crate::rest::RestClient;
+#[cfg(feature = "runtime")]
+use crate::AppState;
+
+/// Trading rules of one instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct InstrumentInfo {
+    /// Price increment
+    pub tick_size: Decimal,
+    /// Quantity increment
+    pub step_size: Decimal,
+    /// Smallest order quantity
+    pub min_qty: Decimal,
+    /// Smallest order value in the quote asset, if the symbol has one
+    pub min_notional: Option<Decimal>,
+}
+
+impl InstrumentInfo {
+    /// Whether `price` is a multiple of the tick size
+    pub fn on_tick(&self, price: Decimal) -> bool {
+        self.tick_size.is_zero() || (price % self.tick_size).is_zero()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeInfoResponse {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolInfo {
+    symbol: String,
+    filters: Vec<SymbolFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "filterType")]
+enum SymbolFilter {
+    #[serde(rename = "PRICE_FILTER")]
+    Price {
+        #[serde(rename = "tickSize")]
+        tick_size: Decimal,
+    },
+    #[serde(rename = "LOT_SIZE")]
+    LotSize {
+        #[serde(rename = "stepSize")]
+        step_size: Decimal,
+        #[serde(rename = "minQty")]
+        min_qty: Decimal,
+    },
+    #[serde(rename = "NOTIONAL", alias = "MIN_NOTIONAL")]
+    Notional {
+        #[serde(rename = "minNotional")]
+        min_notional: Decimal,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Instruments of an `exchangeInfo` response body, by symbol
+pub fn parse(body: &str) -> Result<HashMap<String, InstrumentInfo>> {
+    let response: ExchangeInfoResponse = serde_json::from_str(body)?;
+    Ok(response
+        .symbols
+        .into_iter()
+        .map(|symbol| {
+            let mut info = InstrumentInfo::default();
+            for filter in symbol.filters {
+                match filter {
+                    SymbolFilter::Price { tick_size } => info.tick_size = tick_size,
+                    SymbolFilter::LotSize { step_size, min_qty } => {
+                        info.step_size = step_size;
+                        info.min_qty = min_qty;
+                    }
+                    SymbolFilter::Notional { min_notional } => {
+                        info.min_notional = Some(min_notional)
+                    }
+                    SymbolFilter::Other => {}
+                }
+            }
+            (symbol.symbol, info)
+        })
+        .collect())
+}
+
+/// Request weight of `exchangeInfo`
+#[cfg(feature = "runtime")]
+const EXCHANGE_INFO_WEIGHT: u32 = 20;
+
+/// Fetch the instruments of `symbols` from the REST API
+#[cfg(feature = "runtime")]
+pub async fn fetch(
+    rest: &RestClient,
+    symbols: &[String],
+) -> Result<HashMap<String, InstrumentInfo>> {
+    let symbols = serde_json::to_string(symbols)?;
+    let body = rest
+        .get(
+            "exchangeInfo",
+            &[("symbols", &symbols)],
+            EXCHANGE_INFO_WEIGHT,
+        )
+        .await?;
+    parse(&body)
+}
+
+/// Refetch instrument metadata every `interval` and hand it to the books
+#[cfg(feature = "runtime")]
+pub async fn run_refresh(state: Arc<AppState>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match fetch(&state.rest, &state.config.symbols).await {
+            Ok(instruments) => {
+                for (symbol, info) in instruments {
+                    state.books.set_instrument(&symbol, info).await;
+                }
+                info!("Instrument metadata refreshed");
+            }
+            Err(e) => warn!(error = %e, "Failed to refresh instrument metadata"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_exchange_info() {
+        let body = r#"{
+            "timezone": "UTC",
+            "symbols": [{
+                "symbol": "BTCUSDT",
+                "status": "TRADING",
+                "filters": [
+                    {"filterType": "PRICE_FILTER", "minPrice": "0.01000000",
+                     "maxPrice": "1000000.00000000", "tickSize": "0.01000000"},
+                    {"filterType": "LOT_SIZE", "minQty": "0.00001000",
+                     "maxQty": "9000.00000000", "stepSize": "0.00001000"},
+                    {"filterType": "ICEBERG_PARTS", "limit": 10},
+                    {"filterType": "NOTIONAL", "minNotional": "5.00000000",
+                     "applyMinToMarket": true}
+                ]
+            }]
+        }"#;
+        let instruments = parse(body).unwrap();
+        let btc = instruments["BTCUSDT"];
+        assert_eq!(btc.tick_size, dec!(0.01));
+        assert_eq!(btc.step_size, dec!(0.00001));
+        assert_eq!(btc.min_qty, dec!(0.00001));
+        assert_eq!(btc.min_notional, Some(dec!(5)));
+
+        assert!(btc.on_tick(dec!(50000.12)));
+        assert!(!btc.on_tick(dec!(50000.125)));
+    }
+}
@@ -0,0 +1,153 @@
+//! gRPC streaming server
+//!
+//! Serves the `MarketData` service from `proto/market_data.proto` so remote
+//! consumers in any language can subscribe to order books and trades,
+//! filtered by symbol, instead of tailing the Unix socket. Streams are fed
+//! from the publisher's `LiveFeed`; order book streams open with the
+//! latest state of every matching book. Enabled with the `grpc` feature.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+use crate::proto;
+use crate::publisher::LiveFeed;
+
+use proto::market_data_server::{MarketData, MarketDataServer};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = std::result::Result<T, Status>> + Send>>;
+
+/// Serve the `MarketData` service on `addr` until the server fails
+pub async fn serve(addr: SocketAddr, feed: LiveFeed) -> Result<()> {
+    info!(addr = %addr, "gRPC server listening");
+
+    tonic::transport::Server::builder()
+        .add_service(MarketDataServer::new(MarketDataService { feed }))
+        .serve(addr)
+        .await
+        .map_err(|e| MarketDataError::IpcError(format!("gRPC server: {}", e)))
+}
+
+struct MarketDataService {
+    feed: LiveFeed,
+}
+
+#[tonic::async_trait]
+impl MarketData for MarketDataService {
+    type SubscribeOrderBookStream = ResponseStream<proto::OrderBook>;
+    type SubscribeTradesStream = ResponseStream<proto::Trade>;
+
+    async fn subscribe_order_book(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> std::result::Result<Response<Self::SubscribeOrderBookStream>, Status> {
+        let filter = SymbolFilter::new(request.into_inner().symbols);
+        let (snapshot, receiver) = self.feed.subscribe_books_with_snapshot();
+        // Start from the current books rather than each one's next update
+        let current: Vec<_> = snapshot
+            .iter()
+            .filter(|state| filter.matches(&state.symbol))
+            .map(|state| proto::OrderBook::from(state.as_ref()))
+            .collect();
+        let stream = subscribe(receiver, move |state: &OrderBookState| {
+            filter
+                .matches(&state.symbol)
+                .then(|| proto::OrderBook::from(state))
+        });
+        Ok(Response::new(Box::pin(
+            tokio_stream::iter(current).map(Ok).chain(stream),
+        )))
+    }
+
+    async fn subscribe_trades(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> std::result::Result<Response<Self::SubscribeTradesStream>, Status> {
+        let filter = SymbolFilter::new(request.into_inner().symbols);
+        let stream = subscribe(self.feed.subscribe_trades(), move |trade: &Trade| {
+            filter
+                .matches(&trade.symbol)
+                .then(|| proto::Trade::from(trade))
+        });
+        Ok(Response::new(stream))
+    }
+}
+
+/// Subscribe to a broadcast channel, converting and filtering each message
+fn subscribe<T, U, F>(receiver: broadcast::Receiver<Arc<T>>, convert: F) -> ResponseStream<U>
+where
+    T: Send + Sync + 'static,
+    U: Send + 'static,
+    F: Fn(&T) -> Option<U> + Send + 'static,
+{
+    let stream = BroadcastStream::new(receiver).filter_map(move |item| match item {
+        Ok(msg) => convert(&msg).map(Ok),
+        Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+            warn!(skipped, "gRPC subscriber lagging, messages skipped");
+            None
+        }
+    });
+    Box::pin(stream)
+}
+
+/// Symbol subscription filter; empty matches everything
+struct SymbolFilter(HashSet<String>);
+
+impl SymbolFilter {
+    fn new(symbols: Vec<String>) -> Self {
+        Self(symbols.into_iter().map(|s| s.to_uppercase()).collect())
+    }
+
+    fn matches(&self, symbol: &str) -> bool {
+        self.0.is_empty() || self.0.contains(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use crate::orderbook::Side;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_trade_stream_filters_by_symbol() {
+        let feed = LiveFeed::new(16);
+        let mut stream = subscribe(feed.subscribe_trades(), {
+            let filter = SymbolFilter::new(vec!["btcusdt".to_string()]);
+            move |trade: &Trade| {
+                filter
+                    .matches(&trade.symbol)
+                    .then(|| proto::Trade::from(trade))
+            }
+        });
+
+        for symbol in ["ETHUSDT", "BTCUSDT"] {
+            feed.publish_trade(&Trade {
+                venue: Venue::Binance,
+                symbol: symbol.to_string(),
+                event_time: 1,
+                trade_id: 1,
+                price: dec!(50000.10),
+                quantity: dec!(0.5),
+                taker_side: Side::Ask,
+                trade_time: 1,
+            });
+        }
+
+        let trade = stream.next().await.unwrap().unwrap();
+        assert_eq!(trade.symbol, "BTCUSDT");
+        assert_eq!(trade.price, "50000.10");
+        assert_eq!(trade.taker_side(), proto::Side::Ask);
+    }
+}
@@ -0,0 +1,121 @@
+//! Liveness and readiness checks
+//!
+//! `/livez` reports whether the process is still making progress: the
+//! WebSocket manager beats a heartbeat on every pass of its loop (each
+//! message, keepalive timeout or reconnect), so a loop wedged on a lock or
+//! a stuck await stops it. `/readyz` reports whether consumers are getting
+//! current data: the WebSocket is connected, every configured book is
+//! initialized and recently updated, and the IPC consumer is connected.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::AppState;
+
+/// Time of the WebSocket manager's last loop pass
+#[derive(Debug, Default)]
+pub struct Heartbeat {
+    /// Milliseconds since the Unix epoch; 0 before the first beat
+    last_ms: AtomicU64,
+}
+
+impl Heartbeat {
+    /// Record a pass at `now_ms`
+    pub fn beat(&self, now_ms: u64) {
+        self.last_ms.store(now_ms, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat; `None` before the first
+    pub fn age_ms(&self, now_ms: u64) -> Option<u64> {
+        match self.last_ms.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(now_ms.saturating_sub(last)),
+        }
+    }
+}
+
+/// Result of the liveness check
+#[derive(Debug, Clone, Serialize)]
+pub struct Liveness {
+    pub alive: bool,
+    /// Time since the WebSocket manager's last loop pass
+    pub heartbeat_age_ms: Option<u64>,
+}
+
+/// Result of the readiness check
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub websocket_connected: bool,
+    /// Configured symbols without an initialized book
+    pub uninitialized: Vec<String>,
+    /// Initialized books not updated within the staleness limit
+    pub stale: Vec<String>,
+    /// Whether an IPC consumer is connected (`None` when not required)
+    pub publisher_connected: Option<bool>,
+}
+
+/// Liveness as of `now_ms`; alive until the first beat, which startup
+/// work (instrument metadata, restores) may delay
+pub fn liveness(state: &AppState, now_ms: u64) -> Liveness {
+    let heartbeat_age_ms = state.heartbeat.age_ms(now_ms);
+    let timeout_ms = state.config.liveness_timeout_secs * 1000;
+    Liveness {
+        alive: heartbeat_age_ms.is_none_or(|age| age <= timeout_ms),
+        heartbeat_age_ms,
+    }
+}
+
+/// Readiness as of `now_ms` (exchange time of the books' updates is
+/// compared against it)
+pub fn readiness(state: &AppState, now_ms: u64) -> Readiness {
+    let config = &state.config;
+    let websocket_connected = state.subscriptions.status().connected;
+
+    let books = &state.books;
+    let uninitialized: Vec<String> = config
+        .symbols
+        .iter()
+        .filter(|symbol| !books.is_initialized(symbol))
+        .cloned()
+        .collect();
+    let max_age_ms = config.readiness_max_book_age_secs * 1000;
+    let mut stale: Vec<String> = books
+        .update_times()
+        .into_iter()
+        .filter(|(_, updated_at)| max_age_ms > 0 && now_ms.saturating_sub(*updated_at) > max_age_ms)
+        .map(|(symbol, _)| symbol)
+        .collect();
+    stale.sort();
+
+    let publisher_connected = config
+        .readiness_require_ipc
+        .then(|| state.publisher.is_connected());
+
+    Readiness {
+        ready: websocket_connected
+            && uninitialized.is_empty()
+            && stale.is_empty()
+            && publisher_connected != Some(false),
+        websocket_connected,
+        uninitialized,
+        stale,
+        publisher_connected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_age() {
+        let heartbeat = Heartbeat::default();
+        assert_eq!(heartbeat.age_ms(5_000), None);
+
+        heartbeat.beat(5_000);
+        assert_eq!(heartbeat.age_ms(7_500), Some(2_500));
+        // A clock step backwards reads as fresh
+        assert_eq!(heartbeat.age_ms(4_000), Some(0));
+    }
+}
@@ -0,0 +1,254 @@
+//! Per-symbol stage latency heat map
+//!
+//! The hot path records how long each message spent in each pipeline stage
+//! (exchange → receive → parse → apply → publish). Samples are aggregated per symbol
+//! over a fixed window; the last complete window is served as a
+//! symbol × stage matrix at `/debug/latency`, so a latency regression can
+//! be pinned to a symbol or stage at a glance.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Samples kept per symbol and stage within a window; older ones are
+/// overwritten but still counted
+const MAX_SAMPLES: usize = 4096;
+
+/// Pipeline stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Exchange event time → socket receive (network, plus whatever clock
+    /// offset the estimate missed)
+    Exchange,
+    /// Socket receive → parsed event
+    Parse,
+    /// Parsed event → applied to the book (includes alignment hold and
+    /// lock wait)
+    Apply,
+    /// Applied → handed to the publisher
+    Publish,
+    /// Socket receive → handed to the publisher
+    Total,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Exchange,
+        Stage::Parse,
+        Stage::Apply,
+        Stage::Publish,
+        Stage::Total,
+    ];
+
+    /// Lower-case name, as serialized
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Exchange => "exchange",
+            Stage::Parse => "parse",
+            Stage::Apply => "apply",
+            Stage::Publish => "publish",
+            Stage::Total => "total",
+        }
+    }
+}
+
+/// Wall-clock timestamps of one message through the pipeline (microseconds
+/// since epoch)
+#[derive(Debug, Clone, Copy)]
+pub struct StageTimes {
+    /// Exchange event time, moved onto the local clock
+    pub event_time_us: u64,
+    pub received_at_us: u64,
+    pub parsed_at_us: u64,
+    pub applied_at_us: u64,
+    pub published_at_us: u64,
+}
+
+impl StageTimes {
+    /// Duration of each stage, in `Stage::ALL` order; a local clock behind
+    /// the exchange's reads as zero exchange latency
+    pub fn durations(&self) -> [u64; 5] {
+        [
+            self.received_at_us.saturating_sub(self.event_time_us),
+            self.parsed_at_us.saturating_sub(self.received_at_us),
+            self.applied_at_us.saturating_sub(self.parsed_at_us),
+            self.published_at_us.saturating_sub(self.applied_at_us),
+            self.published_at_us.saturating_sub(self.received_at_us),
+        ]
+    }
+}
+
+/// Latency summary for one symbol and stage
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StageStats {
+    pub count: u64,
+    pub mean_us: u64,
+    pub p50_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+/// Symbol × stage latency matrix for one window
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyMatrix {
+    /// Window length in seconds
+    pub window_secs: u64,
+    /// When the window closed (RFC 3339); unset before the first window
+    pub generated_at: Option<String>,
+    pub symbols: BTreeMap<String, BTreeMap<Stage, StageStats>>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    values: Vec<u64>,
+    count: u64,
+    sum: u64,
+    max: u64,
+}
+
+impl Samples {
+    fn record(&mut self, value: u64) {
+        if self.values.len() < MAX_SAMPLES {
+            self.values.push(value);
+        } else {
+            self.values[self.count as usize % MAX_SAMPLES] = value;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    fn stats(&mut self) -> StageStats {
+        self.values.sort_unstable();
+        let percentile = |p: usize| self.values[(self.values.len() - 1) * p / 100];
+        StageStats {
+            count: self.count,
+            mean_us: self.sum / self.count.max(1),
+            p50_us: percentile(50),
+            p99_us: percentile(99),
+            max_us: self.max,
+        }
+    }
+}
+
+struct Window {
+    started: Instant,
+    samples: HashMap<String, [Samples; 5]>,
+    last: LatencyMatrix,
+}
+
+/// Aggregates stage latencies into a rolling per-symbol matrix
+pub struct LatencyTracker {
+    window: Duration,
+    inner: Mutex<Window>,
+}
+
+impl LatencyTracker {
+    /// Create a tracker publishing a new matrix every `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            inner: Mutex::new(Window {
+                started: Instant::now(),
+                samples: HashMap::new(),
+                last: LatencyMatrix {
+                    window_secs: window.as_secs(),
+                    ..Default::default()
+                },
+            }),
+        }
+    }
+
+    /// Record one message's pass through the pipeline
+    pub fn record(&self, symbol: &str, times: &StageTimes) {
+        self.record_at(symbol, times, Instant::now());
+    }
+
+    /// Matrix for the last complete window
+    pub fn matrix(&self) -> LatencyMatrix {
+        let mut inner = self.inner.lock().unwrap();
+        self.rotate_if_due(&mut inner, Instant::now());
+        inner.last.clone()
+    }
+
+    fn record_at(&self, symbol: &str, times: &StageTimes, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        self.rotate_if_due(&mut inner, now);
+
+        if !inner.samples.contains_key(symbol) {
+            inner.samples.insert(symbol.to_string(), Default::default());
+        }
+        let samples = inner.samples.get_mut(symbol).unwrap();
+        for (stage, value) in samples.iter_mut().zip(times.durations()) {
+            stage.record(value);
+        }
+    }
+
+    fn rotate_if_due(&self, inner: &mut Window, now: Instant) {
+        if now.duration_since(inner.started) < self.window {
+            return;
+        }
+
+        let symbols = inner
+            .samples
+            .drain()
+            .map(|(symbol, mut stages)| {
+                let stats = Stage::ALL
+                    .into_iter()
+                    .zip(stages.iter_mut().map(Samples::stats))
+                    .collect();
+                (symbol, stats)
+            })
+            .collect();
+
+        inner.last = LatencyMatrix {
+            window_secs: self.window.as_secs(),
+            generated_at: Some(chrono::Utc::now().to_rfc3339()),
+            symbols,
+        };
+        inner.started = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(parse: u64, apply: u64, publish: u64) -> StageTimes {
+        StageTimes {
+            event_time_us: 800,
+            received_at_us: 1_000,
+            parsed_at_us: 1_000 + parse,
+            applied_at_us: 1_000 + parse + apply,
+            published_at_us: 1_000 + parse + apply + publish,
+        }
+    }
+
+    #[test]
+    fn test_matrix_published_per_window() {
+        let tracker = LatencyTracker::new(Duration::from_secs(5));
+        let start = Instant::now();
+
+        for i in 1..=100 {
+            tracker.record_at("BTCUSDT", &times(i, 10, 1), start);
+        }
+        tracker.record_at("ETHUSDT", &times(5, 500, 1), start);
+
+        // Nothing is visible until the window closes
+        assert!(tracker.matrix().symbols.is_empty());
+
+        tracker.record_at("BTCUSDT", &times(1, 1, 1), start + Duration::from_secs(5));
+        let matrix = tracker.matrix();
+
+        let btc = &matrix.symbols["BTCUSDT"];
+        assert_eq!(btc[&Stage::Parse].count, 100);
+        assert_eq!(btc[&Stage::Parse].p50_us, 50);
+        assert_eq!(btc[&Stage::Parse].p99_us, 99);
+        assert_eq!(btc[&Stage::Parse].max_us, 100);
+        assert_eq!(btc[&Stage::Total].max_us, 111);
+        assert_eq!(btc[&Stage::Exchange].mean_us, 200);
+        assert_eq!(matrix.symbols["ETHUSDT"][&Stage::Apply].mean_us, 500);
+    }
+}
@@ -2,27 +2,135 @@
 //!
 //! This crate provides high-performance market data handling for connecting
 //! to Binance WebSocket streams and maintaining order book state.
+//!
+//! Features split the daemon from the library: `daemon` (default) adds the
+//! binary's HTTP server, `/ws` re-broadcast, CLI and logging to `runtime`,
+//! the feed itself (exchange connections, pipeline, publishers, metrics).
+//! Without `runtime` only the parser and order book (with the event,
+//! error, instrument and trade metric types they use) are built, free of
+//! the async runtime and sockets, so they compile to wasm32.
 
-use std::sync::Arc;
-use tokio::sync::RwLock;
-
+#[cfg(feature = "runtime")]
+pub mod analytics;
+#[cfg(feature = "runtime")]
+pub mod anomaly;
+#[cfg(feature = "parquet")]
+pub mod archive;
+#[cfg(feature = "runtime")]
+pub mod clock;
+#[cfg(feature = "runtime")]
 pub mod config;
+#[cfg(feature = "runtime")]
+pub mod dead_man;
+#[cfg(feature = "runtime")]
+pub mod degradation;
+#[cfg(feature = "runtime")]
+pub mod endpoints;
 pub mod error;
+pub mod event;
+pub mod exchange_info;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "runtime")]
+pub mod health;
+#[cfg(feature = "runtime")]
+pub mod latency;
+#[cfg(feature = "runtime")]
+pub mod metrics;
 pub mod orderbook;
 pub mod parser;
+#[cfg(feature = "runtime")]
+pub mod pipeline;
+#[cfg(feature = "pprof")]
+pub mod profiling;
+#[cfg(feature = "protobuf")]
+pub mod proto;
+#[cfg(feature = "runtime")]
 pub mod publisher;
+#[cfg(feature = "daemon")]
+pub mod rebroadcast;
+#[cfg(feature = "runtime")]
+pub mod recording;
+#[cfg(feature = "runtime")]
+pub mod rest;
+#[cfg(feature = "runtime")]
+pub mod shutdown;
+#[cfg(feature = "runtime")]
+pub mod socket;
+#[cfg(feature = "client")]
+pub mod subscriber;
+#[cfg(feature = "runtime")]
+pub mod tap;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(all(feature = "testing", feature = "runtime"))]
+pub mod testing;
+#[cfg(feature = "runtime")]
+pub mod time;
+pub mod trade_metrics;
+#[cfg(feature = "runtime")]
+pub mod user_data;
+#[cfg(feature = "runtime")]
+pub mod volume_profile;
+#[cfg(feature = "runtime")]
 pub mod websocket;
 
+#[cfg(feature = "runtime")]
+use std::sync::Arc;
+#[cfg(feature = "runtime")]
+use tokio::sync::RwLock;
+
+#[cfg(feature = "runtime")]
+pub use analytics::TradeAnalytics;
+#[cfg(feature = "runtime")]
+pub use anomaly::{AnomalyDetector, MarketAnomaly};
+#[cfg(feature = "runtime")]
 pub use config::Config;
+#[cfg(feature = "runtime")]
+pub use degradation::Degradation;
 pub use error::{MarketDataError, Result};
-pub use orderbook::{OrderBook, OrderBookManager, OrderBookMetrics, OrderBookState};
-pub use parser::{DepthUpdate, OrderBookSnapshot, ParsedMessage, Trade};
+pub use event::{MarketEvent, Venue};
+#[cfg(feature = "runtime")]
+pub use latency::{LatencyMatrix, LatencyTracker};
+pub use orderbook::{
+    BookEvent, OrderBook, OrderBookManager, OrderBookMetrics, OrderBookState, Provenance,
+    SavedBooks, WarmupPolicy,
+};
+pub use parser::{
+    DepthUpdate, ExchangeError, OrderBookSnapshot, ParsedMessage, SubscriptionAck, Trade,
+};
+#[cfg(feature = "runtime")]
 pub use publisher::Publisher;
-pub use websocket::WebSocketManager;
+pub use trade_metrics::{TradeMetrics, TradeMetricsTracker};
+#[cfg(feature = "runtime")]
+pub use volume_profile::{VolumeProfile, VolumeProfileTracker};
+#[cfg(feature = "runtime")]
+pub use websocket::{SubscriptionProgress, WebSocketManager};
 
 /// Application state shared across components
+#[cfg(feature = "runtime")]
 pub struct AppState {
-    pub orderbook_manager: Arc<RwLock<OrderBookManager>>,
+    /// Per-symbol book tasks
+    pub books: pipeline::Books,
+    pub analytics: Arc<RwLock<TradeAnalytics>>,
+    pub volume_profile: Arc<RwLock<VolumeProfileTracker>>,
     pub publisher: Arc<Publisher>,
     pub config: Arc<Config>,
+    pub latency: LatencyTracker,
+    pub subscriptions: Arc<SubscriptionProgress>,
+    pub degradation: Arc<Degradation>,
+    /// Declares the feed degraded for strategies to flatten on
+    pub dead_man: dead_man::DeadManSwitch,
+    /// Beaten by the WebSocket manager loop, for liveness
+    pub heartbeat: health::Heartbeat,
+    /// Rate-limited REST client shared by snapshot and metadata fetches
+    pub rest: rest::RestClient,
+    /// Estimated offset of the exchange's clock, for latency measurements
+    pub clock: clock::ClockSkew,
+    /// Time source of the feed's timers; manual in tests and replays
+    pub time: time::Clock,
+    /// WebSocket endpoints the feed connects to, with failover
+    pub ws_endpoints: endpoints::Endpoints,
+    /// TLS settings of every WebSocket connection
+    pub tls: websocket::Tls,
 }
@@ -3,57 +3,358 @@
 //! High-performance market data handler for connecting to Binance WebSocket streams,
 //! maintaining order book state, and publishing normalized data to other system components.
 
-mod config;
-mod error;
-mod orderbook;
-mod parser;
-mod publisher;
-mod websocket;
-
-use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderName, StatusCode};
+use axum::response::IntoResponse;
 use axum::{routing::get, Json, Router};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{info, warn, Level};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use crate::config::Config;
-use crate::orderbook::OrderBookManager;
-use crate::publisher::Publisher;
-use crate::websocket::WebSocketManager;
+#[cfg(feature = "parquet")]
+use orp_flow_market_data::archive;
+use orp_flow_market_data::config::BookRepresentation;
+use orp_flow_market_data::dead_man::DeadManSwitch;
+use orp_flow_market_data::degradation::Tier;
+use orp_flow_market_data::endpoints::{self, Endpoints};
+use orp_flow_market_data::exchange_info::{self, InstrumentInfo};
+use orp_flow_market_data::health::{self, Heartbeat, Liveness, Readiness};
+use orp_flow_market_data::pipeline::Books;
+use orp_flow_market_data::recording;
+use orp_flow_market_data::rest::RestClient;
+use orp_flow_market_data::shutdown::Shutdown;
+use orp_flow_market_data::tap::RawTap;
+#[cfg(feature = "otel")]
+use orp_flow_market_data::telemetry::Telemetry;
+use orp_flow_market_data::websocket::{fetch_snapshot, Tls};
+use orp_flow_market_data::{
+    AppState, Config, Degradation, LatencyMatrix, LatencyTracker, OrderBookManager, OrderBookState,
+    Publisher, SavedBooks, SubscriptionProgress, TradeAnalytics, VolumeProfileTracker,
+    WebSocketManager,
+};
+
+/// QuantumFlow market data handler
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Config file (TOML, YAML or JSON); defaults to CONFIG_PATH
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-/// Application state shared across components
-pub struct AppState {
-    pub orderbook_manager: Arc<RwLock<OrderBookManager>>,
-    pub publisher: Arc<Publisher>,
-    pub config: Arc<Config>,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Stream, maintain and publish the configured books (the default)
+    Run,
+    /// Rebuild books from a recording and print the final states
+    Replay {
+        /// Recording written by `record`
+        file: PathBuf,
+    },
+    /// Record the raw feed of the configured symbols
+    Record {
+        /// Output file (JSON lines)
+        #[arg(short, long, default_value = "recording.jsonl")]
+        output: PathBuf,
+        /// Stop after this many seconds instead of on Ctrl-C
+        #[arg(long)]
+        duration_secs: Option<u64>,
+    },
+    /// Fetch one REST depth snapshot and print it
+    Snapshot {
+        /// Symbol to fetch, e.g. BTCUSDT
+        symbol: String,
+    },
+    /// Validate the configuration and exit
+    CheckConfig,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Run);
+
+    let config = Config::load_from(cli.config.as_deref())?;
+
+    // Initialize logging; one-shot commands print results on stdout, so
+    // they log to stderr
+    let filter = EnvFilter::from_default_env().add_directive(Level::INFO.into());
+    let logs = if matches!(command, Command::Run) {
+        fmt::layer().json().boxed()
+    } else {
+        fmt::layer().json().with_writer(std::io::stderr).boxed()
+    }
+    .with_filter(filter);
+    #[cfg(feature = "otel")]
+    let telemetry = config
+        .otel_endpoint
+        .as_deref()
+        .map(|endpoint| Telemetry::init(endpoint, &config.otel_service_name))
+        .transpose()?;
+    #[cfg(feature = "otel")]
     tracing_subscriber::registry()
-        .with(fmt::layer().json())
-        .with(EnvFilter::from_default_env().add_directive(Level::INFO.into()))
+        .with(logs)
+        .with(telemetry.as_ref().map(Telemetry::layer))
         .init();
+    #[cfg(not(feature = "otel"))]
+    {
+        tracing_subscriber::registry().with(logs).init();
+        if config.otel_endpoint.is_some() {
+            warn!("OTEL_EXPORTER_OTLP_ENDPOINT is set but the otel feature is not compiled in");
+        }
+    }
+
+    let result = match command {
+        Command::Run => run(Arc::new(config)).await,
+        Command::Replay { file } => {
+            let mut manager = build_manager(&config, HashMap::new());
+            let stats = tokio::task::spawn_blocking(move || {
+                recording::replay(&file, &mut manager).map(|stats| (stats, manager))
+            })
+            .await??;
+            let (stats, manager) = stats;
+            let books: BTreeMap<String, OrderBookState> = manager
+                .symbols()
+                .into_iter()
+                .filter_map(|symbol| manager.get_state(&symbol))
+                .map(|book| (book.symbol.clone(), book))
+                .collect();
+            let output = serde_json::json!({ "stats": stats, "books": books });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            Ok(())
+        }
+        Command::Record {
+            output,
+            duration_secs,
+        } => {
+            let shutdown = Shutdown::new();
+            tokio::spawn({
+                let shutdown = shutdown.clone();
+                async move {
+                    orp_flow_market_data::shutdown::signal().await;
+                    shutdown.trigger();
+                }
+            });
+            let duration = duration_secs.map(Duration::from_secs);
+            let stats = recording::record(&config, &output, duration, shutdown).await?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            Ok(())
+        }
+        Command::Snapshot { symbol } => {
+            let rest = RestClient::from_config(&config);
+            let snapshot = fetch_snapshot(&rest, &config, &symbol.to_uppercase()).await?;
+            println!("{}", serde_json::to_string_pretty(&snapshot)?);
+            Ok(())
+        }
+        Command::CheckConfig => {
+            println!("Configuration OK");
+            for symbol in &config.symbols {
+                println!(
+                    "  {}: depth {}, stream {}{}, throttle {}ms, trade window {}s, vol window {}s",
+                    symbol,
+                    config.depth_levels_for(symbol),
+                    symbol.to_lowercase(),
+                    config.depth_update_speed_for(symbol).stream_suffix(),
+                    config.publish_throttle_ms_for(symbol),
+                    config.trade_metrics_window_secs_for(symbol),
+                    config.realized_vol_window_secs_for(symbol)
+                );
+            }
+            Ok(())
+        }
+    };
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = &telemetry {
+        telemetry.shutdown();
+    }
+    result
+}
+
+/// Stream, maintain and publish books until shut down
+async fn run(config: Arc<Config>) -> anyhow::Result<()> {
+    info!(symbols = ?config.symbols, "Starting QuantumFlow Market Data Handler");
+    orp_flow_market_data::metrics::register();
+
+    let tls = Tls::from_config(&config)?;
+    info!(
+        backend = Tls::backend(),
+        pinned_roots = config.tls_ca_file.is_some(),
+        "TLS configured"
+    );
+
+    // Instrument metadata for price validation and fixed-point scales
+    let rest = RestClient::from_config(&config);
+    let instruments = if config.exchange_info_enabled {
+        match exchange_info::fetch(&rest, &config.symbols).await {
+            Ok(instruments) => {
+                info!(symbols = instruments.len(), "Instrument metadata loaded");
+                instruments
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch instrument metadata");
+                HashMap::new()
+            }
+        }
+    } else {
+        HashMap::new()
+    };
+
+    let mut manager = build_manager(&config, instruments);
 
-    info!("Starting QuantumFlow Market Data Handler");
+    // Resume books saved by the last run if they are recent enough for the
+    // diff stream to continue them
+    if let Some(path) = &config.book_state_path {
+        match SavedBooks::load(std::path::Path::new(path)) {
+            Ok(saved) => {
+                let now = chrono::Utc::now().timestamp_millis() as u64;
+                if saved.is_fresh(now, config.book_resume_max_age_secs * 1000) {
+                    info!(path = %path, books = saved.books.len(), "Resuming saved books");
+                    manager.resume(&saved.books);
+                } else if !saved.books.is_empty() {
+                    info!(path = %path, "Saved books too old, fetching snapshots");
+                }
+            }
+            Err(e) => warn!(error = %e, path = %path, "Failed to load saved books"),
+        }
+    }
+    let (books, book_tasks) = Books::new(manager, &config.symbols);
 
-    // Load configuration
-    let config = Arc::new(Config::load()?);
-    info!(symbols = ?config.symbols, "Configuration loaded");
+    // Restore day-anchored analytics from the last run
+    let analytics = match &config.analytics_state_path {
+        Some(path) => match TradeAnalytics::load(std::path::Path::new(path)) {
+            Ok(analytics) => {
+                info!(path = %path, symbols = analytics.all().len(), "Analytics state restored");
+                analytics
+            }
+            Err(e) => {
+                warn!(error = %e, path = %path, "Failed to restore analytics state, starting empty");
+                TradeAnalytics::default()
+            }
+        },
+        None => TradeAnalytics::default(),
+    };
+    let analytics = Arc::new(RwLock::new(analytics));
 
-    // Initialize order book manager
-    let orderbook_manager = Arc::new(RwLock::new(OrderBookManager::new()));
+    // Sheds auxiliary subsystems to protect the book + publish path
+    let degradation = Arc::new(Degradation::new(config.degradation_policy()));
 
     // Initialize publisher for IPC
-    let publisher = Arc::new(Publisher::new(&config.ipc_socket_path).await?);
+    let publisher = Arc::new(
+        Publisher::new(&config)
+            .await?
+            .with_degradation(degradation.clone()),
+    );
+    publisher.spawn_tasks();
+
+    let volume_profile = Arc::new(RwLock::new(VolumeProfileTracker::new(
+        config.volume_profile_window_secs.max(1) * 1000,
+        config.volume_profile_bucket_bps,
+    )));
+    if config.volume_profile_enabled {
+        let interval = Duration::from_secs(config.volume_profile_publish_interval_secs.max(1));
+        tokio::spawn(orp_flow_market_data::volume_profile::run(
+            volume_profile.clone(),
+            publisher.clone(),
+            interval,
+        ));
+    }
 
     // Create shared application state
     let state = Arc::new(AppState {
-        orderbook_manager: orderbook_manager.clone(),
+        books,
+        analytics: analytics.clone(),
+        volume_profile,
         publisher: publisher.clone(),
         config: config.clone(),
+        subscriptions: Arc::new(SubscriptionProgress::default()),
+        degradation,
+        dead_man: DeadManSwitch::new(config.dead_man_policy()),
+        latency: LatencyTracker::new(Duration::from_secs(config.latency_window_secs.max(1))),
+        heartbeat: Heartbeat::default(),
+        rest,
+        clock: Default::default(),
+        time: Default::default(),
+        ws_endpoints: Endpoints::new("ws", config.ws_endpoints(), config.endpoint_failover_after),
+        tls,
     });
+    let resyncs = book_tasks.spawn(state.clone());
+
+    if config.exchange_info_enabled && config.exchange_info_refresh_secs > 0 {
+        tokio::spawn(exchange_info::run_refresh(
+            state.clone(),
+            Duration::from_secs(config.exchange_info_refresh_secs),
+        ));
+    }
+
+    // Tell IPC consumers each book is alive even when the market is quiet
+    if config.ipc_heartbeat_interval_ms > 0 {
+        tokio::spawn(orp_flow_market_data::publisher::heartbeat::run(
+            state.clone(),
+            Duration::from_millis(config.ipc_heartbeat_interval_ms),
+        ));
+    }
+
+    // Tell strategies when the feed can no longer be trusted
+    if config.dead_man_enabled {
+        tokio::spawn(orp_flow_market_data::dead_man::run(state.clone()));
+    }
+
+    // Estimate the exchange clock offset for latency measurements
+    if config.clock_sync_interval_secs > 0 {
+        tokio::spawn(orp_flow_market_data::clock::run(
+            state.clone(),
+            Duration::from_secs(config.clock_sync_interval_secs),
+        ));
+    }
+
+    // Prefer the fastest of several endpoints
+    let has_fallbacks =
+        !config.ws_fallback_endpoints.is_empty() || !config.rest_fallback_endpoints.is_empty();
+    if has_fallbacks && config.endpoint_probe_interval_secs > 0 {
+        tokio::spawn(endpoints::run_probes(
+            state.clone(),
+            Duration::from_secs(config.endpoint_probe_interval_secs),
+        ));
+    }
+
+    // Periodically persist analytics state
+    if let Some(path) = config.analytics_state_path.clone() {
+        let interval = Duration::from_secs(config.analytics_persist_interval_secs.max(1));
+        tokio::spawn(persist_analytics(analytics.clone(), path.into(), interval));
+    }
+
+    // Periodically persist books for warm restarts
+    if let Some(path) = config.book_state_path.clone() {
+        let interval = Duration::from_secs(config.book_persist_interval_secs.max(1));
+        tokio::spawn(persist_books(state.clone(), path.into(), interval));
+    }
+
+    // Archive trades and book snapshots to Parquet
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = config.archive_dir.clone() {
+        let feed = publisher.live().clone();
+        let flush = Duration::from_secs(config.archive_flush_interval_secs.max(1));
+        let snapshot = Duration::from_secs(config.archive_snapshot_interval_secs.max(1));
+        tokio::spawn(async move {
+            if let Err(e) = archive::run(dir.into(), flush, snapshot, feed).await {
+                warn!(error = %e, "Parquet archive stopped");
+            }
+        });
+    }
+    #[cfg(not(feature = "parquet"))]
+    if config.archive_dir.is_some() {
+        warn!("ARCHIVE_DIR is set but the parquet feature is not compiled in");
+    }
 
     // Start health check server
     let health_state = state.clone();
@@ -63,20 +364,132 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Stop on SIGINT / SIGTERM
+    let shutdown = Shutdown::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            orp_flow_market_data::shutdown::signal().await;
+            info!("Shutdown signal received");
+            shutdown.trigger();
+        }
+    });
+
+    // Follow the account's orders and balances
+    if state.rest.credentials().is_some() {
+        tokio::spawn(orp_flow_market_data::user_data::run(
+            state.clone(),
+            shutdown.clone(),
+        ));
+    }
+
     // Start WebSocket manager
-    let mut ws_manager = WebSocketManager::new(state);
+    let mut ws_manager = WebSocketManager::new(state.clone())
+        .with_shutdown(shutdown)
+        .with_resyncs(resyncs);
+    if let Some(sink) = &config.raw_tap {
+        ws_manager = ws_manager.with_tap(RawTap::spawn(sink.clone(), config.raw_tap_capacity));
+    }
     ws_manager.run().await?;
 
+    // Save state and drain outputs before exiting
+    let grace = Duration::from_secs(config.shutdown_timeout_secs.max(1));
+    let flush = async {
+        if let Some(path) = &config.book_state_path {
+            if let Err(e) = save_books(&state, path.into()).await {
+                warn!(error = %e, "Failed to save books on shutdown");
+            }
+        }
+        if let Some(path) = &config.analytics_state_path {
+            if let Err(e) = save_analytics(&analytics, path.into()).await {
+                warn!(error = %e, "Failed to save analytics state on shutdown");
+            }
+        }
+        publisher.shutdown().await;
+    };
+    if tokio::time::timeout(grace, flush).await.is_err() {
+        warn!(timeout_secs = grace.as_secs(), "Shutdown timed out");
+    }
+    info!("Shutdown complete");
+
     Ok(())
 }
 
+/// Order book manager configured for the symbols in `config`
+fn build_manager(
+    config: &Config,
+    instruments: HashMap<String, InstrumentInfo>,
+) -> OrderBookManager {
+    let mut manager = OrderBookManager::with_depth(config.depth_levels)
+        .with_symbol_depths(config.symbol_depth_levels.clone())
+        .with_overflow_levels(config.overflow_levels)
+        .with_crossed_policy(config.crossed_book_policy)
+        .with_warmup(config.warmup_policy())
+        .with_depth_bands(config.depth_bands_bps.clone())
+        .with_impact_sizes(
+            config
+                .symbols
+                .iter()
+                .map(|symbol| (symbol.clone(), config.impact_sizes_for(symbol)))
+                .collect(),
+        )
+        .with_metrics_configs(
+            config
+                .symbols
+                .iter()
+                .map(|symbol| (symbol.clone(), config.metrics_config_for(symbol)))
+                .collect(),
+        );
+    if config.book_representation == BookRepresentation::FixedPoint {
+        let scales = config.tick_scales(&instruments);
+        for symbol in config.symbols.iter().filter(|s| !scales.contains_key(*s)) {
+            warn!(symbol = %symbol, "No tick sizes configured, keeping a Decimal book");
+        }
+        manager = manager.with_tick_scales(scales);
+    }
+    let vol_windows: HashMap<String, u64> = config
+        .symbols
+        .iter()
+        .map(|symbol| {
+            let window_secs = config.realized_vol_window_secs_for(symbol);
+            (symbol.clone(), window_secs * 1000)
+        })
+        .collect();
+    if vol_windows.values().any(|window_ms| *window_ms > 0) {
+        manager = manager
+            .with_volatility(
+                config.realized_vol_window_secs * 1000,
+                config.realized_vol_sample_ms,
+            )
+            .with_volatility_windows(vol_windows);
+    }
+    manager.with_instruments(instruments)
+}
+
 /// Start HTTP server for health checks and metrics
-async fn start_health_server(_state: Arc<AppState>) -> anyhow::Result<()> {
+async fn start_health_server(state: Arc<AppState>) -> anyhow::Result<()> {
     use std::net::SocketAddr;
 
     let app = Router::new()
         .route("/health", get(health_check))
-        .route("/metrics", get(metrics));
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .route("/books", get(books))
+        .route("/analytics", get(analytics))
+        .route("/book/:symbol", get(book))
+        .route("/volume-profile/:symbol", get(volume_profile))
+        .route("/debug/latency", get(latency))
+        .route("/replay", get(replay))
+        .route("/ws", get(orp_flow_market_data::rebroadcast::handler));
+
+    #[cfg(feature = "pprof")]
+    let app = app.route(
+        "/debug/pprof",
+        get(orp_flow_market_data::profiling::flamegraph),
+    );
+
+    let app = app.with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 9090));
     info!(addr = %addr, "Starting health check server");
@@ -87,16 +500,273 @@ async fn start_health_server(_state: Arc<AppState>) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn health_check() -> Json<serde_json::Value> {
+async fn health_check(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let books = &state.books;
+    let symbols: serde_json::Map<String, serde_json::Value> = state
+        .config
+        .symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.clone(),
+                serde_json::json!({
+                    "initialized": books.is_initialized(symbol),
+                    "warmed_up": books.is_warmed_up(symbol),
+                }),
+            )
+        })
+        .collect();
+
+    let degradation = state.degradation.status();
+    let status = if degradation.tier == Tier::Normal {
+        "healthy"
+    } else {
+        "degraded"
+    };
+
     Json(serde_json::json!({
-        "status": "healthy",
+        "status": status,
         "component": "market-data",
-        "timestamp": chrono::Utc::now().to_rfc3339()
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "symbols": symbols,
+        "subscriptions": state.subscriptions.status(),
+        "degradation": degradation
     }))
 }
 
-async fn metrics() -> String {
+/// Liveness probe: 503 once the WebSocket loop has stopped making progress
+async fn livez(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Liveness>) {
+    let liveness = health::liveness(&state, chrono::Utc::now().timestamp_millis() as u64);
+    let status = if liveness.alive {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(liveness))
+}
+
+/// Readiness probe: 503 until the feed is connected, every book is
+/// initialized and fresh, and the IPC consumer is connected
+async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Readiness>) {
+    let readiness = health::readiness(&state, chrono::Utc::now().timestamp_millis() as u64);
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// Save analytics state every `interval`
+async fn persist_analytics(
+    analytics: Arc<RwLock<TradeAnalytics>>,
+    path: std::path::PathBuf,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = save_analytics(&analytics, path.clone()).await {
+            warn!(error = %e, "Failed to persist analytics state");
+        }
+    }
+}
+
+/// Save analytics state to `path`
+async fn save_analytics(
+    analytics: &RwLock<TradeAnalytics>,
+    path: std::path::PathBuf,
+) -> anyhow::Result<()> {
+    let snapshot = analytics.read().await.clone();
+    tokio::task::spawn_blocking(move || snapshot.save(&path)).await??;
+    Ok(())
+}
+
+/// Save book levels every `interval`
+async fn persist_books(state: Arc<AppState>, path: std::path::PathBuf, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = save_books(&state, path.clone()).await {
+            warn!(error = %e, "Failed to persist books");
+        }
+    }
+}
+
+/// Save the retained levels of every initialized book to `path`
+async fn save_books(state: &AppState, path: std::path::PathBuf) -> anyhow::Result<()> {
+    let saved = SavedBooks {
+        saved_at: chrono::Utc::now().timestamp_millis() as u64,
+        books: state.books.snapshots().await,
+    };
+    tokio::task::spawn_blocking(move || saved.save(&path)).await??;
+    Ok(())
+}
+
+async fn analytics(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    let mut rolling = HashMap::new();
+    for symbol in state.books.symbols() {
+        if let Some(metrics) = state.books.trade_metrics(symbol, now_ms).await {
+            rolling.insert(symbol.clone(), metrics);
+        }
+    }
+    let analytics = state.analytics.read().await;
+    let symbols: serde_json::Map<String, serde_json::Value> = analytics
+        .all()
+        .iter()
+        .map(|(symbol, stats)| {
+            (
+                symbol.clone(),
+                serde_json::json!({
+                    "utc_day": stats,
+                    "vwap": stats.vwap(),
+                    "cvd": stats.cvd(),
+                    "rolling": rolling.remove(symbol),
+                }),
+            )
+        })
+        .collect();
+
+    Json(serde_json::Value::Object(symbols))
+}
+
+async fn volume_profile(
+    Path(symbol): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let symbol = symbol.to_uppercase();
+    let tracker = state.volume_profile.read().await;
+    let current = tracker.current(&symbol);
+    let previous = tracker.previous(&symbol);
+    if current.is_none() && previous.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("No volume profile for {}", symbol),
+        ));
+    }
+
+    Ok(Json(serde_json::json!({
+        "current": current,
+        "previous": previous,
+    })))
+}
+
+/// Query parameters for `/book/:symbol` and `/books`
+#[derive(Debug, Deserialize)]
+struct BookParams {
+    /// Levels per side to return (default: all maintained levels)
+    depth: Option<usize>,
+}
+
+async fn book(
+    Path(symbol): Path<String>,
+    Query(params): Query<BookParams>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<OrderBookState>, (StatusCode, String)> {
+    let symbol = symbol.to_uppercase();
+    let mut book = state
+        .books
+        .state(&symbol)
+        .map(Arc::unwrap_or_clone)
+        .ok_or((StatusCode::NOT_FOUND, format!("No book for {}", symbol)))?;
+
+    if let Some(depth) = params.depth {
+        book.truncate(depth);
+    }
+    Ok(Json(book))
+}
+
+async fn books(
+    Query(params): Query<BookParams>,
+    State(state): State<Arc<AppState>>,
+) -> Json<BTreeMap<String, OrderBookState>> {
+    Json(
+        state
+            .books
+            .states()
+            .into_iter()
+            .map(|book| {
+                let mut book = Arc::unwrap_or_clone(book);
+                if let Some(depth) = params.depth {
+                    book.truncate(depth);
+                }
+                (book.symbol.clone(), book)
+            })
+            .collect(),
+    )
+}
+
+async fn latency(State(state): State<Arc<AppState>>) -> Json<LatencyMatrix> {
+    Json(state.latency.matrix())
+}
+
+/// Most frames `/replay` returns at once
+const MAX_REPLAY_FRAMES: usize = 100_000;
+
+/// Query parameters for `/replay`
+#[derive(Debug, Deserialize)]
+struct ReplayParams {
+    /// Sequence number of the first frame wanted
+    from_seq: u64,
+    /// Frames to return at most (default and cap: `MAX_REPLAY_FRAMES`)
+    limit: Option<usize>,
+}
+
+/// Journaled IPC frames from `from_seq` on, length-prefixed as on the
+/// socket, with how many in the range failed to journal; 410 once they
+/// were deleted from the journal
+async fn replay(
+    Query(params): Query<ReplayParams>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let reader = state.publisher.journal().ok_or((
+        StatusCode::NOT_FOUND,
+        "The journal is disabled (JOURNAL_DIR)".to_string(),
+    ))?;
+    let limit = params
+        .limit
+        .unwrap_or(MAX_REPLAY_FRAMES)
+        .min(MAX_REPLAY_FRAMES);
+    let replay = tokio::task::spawn_blocking(move || reader.read(params.from_seq, limit))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((
+            StatusCode::GONE,
+            format!("Frames from {} are no longer journaled", params.from_seq),
+        ))?;
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+        (
+            HeaderName::from_static("x-journal-first-seq"),
+            replay.first_seq.to_string(),
+        ),
+        (
+            HeaderName::from_static("x-journal-last-seq"),
+            replay.last_seq.to_string(),
+        ),
+        (
+            HeaderName::from_static("x-replay-frames"),
+            replay.count.to_string(),
+        ),
+        (
+            HeaderName::from_static("x-replay-missing"),
+            replay.missing.to_string(),
+        ),
+    ];
+    Ok((headers, replay.frames))
+}
+
+async fn metrics(State(state): State<Arc<AppState>>) -> String {
     use prometheus::{Encoder, TextEncoder};
+    orp_flow_market_data::metrics::observe_book_ages(
+        &state.books.update_times(),
+        chrono::Utc::now().timestamp_millis() as u64,
+    );
     let encoder = TextEncoder::new();
     let metric_families = prometheus::gather();
     let mut buffer = Vec::new();
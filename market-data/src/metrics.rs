@@ -0,0 +1,281 @@
+//! Prometheus metrics of the feed, books and publisher
+//!
+//! Everything is registered with the default registry, which `/metrics`
+//! exports. Metrics owned by a single component (crossed books, send
+//! queues, anomalies) live next to it; this module holds the ones recorded
+//! across the pipeline.
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::latency::{Stage, StageTimes};
+
+static MESSAGES_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "market_data_messages_received_total",
+            "WebSocket data messages received, per symbol and stream",
+        ),
+        &["symbol", "stream"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static BOOK_UPDATES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "orderbook_updates_total",
+            "Depth updates offered to a book, by result (applied, skipped or rejected)",
+        ),
+        &["symbol", "result"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static PUBLISH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let histogram = HistogramVec::new(
+        HistogramOpts::new(
+            "publisher_publish_duration_seconds",
+            "Time to hand a book state to the publisher outputs",
+        )
+        .buckets(prometheus::exponential_buckets(0.000_005, 2.0, 16).unwrap()),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(histogram.clone()));
+    histogram
+});
+
+static STAGE_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let histogram = HistogramVec::new(
+        HistogramOpts::new(
+            "market_data_stage_latency_seconds",
+            "Latency of each pipeline stage of published depth updates, from the exchange event time to the publish",
+        )
+        .buckets(prometheus::exponential_buckets(0.000_005, 2.0, 20).unwrap()),
+        &["symbol", "stage"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(histogram.clone()));
+    histogram
+});
+
+static BOOK_STALE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "orderbook_stale",
+            "Whether a book has gone without updates past STALE_BOOK_SECS (1) or not (0)",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static STALE_RECOVERIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "orderbook_stale_recoveries_total",
+            "Resubscribes and resyncs of books that went silent while others kept updating",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static SNAPSHOT_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "orderbook_snapshot_failures_total",
+            "Failed snapshot fetches, each retried for its symbol alone",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static RECONNECTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "websocket_reconnects_total",
+        "WebSocket reconnections after an error",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static SUBSCRIPTION_ACKS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "websocket_subscription_acks_total",
+        "Control requests (SUBSCRIBE, UNSUBSCRIBE) confirmed by the exchange",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static EXCHANGE_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "websocket_exchange_errors_total",
+            "Error frames and rejected control requests from the exchange, by error code",
+        ),
+        &["code"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static BOOK_AGE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "orderbook_age_milliseconds",
+            "Time since the event time of a book's last applied update",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+/// Count a data message of `stream` (`depth`, `trade` or `other`)
+pub fn message_received(symbol: &str, stream: &str) {
+    MESSAGES_RECEIVED.with_label_values(&[symbol, stream]).inc();
+}
+
+/// Count the result of offering a depth update to a book
+pub fn book_update(symbol: &str, result: &Result<bool>) {
+    let label = match result {
+        Ok(true) => "applied",
+        Ok(false) => "skipped",
+        Err(_) => "rejected",
+    };
+    BOOK_UPDATES.with_label_values(&[symbol, label]).inc();
+}
+
+/// Record how long publishing a book state took
+pub fn observe_publish(symbol: &str, elapsed: Duration) {
+    PUBLISH_DURATION
+        .with_label_values(&[symbol])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record one published update's pass through the pipeline
+pub fn observe_stages(symbol: &str, times: &StageTimes) {
+    for (stage, micros) in Stage::ALL.into_iter().zip(times.durations()) {
+        STAGE_LATENCY
+            .with_label_values(&[symbol, stage.name()])
+            .observe(micros as f64 / 1e6);
+    }
+}
+
+/// Count a reconnection
+pub fn reconnected() {
+    RECONNECTS.inc();
+}
+
+/// Mark whether a book is stale
+pub fn set_stale(symbol: &str, stale: bool) {
+    BOOK_STALE.with_label_values(&[symbol]).set(stale as i64);
+}
+
+/// Count a stale book's recovery
+pub fn stale_recovery(symbol: &str) {
+    STALE_RECOVERIES.with_label_values(&[symbol]).inc();
+}
+
+/// Count a failed snapshot fetch
+pub fn snapshot_failed(symbol: &str) {
+    SNAPSHOT_FAILURES.with_label_values(&[symbol]).inc();
+}
+
+/// Count a confirmed control request
+pub fn subscription_acked() {
+    SUBSCRIPTION_ACKS.inc();
+}
+
+/// Count an error from the exchange
+pub fn exchange_error(code: i64) {
+    EXCHANGE_ERRORS
+        .with_label_values(&[&code.to_string()])
+        .inc();
+}
+
+/// Refresh the age gauge of the books in `update_times` (symbol, last
+/// update ms) as of `now_ms`
+pub fn observe_book_ages(update_times: &[(String, u64)], now_ms: u64) {
+    for (symbol, updated_at) in update_times {
+        BOOK_AGE
+            .with_label_values(&[symbol])
+            .set(now_ms.saturating_sub(*updated_at) as i64);
+    }
+}
+
+/// Force registration of every metric so `/metrics` lists them before
+/// their first sample
+pub fn register() {
+    LazyLock::force(&MESSAGES_RECEIVED);
+    LazyLock::force(&BOOK_UPDATES);
+    LazyLock::force(&PUBLISH_DURATION);
+    LazyLock::force(&STAGE_LATENCY);
+    LazyLock::force(&BOOK_STALE);
+    LazyLock::force(&STALE_RECOVERIES);
+    LazyLock::force(&SNAPSHOT_FAILURES);
+    LazyLock::force(&RECONNECTS);
+    LazyLock::force(&SUBSCRIPTION_ACKS);
+    LazyLock::force(&EXCHANGE_ERRORS);
+    LazyLock::force(&BOOK_AGE);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{BookSnapshot, DepthDelta, PriceLevel, Venue};
+    use crate::orderbook::OrderBookManager;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_book_updates_and_age() {
+        let symbol = "METRICSUSDT";
+        let level = |price, quantity| PriceLevel { price, quantity };
+        let mut manager = OrderBookManager::new();
+        manager.init_book(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: symbol.to_string(),
+            last_update_id: 100,
+            bids: vec![level(dec!(100), dec!(1))],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        let update = |id| DepthDelta {
+            venue: Venue::Binance,
+            event_time: 5_000,
+            symbol: symbol.to_string(),
+            first_update_id: id,
+            final_update_id: id,
+            bids: vec![level(dec!(100), dec!(2))],
+            asks: vec![],
+        };
+        manager.apply_update(&update(101)).unwrap();
+        manager.apply_update(&update(101)).unwrap();
+
+        let count = |result| BOOK_UPDATES.with_label_values(&[symbol, result]).get();
+        assert_eq!(count("applied"), 1);
+        assert_eq!(count("skipped"), 1);
+
+        observe_book_ages(&manager.update_times(), 7_500);
+        assert_eq!(BOOK_AGE.with_label_values(&[symbol]).get(), 2_500);
+    }
+}
@@ -1,30 +1,164 @@
 //! Core order book implementation
 //!
-//! Uses BTreeMap for efficient sorted price level management.
+//! Levels are kept in sorted maps, as `Decimal`s or as fixed-point
+//! integers, and in a sorted `Vec` rather than a tree for depths of at most
+//! `COMPACT_MAX_LEVELS` (see `levels`). Books fed by per-order (L3)
+//! feeds also track the individual orders (see `l3`) and are fed their
+//! per-price totals.
+//!
+//! No quantity makes the book panic, which would abort a host embedding it
+//! through the C ABI: sums saturate at `Decimal::MAX`, and a metric whose
+//! value doesn't fit a `Decimal` is `None`.
 
+#[cfg(feature = "runtime")]
+use prometheus::{IntCounterVec, Opts};
 use rust_decimal::Decimal;
-use std::cmp::Reverse;
-use std::collections::BTreeMap;
+#[cfg(feature = "runtime")]
+use std::sync::LazyLock;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+use tracing::warn;
+
+use super::l3::OrderTracker;
+use super::levels::{Levels, COMPACT_MAX_LEVELS};
+use super::{
+    checksum, BboChanged, CrossedBookPolicy, DepthBand, Level, Metric, MetricsConfig,
+    OrderBookMetrics, OrderBookState, PriceImpact, Side, TickScale, VolatilityEstimator,
+    WarmupPolicy,
+};
+use crate::error::{MarketDataError, Result};
+use crate::event::{BookSnapshot, DepthDelta, OrderSnapshot, OrderUpdate, PriceLevel, Venue};
+use crate::exchange_info::InstrumentInfo;
+
+/// wasm32 has no `Instant`: books there take any warm-up duration as
+/// elapsed, warming up on their update count alone
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy)]
+struct Instant;
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    fn now() -> Self {
+        Instant
+    }
+
+    fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::MAX
+    }
+}
+
+#[cfg(feature = "runtime")]
+static CROSSED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "orderbook_crossed_updates_total",
+            "Depth updates that left the book crossed or locked",
+        ),
+        &["symbol", "kind"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
 
-use super::{Level, OrderBookMetrics, OrderBookState, Side};
-use crate::parser::{DepthUpdate, OrderBookSnapshot, PriceLevel};
+#[cfg(feature = "runtime")]
+static OFF_TICK: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "orderbook_off_tick_prices_total",
+            "Depth update prices that are not a multiple of the symbol's tick size",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// A level's quantities before an update, to undo it: (side, price, visible
+/// quantity, overflow quantity)
+type LevelUndo = (Side, Decimal, Option<Decimal>, Option<Decimal>);
+
+/// Running totals of one side's visible levels, kept in step with updates
+/// so emitting metrics doesn't walk the book; they saturate rather than
+/// overflow
+#[derive(Debug, Clone, Copy, Default)]
+struct SideTotals {
+    /// Total visible quantity
+    depth: Decimal,
+    /// Quantity in the top `imbalance_levels`
+    top: Decimal,
+    /// Decay-weighted quantity in the top `weighted_imbalance_levels`
+    weighted_top: Decimal,
+    /// Worst price inside the top window; `None` while the side has fewer
+    /// levels than the window, so any change reaches it
+    edge: Option<Decimal>,
+}
 
 /// Order book for a single symbol
 #[derive(Debug)]
 pub struct OrderBook {
     symbol: String,
-    /// Bids sorted by price descending (highest first)
-    bids: BTreeMap<Reverse<Decimal>, Decimal>,
-    /// Asks sorted by price ascending (lowest first)
-    asks: BTreeMap<Decimal, Decimal>,
+    /// Venue of the last snapshot
+    venue: Venue,
+    /// Bids, highest price first
+    bids: Levels,
+    /// Asks, lowest price first
+    asks: Levels,
     /// Last processed update ID
     last_update_id: u64,
     /// Whether the book has been initialized with a snapshot
     initialized: bool,
     /// Maximum depth levels to maintain
     max_depth: usize,
+    /// Bid levels trimmed beyond max_depth, kept to refill the visible book
+    bid_overflow: Levels,
+    /// Ask levels trimmed beyond max_depth, kept to refill the visible book
+    ask_overflow: Levels,
+    /// Maximum levels retained per side in the overflow buffers
+    overflow_levels: usize,
+    /// Worst bid price through which the retained bids are known complete;
+    /// `None` while nothing below the best bids has been discarded
+    bid_window: Option<Decimal>,
+    /// Worst ask price through which the retained asks are known complete
+    ask_window: Option<Decimal>,
     /// Timestamp of last update
     last_update_time: u64,
+    /// Warm-up requirement after each snapshot
+    warmup: WarmupPolicy,
+    /// When the current snapshot was applied
+    initialized_at: Option<Instant>,
+    /// Diffs applied since the current snapshot
+    updates_since_init: u64,
+    /// Order sizes whose price impact is included in metrics
+    impact_sizes: Vec<Decimal>,
+    /// Realized volatility of the mid, when enabled
+    volatility: Option<VolatilityEstimator>,
+    /// Distances from mid (bps) whose cumulative depth is included in metrics
+    depth_bands: Vec<Decimal>,
+    /// Handling of updates that cross or lock the book
+    crossed_policy: CrossedBookPolicy,
+    /// Which metrics are computed, and their parameters
+    metrics_config: MetricsConfig,
+    /// Running totals of the visible bids
+    bid_totals: SideTotals,
+    /// Running totals of the visible asks
+    ask_totals: SideTotals,
+    /// Whether an update reached the top window since its sums were taken
+    top_dirty: bool,
+    /// Fixed-point scale of the stored levels, if not `Decimal`
+    tick_scale: Option<TickScale>,
+    /// Forced choice of sorted-`Vec` level storage; by default it is used
+    /// for sides of at most `COMPACT_MAX_LEVELS` levels
+    compact: Option<bool>,
+    /// Trading rules from `exchangeInfo`, when fetched
+    instrument: Option<InstrumentInfo>,
+    /// Whether the last applied update changed the best bid or ask
+    bbo_changed: bool,
+    /// Resting orders, for books fed per-order (L3) updates
+    orders: Option<OrderTracker>,
+    /// Restored from disk and not yet confirmed by a contiguous update
+    resumed: bool,
 }
 
 impl OrderBook {
@@ -32,23 +166,152 @@ impl OrderBook {
     pub fn new(symbol: &str, max_depth: usize) -> Self {
         Self {
             symbol: symbol.to_string(),
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
+            venue: Venue::default(),
+            bids: Levels::new(Side::Bid, None, max_depth, max_depth <= COMPACT_MAX_LEVELS),
+            asks: Levels::new(Side::Ask, None, max_depth, max_depth <= COMPACT_MAX_LEVELS),
             last_update_id: 0,
             initialized: false,
             max_depth,
+            bid_overflow: Levels::new(Side::Bid, None, 0, true),
+            ask_overflow: Levels::new(Side::Ask, None, 0, true),
+            overflow_levels: 0,
+            bid_window: None,
+            ask_window: None,
             last_update_time: 0,
+            warmup: WarmupPolicy::default(),
+            initialized_at: None,
+            updates_since_init: 0,
+            impact_sizes: Vec::new(),
+            volatility: None,
+            depth_bands: Vec::new(),
+            crossed_policy: CrossedBookPolicy::default(),
+            metrics_config: MetricsConfig::default(),
+            bid_totals: SideTotals::default(),
+            ask_totals: SideTotals::default(),
+            top_dirty: false,
+            tick_scale: None,
+            compact: None,
+            instrument: None,
+            bbo_changed: false,
+            orders: None,
+            resumed: false,
         }
     }
 
-    /// Initialize with a snapshot from REST API
-    pub fn init_snapshot(&mut self, snapshot: &OrderBookSnapshot) {
+    /// Require a warm-up period after each snapshot before the book is
+    /// considered ready for publishing
+    pub fn with_warmup(mut self, warmup: WarmupPolicy) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Retain up to `levels` trimmed levels per side to refill the visible book
+    pub fn with_overflow_levels(mut self, levels: usize) -> Self {
+        self.overflow_levels = levels;
+        self.rebuild_levels();
+        self
+    }
+
+    /// Publish the price impact of market orders of these sizes
+    pub fn with_impact_sizes(mut self, sizes: Vec<Decimal>) -> Self {
+        self.impact_sizes = sizes;
+        self
+    }
+
+    /// Publish cumulative depth within these distances from mid (bps)
+    pub fn with_depth_bands(mut self, bands_bps: Vec<Decimal>) -> Self {
+        self.depth_bands = bands_bps;
+        self
+    }
+
+    /// Store levels as fixed-point ticks and lots of `scale` instead of
+    /// `Decimal`s; set before the first snapshot
+    pub fn with_tick_scale(mut self, scale: TickScale) -> Self {
+        self.tick_scale = Some(scale);
+        self.rebuild_levels();
+        self
+    }
+
+    /// Force sorted-`Vec` (`true`) or `BTreeMap` (`false`) level storage
+    /// instead of choosing by depth; set before the first snapshot
+    pub fn with_compact_levels(mut self, compact: bool) -> Self {
+        self.compact = Some(compact);
+        self.rebuild_levels();
+        self
+    }
+
+    /// Recreate the (empty) level stores after a storage setting changed
+    fn rebuild_levels(&mut self) {
+        let scale = self.tick_scale;
+        let compact = |levels| self.compact.unwrap_or(levels <= COMPACT_MAX_LEVELS);
+        let (visible, overflow) = (self.max_depth, self.overflow_levels);
+        let (visible_compact, overflow_compact) = (compact(visible), compact(overflow));
+        self.bids = Levels::new(Side::Bid, scale, visible, visible_compact);
+        self.asks = Levels::new(Side::Ask, scale, visible, visible_compact);
+        self.bid_overflow = Levels::new(Side::Bid, scale, overflow, overflow_compact);
+        self.ask_overflow = Levels::new(Side::Ask, scale, overflow, overflow_compact);
+    }
+
+    /// Validate prices against, and publish, the instrument's trading rules
+    pub fn with_instrument(mut self, instrument: InstrumentInfo) -> Self {
+        self.set_instrument(instrument);
+        self
+    }
+
+    /// Replace the instrument's trading rules, e.g. after a refresh
+    pub fn set_instrument(&mut self, instrument: InstrumentInfo) {
+        self.instrument = Some(instrument);
+    }
+
+    /// Trading rules of the instrument, if known
+    pub fn instrument(&self) -> Option<&InstrumentInfo> {
+        self.instrument.as_ref()
+    }
+
+    /// Compute metrics according to `config`
+    pub fn with_metrics_config(mut self, config: MetricsConfig) -> Self {
+        self.metrics_config = config;
+        self.refresh_top();
+        self
+    }
+
+    /// Handle updates that cross or lock the book according to `policy`
+    pub fn with_crossed_policy(mut self, policy: CrossedBookPolicy) -> Self {
+        self.crossed_policy = policy;
+        self
+    }
+
+    /// Track realized volatility of the mid over `window_ms`, sampled every
+    /// `sample_interval_ms` of exchange time
+    pub fn with_volatility(mut self, window_ms: u64, sample_interval_ms: u64) -> Self {
+        self.volatility = Some(VolatilityEstimator::new(window_ms, sample_interval_ms));
+        self
+    }
+
+    /// Continue a previous book's volatility history, e.g. across a resync
+    pub(super) fn with_volatility_estimator(mut self, estimator: VolatilityEstimator) -> Self {
+        self.volatility = Some(estimator);
+        self
+    }
+
+    /// Hand over the volatility history to a replacement book
+    pub(super) fn take_volatility(&mut self) -> Option<VolatilityEstimator> {
+        self.volatility.take()
+    }
+
+    /// Initialize with a full book snapshot
+    pub fn init_snapshot(&mut self, snapshot: &BookSnapshot) {
+        self.venue = snapshot.venue;
+        self.orders = None;
+        self.resumed = false;
         self.bids.clear();
         self.asks.clear();
+        self.bid_overflow.clear();
+        self.ask_overflow.clear();
 
         for level in &snapshot.bids {
             if level.quantity > Decimal::ZERO {
-                self.bids.insert(Reverse(level.price), level.quantity);
+                self.bids.insert(level.price, level.quantity);
             }
         }
 
@@ -58,25 +321,134 @@ impl OrderBook {
             }
         }
 
+        // A snapshot side shorter than the retention depth is the whole
+        // side; a full one may have been cut off after its worst level.
+        // Without an overflow buffer there is nothing to refill from, so
+        // the book thins instead of keeping a window
+        let retention = self.max_depth + self.overflow_levels;
+        let window = |levels: &Levels, len: usize| {
+            (self.overflow_levels > 0 && len >= retention)
+                .then(|| levels.worst().map(|(price, _)| price))
+                .flatten()
+        };
+        self.bid_window = window(&self.bids, snapshot.bids.len());
+        self.ask_window = window(&self.asks, snapshot.asks.len());
+
         self.last_update_id = snapshot.last_update_id;
         self.initialized = true;
+        self.initialized_at = Some(Instant::now());
+        self.updates_since_init = 0;
         self.trim_depth();
+        self.recompute_totals();
+    }
+
+    /// Initialize the book from a per-order (L3) snapshot, after which it
+    /// takes `apply_order_update`s
+    ///
+    /// The book knows every resting order, so it has no price window:
+    /// levels trimmed away are refilled from the orders when a side thins.
+    pub fn init_orders(&mut self, snapshot: &OrderSnapshot) {
+        let tracker = OrderTracker::new(&snapshot.orders);
+        self.init_snapshot(&BookSnapshot {
+            venue: snapshot.venue,
+            symbol: snapshot.symbol.clone(),
+            last_update_id: snapshot.sequence,
+            bids: tracker.levels(Side::Bid),
+            asks: tracker.levels(Side::Ask),
+        });
+        self.orders = Some(tracker);
+        self.bid_window = None;
+        self.ask_window = None;
+    }
+
+    /// Apply a per-order (L3) update to a book initialized with
+    /// `init_orders`
+    ///
+    /// The totals of the levels the order leaves and joins go through
+    /// `apply_update`; if that fails (a rejected crossing, say) the order
+    /// is rolled back too, so the tracked orders keep matching the levels.
+    /// A gap in the sequence discards the book for a resync and returns
+    /// `MarketDataError::SequenceMismatch`.
+    pub fn apply_order_update(&mut self, update: &OrderUpdate) -> Result<bool> {
+        if !self.initialized || self.orders.is_none() || update.sequence <= self.last_update_id {
+            return Ok(false);
+        }
+        if update.sequence != self.last_update_id + 1 {
+            self.initialized = false;
+            return Err(MarketDataError::SequenceMismatch {
+                expected: self.last_update_id + 1,
+                got: update.sequence,
+            });
+        }
+
+        let mut delta = DepthDelta {
+            venue: update.venue,
+            symbol: update.symbol.clone(),
+            event_time: update.event_time,
+            first_update_id: update.sequence,
+            final_update_id: update.sequence,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        };
+        if let Some(orders) = &self.orders {
+            for (side, level) in orders.changes(update) {
+                match side {
+                    Side::Bid => delta.bids.push(level),
+                    Side::Ask => delta.asks.push(level),
+                }
+            }
+        }
+        // Refills read the new totals, so the order goes in first
+        let replaced = self.orders.as_mut().map(|orders| orders.apply(update));
+        let result = self.apply_update(&delta);
+        if result.is_err() {
+            if let (Some(orders), Some(replaced)) = (&mut self.orders, replaced) {
+                orders.undo(update, replaced);
+            }
+        }
+        result
     }
 
     /// Apply a depth update
     ///
-    /// Returns true if the update was applied successfully
-    pub fn apply_update(&mut self, update: &DepthUpdate) -> bool {
+    /// Returns `Ok(true)` if the update was applied, `Ok(false)` if it was
+    /// skipped (uninitialized book or stale update). An update starting
+    /// past `last_update_id + 1` means diffs were missed: the book is
+    /// discarded for a resync and `MarketDataError::SequenceMismatch`
+    /// returned. An update that leaves
+    /// the book crossed or locked is handled per the crossed book policy:
+    /// trimmed books are returned as applied, while rejected updates and
+    /// books discarded for a resync return `MarketDataError::CrossedBook`.
+    ///
+    /// Changes beyond the price window a side is known to be complete
+    /// through are ignored, since the levels between it and them are
+    /// unknown. Once a side's window holds fewer levels than the max depth,
+    /// the book is discarded for a resync and
+    /// `MarketDataError::DepthWindowExhausted` returned.
+    pub fn apply_update(&mut self, update: &DepthDelta) -> Result<bool> {
         // Validate sequence - first event's U should be <= lastUpdateId + 1
         // and u should be >= lastUpdateId + 1 in the first valid event
         if !self.initialized {
-            return false;
+            return Ok(false);
         }
 
         // Check if this update is relevant (not stale)
         if update.final_update_id <= self.last_update_id {
-            return false; // Stale update, skip
+            return Ok(false); // Stale update, skip
         }
+        if update.first_update_id > self.last_update_id + 1 {
+            self.initialized = false;
+            return Err(MarketDataError::SequenceMismatch {
+                expected: self.last_update_id + 1,
+                got: update.first_update_id,
+            });
+        }
+        self.resumed = false;
+
+        let undo =
+            (self.crossed_policy == CrossedBookPolicy::Reject).then(|| self.undo_log(update));
+        let bbo = (self.bids.best(), self.asks.best());
+        self.bbo_changed = false;
 
         // Apply bid updates
         for level in &update.bids {
@@ -90,75 +462,472 @@ impl OrderBook {
 
         self.last_update_id = update.final_update_id;
         self.last_update_time = update.event_time;
+
+        if let Some((best_bid, best_ask)) = self.crossing() {
+            #[cfg(feature = "runtime")]
+            {
+                let kind = if best_bid == best_ask {
+                    "locked"
+                } else {
+                    "crossed"
+                };
+                CROSSED.with_label_values(&[&self.symbol, kind]).inc();
+            }
+            let error = MarketDataError::CrossedBook {
+                symbol: self.symbol.clone(),
+                best_bid,
+                best_ask,
+            };
+            match self.crossed_policy {
+                CrossedBookPolicy::Reject => {
+                    // The update is consumed, so the sequence still advances
+                    for (side, price, visible, overflow) in undo.into_iter().flatten().rev() {
+                        self.restore_level(side, price, visible, overflow);
+                    }
+                    self.recompute_totals();
+                    return Err(error);
+                }
+                CrossedBookPolicy::Trim => {
+                    warn!(error = %error, "Trimming crossing levels");
+                    self.trim_crossing(update);
+                    self.recompute_totals();
+                }
+                CrossedBookPolicy::Resync => {
+                    self.initialized = false;
+                    return Err(error);
+                }
+            }
+        }
+
+        self.updates_since_init += 1;
         self.trim_depth();
+        if self.window_exhausted() {
+            self.initialized = false;
+            return Err(MarketDataError::DepthWindowExhausted {
+                symbol: self.symbol.clone(),
+            });
+        }
+        if self.top_dirty {
+            self.refresh_top();
+        }
+        self.bbo_changed = (self.bids.best(), self.asks.best()) != bbo;
+
+        if let Some(mid) = self.mid_price() {
+            if let Some(volatility) = &mut self.volatility {
+                volatility.observe(update.event_time, mid);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Whether a side has lost levels it can't be refilled with: its
+    /// window is bounded and holds fewer than max depth levels
+    ///
+    /// Books without an overflow buffer keep no window and just thin.
+    fn window_exhausted(&self) -> bool {
+        self.overflow_levels > 0
+            && ((self.bid_window.is_some() && self.bids.len() < self.max_depth)
+                || (self.ask_window.is_some() && self.asks.len() < self.max_depth))
+    }
 
-        true
+    /// Whether `price` lies beyond the window its side is known complete
+    /// through
+    fn beyond_window(&self, side: Side, price: Decimal) -> bool {
+        match side {
+            Side::Bid => self.bid_window.is_some_and(|edge| price < edge),
+            Side::Ask => self.ask_window.is_some_and(|edge| price > edge),
+        }
+    }
+
+    /// Best bid and ask if the bid is at or above the ask
+    ///
+    /// Overflow levels are always worse than visible ones, so they only
+    /// count once their side's visible levels are gone.
+    fn crossing(&self) -> Option<(Decimal, Decimal)> {
+        let (bid, _) = self.bids.best().or_else(|| self.bid_overflow.best())?;
+        let (ask, _) = self.asks.best().or_else(|| self.ask_overflow.best())?;
+        (bid >= ask).then_some((bid, ask))
+    }
+
+    /// Drop the best levels of the side the update didn't set until the
+    /// book no longer crosses; those are the stale ones
+    fn trim_crossing(&mut self, update: &DepthDelta) {
+        while let Some((best_bid, _)) = self.crossing() {
+            let bid_fresh = update
+                .bids
+                .iter()
+                .any(|l| l.price == best_bid && !l.quantity.is_zero());
+            if bid_fresh {
+                if self.asks.pop_best().is_none() {
+                    self.ask_overflow.pop_best();
+                }
+            } else if self.bids.pop_best().is_none() {
+                self.bid_overflow.pop_best();
+            }
+        }
+    }
+
+    /// Quantities of the levels an update touches, before it is applied
+    fn undo_log(&self, update: &DepthDelta) -> Vec<LevelUndo> {
+        let bids = update.bids.iter().map(|level| {
+            (
+                Side::Bid,
+                level.price,
+                self.bids.get(level.price),
+                self.bid_overflow.get(level.price),
+            )
+        });
+        let asks = update.asks.iter().map(|level| {
+            (
+                Side::Ask,
+                level.price,
+                self.asks.get(level.price),
+                self.ask_overflow.get(level.price),
+            )
+        });
+        bids.chain(asks).collect()
+    }
+
+    /// Put a level back as recorded by `undo_log`
+    fn restore_level(
+        &mut self,
+        side: Side,
+        price: Decimal,
+        visible: Option<Decimal>,
+        overflow: Option<Decimal>,
+    ) {
+        fn restore(levels: &mut Levels, price: Decimal, quantity: Option<Decimal>) {
+            match quantity {
+                Some(quantity) => levels.insert(price, quantity),
+                None => levels.remove(price),
+            };
+        }
+        let (visible_levels, overflow_levels) = match side {
+            Side::Bid => (&mut self.bids, &mut self.bid_overflow),
+            Side::Ask => (&mut self.asks, &mut self.ask_overflow),
+        };
+        restore(visible_levels, price, visible);
+        restore(overflow_levels, price, overflow);
     }
 
     /// Update a single price level
+    ///
+    /// Levels currently held in the overflow buffer are updated in place,
+    /// and levels beyond the side's window are ignored. Prices off the
+    /// instrument's tick are counted but still applied, since a tick size
+    /// change may not have been picked up yet.
     fn update_side(&mut self, side: Side, level: &PriceLevel) {
+        if self
+            .instrument
+            .is_some_and(|instrument| !instrument.on_tick(level.price))
+        {
+            #[cfg(feature = "runtime")]
+            OFF_TICK.with_label_values(&[&self.symbol]).inc();
+        }
+        if self.beyond_window(side, level.price) {
+            return;
+        }
+        self.top_dirty |= self.reaches_top(side, level.price);
+        let (visible, overflow, totals) = match side {
+            Side::Bid => (&mut self.bids, &mut self.bid_overflow, &mut self.bid_totals),
+            Side::Ask => (&mut self.asks, &mut self.ask_overflow, &mut self.ask_totals),
+        };
+        // A new level behind the buffer's best belongs in the buffer, or
+        // refilling a thinned side would skip the better buffered levels
+        let behind_overflow = overflow.best().is_some_and(|(best, _)| match side {
+            Side::Bid => level.price < best,
+            Side::Ask => level.price > best,
+        });
+        if overflow.get(level.price).is_some()
+            || (behind_overflow && visible.get(level.price).is_none())
+        {
+            if level.quantity == Decimal::ZERO {
+                overflow.remove(level.price);
+            } else {
+                overflow.insert(level.price, level.quantity);
+            }
+        } else {
+            let previous = if level.quantity == Decimal::ZERO {
+                visible.remove(level.price)
+            } else {
+                visible.insert(level.price, level.quantity)
+            };
+            totals.depth = totals
+                .depth
+                .saturating_add(level.quantity - previous.unwrap_or_default());
+        }
+    }
+
+    /// Whether a change at `price` can affect the top window sums
+    fn reaches_top(&self, side: Side, price: Decimal) -> bool {
         match side {
-            Side::Bid => {
-                if level.quantity == Decimal::ZERO {
-                    self.bids.remove(&Reverse(level.price));
-                } else {
-                    self.bids.insert(Reverse(level.price), level.quantity);
+            Side::Bid => self.bid_totals.edge.is_none_or(|edge| price >= edge),
+            Side::Ask => self.ask_totals.edge.is_none_or(|edge| price <= edge),
+        }
+    }
+
+    /// Retake both sides' totals from the book, after changes that bypass
+    /// `update_side`
+    fn recompute_totals(&mut self) {
+        self.bid_totals.depth = saturating_sum(self.bids.iter().map(|(_, q)| q));
+        self.ask_totals.depth = saturating_sum(self.asks.iter().map(|(_, q)| q));
+        self.refresh_top();
+    }
+
+    /// Retake the top window sums and edges of both sides
+    fn refresh_top(&mut self) {
+        let config = &self.metrics_config;
+        let window = config
+            .imbalance_levels
+            .max(config.weighted_imbalance_levels);
+        let top = |levels: &mut dyn Iterator<Item = (Decimal, Decimal)>,
+                   totals: &mut SideTotals| {
+            let mut weight = Decimal::ONE;
+            totals.top = Decimal::ZERO;
+            totals.weighted_top = Decimal::ZERO;
+            totals.edge = None;
+            for (i, (price, quantity)) in levels.take(window).enumerate() {
+                if i < config.imbalance_levels {
+                    totals.top = totals.top.saturating_add(quantity);
                 }
-            }
-            Side::Ask => {
-                if level.quantity == Decimal::ZERO {
-                    self.asks.remove(&level.price);
-                } else {
-                    self.asks.insert(level.price, level.quantity);
+                if i < config.weighted_imbalance_levels {
+                    totals.weighted_top = totals
+                        .weighted_top
+                        .saturating_add(quantity.saturating_mul(weight));
+                    weight *= config.weighted_imbalance_decay;
+                }
+                if i + 1 == window {
+                    totals.edge = Some(price);
                 }
             }
-        }
+        };
+        top(&mut self.bids.iter(), &mut self.bid_totals);
+        top(&mut self.asks.iter(), &mut self.ask_totals);
+        self.top_dirty = false;
     }
 
     /// Trim the book to max depth
+    ///
+    /// Trimmed levels move to the bounded overflow buffers, and the visible
+    /// book is refilled from them when it thins out, so depth doesn't erode
+    /// between snapshots.
+    /// Levels dropped from a full buffer narrow their side's window.
+    ///
+    /// Books without a buffer and per-order books keep no window; the
+    /// former thin, the latter reload a side that runs short from its
+    /// orders.
     fn trim_depth(&mut self) {
-        while self.bids.len() > self.max_depth {
-            self.bids.pop_last();
+        let windowed = self.orders.is_none() && self.overflow_levels > 0;
+        trim_side(
+            &mut self.bids,
+            &mut self.bid_overflow,
+            &mut self.bid_totals,
+            windowed.then_some(&mut self.bid_window),
+            self.max_depth,
+            self.overflow_levels,
+        );
+        trim_side(
+            &mut self.asks,
+            &mut self.ask_overflow,
+            &mut self.ask_totals,
+            windowed.then_some(&mut self.ask_window),
+            self.max_depth,
+            self.overflow_levels,
+        );
+        if let Some(orders) = &self.orders {
+            let short = |visible: &Levels, side| {
+                visible.len() < self.max_depth && orders.depth(side) > visible.len()
+            };
+            let (bids_short, asks_short) =
+                (short(&self.bids, Side::Bid), short(&self.asks, Side::Ask));
+            if bids_short {
+                self.reload_side(Side::Bid);
+            }
+            if asks_short {
+                self.reload_side(Side::Ask);
+            }
+        }
+    }
+
+    /// Refill a per-order book's side from its orders' totals
+    fn reload_side(&mut self, side: Side) {
+        let Some(orders) = &self.orders else {
+            return;
+        };
+        let levels = orders.levels(side);
+        let (visible, overflow) = match side {
+            Side::Bid => (&mut self.bids, &mut self.bid_overflow),
+            Side::Ask => (&mut self.asks, &mut self.ask_overflow),
+        };
+        visible.clear();
+        overflow.clear();
+        for (i, level) in levels
+            .iter()
+            .take(self.max_depth + self.overflow_levels)
+            .enumerate()
+        {
+            if i < self.max_depth {
+                visible.insert(level.price, level.quantity);
+            } else {
+                overflow.insert(level.price, level.quantity);
+            }
         }
-        while self.asks.len() > self.max_depth {
-            self.asks.pop_last();
+        self.recompute_totals();
+    }
+
+    /// Whether the last applied update changed the best bid or ask price
+    /// or size
+    pub fn bbo_changed(&self) -> bool {
+        self.bbo_changed
+    }
+
+    /// Current best bid and ask
+    pub fn bbo(&self) -> BboChanged {
+        let level = |(price, quantity)| Level { price, quantity };
+        BboChanged {
+            symbol: self.symbol.clone(),
+            timestamp: self.last_update_time,
+            last_update_id: self.last_update_id,
+            best_bid: self.bids.best().map(level),
+            best_ask: self.asks.best().map(level),
         }
     }
 
     /// Get best bid price
     pub fn best_bid(&self) -> Option<Decimal> {
-        self.bids.first_key_value().map(|(Reverse(p), _)| *p)
+        self.bids.best().map(|(price, _)| price)
     }
 
     /// Get best ask price
     pub fn best_ask(&self) -> Option<Decimal> {
-        self.asks.first_key_value().map(|(p, _)| *p)
+        self.asks.best().map(|(price, _)| price)
     }
 
     /// Get mid price
     pub fn mid_price(&self) -> Option<Decimal> {
         match (self.best_bid(), self.best_ask()) {
-            (Some(bid), Some(ask)) => Some((bid + ask) / Decimal::from(2)),
+            (Some(bid), Some(ask)) => Some(bid.checked_add(ask)? / Decimal::from(2)),
             _ => None,
         }
     }
 
+    /// Get microprice: `(bid * ask_qty + ask * bid_qty) / (bid_qty + ask_qty)`
+    ///
+    /// Leans towards the side with less resting size, where the next
+    /// trade is more likely to move the price.
+    pub fn microprice(&self) -> Option<Decimal> {
+        let (bid, bid_qty) = self.bids.best()?;
+        let (ask, ask_qty) = self.asks.best()?;
+
+        let total = bid_qty.checked_add(ask_qty)?;
+        if total > Decimal::ZERO {
+            bid.checked_mul(ask_qty)?
+                .checked_add(ask.checked_mul(bid_qty)?)?
+                .checked_div(total)
+        } else {
+            None
+        }
+    }
+
+    /// Average fill price of a market order for `quantity`, walking the
+    /// visible book: `Side::Bid` buys from the asks, `Side::Ask` sells into
+    /// the bids. `None` if the book is too thin to fill it.
+    pub fn vwap_for_quantity(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
+        if quantity <= Decimal::ZERO {
+            return None;
+        }
+        let levels = match side {
+            Side::Bid => self.asks.iter(),
+            Side::Ask => self.bids.iter(),
+        };
+
+        let mut remaining = quantity;
+        let mut notional = Decimal::ZERO;
+        for (price, available) in levels {
+            let fill = remaining.min(available);
+            notional = notional.checked_add(price.checked_mul(fill)?)?;
+            remaining -= fill;
+            if remaining.is_zero() {
+                return notional.checked_div(quantity);
+            }
+        }
+        None
+    }
+
+    /// Slippage of a market order for `quantity` from mid, in basis points
+    /// (positive for both sides); see `vwap_for_quantity`
+    pub fn price_impact_bps(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
+        let mid = self.mid_price().filter(|mid| *mid > Decimal::ZERO)?;
+        let vwap = self.vwap_for_quantity(side, quantity)?;
+        let slippage = match side {
+            Side::Bid => vwap - mid,
+            Side::Ask => mid - vwap,
+        };
+        slippage.checked_div(mid)?.checked_mul(Decimal::from(10000))
+    }
+
+    /// Visible levels of one side as (distance from mid in bps, quantity),
+    /// nearest first
+    fn levels_from_mid(&self, side: Side) -> Option<Vec<(Decimal, Decimal)>> {
+        let mid = self.mid_price().filter(|mid| *mid > Decimal::ZERO)?;
+        // Levels too far out to measure count as infinitely far
+        let bps = |price: Decimal| {
+            (price - mid)
+                .abs()
+                .checked_div(mid)
+                .and_then(|ratio| ratio.checked_mul(Decimal::from(10000)))
+                .unwrap_or(Decimal::MAX)
+        };
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        Some(levels.iter().map(|(p, q)| (bps(p), q)).collect())
+    }
+
+    /// Resting quantity priced within `bps` of mid on one side
+    pub fn depth_within_bps(&self, side: Side, bps: Decimal) -> Decimal {
+        saturating_sum(
+            self.levels_from_mid(side)
+                .unwrap_or_default()
+                .into_iter()
+                .take_while(|(distance, _)| *distance <= bps)
+                .map(|(_, quantity)| quantity),
+        )
+    }
+
+    /// How fast cumulative depth grows with distance from mid: the
+    /// least-squares slope (through the origin) of cumulative quantity
+    /// against distance in bps over the visible levels of one side
+    pub fn slope(&self, side: Side) -> Option<Decimal> {
+        let mut cumulative = Decimal::ZERO;
+        let mut xy = Decimal::ZERO;
+        let mut xx = Decimal::ZERO;
+        for (distance, quantity) in self.levels_from_mid(side)? {
+            cumulative = cumulative.checked_add(quantity)?;
+            xy = xy.checked_add(distance.checked_mul(cumulative)?)?;
+            xx = xx.checked_add(distance.checked_mul(distance)?)?;
+        }
+        (xx > Decimal::ZERO).then(|| xy.checked_div(xx)).flatten()
+    }
+
     /// Get spread in basis points
     pub fn spread_bps(&self) -> Option<Decimal> {
         match (self.best_bid(), self.best_ask(), self.mid_price()) {
-            (Some(bid), Some(ask), Some(mid)) if mid > Decimal::ZERO => {
-                Some((ask - bid) / mid * Decimal::from(10000))
-            }
+            (Some(bid), Some(ask), Some(mid)) if mid > Decimal::ZERO => (ask - bid)
+                .checked_div(mid)?
+                .checked_mul(Decimal::from(10000)),
             _ => None,
         }
     }
 
     /// Calculate order book imbalance at top N levels
     pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
-        let bid_volume: Decimal = self.bids.iter().take(levels).map(|(_, q)| q).sum();
-        let ask_volume: Decimal = self.asks.iter().take(levels).map(|(_, q)| q).sum();
+        let bid_volume = saturating_sum(self.bids.iter().take(levels).map(|(_, q)| q));
+        let ask_volume = saturating_sum(self.asks.iter().take(levels).map(|(_, q)| q));
 
-        let total = bid_volume + ask_volume;
+        let total = bid_volume.saturating_add(ask_volume);
         if total > Decimal::ZERO {
             Some((bid_volume - ask_volume) / total)
         } else {
@@ -172,32 +941,26 @@ impl OrderBook {
 
         // Helper to calculate decay^i without maths feature
         let pow = |exp: usize| -> Decimal {
-            (0..exp).fold(Decimal::ONE, |acc, _| acc * decay)
+            (0..exp).fold(Decimal::ONE, |acc, _| acc.saturating_mul(decay))
         };
 
-        let bid_weighted: Decimal = self
-            .bids
-            .iter()
-            .take(levels)
-            .enumerate()
-            .map(|(i, (_, q))| {
-                let weight = pow(i);
-                *q * weight
-            })
-            .sum();
+        let bid_weighted = saturating_sum(
+            self.bids
+                .iter()
+                .take(levels)
+                .enumerate()
+                .map(|(i, (_, q))| q.saturating_mul(pow(i))),
+        );
 
-        let ask_weighted: Decimal = self
-            .asks
-            .iter()
-            .take(levels)
-            .enumerate()
-            .map(|(i, (_, q))| {
-                let weight = pow(i);
-                *q * weight
-            })
-            .sum();
+        let ask_weighted = saturating_sum(
+            self.asks
+                .iter()
+                .take(levels)
+                .enumerate()
+                .map(|(i, (_, q))| q.saturating_mul(pow(i))),
+        );
 
-        let total = bid_weighted + ask_weighted;
+        let total = bid_weighted.saturating_add(ask_weighted);
         if total > Decimal::ZERO {
             Some((bid_weighted - ask_weighted) / total)
         } else {
@@ -210,6 +973,63 @@ impl OrderBook {
         self.initialized
     }
 
+    /// Initialize from a snapshot saved by an earlier run; the book is
+    /// held back from publishing until an update continues its sequence
+    pub fn resume(&mut self, snapshot: &BookSnapshot) {
+        self.init_snapshot(snapshot);
+        self.resumed = true;
+    }
+
+    /// Whether the book was restored from disk and awaits confirmation
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// All retained levels (visible and overflow) as a snapshot, to save
+    /// and `resume` from
+    pub fn snapshot(&self) -> BookSnapshot {
+        let levels = |visible: &Levels, overflow: &Levels| {
+            visible
+                .iter()
+                .chain(overflow.iter())
+                .map(|(price, quantity)| PriceLevel { price, quantity })
+                .collect()
+        };
+        BookSnapshot {
+            venue: self.venue,
+            symbol: self.symbol.clone(),
+            last_update_id: self.last_update_id,
+            bids: levels(&self.bids, &self.bid_overflow),
+            asks: levels(&self.asks, &self.ask_overflow),
+        }
+    }
+
+    /// Check if the warm-up requirement since the last snapshot is met
+    ///
+    /// Books restored from disk additionally wait for an update that
+    /// continues their sequence.
+    pub fn is_warmed_up(&self) -> bool {
+        let Some(initialized_at) = self.initialized_at else {
+            return false;
+        };
+        if self.resumed {
+            return false;
+        }
+
+        initialized_at.elapsed() >= self.warmup.min_duration
+            && self.updates_since_init >= self.warmup.min_updates
+    }
+
+    /// Symbol of the book
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Event time (ms) of the last applied update
+    pub fn last_update_time(&self) -> u64 {
+        self.last_update_time
+    }
+
     /// Get last update ID
     pub fn last_update_id(&self) -> u64 {
         self.last_update_id
@@ -217,42 +1037,125 @@ impl OrderBook {
 
     /// Get current state for publishing
     pub fn state(&self) -> OrderBookState {
+        let bids: Vec<Level> = self
+            .bids
+            .iter()
+            .map(|(price, quantity)| Level { price, quantity })
+            .collect();
+        let asks: Vec<Level> = self
+            .asks
+            .iter()
+            .map(|(price, quantity)| Level { price, quantity })
+            .collect();
         OrderBookState {
             symbol: self.symbol.clone(),
             timestamp: self.last_update_time,
             last_update_id: self.last_update_id,
-            bids: self
-                .bids
+            checksum: Some(checksum(&bids, &asks)),
+            bids,
+            asks,
+            metrics: self.metrics(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: self.instrument,
+        }
+    }
+
+    /// Calculate order book metrics
+    ///
+    /// Depth and the imbalances come from running totals; the remaining
+    /// metrics only look at the top of the book unless configured (slope,
+    /// depth bands, price impact).
+    pub fn metrics(&self) -> OrderBookMetrics {
+        let config = &self.metrics_config;
+        let enabled = |metric| config.is_enabled(metric);
+        let ratio = |bid: Decimal, ask: Decimal| {
+            let total = bid.saturating_add(ask);
+            (total > Decimal::ZERO).then(|| (bid - ask) / total)
+        };
+        let (bids, asks) = (&self.bid_totals, &self.ask_totals);
+        let mid_price = self.mid_price();
+        OrderBookMetrics {
+            mid_price,
+            microprice: self.microprice().filter(|_| enabled(Metric::Microprice)),
+            spread_bps: self.spread_bps(),
+            realized_vol: self.volatility.as_ref().and_then(|v| v.realized_vol()),
+            imbalance: enabled(Metric::Imbalance)
+                .then(|| ratio(bids.top, asks.top))
+                .flatten(),
+            weighted_imbalance: (enabled(Metric::WeightedImbalance) && mid_price.is_some())
+                .then(|| ratio(bids.weighted_top, asks.weighted_top))
+                .flatten(),
+            bid_depth: enabled(Metric::Depth).then_some(bids.depth),
+            ask_depth: enabled(Metric::Depth).then_some(asks.depth),
+            bid_levels: self.bids.len(),
+            ask_levels: self.asks.len(),
+            bid_slope: enabled(Metric::Slope)
+                .then(|| self.slope(Side::Bid))
+                .flatten(),
+            ask_slope: enabled(Metric::Slope)
+                .then(|| self.slope(Side::Ask))
+                .flatten(),
+            depth_bands: self
+                .depth_bands
                 .iter()
-                .map(|(Reverse(p), q)| Level {
-                    price: *p,
-                    quantity: *q,
+                .map(|&bps| DepthBand {
+                    bps,
+                    bid_depth: self.depth_within_bps(Side::Bid, bps),
+                    ask_depth: self.depth_within_bps(Side::Ask, bps),
                 })
                 .collect(),
-            asks: self
-                .asks
+            price_impact: self
+                .impact_sizes
                 .iter()
-                .map(|(p, q)| Level {
-                    price: *p,
-                    quantity: *q,
+                .map(|&quantity| PriceImpact {
+                    quantity,
+                    buy_bps: self.price_impact_bps(Side::Bid, quantity),
+                    sell_bps: self.price_impact_bps(Side::Ask, quantity),
                 })
                 .collect(),
-            metrics: self.calculate_metrics(),
         }
     }
+}
 
-    /// Calculate order book metrics
-    fn calculate_metrics(&self) -> OrderBookMetrics {
-        OrderBookMetrics {
-            mid_price: self.mid_price(),
-            spread_bps: self.spread_bps(),
-            imbalance: self.imbalance(5),
-            weighted_imbalance: self
-                .weighted_imbalance(10, Decimal::from_str_exact("0.9").unwrap()),
-            bid_depth: self.bids.values().copied().sum(),
-            ask_depth: self.asks.values().copied().sum(),
-            bid_levels: self.bids.len(),
-            ask_levels: self.asks.len(),
+/// Sum of quantities, pinned at `Decimal::MAX` rather than overflowing on
+/// absurd sizes
+fn saturating_sum(quantities: impl Iterator<Item = Decimal>) -> Decimal {
+    quantities.fold(Decimal::ZERO, Decimal::saturating_add)
+}
+
+/// Trim one side to `max_depth` visible levels; see `OrderBook::trim_depth`
+fn trim_side(
+    visible: &mut Levels,
+    overflow: &mut Levels,
+    totals: &mut SideTotals,
+    window: Option<&mut Option<Decimal>>,
+    max_depth: usize,
+    overflow_levels: usize,
+) {
+    while visible.len() > max_depth {
+        if let Some((price, qty)) = visible.pop_worst() {
+            totals.depth = totals.depth.saturating_sub(qty);
+            overflow.insert(price, qty);
+        }
+    }
+    while visible.len() < max_depth {
+        let Some((price, qty)) = overflow.pop_best() else {
+            break;
+        };
+        totals.depth = totals.depth.saturating_add(qty);
+        visible.insert(price, qty);
+    }
+    if overflow.len() > overflow_levels {
+        while overflow.len() > overflow_levels {
+            overflow.pop_worst();
+        }
+        // Nothing is known past the levels still held
+        if let Some(window) = window {
+            *window = overflow
+                .worst()
+                .or_else(|| visible.worst())
+                .map(|(price, _)| price);
         }
     }
 }
@@ -260,11 +1163,14 @@ impl OrderBook {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::event::Venue;
     use rust_decimal_macros::dec;
 
     fn create_test_book() -> OrderBook {
         let mut book = OrderBook::new("BTCUSDT", 10);
-        let snapshot = OrderBookSnapshot {
+        let snapshot = BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
             last_update_id: 100,
             bids: vec![
                 PriceLevel {
@@ -304,6 +1210,156 @@ mod tests {
         assert_eq!(book.mid_price(), Some(dec!(50000.5)));
     }
 
+    #[test]
+    fn test_microprice() {
+        let book = create_test_book();
+        // (50000 * 1.5 + 50001 * 1.0) / 2.5, below mid as the bid is thinner
+        assert_eq!(book.microprice(), Some(dec!(50000.4)));
+    }
+
+    #[test]
+    fn test_vwap_and_price_impact() {
+        let book = create_test_book();
+        // Buying 2.0: 1.5 @ 50001 + 0.5 @ 50002
+        assert_eq!(
+            book.vwap_for_quantity(Side::Bid, dec!(2.0)),
+            Some(dec!(50001.25))
+        );
+        assert_eq!(
+            book.vwap_for_quantity(Side::Ask, dec!(1.0)),
+            Some(dec!(50000))
+        );
+        assert_eq!(book.vwap_for_quantity(Side::Ask, dec!(3.5)), None);
+
+        let buy = book.price_impact_bps(Side::Bid, dec!(2.0)).unwrap();
+        let sell = book.price_impact_bps(Side::Ask, dec!(1.0)).unwrap();
+        assert!(buy > sell && sell > Decimal::ZERO);
+
+        let book = create_test_book().with_impact_sizes(vec![dec!(1.0), dec!(5.0)]);
+        let impact = book.state().metrics.price_impact;
+        assert_eq!(impact.len(), 2);
+        assert_eq!(impact[0].sell_bps, Some(sell));
+        assert_eq!(impact[1].buy_bps, None);
+    }
+
+    #[test]
+    fn test_depth_bands_and_slope() {
+        let book = create_test_book().with_depth_bands(vec![dec!(0.1), dec!(1)]);
+        // Mid 50000.5: best levels are 0.1 bps away, second levels 0.3 bps
+        let bands = book.state().metrics.depth_bands;
+        assert_eq!(bands[0].bid_depth, dec!(1.0));
+        assert_eq!(bands[0].ask_depth, dec!(1.5));
+        assert_eq!(bands[1].bid_depth, dec!(3.0));
+        assert_eq!(bands[1].ask_depth, dec!(4.0));
+
+        // The ask side holds more quantity at the same distances
+        let bid = book.slope(Side::Bid).unwrap();
+        let ask = book.slope(Side::Ask).unwrap();
+        assert!(ask > bid && bid > Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_metrics_config() {
+        let book = create_test_book().with_metrics_config(MetricsConfig {
+            imbalance_levels: 1,
+            disabled: [Metric::Slope, Metric::Depth].into(),
+            ..MetricsConfig::default()
+        });
+        let metrics = book.state().metrics;
+
+        // (1.0 - 1.5) / 2.5 at the top level only
+        assert_eq!(metrics.imbalance, Some(dec!(-0.2)));
+        assert!(metrics.weighted_imbalance.is_some());
+        assert_eq!(metrics.bid_slope, None);
+        assert_eq!(metrics.bid_depth, None);
+        assert_eq!(metrics.bid_levels, 2);
+    }
+
+    #[test]
+    fn test_running_totals_match_book() {
+        let config = MetricsConfig {
+            imbalance_levels: 3,
+            weighted_imbalance_levels: 4,
+            ..MetricsConfig::default()
+        };
+        let mut book = OrderBook::new("BTCUSDT", 8)
+            .with_overflow_levels(8)
+            .with_metrics_config(config.clone());
+        // Same book stored in trees rather than sorted vecs
+        let mut tree_book = OrderBook::new("BTCUSDT", 8)
+            .with_overflow_levels(8)
+            .with_compact_levels(false);
+        let snapshot = BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: (0..12)
+                .map(|i| PriceLevel {
+                    price: Decimal::from(100 - i),
+                    quantity: Decimal::from(i + 1),
+                })
+                .collect(),
+            asks: (0..12)
+                .map(|i| PriceLevel {
+                    price: Decimal::from(101 + i),
+                    quantity: Decimal::from(i + 1),
+                })
+                .collect(),
+        };
+        book.init_snapshot(&snapshot);
+        tree_book.init_snapshot(&snapshot);
+
+        // Pseudo-random changes around the top, deep in the book and in
+        // the overflow
+        let mut seed = 7u64;
+        let mut next = |n: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for id in 101..400 {
+            let level = |offset: u64, quantity: u64| PriceLevel {
+                price: Decimal::from(offset),
+                quantity: Decimal::from(quantity),
+            };
+            let update = DepthDelta {
+                venue: Venue::Binance,
+                event_time: id,
+                symbol: "BTCUSDT".to_string(),
+                first_update_id: id,
+                final_update_id: id,
+                bids: vec![level(100 - next(15), next(4))],
+                asks: vec![level(101 + next(15), next(4))],
+            };
+            book.apply_update(&update).unwrap();
+            tree_book.apply_update(&update).unwrap();
+
+            let metrics = book.metrics();
+            let state = book.state();
+            let sum = |levels: &[Level]| levels.iter().map(|l| l.quantity).sum::<Decimal>();
+            let pairs = |levels: &[Level]| {
+                levels
+                    .iter()
+                    .map(|l| (l.price, l.quantity))
+                    .collect::<Vec<_>>()
+            };
+            let tree_state = tree_book.state();
+            assert_eq!(pairs(&state.bids), pairs(&tree_state.bids));
+            assert_eq!(pairs(&state.asks), pairs(&tree_state.asks));
+            assert_eq!(metrics.bid_depth, Some(sum(&state.bids)));
+            assert_eq!(metrics.ask_depth, Some(sum(&state.asks)));
+            assert_eq!(metrics.imbalance, book.imbalance(config.imbalance_levels));
+            assert_eq!(
+                metrics.weighted_imbalance,
+                book.weighted_imbalance(
+                    config.weighted_imbalance_levels,
+                    config.weighted_imbalance_decay
+                )
+            );
+        }
+    }
+
     #[test]
     fn test_imbalance() {
         let book = create_test_book();
@@ -316,8 +1372,8 @@ mod tests {
     #[test]
     fn test_apply_update() {
         let mut book = create_test_book();
-        let update = DepthUpdate {
-            event_type: "depthUpdate".to_string(),
+        let update = DepthDelta {
+            venue: Venue::Binance,
             event_time: 1000,
             symbol: "BTCUSDT".to_string(),
             first_update_id: 101,
@@ -329,7 +1385,321 @@ mod tests {
             asks: vec![],
         };
 
-        assert!(book.apply_update(&update));
+        assert!(book.apply_update(&update).unwrap());
         assert_eq!(book.last_update_id(), 102);
     }
+
+    #[test]
+    fn test_resumed_book_needs_continuous_updates() {
+        let saved = create_test_book().snapshot();
+        let update = |first_update_id, final_update_id| DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id,
+            final_update_id,
+            bids: vec![],
+            asks: vec![],
+        };
+
+        let mut book = OrderBook::new("BTCUSDT", 10);
+        book.resume(&saved);
+        assert_eq!(book.best_ask(), Some(dec!(50001)));
+        assert!(book.is_resumed() && !book.is_warmed_up());
+
+        // The stream continues where the saved book left off
+        assert!(book.apply_update(&update(99, 101)).unwrap());
+        assert!(!book.is_resumed() && book.is_warmed_up());
+
+        // Missed diffs discard the book
+        assert!(matches!(
+            book.apply_update(&update(105, 106)),
+            Err(MarketDataError::SequenceMismatch {
+                expected: 102,
+                got: 105
+            })
+        ));
+        assert!(!book.is_initialized());
+    }
+
+    #[test]
+    fn test_warmup_requires_updates_after_snapshot() {
+        let mut book = OrderBook::new("BTCUSDT", 10).with_warmup(WarmupPolicy {
+            min_duration: std::time::Duration::ZERO,
+            min_updates: 2,
+        });
+        assert!(!book.is_warmed_up());
+
+        book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![],
+            asks: vec![],
+        });
+        assert!(!book.is_warmed_up());
+
+        for id in [101, 102] {
+            book.apply_update(&DepthDelta {
+                venue: Venue::Binance,
+                event_time: 1000,
+                symbol: "BTCUSDT".to_string(),
+                first_update_id: id,
+                final_update_id: id,
+                bids: vec![],
+                asks: vec![],
+            })
+            .unwrap();
+        }
+        assert!(book.is_warmed_up());
+    }
+
+    #[test]
+    fn test_overflow_refills_visible_book() {
+        let mut book = OrderBook::new("BTCUSDT", 2).with_overflow_levels(2);
+        let level = |price, quantity| PriceLevel { price, quantity };
+        book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![
+                level(dec!(100), dec!(1)),
+                level(dec!(99), dec!(1)),
+                level(dec!(98), dec!(1)),
+                level(dec!(97), dec!(1)),
+                level(dec!(96), dec!(1)),
+            ],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        assert_eq!(book.state().bids.len(), 2);
+
+        // Overflow level updated while hidden, then surfaced by removals
+        let update = DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![
+                level(dec!(98), dec!(5)),
+                level(dec!(100), dec!(0)),
+                level(dec!(99), dec!(0)),
+            ],
+            asks: vec![],
+        };
+        assert!(book.apply_update(&update).unwrap());
+
+        let state = book.state();
+        assert_eq!(state.bids.len(), 2);
+        assert_eq!(state.bids[0].price, dec!(98));
+        assert_eq!(state.bids[0].quantity, dec!(5));
+        // 96 was beyond the overflow capacity and is gone
+        assert_eq!(state.bids[1].price, dec!(97));
+
+        // Below 97 nothing is known any more, so a new level there would
+        // leave a gap and is ignored
+        let update = DepthDelta {
+            first_update_id: 102,
+            final_update_id: 102,
+            bids: vec![level(dec!(95), dec!(3))],
+            ..update
+        };
+        assert!(book.apply_update(&update).unwrap());
+        assert_eq!(book.state().bids.len(), 2);
+        assert_eq!(book.state().bids[1].price, dec!(97));
+
+        // Losing a known level leaves too few to fill the depth
+        let update = DepthDelta {
+            first_update_id: 103,
+            final_update_id: 103,
+            bids: vec![level(dec!(98), dec!(0))],
+            ..update
+        };
+        assert!(matches!(
+            book.apply_update(&update),
+            Err(MarketDataError::DepthWindowExhausted { .. })
+        ));
+        assert!(!book.is_initialized());
+    }
+
+    #[test]
+    fn test_book_without_overflow_thins_instead_of_resyncing() {
+        let mut book = OrderBook::new("BTCUSDT", 2);
+        let level = |price, quantity| PriceLevel { price, quantity };
+        book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![
+                level(dec!(100), dec!(1)),
+                level(dec!(99), dec!(1)),
+                level(dec!(98), dec!(1)),
+                level(dec!(97), dec!(1)),
+            ],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+
+        // Cancelling a visible level leaves the side one short
+        let update = DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![level(dec!(99), dec!(0))],
+            asks: vec![],
+        };
+        assert!(book.apply_update(&update).unwrap());
+        assert!(book.is_initialized());
+        let prices: Vec<Decimal> = book.state().bids.iter().map(|l| l.price).collect();
+        assert_eq!(prices, vec![dec!(100)]);
+
+        // With no window, a new level anywhere is taken
+        let update = DepthDelta {
+            first_update_id: 102,
+            final_update_id: 102,
+            bids: vec![level(dec!(95), dec!(2))],
+            ..update
+        };
+        assert!(book.apply_update(&update).unwrap());
+        let prices: Vec<Decimal> = book.state().bids.iter().map(|l| l.price).collect();
+        assert_eq!(prices, vec![dec!(100), dec!(95)]);
+    }
+
+    #[test]
+    fn test_new_level_behind_overflow_is_buffered() {
+        let mut book = OrderBook::new("BTCUSDT", 2).with_overflow_levels(2);
+        let level = |price, quantity| PriceLevel { price, quantity };
+        book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![],
+            asks: vec![
+                level(dec!(101), dec!(1)),
+                level(dec!(102), dec!(1)),
+                level(dec!(104), dec!(1)),
+            ],
+        });
+
+        // 101 leaves while 105 arrives: 104 is refilled ahead of it
+        let update = DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![],
+            asks: vec![level(dec!(101), dec!(0)), level(dec!(105), dec!(1))],
+        };
+        assert!(book.apply_update(&update).unwrap());
+
+        let prices: Vec<Decimal> = book.state().asks.iter().map(|l| l.price).collect();
+        assert_eq!(prices, vec![dec!(102), dec!(104)]);
+    }
+
+    #[test]
+    fn test_order_updates_feed_levels() {
+        use crate::event::{OrderUpdateKind, RestingOrder};
+
+        let order = |id: &str, side, price, quantity| RestingOrder {
+            order_id: id.to_string(),
+            side,
+            price,
+            quantity,
+        };
+        let mut book = OrderBook::new("BTCUSD", 2);
+        book.init_orders(&OrderSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSD".to_string(),
+            sequence: 10,
+            orders: vec![
+                order("a", Side::Bid, dec!(100), dec!(1)),
+                order("b", Side::Bid, dec!(100), dec!(2)),
+                order("c", Side::Bid, dec!(99), dec!(1)),
+                order("d", Side::Bid, dec!(98), dec!(4)),
+                order("e", Side::Ask, dec!(101), dec!(1)),
+            ],
+        });
+        let state = book.state();
+        assert_eq!(state.bids.len(), 2);
+        assert_eq!(state.bids[0].quantity, dec!(3));
+
+        let update = |sequence, kind, id: &str, price, quantity| OrderUpdate {
+            venue: Venue::Binance,
+            symbol: "BTCUSD".to_string(),
+            event_time: 1000,
+            sequence,
+            kind,
+            order_id: id.to_string(),
+            side: Side::Bid,
+            price,
+            quantity,
+        };
+        let partial_fill = update(11, OrderUpdateKind::Modify, "a", dec!(100), dec!(0.5));
+        assert!(book.apply_order_update(&partial_fill).unwrap());
+        assert_eq!(book.state().bids[0].quantity, dec!(2.5));
+
+        // The trimmed 98 level comes back from the orders once 99 goes
+        let cancel = update(12, OrderUpdateKind::Delete, "c", dec!(0), dec!(0));
+        assert!(book.apply_order_update(&cancel).unwrap());
+        let state = book.state();
+        assert_eq!(state.bids.len(), 2);
+        assert_eq!(state.bids[1].price, dec!(98));
+        assert_eq!(state.bids[1].quantity, dec!(4));
+
+        // A rejected order leaves the tracked orders alone: re-pricing it
+        // later is an unknown order, not a move onto the 100 level
+        book.crossed_policy = CrossedBookPolicy::Reject;
+        let crossing = update(13, OrderUpdateKind::Add, "x", dec!(101.5), dec!(1));
+        assert!(matches!(
+            book.apply_order_update(&crossing),
+            Err(MarketDataError::CrossedBook { .. })
+        ));
+        let reprice = update(14, OrderUpdateKind::Modify, "x", dec!(100), dec!(1));
+        assert!(book.apply_order_update(&reprice).unwrap());
+        assert_eq!(book.state().bids[0].quantity, dec!(2.5));
+
+        let gap = update(16, OrderUpdateKind::Add, "f", dec!(97), dec!(1));
+        assert!(matches!(
+            book.apply_order_update(&gap),
+            Err(MarketDataError::SequenceMismatch {
+                expected: 15,
+                got: 16
+            })
+        ));
+        assert!(!book.is_initialized());
+    }
+
+    #[test]
+    fn test_crossed_book_policies() {
+        let level = |price, quantity| PriceLevel { price, quantity };
+        // Bid at 50001.5 crosses the ask at 50001
+        let crossing = DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![level(dec!(50001.5), dec!(2))],
+            asks: vec![],
+        };
+
+        let mut book = create_test_book().with_crossed_policy(CrossedBookPolicy::Reject);
+        assert!(matches!(
+            book.apply_update(&crossing),
+            Err(MarketDataError::CrossedBook { .. })
+        ));
+        assert_eq!(book.best_bid(), Some(dec!(50000)));
+        assert_eq!(book.last_update_id(), 101);
+
+        let mut book = create_test_book().with_crossed_policy(CrossedBookPolicy::Trim);
+        assert!(book.apply_update(&crossing).unwrap());
+        assert_eq!(book.best_bid(), Some(dec!(50001.5)));
+        assert_eq!(book.best_ask(), Some(dec!(50002)));
+
+        let mut book = create_test_book().with_crossed_policy(CrossedBookPolicy::Resync);
+        assert!(book.apply_update(&crossing).is_err());
+        assert!(!book.is_initialized());
+    }
 }
@@ -0,0 +1,105 @@
+//! Checksum of a book's top levels
+//!
+//! Published states and deltas carry a CRC32 of the book they describe, so
+//! a consumer keeping its own copy from deltas can check after each one
+//! that it hasn't diverged, and request a snapshot if it has.
+//!
+//! The checksum covers the best `CHECKSUM_LEVELS` levels of each side,
+//! interleaved best first as `bid_price:bid_qty:ask_price:ask_qty:...`,
+//! a side that runs out of levels simply contributing none. Prices and
+//! quantities are written in their shortest decimal form, without
+//! trailing zeros or an exponent (`67250.1`, `0.003`), and the text's
+//! CRC32 (IEEE, as zlib's `crc32`) is the checksum.
+
+use std::fmt::{self, Write};
+
+use super::Level;
+
+/// Levels per side the checksum covers
+pub const CHECKSUM_LEVELS: usize = 25;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 fed by formatting, so no text is allocated
+struct Crc32(u32);
+
+impl Write for Crc32 {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.0 = TABLE[((self.0 ^ byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+        Ok(())
+    }
+}
+
+/// Checksum of the book with these sides, best levels first
+pub fn checksum(bids: &[Level], asks: &[Level]) -> u32 {
+    let mut crc = Crc32(!0);
+    let mut first = true;
+    for i in 0..CHECKSUM_LEVELS {
+        for level in [bids.get(i), asks.get(i)].into_iter().flatten() {
+            if !std::mem::take(&mut first) {
+                let _ = crc.write_char(':');
+            }
+            let _ = write!(
+                crc,
+                "{}:{}",
+                level.price.normalize(),
+                level.quantity.normalize()
+            );
+        }
+    }
+    !crc.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn level(price: Decimal, quantity: Decimal) -> Level {
+        Level { price, quantity }
+    }
+
+    #[test]
+    fn test_checksum_of_interleaved_shortest_levels() {
+        // CRC32 of "0:0" and of "100.5:2:101:0.25:100:1", as zlib computes
+        // them
+        assert_eq!(checksum(&[level(dec!(0), dec!(0))], &[]), 0xD85A_D257);
+        let bids = [
+            level(dec!(100.50), dec!(2.000)),
+            level(dec!(100.0), dec!(1)),
+        ];
+        let asks = [level(dec!(101.00000000), dec!(0.25000000))];
+        assert_eq!(checksum(&bids, &asks), 0xB95A_BE01);
+
+        // Levels beyond the covered depth don't count
+        let deep: Vec<Level> = (0..40)
+            .map(|i| level(Decimal::from(1000 - i), dec!(1)))
+            .collect();
+        assert_eq!(
+            checksum(&deep, &asks),
+            checksum(&deep[..CHECKSUM_LEVELS], &asks)
+        );
+        assert_ne!(checksum(&deep, &[]), checksum(&deep[1..], &[]));
+    }
+}
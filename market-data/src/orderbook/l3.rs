@@ -0,0 +1,183 @@
+//! Per-order (L3) tracking
+//!
+//! Venues with per-order feeds (e.g. Coinbase's full channel, Bitstamp's
+//! live orders) report individual orders being added, modified and
+//! deleted. `OrderTracker` keeps those orders and the total quantity they
+//! add up to at each price, which is what the L2 book is fed.
+
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+use super::Side;
+use crate::event::{OrderUpdate, OrderUpdateKind, PriceLevel, RestingOrder};
+
+/// Resting orders of one book and their per-price totals
+#[derive(Debug, Clone, Default)]
+pub(super) struct OrderTracker {
+    orders: HashMap<String, RestingOrder>,
+    bid_totals: BTreeMap<Decimal, Decimal>,
+    ask_totals: BTreeMap<Decimal, Decimal>,
+}
+
+impl OrderTracker {
+    /// Tracker holding `orders`
+    pub fn new(orders: &[RestingOrder]) -> Self {
+        let mut tracker = Self::default();
+        for order in orders.iter().filter(|o| o.quantity > Decimal::ZERO) {
+            tracker.add(order.clone());
+        }
+        tracker
+    }
+
+    /// Aggregated levels of one side, best first
+    pub fn levels(&self, side: Side) -> Vec<PriceLevel> {
+        let level = |(price, quantity): (&Decimal, &Decimal)| PriceLevel {
+            price: *price,
+            quantity: *quantity,
+        };
+        match side {
+            Side::Bid => self.bid_totals.iter().rev().map(level).collect(),
+            Side::Ask => self.ask_totals.iter().map(level).collect(),
+        }
+    }
+
+    /// Number of prices with resting orders on a side
+    pub fn depth(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bid_totals.len(),
+            Side::Ask => self.ask_totals.len(),
+        }
+    }
+
+    /// New totals of the levels an update would change, without applying
+    /// it; a zero total removes the level
+    pub fn changes(&self, update: &OrderUpdate) -> Vec<(Side, PriceLevel)> {
+        let mut changes: Vec<(Side, PriceLevel)> = Vec::with_capacity(2);
+        let mut adjust = |side: Side, price: Decimal, delta: Decimal| match changes
+            .iter_mut()
+            .find(|(s, level)| *s == side && level.price == price)
+        {
+            Some((_, level)) => level.quantity = level.quantity.saturating_add(delta),
+            None => {
+                let quantity = self.total(side, price).saturating_add(delta);
+                changes.push((side, PriceLevel { price, quantity }));
+            }
+        };
+        if let Some(old) = self.orders.get(&update.order_id) {
+            adjust(old.side, old.price, -old.quantity);
+        } else if update.kind != OrderUpdateKind::Add {
+            // Orders that never rested, e.g. filled on arrival
+            return Vec::new();
+        }
+        if update.kind != OrderUpdateKind::Delete && update.quantity > Decimal::ZERO {
+            adjust(update.side, update.price, update.quantity);
+        }
+        changes
+    }
+
+    /// Apply an update to the tracked orders, returning the order it
+    /// replaced for `undo`
+    pub fn apply(&mut self, update: &OrderUpdate) -> Option<RestingOrder> {
+        let replaced = self.orders.remove(&update.order_id);
+        if let Some(old) = &replaced {
+            self.adjust(old.side, old.price, -old.quantity);
+        } else if update.kind != OrderUpdateKind::Add {
+            return None;
+        }
+        if update.kind != OrderUpdateKind::Delete && update.quantity > Decimal::ZERO {
+            self.add(RestingOrder {
+                order_id: update.order_id.clone(),
+                side: update.side,
+                price: update.price,
+                quantity: update.quantity,
+            });
+        }
+        replaced
+    }
+
+    /// Revert `apply` of an update, given the order it replaced
+    pub fn undo(&mut self, update: &OrderUpdate, replaced: Option<RestingOrder>) {
+        if let Some(added) = self.orders.remove(&update.order_id) {
+            self.adjust(added.side, added.price, -added.quantity);
+        }
+        if let Some(order) = replaced {
+            self.add(order);
+        }
+    }
+
+    fn add(&mut self, order: RestingOrder) {
+        self.adjust(order.side, order.price, order.quantity);
+        self.orders.insert(order.order_id.clone(), order);
+    }
+
+    fn total(&self, side: Side, price: Decimal) -> Decimal {
+        let totals = match side {
+            Side::Bid => &self.bid_totals,
+            Side::Ask => &self.ask_totals,
+        };
+        totals.get(&price).copied().unwrap_or_default()
+    }
+
+    fn adjust(&mut self, side: Side, price: Decimal, delta: Decimal) {
+        let totals = match side {
+            Side::Bid => &mut self.bid_totals,
+            Side::Ask => &mut self.ask_totals,
+        };
+        let total = totals.entry(price).or_default();
+        *total = total.saturating_add(delta);
+        if *total <= Decimal::ZERO {
+            totals.remove(&price);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use rust_decimal_macros::dec;
+
+    fn update(kind: OrderUpdateKind, id: &str, price: Decimal, quantity: Decimal) -> OrderUpdate {
+        OrderUpdate {
+            venue: Venue::Binance,
+            symbol: "BTCUSD".to_string(),
+            event_time: 0,
+            sequence: 0,
+            kind,
+            order_id: id.to_string(),
+            side: Side::Bid,
+            price,
+            quantity,
+        }
+    }
+
+    #[test]
+    fn test_orders_aggregate_to_levels() {
+        let mut tracker = OrderTracker::new(&[]);
+        for u in [
+            update(OrderUpdateKind::Add, "a", dec!(100), dec!(1)),
+            update(OrderUpdateKind::Add, "b", dec!(100), dec!(2)),
+            update(OrderUpdateKind::Add, "c", dec!(99), dec!(4)),
+        ] {
+            tracker.apply(&u);
+        }
+        assert_eq!(tracker.levels(Side::Bid)[0].quantity, dec!(3));
+
+        // Re-pricing moves quantity between levels
+        let modify = update(OrderUpdateKind::Modify, "b", dec!(99), dec!(1.5));
+        let changes = tracker.changes(&modify);
+        assert_eq!(changes[0].1.quantity, dec!(1));
+        assert_eq!(changes[1].1.quantity, dec!(5.5));
+        tracker.apply(&modify);
+
+        tracker.apply(&update(OrderUpdateKind::Delete, "a", dec!(0), dec!(0)));
+        let levels = tracker.levels(Side::Bid);
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].price, dec!(99));
+        assert_eq!(levels[0].quantity, dec!(5.5));
+
+        // Unknown orders leave the book alone
+        let unknown = update(OrderUpdateKind::Delete, "zz", dec!(99), dec!(1));
+        assert!(tracker.changes(&unknown).is_empty());
+    }
+}
@@ -0,0 +1,381 @@
+//! Price level storage for one side of a book
+//!
+//! `Levels` keeps a side's levels best first, keyed so the best level sorts
+//! lowest (bid prices are negated). By default prices and quantities are
+//! kept as `Decimal`s. With a `TickScale` they are kept as `i64` counts of
+//! the symbol's price and quantity increments instead, so the map compares
+//! and moves plain integers; values are converted on the way in (updates)
+//! and on the way out (states and metrics). Values finer than the scale are
+//! rounded to it, which exchange data never needs.
+//!
+//! Sides holding at most `COMPACT_MAX_LEVELS` levels are kept in a sorted
+//! `Vec` searched by bisection rather than a `BTreeMap`: at those sizes the
+//! contiguous layout beats the tree's pointer chasing, and the shifts on
+//! insert and remove are cheap.
+
+use rust_decimal::Decimal;
+use std::collections::{btree_map, BTreeMap};
+use std::slice;
+
+use super::Side;
+
+/// Largest capacity kept in a sorted `Vec` instead of a `BTreeMap`
+pub const COMPACT_MAX_LEVELS: usize = 50;
+
+/// Decimal places of a symbol's price and quantity increments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickScale {
+    pub price_decimals: u32,
+    pub quantity_decimals: u32,
+}
+
+impl TickScale {
+    /// Scale for a symbol's tick size and lot step size
+    pub fn from_sizes(tick_size: Decimal, step_size: Decimal) -> Self {
+        Self {
+            price_decimals: tick_size.normalize().scale(),
+            quantity_decimals: step_size.normalize().scale(),
+        }
+    }
+}
+
+/// `value` as a count of `10^-decimals` units, rounding half away from
+/// zero when it has more decimals
+fn to_units(value: Decimal, decimals: u32) -> i64 {
+    let mantissa = value.mantissa();
+    let scale = value.scale();
+    let units = if scale <= decimals {
+        mantissa.saturating_mul(10i128.pow(decimals - scale))
+    } else {
+        let divisor = 10i128.pow(scale - decimals);
+        let (quotient, remainder) = (mantissa / divisor, mantissa % divisor);
+        if remainder.abs() * 2 >= divisor {
+            quotient + mantissa.signum()
+        } else {
+            quotient
+        }
+    };
+    units.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Ordered map backed by a tree or, for small sides, a sorted `Vec`
+#[derive(Debug, Clone)]
+enum Map<K, V> {
+    Tree(BTreeMap<K, V>),
+    Compact(Vec<(K, V)>),
+}
+
+impl<K: Ord + Copy, V: Copy> Map<K, V> {
+    fn new(capacity: usize, compact: bool) -> Self {
+        if compact {
+            // Room for the level inserted before a trim
+            Map::Compact(Vec::with_capacity(capacity + 1))
+        } else {
+            Map::Tree(BTreeMap::new())
+        }
+    }
+
+    fn get(&self, key: &K) -> Option<V> {
+        match self {
+            Map::Tree(map) => map.get(key).copied(),
+            Map::Compact(vec) => vec
+                .binary_search_by(|(k, _)| k.cmp(key))
+                .ok()
+                .map(|i| vec[i].1),
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self {
+            Map::Tree(map) => map.insert(key, value),
+            Map::Compact(vec) => match vec.binary_search_by(|(k, _)| k.cmp(&key)) {
+                Ok(i) => Some(std::mem::replace(&mut vec[i].1, value)),
+                Err(i) => {
+                    vec.insert(i, (key, value));
+                    None
+                }
+            },
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        match self {
+            Map::Tree(map) => map.remove(key),
+            Map::Compact(vec) => vec
+                .binary_search_by(|(k, _)| k.cmp(key))
+                .ok()
+                .map(|i| vec.remove(i).1),
+        }
+    }
+
+    fn last(&self) -> Option<(K, V)> {
+        match self {
+            Map::Tree(map) => map.last_key_value().map(|(k, v)| (*k, *v)),
+            Map::Compact(vec) => vec.last().copied(),
+        }
+    }
+
+    fn pop_first(&mut self) -> Option<(K, V)> {
+        match self {
+            Map::Tree(map) => map.pop_first(),
+            Map::Compact(vec) => (!vec.is_empty()).then(|| vec.remove(0)),
+        }
+    }
+
+    fn pop_last(&mut self) -> Option<(K, V)> {
+        match self {
+            Map::Tree(map) => map.pop_last(),
+            Map::Compact(vec) => vec.pop(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Map::Tree(map) => map.len(),
+            Map::Compact(vec) => vec.len(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Map::Tree(map) => map.clear(),
+            Map::Compact(vec) => vec.clear(),
+        }
+    }
+
+    fn iter(&self) -> MapIter<'_, K, V> {
+        match self {
+            Map::Tree(map) => MapIter::Tree(map.iter()),
+            Map::Compact(vec) => MapIter::Compact(vec.iter()),
+        }
+    }
+}
+
+enum MapIter<'a, K, V> {
+    Tree(btree_map::Iter<'a, K, V>),
+    Compact(slice::Iter<'a, (K, V)>),
+}
+
+impl<K: Copy, V: Copy> Iterator for MapIter<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            MapIter::Tree(iter) => iter.next().map(|(k, v)| (*k, *v)),
+            MapIter::Compact(iter) => iter.next().copied(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Store {
+    Decimal(Map<Decimal, Decimal>),
+    Ticks(TickScale, Map<i64, i64>),
+}
+
+/// One side's price levels, best first
+#[derive(Debug, Clone)]
+pub(super) struct Levels {
+    side: Side,
+    store: Store,
+}
+
+impl Levels {
+    /// Empty side for about `capacity` levels, stored as fixed-point when
+    /// a scale is given and in a `Vec` when `compact`
+    pub fn new(side: Side, scale: Option<TickScale>, capacity: usize, compact: bool) -> Self {
+        let store = match scale {
+            Some(scale) => Store::Ticks(scale, Map::new(capacity, compact)),
+            None => Store::Decimal(Map::new(capacity, compact)),
+        };
+        Self { side, store }
+    }
+
+    /// Sort key of a price: ascending from the best level
+    fn key(side: Side, price: Decimal) -> Decimal {
+        match side {
+            Side::Bid => -price,
+            Side::Ask => price,
+        }
+    }
+
+    fn tick_key(side: Side, scale: TickScale, price: Decimal) -> i64 {
+        let ticks = to_units(price, scale.price_decimals);
+        match side {
+            Side::Bid => -ticks,
+            Side::Ask => ticks,
+        }
+    }
+
+    fn from_ticks(side: Side, scale: TickScale, key: i64, lots: i64) -> (Decimal, Decimal) {
+        let ticks = match side {
+            Side::Bid => -key,
+            Side::Ask => key,
+        };
+        (
+            Decimal::new(ticks, scale.price_decimals),
+            Decimal::new(lots, scale.quantity_decimals),
+        )
+    }
+
+    /// Quantity resting at `price`
+    pub fn get(&self, price: Decimal) -> Option<Decimal> {
+        match &self.store {
+            Store::Decimal(map) => map.get(&Self::key(self.side, price)),
+            Store::Ticks(scale, map) => map
+                .get(&Self::tick_key(self.side, *scale, price))
+                .map(|lots| Decimal::new(lots, scale.quantity_decimals)),
+        }
+    }
+
+    /// Set the quantity at `price`, returning the previous one
+    pub fn insert(&mut self, price: Decimal, quantity: Decimal) -> Option<Decimal> {
+        match &mut self.store {
+            Store::Decimal(map) => map.insert(Self::key(self.side, price), quantity),
+            Store::Ticks(scale, map) => map
+                .insert(
+                    Self::tick_key(self.side, *scale, price),
+                    to_units(quantity, scale.quantity_decimals),
+                )
+                .map(|lots| Decimal::new(lots, scale.quantity_decimals)),
+        }
+    }
+
+    /// Remove the level at `price`, returning its quantity
+    pub fn remove(&mut self, price: Decimal) -> Option<Decimal> {
+        match &mut self.store {
+            Store::Decimal(map) => map.remove(&Self::key(self.side, price)),
+            Store::Ticks(scale, map) => map
+                .remove(&Self::tick_key(self.side, *scale, price))
+                .map(|lots| Decimal::new(lots, scale.quantity_decimals)),
+        }
+    }
+
+    /// Best price and its quantity
+    pub fn best(&self) -> Option<(Decimal, Decimal)> {
+        self.iter().next()
+    }
+
+    /// Worst price and its quantity
+    pub fn worst(&self) -> Option<(Decimal, Decimal)> {
+        let side = self.side;
+        match &self.store {
+            Store::Decimal(map) => map.last().map(|(k, q)| (Self::key(side, k), q)),
+            Store::Ticks(scale, map) => map
+                .last()
+                .map(|(k, lots)| Self::from_ticks(side, *scale, k, lots)),
+        }
+    }
+
+    /// Remove and return the best level
+    pub fn pop_best(&mut self) -> Option<(Decimal, Decimal)> {
+        let side = self.side;
+        match &mut self.store {
+            Store::Decimal(map) => map.pop_first().map(|(k, q)| (Self::key(side, k), q)),
+            Store::Ticks(scale, map) => map
+                .pop_first()
+                .map(|(k, lots)| Self::from_ticks(side, *scale, k, lots)),
+        }
+    }
+
+    /// Remove and return the worst level
+    pub fn pop_worst(&mut self) -> Option<(Decimal, Decimal)> {
+        let side = self.side;
+        match &mut self.store {
+            Store::Decimal(map) => map.pop_last().map(|(k, q)| (Self::key(side, k), q)),
+            Store::Ticks(scale, map) => map
+                .pop_last()
+                .map(|(k, lots)| Self::from_ticks(side, *scale, k, lots)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.store {
+            Store::Decimal(map) => map.len(),
+            Store::Ticks(_, map) => map.len(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match &mut self.store {
+            Store::Decimal(map) => map.clear(),
+            Store::Ticks(_, map) => map.clear(),
+        }
+    }
+
+    /// Levels as (price, quantity), best first
+    pub fn iter(&self) -> Iter<'_> {
+        let inner = match &self.store {
+            Store::Decimal(map) => IterInner::Decimal(map.iter()),
+            Store::Ticks(scale, map) => IterInner::Ticks(*scale, map.iter()),
+        };
+        Iter {
+            side: self.side,
+            inner,
+        }
+    }
+}
+
+/// Iterator over a side's levels, best first
+pub(super) struct Iter<'a> {
+    side: Side,
+    inner: IterInner<'a>,
+}
+
+enum IterInner<'a> {
+    Decimal(MapIter<'a, Decimal, Decimal>),
+    Ticks(TickScale, MapIter<'a, i64, i64>),
+}
+
+impl Iterator for Iter<'_> {
+    type Item = (Decimal, Decimal);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IterInner::Decimal(iter) => iter.next().map(|(k, q)| (Levels::key(self.side, k), q)),
+            IterInner::Ticks(scale, iter) => iter
+                .next()
+                .map(|(k, lots)| Levels::from_ticks(self.side, *scale, k, lots)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_representations_agree() {
+        let scale = TickScale::from_sizes(dec!(0.01000000), dec!(0.00001000));
+        assert_eq!(scale.price_decimals, 2);
+        assert_eq!(scale.quantity_decimals, 5);
+
+        for (side, compact) in [(Side::Bid, false), (Side::Ask, false), (Side::Bid, true)] {
+            let mut decimal = Levels::new(side, None, 10, false);
+            let mut ticks = Levels::new(side, Some(scale), 10, compact);
+            for levels in [&mut decimal, &mut ticks] {
+                levels.insert(dec!(100.01), dec!(1.5));
+                levels.insert(dec!(99.990000), dec!(0.00002));
+                levels.insert(dec!(100.00), dec!(3));
+                assert_eq!(levels.insert(dec!(100.00), dec!(2)), Some(dec!(3)));
+                assert_eq!(levels.remove(dec!(250)), None);
+            }
+            let decimal_levels: Vec<_> = decimal.iter().collect();
+            assert_eq!(decimal_levels, ticks.iter().collect::<Vec<_>>());
+            assert_eq!(decimal.worst(), ticks.worst());
+            assert_eq!(decimal.pop_worst(), ticks.pop_worst());
+            assert_eq!(decimal.best(), ticks.best());
+        }
+
+        assert_eq!(to_units(dec!(1.005), 2), 101);
+        assert_eq!(to_units(dec!(-1.004), 2), -100);
+        assert_eq!(to_units(dec!(7), 3), 7000);
+
+        let mut bids = Levels::new(Side::Bid, None, 10, true);
+        bids.insert(dec!(99.99), dec!(1));
+        bids.insert(dec!(100.01), dec!(1));
+        assert_eq!(bids.pop_best(), Some((dec!(100.01), dec!(1))));
+        assert_eq!(bids.len(), 1);
+    }
+}
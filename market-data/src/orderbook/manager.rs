@@ -3,15 +3,49 @@
 //! Manages multiple order books for different symbols.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use super::{OrderBook, OrderBookState};
-use crate::parser::{DepthUpdate, OrderBookSnapshot};
+use rust_decimal::Decimal;
+use tokio::sync::broadcast;
+
+use super::{
+    BboChanged, BookEvent, CrossedBookPolicy, MetricsConfig, OrderBook, OrderBookState, TickScale,
+    WarmupPolicy,
+};
+use crate::error::Result;
+use crate::event::{BookSnapshot, DepthDelta, OrderSnapshot, OrderUpdate};
+use crate::exchange_info::InstrumentInfo;
+
+/// Events buffered per symbol before a slow subscriber starts lagging
+const EVENT_CAPACITY: usize = 1024;
 
 /// Manages order books for multiple symbols
 #[derive(Debug, Default)]
 pub struct OrderBookManager {
     books: HashMap<String, OrderBook>,
     max_depth: usize,
+    /// Depth overrides per symbol
+    symbol_depths: HashMap<String, usize>,
+    overflow_levels: usize,
+    warmup: WarmupPolicy,
+    /// Price impact reference sizes per symbol
+    impact_sizes: HashMap<String, Vec<Decimal>>,
+    /// Realized volatility window and sampling interval (ms), when enabled
+    volatility: Option<(u64, u64)>,
+    /// Realized volatility window overrides per symbol (ms, 0 = off)
+    volatility_windows: HashMap<String, u64>,
+    /// Distances from mid (bps) whose cumulative depth is published
+    depth_bands: Vec<Decimal>,
+    /// Handling of updates that cross or lock a book
+    crossed_policy: CrossedBookPolicy,
+    /// Metrics settings per symbol; others use the defaults
+    metrics_configs: HashMap<String, MetricsConfig>,
+    /// Symbols whose books store fixed-point levels, and their scales
+    tick_scales: HashMap<String, TickScale>,
+    /// Trading rules per symbol, from `exchangeInfo`
+    instruments: HashMap<String, InstrumentInfo>,
+    /// Book event channels per subscribed symbol
+    events: HashMap<String, broadcast::Sender<BookEvent>>,
 }
 
 impl OrderBookManager {
@@ -20,6 +54,18 @@ impl OrderBookManager {
         Self {
             books: HashMap::new(),
             max_depth: 20,
+            symbol_depths: HashMap::new(),
+            overflow_levels: 0,
+            warmup: WarmupPolicy::default(),
+            impact_sizes: HashMap::new(),
+            volatility: None,
+            volatility_windows: HashMap::new(),
+            depth_bands: Vec::new(),
+            crossed_policy: CrossedBookPolicy::default(),
+            metrics_configs: HashMap::new(),
+            tick_scales: HashMap::new(),
+            instruments: HashMap::new(),
+            events: HashMap::new(),
         }
     }
 
@@ -28,23 +74,253 @@ impl OrderBookManager {
         Self {
             books: HashMap::new(),
             max_depth,
+            symbol_depths: HashMap::new(),
+            overflow_levels: 0,
+            warmup: WarmupPolicy::default(),
+            impact_sizes: HashMap::new(),
+            volatility: None,
+            volatility_windows: HashMap::new(),
+            depth_bands: Vec::new(),
+            crossed_policy: CrossedBookPolicy::default(),
+            metrics_configs: HashMap::new(),
+            tick_scales: HashMap::new(),
+            instruments: HashMap::new(),
+            events: HashMap::new(),
         }
     }
 
+    /// Keep these symbols' books at their own depth instead of the default
+    pub fn with_symbol_depths(mut self, depths: HashMap<String, usize>) -> Self {
+        self.symbol_depths = depths;
+        self
+    }
+
+    /// Retain trimmed levels beyond max depth to refill thinning books
+    pub fn with_overflow_levels(mut self, levels: usize) -> Self {
+        self.overflow_levels = levels;
+        self
+    }
+
+    /// Require a warm-up period after each snapshot before publishing
+    pub fn with_warmup(mut self, warmup: WarmupPolicy) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Publish price impact at these order sizes, per symbol
+    pub fn with_impact_sizes(mut self, sizes: HashMap<String, Vec<Decimal>>) -> Self {
+        self.impact_sizes = sizes;
+        self
+    }
+
+    /// Publish cumulative depth within these distances from mid (bps)
+    pub fn with_depth_bands(mut self, bands_bps: Vec<Decimal>) -> Self {
+        self.depth_bands = bands_bps;
+        self
+    }
+
+    /// Choose and tune the metrics computed, per symbol
+    pub fn with_metrics_configs(mut self, configs: HashMap<String, MetricsConfig>) -> Self {
+        self.metrics_configs = configs;
+        self
+    }
+
+    /// Store these symbols' levels as fixed-point integers of their scale
+    pub fn with_tick_scales(mut self, scales: HashMap<String, TickScale>) -> Self {
+        self.tick_scales = scales;
+        self
+    }
+
+    /// Validate and publish these trading rules, per symbol
+    pub fn with_instruments(mut self, instruments: HashMap<String, InstrumentInfo>) -> Self {
+        self.instruments = instruments;
+        self
+    }
+
+    /// Replace a symbol's trading rules, including on its live book
+    pub fn set_instrument(&mut self, symbol: &str, instrument: InstrumentInfo) {
+        if let Some(book) = self.books.get_mut(symbol) {
+            book.set_instrument(instrument);
+        }
+        self.instruments.insert(symbol.to_string(), instrument);
+    }
+
+    /// Handle updates that cross or lock a book according to `policy`
+    pub fn with_crossed_policy(mut self, policy: CrossedBookPolicy) -> Self {
+        self.crossed_policy = policy;
+        self
+    }
+
+    /// Track realized volatility of each book's mid price
+    pub fn with_volatility(mut self, window_ms: u64, sample_interval_ms: u64) -> Self {
+        self.volatility = Some((window_ms, sample_interval_ms));
+        self
+    }
+
+    /// Use these realized volatility windows (ms) instead of the default,
+    /// per symbol; 0 turns the estimate off for a symbol
+    pub fn with_volatility_windows(mut self, windows: HashMap<String, u64>) -> Self {
+        self.volatility_windows = windows;
+        self
+    }
+
+    /// One manager per symbol with this manager's settings, each taking
+    /// over its symbol's book and event channel if there is one
+    pub fn split(mut self, symbols: &[String]) -> Vec<OrderBookManager> {
+        symbols
+            .iter()
+            .map(|symbol| {
+                let mut manager = Self {
+                    books: HashMap::new(),
+                    max_depth: self.max_depth,
+                    symbol_depths: self.symbol_depths.clone(),
+                    overflow_levels: self.overflow_levels,
+                    warmup: self.warmup,
+                    impact_sizes: self.impact_sizes.clone(),
+                    volatility: self.volatility,
+                    volatility_windows: self.volatility_windows.clone(),
+                    depth_bands: self.depth_bands.clone(),
+                    crossed_policy: self.crossed_policy,
+                    metrics_configs: self.metrics_configs.clone(),
+                    tick_scales: self.tick_scales.clone(),
+                    instruments: self.instruments.clone(),
+                    events: HashMap::new(),
+                };
+                if let Some(book) = self.books.remove(symbol) {
+                    manager.books.insert(symbol.clone(), book);
+                }
+                if let Some(events) = self.events.remove(symbol) {
+                    manager.events.insert(symbol.clone(), events);
+                }
+                manager
+            })
+            .collect()
+    }
+
     /// Initialize an order book with a snapshot
-    pub fn init_book(&mut self, symbol: &str, snapshot: OrderBookSnapshot) {
-        let mut book = OrderBook::new(symbol, self.max_depth);
-        book.init_snapshot(&snapshot);
-        self.books.insert(symbol.to_string(), book);
-    }
-
-    /// Apply a depth update to the appropriate book
-    pub fn apply_update(&mut self, update: &DepthUpdate) -> bool {
-        if let Some(book) = self.books.get_mut(&update.symbol) {
-            book.apply_update(update)
-        } else {
-            false
+    pub fn init_book(&mut self, snapshot: &BookSnapshot) {
+        let mut book = self.new_book(&snapshot.symbol);
+        book.init_snapshot(snapshot);
+        self.insert_book(book);
+    }
+
+    /// Resume books saved by an earlier run; see `OrderBook::resume`
+    pub fn resume(&mut self, snapshots: &[BookSnapshot]) {
+        for snapshot in snapshots {
+            let mut book = self.new_book(&snapshot.symbol);
+            book.resume(snapshot);
+            self.insert_book(book);
+        }
+    }
+
+    /// Retained levels of every initialized book, to save for a warm
+    /// restart
+    pub fn snapshots(&self) -> Vec<BookSnapshot> {
+        self.books
+            .values()
+            .filter(|book| book.is_initialized())
+            .map(|book| book.snapshot())
+            .collect()
+    }
+
+    /// Initialize a per-order (L3) book with a snapshot of its orders
+    pub fn init_orders(&mut self, snapshot: &OrderSnapshot) {
+        let mut book = self.new_book(&snapshot.symbol);
+        book.init_orders(snapshot);
+        self.insert_book(book);
+    }
+
+    /// Empty book for `symbol` with the manager's settings
+    fn new_book(&mut self, symbol: &str) -> OrderBook {
+        let depth = self
+            .symbol_depths
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.max_depth);
+        let mut book = OrderBook::new(symbol, depth)
+            .with_overflow_levels(self.overflow_levels)
+            .with_warmup(self.warmup)
+            .with_impact_sizes(self.impact_sizes.get(symbol).cloned().unwrap_or_default())
+            .with_depth_bands(self.depth_bands.clone())
+            .with_crossed_policy(self.crossed_policy)
+            .with_metrics_config(
+                self.metrics_configs
+                    .get(symbol)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        if let Some(scale) = self.tick_scales.get(symbol) {
+            book = book.with_tick_scale(*scale);
         }
+        if let Some(instrument) = self.instruments.get(symbol) {
+            book = book.with_instrument(*instrument);
+        }
+        // Resyncs keep the volatility history
+        let previous = self
+            .books
+            .get_mut(symbol)
+            .and_then(|book| book.take_volatility());
+        if let Some(estimator) = previous {
+            book = book.with_volatility_estimator(estimator);
+        } else if let Some((window_ms, sample_interval_ms)) = self.volatility {
+            let window_ms = self
+                .volatility_windows
+                .get(symbol)
+                .copied()
+                .unwrap_or(window_ms);
+            if window_ms > 0 {
+                book = book.with_volatility(window_ms, sample_interval_ms);
+            }
+        }
+        book
+    }
+
+    /// Replace a symbol's book with a freshly initialized one
+    fn insert_book(&mut self, book: OrderBook) {
+        emit(&self.events, book.symbol(), || {
+            BookEvent::Snapshot(Arc::new(book.state()))
+        });
+        self.books.insert(book.symbol().to_string(), book);
+    }
+
+    /// Apply a depth update to the appropriate book; see
+    /// `OrderBook::apply_update`
+    pub fn apply_update(&mut self, update: &DepthDelta) -> Result<bool> {
+        let Some(book) = self.books.get_mut(&update.symbol) else {
+            return Ok(false);
+        };
+        let result = book.apply_update(update);
+        #[cfg(feature = "runtime")]
+        crate::metrics::book_update(&update.symbol, &result);
+        notify(&self.events, book, &result);
+        result
+    }
+
+    /// Apply a per-order (L3) update to the appropriate book; see
+    /// `OrderBook::apply_order_update`
+    pub fn apply_order_update(&mut self, update: &OrderUpdate) -> Result<bool> {
+        let Some(book) = self.books.get_mut(&update.symbol) else {
+            return Ok(false);
+        };
+        let result = book.apply_order_update(update);
+        #[cfg(feature = "runtime")]
+        crate::metrics::book_update(&update.symbol, &result);
+        notify(&self.events, book, &result);
+        result
+    }
+
+    /// Receive a symbol's book events: a `Snapshot` on each
+    /// (re)initialization, `BboChanged` when an applied depth update moved
+    /// the best bid or ask, followed by an `Update` once the book is warmed
+    /// up, and `Resync` when it is discarded
+    ///
+    /// States are only built while someone is subscribed. Receivers that
+    /// fall more than `EVENT_CAPACITY` events behind skip ahead (`Lagged`).
+    pub fn subscribe(&mut self, symbol: &str) -> broadcast::Receiver<BookEvent> {
+        self.events
+            .entry(symbol.to_string())
+            .or_insert_with(|| broadcast::channel(EVENT_CAPACITY).0)
+            .subscribe()
     }
 
     /// Get the state of a specific book
@@ -52,11 +328,29 @@ impl OrderBookManager {
         self.books.get(symbol).map(|book| book.state())
     }
 
+    /// Best bid and ask of a book if its last applied update changed them
+    pub fn bbo_change(&self, symbol: &str) -> Option<BboChanged> {
+        self.books
+            .get(symbol)
+            .filter(|book| book.bbo_changed())
+            .map(|book| book.bbo())
+    }
+
     /// Get states of all books
     pub fn get_all_states(&self) -> Vec<OrderBookState> {
         self.books.values().map(|book| book.state()).collect()
     }
 
+    /// Event time (ms) of the last update applied to each initialized
+    /// book; books with only a snapshot so far have none
+    pub fn update_times(&self) -> Vec<(String, u64)> {
+        self.books
+            .values()
+            .filter(|book| book.is_initialized() && book.last_update_time() > 0)
+            .map(|book| (book.symbol().to_string(), book.last_update_time()))
+            .collect()
+    }
+
     /// Check if a book is initialized
     pub fn is_initialized(&self, symbol: &str) -> bool {
         self.books
@@ -65,6 +359,32 @@ impl OrderBookManager {
             .unwrap_or(false)
     }
 
+    /// Check if a book was resumed from disk and awaits confirmation by
+    /// the diff stream
+    pub fn is_resumed(&self, symbol: &str) -> bool {
+        self.books
+            .get(symbol)
+            .map(|book| book.is_resumed())
+            .unwrap_or(false)
+    }
+
+    /// Check if a book has completed its warm-up period
+    pub fn is_warmed_up(&self, symbol: &str) -> bool {
+        self.books
+            .get(symbol)
+            .map(|book| book.is_warmed_up())
+            .unwrap_or(false)
+    }
+
+    /// Event time (ms) of the last update applied to a book; 0 before the
+    /// first
+    pub fn last_update_time(&self, symbol: &str) -> u64 {
+        self.books
+            .get(symbol)
+            .map(|book| book.last_update_time())
+            .unwrap_or(0)
+    }
+
     /// Get the last update ID for a symbol
     pub fn last_update_id(&self, symbol: &str) -> Option<u64> {
         self.books.get(symbol).map(|book| book.last_update_id())
@@ -80,3 +400,122 @@ impl OrderBookManager {
         self.books.contains_key(symbol)
     }
 }
+
+/// Send the events following an update to `book`
+fn notify(
+    events: &HashMap<String, broadcast::Sender<BookEvent>>,
+    book: &OrderBook,
+    result: &Result<bool>,
+) {
+    match result {
+        Ok(true) => {
+            if book.bbo_changed() {
+                emit(events, book.symbol(), || BookEvent::BboChanged(book.bbo()));
+            }
+            if book.is_warmed_up() {
+                emit(events, book.symbol(), || {
+                    BookEvent::Update(Arc::new(book.state()))
+                });
+            }
+        }
+        Err(_) if !book.is_initialized() => emit(events, book.symbol(), || BookEvent::Resync {
+            symbol: book.symbol().to_string(),
+        }),
+        _ => {}
+    }
+}
+
+/// Send a symbol's event if anyone is listening; `event` is only built then
+fn emit(
+    events: &HashMap<String, broadcast::Sender<BookEvent>>,
+    symbol: &str,
+    event: impl FnOnce() -> BookEvent,
+) {
+    if let Some(sender) = events.get(symbol).filter(|s| s.receiver_count() > 0) {
+        let _ = sender.send(event());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PriceLevel, Venue};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_subscribe_receives_book_events() {
+        let mut manager = OrderBookManager::new();
+        let mut events = manager.subscribe("BTCUSDT");
+        let level = |price, quantity| PriceLevel { price, quantity };
+
+        manager.init_book(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![level(dec!(100), dec!(1))],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        let update = DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1000,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![level(dec!(100), dec!(3))],
+            asks: vec![],
+        };
+        assert!(manager.apply_update(&update).unwrap());
+
+        match events.try_recv().unwrap() {
+            BookEvent::Snapshot(state) => assert_eq!(state.last_update_id, 100),
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+        match events.try_recv().unwrap() {
+            BookEvent::BboChanged(bbo) => {
+                assert_eq!(bbo.best_bid.unwrap().quantity, dec!(3));
+                assert_eq!(bbo.best_ask.unwrap().price, dec!(101));
+            }
+            other => panic!("expected a BBO change, got {:?}", other),
+        }
+        match events.try_recv().unwrap() {
+            BookEvent::Update(state) => assert_eq!(state.bids[0].quantity, dec!(3)),
+            other => panic!("expected an update, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        // Below the best bid: no BBO change
+        let update = DepthDelta {
+            first_update_id: 102,
+            final_update_id: 102,
+            bids: vec![level(dec!(99), dec!(2))],
+            ..update
+        };
+        assert!(manager.apply_update(&update).unwrap());
+        assert!(manager.bbo_change("BTCUSDT").is_none());
+        assert!(matches!(events.try_recv(), Ok(BookEvent::Update(_))));
+
+        // A book still warming up moves its BBO but holds back its state
+        let mut manager = OrderBookManager::new().with_warmup(WarmupPolicy {
+            min_updates: 5,
+            ..Default::default()
+        });
+        let mut events = manager.subscribe("BTCUSDT");
+        manager.init_book(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![level(dec!(100), dec!(1))],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        let update = DepthDelta {
+            first_update_id: 101,
+            final_update_id: 101,
+            bids: vec![level(dec!(100.5), dec!(1))],
+            ..update
+        };
+        assert!(manager.apply_update(&update).unwrap());
+        assert!(matches!(events.try_recv(), Ok(BookEvent::Snapshot(_))));
+        assert!(matches!(events.try_recv(), Ok(BookEvent::BboChanged(_))));
+        assert!(events.try_recv().is_err());
+    }
+}
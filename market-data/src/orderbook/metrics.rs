@@ -2,6 +2,67 @@
 
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Book metrics that can be switched off per symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Microprice,
+    Imbalance,
+    WeightedImbalance,
+    /// Total bid and ask depth
+    Depth,
+    /// Bid and ask book slope
+    Slope,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "microprice" => Ok(Metric::Microprice),
+            "imbalance" => Ok(Metric::Imbalance),
+            "weighted_imbalance" => Ok(Metric::WeightedImbalance),
+            "depth" => Ok(Metric::Depth),
+            "slope" => Ok(Metric::Slope),
+            other => Err(format!("Invalid metric: {}", other)),
+        }
+    }
+}
+
+/// Which metrics a book computes, and over how much of the book
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsConfig {
+    /// Levels per side summed for `imbalance`
+    pub imbalance_levels: usize,
+    /// Levels per side weighted for `weighted_imbalance`
+    pub weighted_imbalance_levels: usize,
+    /// Weight multiplier per level away from the top
+    pub weighted_imbalance_decay: Decimal,
+    /// Metrics left empty (`None`)
+    pub disabled: HashSet<Metric>,
+}
+
+impl MetricsConfig {
+    /// Whether `metric` is computed
+    pub fn is_enabled(&self, metric: Metric) -> bool {
+        !self.disabled.contains(&metric)
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            imbalance_levels: 5,
+            weighted_imbalance_levels: 10,
+            weighted_imbalance_decay: Decimal::new(9, 1),
+            disabled: HashSet::new(),
+        }
+    }
+}
 
 /// Computed metrics for an order book
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -9,9 +70,16 @@ pub struct OrderBookMetrics {
     /// Mid price (average of best bid and ask)
     pub mid_price: Option<Decimal>,
 
+    /// Microprice: best bid and ask weighted by the opposite side's size
+    pub microprice: Option<Decimal>,
+
     /// Spread in basis points
     pub spread_bps: Option<Decimal>,
 
+    /// Annualized realized volatility of the mid price over the configured
+    /// window
+    pub realized_vol: Option<Decimal>,
+
     /// Simple imbalance: (bid_vol - ask_vol) / (bid_vol + ask_vol)
     pub imbalance: Option<Decimal>,
 
@@ -19,16 +87,60 @@ pub struct OrderBookMetrics {
     pub weighted_imbalance: Option<Decimal>,
 
     /// Total bid depth (volume)
-    pub bid_depth: Decimal,
+    pub bid_depth: Option<Decimal>,
 
     /// Total ask depth (volume)
-    pub ask_depth: Decimal,
+    pub ask_depth: Option<Decimal>,
 
     /// Number of bid levels
     pub bid_levels: usize,
 
     /// Number of ask levels
     pub ask_levels: usize,
+
+    /// Slippage at each configured reference size
+    #[serde(default)]
+    pub price_impact: Vec<PriceImpact>,
+
+    /// Bid book slope: cumulative quantity per bps of distance from mid
+    #[serde(default)]
+    pub bid_slope: Option<Decimal>,
+
+    /// Ask book slope: cumulative quantity per bps of distance from mid
+    #[serde(default)]
+    pub ask_slope: Option<Decimal>,
+
+    /// Cumulative depth within each configured distance from mid
+    #[serde(default)]
+    pub depth_bands: Vec<DepthBand>,
+}
+
+/// Resting quantity within a distance of mid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthBand {
+    /// Distance from mid in basis points
+    pub bps: Decimal,
+
+    /// Bid quantity priced within `bps` of mid
+    pub bid_depth: Decimal,
+
+    /// Ask quantity priced within `bps` of mid
+    pub ask_depth: Decimal,
+}
+
+/// Estimated cost of a market order of a reference size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceImpact {
+    /// Order size in the base asset
+    pub quantity: Decimal,
+
+    /// Slippage of a buy from mid, in basis points; `None` if the visible
+    /// asks can't fill it
+    pub buy_bps: Option<Decimal>,
+
+    /// Slippage of a sell from mid, in basis points; `None` if the visible
+    /// bids can't fill it
+    pub sell_bps: Option<Decimal>,
 }
 
 impl OrderBookMetrics {
@@ -42,10 +154,7 @@ impl OrderBookMetrics {
 
     /// Get volume ratio (bid_depth / ask_depth)
     pub fn volume_ratio(&self) -> Option<Decimal> {
-        if self.ask_depth > Decimal::ZERO {
-            Some(self.bid_depth / self.ask_depth)
-        } else {
-            None
-        }
+        let (bid_depth, ask_depth) = (self.bid_depth?, self.ask_depth?);
+        (ask_depth > Decimal::ZERO).then(|| bid_depth / ask_depth)
     }
 }
@@ -3,23 +3,76 @@
 //! Maintains synchronized order book state from Binance depth updates.
 
 mod book;
+mod checksum;
+mod l3;
+mod levels;
 mod manager;
 mod metrics;
+mod persist;
+mod volatility;
 
 pub use book::OrderBook;
+pub use checksum::{checksum, CHECKSUM_LEVELS};
+pub use levels::TickScale;
 pub use manager::OrderBookManager;
-pub use metrics::OrderBookMetrics;
+pub use metrics::{DepthBand, Metric, MetricsConfig, OrderBookMetrics, PriceImpact};
+pub use persist::SavedBooks;
+pub use volatility::VolatilityEstimator;
 
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::exchange_info::InstrumentInfo;
+use crate::trade_metrics::TradeMetrics;
 
 /// Side of the order book
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Side {
     Bid,
     Ask,
 }
 
+/// What a book does when a depth update leaves it crossed or locked
+/// (best bid at or above best ask)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossedBookPolicy {
+    /// Undo the update
+    Reject,
+    /// Drop the crossing levels on the side the update didn't set
+    #[default]
+    Trim,
+    /// Discard the book and fetch a fresh snapshot
+    Resync,
+}
+
+impl FromStr for CrossedBookPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "reject" => Ok(CrossedBookPolicy::Reject),
+            "trim" => Ok(CrossedBookPolicy::Trim),
+            "resync" => Ok(CrossedBookPolicy::Resync),
+            other => Err(format!("Invalid crossed book policy: {}", other)),
+        }
+    }
+}
+
+/// Warm-up requirement after a book is (re)initialized
+///
+/// A book is warmed up once it has been live for `min_duration` and has
+/// applied `min_updates` diffs since its last snapshot. Zero disables a
+/// threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WarmupPolicy {
+    pub min_duration: Duration,
+    pub min_updates: u64,
+}
+
 /// A single level in the order book
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Level {
@@ -27,6 +80,22 @@ pub struct Level {
     pub quantity: Decimal,
 }
 
+/// Where and when a published state came from, for per-hop latency
+/// attribution downstream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// WebSocket connection that delivered the triggering update
+    pub connection_id: u64,
+    /// Connection shard index
+    pub shard: u32,
+    /// Local receive time of the raw message (microseconds since epoch)
+    pub received_at_us: u64,
+    /// Time the update was applied to the book (microseconds since epoch)
+    pub applied_at_us: u64,
+    /// Number of superseded states folded into this one by conflation
+    pub conflated: u32,
+}
+
 /// Order book state to be published
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBookState {
@@ -36,4 +105,57 @@ pub struct OrderBookState {
     pub bids: Vec<Level>,
     pub asks: Vec<Level>,
     pub metrics: OrderBookMetrics,
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// Rolling trade activity, attached when published
+    #[serde(default)]
+    pub trade_metrics: Option<TradeMetrics>,
+    /// Tick size, lot size and minimum notional, when fetched
+    #[serde(default)]
+    pub instrument: Option<InstrumentInfo>,
+    /// [`checksum`] of `bids` and `asks`
+    #[serde(default)]
+    pub checksum: Option<u32>,
+}
+
+/// Best bid and ask after an update that changed either's price or size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BboChanged {
+    pub symbol: String,
+    pub timestamp: u64,
+    pub last_update_id: u64,
+    pub best_bid: Option<Level>,
+    pub best_ask: Option<Level>,
+}
+
+impl OrderBookState {
+    /// Keep only the best `depth` levels per side; metrics still reflect
+    /// the full book
+    pub fn truncate(&mut self, depth: usize) {
+        self.bids.truncate(depth);
+        self.asks.truncate(depth);
+        if self.checksum.is_some() {
+            self.checksum = Some(checksum(&self.bids, &self.asks));
+        }
+    }
+
+    /// Whether the levels match the checksum they were published with;
+    /// true for states without one
+    pub fn checksum_matches(&self) -> bool {
+        self.checksum
+            .is_none_or(|expected| checksum(&self.bids, &self.asks) == expected)
+    }
+}
+
+/// Change to a book, delivered to `OrderBookManager::subscribe` receivers
+#[derive(Debug, Clone)]
+pub enum BookEvent {
+    /// The book was (re)initialized from a snapshot
+    Snapshot(Arc<OrderBookState>),
+    /// A depth update was applied to a warmed-up book
+    Update(Arc<OrderBookState>),
+    /// That update changed the best bid or ask; sent before its `Update`
+    BboChanged(BboChanged),
+    /// The book was discarded and awaits a new snapshot
+    Resync { symbol: String },
 }
@@ -0,0 +1,83 @@
+//! Book persistence for warm restarts
+//!
+//! The retained levels and last update ID of every book are saved to disk
+//! periodically and on shutdown. On startup, books saved recently enough
+//! are resumed from the file instead of fetching REST snapshots; the diff
+//! stream confirms them, or a sequence gap sends them back to REST.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{MarketDataError, Result};
+use crate::event::BookSnapshot;
+
+/// Books saved by a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedBooks {
+    /// When the books were saved (milliseconds since epoch)
+    pub saved_at: u64,
+    pub books: Vec<BookSnapshot>,
+}
+
+impl SavedBooks {
+    /// Write the books to `path`, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec(self).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize books: {}", e))
+        })?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read books saved by `save`; a missing file yields none
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the books are recent enough at `now` (ms) to resume from
+    pub fn is_fresh(&self, now: u64, max_age_ms: u64) -> bool {
+        now.saturating_sub(self.saved_at) <= max_age_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PriceLevel, Venue};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("books.json");
+        assert!(SavedBooks::load(&path).unwrap().books.is_empty());
+
+        let saved = SavedBooks {
+            saved_at: 1_000,
+            books: vec![BookSnapshot {
+                venue: Venue::Binance,
+                symbol: "BTCUSDT".to_string(),
+                last_update_id: 42,
+                bids: vec![PriceLevel {
+                    price: dec!(100),
+                    quantity: dec!(1.5),
+                }],
+                asks: vec![],
+            }],
+        };
+        saved.save(&path).unwrap();
+
+        let loaded = SavedBooks::load(&path).unwrap();
+        assert_eq!(loaded.books[0].last_update_id, 42);
+        assert_eq!(loaded.books[0].bids, saved.books[0].bids);
+        assert!(loaded.is_fresh(61_000, 60_000));
+        assert!(!loaded.is_fresh(61_001, 60_000));
+    }
+}
@@ -0,0 +1,106 @@
+//! Realized volatility from mid-price returns
+//!
+//! Samples the mid price on a fixed interval of exchange time and keeps the
+//! log returns between consecutive samples over a rolling window. The
+//! estimate is the root mean square return scaled to a year, so windows and
+//! sampling intervals of different lengths give comparable numbers.
+
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+
+const MS_PER_YEAR: f64 = 365.0 * 86_400_000.0;
+
+/// Rolling realized volatility of one book's mid price
+#[derive(Debug, Clone)]
+pub struct VolatilityEstimator {
+    window_ms: u64,
+    sample_interval_ms: u64,
+    /// Time and mid of the last sample
+    last: Option<(u64, f64)>,
+    /// Sample time and log return since the previous sample
+    returns: VecDeque<(u64, f64)>,
+    sum_squares: f64,
+}
+
+impl VolatilityEstimator {
+    /// Estimate over `window_ms`, sampling every `sample_interval_ms`
+    pub fn new(window_ms: u64, sample_interval_ms: u64) -> Self {
+        Self {
+            window_ms,
+            sample_interval_ms: sample_interval_ms.max(1),
+            last: None,
+            returns: VecDeque::new(),
+            sum_squares: 0.0,
+        }
+    }
+
+    /// Offer the mid price at `time_ms`; taken if a sample is due
+    pub fn observe(&mut self, time_ms: u64, mid: Decimal) {
+        let Some(mid) = mid.to_f64().filter(|m| *m > 0.0) else {
+            return;
+        };
+        match self.last {
+            Some((last_time, _)) if time_ms < last_time + self.sample_interval_ms => return,
+            Some((_, last_mid)) => {
+                let r = (mid / last_mid).ln();
+                self.returns.push_back((time_ms, r));
+                self.sum_squares += r * r;
+            }
+            None => {}
+        }
+        self.last = Some((time_ms, mid));
+
+        let cutoff = time_ms.saturating_sub(self.window_ms);
+        while let Some(&(time, r)) = self.returns.front() {
+            if time > cutoff {
+                break;
+            }
+            self.returns.pop_front();
+            self.sum_squares -= r * r;
+        }
+        if self.returns.is_empty() {
+            // Don't let float error accumulate across quiet periods
+            self.sum_squares = 0.0;
+        }
+    }
+
+    /// Annualized realized volatility; `None` until two returns are in the
+    /// window
+    pub fn realized_vol(&self) -> Option<Decimal> {
+        if self.returns.len() < 2 {
+            return None;
+        }
+        let mean_square = self.sum_squares.max(0.0) / self.returns.len() as f64;
+        let periods_per_year = MS_PER_YEAR / self.sample_interval_ms as f64;
+        Decimal::from_f64((mean_square * periods_per_year).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_samples_on_interval_and_rolls() {
+        let mut vol = VolatilityEstimator::new(10_000, 1_000);
+        vol.observe(0, dec!(100));
+        vol.observe(500, dec!(500)); // not due yet
+        vol.observe(1_000, dec!(101));
+        assert_eq!(vol.realized_vol(), None);
+        vol.observe(2_000, dec!(100));
+
+        // Two returns of ±ln(1.01), annualized over 1s periods
+        let r = (101f64 / 100.0).ln();
+        let expected = ((r * r + r * r) / 2.0 * MS_PER_YEAR / 1_000.0).sqrt();
+        let estimate = vol.realized_vol().unwrap().to_f64().unwrap();
+        assert!((estimate - expected).abs() / expected < 1e-9);
+
+        // A flat stretch pushes the moves out of the window
+        for t in (3..=13).map(|s| s * 1_000) {
+            vol.observe(t, dec!(100));
+        }
+        assert!(vol.realized_vol().unwrap() < dec!(0.000001));
+    }
+}
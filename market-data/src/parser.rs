@@ -1,11 +1,34 @@
 //! Parser module for Binance WebSocket messages
 //!
-//! Handles deserialization of depth updates, trades, and other market data messages.
+//! Handles deserialization of depth updates, trades, and other market data messages,
+//! and their translation into normalized `MarketEvent`s.
+//!
+//! [`parse_event`] is the feed's hot path. It routes a message by the stream
+//! type in its stream name (`btcusdt@depth@100ms` is a depth stream) or by
+//! its event type, read off the first bytes of the message when it is laid
+//! out as Binance sends it and from its top-level fields otherwise. The
+//! payload is then deserialized once, borrowed from the message rather than
+//! copied into a `Value` tree, with prices and quantities parsed straight
+//! from the input; a depth update costs only its symbol and its two level
+//! vectors.
+//!
+//! With the `simd-json` feature, messages are parsed by simd-json instead,
+//! which picks the widest SIMD instruction set the CPU supports at runtime.
+//! A message it rejects is parsed again with serde_json, so both backends
+//! accept and reject the same input.
 
 use rust_decimal::Decimal;
+use serde::de::{DeserializeOwned, IgnoredAny, SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use std::fmt;
 use std::str::FromStr;
 
+use crate::event::{self, BookSnapshot, DepthDelta, MarketEvent, Venue};
+use crate::orderbook::Side;
+
+pub use crate::event::PriceLevel;
+
 /// Binance depth update message
 #[derive(Debug, Clone, Deserialize)]
 pub struct DepthUpdate {
@@ -83,13 +106,6 @@ pub struct Trade {
     pub is_buyer_maker: bool,
 }
 
-/// Price level (price, quantity pair)
-#[derive(Debug, Clone)]
-pub struct PriceLevel {
-    pub price: Decimal,
-    pub quantity: Decimal,
-}
-
 /// Order book snapshot from REST API
 #[derive(Debug, Clone, Deserialize)]
 pub struct OrderBookSnapshot {
@@ -116,80 +132,506 @@ pub struct StreamMessage {
     pub data: serde_json::Value,
 }
 
+/// Confirmation of a control request (`{"result": null, "id": 1}`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionAck {
+    /// Id of the confirmed request
+    pub id: u64,
+}
+
+/// Error reply to a control request (`{"error": {"code": 2, "msg": ".."},
+/// "id": 1}`), or an error frame on its own (`{"code": 2, "msg": ".."}`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeError {
+    /// Id of the rejected request, if the error answers one
+    pub id: Option<u64>,
+    pub code: i64,
+    pub msg: String,
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.msg, self.code)
+    }
+}
+
 /// Parsed WebSocket message
 #[derive(Debug, Clone)]
 pub enum ParsedMessage {
     DepthUpdate(DepthUpdate),
     Trade(Trade),
+    SubscriptionAck(SubscriptionAck),
+    ExchangeError(ExchangeError),
     Unknown(String),
 }
 
 impl ParsedMessage {
+    /// Exchange event time in milliseconds, if the message carries one
+    pub fn event_time(&self) -> Option<u64> {
+        match self {
+            ParsedMessage::DepthUpdate(depth) => Some(depth.event_time),
+            ParsedMessage::Trade(trade) => Some(trade.event_time),
+            _ => None,
+        }
+    }
+
+    /// Translate into a normalized event; unknown messages have none
+    pub fn into_event(self) -> Option<MarketEvent> {
+        match self {
+            ParsedMessage::DepthUpdate(depth) => Some(MarketEvent::DepthDelta(depth.into())),
+            ParsedMessage::Trade(trade) => Some(MarketEvent::Trade(trade.into())),
+            _ => None,
+        }
+    }
+
     /// Parse a raw WebSocket message
     pub fn parse(raw: &str) -> Result<Self, serde_json::Error> {
-        // Try to parse as stream message first (combined streams)
-        if let Ok(stream_msg) = serde_json::from_str::<StreamMessage>(raw) {
-            return Self::parse_stream_data(&stream_msg.stream, &stream_msg.data);
-        }
+        Ok(match decode(raw)? {
+            Decoded::Depth(depth) => ParsedMessage::DepthUpdate(depth),
+            Decoded::Trade(trade) => ParsedMessage::Trade(trade),
+            Decoded::Other => match Self::parse_control(raw) {
+                Some(control) => control,
+                None => ParsedMessage::Unknown(route(raw)?.1.to_string()),
+            },
+        })
+    }
 
-        // Try direct parsing
-        if let Ok(depth) = serde_json::from_str::<DepthUpdate>(raw) {
-            if depth.event_type == "depthUpdate" {
-                return Ok(ParsedMessage::DepthUpdate(depth));
+    /// Parse a control frame: a reply to a control request or an error
+    /// frame; anything else yields `None`
+    pub fn parse_control(raw: &str) -> Option<Self> {
+        let frame: ControlFrame = serde_json::from_str(raw).ok()?;
+        match (frame.error, frame.code, frame.msg) {
+            (Some(ErrorBody { code, msg }), _, _) | (None, Some(code), Some(msg)) => {
+                Some(ParsedMessage::ExchangeError(ExchangeError {
+                    id: frame.id,
+                    code,
+                    msg,
+                }))
+            }
+            (None, None, _) if frame.result => {
+                Some(ParsedMessage::SubscriptionAck(SubscriptionAck {
+                    id: frame.id?,
+                }))
             }
+            _ => None,
         }
+    }
+}
 
-        if let Ok(trade) = serde_json::from_str::<Trade>(raw) {
-            if trade.event_type == "trade" {
-                return Ok(ParsedMessage::Trade(trade));
-            }
+/// Fields of a control frame
+#[derive(Deserialize)]
+struct ControlFrame {
+    #[serde(default)]
+    id: Option<u64>,
+    /// Whether a `result` is present; it is `null` for most requests
+    #[serde(default, deserialize_with = "present")]
+    result: bool,
+    #[serde(default)]
+    error: Option<ErrorBody>,
+    #[serde(default)]
+    code: Option<i64>,
+    #[serde(default)]
+    msg: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: i64,
+    msg: String,
+}
+
+/// Deserializer for fields whose presence alone matters
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    IgnoredAny::deserialize(deserializer)?;
+    Ok(true)
+}
+
+/// Parse a raw WebSocket message straight into a normalized event; unknown
+/// messages have none
+///
+/// Same result as `ParsedMessage::parse(raw)?.into_event()` without the
+/// intermediate message.
+pub fn parse_event(raw: &str) -> Result<Option<MarketEvent>, serde_json::Error> {
+    Ok(match decode::<DepthPayload, Trade>(raw)? {
+        Decoded::Depth(depth) => Some(MarketEvent::DepthDelta(depth.into())),
+        Decoded::Trade(trade) => Some(MarketEvent::Trade(trade.into())),
+        Decoded::Other => None,
+    })
+}
+
+/// What a message carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Depth,
+    Trade,
+    Other,
+}
+
+impl Kind {
+    /// Kind of a combined-stream message on `stream`, named
+    /// `<symbol>@<stream type>[@<options>]`
+    ///
+    /// Only diff depth streams are depth updates; partial book streams
+    /// (`depth5`, `depth20`...) carry snapshots, and `aggTrade` a different
+    /// payload than `trade`.
+    fn of_stream(stream: &str) -> Self {
+        match stream.split('@').nth(1) {
+            Some("depth") => Kind::Depth,
+            Some("trade") => Kind::Trade,
+            _ => Kind::Other,
         }
+    }
 
-        Ok(ParsedMessage::Unknown(raw.to_string()))
+    /// Kind of a raw stream message of `event_type`
+    fn of_event(event_type: Option<&str>) -> Self {
+        match event_type {
+            Some("depthUpdate") => Kind::Depth,
+            Some("trade") => Kind::Trade,
+            _ => Kind::Other,
+        }
     }
+}
 
-    fn parse_stream_data(
-        stream: &str,
-        data: &serde_json::Value,
-    ) -> Result<Self, serde_json::Error> {
-        if stream.contains("depth") {
-            let depth: DepthUpdate = serde_json::from_value(data.clone())?;
-            Ok(ParsedMessage::DepthUpdate(depth))
-        } else if stream.contains("trade") {
-            let trade: Trade = serde_json::from_value(data.clone())?;
-            Ok(ParsedMessage::Trade(trade))
-        } else {
-            Ok(ParsedMessage::Unknown(data.to_string()))
+/// A message's payload, deserialized as its kind's type
+#[derive(Debug)]
+enum Decoded<D, T> {
+    Depth(D),
+    Trade(T),
+    Other,
+}
+
+/// Parse the depth or trade payload of `raw` with the enabled backend
+fn decode<D, T>(raw: &str) -> Result<Decoded<D, T>, serde_json::Error>
+where
+    D: DeserializeOwned,
+    T: DeserializeOwned,
+{
+    if let Some((kind, payload)) = scan(raw) {
+        // A payload that doesn't parse on its own may still belong to valid
+        // JSON laid out differently, which the full parse below settles
+        if let Ok(decoded) = decode_as(kind, payload) {
+            return Ok(decoded);
         }
     }
+    #[cfg(feature = "simd-json")]
+    if let Ok(decoded) = simd::decode(raw) {
+        return Ok(decoded);
+    }
+    decode_json(raw)
+}
+
+/// Parse `payload` as `kind` with the enabled backend
+fn decode_as<D, T>(kind: Kind, payload: &str) -> Result<Decoded<D, T>, serde_json::Error>
+where
+    D: DeserializeOwned,
+    T: DeserializeOwned,
+{
+    #[cfg(feature = "simd-json")]
+    if let Ok(decoded) = simd::decode_as(kind, payload) {
+        return Ok(decoded);
+    }
+    decode_as_json(kind, payload)
+}
+
+/// Parse the depth or trade payload of `raw` with serde_json
+fn decode_json<D, T>(raw: &str) -> Result<Decoded<D, T>, serde_json::Error>
+where
+    D: DeserializeOwned,
+    T: DeserializeOwned,
+{
+    let (kind, payload) = route(raw)?;
+    decode_as_json(kind, payload)
+}
+
+/// Parse `payload` as `kind` with serde_json
+fn decode_as_json<D, T>(kind: Kind, payload: &str) -> Result<Decoded<D, T>, serde_json::Error>
+where
+    D: DeserializeOwned,
+    T: DeserializeOwned,
+{
+    Ok(match kind {
+        Kind::Depth => Decoded::Depth(serde_json::from_str(payload)?),
+        Kind::Trade => Decoded::Trade(serde_json::from_str(payload)?),
+        Kind::Other => Decoded::Other,
+    })
+}
+
+/// Kind and payload of `raw` read off its first bytes, for messages laid
+/// out as Binance sends them: `{"stream":"<name>","data":<payload>}` or
+/// `{"e":"<event type>",...}`
+fn scan(raw: &str) -> Option<(Kind, &str)> {
+    let raw = raw.trim_end();
+    if let Some(rest) = raw.strip_prefix(r#"{"stream":""#) {
+        let (stream, rest) = rest.split_once('"')?;
+        let payload = rest.strip_prefix(r#","data":"#)?.strip_suffix('}')?;
+        return (!stream.contains('\\')).then(|| (Kind::of_stream(stream), payload));
+    }
+    let (event_type, _) = raw.strip_prefix(r#"{"e":""#)?.split_once('"')?;
+    (!event_type.contains('\\')).then(|| (Kind::of_event(Some(event_type)), raw))
 }
 
+/// Top-level fields that tell messages apart: a combined-stream envelope
+/// has `stream` and `data`, a raw stream message its event type `e`. The
+/// rest is skipped without being copied.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow, default)]
+    stream: Option<&'a str>,
+    #[serde(borrow, default)]
+    data: Option<&'a RawValue>,
+    #[serde(rename = "e", borrow, default)]
+    event_type: Option<&'a str>,
+}
+
+/// Kind and payload of `raw`, the payload borrowed from it
+fn route(raw: &str) -> Result<(Kind, &str), serde_json::Error> {
+    let envelope: Envelope = serde_json::from_str(raw)?;
+    if let (Some(stream), Some(data)) = (envelope.stream, envelope.data) {
+        return Ok((Kind::of_stream(stream), data.get()));
+    }
+    Ok((Kind::of_event(envelope.event_type), raw))
+}
+
+/// simd-json backend
+///
+/// simd-json parses in place, so each message is copied into a per-thread
+/// buffer that is reused along with the parser's own. The routing fields
+/// are read in a first pass over the parsed tape and the payload in a
+/// second, without parsing the text again.
+#[cfg(feature = "simd-json")]
+mod simd {
+    use serde::de::{DeserializeOwned, IgnoredAny};
+    use serde::Deserialize;
+    use std::cell::RefCell;
+
+    use super::{Decoded, Kind};
+
+    /// Top-level fields that tell messages apart, the payload skipped
+    #[derive(Deserialize)]
+    struct Header<'a> {
+        #[serde(borrow, default)]
+        stream: Option<&'a str>,
+        #[serde(default)]
+        data: Option<IgnoredAny>,
+        #[serde(rename = "e", borrow, default)]
+        event_type: Option<&'a str>,
+    }
+
+    /// Combined-stream envelope
+    #[derive(Deserialize)]
+    struct Combined<P> {
+        data: P,
+    }
+
+    #[derive(Default)]
+    struct Scratch {
+        input: Vec<u8>,
+        buffers: simd_json::Buffers,
+    }
+
+    thread_local! {
+        static SCRATCH: RefCell<Scratch> = RefCell::default();
+    }
+
+    /// Run `f` on a deserializer over a copy of `json`
+    fn with_deserializer<R>(
+        json: &str,
+        f: impl FnOnce(&mut simd_json::Deserializer) -> simd_json::Result<R>,
+    ) -> simd_json::Result<R> {
+        SCRATCH.with_borrow_mut(|scratch| {
+            let Scratch { input, buffers } = scratch;
+            input.clear();
+            input.extend_from_slice(json.as_bytes());
+            f(&mut simd_json::Deserializer::from_slice_with_buffers(
+                input, buffers,
+            )?)
+        })
+    }
+
+    pub(super) fn decode<D, T>(raw: &str) -> simd_json::Result<Decoded<D, T>>
+    where
+        D: DeserializeOwned,
+        T: DeserializeOwned,
+    {
+        with_deserializer(raw, |de| {
+            let header = Header::deserialize(&mut *de)?;
+            let (kind, combined) = match (header.stream, header.data) {
+                (Some(stream), Some(_)) => (Kind::of_stream(stream), true),
+                _ => (Kind::of_event(header.event_type), false),
+            };
+            de.restart();
+            Ok(match (kind, combined) {
+                (Kind::Depth, true) => Decoded::Depth(Combined::deserialize(de)?.data),
+                (Kind::Depth, false) => Decoded::Depth(D::deserialize(de)?),
+                (Kind::Trade, true) => Decoded::Trade(Combined::deserialize(de)?.data),
+                (Kind::Trade, false) => Decoded::Trade(T::deserialize(de)?),
+                (Kind::Other, _) => Decoded::Other,
+            })
+        })
+    }
+
+    pub(super) fn decode_as<D, T>(kind: Kind, payload: &str) -> simd_json::Result<Decoded<D, T>>
+    where
+        D: DeserializeOwned,
+        T: DeserializeOwned,
+    {
+        if kind == Kind::Other {
+            return Ok(Decoded::Other);
+        }
+        with_deserializer(payload, |de| {
+            Ok(match kind {
+                Kind::Depth => Decoded::Depth(D::deserialize(de)?),
+                Kind::Trade => Decoded::Trade(T::deserialize(de)?),
+                Kind::Other => Decoded::Other,
+            })
+        })
+    }
+}
+
+/// Depth update fields a `DepthDelta` needs; the event type is already
+/// known from routing and isn't copied
+#[derive(Debug, Deserialize)]
+struct DepthPayload {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    #[serde(rename = "b", deserialize_with = "deserialize_price_levels")]
+    bids: Vec<PriceLevel>,
+    #[serde(rename = "a", deserialize_with = "deserialize_price_levels")]
+    asks: Vec<PriceLevel>,
+}
+
+impl From<DepthPayload> for DepthDelta {
+    fn from(update: DepthPayload) -> Self {
+        DepthDelta {
+            venue: Venue::Binance,
+            symbol: update.symbol,
+            event_time: update.event_time,
+            first_update_id: update.first_update_id,
+            final_update_id: update.final_update_id,
+            bids: update.bids,
+            asks: update.asks,
+        }
+    }
+}
+
+impl From<DepthUpdate> for DepthDelta {
+    fn from(update: DepthUpdate) -> Self {
+        DepthDelta {
+            venue: Venue::Binance,
+            symbol: update.symbol,
+            event_time: update.event_time,
+            first_update_id: update.first_update_id,
+            final_update_id: update.final_update_id,
+            bids: update.bids,
+            asks: update.asks,
+        }
+    }
+}
+
+impl From<Trade> for event::Trade {
+    fn from(trade: Trade) -> Self {
+        event::Trade {
+            venue: Venue::Binance,
+            symbol: trade.symbol,
+            event_time: trade.event_time,
+            trade_id: trade.trade_id,
+            price: trade.price,
+            quantity: trade.quantity,
+            // A maker buyer means the seller crossed the spread
+            taker_side: if trade.is_buyer_maker {
+                Side::Ask
+            } else {
+                Side::Bid
+            },
+            trade_time: trade.trade_time,
+        }
+    }
+}
+
+impl OrderBookSnapshot {
+    /// Translate into a normalized snapshot for `symbol`
+    ///
+    /// The REST response doesn't name its symbol, so the caller supplies it.
+    pub fn into_event(self, symbol: &str) -> BookSnapshot {
+        BookSnapshot {
+            venue: Venue::Binance,
+            symbol: symbol.to_string(),
+            last_update_id: self.last_update_id,
+            bids: self.bids,
+            asks: self.asks,
+        }
+    }
+}
+
+/// Initial capacity of a level vector, as JSON arrays don't announce their
+/// length; most diffs fit without growing it
+const LEVELS_HINT: usize = 32;
+
 /// Custom deserializer for Decimal from string
 fn deserialize_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s: &str = Deserialize::deserialize(deserializer)?;
-    Decimal::from_str(s).map_err(serde::de::Error::custom)
+    deserializer.deserialize_str(DecimalVisitor)
+}
+
+/// Parses a decimal string in place, whether or not the input can lend it
+struct DecimalVisitor;
+
+impl Visitor<'_> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Decimal, E> {
+        Decimal::from_str(v).map_err(E::custom)
+    }
+}
+
+/// A decimal sent as a JSON string
+struct DecimalStr(Decimal);
+
+impl<'de> Deserialize<'de> for DecimalStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_decimal(deserializer).map(DecimalStr)
+    }
 }
 
 /// Custom deserializer for price levels from array of string pairs
+///
+/// Levels are parsed one by one into the result, without collecting the
+/// strings first.
 fn deserialize_price_levels<'de, D>(deserializer: D) -> Result<Vec<PriceLevel>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let raw: Vec<Vec<String>> = Deserialize::deserialize(deserializer)?;
-    raw.into_iter()
-        .map(|pair| {
-            if pair.len() != 2 {
-                return Err(serde::de::Error::custom("Invalid price level format"));
+    struct Levels;
+
+    impl<'de> Visitor<'de> for Levels {
+        type Value = Vec<PriceLevel>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of [price, quantity] string pairs")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut levels = Vec::with_capacity(seq.size_hint().unwrap_or(LEVELS_HINT));
+            while let Some((DecimalStr(price), DecimalStr(quantity))) = seq.next_element()? {
+                levels.push(PriceLevel { price, quantity });
             }
-            Ok(PriceLevel {
-                price: Decimal::from_str(&pair[0]).map_err(serde::de::Error::custom)?,
-                quantity: Decimal::from_str(&pair[1]).map_err(serde::de::Error::custom)?,
-            })
-        })
-        .collect()
+            Ok(levels)
+        }
+    }
+
+    deserializer.deserialize_seq(Levels)
 }
 
 #[cfg(test)]
@@ -239,8 +681,146 @@ mod tests {
             assert_eq!(trade.symbol, "BTCUSDT");
             assert_eq!(trade.price, Decimal::from_str("50000.50").unwrap());
             assert!(!trade.is_buyer_maker);
+
+            let event::Trade { taker_side, .. } = trade.into();
+            assert_eq!(taker_side, Side::Bid);
         } else {
             panic!("Expected Trade");
         }
     }
+
+    #[test]
+    fn test_parse_event_matches_parse() {
+        let depth = r#"{"e":"depthUpdate","E":1672531200000,"s":"BTCUSDT","U":100,"u":105,"b":[["50000.00","1.5"]],"a":[["50001.00","0"]]}"#;
+        let trade = r#"{"e":"trade","E":1672531200000,"s":"ETHUSDT","t":1,"p":"2000.5","q":"0.1","b":1,"a":2,"T":1672531200000,"m":true}"#;
+        let messages = [
+            depth.to_string(),
+            trade.to_string(),
+            format!(r#"{{"stream":"btcusdt@depth@100ms","data":{}}}"#, depth),
+            format!(r#"{{"stream":"ethusdt@trade","data":{}}}"#, trade),
+            r#"{"stream":"btcusdt@kline_1m","data":{"e":"kline"}}"#.to_string(),
+            r#"{"e":"aggTrade","s":"BTCUSDT"}"#.to_string(),
+        ];
+        for raw in &messages {
+            let expected = ParsedMessage::parse(raw).unwrap().into_event();
+            let event = parse_event(raw).unwrap();
+            assert_eq!(format!("{:?}", event), format!("{:?}", expected), "{}", raw);
+        }
+        let Some(MarketEvent::DepthDelta(delta)) = parse_event(&messages[2]).unwrap() else {
+            panic!("Expected DepthDelta");
+        };
+        assert_eq!(delta.final_update_id, 105);
+        assert_eq!(delta.asks[0].quantity, Decimal::ZERO);
+        assert!(matches!(
+            parse_event(&messages[3]).unwrap(),
+            Some(MarketEvent::Trade(_))
+        ));
+
+        // Malformed levels are rejected, not skipped
+        assert!(parse_event(&depth.replace(r#"["50000.00","1.5"]"#, r#"["50000.00"]"#)).is_err());
+    }
+
+    #[test]
+    fn test_routes_by_stream_type() {
+        let depth = r#"{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":1,"u":2,"b":[],"a":[]}"#;
+        let combined =
+            |stream: &str, data: &str| format!(r#"{{"stream":"{}","data":{}}}"#, stream, data);
+        let is_depth = |raw: &str| matches!(parse_event(raw), Ok(Some(MarketEvent::DepthDelta(_))));
+
+        assert!(is_depth(&combined("btcusdt@depth@100ms", depth)));
+        assert!(is_depth(&combined("btcusdt@depth", depth)));
+        assert!(is_depth(&format!("{}\n", depth)));
+
+        // Partial book and aggregate trade streams aren't diffs or trades
+        let partial = r#"{"lastUpdateId":1,"bids":[],"asks":[]}"#;
+        assert!(matches!(
+            parse_event(&combined("btcusdt@depth20@100ms", partial)),
+            Ok(None)
+        ));
+        let agg = r#"{"e":"aggTrade","E":1,"s":"BTCUSDT","a":1,"p":"1","q":"1"}"#;
+        assert!(matches!(
+            parse_event(&combined("btcusdt@aggTrade", agg)),
+            Ok(None)
+        ));
+
+        // Other layouts go through the full parse
+        assert!(is_depth(&format!(
+            r#"{{ "data": {}, "stream": "btcusdt@depth" }}"#,
+            depth
+        )));
+        assert!(is_depth(&format!(
+            r#"{{"stream":"btcusdt@depth","data":{},"extra":1}}"#,
+            depth
+        )));
+        assert!(is_depth(
+            r#"{"s":"BTCUSDT","e":"depthUpdate","E":1,"U":1,"u":2,"b":[],"a":[]}"#
+        ));
+        assert!(parse_event(&combined("btcusdt@depth", r#"{"e":"depthUpdate"}"#)).is_err());
+    }
+
+    #[test]
+    fn test_parse_control_frames() {
+        let parse = |raw| match ParsedMessage::parse(raw).unwrap() {
+            ParsedMessage::SubscriptionAck(ack) => Ok(ack),
+            ParsedMessage::ExchangeError(error) => Err(error),
+            other => panic!("Expected a control frame, got {:?}", other),
+        };
+        assert_eq!(
+            parse(r#"{"result":null,"id":1}"#),
+            Ok(SubscriptionAck { id: 1 })
+        );
+        assert_eq!(
+            parse(r#"{"result":["btcusdt@depth"],"id":3}"#),
+            Ok(SubscriptionAck { id: 3 })
+        );
+        assert_eq!(
+            parse(r#"{"error":{"code":2,"msg":"Invalid request"},"id":2}"#),
+            Err(ExchangeError {
+                id: Some(2),
+                code: 2,
+                msg: "Invalid request".to_string()
+            })
+        );
+        assert_eq!(
+            parse(r#"{"code":3,"msg":"Invalid JSON"}"#).unwrap_err().id,
+            None
+        );
+
+        // Neither data nor anything without a result or error is a control frame
+        assert!(ParsedMessage::parse_control(r#"{"id":4}"#).is_none());
+        assert!(ParsedMessage::parse_control(r#"{"stream":"x@depth","data":{}}"#).is_none());
+        assert!(matches!(
+            ParsedMessage::parse(r#"{"id":4}"#).unwrap(),
+            ParsedMessage::Unknown(_)
+        ));
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn test_simd_matches_serde_json() {
+        let depth = r#"{"e":"depthUpdate","E":1672531200000,"s":"BTC\u0055SDT","U":100,"u":105,"b":[["50000.00","1.5"],["49999.10","0.25"]],"a":[]}"#;
+        let messages = [
+            depth.to_string(),
+            format!(r#"{{"stream":"btcusdt@depth@100ms","data":{}}}"#, depth),
+            r#"{"stream":"ethusdt@trade","data":{"e":"trade","E":1,"s":"ETHUSDT","t":1,"p":"2000.5","q":"0.1","b":1,"a":2,"T":1,"m":true}}"#.to_string(),
+            r#"{"e":"aggTrade","s":"BTCUSDT"}"#.to_string(),
+        ];
+        for raw in &messages {
+            let simd: Decoded<DepthPayload, Trade> = simd::decode(raw).unwrap();
+            let json: Decoded<DepthPayload, Trade> = decode_json(raw).unwrap();
+            assert_eq!(format!("{:?}", simd), format!("{:?}", json), "{}", raw);
+
+            let (kind, payload) = scan(raw).unwrap();
+            let simd: Decoded<DepthPayload, Trade> = simd::decode_as(kind, payload).unwrap();
+            assert_eq!(format!("{:?}", simd), format!("{:?}", json), "{}", raw);
+        }
+        let Decoded::Depth(depth) = simd::decode::<DepthPayload, Trade>(depth).unwrap() else {
+            panic!("Expected depth");
+        };
+        assert_eq!(depth.symbol, "BTCUSDT");
+
+        // What simd-json rejects falls back to serde_json's verdict
+        assert!(simd::decode::<DepthPayload, Trade>("{\"e\":").is_err());
+        assert!(parse_event("{\"e\":").is_err());
+    }
 }
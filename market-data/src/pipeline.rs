@@ -0,0 +1,643 @@
+//! Per-symbol book pipeline
+//!
+//! Each symbol's book lives in its own task, which owns it outright and
+//! works through a channel of commands: snapshots, depth diffs, trades,
+//! and inspections from the HTTP handlers and persistence. Diffs are
+//! applied and published by the symbol's task, so symbols no longer queue
+//! behind one shared lock, and a slow symbol only delays itself. The task
+//! also owns the symbol's rolling trade metrics and anomaly detector, so
+//! the publish path takes no shared lock for them either.
+//!
+//! [`Books`] is the handle the rest of the handler uses. Whether a book is
+//! initialized, warmed up or resumed and when it last updated are mirrored
+//! into atomics after every command, and the last published state behind a
+//! lock of its own, so health checks, the staleness watchdog and HTTP
+//! readers never queue behind the feed or contend across symbols. Work
+//! needing the book itself (persistence, instrument refreshes) goes through
+//! the task.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::{error, warn};
+
+use crate::anomaly::{AnomalyDetector, MarketAnomaly};
+use crate::error::MarketDataError;
+use crate::event::{BookSnapshot, DepthDelta, Trade};
+use crate::exchange_info::InstrumentInfo;
+use crate::latency::StageTimes;
+use crate::metrics;
+use crate::orderbook::{BboChanged, BookEvent, OrderBookManager, OrderBookState, Provenance};
+use crate::publisher::StatusEvent;
+use crate::trade_metrics::{TradeMetrics, TradeMetricsTracker};
+use crate::AppState;
+
+/// Commands queued per symbol before the feed waits for its task
+const COMMAND_CAPACITY: usize = 4096;
+
+/// A depth diff with where and when it arrived
+#[derive(Debug)]
+pub struct Delta {
+    pub update: DepthDelta,
+    /// Local wall-clock receive time (microseconds since epoch)
+    pub received_at_us: u64,
+    /// Local wall-clock time parsing finished (microseconds since epoch)
+    pub parsed_at_us: u64,
+    /// Local wall-clock time alignment released it to the book
+    /// (microseconds since epoch); time held waiting for a snapshot is
+    /// not load, so the degradation controller measures from here
+    pub released_at_us: u64,
+    /// Connection and shard it arrived on
+    pub connection_id: u64,
+    pub shard: u32,
+}
+
+type Inspect = Box<dyn FnOnce(&mut OrderBookManager) + Send>;
+
+/// Work for a symbol's task
+enum BookCommand {
+    /// (Re)initialize the book
+    Snapshot(BookSnapshot),
+    /// Apply a diff and publish the result
+    Delta(Box<Delta>),
+    /// Count a trade in the rolling metrics
+    Trade(Box<Trade>),
+    /// Rolling metrics over the window ending at this time (ms)
+    TradeMetrics(u64, oneshot::Sender<Option<TradeMetrics>>),
+    /// Publish the book if its warm-up period has just run out
+    WarmupCheck,
+    /// Run a closure against the book
+    Inspect(Inspect),
+}
+
+/// A symbol's analytics, owned by its book task
+#[derive(Debug)]
+struct BookAnalytics {
+    trade_metrics: TradeMetricsTracker,
+    anomalies: Option<AnomalyDetector>,
+}
+
+/// A book's lifecycle flags and latest state, mirrored by its task
+#[derive(Debug, Default)]
+struct BookStatus {
+    initialized: AtomicBool,
+    warmed_up: AtomicBool,
+    resumed: AtomicBool,
+    /// Event time (ms) of the last applied update; 0 until the first
+    last_update_time: AtomicU64,
+    /// Local time (ms) the book was last initialized, its age baseline
+    /// until updates arrive
+    snapshot_at: AtomicU64,
+    /// State as of the last snapshot or published update, while initialized
+    latest: RwLock<Option<Arc<OrderBookState>>>,
+}
+
+impl BookStatus {
+    fn latest(&self) -> Option<Arc<OrderBookState>> {
+        self.latest.read().unwrap().clone()
+    }
+
+    fn set_latest(&self, state: Option<OrderBookState>) {
+        *self.latest.write().unwrap() = state.map(Arc::new);
+    }
+
+    fn snapshot_applied(&self, now_ms: u64) {
+        self.snapshot_at.store(now_ms, Ordering::Relaxed);
+    }
+
+    fn refresh(&self, manager: &OrderBookManager, symbol: &str) {
+        if !manager.is_initialized(symbol) {
+            self.set_latest(None);
+        }
+        self.initialized
+            .store(manager.is_initialized(symbol), Ordering::Relaxed);
+        self.warmed_up
+            .store(manager.is_warmed_up(symbol), Ordering::Relaxed);
+        self.resumed
+            .store(manager.is_resumed(symbol), Ordering::Relaxed);
+        self.last_update_time
+            .store(manager.last_update_time(symbol), Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct SymbolBook {
+    commands: mpsc::Sender<BookCommand>,
+    status: Arc<BookStatus>,
+}
+
+/// Handle to the per-symbol book tasks
+#[derive(Debug)]
+pub struct Books {
+    /// Configured symbols, in order
+    symbols: Vec<String>,
+    books: HashMap<String, SymbolBook>,
+    resyncs: mpsc::UnboundedSender<String>,
+}
+
+/// Book tasks created with a [`Books`] handle, not yet running
+pub struct BookTasks {
+    tasks: Vec<(
+        String,
+        OrderBookManager,
+        mpsc::Receiver<BookCommand>,
+        Arc<BookStatus>,
+    )>,
+    resyncs: mpsc::UnboundedReceiver<String>,
+}
+
+impl Books {
+    /// Handle for `symbols`' books, each taken over (with the settings)
+    /// from `manager`; the tasks run once [`BookTasks::spawn`]ed
+    pub fn new(manager: OrderBookManager, symbols: &[String]) -> (Self, BookTasks) {
+        let (resync_tx, resync_rx) = mpsc::unbounded_channel();
+        let mut books = HashMap::new();
+        let mut tasks = Vec::new();
+        for (symbol, manager) in symbols.iter().zip(manager.split(symbols)) {
+            let (tx, rx) = mpsc::channel(COMMAND_CAPACITY);
+            let status = Arc::new(BookStatus::default());
+            status.set_latest(manager.get_state(symbol));
+            status.refresh(&manager, symbol);
+            books.insert(
+                symbol.clone(),
+                SymbolBook {
+                    commands: tx,
+                    status: status.clone(),
+                },
+            );
+            tasks.push((symbol.clone(), manager, rx, status));
+        }
+        let handle = Self {
+            symbols: symbols.to_vec(),
+            books,
+            resyncs: resync_tx,
+        };
+        let tasks = BookTasks {
+            tasks,
+            resyncs: resync_rx,
+        };
+        (handle, tasks)
+    }
+
+    /// Configured symbols, in order
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    fn status(&self, symbol: &str) -> Option<&BookStatus> {
+        self.books.get(symbol).map(|book| book.status.as_ref())
+    }
+
+    /// Check if a book is initialized
+    pub fn is_initialized(&self, symbol: &str) -> bool {
+        self.status(symbol)
+            .is_some_and(|status| status.initialized.load(Ordering::Relaxed))
+    }
+
+    /// Check if a book has completed its warm-up period
+    pub fn is_warmed_up(&self, symbol: &str) -> bool {
+        self.status(symbol)
+            .is_some_and(|status| status.warmed_up.load(Ordering::Relaxed))
+    }
+
+    /// Check if a book was resumed from disk and awaits confirmation by
+    /// the diff stream
+    pub fn is_resumed(&self, symbol: &str) -> bool {
+        self.status(symbol)
+            .is_some_and(|status| status.resumed.load(Ordering::Relaxed))
+    }
+
+    /// When each initialized book last changed (ms): the event time of
+    /// its last applied update, or the local time its snapshot was applied
+    /// if later, so a book that never updates after a snapshot still ages
+    pub fn update_times(&self) -> Vec<(String, u64)> {
+        self.symbols
+            .iter()
+            .filter(|symbol| self.is_initialized(symbol))
+            .filter_map(|symbol| {
+                let status = self.status(symbol)?;
+                let updated_at = status
+                    .last_update_time
+                    .load(Ordering::Relaxed)
+                    .max(status.snapshot_at.load(Ordering::Relaxed));
+                (updated_at > 0).then(|| (symbol.clone(), updated_at))
+            })
+            .collect()
+    }
+
+    async fn send(&self, symbol: &str, command: BookCommand) {
+        if let Some(book) = self.books.get(symbol) {
+            // Tasks only stop with the runtime
+            let _ = book.commands.send(command).await;
+        }
+    }
+
+    /// (Re)initialize a book from a snapshot
+    pub async fn init(&self, snapshot: BookSnapshot) {
+        let symbol = snapshot.symbol.clone();
+        self.send(&symbol, BookCommand::Snapshot(snapshot)).await;
+    }
+
+    /// Queue a diff for its book; unconfigured symbols are ignored
+    pub async fn apply(&self, delta: Delta) {
+        let symbol = delta.update.symbol.clone();
+        self.send(&symbol, BookCommand::Delta(Box::new(delta)))
+            .await;
+    }
+
+    /// Queue a trade for its symbol's rolling metrics
+    pub async fn trade(&self, trade: Trade) {
+        let symbol = trade.symbol.clone();
+        self.send(&symbol, BookCommand::Trade(Box::new(trade)))
+            .await;
+    }
+
+    /// A symbol's rolling trade metrics over the window ending at `now_ms`
+    /// (exchange time); `None` before its first trade
+    pub async fn trade_metrics(&self, symbol: &str, now_ms: u64) -> Option<TradeMetrics> {
+        let (tx, rx) = oneshot::channel();
+        self.send(symbol, BookCommand::TradeMetrics(now_ms, tx))
+            .await;
+        rx.await.ok().flatten()
+    }
+
+    /// Run `f` against a symbol's manager on its task, after the commands
+    /// already queued; `None` for unconfigured symbols
+    pub async fn inspect<R: Send + 'static>(
+        &self,
+        symbol: &str,
+        f: impl FnOnce(&mut OrderBookManager) -> R + Send + 'static,
+    ) -> Option<R> {
+        let (tx, rx) = oneshot::channel();
+        let inspect: Inspect = Box::new(move |manager| {
+            let _ = tx.send(f(manager));
+        });
+        self.send(symbol, BookCommand::Inspect(inspect)).await;
+        rx.await.ok()
+    }
+
+    /// State of an initialized book as of its snapshot or last published
+    /// update
+    pub fn state(&self, symbol: &str) -> Option<Arc<OrderBookState>> {
+        self.status(symbol)?.latest()
+    }
+
+    /// States of every initialized book, in symbol order
+    pub fn states(&self) -> Vec<Arc<OrderBookState>> {
+        self.symbols
+            .iter()
+            .filter_map(|symbol| self.state(symbol))
+            .collect()
+    }
+
+    /// Retained levels of every initialized book, to save for a warm
+    /// restart
+    pub async fn snapshots(&self) -> Vec<BookSnapshot> {
+        let mut snapshots = Vec::new();
+        for symbol in &self.symbols {
+            if let Some(saved) = self.inspect(symbol, |manager| manager.snapshots()).await {
+                snapshots.extend(saved);
+            }
+        }
+        snapshots
+    }
+
+    /// Update a symbol's trading rules
+    pub async fn set_instrument(&self, symbol: &str, instrument: InstrumentInfo) {
+        let owned = symbol.to_string();
+        self.inspect(symbol, move |manager| {
+            manager.set_instrument(&owned, instrument)
+        })
+        .await;
+    }
+
+    /// Receive a symbol's book events; see `OrderBookManager::subscribe`
+    pub async fn subscribe(&self, symbol: &str) -> Option<broadcast::Receiver<BookEvent>> {
+        let owned = symbol.to_string();
+        self.inspect(symbol, move |manager| manager.subscribe(&owned))
+            .await
+    }
+
+    /// Ask the feed to resync a discarded book from a snapshot
+    fn request_resync(&self, symbol: &str) {
+        let _ = self.resyncs.send(symbol.to_string());
+    }
+}
+
+impl BookTasks {
+    /// Start every book's task; returns the symbols whose books were
+    /// discarded and need a snapshot, for the feed to act on
+    pub fn spawn(self, state: Arc<AppState>) -> mpsc::UnboundedReceiver<String> {
+        let config = &state.config;
+        for (symbol, manager, commands, status) in self.tasks {
+            // Books resumed from disk age from startup
+            if manager.is_initialized(&symbol) {
+                status.snapshot_applied(state.time.now_millis());
+            }
+            let window_ms = config.trade_metrics_window_secs_for(&symbol).max(1) * 1000;
+            let analytics = BookAnalytics {
+                trade_metrics: TradeMetricsTracker::new(window_ms),
+                anomalies: config
+                    .anomaly_detection_enabled
+                    .then(|| AnomalyDetector::new(config.anomaly_policy())),
+            };
+            tokio::spawn(run_book(
+                symbol,
+                manager,
+                analytics,
+                commands,
+                status,
+                state.clone(),
+            ));
+        }
+        self.resyncs
+    }
+}
+
+/// Work through one symbol's commands
+async fn run_book(
+    symbol: String,
+    mut manager: OrderBookManager,
+    mut analytics: BookAnalytics,
+    mut commands: mpsc::Receiver<BookCommand>,
+    status: Arc<BookStatus>,
+    state: Arc<AppState>,
+) {
+    // Initialized but not published since, for want of warm-up
+    let mut warming = false;
+    while let Some(command) = commands.recv().await {
+        match command {
+            BookCommand::Snapshot(snapshot) => {
+                manager.init_book(&snapshot);
+                status.set_latest(manager.get_state(&symbol));
+                status.snapshot_applied(state.time.now_millis());
+                warming = !manager.is_warmed_up(&symbol);
+                schedule_warmup_check(&state, &symbol);
+                state
+                    .dead_man
+                    .record_resync(&symbol, state.time.now_millis());
+                publish_status(
+                    &state,
+                    StatusEvent::Resynced {
+                        symbol: symbol.clone(),
+                        last_update_id: snapshot.last_update_id,
+                        timestamp: state.time.now_millis(),
+                    },
+                )
+                .await;
+            }
+            BookCommand::Delta(delta) => {
+                match apply_delta(&mut manager, &mut analytics, *delta, &state).await {
+                    Ok(Some(published)) => {
+                        warming = false;
+                        status.set_latest(Some(published));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!(error = %e, symbol = %symbol, "Failed to process depth update")
+                    }
+                }
+            }
+            BookCommand::Trade(trade) => {
+                let trade_metrics = &mut analytics.trade_metrics;
+                trade_metrics.note_sheds(state.degradation.analytics_sheds(), trade.trade_time);
+                trade_metrics.on_trade(&trade);
+            }
+            BookCommand::TradeMetrics(now_ms, reply) => {
+                let _ = reply.send(analytics.trade_metrics.metrics(&symbol, now_ms));
+            }
+            BookCommand::WarmupCheck => {
+                if warming && manager.is_warmed_up(&symbol) {
+                    warming = false;
+                    if let Some(published) =
+                        publish_warmed_up(&manager, &mut analytics, &symbol, &state).await
+                    {
+                        status.set_latest(Some(published));
+                    }
+                }
+            }
+            BookCommand::Inspect(f) => f(&mut manager),
+        }
+        status.refresh(&manager, &symbol);
+    }
+    error!(symbol = %symbol, "Book task stopped");
+}
+
+/// Check a freshly initialized book again once its warm-up time is up, so
+/// a quiet symbol is published then rather than at its next diff
+fn schedule_warmup_check(state: &Arc<AppState>, symbol: &str) {
+    let min_duration = state.config.warmup_policy().min_duration;
+    if min_duration.is_zero() {
+        return;
+    }
+    let (state, symbol) = (state.clone(), symbol.to_string());
+    tokio::spawn(async move {
+        // Books time their warm-up on the wall clock
+        tokio::time::sleep(min_duration).await;
+        state.books.send(&symbol, BookCommand::WarmupCheck).await;
+    });
+}
+
+/// Publish the state of a book that warmed up between diffs
+async fn publish_warmed_up(
+    manager: &OrderBookManager,
+    analytics: &mut BookAnalytics,
+    symbol: &str,
+    state: &AppState,
+) -> Option<OrderBookState> {
+    let mut book = manager.get_state(symbol)?;
+    if state.degradation.analytics_enabled() {
+        book.trade_metrics = analytics.trade_metrics.metrics(symbol, book.timestamp);
+    }
+    if let Err(e) = state.publisher.publish(&book).await {
+        warn!(error = %e, symbol, "Failed to publish warmed-up book");
+        return None;
+    }
+    Some(book)
+}
+
+/// Apply a diff to the book and publish the result, which is returned
+async fn apply_delta(
+    manager: &mut OrderBookManager,
+    analytics: &mut BookAnalytics,
+    delta: Delta,
+    state: &AppState,
+) -> crate::error::Result<Option<OrderBookState>> {
+    let update = &delta.update;
+    let anomalies = match &mut analytics.anomalies {
+        Some(detector) if state.degradation.analytics_enabled() => detector.on_delta(update),
+        _ => Vec::new(),
+    };
+    for anomaly in &anomalies {
+        publish_anomaly(state, anomaly).await;
+    }
+
+    let applied = match manager.apply_update(update) {
+        Ok(applied) => applied,
+        Err(e) => {
+            // Books that crossed under the resync policy, ran out of known
+            // depth or missed diffs are discarded
+            if !manager.is_initialized(&update.symbol) {
+                warn!(error = %e, "Book discarded, resyncing from snapshot");
+                state.books.request_resync(&update.symbol);
+                if let MarketDataError::SequenceMismatch { expected, got } = e {
+                    publish_status(
+                        state,
+                        StatusEvent::Gap {
+                            symbol: update.symbol.clone(),
+                            expected,
+                            received: got,
+                            timestamp: state.time.now_millis(),
+                        },
+                    )
+                    .await;
+                }
+            }
+            return Err(e);
+        }
+    };
+    if !applied {
+        return Ok(None);
+    }
+    let applied_at_us = state.time.now_micros();
+    let bbo = state
+        .config
+        .bbo_events_enabled
+        .then(|| manager.bbo_change(&update.symbol))
+        .flatten();
+    let Some(mut book) = manager.get_state(&update.symbol) else {
+        return Ok(None);
+    };
+
+    // Top-of-book consumers hear first
+    if let Some(bbo) = &bbo {
+        publish_bbo(state, bbo).await;
+    }
+    let crossed = match &mut analytics.anomalies {
+        Some(detector) if state.degradation.analytics_enabled() => detector.on_state(&book),
+        _ => None,
+    };
+    if let Some(anomaly) = &crossed {
+        publish_anomaly(state, anomaly).await;
+    }
+    // Books still warming up after a snapshot are kept current, and their
+    // BBO changes go out, but their states are not published
+    if !manager.is_warmed_up(&update.symbol) {
+        return Ok(None);
+    }
+    if state.degradation.analytics_enabled() {
+        let trade_metrics = &mut analytics.trade_metrics;
+        trade_metrics.note_sheds(state.degradation.analytics_sheds(), book.timestamp);
+        book.trade_metrics = trade_metrics.metrics(&update.symbol, book.timestamp);
+    }
+    book.provenance = Some(Provenance {
+        connection_id: delta.connection_id,
+        shard: delta.shard,
+        received_at_us: delta.received_at_us,
+        applied_at_us,
+        conflated: 0,
+    });
+    state.publisher.publish(&book).await?;
+
+    let published_at_us = state.time.now_micros();
+    state.degradation.observe(Duration::from_micros(
+        published_at_us.saturating_sub(delta.released_at_us),
+    ));
+    let times = StageTimes {
+        event_time_us: state.clock.to_local_us(update.event_time * 1000),
+        received_at_us: delta.received_at_us,
+        parsed_at_us: delta.parsed_at_us,
+        applied_at_us,
+        published_at_us,
+    };
+    metrics::observe_stages(&update.symbol, &times);
+    if state.degradation.analytics_enabled() {
+        state.latency.record(&update.symbol, &times);
+    }
+    Ok(Some(book))
+}
+
+/// Send a top-of-book change to consumers
+async fn publish_bbo(state: &AppState, bbo: &BboChanged) {
+    if let Err(e) = state.publisher.publish_bbo(bbo).await {
+        warn!(error = %e, symbol = %bbo.symbol, "Failed to publish BBO change");
+    }
+}
+
+/// Send a detected anomaly to consumers
+async fn publish_anomaly(state: &AppState, anomaly: &MarketAnomaly) {
+    if let Err(e) = state.publisher.publish_anomaly(anomaly).await {
+        warn!(error = %e, symbol = %anomaly.symbol, "Failed to publish anomaly");
+    }
+}
+
+/// Tell consumers the feed's state changed
+pub(crate) async fn publish_status(state: &AppState, status: StatusEvent) {
+    if let Err(e) = state.publisher.publish_status(&status).await {
+        warn!(error = %e, status = ?status, "Failed to publish status");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PriceLevel, Venue};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_status_mirrors_each_book() {
+        let symbols = vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()];
+        let (books, tasks) = Books::new(OrderBookManager::new(), &symbols);
+        let (symbol, mut manager, _, status) = tasks.tasks.into_iter().next().unwrap();
+        assert_eq!(symbol, "BTCUSDT");
+
+        let level = |price, quantity| PriceLevel { price, quantity };
+        manager.init_book(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: symbol.clone(),
+            last_update_id: 100,
+            bids: vec![level(dec!(100), dec!(1))],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        status.set_latest(manager.get_state(&symbol));
+        status.refresh(&manager, &symbol);
+        assert!(books.is_initialized("BTCUSDT"));
+        assert_eq!(books.state("BTCUSDT").unwrap().last_update_id, 100);
+        assert!(books.state("ETHUSDT").is_none());
+        assert!(!books.is_initialized("ETHUSDT"));
+        // Snapshot only: the book ages from when it was applied
+        status.snapshot_applied(4_000);
+        assert_eq!(books.update_times(), [("BTCUSDT".to_string(), 4_000)]);
+
+        manager
+            .apply_update(&DepthDelta {
+                venue: Venue::Binance,
+                event_time: 5_000,
+                symbol: symbol.clone(),
+                first_update_id: 101,
+                final_update_id: 101,
+                bids: vec![level(dec!(100), dec!(2))],
+                asks: vec![],
+            })
+            .unwrap();
+        status.refresh(&manager, &symbol);
+        assert_eq!(books.update_times(), [("BTCUSDT".to_string(), 5_000)]);
+
+        // A discarded book has no state to serve
+        manager
+            .apply_update(&DepthDelta {
+                venue: Venue::Binance,
+                event_time: 6_000,
+                symbol: symbol.clone(),
+                first_update_id: 200,
+                final_update_id: 200,
+                bids: vec![],
+                asks: vec![],
+            })
+            .unwrap_err();
+        status.refresh(&manager, &symbol);
+        assert!(books.state("BTCUSDT").is_none());
+    }
+}
@@ -0,0 +1,53 @@
+//! In-process sampling profiler endpoint
+//!
+//! Exposes CPU flamegraphs over HTTP so latency investigations on a live
+//! box don't require attaching `perf`. Enabled with the `pprof` feature.
+
+use axum::extract::Query;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Query parameters for `/debug/pprof`
+#[derive(Debug, Deserialize)]
+pub struct ProfileParams {
+    /// Sampling duration in seconds (default 10, max 120)
+    pub seconds: Option<u64>,
+    /// Sampling frequency in Hz (default 99, max 1000)
+    pub frequency: Option<i32>,
+}
+
+/// Profile the whole process for a while and return an SVG flamegraph
+pub async fn flamegraph(
+    Query(params): Query<ProfileParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let seconds = params.seconds.unwrap_or(10).clamp(1, 120);
+    let frequency = params.frequency.unwrap_or(99).clamp(1, 1000);
+
+    // The profiler guard is not Send; sample on a blocking thread
+    let svg = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(frequency)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+            .map_err(|e| format!("Failed to start profiler: {}", e))?;
+
+        std::thread::sleep(Duration::from_secs(seconds));
+
+        let report = guard
+            .report()
+            .build()
+            .map_err(|e| format!("Failed to build report: {}", e))?;
+        let mut svg = Vec::new();
+        report
+            .flamegraph(&mut svg)
+            .map_err(|e| format!("Failed to render flamegraph: {}", e))?;
+        Ok(svg)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
@@ -0,0 +1,152 @@
+//! Protobuf message types
+//!
+//! Generated from `proto/market_data.proto`, with conversions from the
+//! crate's own types. Decimals are carried as strings. Enabled with the
+//! `protobuf` feature (implied by `grpc`).
+
+use crate::{event, orderbook};
+
+include!(concat!(env!("OUT_DIR"), "/orpflow.marketdata.v1.rs"));
+
+impl From<&orderbook::OrderBookState> for OrderBook {
+    fn from(state: &orderbook::OrderBookState) -> Self {
+        let levels = |levels: &[orderbook::Level]| {
+            levels
+                .iter()
+                .map(|l| Level {
+                    price: l.price.to_string(),
+                    quantity: l.quantity.to_string(),
+                })
+                .collect()
+        };
+        let metrics = &state.metrics;
+
+        OrderBook {
+            symbol: state.symbol.clone(),
+            timestamp: state.timestamp,
+            last_update_id: state.last_update_id,
+            bids: levels(&state.bids),
+            asks: levels(&state.asks),
+            metrics: Some(Metrics {
+                mid_price: metrics.mid_price.map(|d| d.to_string()),
+                microprice: metrics.microprice.map(|d| d.to_string()),
+                realized_vol: metrics.realized_vol.map(|d| d.to_string()),
+                spread_bps: metrics.spread_bps.map(|d| d.to_string()),
+                imbalance: metrics.imbalance.map(|d| d.to_string()),
+                weighted_imbalance: metrics.weighted_imbalance.map(|d| d.to_string()),
+                bid_depth: metrics.bid_depth.map(|d| d.to_string()),
+                ask_depth: metrics.ask_depth.map(|d| d.to_string()),
+                bid_levels: metrics.bid_levels as u32,
+                ask_levels: metrics.ask_levels as u32,
+                price_impact: metrics
+                    .price_impact
+                    .iter()
+                    .map(|impact| PriceImpact {
+                        quantity: impact.quantity.to_string(),
+                        buy_bps: impact.buy_bps.map(|d| d.to_string()),
+                        sell_bps: impact.sell_bps.map(|d| d.to_string()),
+                    })
+                    .collect(),
+                bid_slope: metrics.bid_slope.map(|d| d.to_string()),
+                ask_slope: metrics.ask_slope.map(|d| d.to_string()),
+                depth_bands: metrics
+                    .depth_bands
+                    .iter()
+                    .map(|band| DepthBand {
+                        bps: band.bps.to_string(),
+                        bid_depth: band.bid_depth.to_string(),
+                        ask_depth: band.ask_depth.to_string(),
+                    })
+                    .collect(),
+            }),
+            provenance: state.provenance.as_ref().map(|p| Provenance {
+                connection_id: p.connection_id,
+                shard: p.shard,
+                received_at_us: p.received_at_us,
+                applied_at_us: p.applied_at_us,
+                conflated: p.conflated,
+            }),
+            checksum: state.checksum,
+        }
+    }
+}
+
+impl From<&event::Trade> for Trade {
+    fn from(trade: &event::Trade) -> Self {
+        let taker_side = match trade.taker_side {
+            orderbook::Side::Bid => Side::Bid,
+            orderbook::Side::Ask => Side::Ask,
+        };
+
+        Trade {
+            venue: trade.venue.to_string(),
+            symbol: trade.symbol.clone(),
+            event_time: trade.event_time,
+            trade_id: trade.trade_id,
+            price: trade.price.to_string(),
+            quantity: trade.quantity.to_string(),
+            taker_side: taker_side.into(),
+            trade_time: trade.trade_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{
+        DepthBand as BookDepthBand, Level, OrderBookMetrics, OrderBookState,
+        Provenance as BookProvenance,
+    };
+    use prost::Message;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_order_book_round_trip() {
+        let state = OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            last_update_id: 42,
+            bids: vec![Level {
+                price: dec!(50000.10),
+                quantity: dec!(1.5),
+            }],
+            asks: vec![Level {
+                price: dec!(50000.20),
+                quantity: dec!(0.25),
+            }],
+            metrics: OrderBookMetrics {
+                bid_slope: Some(dec!(1.25)),
+                depth_bands: vec![BookDepthBand {
+                    bps: dec!(10),
+                    bid_depth: dec!(1.5),
+                    ask_depth: dec!(0.25),
+                }],
+                ..Default::default()
+            },
+            provenance: Some(BookProvenance {
+                connection_id: 3,
+                shard: 1,
+                received_at_us: 10,
+                applied_at_us: 20,
+                conflated: 2,
+            }),
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        };
+
+        let bytes = OrderBook::from(&state).encode_to_vec();
+        let decoded = OrderBook::decode(bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded.symbol, "BTCUSDT");
+        assert_eq!(decoded.bids[0].price, "50000.10");
+        assert_eq!(decoded.asks[0].quantity, "0.25");
+        assert_eq!(decoded.provenance.unwrap().conflated, 2);
+        let metrics = decoded.metrics.unwrap();
+        assert_eq!(metrics.bid_slope.as_deref(), Some("1.25"));
+        assert_eq!(metrics.ask_slope, None);
+        assert_eq!(metrics.depth_bands[0].bps, "10");
+        assert_eq!(metrics.depth_bands[0].ask_depth, "0.25");
+    }
+}
@@ -0,0 +1,496 @@
+//! Arrow IPC batch export
+//!
+//! Buffers published book states and trades from the live feed and writes
+//! them on a timer as Arrow record batches, one IPC stream file per kind
+//! (`books-<start>.arrows`, `trades-<start>.arrows`). Research tooling reads
+//! the files directly, e.g. `pyarrow.ipc.open_stream` or
+//! `polars.read_ipc_stream`, also while they are still being written.
+//!
+//! Books are exported as top of book plus metrics, one row per published
+//! state; trades one row per trade. Prices and quantities are converted to
+//! doubles. Batches are built with `arrow-array` and framed by the
+//! `arrow-ipc` stream writer (`--features arrow`).
+
+use arrow_array::builder::{
+    ArrayBuilder, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder,
+    TimestampMillisecondBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{Field, Schema, SchemaRef, TimeUnit};
+use rust_decimal::prelude::ToPrimitive;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use super::LiveFeed;
+use crate::error::Result;
+use crate::event::Trade;
+use crate::orderbook::{OrderBookState, Side};
+
+/// Column type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataType {
+    Utf8,
+    Bool,
+    Int64,
+    UInt64,
+    Float64,
+    /// Milliseconds since the epoch, UTC
+    TimestampMs,
+}
+
+impl DataType {
+    fn to_arrow(self) -> arrow_schema::DataType {
+        match self {
+            DataType::Utf8 => arrow_schema::DataType::Utf8,
+            DataType::Bool => arrow_schema::DataType::Boolean,
+            DataType::Int64 => arrow_schema::DataType::Int64,
+            DataType::UInt64 => arrow_schema::DataType::UInt64,
+            DataType::Float64 => arrow_schema::DataType::Float64,
+            DataType::TimestampMs => {
+                arrow_schema::DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+            }
+        }
+    }
+}
+
+/// Array builder of a column
+#[derive(Debug)]
+enum Builder {
+    Utf8(StringBuilder),
+    Bool(BooleanBuilder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float64(Float64Builder),
+    TimestampMs(TimestampMillisecondBuilder),
+}
+
+/// One column of a batch being built
+#[derive(Debug)]
+pub struct Column {
+    field: Field,
+    builder: Builder,
+}
+
+impl Column {
+    fn new(name: &'static str, data_type: DataType, nullable: bool) -> Self {
+        let builder = match data_type {
+            DataType::Utf8 => Builder::Utf8(StringBuilder::new()),
+            DataType::Bool => Builder::Bool(BooleanBuilder::new()),
+            DataType::Int64 => Builder::Int64(Int64Builder::new()),
+            DataType::UInt64 => Builder::UInt64(UInt64Builder::new()),
+            DataType::Float64 => Builder::Float64(Float64Builder::new()),
+            DataType::TimestampMs => {
+                Builder::TimestampMs(TimestampMillisecondBuilder::new().with_timezone("UTC"))
+            }
+        };
+        Self {
+            field: Field::new(name, data_type.to_arrow(), nullable),
+            builder,
+        }
+    }
+
+    fn push_str(&mut self, value: &str) {
+        match &mut self.builder {
+            Builder::Utf8(builder) => builder.append_value(value),
+            _ => self.mismatch("a string"),
+        }
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        match &mut self.builder {
+            Builder::Bool(builder) => builder.append_value(value),
+            _ => self.mismatch("a bool"),
+        }
+    }
+
+    /// An `Int64` value, or milliseconds of a `TimestampMs` column
+    fn push_i64(&mut self, value: i64) {
+        match &mut self.builder {
+            Builder::Int64(builder) => builder.append_value(value),
+            Builder::TimestampMs(builder) => builder.append_value(value),
+            _ => self.mismatch("an i64"),
+        }
+    }
+
+    fn push_u64(&mut self, value: u64) {
+        match &mut self.builder {
+            Builder::UInt64(builder) => builder.append_value(value),
+            _ => self.mismatch("a u64"),
+        }
+    }
+
+    fn push_f64(&mut self, value: Option<f64>) {
+        match &mut self.builder {
+            Builder::Float64(builder) => builder.append_option(value),
+            _ => self.mismatch("an f64"),
+        }
+    }
+
+    fn mismatch(&self, value: &str) -> ! {
+        panic!(
+            "{} pushed to {} column {}",
+            value,
+            self.field.data_type(),
+            self.field.name()
+        )
+    }
+
+    fn len(&self) -> usize {
+        match &self.builder {
+            Builder::Utf8(builder) => builder.len(),
+            Builder::Bool(builder) => builder.len(),
+            Builder::Int64(builder) => builder.len(),
+            Builder::UInt64(builder) => builder.len(),
+            Builder::Float64(builder) => builder.len(),
+            Builder::TimestampMs(builder) => builder.len(),
+        }
+    }
+
+    /// The values pushed so far as an array; the builder starts over
+    fn finish(&mut self) -> ArrayRef {
+        match &mut self.builder {
+            Builder::Utf8(builder) => Arc::new(builder.finish()),
+            Builder::Bool(builder) => Arc::new(builder.finish()),
+            Builder::Int64(builder) => Arc::new(builder.finish()),
+            Builder::UInt64(builder) => Arc::new(builder.finish()),
+            Builder::Float64(builder) => Arc::new(builder.finish()),
+            Builder::TimestampMs(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// A record type exported as rows
+pub trait Row {
+    /// Empty columns, in schema order
+    fn columns() -> Vec<Column>;
+
+    /// Append this record to columns created by `columns`
+    fn append(&self, columns: &mut [Column]);
+}
+
+impl Row for OrderBookState {
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("symbol", DataType::Utf8, false),
+            Column::new("timestamp", DataType::TimestampMs, false),
+            Column::new("last_update_id", DataType::UInt64, false),
+            Column::new("bid_price", DataType::Float64, true),
+            Column::new("bid_quantity", DataType::Float64, true),
+            Column::new("ask_price", DataType::Float64, true),
+            Column::new("ask_quantity", DataType::Float64, true),
+            Column::new("mid_price", DataType::Float64, true),
+            Column::new("spread_bps", DataType::Float64, true),
+            Column::new("imbalance", DataType::Float64, true),
+            Column::new("weighted_imbalance", DataType::Float64, true),
+            Column::new("bid_depth", DataType::Float64, true),
+            Column::new("ask_depth", DataType::Float64, true),
+        ]
+    }
+
+    fn append(&self, columns: &mut [Column]) {
+        let metrics = &self.metrics;
+        let bid = self.bids.first();
+        let ask = self.asks.first();
+        columns[0].push_str(&self.symbol);
+        columns[1].push_i64(self.timestamp as i64);
+        columns[2].push_u64(self.last_update_id);
+        columns[3].push_f64(bid.and_then(|l| l.price.to_f64()));
+        columns[4].push_f64(bid.and_then(|l| l.quantity.to_f64()));
+        columns[5].push_f64(ask.and_then(|l| l.price.to_f64()));
+        columns[6].push_f64(ask.and_then(|l| l.quantity.to_f64()));
+        columns[7].push_f64(metrics.mid_price.and_then(|d| d.to_f64()));
+        columns[8].push_f64(metrics.spread_bps.and_then(|d| d.to_f64()));
+        columns[9].push_f64(metrics.imbalance.and_then(|d| d.to_f64()));
+        columns[10].push_f64(metrics.weighted_imbalance.and_then(|d| d.to_f64()));
+        columns[11].push_f64(metrics.bid_depth.and_then(|d| d.to_f64()));
+        columns[12].push_f64(metrics.ask_depth.and_then(|d| d.to_f64()));
+    }
+}
+
+impl Row for Trade {
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("symbol", DataType::Utf8, false),
+            Column::new("trade_time", DataType::TimestampMs, false),
+            Column::new("event_time", DataType::TimestampMs, false),
+            Column::new("trade_id", DataType::UInt64, false),
+            Column::new("price", DataType::Float64, true),
+            Column::new("quantity", DataType::Float64, true),
+            Column::new("buyer_is_taker", DataType::Bool, false),
+        ]
+    }
+
+    fn append(&self, columns: &mut [Column]) {
+        columns[0].push_str(&self.symbol);
+        columns[1].push_i64(self.trade_time as i64);
+        columns[2].push_i64(self.event_time as i64);
+        columns[3].push_u64(self.trade_id);
+        columns[4].push_f64(self.price.to_f64());
+        columns[5].push_f64(self.quantity.to_f64());
+        columns[6].push_bool(self.taker_side == Side::Bid);
+    }
+}
+
+/// Rows buffered for the next record batch
+#[derive(Debug)]
+pub struct Batch<R> {
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    rows: PhantomData<R>,
+}
+
+impl<R: Row> Batch<R> {
+    pub fn new() -> Self {
+        let columns = R::columns();
+        let fields: Vec<Field> = columns.iter().map(|c| c.field.clone()).collect();
+        Self {
+            schema: Arc::new(Schema::new(fields)),
+            columns,
+            rows: PhantomData,
+        }
+    }
+
+    /// Add a row
+    pub fn push(&mut self, row: &R) {
+        row.append(&mut self.columns);
+    }
+
+    /// Number of buffered rows
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, Column::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Record batch of the buffered rows; clears them
+    pub fn take(&mut self) -> Result<RecordBatch> {
+        let arrays = self.columns.iter_mut().map(Column::finish).collect();
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+impl<R: Row> Default for Batch<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One IPC stream file and the batch pending for it
+///
+/// The stream writer encodes into memory; its output is moved to the file
+/// after every message.
+struct Output<R> {
+    path: PathBuf,
+    file: File,
+    writer: StreamWriter<Vec<u8>>,
+    batch: Batch<R>,
+}
+
+impl<R: Row> Output<R> {
+    async fn create(path: PathBuf) -> Result<Self> {
+        let batch = Batch::new();
+        let writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+        let file = File::create(&path).await?;
+        let mut output = Self {
+            path,
+            file,
+            writer,
+            batch,
+        };
+        output.write_out().await?;
+        Ok(output)
+    }
+
+    /// Move what the stream writer encoded to the file
+    async fn write_out(&mut self) -> Result<()> {
+        let bytes = std::mem::take(self.writer.get_mut());
+        self.file.write_all(&bytes).await?;
+        Ok(())
+    }
+
+    async fn write_batch(&mut self) -> Result<()> {
+        let batch = self.batch.take()?;
+        let written = self.writer.write(&batch);
+        self.write_out().await?;
+        Ok(written?)
+    }
+
+    async fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let rows = self.batch.len();
+        if let Err(e) = self.write_batch().await {
+            warn!(error = %e, path = %self.path.display(), rows, "Failed to write Arrow batch");
+        }
+    }
+
+    /// Write the pending rows and the end-of-stream marker
+    async fn finish(&mut self) -> Result<()> {
+        self.flush().await;
+        self.writer.finish()?;
+        self.write_out().await
+    }
+}
+
+/// Export the live feed to Arrow IPC stream files in `dir`, writing a
+/// record batch per kind every `interval`
+pub async fn export(dir: &Path, interval: Duration, feed: LiveFeed) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let started = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+    let mut books =
+        Output::<OrderBookState>::create(dir.join(format!("books-{}.arrows", started))).await?;
+    let mut trades =
+        Output::<Trade>::create(dir.join(format!("trades-{}.arrows", started))).await?;
+    info!(dir = %dir.display(), "Arrow export enabled");
+
+    let mut book_rx = feed.subscribe_books();
+    let mut trade_rx = feed.subscribe_trades();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            state = book_rx.recv() => match state {
+                Ok(state) => books.batch.push(&state),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Arrow export lagging, skipped book states");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            trade = trade_rx.recv() => match trade {
+                Ok(trade) => trades.batch.push(&trade),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Arrow export lagging, skipped trades");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = ticker.tick() => {
+                books.flush().await;
+                trades.flush().await;
+            }
+        }
+    }
+
+    books.finish().await?;
+    trades.finish().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use crate::orderbook::{Level, OrderBookMetrics};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float64Type, TimestampMillisecondType, UInt64Type};
+    use arrow_array::Array;
+    use arrow_ipc::reader::StreamReader;
+    use rust_decimal_macros::dec;
+
+    /// Batches of a finished stream file, read back with the Arrow reader
+    fn read(path: &Path) -> Vec<RecordBatch> {
+        let file = std::fs::File::open(path).unwrap();
+        StreamReader::try_new(file, None)
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streams_read_back_with_arrow_reader() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut trades = Output::<Trade>::create(dir.path().join("trades.arrows"))
+            .await
+            .unwrap();
+        for (i, side) in [Side::Bid, Side::Ask, Side::Bid].into_iter().enumerate() {
+            trades.batch.push(&Trade {
+                venue: Venue::Binance,
+                symbol: "BTCUSDT".to_string(),
+                event_time: 1_000 + i as u64,
+                trade_id: i as u64,
+                price: dec!(50000.5),
+                quantity: dec!(0.25),
+                taker_side: side,
+                trade_time: 1_000 + i as u64,
+            });
+        }
+        assert_eq!(trades.batch.len(), 3);
+        trades.flush().await;
+        assert!(trades.batch.is_empty());
+        trades.finish().await.unwrap();
+
+        let batches = read(&dir.path().join("trades.arrows"));
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema(), Batch::<Trade>::new().schema());
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.column(0).as_string::<i32>().value(2), "BTCUSDT");
+        let trade_time = batch.column(1).as_primitive::<TimestampMillisecondType>();
+        assert_eq!(trade_time.values(), &[1_000, 1_001, 1_002]);
+        assert_eq!(batch.column(3).as_primitive::<UInt64Type>().value(1), 1);
+        assert_eq!(
+            batch.column(4).as_primitive::<Float64Type>().value(0),
+            50000.5
+        );
+        let taker = batch.column(6).as_boolean();
+        assert_eq!(
+            taker.iter().collect::<Vec<_>>(),
+            [Some(true), Some(false), Some(true)]
+        );
+
+        // A book without asks has null ask columns
+        let mut books = Output::<OrderBookState>::create(dir.path().join("books.arrows"))
+            .await
+            .unwrap();
+        books.batch.push(&OrderBookState {
+            symbol: "ETHUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            last_update_id: 7,
+            bids: vec![Level {
+                price: dec!(2000),
+                quantity: dec!(1.5),
+            }],
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        });
+        books.finish().await.unwrap();
+
+        let batches = read(&dir.path().join("books.arrows"));
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 1);
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_primitive::<Float64Type>()
+        };
+        assert_eq!(column("bid_price").value(0), 2000.0);
+        assert_eq!(column("bid_quantity").value(0), 1.5);
+        assert!(column("ask_price").is_null(0));
+        assert!(column("bid_depth").is_null(0));
+        assert_eq!(
+            batch
+                .column_by_name("last_update_id")
+                .unwrap()
+                .as_primitive::<UInt64Type>()
+                .value(0),
+            7
+        );
+    }
+}
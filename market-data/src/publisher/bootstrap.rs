@@ -0,0 +1,241 @@
+//! Consumer bootstrap protocol
+//!
+//! With bootstrap enabled, every state written to the IPC socket is wrapped
+//! in a `BootstrapFrame::Book` carrying a per-symbol sequence number, and
+//! the publisher keeps the most recent states per symbol in a replay buffer.
+//!
+//! After connecting, the publisher waits briefly for the consumer to send a
+//! `BootstrapRequest` with the publisher epoch and last sequence it saw per
+//! symbol. It answers with a `BootstrapFrame::Reply` describing how each
+//! symbol is recovered, followed by the gap-fill or snapshot frames, and
+//! then continues with live frames:
+//!
+//! - gap fill: every missed state, when all of them are still buffered
+//! - snapshot: the latest state only, when the gap can't be filled, the
+//!   consumer is new, or the publisher restarted (epoch mismatch)
+//!
+//! A consumer that sends nothing within the timeout is treated as new.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::orderbook::OrderBookState;
+
+/// Consumer → publisher, first frame after the connection is established
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BootstrapRequest {
+    /// Publisher epoch the sequence numbers belong to (0 if unknown)
+    pub epoch: u64,
+    /// Last sequence number seen per symbol
+    pub last_seen: HashMap<String, u64>,
+}
+
+/// How a symbol is brought up to date
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recovery {
+    /// Nothing was missed
+    UpToDate,
+    /// Missed states `from_seq..=to_seq` follow
+    GapFill { from_seq: u64, to_seq: u64 },
+    /// Only the latest state, `seq`, follows
+    Snapshot { seq: u64 },
+}
+
+/// Publisher → consumer frames in bootstrap mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BootstrapFrame {
+    /// Answer to a bootstrap request, sent before any book frame
+    Reply {
+        epoch: u64,
+        symbols: HashMap<String, Recovery>,
+    },
+    /// Book state with its per-symbol sequence; `replayed` marks states
+    /// sent during bootstrap rather than live
+    Book {
+        seq: u64,
+        replayed: bool,
+        state: Box<OrderBookState>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct SymbolHistory {
+    last_seq: u64,
+    states: VecDeque<(u64, OrderBookState)>,
+}
+
+/// Recent published states per symbol, numbered per symbol from 1
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    epoch: u64,
+    depth: usize,
+    symbols: HashMap<String, SymbolHistory>,
+}
+
+impl ReplayBuffer {
+    /// Create a buffer keeping up to `depth` states per symbol
+    ///
+    /// `epoch` identifies this publisher instance; sequence numbers from a
+    /// different epoch are never gap-filled.
+    pub fn new(epoch: u64, depth: usize) -> Self {
+        Self {
+            epoch,
+            depth: depth.max(1),
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Publisher epoch
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Record a published state; returns its sequence number
+    pub fn record(&mut self, state: &OrderBookState) -> u64 {
+        let history = self.symbols.entry(state.symbol.clone()).or_default();
+        history.last_seq += 1;
+        if history.states.len() == self.depth {
+            history.states.pop_front();
+        }
+        history.states.push_back((history.last_seq, state.clone()));
+        history.last_seq
+    }
+
+    /// Build the reply and the frames that bring a consumer up to date
+    pub fn bootstrap(&self, request: &BootstrapRequest) -> Vec<BootstrapFrame> {
+        let same_epoch = request.epoch == self.epoch;
+        let mut recoveries = HashMap::new();
+        let mut frames = Vec::new();
+
+        for (symbol, history) in &self.symbols {
+            let Some((latest_seq, latest)) = history.states.back() else {
+                continue;
+            };
+
+            let last_seen = request
+                .last_seen
+                .get(symbol)
+                .copied()
+                .filter(|_| same_epoch);
+            let oldest_seq = history.states.front().map_or(0, |(seq, _)| *seq);
+
+            let recovery = match last_seen {
+                Some(seen) if seen == *latest_seq => Recovery::UpToDate,
+                Some(seen) if seen < *latest_seq && seen + 1 >= oldest_seq => {
+                    frames.extend(history.states.iter().filter(|(seq, _)| *seq > seen).map(
+                        |(seq, state)| BootstrapFrame::Book {
+                            seq: *seq,
+                            replayed: true,
+                            state: Box::new(state.clone()),
+                        },
+                    ));
+                    Recovery::GapFill {
+                        from_seq: seen + 1,
+                        to_seq: *latest_seq,
+                    }
+                }
+                _ => {
+                    frames.push(BootstrapFrame::Book {
+                        seq: *latest_seq,
+                        replayed: true,
+                        state: Box::new(latest.clone()),
+                    });
+                    Recovery::Snapshot { seq: *latest_seq }
+                }
+            };
+            recoveries.insert(symbol.clone(), recovery);
+        }
+
+        frames.insert(
+            0,
+            BootstrapFrame::Reply {
+                epoch: self.epoch,
+                symbols: recoveries,
+            },
+        );
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBookMetrics;
+
+    fn state(symbol: &str, update_id: u64) -> OrderBookState {
+        OrderBookState {
+            symbol: symbol.to_string(),
+            timestamp: 0,
+            last_update_id: update_id,
+            bids: vec![],
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        }
+    }
+
+    fn recovery(frames: &[BootstrapFrame], symbol: &str) -> Recovery {
+        match &frames[0] {
+            BootstrapFrame::Reply { symbols, .. } => symbols[symbol].clone(),
+            BootstrapFrame::Book { .. } => panic!("Expected reply first"),
+        }
+    }
+
+    #[test]
+    fn test_gap_fill_when_buffered_else_snapshot() {
+        let mut buffer = ReplayBuffer::new(7, 3);
+        for id in 1..=5 {
+            buffer.record(&state("BTCUSDT", id));
+        }
+        buffer.record(&state("ETHUSDT", 1));
+
+        // Seqs 3..=5 are buffered for BTCUSDT
+        let request = BootstrapRequest {
+            epoch: 7,
+            last_seen: HashMap::from([("BTCUSDT".to_string(), 3), ("ETHUSDT".to_string(), 1)]),
+        };
+        let frames = buffer.bootstrap(&request);
+        assert_eq!(
+            recovery(&frames, "BTCUSDT"),
+            Recovery::GapFill {
+                from_seq: 4,
+                to_seq: 5
+            }
+        );
+        assert_eq!(recovery(&frames, "ETHUSDT"), Recovery::UpToDate);
+        assert_eq!(frames.len(), 3);
+
+        let too_old = BootstrapRequest {
+            epoch: 7,
+            last_seen: HashMap::from([("BTCUSDT".to_string(), 1)]),
+        };
+        let frames = buffer.bootstrap(&too_old);
+        assert_eq!(recovery(&frames, "BTCUSDT"), Recovery::Snapshot { seq: 5 });
+        assert_eq!(recovery(&frames, "ETHUSDT"), Recovery::Snapshot { seq: 1 });
+    }
+
+    #[test]
+    fn test_epoch_mismatch_forces_snapshot() {
+        let mut buffer = ReplayBuffer::new(7, 10);
+        buffer.record(&state("BTCUSDT", 1));
+        buffer.record(&state("BTCUSDT", 2));
+
+        let request = BootstrapRequest {
+            epoch: 6,
+            last_seen: HashMap::from([("BTCUSDT".to_string(), 1)]),
+        };
+        let frames = buffer.bootstrap(&request);
+        assert_eq!(recovery(&frames, "BTCUSDT"), Recovery::Snapshot { seq: 2 });
+        assert!(matches!(
+            frames[1],
+            BootstrapFrame::Book {
+                seq: 2,
+                replayed: true,
+                ..
+            }
+        ));
+    }
+}
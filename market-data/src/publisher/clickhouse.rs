@@ -0,0 +1,329 @@
+//! ClickHouse sink
+//!
+//! Batches trades and book metrics and inserts them into ClickHouse over
+//! its HTTP interface (`INSERT ... FORMAT JSONEachRow`), so a time-series
+//! store is populated directly from the handler. Rows are handed to a
+//! background writer through a bounded queue; while an insert is retried
+//! the queue fills and further rows are dropped instead of blocking the
+//! hot path, counted in `clickhouse_dropped_rows_total` with a warning at
+//! most every few seconds.
+//!
+//! Expected tables (names are fixed, the database is configurable):
+//!
+//! ```sql
+//! CREATE TABLE trades (
+//!     symbol LowCardinality(String), trade_id UInt64,
+//!     price Float64, quantity Float64, buyer_is_taker Bool,
+//!     trade_time DateTime64(3, 'UTC'), event_time DateTime64(3, 'UTC')
+//! ) ENGINE = MergeTree ORDER BY (symbol, trade_time);
+//!
+//! CREATE TABLE book_metrics (
+//!     symbol LowCardinality(String), timestamp DateTime64(3, 'UTC'),
+//!     last_update_id UInt64, mid_price Nullable(Float64),
+//!     spread_bps Nullable(Float64), imbalance Nullable(Float64),
+//!     weighted_imbalance Nullable(Float64), bid_depth Nullable(Float64),
+//!     ask_depth Nullable(Float64), bid_levels UInt32, ask_levels UInt32
+//! ) ENGINE = MergeTree ORDER BY (symbol, timestamp);
+//! ```
+
+use prometheus::{IntCounterVec, Opts};
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{debug, error, warn};
+
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::{OrderBookState, Side};
+
+/// Insert attempts per batch before it is dropped
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled on each further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Shortest time between warnings about a full queue
+const DROP_WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+const TRADES_TABLE: &str = "trades";
+const METRICS_TABLE: &str = "book_metrics";
+
+static DROPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "clickhouse_dropped_rows_total",
+            "Rows dropped because the ClickHouse queue was full",
+        ),
+        &["table"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+#[derive(Debug, Serialize)]
+struct TradeRow {
+    symbol: String,
+    trade_id: u64,
+    price: f64,
+    quantity: f64,
+    buyer_is_taker: bool,
+    trade_time: String,
+    event_time: String,
+}
+
+#[derive(Debug, Serialize)]
+struct MetricsRow {
+    symbol: String,
+    timestamp: String,
+    last_update_id: u64,
+    mid_price: Option<f64>,
+    spread_bps: Option<f64>,
+    imbalance: Option<f64>,
+    weighted_imbalance: Option<f64>,
+    bid_depth: Option<f64>,
+    ask_depth: Option<f64>,
+    bid_levels: usize,
+    ask_levels: usize,
+}
+
+/// A row bound for one of the tables, already encoded as a JSON line
+#[derive(Debug)]
+enum Row {
+    Trade(String),
+    Metrics(String),
+}
+
+impl Row {
+    fn table(&self) -> &'static str {
+        match self {
+            Row::Trade(_) => TRADES_TABLE,
+            Row::Metrics(_) => METRICS_TABLE,
+        }
+    }
+}
+
+/// ClickHouse publisher
+pub struct ClickHouseSink {
+    rows: mpsc::Sender<Row>,
+    /// When a full queue was last warned about
+    warned_at: Mutex<Option<Instant>>,
+}
+
+impl ClickHouseSink {
+    /// Start the background writer for the server at `url`
+    pub fn new(url: &str, config: &Config) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| MarketDataError::ConfigError(format!("ClickHouse client: {}", e)))?;
+        let (rows, rx) = mpsc::channel(config.clickhouse_queue_capacity.max(1));
+
+        let writer = Writer {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            database: config.clickhouse_database.clone(),
+            user: config.clickhouse_user.clone(),
+            password: config.clickhouse_password.clone(),
+            batch_size: config.clickhouse_batch_size.max(1),
+        };
+        let interval = Duration::from_millis(config.clickhouse_flush_interval_ms.max(1));
+        tokio::spawn(writer.run(rx, interval));
+
+        Ok(Self {
+            rows,
+            warned_at: Mutex::new(None),
+        })
+    }
+
+    /// Queue a state's metrics for `book_metrics`
+    pub fn publish_state(&self, state: &OrderBookState) -> Result<()> {
+        let metrics = &state.metrics;
+        let row = MetricsRow {
+            symbol: state.symbol.clone(),
+            timestamp: datetime(state.timestamp),
+            last_update_id: state.last_update_id,
+            mid_price: metrics.mid_price.and_then(|d| d.to_f64()),
+            spread_bps: metrics.spread_bps.and_then(|d| d.to_f64()),
+            imbalance: metrics.imbalance.and_then(|d| d.to_f64()),
+            weighted_imbalance: metrics.weighted_imbalance.and_then(|d| d.to_f64()),
+            bid_depth: metrics.bid_depth.and_then(|d| d.to_f64()),
+            ask_depth: metrics.ask_depth.and_then(|d| d.to_f64()),
+            bid_levels: metrics.bid_levels,
+            ask_levels: metrics.ask_levels,
+        };
+        self.enqueue(Row::Metrics(serde_json::to_string(&row)?))
+    }
+
+    /// Queue a trade for `trades`
+    pub fn publish_trade(&self, trade: &Trade) -> Result<()> {
+        let row = TradeRow {
+            symbol: trade.symbol.clone(),
+            trade_id: trade.trade_id,
+            price: trade.price.to_f64().unwrap_or_default(),
+            quantity: trade.quantity.to_f64().unwrap_or_default(),
+            buyer_is_taker: trade.taker_side == Side::Bid,
+            trade_time: datetime(trade.trade_time),
+            event_time: datetime(trade.event_time),
+        };
+        self.enqueue(Row::Trade(serde_json::to_string(&row)?))
+    }
+
+    /// Queue a row; one that finds the queue full is dropped and counted
+    fn enqueue(&self, row: Row) -> Result<()> {
+        match self.rows.try_send(row) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(row)) => {
+                DROPPED.with_label_values(&[row.table()]).inc();
+                let mut warned_at = self.warned_at.lock().unwrap();
+                if warned_at.is_none_or(|at| at.elapsed() >= DROP_WARN_INTERVAL) {
+                    *warned_at = Some(Instant::now());
+                    warn!(
+                        table = row.table(),
+                        "ClickHouse queue full, dropping rows (see clickhouse_dropped_rows_total)"
+                    );
+                }
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(MarketDataError::IpcError(
+                "ClickHouse writer stopped".into(),
+            )),
+        }
+    }
+}
+
+/// Millisecond timestamp in a format `DateTime64(3)` parses
+fn datetime(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M:%S%.3f")
+        .to_string()
+}
+
+/// Background inserter
+struct Writer {
+    client: reqwest::Client,
+    url: String,
+    database: String,
+    user: Option<String>,
+    password: Option<String>,
+    batch_size: usize,
+}
+
+impl Writer {
+    async fn run(self, mut rx: mpsc::Receiver<Row>, interval: Duration) {
+        let mut trades = Vec::new();
+        let mut metrics = Vec::new();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                row = rx.recv() => match row {
+                    Some(Row::Trade(line)) => trades.push(line),
+                    Some(Row::Metrics(line)) => metrics.push(line),
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    self.flush(TRADES_TABLE, &mut trades).await;
+                    self.flush(METRICS_TABLE, &mut metrics).await;
+                    continue;
+                }
+            }
+            if trades.len() >= self.batch_size {
+                self.flush(TRADES_TABLE, &mut trades).await;
+            }
+            if metrics.len() >= self.batch_size {
+                self.flush(METRICS_TABLE, &mut metrics).await;
+            }
+        }
+
+        self.flush(TRADES_TABLE, &mut trades).await;
+        self.flush(METRICS_TABLE, &mut metrics).await;
+    }
+
+    /// Insert buffered rows, retrying with backoff; drops them after the
+    /// last attempt fails
+    async fn flush(&self, table: &str, rows: &mut Vec<String>) {
+        if rows.is_empty() {
+            return;
+        }
+        let body = rows.join("\n");
+        let count = rows.len();
+        rows.clear();
+
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.insert(table, body.clone()).await {
+                Ok(()) => {
+                    debug!(table, rows = count, "Inserted into ClickHouse");
+                    return;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    warn!(error = %e, table, attempt, "ClickHouse insert failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    error!(error = %e, table, rows = count, "ClickHouse insert failed, dropping batch");
+                }
+            }
+        }
+    }
+
+    async fn insert(&self, table: &str, body: String) -> Result<()> {
+        let query = format!(
+            "INSERT INTO `{}`.`{}` FORMAT JSONEachRow",
+            self.database, table
+        );
+        let mut request = self
+            .client
+            .post(&self.url)
+            .query(&[("query", query)])
+            .body(body);
+        if let Some(user) = &self.user {
+            request = request.basic_auth(user, self.password.as_ref());
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(MarketDataError::IpcError(format!(
+                "ClickHouse returned {}: {}",
+                status,
+                text.trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datetime_format() {
+        assert_eq!(datetime(1_700_000_000_123), "2023-11-14 22:13:20.123");
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_rows_without_failing() {
+        let (rows, _rx) = mpsc::channel(1);
+        let sink = ClickHouseSink {
+            rows,
+            warned_at: Mutex::new(None),
+        };
+        let dropped = DROPPED.with_label_values(&[TRADES_TABLE]).get();
+        for _ in 0..3 {
+            sink.enqueue(Row::Trade("{}".to_string())).unwrap();
+        }
+        assert_eq!(
+            DROPPED.with_label_values(&[TRADES_TABLE]).get(),
+            dropped + 2
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! IPC payload compression
+//!
+//! Deep books serialize to tens of kilobytes per state, which adds up on
+//! TCP-bridged or remote consumers. With `IPC_COMPRESSION` set, payloads
+//! are compressed after serialization and the codec is recorded in the
+//! envelope's flag byte, so consumers decompress per frame without sharing
+//! configuration. Snappy and LZ4 both trade ratio for speed; see
+//! `benches/compression_benchmark.rs` for serialize+compress latency.
+
+use crate::config::Compression;
+use crate::error::{MarketDataError, Result};
+
+impl TryFrom<u8> for Compression {
+    type Error = MarketDataError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Snappy),
+            2 => Ok(Compression::Lz4),
+            other => Err(MarketDataError::ParseError(format!(
+                "Unknown compression flag {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Compress a payload with `codec`
+pub fn compress(codec: Compression, payload: &[u8]) -> Result<Vec<u8>> {
+    match codec {
+        Compression::None => Ok(payload.to_vec()),
+        Compression::Snappy => snap::raw::Encoder::new()
+            .compress_vec(payload)
+            .map_err(|e| MarketDataError::SerializationError(format!("Snappy: {}", e))),
+        Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(payload)),
+    }
+}
+
+/// Reverse `compress`
+pub fn decompress(codec: Compression, payload: &[u8]) -> Result<Vec<u8>> {
+    match codec {
+        Compression::None => Ok(payload.to_vec()),
+        Compression::Snappy => snap::raw::Decoder::new()
+            .decompress_vec(payload)
+            .map_err(|e| MarketDataError::ParseError(format!("Snappy: {}", e))),
+        Compression::Lz4 => lz4_flex::decompress_size_prepended(payload)
+            .map_err(|e| MarketDataError::ParseError(format!("LZ4: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let payload = b"50000.10:1.5,50000.20:1.5,50000.30:1.5,".repeat(64);
+        for codec in [Compression::None, Compression::Snappy, Compression::Lz4] {
+            let compressed = compress(codec, &payload).unwrap();
+            if codec != Compression::None {
+                assert!(compressed.len() < payload.len());
+            }
+            assert_eq!(decompress(codec, &compressed).unwrap(), payload);
+            assert_eq!(Compression::try_from(codec as u8).unwrap(), codec);
+        }
+        assert!(decompress(Compression::Lz4, b"\xff\xff").is_err());
+    }
+}
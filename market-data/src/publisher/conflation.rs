@@ -0,0 +1,229 @@
+//! Per-symbol conflation of order book states
+//!
+//! Coalesces bursts of updates so that at most one state per symbol is
+//! emitted per throttle interval, while always delivering the latest state.
+//! Symbols can override the default interval; zero disables throttling for
+//! that symbol.
+
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::orderbook::OrderBookState;
+
+/// Best bid/ask prices used to detect top-of-book changes
+type Bbo = (Option<Decimal>, Option<Decimal>);
+
+#[derive(Debug, Default)]
+struct SymbolSlot {
+    last_sent: Option<Instant>,
+    last_bbo: Bbo,
+    pending: Option<OrderBookState>,
+    /// States superseded since the last emission
+    coalesced: u32,
+}
+
+impl SymbolSlot {
+    fn is_due(&self, now: Instant, interval: Duration) -> bool {
+        self.last_sent
+            .is_none_or(|sent| now.duration_since(sent) >= interval)
+    }
+
+    /// Record the coalesced count in the state's provenance and reset it
+    fn stamp(&mut self, mut state: OrderBookState) -> OrderBookState {
+        if let Some(provenance) = state.provenance.as_mut() {
+            provenance.conflated = self.coalesced;
+        }
+        self.coalesced = 0;
+        state
+    }
+}
+
+/// Coalesces order book states per symbol
+#[derive(Debug)]
+pub struct Conflator {
+    /// Minimum time between two emissions for the same symbol
+    interval: Duration,
+    /// Per-symbol overrides of `interval`
+    symbol_intervals: HashMap<String, Duration>,
+    /// Emit immediately when best bid/ask price changes
+    on_bbo_change: bool,
+    slots: HashMap<String, SymbolSlot>,
+}
+
+impl Conflator {
+    /// Create a new conflator
+    pub fn new(interval: Duration, on_bbo_change: bool) -> Self {
+        Self {
+            interval,
+            on_bbo_change,
+            symbol_intervals: HashMap::new(),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Override the throttle interval of individual symbols
+    pub fn with_symbol_intervals(mut self, intervals: HashMap<String, Duration>) -> Self {
+        self.symbol_intervals = intervals;
+        self
+    }
+
+    /// Shortest non-zero throttle interval, i.e. how often pending states
+    /// need draining
+    pub fn interval(&self) -> Duration {
+        std::iter::once(self.interval)
+            .chain(self.symbol_intervals.values().copied())
+            .filter(|interval| !interval.is_zero())
+            .min()
+            .unwrap_or(self.interval)
+    }
+
+    fn interval_for(&self, symbol: &str) -> Duration {
+        self.symbol_intervals
+            .get(symbol)
+            .copied()
+            .unwrap_or(self.interval)
+    }
+
+    /// Offer a new state; returns it if it should be sent right away,
+    /// otherwise keeps it as the pending state for its symbol
+    pub fn offer(&mut self, state: OrderBookState, now: Instant) -> Option<OrderBookState> {
+        let bbo = bbo(&state);
+        let interval = self.interval_for(&state.symbol);
+        let slot = self.slots.entry(state.symbol.clone()).or_default();
+
+        let due = slot.is_due(now, interval);
+        let bbo_changed = self.on_bbo_change && slot.last_bbo != bbo;
+
+        if slot.pending.is_some() {
+            slot.coalesced += 1;
+        }
+
+        if due || bbo_changed {
+            slot.last_sent = Some(now);
+            slot.last_bbo = bbo;
+            slot.pending = None;
+            Some(slot.stamp(state))
+        } else {
+            slot.pending = Some(state);
+            None
+        }
+    }
+
+    /// Take all pending states whose throttle interval has elapsed
+    pub fn drain_due(&mut self, now: Instant) -> Vec<OrderBookState> {
+        let mut due = Vec::new();
+
+        for (symbol, slot) in self.slots.iter_mut() {
+            let interval = self
+                .symbol_intervals
+                .get(symbol)
+                .copied()
+                .unwrap_or(self.interval);
+            if slot.is_due(now, interval) {
+                if let Some(state) = slot.pending.take() {
+                    slot.last_sent = Some(now);
+                    slot.last_bbo = bbo(&state);
+                    due.push(slot.stamp(state));
+                }
+            }
+        }
+
+        due
+    }
+
+    /// Take every pending state regardless of its throttle interval
+    pub fn drain_all(&mut self) -> Vec<OrderBookState> {
+        self.slots
+            .values_mut()
+            .filter_map(|slot| {
+                let state = slot.pending.take()?;
+                slot.last_bbo = bbo(&state);
+                Some(slot.stamp(state))
+            })
+            .collect()
+    }
+}
+
+fn bbo(state: &OrderBookState) -> Bbo {
+    (
+        state.bids.first().map(|l| l.price),
+        state.asks.first().map(|l| l.price),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{Level, OrderBookMetrics, Provenance};
+    use rust_decimal_macros::dec;
+
+    fn state(update_id: u64, bid: Decimal) -> OrderBookState {
+        OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 0,
+            last_update_id: update_id,
+            bids: vec![Level {
+                price: bid,
+                quantity: dec!(1),
+            }],
+            asks: vec![Level {
+                price: dec!(50001),
+                quantity: dec!(1),
+            }],
+            metrics: OrderBookMetrics::default(),
+            provenance: Some(Provenance::default()),
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_coalesces_within_interval_and_keeps_latest() {
+        let mut conflator = Conflator::new(Duration::from_millis(10), false);
+        let start = Instant::now();
+
+        assert!(conflator.offer(state(1, dec!(50000)), start).is_some());
+        assert!(conflator.offer(state(2, dec!(50000)), start).is_none());
+        assert!(conflator.offer(state(3, dec!(50000)), start).is_none());
+        assert!(conflator.drain_due(start).is_empty());
+
+        let later = start + Duration::from_millis(10);
+        let drained = conflator.drain_due(later);
+        assert_eq!(drained.len(), 1);
+        assert_eq!(drained[0].last_update_id, 3);
+        assert_eq!(drained[0].provenance.unwrap().conflated, 1);
+        assert!(conflator.drain_due(later).is_empty());
+
+        // Shutdown flushes pending states before their interval is up
+        assert!(conflator.offer(state(4, dec!(50000)), later).is_none());
+        assert_eq!(conflator.drain_all()[0].last_update_id, 4);
+        assert!(conflator.drain_all().is_empty());
+    }
+
+    #[test]
+    fn test_bbo_change_bypasses_throttle() {
+        let mut conflator = Conflator::new(Duration::from_secs(1), true);
+        let now = Instant::now();
+
+        assert!(conflator.offer(state(1, dec!(50000)), now).is_some());
+        assert!(conflator.offer(state(2, dec!(50000)), now).is_none());
+        let sent = conflator.offer(state(3, dec!(49999)), now).unwrap();
+        assert_eq!(sent.last_update_id, 3);
+        assert!(conflator.drain_due(now + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_symbol_interval_override() {
+        let intervals = HashMap::from([("BTCUSDT".to_string(), Duration::ZERO)]);
+        let mut conflator =
+            Conflator::new(Duration::from_secs(1), false).with_symbol_intervals(intervals);
+        let now = Instant::now();
+
+        // Unthrottled symbol: every state goes out
+        assert!(conflator.offer(state(1, dec!(50000)), now).is_some());
+        assert!(conflator.offer(state(2, dec!(50000)), now).is_some());
+        assert_eq!(conflator.interval(), Duration::from_secs(1));
+    }
+}
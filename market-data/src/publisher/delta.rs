@@ -0,0 +1,253 @@
+//! Incremental (delta) encoding of order book states
+//!
+//! The first message for a symbol is a full snapshot; subsequent messages
+//! only carry levels that changed since the previous message, with a
+//! per-symbol sequence number. A full snapshot is re-sent periodically so
+//! consumers that missed a delta can recover without reconnecting.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::orderbook::{Level, OrderBookMetrics, OrderBookState, Provenance};
+use crate::trade_metrics::TradeMetrics;
+
+/// Message published in delta mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BookMessage {
+    /// Full book state; resets the consumer's copy
+    Snapshot { seq: u64, state: OrderBookState },
+    /// Changed levels since the message with `seq - 1`
+    Delta(BookDelta),
+}
+
+/// Changed levels for one symbol
+///
+/// A level with zero quantity means the price left the published book.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDelta {
+    pub symbol: String,
+    pub seq: u64,
+    pub timestamp: u64,
+    pub last_update_id: u64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+    pub metrics: OrderBookMetrics,
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    #[serde(default)]
+    pub trade_metrics: Option<TradeMetrics>,
+    /// Checksum of the book once this delta is applied, to verify a copy
+    /// kept from deltas against (`orderbook::checksum`)
+    #[serde(default)]
+    pub checksum: Option<u32>,
+}
+
+#[derive(Debug)]
+struct SymbolSlot {
+    seq: u64,
+    bids: Vec<Level>,
+    asks: Vec<Level>,
+    last_snapshot: Instant,
+}
+
+/// Tracks the last published state per symbol and encodes deltas
+#[derive(Debug)]
+pub struct DeltaEncoder {
+    refresh_interval: Duration,
+    slots: HashMap<String, SymbolSlot>,
+}
+
+impl DeltaEncoder {
+    /// Create a new encoder sending a full snapshot every `refresh_interval`
+    pub fn new(refresh_interval: Duration) -> Self {
+        Self {
+            refresh_interval,
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Forget all published state so the next message per symbol is a snapshot
+    pub fn reset(&mut self) {
+        self.slots.clear();
+    }
+
+    /// Encode a state as a snapshot or a delta against the previous one
+    pub fn encode(&mut self, state: &OrderBookState, now: Instant) -> BookMessage {
+        let refresh_interval = self.refresh_interval;
+
+        match self.slots.get_mut(&state.symbol) {
+            Some(slot) if now.duration_since(slot.last_snapshot) < refresh_interval => {
+                slot.seq += 1;
+                let delta = BookDelta {
+                    symbol: state.symbol.clone(),
+                    seq: slot.seq,
+                    timestamp: state.timestamp,
+                    last_update_id: state.last_update_id,
+                    bids: diff_levels(&slot.bids, &state.bids),
+                    asks: diff_levels(&slot.asks, &state.asks),
+                    metrics: state.metrics.clone(),
+                    provenance: state.provenance,
+                    trade_metrics: state.trade_metrics.clone(),
+                    checksum: state.checksum,
+                };
+                slot.bids.clone_from(&state.bids);
+                slot.asks.clone_from(&state.asks);
+                BookMessage::Delta(delta)
+            }
+            Some(slot) => {
+                slot.seq += 1;
+                slot.bids.clone_from(&state.bids);
+                slot.asks.clone_from(&state.asks);
+                slot.last_snapshot = now;
+                BookMessage::Snapshot {
+                    seq: slot.seq,
+                    state: state.clone(),
+                }
+            }
+            None => {
+                self.slots.insert(
+                    state.symbol.clone(),
+                    SymbolSlot {
+                        seq: 1,
+                        bids: state.bids.clone(),
+                        asks: state.asks.clone(),
+                        last_snapshot: now,
+                    },
+                );
+                BookMessage::Snapshot {
+                    seq: 1,
+                    state: state.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// Levels added or changed in `new`, plus zero-quantity removals from `old`
+///
+/// Published books are shallow, so a linear scan beats hashing here.
+fn diff_levels(old: &[Level], new: &[Level]) -> Vec<Level> {
+    let mut changed: Vec<Level> = new
+        .iter()
+        .filter(|l| {
+            !old.iter()
+                .any(|o| o.price == l.price && o.quantity == l.quantity)
+        })
+        .cloned()
+        .collect();
+
+    changed.extend(
+        old.iter()
+            .filter(|o| !new.iter().any(|l| l.price == o.price))
+            .map(|o| Level {
+                price: o.price,
+                quantity: Decimal::ZERO,
+            }),
+    );
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::checksum;
+    use rust_decimal_macros::dec;
+
+    fn state(bids: &[(Decimal, Decimal)]) -> OrderBookState {
+        let bids: Vec<Level> = bids
+            .iter()
+            .map(|&(price, quantity)| Level { price, quantity })
+            .collect();
+        OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 0,
+            last_update_id: 1,
+            checksum: Some(checksum(&bids, &[])),
+            bids,
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_then_delta() {
+        let mut encoder = DeltaEncoder::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        let first = encoder.encode(&state(&[(dec!(100), dec!(1)), (dec!(99), dec!(2))]), now);
+        assert!(matches!(first, BookMessage::Snapshot { seq: 1, .. }));
+
+        let second = encoder.encode(&state(&[(dec!(100), dec!(3)), (dec!(98), dec!(1))]), now);
+        let BookMessage::Delta(delta) = second else {
+            panic!("Expected delta");
+        };
+        assert_eq!(delta.seq, 2);
+        assert_eq!(delta.bids.len(), 3);
+        assert!(delta
+            .bids
+            .iter()
+            .any(|l| l.price == dec!(99) && l.quantity.is_zero()));
+        assert!(delta
+            .bids
+            .iter()
+            .any(|l| l.price == dec!(100) && l.quantity == dec!(3)));
+    }
+
+    #[test]
+    fn test_copy_kept_from_deltas_matches_checksum() {
+        let mut encoder = DeltaEncoder::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let BookMessage::Snapshot { state: first, .. } =
+            encoder.encode(&state(&[(dec!(100), dec!(1)), (dec!(99), dec!(2))]), now)
+        else {
+            panic!("Expected snapshot");
+        };
+        let mut copy = first.bids;
+
+        let next = state(&[(dec!(100.5), dec!(1)), (dec!(100), dec!(3))]);
+        let BookMessage::Delta(delta) = encoder.encode(&next, now) else {
+            panic!("Expected delta");
+        };
+        for level in delta.bids {
+            copy.retain(|l| l.price != level.price);
+            if !level.quantity.is_zero() {
+                copy.push(level);
+            }
+        }
+        copy.sort_by_key(|l| std::cmp::Reverse(l.price));
+        assert_eq!(delta.checksum, Some(checksum(&copy, &[])));
+        assert_ne!(delta.checksum, first.checksum);
+    }
+
+    #[test]
+    fn test_periodic_full_refresh_and_reset() {
+        let mut encoder = DeltaEncoder::new(Duration::from_secs(1));
+        let now = Instant::now();
+        let book = state(&[(dec!(100), dec!(1))]);
+
+        encoder.encode(&book, now);
+        let refreshed = encoder.encode(&book, now + Duration::from_secs(1));
+        assert!(matches!(refreshed, BookMessage::Snapshot { seq: 2, .. }));
+
+        encoder.reset();
+        assert!(matches!(
+            encoder.encode(&book, now),
+            BookMessage::Snapshot { seq: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let mut encoder = DeltaEncoder::new(Duration::from_secs(60));
+        let msg = encoder.encode(&state(&[(dec!(100), dec!(1))]), Instant::now());
+        let bytes = rmp_serde::to_vec(&msg).unwrap();
+        let decoded: BookMessage = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(matches!(decoded, BookMessage::Snapshot { seq: 1, .. }));
+    }
+}
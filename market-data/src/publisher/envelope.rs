@@ -0,0 +1,222 @@
+//! IPC message envelope
+//!
+//! Every frame the publisher writes to the socket carries a fixed binary
+//! header in front of the payload, whatever the wire format:
+//!
+//! ```text
+//! len: u32 | seq: u64 | sent_at_us: u64 | type: u8 | compression: u8 | symbol_len: u8 | symbol | payload
+//! ```
+//!
+//! Integers are big-endian, like the length prefix, which covers
+//! everything after itself. `seq` increases by one per frame across the
+//! publisher's lifetime, so a consumer detects drops as a gap between
+//! consecutive frames (the first frame after connecting may start
+//! anywhere). `sent_at_us` is the wall-clock time the frame was encoded
+//! for the socket, in microseconds since the epoch. `symbol` is empty for
+//! messages not about a single symbol (bootstrap replies). `compression`
+//! names the codec the payload was compressed with (0 none, 1 Snappy,
+//! 2 LZ4; see `compression::decompress`).
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Compression;
+use crate::dead_man::DegradedReason;
+use crate::error::{MarketDataError, Result};
+
+/// Bytes of the header before the symbol
+pub const FIXED_HEADER_LEN: usize = 19;
+
+/// Kind of payload in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    /// `OrderBookState`, or the format's own book message
+    Book = 1,
+    /// `BookMessage` (delta publish mode)
+    Delta = 2,
+    /// `BootstrapFrame` (bootstrap mode)
+    Bootstrap = 3,
+    /// `VolumeProfile`
+    VolumeProfile = 4,
+    /// `MarketAnomaly`
+    Anomaly = 5,
+    /// `BboChanged`
+    Bbo = 6,
+    /// `ControlMessage`
+    Control = 7,
+    /// `OrderEvent` (user data stream)
+    Order = 8,
+    /// `AccountPosition` (user data stream)
+    Account = 9,
+    /// `StatusEvent`
+    Status = 10,
+    /// `BookHeartbeat`
+    Heartbeat = 11,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = MarketDataError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(MessageType::Book),
+            2 => Ok(MessageType::Delta),
+            3 => Ok(MessageType::Bootstrap),
+            4 => Ok(MessageType::VolumeProfile),
+            5 => Ok(MessageType::Anomaly),
+            6 => Ok(MessageType::Bbo),
+            7 => Ok(MessageType::Control),
+            8 => Ok(MessageType::Order),
+            9 => Ok(MessageType::Account),
+            10 => Ok(MessageType::Status),
+            11 => Ok(MessageType::Heartbeat),
+            other => Err(MarketDataError::ParseError(format!(
+                "Unknown message type {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Publisher lifecycle notice to consumers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlMessage {
+    /// The publisher is shutting down; no further frames follow on this
+    /// connection
+    Shutdown {
+        /// Milliseconds since the epoch
+        timestamp: u64,
+    },
+}
+
+/// Change of the feed's state, telling consumers when what they hold may
+/// be stale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusEvent {
+    /// The exchange connection is up; books follow once resynced
+    Connected {
+        connection_id: u64,
+        /// Milliseconds since the epoch
+        timestamp: u64,
+    },
+    /// The exchange connection dropped; every book is stale until it is
+    /// resynced on the next connection
+    Disconnected {
+        connection_id: u64,
+        reason: String,
+        timestamp: u64,
+    },
+    /// A book was (re)initialized from a snapshot
+    Resynced {
+        symbol: String,
+        last_update_id: u64,
+        timestamp: u64,
+    },
+    /// A book missed diffs and was discarded; it resyncs from a snapshot
+    Gap {
+        symbol: String,
+        expected: u64,
+        received: u64,
+        timestamp: u64,
+    },
+    /// The feed can no longer vouch for its books and strategies should
+    /// stop trading on them; repeated while it lasts
+    FeedDegraded {
+        reasons: Vec<DegradedReason>,
+        timestamp: u64,
+    },
+    /// Every condition behind `FeedDegraded` cleared
+    FeedRecovered { timestamp: u64 },
+}
+
+impl StatusEvent {
+    /// Symbol of a book's event; empty for the connection's
+    pub fn symbol(&self) -> &str {
+        match self {
+            StatusEvent::Resynced { symbol, .. } | StatusEvent::Gap { symbol, .. } => symbol,
+            StatusEvent::Connected { .. }
+            | StatusEvent::Disconnected { .. }
+            | StatusEvent::FeedDegraded { .. }
+            | StatusEvent::FeedRecovered { .. } => "",
+        }
+    }
+}
+
+/// Envelope header of one frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub seq: u64,
+    pub sent_at_us: u64,
+    pub message_type: MessageType,
+    pub compression: Compression,
+    pub symbol: String,
+}
+
+impl Envelope {
+    /// Build the complete length-prefixed frame around `payload`
+    pub fn frame(&self, payload: &[u8]) -> Vec<u8> {
+        // Symbols are short exchange tickers; cap rather than fail
+        let symbol = &self.symbol.as_bytes()[..self.symbol.len().min(u8::MAX as usize)];
+        let len = FIXED_HEADER_LEN + symbol.len() + payload.len();
+
+        let mut frame = Vec::with_capacity(4 + len);
+        frame.extend_from_slice(&(len as u32).to_be_bytes());
+        frame.extend_from_slice(&self.seq.to_be_bytes());
+        frame.extend_from_slice(&self.sent_at_us.to_be_bytes());
+        frame.push(self.message_type as u8);
+        frame.push(self.compression as u8);
+        frame.push(symbol.len() as u8);
+        frame.extend_from_slice(symbol);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// Split a frame body (after the length prefix) into envelope and payload
+    pub fn decode(body: &[u8]) -> Result<(Self, &[u8])> {
+        let truncated = || MarketDataError::ParseError("Truncated envelope".to_string());
+        if body.len() < FIXED_HEADER_LEN {
+            return Err(truncated());
+        }
+        let symbol_len = body[18] as usize;
+        let symbol = body
+            .get(FIXED_HEADER_LEN..FIXED_HEADER_LEN + symbol_len)
+            .ok_or_else(truncated)?;
+
+        let envelope = Self {
+            seq: u64::from_be_bytes(body[0..8].try_into().unwrap()),
+            sent_at_us: u64::from_be_bytes(body[8..16].try_into().unwrap()),
+            message_type: MessageType::try_from(body[16])?,
+            compression: Compression::try_from(body[17])?,
+            symbol: String::from_utf8_lossy(symbol).into_owned(),
+        };
+        Ok((envelope, &body[FIXED_HEADER_LEN + symbol_len..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let envelope = Envelope {
+            seq: 42,
+            sent_at_us: 1_700_000_000_000_000,
+            message_type: MessageType::Delta,
+            compression: Compression::Lz4,
+            symbol: "BTCUSDT".to_string(),
+        };
+        let frame = envelope.frame(b"payload");
+
+        let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, frame.len() - 4);
+        let (decoded, payload) = Envelope::decode(&frame[4..]).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(payload, b"payload");
+
+        assert!(Envelope::decode(&frame[4..20]).is_err());
+        assert_eq!(MessageType::try_from(10).unwrap(), MessageType::Status);
+        assert_eq!(MessageType::try_from(11).unwrap(), MessageType::Heartbeat);
+        assert!(MessageType::try_from(12).is_err());
+    }
+}
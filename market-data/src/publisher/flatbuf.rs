@@ -0,0 +1,508 @@
+//! FlatBuffers encoding of published states
+//!
+//! Encodes `OrderBookState` as the `OrderBook` table from
+//! `schema/order_book.fbs` with the `flatbuffers` runtime's builder, so
+//! low-latency consumers read book levels in place without a
+//! deserialization pass. Consumers generate readers with `flatc`;
+//! `OrderBookView` is the zero-copy reader on the Rust side.
+//!
+//! The struct and table bindings below are what `flatc --rust` emits for
+//! the schema (field slots, struct layout and alignment), written out here
+//! so the build doesn't need `flatc`; keep them in step with the schema.
+//! Buffers are checked by the runtime's verifier before they are read.
+
+use flatbuffers::{
+    FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Push, PushAlignment,
+    SimpleToVerifyInSlice, Table, VOffsetT, Vector, Verifiable, Verifier,
+};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+
+use crate::error::{MarketDataError, Result};
+use crate::orderbook::{self, OrderBookState};
+
+/// File identifier of the `OrderBook` root table
+pub const FILE_IDENTIFIER: &str = "ORPB";
+
+// Field slots (vtable offsets), in schema order
+const VT_SYMBOL: VOffsetT = 4;
+const VT_TIMESTAMP: VOffsetT = 6;
+const VT_LAST_UPDATE_ID: VOffsetT = 8;
+const VT_BIDS: VOffsetT = 10;
+const VT_ASKS: VOffsetT = 12;
+const VT_MID_PRICE: VOffsetT = 14;
+const VT_SPREAD_BPS: VOffsetT = 16;
+const VT_IMBALANCE: VOffsetT = 18;
+const VT_WEIGHTED_IMBALANCE: VOffsetT = 20;
+const VT_BID_DEPTH: VOffsetT = 22;
+const VT_ASK_DEPTH: VOffsetT = 24;
+const VT_MICROPRICE: VOffsetT = 26;
+const VT_REALIZED_VOL: VOffsetT = 28;
+const VT_BID_SLOPE: VOffsetT = 30;
+const VT_ASK_SLOPE: VOffsetT = 32;
+const VT_DEPTH_BANDS: VOffsetT = 34;
+
+/// Schema structs: little-endian doubles, 8-byte aligned
+macro_rules! fixed_struct {
+    ($(#[$doc:meta])* $name:ident, $size:literal { $($field:ident: $at:literal),+ }) => {
+        $(#[$doc])*
+        #[repr(transparent)]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $name([u8; $size]);
+
+        impl $name {
+            pub fn new($($field: f64),+) -> Self {
+                let mut bytes = [0; $size];
+                $(bytes[$at..$at + 8].copy_from_slice(&$field.to_le_bytes());)+
+                Self(bytes)
+            }
+
+            $(pub fn $field(&self) -> f64 {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&self.0[$at..$at + 8]);
+                f64::from_le_bytes(bytes)
+            })+
+        }
+
+        impl<'a> Follow<'a> for &'a $name {
+            type Inner = &'a $name;
+
+            unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+                flatbuffers::follow_cast_ref::<$name>(buf, loc)
+            }
+        }
+
+        impl<'a> Follow<'a> for $name {
+            type Inner = &'a $name;
+
+            unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+                <&'a $name>::follow(buf, loc)
+            }
+        }
+
+        impl Push for $name {
+            type Output = $name;
+
+            unsafe fn push(&self, dst: &mut [u8], _written_len: usize) {
+                dst.copy_from_slice(&self.0);
+            }
+
+            fn alignment() -> PushAlignment {
+                PushAlignment::new(8)
+            }
+        }
+
+        impl Verifiable for $name {
+            fn run_verifier(
+                v: &mut Verifier,
+                pos: usize,
+            ) -> std::result::Result<(), InvalidFlatbuffer> {
+                v.in_buffer::<Self>(pos)
+            }
+        }
+
+        impl SimpleToVerifyInSlice for $name {}
+    };
+}
+
+fixed_struct!(
+    /// `Level` struct
+    Level, 16 { price: 0, quantity: 8 }
+);
+
+fixed_struct!(
+    /// `DepthBand` struct
+    DepthBand, 24 { bps: 0, bid_depth: 8, ask_depth: 16 }
+);
+
+thread_local! {
+    /// Builder reused across states, so encoding doesn't regrow its buffer
+    static BUILDER: RefCell<FlatBufferBuilder<'static>> =
+        RefCell::new(FlatBufferBuilder::with_capacity(4096));
+}
+
+/// Encode a state as a FlatBuffers `OrderBook`
+pub fn encode(state: &OrderBookState) -> Vec<u8> {
+    let metrics = &state.metrics;
+    let to_f64 = |d: Decimal| d.to_f64();
+    let level = |l: &orderbook::Level| {
+        Level::new(
+            l.price.to_f64().unwrap_or(0.0),
+            l.quantity.to_f64().unwrap_or(0.0),
+        )
+    };
+    let doubles = [
+        (VT_MID_PRICE, metrics.mid_price.and_then(to_f64)),
+        (VT_SPREAD_BPS, metrics.spread_bps.and_then(to_f64)),
+        (VT_IMBALANCE, metrics.imbalance.and_then(to_f64)),
+        (
+            VT_WEIGHTED_IMBALANCE,
+            metrics.weighted_imbalance.and_then(to_f64),
+        ),
+        (VT_BID_DEPTH, metrics.bid_depth.and_then(to_f64)),
+        (VT_ASK_DEPTH, metrics.ask_depth.and_then(to_f64)),
+        (VT_MICROPRICE, metrics.microprice.and_then(to_f64)),
+        (VT_REALIZED_VOL, metrics.realized_vol.and_then(to_f64)),
+        (VT_BID_SLOPE, metrics.bid_slope.and_then(to_f64)),
+        (VT_ASK_SLOPE, metrics.ask_slope.and_then(to_f64)),
+    ];
+
+    BUILDER.with(|builder| {
+        let mut fbb = builder.borrow_mut();
+        fbb.reset();
+
+        let symbol = fbb.create_string(&state.symbol);
+        let bids = fbb.create_vector_from_iter(state.bids.iter().map(level));
+        let asks = fbb.create_vector_from_iter(state.asks.iter().map(level));
+        let depth_bands = fbb.create_vector_from_iter(metrics.depth_bands.iter().map(|band| {
+            DepthBand::new(
+                band.bps.to_f64().unwrap_or(0.0),
+                band.bid_depth.to_f64().unwrap_or(0.0),
+                band.ask_depth.to_f64().unwrap_or(0.0),
+            )
+        }));
+
+        // Absent metrics are left out of the table
+        let table = fbb.start_table();
+        fbb.push_slot::<u64>(VT_TIMESTAMP, state.timestamp, 0);
+        fbb.push_slot::<u64>(VT_LAST_UPDATE_ID, state.last_update_id, 0);
+        for (slot, value) in doubles {
+            if let Some(value) = value {
+                fbb.push_slot_always::<f64>(slot, value);
+            }
+        }
+        fbb.push_slot_always(VT_SYMBOL, symbol);
+        fbb.push_slot_always(VT_BIDS, bids);
+        fbb.push_slot_always(VT_ASKS, asks);
+        fbb.push_slot_always(VT_DEPTH_BANDS, depth_bands);
+        let root = fbb.end_table(table);
+        fbb.finish(root, Some(FILE_IDENTIFIER));
+
+        fbb.finished_data().to_vec()
+    })
+}
+
+/// Zero-copy reader over an encoded `OrderBook`
+///
+/// The buffer is verified once in `new`; accessors then read in place.
+#[derive(Debug, Clone, Copy)]
+pub struct OrderBookView<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> Follow<'a> for OrderBookView<'a> {
+    type Inner = OrderBookView<'a>;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Self {
+            table: Table::new(buf, loc),
+        }
+    }
+}
+
+impl Verifiable for OrderBookView<'_> {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> std::result::Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<&str>>("symbol", VT_SYMBOL, false)?
+            .visit_field::<u64>("timestamp", VT_TIMESTAMP, false)?
+            .visit_field::<u64>("last_update_id", VT_LAST_UPDATE_ID, false)?
+            .visit_field::<ForwardsUOffset<Vector<'_, Level>>>("bids", VT_BIDS, false)?
+            .visit_field::<ForwardsUOffset<Vector<'_, Level>>>("asks", VT_ASKS, false)?
+            .visit_field::<f64>("mid_price", VT_MID_PRICE, false)?
+            .visit_field::<f64>("spread_bps", VT_SPREAD_BPS, false)?
+            .visit_field::<f64>("imbalance", VT_IMBALANCE, false)?
+            .visit_field::<f64>("weighted_imbalance", VT_WEIGHTED_IMBALANCE, false)?
+            .visit_field::<f64>("bid_depth", VT_BID_DEPTH, false)?
+            .visit_field::<f64>("ask_depth", VT_ASK_DEPTH, false)?
+            .visit_field::<f64>("microprice", VT_MICROPRICE, false)?
+            .visit_field::<f64>("realized_vol", VT_REALIZED_VOL, false)?
+            .visit_field::<f64>("bid_slope", VT_BID_SLOPE, false)?
+            .visit_field::<f64>("ask_slope", VT_ASK_SLOPE, false)?
+            .visit_field::<ForwardsUOffset<Vector<'_, DepthBand>>>(
+                "depth_bands",
+                VT_DEPTH_BANDS,
+                false,
+            )?
+            .finish();
+        Ok(())
+    }
+}
+
+impl<'a> OrderBookView<'a> {
+    /// Verify a buffer and open it for reading
+    pub fn new(buf: &'a [u8]) -> Result<Self> {
+        if buf.get(4..8) != Some(FILE_IDENTIFIER.as_bytes()) {
+            return Err(MarketDataError::ParseError(
+                "FlatBuffer: missing ORPB identifier".to_string(),
+            ));
+        }
+        flatbuffers::root::<OrderBookView>(buf)
+            .map_err(|e| MarketDataError::ParseError(format!("FlatBuffer: {}", e)))
+    }
+
+    pub fn symbol(&self) -> &'a str {
+        self.get::<ForwardsUOffset<&str>>(VT_SYMBOL).unwrap_or("")
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.get::<u64>(VT_TIMESTAMP).unwrap_or(0)
+    }
+
+    pub fn last_update_id(&self) -> u64 {
+        self.get::<u64>(VT_LAST_UPDATE_ID).unwrap_or(0)
+    }
+
+    /// Bid levels, best first
+    pub fn bids(&self) -> Levels<'a> {
+        Levels(self.vector(VT_BIDS))
+    }
+
+    /// Ask levels, best first
+    pub fn asks(&self) -> Levels<'a> {
+        Levels(self.vector(VT_ASKS))
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        self.get::<f64>(VT_MID_PRICE)
+    }
+
+    pub fn spread_bps(&self) -> Option<f64> {
+        self.get::<f64>(VT_SPREAD_BPS)
+    }
+
+    pub fn imbalance(&self) -> Option<f64> {
+        self.get::<f64>(VT_IMBALANCE)
+    }
+
+    pub fn weighted_imbalance(&self) -> Option<f64> {
+        self.get::<f64>(VT_WEIGHTED_IMBALANCE)
+    }
+
+    pub fn microprice(&self) -> Option<f64> {
+        self.get::<f64>(VT_MICROPRICE)
+    }
+
+    pub fn realized_vol(&self) -> Option<f64> {
+        self.get::<f64>(VT_REALIZED_VOL)
+    }
+
+    pub fn bid_slope(&self) -> Option<f64> {
+        self.get::<f64>(VT_BID_SLOPE)
+    }
+
+    pub fn ask_slope(&self) -> Option<f64> {
+        self.get::<f64>(VT_ASK_SLOPE)
+    }
+
+    /// Configured depth bands, nearest first
+    pub fn depth_bands(&self) -> DepthBands<'a> {
+        DepthBands(self.vector(VT_DEPTH_BANDS))
+    }
+
+    pub fn bid_depth(&self) -> Option<f64> {
+        self.get::<f64>(VT_BID_DEPTH)
+    }
+
+    pub fn ask_depth(&self) -> Option<f64> {
+        self.get::<f64>(VT_ASK_DEPTH)
+    }
+
+    /// A field, if present
+    fn get<T: Follow<'a> + 'a>(&self, slot: VOffsetT) -> Option<T::Inner> {
+        // SAFETY: `new` verified every field against its schema type
+        unsafe { self.table.get::<T>(slot, None) }
+    }
+
+    /// A vector of structs, empty if absent
+    fn vector<T: Follow<'a> + 'a>(&self, slot: VOffsetT) -> Vector<'a, T> {
+        self.get::<ForwardsUOffset<Vector<'a, T>>>(slot)
+            .unwrap_or_default()
+    }
+}
+
+/// Zero-copy view of a `[Level]` vector
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Levels<'a>(Vector<'a, Level>);
+
+impl<'a> Levels<'a> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `(price, quantity)` of level `i`
+    pub fn get(&self, i: usize) -> Option<(f64, f64)> {
+        (i < self.len()).then(|| {
+            let level = self.0.get(i);
+            (level.price(), level.quantity())
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64)> + 'a {
+        self.0.iter().map(|level| (level.price(), level.quantity()))
+    }
+}
+
+/// Zero-copy view of a `[DepthBand]` vector
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthBands<'a>(Vector<'a, DepthBand>);
+
+impl<'a> DepthBands<'a> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `(bps, bid_depth, ask_depth)` of band `i`
+    pub fn get(&self, i: usize) -> Option<(f64, f64, f64)> {
+        (i < self.len()).then(|| {
+            let band = self.0.get(i);
+            (band.bps(), band.bid_depth(), band.ask_depth())
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64, f64)> + 'a {
+        self.0
+            .iter()
+            .map(|band| (band.bps(), band.bid_depth(), band.ask_depth()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::{DepthBand, Level, OrderBookMetrics};
+    use rust_decimal_macros::dec;
+
+    fn as_f64(levels: &[Level]) -> Vec<(f64, f64)> {
+        levels
+            .iter()
+            .map(|l| (l.price.to_f64().unwrap(), l.quantity.to_f64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_matches_msgpack() {
+        let state = OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            last_update_id: 42,
+            bids: vec![
+                Level {
+                    price: dec!(50000.10),
+                    quantity: dec!(1.5),
+                },
+                Level {
+                    price: dec!(49999.90),
+                    quantity: dec!(0.75),
+                },
+            ],
+            asks: vec![Level {
+                price: dec!(50000.20),
+                quantity: dec!(0.25),
+            }],
+            metrics: OrderBookMetrics {
+                mid_price: Some(dec!(50000.15)),
+                microprice: Some(dec!(50000.18)),
+                spread_bps: Some(dec!(0.02)),
+                bid_depth: Some(dec!(2.25)),
+                ask_depth: Some(dec!(0.25)),
+                ask_slope: Some(dec!(2.5)),
+                depth_bands: vec![
+                    DepthBand {
+                        bps: dec!(5),
+                        bid_depth: dec!(1.5),
+                        ask_depth: dec!(0.25),
+                    },
+                    DepthBand {
+                        bps: dec!(25),
+                        bid_depth: dec!(2.25),
+                        ask_depth: dec!(0.25),
+                    },
+                ],
+                ..Default::default()
+            },
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        };
+
+        let msgpack: OrderBookState =
+            rmp_serde::from_slice(&rmp_serde::to_vec(&state).unwrap()).unwrap();
+        let buf = encode(&state);
+        let view = OrderBookView::new(&buf).unwrap();
+
+        assert_eq!(view.symbol(), msgpack.symbol);
+        assert_eq!(view.timestamp(), msgpack.timestamp);
+        assert_eq!(view.last_update_id(), msgpack.last_update_id);
+        assert_eq!(
+            view.bids().iter().collect::<Vec<_>>(),
+            as_f64(&msgpack.bids)
+        );
+        assert_eq!(
+            view.asks().iter().collect::<Vec<_>>(),
+            as_f64(&msgpack.asks)
+        );
+        assert_eq!(view.mid_price(), Some(50000.15));
+        assert_eq!(view.microprice(), Some(50000.18));
+        assert_eq!(view.imbalance(), None);
+        assert_eq!(view.bid_depth(), Some(2.25));
+        assert_eq!(view.bid_slope(), None);
+        assert_eq!(view.ask_slope(), Some(2.5));
+        assert_eq!(
+            view.depth_bands().iter().collect::<Vec<_>>(),
+            [(5.0, 1.5, 0.25), (25.0, 2.25, 0.25)]
+        );
+
+        // Truncated or foreign buffers are rejected up front
+        assert!(OrderBookView::new(&buf[..buf.len() - 8]).is_err());
+        assert!(OrderBookView::new(b"ORPB").is_err());
+    }
+
+    #[test]
+    fn test_slots_follow_schema() {
+        let schema = include_str!("../../schema/order_book.fbs");
+        let table = &schema[schema.find("table OrderBook {").unwrap()..];
+        let fields: Vec<&str> = table[..table.find('}').unwrap()]
+            .lines()
+            .skip(1)
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(|line| line.split(':').next().unwrap())
+            .collect();
+        let slots = [
+            ("symbol", VT_SYMBOL),
+            ("timestamp", VT_TIMESTAMP),
+            ("last_update_id", VT_LAST_UPDATE_ID),
+            ("bids", VT_BIDS),
+            ("asks", VT_ASKS),
+            ("mid_price", VT_MID_PRICE),
+            ("spread_bps", VT_SPREAD_BPS),
+            ("imbalance", VT_IMBALANCE),
+            ("weighted_imbalance", VT_WEIGHTED_IMBALANCE),
+            ("bid_depth", VT_BID_DEPTH),
+            ("ask_depth", VT_ASK_DEPTH),
+            ("microprice", VT_MICROPRICE),
+            ("realized_vol", VT_REALIZED_VOL),
+            ("bid_slope", VT_BID_SLOPE),
+            ("ask_slope", VT_ASK_SLOPE),
+            ("depth_bands", VT_DEPTH_BANDS),
+        ];
+
+        // flatc assigns slot 4 + 2 * i to the i-th field
+        assert_eq!(fields.len(), slots.len());
+        for (i, (field, (name, slot))) in fields.iter().zip(slots).enumerate() {
+            assert_eq!(*field, name);
+            assert_eq!(slot as usize, 4 + 2 * i, "{}", name);
+        }
+        assert!(schema.contains(&format!("file_identifier \"{}\";", FILE_IDENTIFIER)));
+    }
+}
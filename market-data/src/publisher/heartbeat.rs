@@ -0,0 +1,102 @@
+//! Per-symbol heartbeats on the IPC socket
+//!
+//! Every `IPC_HEARTBEAT_INTERVAL_MS` each configured symbol gets a
+//! `Heartbeat` message with its book's last update id and age, whether or
+//! not the market moved. A consumer that keeps receiving heartbeats knows
+//! the publisher is alive and a quiet book is just quiet; one whose
+//! heartbeats stop, or whose book age keeps growing, knows its data is
+//! stale.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::pipeline::Books;
+use crate::AppState;
+
+/// Liveness of one symbol's book
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookHeartbeat {
+    pub symbol: String,
+    /// Whether the book is initialized; the other fields are zero or
+    /// `None` until it is
+    pub initialized: bool,
+    /// Update id of the book's last snapshot or applied diff
+    pub last_update_id: u64,
+    /// Milliseconds since the event time of the last applied diff, or
+    /// since the snapshot was applied if later; `None` until the book's
+    /// task has recorded either
+    pub book_age_ms: Option<u64>,
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+}
+
+/// Heartbeats of every configured symbol as of `now_ms`
+pub fn heartbeats(books: &Books, now_ms: u64) -> Vec<BookHeartbeat> {
+    let update_times: HashMap<String, u64> = books.update_times().into_iter().collect();
+    books
+        .symbols()
+        .iter()
+        .map(|symbol| BookHeartbeat {
+            symbol: symbol.clone(),
+            initialized: books.is_initialized(symbol),
+            last_update_id: books
+                .state(symbol)
+                .map(|state| state.last_update_id)
+                .unwrap_or(0),
+            book_age_ms: update_times
+                .get(symbol)
+                .map(|updated_at| now_ms.saturating_sub(*updated_at)),
+            timestamp: now_ms,
+        })
+        .collect()
+}
+
+/// Send every symbol's heartbeat each `interval`
+pub async fn run(state: Arc<AppState>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+        for heartbeat in heartbeats(&state.books, now_ms) {
+            if let Err(e) = state.publisher.publish_heartbeat(&heartbeat).await {
+                warn!(error = %e, symbol = %heartbeat.symbol, "Failed to publish heartbeat");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{BookSnapshot, PriceLevel, Venue};
+    use crate::orderbook::OrderBookManager;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_heartbeat_per_configured_symbol() {
+        let level = |price, quantity| PriceLevel { price, quantity };
+        let mut manager = OrderBookManager::new();
+        manager.init_book(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            last_update_id: 100,
+            bids: vec![level(dec!(100), dec!(1))],
+            asks: vec![level(dec!(101), dec!(1))],
+        });
+        let symbols = vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()];
+        let (books, _tasks) = Books::new(manager, &symbols);
+
+        let beats = heartbeats(&books, 5_000);
+        assert_eq!(beats.len(), 2);
+        assert!(beats[0].initialized);
+        assert_eq!(beats[0].last_update_id, 100);
+        // Its task isn't running to record when the snapshot was applied
+        assert_eq!(beats[0].book_age_ms, None);
+        assert_eq!(beats[1].symbol, "ETHUSDT");
+        assert!(!beats[1].initialized);
+        assert_eq!(beats[1].timestamp, 5_000);
+    }
+}
@@ -0,0 +1,457 @@
+//! Write-ahead journal of published frames
+//!
+//! With `JOURNAL_DIR` set, every frame is queued for the journal as it is
+//! enveloped, before it reaches the socket, whether or not a consumer is
+//! connected, and appended by a writer thread of its own so disk writes
+//! never stall the async runtime. The envelope sequence number is the
+//! journal offset: it keeps counting from the journal's last frame across
+//! restarts, so offsets only ever increase.
+//!
+//! A frame that can't be queued or written leaves a hole at its offset: a
+//! torn write is cut off the segment (or the segment abandoned for a new
+//! one), the frame is counted in `ipc_journal_errors_total`, and replays
+//! spanning it report the frames missing.
+//!
+//! A consumer that restarts, or notices a sequence gap, asks for what it
+//! missed with `GET /replay?from_seq=N` and receives the frames from `N`
+//! on, byte for byte as the socket sent them, which makes the IPC feed an
+//! at-least-once stream: frames it already had may come again and are
+//! recognized by their sequence number.
+//!
+//! The journal is a directory of segment files named after the sequence
+//! number of their first frame, each holding length-prefixed frames back
+//! to back. A segment is closed once it reaches `JOURNAL_SEGMENT_BYTES`,
+//! and the oldest are deleted beyond `JOURNAL_MAX_SEGMENTS`. Frames are
+//! written through to the OS on append, so they survive a crash of the
+//! process but not of the machine.
+
+use prometheus::IntCounter;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::error::{MarketDataError, Result};
+
+const SEGMENT_SUFFIX: &str = ".journal";
+
+/// Frames queued for the writer thread before new ones are dropped
+const WRITE_QUEUE: usize = 65_536;
+
+static ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "ipc_journal_errors_total",
+        "Frames that could not be appended to the journal",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Count a frame missing from the journal
+pub fn failed() {
+    ERRORS.inc();
+}
+
+/// Appends frames to the current segment
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    segment_bytes: u64,
+    max_segments: usize,
+    /// First sequence number of each segment, oldest first
+    segments: VecDeque<u64>,
+    file: Option<File>,
+    /// Bytes in the current segment
+    written: u64,
+    last_seq: u64,
+}
+
+impl Journal {
+    /// Open the journal in `dir`, creating it if needed, and drop a frame
+    /// left half-written by a crash
+    pub fn open(dir: impl AsRef<Path>, segment_bytes: u64, max_segments: usize) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut segments: Vec<u64> = fs::read_dir(&dir)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                name.to_str()?.strip_suffix(SEGMENT_SUFFIX)?.parse().ok()
+            })
+            .collect();
+        segments.sort_unstable();
+
+        let mut journal = Self {
+            dir,
+            segment_bytes: segment_bytes.max(1),
+            max_segments: max_segments.max(1),
+            segments: segments.into(),
+            file: None,
+            written: 0,
+            last_seq: 0,
+        };
+        if let Some(&first) = journal.segments.back() {
+            let path = journal.segment_path(first);
+            let scan = scan(&fs::read(&path)?);
+            if scan.complete_len < scan.len {
+                warn!(
+                    path = %path.display(),
+                    bytes = scan.len - scan.complete_len,
+                    "Dropping a partly written frame from the journal"
+                );
+            }
+            let file = OpenOptions::new().write(true).open(&path)?;
+            file.set_len(scan.complete_len as u64)?;
+            journal.written = scan.complete_len as u64;
+            journal.last_seq = scan.last_seq.unwrap_or(first.saturating_sub(1));
+            journal.file = Some(OpenOptions::new().append(true).open(&path)?);
+        }
+        info!(
+            dir = %journal.dir.display(),
+            segments = journal.segments.len(),
+            last_seq = journal.last_seq,
+            "Opened journal"
+        );
+        Ok(journal)
+    }
+
+    /// Sequence number of the last frame journaled, or lost to a hole;
+    /// 0 when empty
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Append a frame whose envelope carries `seq`; on failure nothing of
+    /// it stays in the journal, leaving a hole at `seq`
+    pub fn append(&mut self, seq: u64, frame: &[u8]) -> Result<()> {
+        if seq <= self.last_seq {
+            return Err(MarketDataError::IpcError(format!(
+                "Journal offset {} is not after {}",
+                seq, self.last_seq
+            )));
+        }
+        self.last_seq = seq;
+        if self.file.is_none() || self.written >= self.segment_bytes {
+            self.roll(seq)?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            if let Err(e) = file.write_all(frame) {
+                // Cut off what made it to the segment, or leave the segment
+                // behind so the next frame starts a new one
+                if file.set_len(self.written).is_err() {
+                    self.file = None;
+                }
+                return Err(e.into());
+            }
+        }
+        self.written += frame.len() as u64;
+        Ok(())
+    }
+
+    /// Start a segment at `seq`, deleting the oldest beyond the limit
+    fn roll(&mut self, seq: u64) -> Result<()> {
+        self.file = None;
+        self.file = Some(
+            OpenOptions::new()
+                .create_new(true)
+                .append(true)
+                .open(self.segment_path(seq))?,
+        );
+        self.segments.push_back(seq);
+        self.written = 0;
+        while self.segments.len() > self.max_segments {
+            if let Some(oldest) = self.segments.pop_front() {
+                if let Err(e) = fs::remove_file(self.segment_path(oldest)) {
+                    warn!(error = %e, segment = oldest, "Failed to delete journal segment");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn segment_path(&self, first_seq: u64) -> PathBuf {
+        segment_path(&self.dir, first_seq)
+    }
+
+    /// Reader over the segments as they are now
+    pub fn reader(&self) -> JournalReader {
+        JournalReader {
+            dir: self.dir.clone(),
+            segments: self.segments.iter().copied().collect(),
+            last_seq: self.last_seq,
+        }
+    }
+}
+
+/// Appends frames to a [`Journal`] on a thread of its own
+#[derive(Debug)]
+pub struct JournalWriter {
+    frames: Option<SyncSender<(u64, Vec<u8>)>>,
+    thread: Option<JoinHandle<()>>,
+    /// Segments and last offset as of the last append
+    view: Arc<Mutex<JournalReader>>,
+}
+
+impl JournalWriter {
+    /// Start the writer thread appending to `journal`
+    pub fn spawn(mut journal: Journal) -> Result<Self> {
+        let (tx, rx) = mpsc::sync_channel::<(u64, Vec<u8>)>(WRITE_QUEUE);
+        let view = Arc::new(Mutex::new(journal.reader()));
+        let shared = view.clone();
+        let thread = std::thread::Builder::new()
+            .name("journal-writer".to_string())
+            .spawn(move || {
+                for (seq, frame) in rx {
+                    if let Err(e) = journal.append(seq, &frame) {
+                        failed();
+                        warn!(error = %e, seq, "Failed to journal frame");
+                    }
+                    let mut view = shared.lock().unwrap();
+                    view.last_seq = journal.last_seq;
+                    if view.segments.last() != journal.segments.back()
+                        || view.segments.first() != journal.segments.front()
+                    {
+                        view.segments = journal.segments.iter().copied().collect();
+                    }
+                }
+            })?;
+        Ok(Self {
+            frames: Some(tx),
+            thread: Some(thread),
+            view,
+        })
+    }
+
+    /// Sequence number of the last frame journaled when the writer started
+    /// or since
+    pub fn last_seq(&self) -> u64 {
+        self.view.lock().unwrap().last_seq
+    }
+
+    /// Queue a frame whose envelope carries `seq`; one that finds the
+    /// queue full is counted and left as a hole
+    pub fn append(&self, seq: u64, frame: Vec<u8>) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+        match frames.try_send((seq, frame)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                failed();
+                warn!(seq, "Journal writer behind, frame not journaled");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                failed();
+                error!(seq, "Journal writer stopped, frame not journaled");
+            }
+        }
+    }
+
+    /// Reader over what has been journaled so far
+    pub fn reader(&self) -> JournalReader {
+        self.view.lock().unwrap().clone()
+    }
+
+    /// Stop accepting frames; the returned thread finishes writing those
+    /// queued, for the caller to join off the async runtime
+    pub fn close(&mut self) -> Option<JoinHandle<()>> {
+        self.frames = None;
+        self.thread.take()
+    }
+}
+
+impl Drop for JournalWriter {
+    fn drop(&mut self) {
+        if let Some(thread) = self.close() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn segment_path(dir: &Path, first_seq: u64) -> PathBuf {
+    dir.join(format!("{:020}{}", first_seq, SEGMENT_SUFFIX))
+}
+
+/// Reads journaled frames without holding up appends
+#[derive(Debug, Clone)]
+pub struct JournalReader {
+    dir: PathBuf,
+    segments: Vec<u64>,
+    last_seq: u64,
+}
+
+/// Frames read from the journal
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// Length-prefixed frames, in sequence order
+    pub frames: Vec<u8>,
+    pub count: usize,
+    /// Sequence numbers in the range read whose frames failed to journal
+    pub missing: u64,
+    /// Oldest sequence number still journaled
+    pub first_seq: u64,
+    pub last_seq: u64,
+}
+
+impl JournalReader {
+    /// Up to `limit` frames from `from_seq` on; `None` when `from_seq`
+    /// was already deleted, so the gap can't be filled
+    pub fn read(&self, from_seq: u64, limit: usize) -> Result<Option<Replay>> {
+        let first_seq = self.segments.first().copied().unwrap_or(self.last_seq + 1);
+        if from_seq < first_seq && from_seq <= self.last_seq {
+            return Ok(None);
+        }
+        let mut replay = Replay {
+            first_seq,
+            last_seq: self.last_seq,
+            ..Default::default()
+        };
+        // Next sequence number expected, to count holes by
+        let mut next = from_seq.max(first_seq);
+        // The segment holding `from_seq` and those after it
+        let start = self
+            .segments
+            .partition_point(|&first| first <= from_seq)
+            .saturating_sub(1);
+        for &segment in &self.segments[start..] {
+            let mut data = Vec::new();
+            match File::open(segment_path(&self.dir, segment)) {
+                Ok(mut file) => file.read_to_end(&mut data)?,
+                // Deleted since the reader was taken: its frames are gone
+                Err(e) if e.kind() == ErrorKind::NotFound && replay.count == 0 => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            for (seq, frame) in frames(&data) {
+                if seq < from_seq || seq > self.last_seq {
+                    continue;
+                }
+                if replay.count == limit {
+                    return Ok(Some(replay));
+                }
+                replay.missing += seq.saturating_sub(next);
+                next = seq + 1;
+                replay.frames.extend_from_slice(frame);
+                replay.count += 1;
+            }
+        }
+        replay.missing += (self.last_seq + 1).saturating_sub(next);
+        Ok(Some(replay))
+    }
+}
+
+/// What a segment holds
+struct Scan {
+    len: usize,
+    /// Bytes up to the end of the last whole frame
+    complete_len: usize,
+    last_seq: Option<u64>,
+}
+
+fn scan(data: &[u8]) -> Scan {
+    let mut scan = Scan {
+        len: data.len(),
+        complete_len: 0,
+        last_seq: None,
+    };
+    for (seq, frame) in frames(data) {
+        scan.complete_len += frame.len();
+        scan.last_seq = Some(seq);
+    }
+    scan
+}
+
+/// Whole frames of a segment with their sequence numbers, stopping at a
+/// partly written one
+fn frames(data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let frame = rest.get(..4 + len)?;
+        let seq = u64::from_be_bytes(frame.get(4..12)?.try_into().ok()?);
+        rest = &rest[4 + len..];
+        Some((seq, frame))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Compression;
+    use crate::publisher::envelope::{Envelope, MessageType};
+
+    fn frame(seq: u64) -> Vec<u8> {
+        Envelope {
+            seq,
+            sent_at_us: 0,
+            message_type: MessageType::Book,
+            compression: Compression::None,
+            symbol: "BTCUSDT".to_string(),
+        }
+        .frame(&[seq as u8; 40])
+    }
+
+    fn seqs(replay: &Replay) -> Vec<u64> {
+        frames(&replay.frames).map(|(seq, _)| seq).collect()
+    }
+
+    #[test]
+    fn test_journal_replays_across_segments_and_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        // Two frames per segment, three segments kept
+        let mut journal = Journal::open(dir.path(), 100, 3).unwrap();
+        for seq in 1..=7 {
+            journal.append(seq, &frame(seq)).unwrap();
+        }
+        assert!(journal.append(7, &frame(7)).is_err());
+
+        let reader = journal.reader();
+        let replay = reader.read(4, 10).unwrap().unwrap();
+        assert_eq!(seqs(&replay), [4, 5, 6, 7]);
+        assert_eq!((replay.first_seq, replay.last_seq), (3, 7));
+        assert_eq!(seqs(&reader.read(3, 2).unwrap().unwrap()), [3, 4]);
+        assert_eq!(reader.read(8, 10).unwrap().unwrap().count, 0);
+        // Segment 1..=2 was deleted
+        assert!(reader.read(2, 10).unwrap().is_none());
+
+        // A crash mid-append leaves half a frame behind
+        let torn = &frame(8)[..20];
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(segment_path(dir.path(), 7))
+            .unwrap();
+        file.write_all(torn).unwrap();
+        drop(journal);
+
+        let mut journal = Journal::open(dir.path(), 100, 3).unwrap();
+        assert_eq!(journal.last_seq(), 7);
+        journal.append(8, &frame(8)).unwrap();
+        let replay = journal.reader().read(6, 10).unwrap().unwrap();
+        assert_eq!(seqs(&replay), [6, 7, 8]);
+        assert_eq!(replay.frames, [frame(6), frame(7), frame(8)].concat());
+        assert_eq!(replay.missing, 0);
+    }
+
+    #[test]
+    fn test_writer_thread_journals_and_reports_holes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = JournalWriter::spawn(Journal::open(dir.path(), 100, 3).unwrap()).unwrap();
+        // Frame 3 never reached the journal
+        for seq in [1, 2, 4, 5] {
+            writer.append(seq, frame(seq));
+        }
+        writer.close().unwrap().join().unwrap();
+        writer.append(6, frame(6));
+
+        let replay = writer.reader().read(1, 10).unwrap().unwrap();
+        assert_eq!(seqs(&replay), [1, 2, 4, 5]);
+        assert_eq!((replay.missing, replay.last_seq), (1, 5));
+        assert_eq!(writer.reader().read(4, 10).unwrap().unwrap().missing, 0);
+
+        let journal = Journal::open(dir.path(), 100, 3).unwrap();
+        assert_eq!(journal.last_seq(), 5);
+    }
+}
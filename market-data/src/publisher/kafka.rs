@@ -0,0 +1,100 @@
+//! Kafka producer backend
+//!
+//! Publishes MessagePack-encoded book states and trades to Kafka for
+//! stream-processing and archival pipelines. Topics are derived per symbol
+//! from a template (`{symbol}` is substituted) and messages are keyed by
+//! symbol, so each symbol stays ordered within one partition unless an
+//! explicit partition is mapped. Enabled with the `kafka` feature.
+
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+
+/// Kafka publisher
+pub struct KafkaSink {
+    producer: FutureProducer,
+    book_topic: String,
+    trade_topic: String,
+    partitions: HashMap<String, i32>,
+}
+
+impl KafkaSink {
+    /// Create a producer for `brokers` using the Kafka settings in `config`
+    pub fn new(brokers: &str, config: &Config) -> Result<Self> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("compression.type", &config.kafka_compression)
+            .set("linger.ms", config.kafka_linger_ms.to_string())
+            .set("message.timeout.ms", "5000")
+            .create()
+            .map_err(|e| MarketDataError::ConfigError(format!("Kafka producer: {}", e)))?;
+
+        Ok(Self {
+            producer,
+            book_topic: config.kafka_book_topic.clone(),
+            trade_topic: config.kafka_trade_topic.clone(),
+            partitions: config.kafka_symbol_partitions.clone(),
+        })
+    }
+
+    /// Publish a book state to its symbol's book topic
+    pub fn publish_state(&self, state: &OrderBookState) -> Result<()> {
+        self.send(&self.book_topic, &state.symbol, state)
+    }
+
+    /// Publish a trade to its symbol's trade topic
+    pub fn publish_trade(&self, trade: &Trade) -> Result<()> {
+        self.send(&self.trade_topic, &trade.symbol, trade)
+    }
+
+    /// Wait up to `timeout` for queued messages to be delivered
+    pub fn flush(&self, timeout: Duration) -> Result<()> {
+        self.producer
+            .flush(timeout)
+            .map_err(|e| MarketDataError::IpcError(format!("Kafka flush: {}", e)))
+    }
+
+    /// Enqueue a message without waiting for delivery
+    ///
+    /// librdkafka batches and retries in the background; a full local
+    /// queue is reported as an error rather than blocking the hot path.
+    fn send<T: Serialize>(&self, template: &str, symbol: &str, value: &T) -> Result<()> {
+        let payload = rmp_serde::to_vec(value).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
+        })?;
+        let topic = topic_for(template, symbol);
+
+        let mut record = FutureRecord::to(&topic).key(symbol).payload(&payload);
+        if let Some(&partition) = self.partitions.get(symbol) {
+            record = record.partition(partition);
+        }
+
+        self.producer
+            .send_result(record)
+            .map(drop)
+            .map_err(|(e, _)| MarketDataError::IpcError(format!("Kafka send: {}", e)))
+    }
+}
+
+/// Expand a topic template for a symbol, e.g. "md.book.{symbol}"
+pub fn topic_for(template: &str, symbol: &str) -> String {
+    template.replace("{symbol}", symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_for() {
+        assert_eq!(topic_for("md.book.{symbol}", "BTCUSDT"), "md.book.BTCUSDT");
+        assert_eq!(topic_for("market-data", "BTCUSDT"), "market-data");
+    }
+}
@@ -0,0 +1,129 @@
+//! In-process live feed
+//!
+//! Broadcasts published book states and trades to in-process subscribers
+//! such as the `/ws` re-broadcast endpoint and the gRPC server. Each
+//! subscriber buffers a bounded number of messages; one that falls behind
+//! skips what it missed rather than slowing the publisher.
+//!
+//! The feed also keeps each symbol's latest state, so a subscriber joining
+//! late starts from a full snapshot of every book instead of waiting for
+//! each symbol's next update.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+
+/// Fan-out point between the publisher and in-process subscribers
+#[derive(Clone)]
+pub struct LiveFeed {
+    books: broadcast::Sender<Arc<OrderBookState>>,
+    trades: broadcast::Sender<Arc<Trade>>,
+    /// Latest state per symbol, updated under the lock with the broadcast
+    latest: Arc<RwLock<HashMap<String, Arc<OrderBookState>>>>,
+}
+
+impl LiveFeed {
+    /// Create a feed buffering `capacity` messages per subscriber
+    pub fn new(capacity: usize) -> Self {
+        let (books, _) = broadcast::channel(capacity.max(1));
+        let (trades, _) = broadcast::channel(capacity.max(1));
+        Self {
+            books,
+            trades,
+            latest: Arc::default(),
+        }
+    }
+
+    /// Hand a state to current subscribers and keep it as the symbol's
+    /// latest
+    pub fn publish_state(&self, state: &OrderBookState) {
+        let state = Arc::new(state.clone());
+        let mut latest = self.latest.write().unwrap();
+        latest.insert(state.symbol.clone(), state.clone());
+        if self.books.receiver_count() > 0 {
+            let _ = self.books.send(state);
+        }
+    }
+
+    /// Hand a trade to current subscribers
+    pub fn publish_trade(&self, trade: &Trade) {
+        if self.trades.receiver_count() > 0 {
+            let _ = self.trades.send(Arc::new(trade.clone()));
+        }
+    }
+
+    /// Receive book states published from now on
+    pub fn subscribe_books(&self) -> broadcast::Receiver<Arc<OrderBookState>> {
+        self.books.subscribe()
+    }
+
+    /// Latest state of every book, and the states published after them
+    ///
+    /// Taken together, so the receiver picks up exactly where the
+    /// snapshot leaves off.
+    pub fn subscribe_books_with_snapshot(
+        &self,
+    ) -> (
+        Vec<Arc<OrderBookState>>,
+        broadcast::Receiver<Arc<OrderBookState>>,
+    ) {
+        let latest = self.latest.read().unwrap();
+        let mut snapshot: Vec<_> = latest.values().cloned().collect();
+        snapshot.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        (snapshot, self.books.subscribe())
+    }
+
+    /// Latest state of a book, if one was published
+    pub fn latest(&self, symbol: &str) -> Option<Arc<OrderBookState>> {
+        self.latest.read().unwrap().get(symbol).cloned()
+    }
+
+    /// Receive trades published from now on
+    pub fn subscribe_trades(&self) -> broadcast::Receiver<Arc<Trade>> {
+        self.trades.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBookMetrics;
+
+    fn state(symbol: &str, last_update_id: u64) -> OrderBookState {
+        OrderBookState {
+            symbol: symbol.to_string(),
+            timestamp: 0,
+            last_update_id,
+            bids: vec![],
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn test_late_subscriber_starts_from_latest_books() {
+        let feed = LiveFeed::new(16);
+        feed.publish_state(&state("ETHUSDT", 1));
+        feed.publish_state(&state("BTCUSDT", 1));
+        feed.publish_state(&state("BTCUSDT", 2));
+
+        let (snapshot, mut receiver) = feed.subscribe_books_with_snapshot();
+        let latest: Vec<_> = snapshot
+            .iter()
+            .map(|s| (s.symbol.as_str(), s.last_update_id))
+            .collect();
+        assert_eq!(latest, [("BTCUSDT", 2), ("ETHUSDT", 1)]);
+        assert!(receiver.try_recv().is_err());
+
+        feed.publish_state(&state("BTCUSDT", 3));
+        assert_eq!(receiver.try_recv().unwrap().last_update_id, 3);
+        assert_eq!(feed.latest("BTCUSDT").unwrap().last_update_id, 3);
+    }
+}
@@ -1,38 +1,415 @@
 //! Publisher module for IPC communication
 //!
 //! Publishes order book state to other system components.
+//!
+//! Framing: every message is a 4-byte big-endian length prefix, an
+//! envelope header with the publisher sequence number, message type, symbol
+//! and send time (see `envelope`), then a payload encoded per `WIRE_FORMAT`
+//! (MessagePack by default, or JSON) and optionally compressed per
+//! `IPC_COMPRESSION` (see `compression`).
+//! In `full` mode the payload is an `OrderBookState`; in `delta` mode it is
+//! a `BookMessage` (snapshot or changed levels). The `protobuf` format
+//! carries full states only, as `OrderBook` messages from
+//! `proto/market_data.proto`; `flatbuffers` carries full states as
+//! `OrderBook` tables from `schema/order_book.fbs` (see `flatbuf`).
+//! With `IPC_BOOTSTRAP` enabled (full mode only) the payload is a
+//! `BootstrapFrame`; see the `bootstrap` module for the handshake.
+//! Volume profiles and anomaly events are sent between states, when
+//! enabled, as `VolumeProfile` and `MarketAnomaly` messages, and the
+//! account's order updates and balances as `OrderEvent` and
+//! `AccountPosition` messages when the user data stream is followed.
+//! Changes of the feed's state (exchange connection, book resyncs and
+//! sequence gaps) are sent as `StatusEvent` messages, and every symbol's
+//! book gets a periodic `BookHeartbeat` (see `heartbeat`). On shutdown the
+//! publisher flushes its queues and sends a final `ControlMessage`.
+//! With `JOURNAL_DIR` set, every frame is also journaled under its
+//! sequence number for consumers to replay what they missed (see
+//! `journal`).
+//!
+//! Optionally, states and trades from the live feed are also exported as
+//! Arrow record batches for research tooling (see `arrow`).
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod bootstrap;
+pub mod clickhouse;
+pub mod compression;
+mod conflation;
+mod delta;
+pub mod envelope;
+pub mod flatbuf;
+pub mod heartbeat;
+pub mod journal;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+mod live;
+pub mod multicast;
+#[cfg(feature = "nats")]
+pub mod nats;
+mod queue;
+mod reconnect;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod shm;
+
+pub use bootstrap::{BootstrapFrame, BootstrapRequest, Recovery, ReplayBuffer};
+pub use clickhouse::ClickHouseSink;
+pub use conflation::Conflator;
+pub use delta::{BookDelta, BookMessage, DeltaEncoder};
+pub use envelope::{ControlMessage, Envelope, MessageType, StatusEvent};
+pub use heartbeat::BookHeartbeat;
+pub use journal::{Journal, JournalReader, JournalWriter, Replay};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+pub use live::LiveFeed;
+pub use multicast::{MulticastSender, Reassembler};
+#[cfg(feature = "nats")]
+pub use nats::NatsSink;
+pub use queue::SendQueue;
+pub use reconnect::{Backlog, Backoff};
+#[cfg(feature = "redis")]
+pub use redis::RedisSink;
+pub use shm::{ShmReader, ShmWriter};
 
+use socket2::SockRef;
+use std::collections::HashMap;
 use std::path::Path;
-use tokio::io::AsyncWriteExt;
+#[cfg(feature = "arrow")]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 use tracing::{debug, info, warn};
 
+use crate::anomaly::MarketAnomaly;
+use crate::config::{Compression, Config, PublishMode, WireFormat};
+use crate::degradation::Degradation;
 use crate::error::{MarketDataError, Result};
-use crate::orderbook::OrderBookState;
+use crate::event::Trade;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::orderbook::{BboChanged, OrderBookState};
+use crate::socket;
+use crate::time::Clock;
+use crate::user_data::{AccountPosition, OrderEvent};
+use crate::volume_profile::VolumeProfile;
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+
+/// Largest bootstrap request accepted from a consumer
+const MAX_BOOTSTRAP_REQUEST: usize = 1 << 20;
 
 /// Publisher for sending order book updates via Unix socket
 pub struct Publisher {
     socket_path: String,
+    /// SO_SNDBUF of the IPC socket; `None` leaves the kernel default
+    send_buffer: Option<usize>,
     stream: Mutex<Option<UnixStream>>,
+    /// Optional per-symbol throttle; `None` publishes every update
+    conflator: Option<std::sync::Mutex<Conflator>>,
+    /// Delta encoder, present in `delta` publish mode
+    delta: Option<std::sync::Mutex<DeltaEncoder>>,
+    /// Recent states for consumer bootstrap, present when enabled
+    replay: Option<std::sync::Mutex<ReplayBuffer>>,
+    /// How long to wait for a consumer's bootstrap request
+    bootstrap_timeout: Duration,
+    /// Payload encoding of socket frames
+    wire_format: WireFormat,
+    /// Codec applied to socket frame payloads
+    compression: Compression,
+    /// Sequence number of the last frame written to the socket
+    seq: AtomicU64,
+    /// Write-ahead journal of every frame, when enabled
+    journal: Option<std::sync::Mutex<JournalWriter>>,
+    /// Set by `shutdown`; the socket is no longer written or reconnected
+    closed: AtomicBool,
+    /// Whether an IPC consumer is connected, readable without the stream
+    /// lock
+    connected: AtomicBool,
+    /// Signalled when the socket drops, waking the reconnect task
+    disconnected: Notify,
+    /// First and longest delay between reconnect attempts
+    reconnect_delay: Duration,
+    reconnect_max_delay: Duration,
+    /// States held while disconnected, written first on reconnect; present
+    /// when enabled and bootstrap is off
+    backlog: Option<std::sync::Mutex<Backlog>>,
+    /// States waiting for the socket, one queue and writer per symbol
+    queues: HashMap<String, SendQueue>,
+    /// Queue for symbols outside the configured set
+    fallback_queue: SendQueue,
+    /// Optional shared-memory ring for co-located readers
+    shm: Option<std::sync::Mutex<ShmWriter>>,
+    /// Optional UDP multicast feed for LAN consumers
+    multicast: Option<MulticastSender>,
+    /// Optional Kafka producer backend
+    #[cfg(feature = "kafka")]
+    kafka: Option<KafkaSink>,
+    /// Optional NATS / JetStream backend
+    #[cfg(feature = "nats")]
+    nats: Option<NatsSink>,
+    /// Optional Redis pub/sub and streams backend
+    #[cfg(feature = "redis")]
+    redis: Option<RedisSink>,
+    /// Optional ClickHouse sink for trades and book metrics
+    clickhouse: Option<ClickHouseSink>,
+    /// In-process broadcast of states and trades (`/ws`, gRPC)
+    live: LiveFeed,
+    /// Sheds the live feed and external backends under overload
+    degradation: Arc<Degradation>,
+    /// Optional gRPC streaming server address
+    #[cfg(feature = "grpc")]
+    grpc_addr: Option<SocketAddr>,
+    /// Optional Arrow IPC export directory
+    #[cfg(feature = "arrow")]
+    arrow_dir: Option<PathBuf>,
+    /// How often buffered rows are written as a record batch
+    #[cfg(feature = "arrow")]
+    arrow_interval: Duration,
+    /// Times conflation and delta keyframes
+    clock: Clock,
 }
 
 impl Publisher {
     /// Create a new publisher
-    pub async fn new(socket_path: &str) -> Result<Self> {
+    pub async fn new(config: &Config) -> Result<Self> {
+        let symbol_intervals: HashMap<String, Duration> = config
+            .publish_throttle_ms_symbols
+            .iter()
+            .map(|(symbol, ms)| (symbol.clone(), Duration::from_millis(*ms)))
+            .collect();
+        let throttled =
+            config.publish_throttle_ms > 0 || symbol_intervals.values().any(|i| !i.is_zero());
+        let conflator = throttled.then(|| {
+            std::sync::Mutex::new(
+                Conflator::new(
+                    Duration::from_millis(config.publish_throttle_ms),
+                    config.publish_on_bbo_change,
+                )
+                .with_symbol_intervals(symbol_intervals),
+            )
+        });
+        let delta = (config.publish_mode == PublishMode::Delta).then(|| {
+            std::sync::Mutex::new(DeltaEncoder::new(Duration::from_millis(
+                config.full_refresh_interval_ms,
+            )))
+        });
+
+        let replay = match (config.ipc_bootstrap, config.publish_mode) {
+            (true, PublishMode::Full) => {
+                let epoch = chrono::Utc::now().timestamp_micros() as u64;
+                Some(std::sync::Mutex::new(ReplayBuffer::new(
+                    epoch,
+                    config.ipc_replay_depth,
+                )))
+            }
+            (true, PublishMode::Delta) => {
+                // Delta consumers already restart from snapshots on connect
+                warn!("IPC_BOOTSTRAP is only supported in full publish mode, ignoring");
+                None
+            }
+            (false, _) => None,
+        };
+        let journal = match &config.journal_dir {
+            Some(dir) => Some(JournalWriter::spawn(Journal::open(
+                dir,
+                config.journal_segment_bytes,
+                config.journal_max_segments,
+            )?)?),
+            None => None,
+        };
+        // Bootstrap gap-fills a returning consumer from the replay buffer,
+        // and journaled states are replayed on request, rather than held
+        let backlog = (config.ipc_backlog_capacity > 0 && replay.is_none() && journal.is_none())
+            .then(|| std::sync::Mutex::new(Backlog::new(config.ipc_backlog_capacity)));
+
+        let wire_format = match config.wire_format {
+            WireFormat::Protobuf if !cfg!(feature = "protobuf") => {
+                warn!("WIRE_FORMAT=protobuf needs the protobuf feature, using msgpack");
+                WireFormat::MessagePack
+            }
+            // The schemas have no delta or bootstrap messages
+            WireFormat::Protobuf | WireFormat::FlatBuffers
+                if delta.is_some() || replay.is_some() =>
+            {
+                warn!(
+                    format = ?config.wire_format,
+                    "Wire format only supports full states without bootstrap, using msgpack"
+                );
+                WireFormat::MessagePack
+            }
+            format => format,
+        };
+
+        let shm = match &config.shm_path {
+            Some(path) => {
+                let writer = ShmWriter::create(path, config.shm_slot_size, config.shm_slot_count)?;
+                info!(path = %path, capacity = writer.capacity(), "Shared-memory ring created");
+                Some(std::sync::Mutex::new(writer))
+            }
+            None => None,
+        };
+
+        let multicast = match &config.multicast_group {
+            Some(group) => {
+                let mut sender = MulticastSender::new(
+                    group,
+                    config.multicast_mtu,
+                    config.multicast_ttl,
+                    config.multicast_interface,
+                )?;
+                if let Some(size) = config.multicast_send_buffer_bytes {
+                    sender = sender.with_send_buffer(size)?;
+                }
+                info!(group = %group, "Multicast publisher enabled");
+                Some(sender)
+            }
+            None => None,
+        };
+
+        #[cfg(feature = "kafka")]
+        let kafka = match &config.kafka_brokers {
+            Some(brokers) => {
+                let sink = KafkaSink::new(brokers, config)?;
+                info!(brokers = %brokers, "Kafka publisher enabled");
+                Some(sink)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "kafka"))]
+        if config.kafka_brokers.is_some() {
+            warn!("KAFKA_BROKERS is set but the kafka feature is not compiled in");
+        }
+
+        #[cfg(feature = "nats")]
+        let nats = match &config.nats_url {
+            Some(url) => {
+                let sink = NatsSink::connect(
+                    url,
+                    &config.nats_subject_prefix,
+                    config.nats_jetstream_stream.as_deref(),
+                )
+                .await?;
+                info!(url = %url, "NATS publisher enabled");
+                Some(sink)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "nats"))]
+        if config.nats_url.is_some() {
+            warn!("NATS_URL is set but the nats feature is not compiled in");
+        }
+
+        #[cfg(feature = "redis")]
+        let redis = match &config.redis_url {
+            Some(url) => {
+                let sink = RedisSink::connect(url, config).await?;
+                info!(mode = ?config.redis_mode, "Redis publisher enabled");
+                Some(sink)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "redis"))]
+        if config.redis_url.is_some() {
+            warn!("REDIS_URL is set but the redis feature is not compiled in");
+        }
+
+        let clickhouse = match &config.clickhouse_url {
+            Some(url) => {
+                let sink = ClickHouseSink::new(url, config)?;
+                info!(url = %url, database = %config.clickhouse_database, "ClickHouse sink enabled");
+                Some(sink)
+            }
+            None => None,
+        };
+
+        #[cfg(not(feature = "grpc"))]
+        if config.grpc_addr.is_some() {
+            warn!("GRPC_ADDR is set but the grpc feature is not compiled in");
+        }
+        #[cfg(not(feature = "arrow"))]
+        if config.arrow_export_dir.is_some() {
+            warn!("ARROW_EXPORT_DIR is set but the arrow feature is not compiled in");
+        }
+
         let publisher = Self {
-            socket_path: socket_path.to_string(),
+            socket_path: config.ipc_socket_path.clone(),
+            send_buffer: config.ipc_send_buffer_bytes,
             stream: Mutex::new(None),
+            conflator,
+            delta,
+            replay,
+            bootstrap_timeout: Duration::from_millis(config.ipc_bootstrap_timeout_ms),
+            wire_format,
+            compression: config.ipc_compression,
+            seq: AtomicU64::new(journal.as_ref().map_or(0, JournalWriter::last_seq)),
+            journal: journal.map(std::sync::Mutex::new),
+            closed: AtomicBool::new(false),
+            connected: AtomicBool::new(false),
+            disconnected: Notify::new(),
+            reconnect_delay: Duration::from_millis(config.ipc_reconnect_delay_ms),
+            reconnect_max_delay: Duration::from_millis(config.ipc_reconnect_max_delay_ms),
+            backlog,
+            queues: config
+                .symbols
+                .iter()
+                .map(|symbol| {
+                    let queue =
+                        SendQueue::new(symbol, config.ipc_queue_capacity, config.ipc_queue_policy);
+                    (symbol.clone(), queue)
+                })
+                .collect(),
+            fallback_queue: SendQueue::new(
+                "other",
+                config.ipc_queue_capacity,
+                config.ipc_queue_policy,
+            ),
+            shm,
+            multicast,
+            #[cfg(feature = "kafka")]
+            kafka,
+            #[cfg(feature = "nats")]
+            nats,
+            #[cfg(feature = "redis")]
+            redis,
+            clickhouse,
+            live: LiveFeed::new(config.live_feed_buffer),
+            degradation: Arc::default(),
+            #[cfg(feature = "grpc")]
+            grpc_addr: config.grpc_addr,
+            #[cfg(feature = "arrow")]
+            arrow_dir: config.arrow_export_dir.as_ref().map(PathBuf::from),
+            #[cfg(feature = "arrow")]
+            arrow_interval: Duration::from_millis(config.arrow_batch_interval_ms.max(1)),
+            clock: Clock::wall(),
         };
 
         // Try initial connection (may fail if core isn't ready)
+        reconnect::set_connected(false);
         if let Err(e) = publisher.connect().await {
-            warn!(error = %e, "Initial IPC connection failed, will retry on publish");
+            warn!(error = %e, "Initial IPC connection failed, will retry in the background");
         }
 
         Ok(publisher)
     }
 
+    /// Share a degradation controller; auxiliary outputs are skipped while
+    /// it has them shed, and their failures are reported to it
+    pub fn with_degradation(mut self, degradation: Arc<Degradation>) -> Self {
+        self.degradation = degradation;
+        self
+    }
+
+    /// Time conflation and delta keyframes by `clock` rather than the wall
+    /// clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Connect to the Unix socket
     async fn connect(&self) -> Result<()> {
         let path = Path::new(&self.socket_path);
@@ -44,43 +421,484 @@ impl Publisher {
             )));
         }
 
-        let stream = UnixStream::connect(path).await.map_err(|e| {
+        let mut stream = UnixStream::connect(path).await.map_err(|e| {
             MarketDataError::IpcError(format!("Failed to connect to {}: {}", self.socket_path, e))
         })?;
+        socket::set_buffers(SockRef::from(&stream), self.send_buffer, None).map_err(|e| {
+            MarketDataError::IpcError(format!("Failed to size the IPC send buffer: {}", e))
+        })?;
+
+        // Wait for the bootstrap request before taking the stream lock, so
+        // publishing doesn't stall on a consumer that is slow to ask
+        let request = match &self.replay {
+            Some(_) => Some(self.bootstrap_request(&mut stream).await?),
+            None => None,
+        };
 
+        // Hold the stream lock through the bootstrap reply and backlog so
+        // no live frame overtakes them
         let mut guard = self.stream.lock().await;
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        if let Some(request) = &request {
+            self.bootstrap(&mut stream, request).await?;
+        }
+
+        // A new consumer needs full snapshots before deltas make sense
+        if let Some(delta) = &self.delta {
+            delta.lock().unwrap().reset();
+        }
+        let held = match &self.backlog {
+            Some(backlog) => backlog.lock().unwrap().take(),
+            None => Default::default(),
+        };
+        let mut message = Vec::new();
+        for state in &held {
+            message.extend(self.encode(state)?);
+        }
+        stream.write_all(&message).await?;
+
         *guard = Some(stream);
+        self.set_connected(true);
+        info!(
+            path = %self.socket_path,
+            backlog = held.len(),
+            "Connected to IPC socket"
+        );
+        Ok(())
+    }
+
+    /// Record the link state, waking the reconnect task when it dropped
+    fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Release);
+        reconnect::set_connected(connected);
+        if !connected {
+            self.disconnected.notify_one();
+        }
+    }
+
+    /// Reconnect whenever the socket is down, backing off between failed
+    /// attempts, until shutdown
+    async fn reconnect(&self) {
+        let mut backoff = Backoff::new(self.reconnect_delay, self.reconnect_max_delay);
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return;
+            }
+            if self.is_connected() {
+                backoff.reset();
+                self.disconnected.notified().await;
+                continue;
+            }
+            if let Err(e) = self.connect().await {
+                let delay = backoff.next_delay();
+                debug!(
+                    error = %e,
+                    delay_ms = delay.as_millis() as u64,
+                    "Failed to reconnect to IPC socket"
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
 
-        info!(path = %self.socket_path, "Connected to IPC socket");
+    /// Read a newly connected consumer's bootstrap request, waiting up to
+    /// `IPC_BOOTSTRAP_TIMEOUT_MS`
+    async fn bootstrap_request(&self, stream: &mut UnixStream) -> Result<BootstrapRequest> {
+        match tokio::time::timeout(self.bootstrap_timeout, read_frame(stream)).await {
+            Ok(Ok(data)) => Ok(decode_frame::<BootstrapRequest>(&data, self.wire_format)
+                .unwrap_or_else(|e| {
+                    warn!(error = %e, "Invalid bootstrap request, sending snapshots");
+                    BootstrapRequest::default()
+                })),
+            Ok(Err(e)) => Err(e),
+            // Consumer didn't ask: treat it as new
+            Err(_) => Ok(BootstrapRequest::default()),
+        }
+    }
+
+    /// Answer a newly connected consumer's bootstrap request
+    async fn bootstrap(&self, stream: &mut UnixStream, request: &BootstrapRequest) -> Result<()> {
+        let Some(replay) = &self.replay else {
+            return Ok(());
+        };
+
+        let frames = replay.lock().unwrap().bootstrap(request);
+        let mut message = Vec::new();
+        for frame in &frames {
+            let symbol = match frame {
+                BootstrapFrame::Book { state, .. } => state.symbol.as_str(),
+                BootstrapFrame::Reply { .. } => "",
+            };
+            let payload = serialize(frame, self.wire_format)?;
+            message.extend(self.frame(MessageType::Bootstrap, symbol, &payload)?);
+        }
+        stream.write_all(&message).await?;
+
+        if let Some(BootstrapFrame::Reply { symbols, .. }) = frames.first() {
+            info!(
+                symbols = symbols.len(),
+                frames = frames.len() - 1,
+                "Consumer bootstrapped"
+            );
+        }
         Ok(())
     }
 
+    /// Reader of the journaled frames, when the journal is enabled
+    pub fn journal(&self) -> Option<JournalReader> {
+        self.journal
+            .as_ref()
+            .map(|journal| journal.lock().unwrap().reader())
+    }
+
+    /// Whether an IPC consumer is connected
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    /// Spawn background tasks (socket writers and reconnection,
+    /// conflation flushing, gRPC server, Arrow export)
+    ///
+    /// Nothing reaches the IPC socket until the writers are running.
+    pub fn spawn_tasks(self: &Arc<Self>) {
+        let publisher = self.clone();
+        tokio::spawn(async move { publisher.reconnect().await });
+
+        for symbol in self.queues.keys().cloned().map(Some).chain([None]) {
+            let publisher = self.clone();
+            tokio::spawn(async move {
+                let queue = match &symbol {
+                    Some(symbol) => &publisher.queues[symbol],
+                    None => &publisher.fallback_queue,
+                };
+                loop {
+                    let state = queue.pop().await;
+                    if let Err(e) = publisher.write(&state).await {
+                        warn!(error = %e, symbol = %state.symbol, "Failed to write to IPC socket");
+                    }
+                }
+            });
+        }
+
+        #[cfg(feature = "grpc")]
+        if let Some(addr) = self.grpc_addr {
+            let feed = self.live.clone();
+            tokio::spawn(async move {
+                if let Err(e) = grpc::serve(addr, feed).await {
+                    tracing::error!(error = %e, "gRPC server stopped");
+                }
+            });
+        }
+
+        #[cfg(feature = "arrow")]
+        if let Some(dir) = self.arrow_dir.clone() {
+            let feed = self.live.clone();
+            let interval = self.arrow_interval;
+            tokio::spawn(async move {
+                if let Err(e) = arrow::export(&dir, interval, feed).await {
+                    tracing::error!(error = %e, "Arrow export stopped");
+                }
+            });
+        }
+
+        let Some(interval) = self
+            .conflator
+            .as_ref()
+            .map(|c| c.lock().unwrap().interval())
+        else {
+            return;
+        };
+
+        let publisher = self.clone();
+        tokio::spawn(async move {
+            let mut tick_at = publisher.clock.now();
+            loop {
+                publisher.clock.sleep_until(tick_at).await;
+                tick_at += interval;
+                let now = publisher.clock.now();
+                let due = match publisher.conflator.as_ref() {
+                    Some(conflator) => conflator.lock().unwrap().drain_due(now),
+                    None => return,
+                };
+                for state in &due {
+                    if let Err(e) = publisher.send(state).await {
+                        warn!(error = %e, "Failed to flush conflated state");
+                    }
+                }
+            }
+        });
+    }
+
     /// Publish order book state
+    ///
+    /// With conflation enabled the state may be held back and sent later
+    /// by the flush task, superseded by newer states for the same symbol.
+    #[tracing::instrument(level = "debug", skip_all, fields(symbol = %state.symbol))]
     pub async fn publish(&self, state: &OrderBookState) -> Result<()> {
-        // Serialize using MessagePack for efficiency
-        let data = rmp_serde::to_vec(state).map_err(|e| {
-            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
-        })?;
+        let started = Instant::now();
+        let result = match &self.conflator {
+            Some(conflator) => {
+                let ready = conflator
+                    .lock()
+                    .unwrap()
+                    .offer(state.clone(), self.clock.now());
+                match ready {
+                    Some(state) => self.send(&state).await,
+                    None => Ok(()),
+                }
+            }
+            None => self.send(state).await,
+        };
+        crate::metrics::observe_publish(&state.symbol, started.elapsed());
+        result
+    }
+
+    /// Send a volume profile on the IPC socket if a consumer is connected
+    pub async fn publish_profile(&self, profile: &VolumeProfile) -> Result<()> {
+        self.send_message(MessageType::VolumeProfile, &profile.symbol, profile)
+            .await
+    }
 
-        // Prepare message with length prefix
-        let len = (data.len() as u32).to_be_bytes();
-        let mut message = Vec::with_capacity(4 + data.len());
-        message.extend_from_slice(&len);
-        message.extend_from_slice(&data);
+    /// Send an anomaly event on the IPC socket if a consumer is connected
+    pub async fn publish_anomaly(&self, anomaly: &MarketAnomaly) -> Result<()> {
+        self.send_message(MessageType::Anomaly, &anomaly.symbol, anomaly)
+            .await
+    }
+
+    /// Send a top-of-book change on the IPC socket if a consumer is
+    /// connected
+    pub async fn publish_bbo(&self, bbo: &BboChanged) -> Result<()> {
+        self.send_message(MessageType::Bbo, &bbo.symbol, bbo).await
+    }
 
-        // Try to send
+    /// Send an update of one of the account's orders on the IPC socket if
+    /// a consumer is connected
+    pub async fn publish_order(&self, order: &OrderEvent) -> Result<()> {
+        self.send_message(MessageType::Order, &order.symbol, order)
+            .await
+    }
+
+    /// Send changed account balances on the IPC socket if a consumer is
+    /// connected
+    pub async fn publish_account(&self, position: &AccountPosition) -> Result<()> {
+        self.send_message(MessageType::Account, "", position).await
+    }
+
+    /// Send a change of the feed's state on the IPC socket if a consumer is
+    /// connected
+    pub async fn publish_status(&self, status: &StatusEvent) -> Result<()> {
+        self.send_message(MessageType::Status, status.symbol(), status)
+            .await
+    }
+
+    /// Send a book's heartbeat on the IPC socket if a consumer is connected
+    pub async fn publish_heartbeat(&self, heartbeat: &BookHeartbeat) -> Result<()> {
+        self.send_message(MessageType::Heartbeat, &heartbeat.symbol, heartbeat)
+            .await
+    }
+
+    /// Write a message other than a book state straight to the socket,
+    /// skipping the send queues; dropped when no consumer is connected
+    async fn send_message<T: serde::Serialize>(
+        &self,
+        message_type: MessageType,
+        symbol: &str,
+        value: &T,
+    ) -> Result<()> {
+        let payload = serialize(value, self.wire_format)?;
+
+        // Numbered under the stream lock, as states are, so frames reach
+        // the socket in sequence order
         let mut guard = self.stream.lock().await;
+        let message = self.frame(message_type, symbol, &payload)?;
+        let Some(stream) = guard.as_mut() else {
+            reconnect::dropped("message");
+            return Ok(());
+        };
+        if let Err(e) = stream.write_all(&message).await {
+            *guard = None; // Mark as disconnected
+            self.set_connected(false);
+            return Err(e.into());
+        }
+        Ok(())
+    }
 
-        // Check if we need to reconnect
-        if guard.is_none() {
+    /// Flush pending states and close the IPC socket
+    ///
+    /// Conflated and queued states are written out, the consumer is sent
+    /// a `ControlMessage::Shutdown` and the socket is shut down. Anything
+    /// published afterwards skips the socket.
+    pub async fn shutdown(&self) {
+        if let Some(conflator) = &self.conflator {
+            let pending = conflator.lock().unwrap().drain_all();
+            for state in &pending {
+                if let Err(e) = self.send(state).await {
+                    warn!(error = %e, "Failed to flush conflated state");
+                }
+            }
+        }
+        for queue in self.queues.values().chain([&self.fallback_queue]) {
+            while let Some(state) = queue.try_pop() {
+                if let Err(e) = self.write(&state).await {
+                    warn!(error = %e, symbol = %state.symbol, "Failed to write to IPC socket");
+                }
+            }
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            if let Err(e) = kafka.flush(Duration::from_secs(5)) {
+                warn!(error = %e, "Failed to flush Kafka producer");
+            }
+        }
+
+        self.closed.store(true, Ordering::Release);
+        let mut guard = self.stream.lock().await;
+        self.set_connected(false);
+        let Some(mut stream) = guard.take() else {
             drop(guard);
-            if let Err(e) = self.connect().await {
-                debug!(error = %e, "Failed to reconnect to IPC socket");
-                return Ok(()); // Don't fail on publish errors
+            self.close_journal().await;
+            return;
+        };
+        let notice = ControlMessage::Shutdown {
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        };
+        let message = serialize(&notice, self.wire_format)
+            .and_then(|payload| self.frame(MessageType::Control, "", &payload));
+        match message {
+            Ok(message) => {
+                if let Err(e) = stream.write_all(&message).await {
+                    warn!(error = %e, "Failed to send shutdown notice");
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to encode shutdown notice"),
+        }
+        if let Err(e) = stream.shutdown().await {
+            debug!(error = %e, "Failed to shut down IPC socket");
+        }
+        info!(path = %self.socket_path, "IPC socket closed");
+        drop(guard);
+        self.close_journal().await;
+    }
+
+    /// Finish writing the journal's queued frames
+    async fn close_journal(&self) {
+        let Some(writer) = self
+            .journal
+            .as_ref()
+            .and_then(|journal| journal.lock().unwrap().close())
+        else {
+            return;
+        };
+        if !matches!(
+            tokio::task::spawn_blocking(move || writer.join()).await,
+            Ok(Ok(()))
+        ) {
+            warn!("Journal writer failed while closing");
+        }
+    }
+
+    /// In-process feed of published states and trades
+    pub fn live(&self) -> &LiveFeed {
+        &self.live
+    }
+
+    /// Publish a trade to backends that carry trades
+    ///
+    /// The IPC socket and shm/multicast feeds carry book states only. A
+    /// failing backend is logged and counted against the degradation
+    /// budget without keeping the trade from the others.
+    pub async fn publish_trade(&self, trade: &Trade) -> Result<()> {
+        if !self.degradation.aux_sinks_enabled() {
+            return Ok(());
+        }
+        self.live.publish_trade(trade);
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            if let Err(e) = kafka.publish_trade(trade) {
+                warn!(error = %e, symbol = %trade.symbol, "Failed to publish trade to Kafka");
+                self.degradation.record_failure();
+            }
+        }
+        #[cfg(feature = "nats")]
+        if let Some(nats) = &self.nats {
+            if let Err(e) = nats.publish_trade(trade).await {
+                warn!(error = %e, symbol = %trade.symbol, "Failed to publish trade to NATS");
+                self.degradation.record_failure();
+            }
+        }
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            if let Err(e) = redis.publish_trade(trade).await {
+                warn!(error = %e, symbol = %trade.symbol, "Failed to publish trade to Redis");
+                self.degradation.record_failure();
+            }
+        }
+        if let Some(clickhouse) = &self.clickhouse {
+            if let Err(e) = clickhouse.publish_trade(trade) {
+                warn!(error = %e, symbol = %trade.symbol, "Failed to publish trade to ClickHouse");
+                self.degradation.record_failure();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a state to the shm ring, multicast group and auxiliary
+    /// outputs, and queue it for the socket
+    async fn send(&self, state: &OrderBookState) -> Result<()> {
+        // The ring and multicast feed always carry full states so readers
+        // can join anytime
+        if let Some(shm) = &self.shm {
+            if let Err(e) = shm.lock().unwrap().write_state(state) {
+                warn!(error = %e, symbol = %state.symbol, "Failed to write to shm ring");
             }
-            guard = self.stream.lock().await;
+        }
+        if let Some(multicast) = &self.multicast {
+            if let Err(e) = multicast.send_state(state) {
+                warn!(error = %e, symbol = %state.symbol, "Failed to send multicast");
+            }
+        }
+        if self.degradation.aux_sinks_enabled() {
+            self.send_aux(state).await;
+        }
+
+        self.queues
+            .get(&state.symbol)
+            .unwrap_or(&self.fallback_queue)
+            .push(state.clone())
+            .await;
+        Ok(())
+    }
+
+    /// Write a queued state to the socket, or keep it for the consumer's
+    /// return while disconnected
+    async fn write(&self, state: &OrderBookState) -> Result<()> {
+        let mut guard = self.stream.lock().await;
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        if guard.is_none() {
+            // Still number the state so a returning consumer can gap-fill it
+            if self.journal.is_some() {
+                self.encode(state)?;
+            } else if let Some(replay) = &self.replay {
+                replay.lock().unwrap().record(state);
+            } else if let Some(backlog) = &self.backlog {
+                backlog.lock().unwrap().push(state.clone());
+            } else {
+                reconnect::dropped("state");
+            }
+            return Ok(()); // Don't fail on publish errors
         }
 
+        // Encode only once connected so deltas are never built against a
+        // previous consumer's view of the book, and sequenced states are
+        // never both bootstrapped and sent live
+        let message = self.encode(state)?;
+
         if let Some(stream) = guard.as_mut() {
             match stream.write_all(&message).await {
                 Ok(_) => {
@@ -93,10 +911,146 @@ impl Publisher {
                 Err(e) => {
                     warn!(error = %e, "Failed to write to IPC socket");
                     *guard = None; // Mark as disconnected
+                    self.set_connected(false);
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Hand a state to the live feed and external backends
+    async fn send_aux(&self, state: &OrderBookState) {
+        self.live.publish_state(state);
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            if let Err(e) = kafka.publish_state(state) {
+                warn!(error = %e, symbol = %state.symbol, "Failed to publish to Kafka");
+                self.degradation.record_failure();
+            }
+        }
+        #[cfg(feature = "nats")]
+        if let Some(nats) = &self.nats {
+            if let Err(e) = nats.publish_state(state).await {
+                warn!(error = %e, symbol = %state.symbol, "Failed to publish to NATS");
+                self.degradation.record_failure();
+            }
+        }
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            if let Err(e) = redis.publish_state(state).await {
+                warn!(error = %e, symbol = %state.symbol, "Failed to publish to Redis");
+                self.degradation.record_failure();
+            }
+        }
+        if let Some(clickhouse) = &self.clickhouse {
+            if let Err(e) = clickhouse.publish_state(state) {
+                warn!(error = %e, symbol = %state.symbol, "Failed to publish to ClickHouse");
+                self.degradation.record_failure();
+            }
+        }
+    }
+
+    /// Serialize a state into an enveloped, length-prefixed frame
+    fn encode(&self, state: &OrderBookState) -> Result<Vec<u8>> {
+        let (message_type, payload) = self.encode_payload(state)?;
+        self.frame(message_type, &state.symbol, &payload)
+    }
+
+    fn encode_payload(&self, state: &OrderBookState) -> Result<(MessageType, Vec<u8>)> {
+        if let Some(delta) = &self.delta {
+            let msg = delta.lock().unwrap().encode(state, self.clock.now());
+            return Ok((MessageType::Delta, serialize(&msg, self.wire_format)?));
+        }
+        if let Some(replay) = &self.replay {
+            let seq = replay.lock().unwrap().record(state);
+            let frame = BootstrapFrame::Book {
+                seq,
+                replayed: false,
+                state: Box::new(state.clone()),
+            };
+            return Ok((MessageType::Bootstrap, serialize(&frame, self.wire_format)?));
+        }
+        #[cfg(feature = "protobuf")]
+        if self.wire_format == WireFormat::Protobuf {
+            use prost::Message;
+            let payload = crate::proto::OrderBook::from(state).encode_to_vec();
+            return Ok((MessageType::Book, payload));
+        }
+        if self.wire_format == WireFormat::FlatBuffers {
+            return Ok((MessageType::Book, flatbuf::encode(state)));
+        }
+        Ok((MessageType::Book, serialize(state, self.wire_format)?))
+    }
+
+    /// Compress a payload and wrap it in the next envelope
+    fn frame(&self, message_type: MessageType, symbol: &str, payload: &[u8]) -> Result<Vec<u8>> {
+        let compressed = match self.compression {
+            Compression::None => None,
+            codec => Some(compression::compress(codec, payload)?),
+        };
+        let envelope = |seq| {
+            Envelope {
+                seq,
+                sent_at_us: chrono::Utc::now().timestamp_micros() as u64,
+                message_type,
+                compression: self.compression,
+                symbol: symbol.to_string(),
+            }
+            .frame(compressed.as_deref().unwrap_or(payload))
+        };
+        let Some(journal) = &self.journal else {
+            return Ok(envelope(self.seq.fetch_add(1, Ordering::Relaxed) + 1));
+        };
+
+        // Numbered under the journal's lock so frames are queued in order
+        let journal = journal.lock().unwrap();
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+        let frame = envelope(seq);
+        journal.append(seq, frame.clone());
+        Ok(frame)
+    }
+}
+
+/// Serialize a value as a frame payload
+///
+/// Protobuf and FlatBuffers are handled by the caller for the messages
+/// their schemas cover; anything else falls back to MessagePack.
+fn serialize<T: serde::Serialize>(value: &T, format: WireFormat) -> Result<Vec<u8>> {
+    match format {
+        WireFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        // Serialize using MessagePack for efficiency
+        WireFormat::MessagePack | WireFormat::Protobuf | WireFormat::FlatBuffers => {
+            rmp_serde::to_vec(value).map_err(|e| e.to_string())
+        }
+    }
+    .map_err(|e| MarketDataError::SerializationError(format!("Failed to serialize: {}", e)))
+}
+
+/// Decode a frame payload in a serde wire format
+pub(crate) fn decode_frame<T: serde::de::DeserializeOwned>(
+    data: &[u8],
+    format: WireFormat,
+) -> Result<T> {
+    match format {
+        WireFormat::Json => Ok(serde_json::from_slice(data)?),
+        WireFormat::MessagePack | WireFormat::Protobuf | WireFormat::FlatBuffers => {
+            rmp_serde::from_slice(data).map_err(|e| MarketDataError::ParseError(e.to_string()))
+        }
+    }
+}
+
+/// Read one length-prefixed frame
+async fn read_frame(stream: &mut UnixStream) -> Result<Vec<u8>> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_BOOTSTRAP_REQUEST {
+        return Err(MarketDataError::IpcError(format!(
+            "Bootstrap request of {} bytes is too large",
+            len
+        )));
+    }
+
+    let mut data = vec![0; len];
+    stream.read_exact(&mut data).await?;
+    Ok(data)
 }
@@ -0,0 +1,241 @@
+//! UDP multicast transport
+//!
+//! Publishes MessagePack-encoded book states to a multicast group so any
+//! number of hosts on the LAN can consume the feed without per-subscriber
+//! fan-out. Messages larger than one datagram are fragmented to fit the
+//! configured MTU.
+//!
+//! Datagram layout (big-endian):
+//!
+//! ```text
+//! 0  u64  message sequence number (per publisher, starts at 1)
+//! 8  u16  fragment index
+//! 10 u16  fragment count
+//! 12 u32  total message length
+//! 16 ..   fragment bytes
+//! ```
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{MarketDataError, Result};
+use crate::orderbook::OrderBookState;
+
+/// Fragment header size
+pub const HEADER_SIZE: usize = 16;
+/// IPv4 + UDP header overhead
+const IP_UDP_OVERHEAD: usize = 28;
+
+/// Multicast publisher
+pub struct MulticastSender {
+    socket: UdpSocket,
+    group: SocketAddr,
+    fragment_size: usize,
+    next_seq: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl MulticastSender {
+    /// Create a sender for `group` (e.g. "239.1.1.1:5000")
+    pub fn new(group: &str, mtu: usize, ttl: u32, interface: Option<Ipv4Addr>) -> Result<Self> {
+        let group: SocketAddrV4 = group.parse().map_err(|e| {
+            MarketDataError::ConfigError(format!("Invalid multicast group {}: {}", group, e))
+        })?;
+        if !group.ip().is_multicast() {
+            return Err(MarketDataError::ConfigError(format!(
+                "{} is not a multicast address",
+                group.ip()
+            )));
+        }
+
+        let fragment_size = mtu
+            .checked_sub(IP_UDP_OVERHEAD + HEADER_SIZE)
+            .filter(|size| *size > 0)
+            .ok_or_else(|| MarketDataError::ConfigError(format!("MTU {} too small", mtu)))?;
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_ttl_v4(ttl)?;
+        socket.set_multicast_loop_v4(true)?;
+        if let Some(interface) = interface {
+            socket.set_multicast_if_v4(&interface)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::from(([0, 0, 0, 0], 0)).into())?;
+
+        Ok(Self {
+            socket: socket.into(),
+            group: group.into(),
+            fragment_size,
+            next_seq: AtomicU64::new(1),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Size the socket's send buffer, for bursts that would otherwise drop
+    /// datagrams
+    pub fn with_send_buffer(self, size: usize) -> Result<Self> {
+        crate::socket::set_buffers(SockRef::from(&self.socket), Some(size), None)?;
+        Ok(self)
+    }
+
+    /// Datagrams dropped because the socket buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Send one message, fragmenting as needed; returns its sequence number
+    pub fn send(&self, payload: &[u8]) -> Result<u64> {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+
+        for datagram in fragment(seq, payload, self.fragment_size)? {
+            match self.socket.send_to(&datagram, self.group) {
+                Ok(_) => {}
+                // Never block the hot path on a full send buffer
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => return Err(MarketDataError::IpcError(e.to_string())),
+            }
+        }
+
+        Ok(seq)
+    }
+
+    /// Serialize and send a book state
+    pub fn send_state(&self, state: &OrderBookState) -> Result<u64> {
+        let data = rmp_serde::to_vec(state).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
+        })?;
+        self.send(&data)
+    }
+}
+
+/// Split a message into datagrams of at most `fragment_size` payload bytes
+pub fn fragment(seq: u64, payload: &[u8], fragment_size: usize) -> Result<Vec<Vec<u8>>> {
+    let count = payload.len().div_ceil(fragment_size).max(1);
+    let count = u16::try_from(count).map_err(|_| {
+        MarketDataError::IpcError(format!(
+            "Message of {} bytes needs too many fragments",
+            payload.len()
+        ))
+    })?;
+
+    let mut chunks: Vec<&[u8]> = payload.chunks(fragment_size).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut datagram = Vec::with_capacity(HEADER_SIZE + chunk.len());
+            datagram.extend_from_slice(&seq.to_be_bytes());
+            datagram.extend_from_slice(&(index as u16).to_be_bytes());
+            datagram.extend_from_slice(&count.to_be_bytes());
+            datagram.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect())
+}
+
+/// Receiver-side reassembly of fragmented messages
+///
+/// Tracks one in-flight message at a time: fragments of a newer sequence
+/// number abandon an incomplete older message.
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    current_seq: u64,
+    received: Vec<Option<Vec<u8>>>,
+    last_complete: Option<u64>,
+    gaps: u64,
+}
+
+impl Reassembler {
+    /// Create an empty reassembler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages detected as missing from the sequence
+    pub fn gaps(&self) -> u64 {
+        self.gaps
+    }
+
+    /// Feed a datagram; returns a complete message with its sequence number
+    pub fn push(&mut self, datagram: &[u8]) -> Option<(u64, Vec<u8>)> {
+        if datagram.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let seq = u64::from_be_bytes(datagram[0..8].try_into().ok()?);
+        let index = u16::from_be_bytes(datagram[8..10].try_into().ok()?);
+        let count = u16::from_be_bytes(datagram[10..12].try_into().ok()?);
+        let total = u32::from_be_bytes(datagram[12..16].try_into().ok()?) as usize;
+
+        if index >= count || self.last_complete.is_some_and(|last| seq <= last) {
+            return None;
+        }
+
+        if seq != self.current_seq || self.received.len() != count as usize {
+            self.current_seq = seq;
+            self.received = vec![None; count as usize];
+        }
+
+        self.received[index as usize] = Some(datagram[HEADER_SIZE..].to_vec());
+        if self.received.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let message: Vec<u8> = self.received.drain(..).flatten().flatten().collect();
+        if message.len() != total {
+            return None;
+        }
+
+        if let Some(last) = self.last_complete {
+            self.gaps += seq - last - 1;
+        }
+        self.last_complete = Some(seq);
+
+        Some((seq, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_and_reassemble() {
+        let payload: Vec<u8> = (0..250u16).map(|i| i as u8).collect();
+        let datagrams = fragment(7, &payload, 100).unwrap();
+        assert_eq!(datagrams.len(), 3);
+        assert!(datagrams.iter().all(|d| d.len() <= HEADER_SIZE + 100));
+
+        let mut reassembler = Reassembler::new();
+        // Out-of-order fragments still reassemble
+        assert!(reassembler.push(&datagrams[2]).is_none());
+        assert!(reassembler.push(&datagrams[0]).is_none());
+        let (seq, message) = reassembler.push(&datagrams[1]).unwrap();
+        assert_eq!(seq, 7);
+        assert_eq!(message, payload);
+    }
+
+    #[test]
+    fn test_gap_detection() {
+        let mut reassembler = Reassembler::new();
+        for seq in [1, 2, 5] {
+            let datagram = &fragment(seq, b"x", 100).unwrap()[0];
+            assert!(reassembler.push(datagram).is_some());
+        }
+        assert_eq!(reassembler.gaps(), 2);
+
+        // Duplicates and stale messages are ignored
+        assert!(reassembler
+            .push(&fragment(5, b"x", 100).unwrap()[0])
+            .is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! NATS / JetStream publisher backend
+//!
+//! Publishes MessagePack-encoded book states and trades to per-symbol
+//! subjects (`md.book.BTCUSDT`, `md.trade.BTCUSDT`) for services in a
+//! distributed stack. With JetStream enabled, a stream capturing all
+//! subjects under the prefix is created so consumers can replay history.
+//! Enabled with the `nats` feature.
+
+use async_nats::jetstream::{self, stream};
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+
+/// Where messages go once connected
+enum Target {
+    Core(async_nats::Client),
+    JetStream(jetstream::Context),
+}
+
+/// NATS publisher
+pub struct NatsSink {
+    target: Target,
+    prefix: String,
+}
+
+impl NatsSink {
+    /// Connect to `url`; with `jetstream_stream` set, messages are persisted
+    /// in that stream (created if missing)
+    pub async fn connect(url: &str, prefix: &str, jetstream_stream: Option<&str>) -> Result<Self> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| MarketDataError::IpcError(format!("NATS connect to {}: {}", url, e)))?;
+
+        let target = match jetstream_stream {
+            Some(name) => {
+                let context = jetstream::new(client);
+                context
+                    .get_or_create_stream(stream::Config {
+                        name: name.to_string(),
+                        subjects: vec![format!("{}.>", prefix)],
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| {
+                        MarketDataError::IpcError(format!("JetStream stream {}: {}", name, e))
+                    })?;
+                info!(stream = %name, "JetStream persistence enabled");
+                Target::JetStream(context)
+            }
+            None => Target::Core(client),
+        };
+
+        Ok(Self {
+            target,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    /// Publish a book state to `<prefix>.book.<SYMBOL>`
+    pub async fn publish_state(&self, state: &OrderBookState) -> Result<()> {
+        let subject = format!("{}.book.{}", self.prefix, state.symbol);
+        self.publish(subject, state).await
+    }
+
+    /// Publish a trade to `<prefix>.trade.<SYMBOL>`
+    pub async fn publish_trade(&self, trade: &Trade) -> Result<()> {
+        let subject = format!("{}.trade.{}", self.prefix, trade.symbol);
+        self.publish(subject, trade).await
+    }
+
+    async fn publish<T: Serialize>(&self, subject: String, value: &T) -> Result<()> {
+        let payload = rmp_serde::to_vec(value).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
+        })?;
+
+        match &self.target {
+            Target::Core(client) => client
+                .publish(subject, payload.into())
+                .await
+                .map_err(|e| MarketDataError::IpcError(e.to_string())),
+            // Don't wait for the server ack on the hot path; a lost message
+            // shows up as a gap in the stream sequence
+            Target::JetStream(context) => context
+                .publish(subject, payload.into())
+                .await
+                .map(drop)
+                .map_err(|e| MarketDataError::IpcError(e.to_string())),
+        }
+    }
+}
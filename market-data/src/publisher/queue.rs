@@ -0,0 +1,215 @@
+//! Bounded send queues in front of the IPC socket
+//!
+//! `publish` hands states to their symbol's queue and returns; one writer
+//! task per queue drains it onto the socket, so a slow consumer no longer
+//! stalls the caller while the stream lock is held, and a burst on one
+//! symbol neither evicts nor delays the others' states beyond their turn
+//! on the (fair) stream lock. When the queue is full the overflow policy
+//! decides what happens: drop the oldest queued state, drop the new one, or
+//! wait for space. Dropped states are counted in the
+//! `ipc_send_queue_dropped_total` metric (per symbol); they are never sequenced or
+//! encoded, so consumers simply see the newer states, as with conflation.
+
+use prometheus::{IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::Notify;
+
+use crate::config::OverflowPolicy;
+use crate::orderbook::OrderBookState;
+
+static DROPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "ipc_send_queue_dropped_total",
+            "Book states dropped by the IPC send queue overflow policy",
+        ),
+        &["queue"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+static DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "ipc_send_queue_depth",
+            "Book states waiting in the IPC send queue",
+        ),
+        &["queue"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+/// Bounded queue of states awaiting the socket writer
+#[derive(Debug)]
+pub struct SendQueue {
+    states: Mutex<VecDeque<OrderBookState>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Signalled when a state is queued
+    ready: Notify,
+    /// Signalled when a state is taken
+    space: Notify,
+    dropped: AtomicU64,
+    dropped_metric: IntCounter,
+    depth_metric: IntGauge,
+}
+
+impl SendQueue {
+    /// Create a queue holding up to `capacity` states, labelled `name`
+    /// in metrics
+    pub fn new(name: &str, capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            states: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+            capacity: capacity.max(1),
+            policy,
+            ready: Notify::new(),
+            space: Notify::new(),
+            dropped: AtomicU64::new(0),
+            dropped_metric: DROPPED.with_label_values(&[name]),
+            depth_metric: DEPTH.with_label_values(&[name]),
+        }
+    }
+
+    /// Queue a state, applying the overflow policy when full
+    pub async fn push(&self, state: OrderBookState) {
+        loop {
+            {
+                let mut states = self.states.lock().unwrap();
+                if states.len() < self.capacity {
+                    states.push_back(state);
+                    self.depth_metric.set(states.len() as i64);
+                    drop(states);
+                    self.ready.notify_one();
+                    return;
+                }
+                match self.policy {
+                    OverflowPolicy::DropNewest => {
+                        self.record_drop();
+                        return;
+                    }
+                    OverflowPolicy::DropOldest => {
+                        states.pop_front();
+                        states.push_back(state);
+                        self.record_drop();
+                        return;
+                    }
+                    OverflowPolicy::Block => {}
+                }
+            }
+            self.space.notified().await;
+        }
+    }
+
+    /// Take the oldest state, waiting until one is queued
+    pub async fn pop(&self) -> OrderBookState {
+        loop {
+            let state = {
+                let mut states = self.states.lock().unwrap();
+                let state = states.pop_front();
+                self.depth_metric.set(states.len() as i64);
+                state
+            };
+            if let Some(state) = state {
+                self.space.notify_one();
+                return state;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Take the oldest state if one is queued
+    pub fn try_pop(&self) -> Option<OrderBookState> {
+        let state = {
+            let mut states = self.states.lock().unwrap();
+            let state = states.pop_front();
+            self.depth_metric.set(states.len() as i64);
+            state
+        };
+        if state.is_some() {
+            self.space.notify_one();
+        }
+        state
+    }
+
+    /// States dropped since creation
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of queued states
+    pub fn len(&self) -> usize {
+        self.states.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn record_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        self.dropped_metric.inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBookMetrics;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn state(last_update_id: u64) -> OrderBookState {
+        OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 0,
+            last_update_id,
+            bids: vec![],
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overflow_policies() {
+        let queue = SendQueue::new("test", 2, OverflowPolicy::DropOldest);
+        for id in 1..=3 {
+            queue.push(state(id)).await;
+        }
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop().await.last_update_id, 2);
+
+        let queue = SendQueue::new("test", 2, OverflowPolicy::DropNewest);
+        for id in 1..=3 {
+            queue.push(state(id)).await;
+        }
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.pop().await.last_update_id, 1);
+        assert_eq!(queue.try_pop().unwrap().last_update_id, 2);
+        assert!(queue.try_pop().is_none());
+        assert!(queue.is_empty());
+
+        // A blocked push completes once the writer takes a state
+        let queue = Arc::new(SendQueue::new("test", 1, OverflowPolicy::Block));
+        queue.push(state(1)).await;
+        let pusher = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(state(2)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!pusher.is_finished());
+        assert_eq!(queue.pop().await.last_update_id, 1);
+        pusher.await.unwrap();
+        assert_eq!(queue.pop().await.last_update_id, 2);
+        assert_eq!(queue.dropped(), 0);
+    }
+}
@@ -0,0 +1,155 @@
+//! Reconnecting the IPC socket
+//!
+//! A background task reconnects to the consumer as soon as the socket
+//! drops, backing off exponentially from `IPC_RECONNECT_DELAY_MS` up to
+//! `IPC_RECONNECT_MAX_DELAY_MS` while it stays away; `ipc_connected` shows
+//! the link state. Whatever is published meanwhile is counted in
+//! `ipc_disconnected_dropped_total`, except the book states kept for the
+//! consumer's return: with `IPC_BOOTSTRAP` they are gap-filled from the
+//! replay buffer, otherwise the last `IPC_BACKLOG_CAPACITY` of them are
+//! held in a [`Backlog`] and written first on reconnect.
+
+use prometheus::{IntCounterVec, IntGauge, Opts};
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::orderbook::OrderBookState;
+
+static CONNECTED: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::with_opts(Opts::new(
+        "ipc_connected",
+        "Whether an IPC consumer is connected (1) or not (0)",
+    ))
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static DROPPED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "ipc_disconnected_dropped_total",
+            "Messages dropped because no IPC consumer was connected, by kind (state or message)",
+        ),
+        &["kind"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Record the IPC link state
+pub fn set_connected(connected: bool) {
+    CONNECTED.set(connected as i64);
+}
+
+/// Count a `kind` of message dropped while disconnected
+pub fn dropped(kind: &str) {
+    DROPPED.with_label_values(&[kind]).inc();
+}
+
+/// Exponential reconnect delays
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max: max.max(base),
+            attempt: 0,
+        }
+    }
+
+    /// Delay before the next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.base.saturating_mul(1 << self.attempt.min(16));
+        self.attempt += 1;
+        delay.min(self.max)
+    }
+
+    /// Start over after a successful attempt
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Book states held for the consumer's return, oldest dropped first
+#[derive(Debug)]
+pub struct Backlog {
+    states: VecDeque<OrderBookState>,
+    capacity: usize,
+}
+
+impl Backlog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            states: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Hold a state, dropping the oldest one when full
+    pub fn push(&mut self, state: OrderBookState) {
+        if self.states.len() >= self.capacity {
+            self.states.pop_front();
+            dropped("state");
+        }
+        self.states.push_back(state);
+    }
+
+    /// Take the held states, oldest first
+    pub fn take(&mut self) -> VecDeque<OrderBookState> {
+        std::mem::take(&mut self.states)
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::OrderBookMetrics;
+
+    #[test]
+    fn test_backoff_and_backlog_bounds() {
+        let ms = Duration::from_millis;
+        let mut backoff = Backoff::new(ms(100), ms(500));
+        let delays: Vec<_> = (0..5).map(|_| backoff.next_delay()).collect();
+        assert_eq!(delays, [ms(100), ms(200), ms(400), ms(500), ms(500)]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), ms(100));
+
+        let state = |last_update_id| OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 0,
+            last_update_id,
+            bids: vec![],
+            asks: vec![],
+            metrics: OrderBookMetrics::default(),
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        };
+        let mut backlog = Backlog::new(2);
+        for id in 1..=3 {
+            backlog.push(state(id));
+        }
+        assert_eq!(backlog.len(), 2);
+        let held: Vec<_> = backlog.take().iter().map(|s| s.last_update_id).collect();
+        assert_eq!(held, [2, 3]);
+        assert!(backlog.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+//! Redis pub/sub and streams backend
+//!
+//! Publishes MessagePack-encoded book states and trades with PUBLISH for
+//! live fan-out, XADD to capped Redis Streams for replayable consumption,
+//! or both in one pipelined round-trip. Channels and stream keys share the
+//! same name, `<prefix>:book:<SYMBOL>` / `<prefix>:trade:<SYMBOL>`, and
+//! stream entries carry the payload in a `data` field. Enabled with the
+//! `redis` feature.
+
+use redis::aio::ConnectionManager;
+use redis::streams::StreamMaxlen;
+use serde::Serialize;
+
+use crate::config::{Config, RedisMode};
+use crate::error::{MarketDataError, Result};
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+
+/// Redis publisher
+pub struct RedisSink {
+    conn: ConnectionManager,
+    mode: RedisMode,
+    prefix: String,
+    stream_maxlen: usize,
+}
+
+impl RedisSink {
+    /// Connect to `url`; the connection manager reconnects transparently
+    pub async fn connect(url: &str, config: &Config) -> Result<Self> {
+        let client = redis::Client::open(url)
+            .map_err(|e| MarketDataError::ConfigError(format!("Invalid Redis URL: {}", e)))?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| MarketDataError::IpcError(format!("Redis connect to {}: {}", url, e)))?;
+
+        Ok(Self {
+            conn,
+            mode: config.redis_mode,
+            prefix: config.redis_key_prefix.clone(),
+            stream_maxlen: config.redis_stream_maxlen,
+        })
+    }
+
+    /// Publish a book state under `<prefix>:book:<SYMBOL>`
+    pub async fn publish_state(&self, state: &OrderBookState) -> Result<()> {
+        let key = format!("{}:book:{}", self.prefix, state.symbol);
+        self.send(&key, state).await
+    }
+
+    /// Publish a trade under `<prefix>:trade:<SYMBOL>`
+    pub async fn publish_trade(&self, trade: &Trade) -> Result<()> {
+        let key = format!("{}:trade:{}", self.prefix, trade.symbol);
+        self.send(&key, trade).await
+    }
+
+    async fn send<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let payload = rmp_serde::to_vec(value).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
+        })?;
+
+        let mut pipe = redis::pipe();
+        if self.mode.publishes() {
+            pipe.publish(key, &payload).ignore();
+        }
+        if self.mode.streams() {
+            // Approximate trimming lets Redis drop whole macro nodes cheaply
+            pipe.xadd_maxlen(
+                key,
+                StreamMaxlen::Approx(self.stream_maxlen),
+                "*",
+                &[("data", &payload)],
+            )
+            .ignore();
+        }
+
+        let mut conn = self.conn.clone();
+        pipe.query_async::<()>(&mut conn)
+            .await
+            .map_err(|e| MarketDataError::IpcError(format!("Redis publish: {}", e)))
+    }
+}
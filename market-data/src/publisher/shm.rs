@@ -0,0 +1,347 @@
+//! Shared-memory ring buffer transport
+//!
+//! A single writer publishes MessagePack-encoded book states into a
+//! memory-mapped file (typically under `/dev/shm`) that co-located readers
+//! poll without syscalls. Each slot is guarded by a seqlock so readers can
+//! detect torn reads and being lapped by the writer.
+//!
+//! Layout (all integers little-endian, native atomics):
+//!
+//! ```text
+//! header (64 bytes):
+//!   0  u64  magic "ORPFSHM1"
+//!   8  u32  version
+//!   12 u32  slot size in bytes
+//!   16 u32  slot count
+//!   24 u64  messages written (atomic)
+//! slot (slot size bytes, 64-byte aligned):
+//!   0  u64  seqlock: 2n+1 while writing message n, 2n+2 once complete
+//!   8  u32  payload length
+//!   16 ..   payload
+//! ```
+
+use memmap2::{Mmap, MmapMut};
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use crate::error::{MarketDataError, Result};
+use crate::orderbook::OrderBookState;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"ORPFSHM1");
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+const OFF_VERSION: usize = 8;
+const OFF_SLOT_SIZE: usize = 12;
+const OFF_SLOT_COUNT: usize = 16;
+const OFF_WRITE_SEQ: usize = 24;
+const SLOT_LEN: usize = 8;
+const SLOT_HEADER: usize = 16;
+const SLOT_ALIGN: usize = 64;
+
+/// Atomic view of a u64 inside the mapping
+///
+/// # Safety
+/// `ptr` must be 8-byte aligned and valid for the lifetime of the mapping.
+unsafe fn atomic_at<'a>(ptr: *const u8) -> &'a AtomicU64 {
+    &*(ptr as *const AtomicU64)
+}
+
+/// Single-producer writer side of the ring
+pub struct ShmWriter {
+    map: MmapMut,
+    slot_size: usize,
+    slot_count: u64,
+    next_seq: u64,
+}
+
+impl ShmWriter {
+    /// Create (or replace) the ring file at `path`
+    ///
+    /// An existing file is unlinked first so readers still mapping it don't
+    /// fault when the new ring is sized.
+    pub fn create(path: &str, slot_size: usize, slot_count: usize) -> Result<Self> {
+        let slot_size = slot_size.max(SLOT_HEADER + 1).next_multiple_of(SLOT_ALIGN);
+        let slot_count = slot_count.max(1);
+        let len = HEADER_SIZE + slot_size * slot_count;
+
+        let _ = fs::remove_file(path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.set_len(len as u64)?;
+
+        // Safety: the file was just created by us and is only mutated
+        // through this mapping
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[OFF_VERSION..OFF_VERSION + 4].copy_from_slice(&VERSION.to_le_bytes());
+        map[OFF_SLOT_SIZE..OFF_SLOT_SIZE + 4].copy_from_slice(&(slot_size as u32).to_le_bytes());
+        map[OFF_SLOT_COUNT..OFF_SLOT_COUNT + 4].copy_from_slice(&(slot_count as u32).to_le_bytes());
+
+        // Publish the magic last so readers never see a half-written header
+        let base = map.as_ptr();
+        unsafe {
+            atomic_at(base.add(OFF_WRITE_SEQ)).store(0, Ordering::Relaxed);
+            atomic_at(base).store(MAGIC, Ordering::Release);
+        }
+
+        Ok(Self {
+            map,
+            slot_size,
+            slot_count: slot_count as u64,
+            next_seq: 0,
+        })
+    }
+
+    /// Maximum payload size per message
+    pub fn capacity(&self) -> usize {
+        self.slot_size - SLOT_HEADER
+    }
+
+    /// Write one payload into the next slot
+    pub fn write(&mut self, payload: &[u8]) -> Result<u64> {
+        if payload.len() > self.capacity() {
+            return Err(MarketDataError::IpcError(format!(
+                "Payload of {} bytes exceeds shm slot capacity {}",
+                payload.len(),
+                self.capacity()
+            )));
+        }
+
+        let n = self.next_seq;
+        let offset = HEADER_SIZE + (n % self.slot_count) as usize * self.slot_size;
+        let base = self.map.as_mut_ptr();
+
+        // Safety: offsets are within the mapping and 8-byte aligned
+        unsafe {
+            let slot = base.add(offset);
+            let seq = atomic_at(slot);
+            seq.store(2 * n + 1, Ordering::Relaxed);
+            fence(Ordering::Release);
+
+            ptr::write_unaligned(slot.add(SLOT_LEN) as *mut u32, payload.len() as u32);
+            ptr::copy_nonoverlapping(payload.as_ptr(), slot.add(SLOT_HEADER), payload.len());
+
+            seq.store(2 * n + 2, Ordering::Release);
+            atomic_at(base.add(OFF_WRITE_SEQ)).store(n + 1, Ordering::Release);
+        }
+
+        self.next_seq = n + 1;
+        Ok(n)
+    }
+
+    /// Serialize and write a book state
+    pub fn write_state(&mut self, state: &OrderBookState) -> Result<u64> {
+        let data = rmp_serde::to_vec(state).map_err(|e| {
+            MarketDataError::SerializationError(format!("Failed to serialize: {}", e))
+        })?;
+        self.write(&data)
+    }
+}
+
+/// Reader side of the ring, for co-located consumers
+pub struct ShmReader {
+    map: Mmap,
+    slot_size: usize,
+    slot_count: u64,
+    next_seq: u64,
+    dropped: u64,
+}
+
+impl ShmReader {
+    /// Open an existing ring, starting at the newest message
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        // Safety: the mapping is read-only; concurrent writes are
+        // coordinated through the seqlocks
+        let map = unsafe { Mmap::map(&file)? };
+
+        if map.len() < HEADER_SIZE {
+            return Err(MarketDataError::IpcError("shm file too small".to_string()));
+        }
+
+        let base = map.as_ptr();
+        let magic = unsafe { atomic_at(base).load(Ordering::Acquire) };
+        if magic != MAGIC {
+            return Err(MarketDataError::IpcError(
+                "shm file is not an initialized ring".to_string(),
+            ));
+        }
+
+        let read_u32 = |off: usize| u32::from_le_bytes(map[off..off + 4].try_into().unwrap());
+        let version = read_u32(OFF_VERSION);
+        if version != VERSION {
+            return Err(MarketDataError::IpcError(format!(
+                "Unsupported shm ring version {}",
+                version
+            )));
+        }
+
+        let slot_size = read_u32(OFF_SLOT_SIZE) as usize;
+        let slot_count = read_u32(OFF_SLOT_COUNT) as u64;
+        // Slots as the writer lays them out: aligned, with room for a payload
+        if slot_count == 0 || slot_size <= SLOT_HEADER || !slot_size.is_multiple_of(SLOT_ALIGN) {
+            return Err(MarketDataError::IpcError(format!(
+                "shm ring has invalid slots: {} of {} bytes",
+                slot_count, slot_size
+            )));
+        }
+        let len = usize::try_from(slot_count)
+            .ok()
+            .and_then(|count| slot_size.checked_mul(count))
+            .and_then(|slots| slots.checked_add(HEADER_SIZE));
+        if len.is_none_or(|len| map.len() < len) {
+            return Err(MarketDataError::IpcError("shm file truncated".to_string()));
+        }
+
+        let next_seq = unsafe { atomic_at(base.add(OFF_WRITE_SEQ)).load(Ordering::Acquire) };
+
+        Ok(Self {
+            map,
+            slot_size,
+            slot_count,
+            next_seq,
+            dropped: 0,
+        })
+    }
+
+    /// Messages skipped because the writer lapped this reader
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Copy the next payload into `buf`; returns false when caught up
+    pub fn try_read(&mut self, buf: &mut Vec<u8>) -> bool {
+        let base = self.map.as_ptr();
+
+        loop {
+            let n = self.next_seq;
+            let written = unsafe { atomic_at(base.add(OFF_WRITE_SEQ)).load(Ordering::Acquire) };
+            if n >= written {
+                return false;
+            }
+
+            // Too far behind: skip to the oldest slot that can still be valid
+            if written - n > self.slot_count {
+                let oldest = written - self.slot_count;
+                self.dropped += oldest - n;
+                self.next_seq = oldest;
+                continue;
+            }
+
+            let offset = HEADER_SIZE + (n % self.slot_count) as usize * self.slot_size;
+            let expected = 2 * n + 2;
+
+            // Safety: offsets are within the mapping; torn reads are
+            // detected by re-checking the seqlock
+            let consistent = unsafe {
+                let slot = base.add(offset);
+                let seq = atomic_at(slot);
+                let before = seq.load(Ordering::Acquire);
+                if before != expected {
+                    false
+                } else {
+                    let len = ptr::read_unaligned(slot.add(SLOT_LEN) as *const u32) as usize;
+                    let len = len.min(self.slot_size - SLOT_HEADER);
+                    buf.clear();
+                    buf.reserve(len);
+                    ptr::copy_nonoverlapping(slot.add(SLOT_HEADER), buf.as_mut_ptr(), len);
+                    buf.set_len(len);
+                    fence(Ordering::Acquire);
+                    seq.load(Ordering::Relaxed) == before
+                }
+            };
+
+            self.next_seq += 1;
+            if consistent {
+                return true;
+            }
+            // Overwritten before or while we read it
+            self.dropped += 1;
+        }
+    }
+
+    /// Read and decode the next book state, if any
+    pub fn try_recv(&mut self) -> Result<Option<OrderBookState>> {
+        let mut buf = Vec::new();
+        if !self.try_read(&mut buf) {
+            return Ok(None);
+        }
+
+        rmp_serde::from_slice(&buf)
+            .map(Some)
+            .map_err(|e| MarketDataError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_then_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ring");
+        let path = path.to_str().unwrap();
+
+        let mut writer = ShmWriter::create(path, 128, 4).unwrap();
+        let mut reader = ShmReader::open(path).unwrap();
+        let mut buf = Vec::new();
+        assert!(!reader.try_read(&mut buf));
+
+        writer.write(b"one").unwrap();
+        writer.write(b"two").unwrap();
+        assert!(reader.try_read(&mut buf));
+        assert_eq!(buf, b"one");
+        assert!(reader.try_read(&mut buf));
+        assert_eq!(buf, b"two");
+        assert!(!reader.try_read(&mut buf));
+        assert_eq!(reader.dropped(), 0);
+    }
+
+    #[test]
+    fn test_lapped_reader_skips_ahead() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ring");
+        let path = path.to_str().unwrap();
+
+        let mut writer = ShmWriter::create(path, 128, 4).unwrap();
+        let mut reader = ShmReader::open(path).unwrap();
+        for i in 0..10u8 {
+            writer.write(&[i]).unwrap();
+        }
+
+        let mut buf = Vec::new();
+        assert!(reader.try_read(&mut buf));
+        assert_eq!(buf, [6]);
+        assert_eq!(reader.dropped(), 6);
+        assert!(writer.write(&[0u8; 200]).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_header_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ring");
+        let path = path.to_str().unwrap();
+        drop(ShmWriter::create(path, 128, 4).unwrap());
+        let valid = fs::read(path).unwrap();
+
+        let corrupt = |slot_size: u32, slot_count: u32| {
+            let mut file = valid.clone();
+            file[OFF_SLOT_SIZE..OFF_SLOT_SIZE + 4].copy_from_slice(&slot_size.to_le_bytes());
+            file[OFF_SLOT_COUNT..OFF_SLOT_COUNT + 4].copy_from_slice(&slot_count.to_le_bytes());
+            fs::write(path, file).unwrap();
+            ShmReader::open(path).err()
+        };
+        assert!(corrupt(128, 4).is_none());
+        assert!(corrupt(128, 0).is_some());
+        assert!(corrupt(0, 4).is_some());
+        assert!(corrupt(SLOT_HEADER as u32, 4).is_some());
+        assert!(corrupt(100, 4).is_some());
+        assert!(corrupt(128, 5).is_some());
+        assert!(corrupt(u32::MAX - 63, u32::MAX).is_some());
+    }
+}
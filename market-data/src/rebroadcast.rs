@@ -0,0 +1,194 @@
+//! WebSocket re-broadcast endpoint
+//!
+//! `/ws` streams published book states and trades as JSON for dashboards
+//! and browser-based monitoring. A connection picks its initial symbols with
+//! `?symbols=BTCUSDT,ETHUSDT` and changes them at runtime by sending
+//! `{"op": "subscribe" | "unsubscribe", "symbols": [...]}`; every change is
+//! answered with the resulting subscription set.
+//!
+//! Server frames are `{"type": "book" | "trade" | "subscriptions" | "error",
+//! "data": ...}`. Each subscribed book is first sent as its latest state,
+//! on connecting or subscribing, then as it updates. A connection that
+//! falls behind skips the messages it missed.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+use crate::event::Trade;
+use crate::orderbook::OrderBookState;
+use crate::publisher::LiveFeed;
+use crate::AppState;
+
+/// Query parameters for `/ws`
+#[derive(Debug, Deserialize)]
+pub struct WsParams {
+    /// Comma-separated symbols to start with
+    pub symbols: Option<String>,
+}
+
+/// Client → server control message
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe { symbols: Vec<String> },
+    Unsubscribe { symbols: Vec<String> },
+}
+
+/// Server → client frame
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+enum ServerMessage<'a> {
+    Book(&'a OrderBookState),
+    Trade(&'a Trade),
+    Subscriptions(&'a BTreeSet<String>),
+    Error(String),
+}
+
+/// Upgrade to a WebSocket streaming the live feed
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<WsParams>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let symbols = params
+        .symbols
+        .iter()
+        .flat_map(|s| s.split(','))
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let feed = state.publisher.live().clone();
+
+    ws.on_upgrade(move |socket| stream(socket, feed, symbols))
+}
+
+/// Forward subscribed messages until either side goes away
+async fn stream(mut socket: WebSocket, feed: LiveFeed, mut symbols: BTreeSet<String>) {
+    let (snapshot, mut books) = feed.subscribe_books_with_snapshot();
+    let mut trades = feed.subscribe_trades();
+
+    // Start from the current books rather than each one's next update
+    for state in snapshot
+        .iter()
+        .filter(|state| symbols.contains(&state.symbol))
+    {
+        let text = ServerMessage::Book(state).to_json();
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        let outgoing = tokio::select! {
+            book = books.recv() => match book {
+                Ok(state) => symbols
+                    .contains(&state.symbol)
+                    .then(|| ServerMessage::Book(&state).to_json()),
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "WebSocket subscriber lagging, books skipped");
+                    None
+                }
+                Err(RecvError::Closed) => break,
+            },
+            trade = trades.recv() => match trade {
+                Ok(trade) => symbols
+                    .contains(&trade.symbol)
+                    .then(|| ServerMessage::Trade(&trade).to_json()),
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "WebSocket subscriber lagging, trades skipped");
+                    None
+                }
+                Err(RecvError::Closed) => break,
+            },
+            received = socket.recv() => match received {
+                Some(Ok(Message::Text(text))) => {
+                    let before = symbols.clone();
+                    let reply = apply(&mut symbols, &text);
+                    if socket.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                    // Newly subscribed books start from their latest state
+                    for symbol in symbols.difference(&before) {
+                        let Some(state) = feed.latest(symbol) else {
+                            continue;
+                        };
+                        let text = ServerMessage::Book(&state).to_json();
+                        if socket.send(Message::Text(text)).await.is_err() {
+                            return;
+                        }
+                    }
+                    None
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the protocol layer
+                Some(Ok(_)) => None,
+            },
+        };
+
+        if let Some(text) = outgoing {
+            if socket.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Apply a control message and build the reply
+fn apply(symbols: &mut BTreeSet<String>, text: &str) -> String {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe { symbols: added }) => {
+            symbols.extend(added.iter().map(|s| s.to_uppercase()));
+            ServerMessage::Subscriptions(symbols).to_json()
+        }
+        Ok(ClientMessage::Unsubscribe { symbols: removed }) => {
+            for symbol in &removed {
+                symbols.remove(&symbol.to_uppercase());
+            }
+            ServerMessage::Subscriptions(symbols).to_json()
+        }
+        Err(e) => ServerMessage::Error(format!("Invalid message: {}", e)).to_json(),
+    }
+}
+
+impl ServerMessage<'_> {
+    fn to_json(&self) -> String {
+        // Plain data structures; serialization can't fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_control_messages() {
+        let mut symbols = BTreeSet::from(["BTCUSDT".to_string()]);
+
+        let reply = apply(
+            &mut symbols,
+            r#"{"op": "subscribe", "symbols": ["ethusdt", "SOLUSDT"]}"#,
+        );
+        assert_eq!(
+            reply,
+            r#"{"type":"subscriptions","data":["BTCUSDT","ETHUSDT","SOLUSDT"]}"#
+        );
+
+        apply(
+            &mut symbols,
+            r#"{"op": "unsubscribe", "symbols": ["BTCUSDT"]}"#,
+        );
+        assert_eq!(symbols.len(), 2);
+        assert!(!symbols.contains("BTCUSDT"));
+
+        let reply = apply(&mut symbols, r#"{"op": "resubscribe"}"#);
+        assert!(reply.starts_with(r#"{"type":"error""#));
+        assert_eq!(symbols.len(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Recording and replay of the raw market data feed
+//!
+//! A recording is a JSON-lines file: the REST snapshots fetched after
+//! connecting, then every WebSocket text frame as received. Replaying it
+//! re-parses the frames with the current parser and rebuilds the books,
+//! which makes parser or book changes testable against captured sessions.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+use crate::event::{BookSnapshot, MarketEvent};
+use crate::orderbook::OrderBookManager;
+use crate::parser;
+use crate::rest::RestClient;
+use crate::shutdown::Shutdown;
+use crate::websocket::redundant::Feed;
+use crate::websocket::{client, fetch_snapshot, now_micros, SubscriptionProgress, Tls};
+
+/// One line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recorded {
+    /// REST snapshot of one symbol's book
+    Snapshot {
+        received_at_us: u64,
+        snapshot: BookSnapshot,
+    },
+    /// WebSocket text frame
+    Message { received_at_us: u64, raw: String },
+}
+
+/// Counts from recording or replaying a feed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeedStats {
+    pub snapshots: u64,
+    pub messages: u64,
+    /// Depth diffs applied to a book (replay only)
+    pub updates_applied: u64,
+    /// Frames that failed to parse or apply (replay only)
+    pub errors: u64,
+}
+
+/// Record the configured symbols' feed to `path` until `duration` has
+/// elapsed (if given) or shutdown is triggered
+pub async fn record(
+    config: &Config,
+    path: &Path,
+    duration: Option<Duration>,
+    shutdown: Shutdown,
+) -> Result<FeedStats> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut stats = FeedStats::default();
+
+    let tls = Tls::from_config(config)?;
+    let mut ws = client(
+        config,
+        &tls,
+        Arc::new(SubscriptionProgress::default()),
+        Feed::Primary,
+    );
+    ws.connect().await?;
+
+    // Diffs are already arriving, so the snapshots overlap the stream as
+    // they do live
+    let rest = RestClient::from_config(config);
+    for symbol in &config.symbols {
+        let snapshot = fetch_snapshot(&rest, config, symbol).await?;
+        write_line(
+            &mut out,
+            &Recorded::Snapshot {
+                received_at_us: now_micros(),
+                snapshot,
+            },
+        )?;
+        stats.snapshots += 1;
+    }
+
+    let deadline = duration.map(|d| Instant::now() + d);
+    loop {
+        let received = tokio::select! {
+            received = ws.recv() => received?,
+            _ = sleep_until(deadline) => break,
+            _ = shutdown.wait() => break,
+        };
+        if let Some(raw) = received {
+            write_line(
+                &mut out,
+                &Recorded::Message {
+                    received_at_us: ws.received_at_us(),
+                    raw,
+                },
+            )?;
+            stats.messages += 1;
+        }
+    }
+
+    ws.close().await;
+    out.flush()?;
+    info!(
+        path = %path.display(),
+        snapshots = stats.snapshots,
+        messages = stats.messages,
+        "Recording finished"
+    );
+    Ok(stats)
+}
+
+/// Replay a recording into `manager`
+pub fn replay(path: &Path, manager: &mut OrderBookManager) -> Result<FeedStats> {
+    let mut stats = FeedStats::default();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: Recorded = serde_json::from_str(&line).map_err(|e| {
+            MarketDataError::ParseError(format!("Line {} of recording: {}", index + 1, e))
+        })?;
+        match recorded {
+            Recorded::Snapshot { snapshot, .. } => {
+                manager.init_book(&snapshot);
+                stats.snapshots += 1;
+            }
+            Recorded::Message { raw, .. } => {
+                stats.messages += 1;
+                let event = match parser::parse_event(&raw) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        warn!(error = %e, line = index + 1, "Unparseable frame in recording");
+                        stats.errors += 1;
+                        continue;
+                    }
+                };
+                if let Some(MarketEvent::DepthDelta(update)) = event {
+                    match manager.apply_update(&update) {
+                        Ok(true) => stats.updates_applied += 1,
+                        Ok(false) => {}
+                        Err(e) => {
+                            warn!(error = %e, line = index + 1, "Failed to apply recorded diff");
+                            stats.errors += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(stats)
+}
+
+fn write_line(out: &mut impl Write, recorded: &Recorded) -> Result<()> {
+    serde_json::to_writer(&mut *out, recorded)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Sleep until the deadline, or forever when there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PriceLevel, Venue};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_replay_rebuilds_books() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("feed.jsonl");
+        let level = |price, quantity| PriceLevel { price, quantity };
+        let lines = [
+            Recorded::Snapshot {
+                received_at_us: 1,
+                snapshot: BookSnapshot {
+                    venue: Venue::Binance,
+                    symbol: "BTCUSDT".to_string(),
+                    last_update_id: 100,
+                    bids: vec![level(dec!(50000), dec!(1))],
+                    asks: vec![level(dec!(50001), dec!(1))],
+                },
+            },
+            Recorded::Message {
+                received_at_us: 2,
+                raw: r#"{"e":"depthUpdate","E":1000,"s":"BTCUSDT","U":101,"u":101,"b":[["50000.5","2"]],"a":[]}"#
+                    .to_string(),
+            },
+            Recorded::Message {
+                received_at_us: 3,
+                raw: r#"{"result":null,"id":1}"#.to_string(),
+            },
+        ];
+        let mut out = File::create(&path).unwrap();
+        for line in &lines {
+            write_line(&mut out, line).unwrap();
+        }
+
+        let mut manager = OrderBookManager::new();
+        let stats = replay(&path, &mut manager).unwrap();
+        assert_eq!(stats.snapshots, 1);
+        assert_eq!(stats.messages, 2);
+        assert_eq!(stats.updates_applied, 1);
+        assert_eq!(stats.errors, 0);
+
+        let state = manager.get_state("BTCUSDT").unwrap();
+        assert_eq!(state.last_update_id, 101);
+        assert_eq!(state.bids[0].price, dec!(50000.5));
+    }
+}
@@ -0,0 +1,611 @@
+//! Rate-limited Binance REST client
+//!
+//! Binance meters REST usage in request weight per IP and minute, reports
+//! the weight used so far in `X-MBX-USED-WEIGHT-1M`, answers 429 once the
+//! limit is exceeded and bans IPs that keep going with 418. Every REST call
+//! goes through one shared [`RestClient`], which spaces requests out, waits
+//! for the next minute when a request would go over the weight limit,
+//! honors `Retry-After`, and retries transient failures with jittered
+//! exponential backoff. Requests go to the endpoint currently preferred by
+//! [`Endpoints`], which moves on after repeated server or transport errors.
+//!
+//! Account endpoints are authenticated with the API key in a header and,
+//! for `SIGNED` endpoints, an HMAC-SHA256 signature of the query keyed with
+//! the secret key. Signed requests carry a `timestamp` and a `recvWindow`,
+//! and the exchange rejects them when the timestamp falls outside the
+//! window of its own clock; the client then measures the offset of the
+//! exchange's clock from `/time` and retries with corrected timestamps.
+
+use hmac::{Hmac, Mac};
+use prometheus::{IntCounterVec, IntGauge, Opts};
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Url};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::clock::ClockSample;
+use crate::config::Config;
+use crate::endpoints::Endpoints;
+use crate::error::{MarketDataError, Result};
+use crate::websocket::now_micros;
+
+/// Header carrying the weight used in the current minute
+const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-1m";
+
+/// Header authenticating requests for account endpoints
+const API_KEY_HEADER: &str = "X-MBX-APIKEY";
+
+/// Error code of a signed request whose timestamp is outside the
+/// `recvWindow` of the exchange's clock
+const TIMESTAMP_ERROR_CODE: i64 = -1021;
+
+/// Weight of a `time` request
+const TIME_WEIGHT: u32 = 1;
+
+/// Longest delay between retries, before jitter
+const MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Time allowed for one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static USED_WEIGHT: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "rest_used_weight",
+        "REST request weight used in the current minute, as last reported by the exchange",
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static RETRIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "rest_retries_total",
+            "REST requests retried, by reason (rate_limited, banned, server_error, timestamp or transport)",
+        ),
+        &["reason"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Weight of a `depth` request for `limit` levels
+pub fn depth_weight(limit: usize) -> u32 {
+    match limit {
+        0..=100 => 5,
+        101..=500 => 25,
+        501..=1000 => 50,
+        _ => 250,
+    }
+}
+
+/// Authentication an endpoint requires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    /// Public market data
+    None,
+    /// The API key alone (`USER_STREAM`, `MARKET_DATA`)
+    ApiKey,
+    /// The API key and a signed, timestamped query (`USER_DATA`, `TRADE`)
+    Signed,
+}
+
+/// Keys of the account authenticated requests act for
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    /// Secret key signing `SIGNED` requests; only API-key endpoints can be
+    /// called without one
+    secret_key: Option<String>,
+    /// Milliseconds after its timestamp a signed request stays valid
+    pub recv_window_ms: u64,
+}
+
+impl Credentials {
+    pub fn new(
+        api_key: impl Into<String>,
+        secret_key: Option<String>,
+        recv_window_ms: u64,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            secret_key,
+            recv_window_ms,
+        }
+    }
+
+    /// Credentials configured with `BINANCE_API_KEY`, if any
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let api_key = config.binance_api_key.clone()?;
+        Some(Self::new(
+            api_key,
+            config.binance_api_secret.clone(),
+            config.rest_recv_window_ms,
+        ))
+    }
+
+    /// Whether `SIGNED` requests can be made
+    pub fn can_sign(&self) -> bool {
+        self.secret_key.is_some()
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key)
+            .field(
+                "secret_key",
+                &self.secret_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("recv_window_ms", &self.recv_window_ms)
+            .finish()
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of `payload` keyed with `secret_key`, the
+/// `signature` parameter of a signed request
+pub fn sign(secret_key: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret_key.as_bytes())
+        .expect("HMAC takes keys of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Request pacing and retry settings
+#[derive(Debug, Clone, Copy)]
+pub struct RestLimits {
+    /// Weight per minute to stay under
+    pub weight_limit: u32,
+    /// Minimum delay between requests
+    pub min_interval_ms: u64,
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub retry_base_ms: u64,
+}
+
+impl RestLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            weight_limit: config.rest_weight_limit,
+            min_interval_ms: config.rest_min_interval_ms,
+            max_retries: config.rest_max_retries,
+            retry_base_ms: config.rest_retry_base_ms,
+        }
+    }
+
+    /// Jittered delay before retry number `attempt` (from 0): between half
+    /// and all of the exponential backoff, so callers failing together
+    /// don't retry together
+    fn backoff_ms(&self, attempt: u32) -> u64 {
+        let backoff = self
+            .retry_base_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(MAX_BACKOFF_MS);
+        backoff / 2 + fastrand::u64(..=backoff / 2)
+    }
+}
+
+/// Request schedule shared by all callers
+#[derive(Debug, Default)]
+struct Throttle {
+    /// Earliest time (ms since the epoch) the next request may go out
+    next_at_ms: u64,
+    /// Minute (since the epoch) `used_weight` belongs to
+    minute: u64,
+    /// Weight used in `minute`: the exchange's last report plus the
+    /// requests sent since
+    used_weight: u32,
+}
+
+impl Throttle {
+    /// Reserve a slot for a request of `weight` at or after `now_ms`;
+    /// returns how long to wait before sending it
+    fn reserve(&mut self, weight: u32, now_ms: u64, limits: &RestLimits) -> u64 {
+        let mut send_at = now_ms.max(self.next_at_ms);
+        if send_at / 60_000 != self.minute {
+            self.minute = send_at / 60_000;
+            self.used_weight = 0;
+        }
+        if self.used_weight > 0 && self.used_weight + weight > limits.weight_limit {
+            // The weight window resets on the minute
+            self.minute += 1;
+            self.used_weight = 0;
+            send_at = self.minute * 60_000;
+        }
+        self.used_weight += weight;
+        self.next_at_ms = send_at + limits.min_interval_ms;
+        send_at - now_ms
+    }
+
+    /// Take the weight the exchange reported at `now_ms`
+    fn record(&mut self, used_weight: u32, now_ms: u64) {
+        let minute = now_ms / 60_000;
+        if minute == self.minute {
+            self.used_weight = self.used_weight.max(used_weight);
+        } else if minute > self.minute {
+            self.minute = minute;
+            self.used_weight = used_weight;
+        }
+    }
+
+    /// Hold every request until `until_ms`
+    fn pause(&mut self, until_ms: u64) {
+        self.next_at_ms = self.next_at_ms.max(until_ms);
+    }
+}
+
+/// Rate-limited client for the exchange's REST API
+#[derive(Debug)]
+pub struct RestClient {
+    http: reqwest::Client,
+    endpoints: Endpoints,
+    limits: RestLimits,
+    throttle: Mutex<Throttle>,
+    credentials: Option<Credentials>,
+    /// Exchange clock minus ours (ms), added to signed request timestamps
+    clock_offset_ms: AtomicI64,
+}
+
+impl RestClient {
+    /// Client for the API at `endpoint`, e.g. `https://api.binance.com/api/v3`
+    pub fn new(endpoint: impl Into<String>, limits: RestLimits) -> Self {
+        LazyLock::force(&USED_WEIGHT);
+        LazyLock::force(&RETRIES);
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            endpoints: Endpoints::single("rest", endpoint),
+            limits,
+            throttle: Mutex::new(Throttle::default()),
+            credentials: None,
+            clock_offset_ms: AtomicI64::new(0),
+        }
+    }
+
+    /// Fail over between `endpoints` instead of the single endpoint
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Authenticate account requests with `credentials`
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let client = Self::new(&config.rest_endpoint, RestLimits::from_config(config))
+            .with_endpoints(Endpoints::new(
+                "rest",
+                config.rest_endpoints(),
+                config.endpoint_failover_after,
+            ));
+        match Credentials::from_config(config) {
+            Some(credentials) => client.with_credentials(credentials),
+            None => client,
+        }
+    }
+
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials.as_ref()
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// GET `path` (relative to the endpoint) with `query`, costing `weight`,
+    /// and return the response body
+    pub async fn get(&self, path: &str, query: &[(&str, &str)], weight: u32) -> Result<String> {
+        self.request(Method::GET, path, query, weight, Auth::None)
+            .await
+    }
+
+    /// Send a `method` request for `path` with `query`, costing `weight`,
+    /// authenticated as `auth` requires, and return the response body
+    ///
+    /// Failed requests are retried, so `method` must be idempotent.
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        weight: u32,
+        auth: Auth,
+    ) -> Result<String> {
+        let credentials = match (auth, &self.credentials) {
+            (Auth::None, _) => None,
+            (Auth::ApiKey, Some(credentials)) => Some(credentials),
+            (Auth::Signed, Some(credentials)) if credentials.can_sign() => Some(credentials),
+            (Auth::ApiKey, None) => {
+                return Err(MarketDataError::ConfigError(format!(
+                    "{} {} requires BINANCE_API_KEY",
+                    method, path
+                )))
+            }
+            (Auth::Signed, _) => {
+                return Err(MarketDataError::ConfigError(format!(
+                    "{} {} requires BINANCE_API_KEY and BINANCE_API_SECRET",
+                    method, path
+                )))
+            }
+        };
+        let mut attempt = 0;
+        loop {
+            let wait_ms = self
+                .throttle
+                .lock()
+                .await
+                .reserve(weight, now_millis(), &self.limits);
+            if wait_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+            }
+
+            // Each attempt goes to the endpoint currently in use; signed
+            // requests are timestamped and signed anew
+            let mut url = Url::parse(&format!("{}/{}", self.endpoints.current(), path))
+                .map_err(|e| MarketDataError::ConfigError(format!("Invalid REST URL: {}", e)))?;
+            url.query_pairs_mut().extend_pairs(query);
+            if let Some(secret_key) = credentials
+                .and_then(|c| c.secret_key.as_deref())
+                .filter(|_| auth == Auth::Signed)
+            {
+                let recv_window = credentials.map_or(0, |c| c.recv_window_ms);
+                let timestamp = now_millis() as i64 + self.clock_offset_ms.load(Ordering::Relaxed);
+                url.query_pairs_mut()
+                    .append_pair("recvWindow", &recv_window.to_string())
+                    .append_pair("timestamp", &timestamp.to_string());
+                let signature = sign(secret_key, url.query().unwrap_or_default());
+                url.query_pairs_mut().append_pair("signature", &signature);
+            }
+            let mut request = self.http.request(method.clone(), url);
+            if let Some(credentials) = credentials {
+                request = request.header(API_KEY_HEADER, &credentials.api_key);
+            }
+            let (error, reason, retry_after) = match request.send().await {
+                Ok(response) => {
+                    self.record_weight(response.headers()).await;
+                    let status = response.status();
+                    if !status.is_server_error() {
+                        self.endpoints.succeeded();
+                    }
+                    if status.is_success() {
+                        return Ok(response.text().await?);
+                    }
+                    let retry_after = retry_after_ms(response.headers());
+                    let body = response.text().await.unwrap_or_default();
+                    let error = MarketDataError::RestApiError(format!(
+                        "{} {} returned {}: {}",
+                        method,
+                        path,
+                        status,
+                        body.trim()
+                    ));
+                    let reason = match status.as_u16() {
+                        429 => "rate_limited",
+                        418 => "banned",
+                        _ if status.is_server_error() => "server_error",
+                        _ if auth == Auth::Signed
+                            && error_code(&body) == Some(TIMESTAMP_ERROR_CODE) =>
+                        {
+                            "timestamp"
+                        }
+                        _ => return Err(error),
+                    };
+                    (error, reason, retry_after)
+                }
+                Err(e) => (
+                    MarketDataError::RestApiError(format!("{} {} failed: {}", method, path, e)),
+                    "transport",
+                    None,
+                ),
+            };
+
+            if matches!(reason, "server_error" | "transport") {
+                self.endpoints.failed();
+            }
+            if attempt >= self.limits.max_retries {
+                return Err(error);
+            }
+            if reason == "timestamp" {
+                // Our clock drifted from the exchange's: measure the offset
+                // and retry right away
+                attempt += 1;
+                RETRIES.with_label_values(&[reason]).inc();
+                let offset_ms = self.sync_clock().await?;
+                warn!(
+                    offset_ms,
+                    "Signed request timestamp rejected, resynced with the exchange clock"
+                );
+                continue;
+            }
+            let delay_ms = retry_after.unwrap_or_else(|| self.limits.backoff_ms(attempt));
+            attempt += 1;
+            RETRIES.with_label_values(&[reason]).inc();
+            warn!(
+                error = %error,
+                attempt,
+                delay_ms,
+                "REST request failed, retrying"
+            );
+            if retry_after.is_some() {
+                // Rate limits and bans apply to the whole IP, so every
+                // caller holds off
+                self.throttle.lock().await.pause(now_millis() + delay_ms);
+            } else {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            }
+        }
+    }
+
+    /// Measure the offset of the exchange's clock from ours with a `time`
+    /// request, use it for signed request timestamps and return it
+    pub async fn sync_clock(&self) -> Result<i64> {
+        let offset_ms = self.measure_clock().await?.offset_us / 1000;
+        self.set_clock_offset(offset_ms);
+        Ok(offset_ms)
+    }
+
+    /// Offset of the exchange's clock from ours and the round trip of the
+    /// `time` request it was read with
+    pub async fn measure_clock(&self) -> Result<ClockSample> {
+        let wait_ms = self
+            .throttle
+            .lock()
+            .await
+            .reserve(TIME_WEIGHT, now_millis(), &self.limits);
+        if wait_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+        }
+
+        let sent_at_us = now_micros();
+        let response = self
+            .http
+            .get(format!("{}/time", self.endpoints.current()))
+            .send()
+            .await?
+            .error_for_status()?;
+        let received_at_us = now_micros();
+        self.record_weight(response.headers()).await;
+        let time: ServerTime = serde_json::from_str(&response.text().await?)?;
+        Ok(ClockSample::new(
+            time.server_time * 1000,
+            sent_at_us,
+            received_at_us,
+        ))
+    }
+
+    /// Add `offset_ms` to the timestamps of signed requests
+    pub fn set_clock_offset(&self, offset_ms: i64) {
+        self.clock_offset_ms.store(offset_ms, Ordering::Relaxed);
+    }
+
+    async fn record_weight(&self, headers: &HeaderMap) {
+        let Some(used_weight) = header_u64(headers, USED_WEIGHT_HEADER) else {
+            return;
+        };
+        USED_WEIGHT.set(used_weight as i64);
+        self.throttle
+            .lock()
+            .await
+            .record(used_weight.min(u32::MAX as u64) as u32, now_millis());
+    }
+}
+
+#[derive(Deserialize)]
+struct ServerTime {
+    #[serde(rename = "serverTime")]
+    server_time: u64,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: i64,
+}
+
+/// Exchange error code of an error response body
+fn error_code(body: &str) -> Option<i64> {
+    serde_json::from_str::<ErrorBody>(body).ok().map(|e| e.code)
+}
+
+/// `Retry-After` (seconds) of a response, in milliseconds
+fn retry_after_ms(headers: &HeaderMap) -> Option<u64> {
+    header_u64(headers, reqwest::header::RETRY_AFTER.as_str()).map(|secs| secs * 1000)
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn now_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_spacing_and_weight_limit() {
+        let limits = RestLimits {
+            weight_limit: 60,
+            min_interval_ms: 100,
+            max_retries: 3,
+            retry_base_ms: 500,
+        };
+        let mut throttle = Throttle::default();
+        let start = 120_000;
+
+        // Requests are spaced out by the minimum interval
+        assert_eq!(throttle.reserve(25, start, &limits), 0);
+        assert_eq!(throttle.reserve(25, start, &limits), 100);
+
+        // A third would exceed the minute's weight, so it waits for the next
+        assert_eq!(throttle.reserve(25, start + 200, &limits), 59_800);
+
+        // Reported weight beyond the local count holds back further requests
+        let mut throttle = Throttle::default();
+        throttle.record(50, start + 1_000);
+        assert_eq!(throttle.reserve(25, start + 1_000, &limits), 59_000);
+
+        // A rate-limit pause holds every request
+        let mut throttle = Throttle::default();
+        throttle.pause(start + 5_000);
+        assert_eq!(throttle.reserve(5, start, &limits), 5_000);
+    }
+
+    #[test]
+    fn test_backoff_is_jittered_and_capped() {
+        let limits = RestLimits {
+            weight_limit: 6000,
+            min_interval_ms: 0,
+            max_retries: 10,
+            retry_base_ms: 500,
+        };
+        for _ in 0..100 {
+            assert!((250..=500).contains(&limits.backoff_ms(0)));
+            assert!((1_000..=2_000).contains(&limits.backoff_ms(2)));
+            assert!((MAX_BACKOFF_MS / 2..=MAX_BACKOFF_MS).contains(&limits.backoff_ms(20)));
+        }
+        assert_eq!(depth_weight(100), 5);
+        assert_eq!(depth_weight(1000), 50);
+        assert_eq!(depth_weight(5000), 250);
+    }
+
+    #[test]
+    fn test_sign_matches_exchange_example() {
+        // Example from the Binance API documentation
+        let secret_key = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(
+            sign(secret_key, query),
+            "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+
+        let credentials = Credentials::new("key", Some(secret_key.to_string()), 5000);
+        assert!(credentials.can_sign());
+        assert!(!format!("{:?}", credentials).contains(secret_key));
+        assert!(!Credentials::new("key", None, 5000).can_sign());
+
+        assert_eq!(
+            error_code(
+                r#"{"code":-1021,"msg":"Timestamp for this request is outside of the recvWindow."}"#
+            ),
+            Some(TIMESTAMP_ERROR_CODE)
+        );
+        assert_eq!(error_code("<html>"), None);
+    }
+}
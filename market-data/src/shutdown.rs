@@ -0,0 +1,96 @@
+//! Graceful shutdown
+//!
+//! `Shutdown` is a cloneable flag that long-running tasks watch: the
+//! binary triggers it on SIGINT or SIGTERM, the WebSocket manager stops
+//! reading, and `main` then saves state and flushes the publisher before
+//! exiting.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Shared shutdown flag
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Create an untriggered flag
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Ask every watcher to stop
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Whether shutdown has been triggered
+    pub fn is_triggered(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Wait until shutdown is triggered; returns at once if it already was
+    pub async fn wait(&self) {
+        let mut rx = self.tx.subscribe();
+        // The sender lives in `self`, so this only ends once triggered
+        let _ = rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Wait for SIGINT (Ctrl-C) or, on Unix, SIGTERM
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_trigger_wakes_waiters() {
+        let shutdown = Shutdown::new();
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        shutdown.trigger();
+        waiter.await.unwrap();
+        assert!(shutdown.is_triggered());
+
+        // Late waiters return immediately
+        tokio::time::timeout(Duration::from_secs(1), shutdown.wait())
+            .await
+            .unwrap();
+    }
+}
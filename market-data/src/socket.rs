@@ -0,0 +1,136 @@
+//! Socket tuning for the WebSocket connections and publisher sockets
+//!
+//! The WebSocket TCP connections disable Nagle's algorithm by default
+//! (`TCP_NODELAY`) so pings, pongs and subscription requests leave at once,
+//! and can enable TCP keepalive (`TCP_KEEPALIVE_SECS`) so a dead peer
+//! behind a silent NAT is noticed by the kernel. Send and receive buffer
+//! sizes (`TCP_SEND_BUFFER_BYTES`, `TCP_RECV_BUFFER_BYTES`) are set before
+//! connecting, so the receive window can scale to them. The IPC Unix
+//! socket and the multicast socket take a send buffer size of their own
+//! (`IPC_SEND_BUFFER_BYTES`, `MULTICAST_SEND_BUFFER_BYTES`). Unset sizes
+//! leave the kernel's defaults; Linux doubles a requested size for its own
+//! bookkeeping and caps it at `net.core.wmem_max` / `rmem_max`.
+
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpSocket, TcpStream};
+
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+
+/// Options of an outgoing TCP connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpOptions {
+    pub nodelay: bool,
+    /// Idle time before keepalive probes start; `None` leaves keepalive off
+    pub keepalive: Option<Duration>,
+    pub send_buffer: Option<usize>,
+    pub recv_buffer: Option<usize>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            send_buffer: None,
+            recv_buffer: None,
+        }
+    }
+}
+
+impl TcpOptions {
+    /// TCP options of the WebSocket connections in `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            nodelay: config.tcp_nodelay,
+            keepalive: (config.tcp_keepalive_secs > 0)
+                .then(|| Duration::from_secs(config.tcp_keepalive_secs)),
+            send_buffer: config.tcp_send_buffer_bytes,
+            recv_buffer: config.tcp_recv_buffer_bytes,
+        }
+    }
+
+    /// Apply the options to an unconnected socket
+    fn apply(&self, socket: SockRef<'_>) -> io::Result<()> {
+        socket.set_nodelay(self.nodelay)?;
+        if let Some(idle) = self.keepalive {
+            socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+        set_buffers(socket, self.send_buffer, self.recv_buffer)
+    }
+
+    /// Connect to `host:port`, trying each resolved address in turn
+    pub async fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let failed = |e: io::Error| {
+            MarketDataError::WebSocketConnection(format!(
+                "Failed to connect to {}:{}: {}",
+                host, port, e
+            ))
+        };
+        let mut last_error = None;
+        for addr in lookup_host((host, port)).await.map_err(failed)? {
+            match self.connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(failed(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no addresses resolved")
+        })))
+    }
+
+    async fn connect_addr(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        self.apply(SockRef::from(&socket))?;
+        socket.connect(addr).await
+    }
+}
+
+/// Set the send and receive buffer sizes of `socket`, where given
+pub fn set_buffers(
+    socket: SockRef<'_>,
+    send: Option<usize>,
+    recv: Option<usize>,
+) -> io::Result<()> {
+    if let Some(size) = send {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = recv {
+        socket.set_recv_buffer_size(size)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_connect_applies_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let options = TcpOptions {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(30)),
+            send_buffer: Some(64 * 1024),
+            recv_buffer: Some(64 * 1024),
+        };
+
+        let stream = options.connect("127.0.0.1", port).await.unwrap();
+        let socket = SockRef::from(&stream);
+        assert!(socket.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+
+        drop(listener);
+        assert!(options.connect("127.0.0.1", port).await.is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! IPC subscriber client
+//!
+//! The consumer side of the IPC wire protocol, so consumers written in
+//! Rust don't each reimplement it. The publisher connects to its
+//! consumer's Unix socket (`IPC_SOCKET_PATH`); a [`Subscriber`] listens
+//! there, accepts the publisher (again after every reconnect), splits the
+//! stream into length-prefixed frames, decodes envelopes, decompresses and
+//! deserializes payloads, and hands them out as a
+//! `Stream<Item = PublishedMessage>`.
+//!
+//! Envelope sequence numbers are checked along the way: frames the
+//! publisher never delivered show up as [`PublishedMessage::Gap`] before
+//! the next frame, including those dropped while disconnected. A sequence
+//! going backwards means the publisher restarted and is not a gap.
+//!
+//! Payloads in the JSON and MessagePack wire formats are deserialized;
+//! book states in the Protobuf and FlatBuffers formats are handed out
+//! encoded, for `proto::OrderBook` or `flatbuf::OrderBookView` to read.
+//! Enabled with the `client` feature.
+
+use futures_util::Stream;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncReadExt;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::anomaly::MarketAnomaly;
+use crate::config::WireFormat;
+use crate::error::{MarketDataError, Result};
+use crate::orderbook::{BboChanged, OrderBookState};
+use crate::publisher::{
+    compression, decode_frame, BookHeartbeat, BookMessage, BootstrapFrame, ControlMessage,
+    Envelope, MessageType, StatusEvent,
+};
+use crate::user_data::{AccountPosition, OrderEvent};
+use crate::volume_profile::VolumeProfile;
+
+/// Largest frame accepted from the publisher
+const MAX_FRAME: usize = 64 << 20;
+/// Messages buffered between the socket reader and the stream
+const BUFFER: usize = 4096;
+
+/// Something that arrived from the publisher
+#[derive(Debug, Clone)]
+pub enum PublishedMessage {
+    /// The publisher connected
+    Connected,
+    /// A frame with its envelope
+    Frame {
+        envelope: Envelope,
+        payload: Payload,
+    },
+    /// Frames `expected..received` never arrived
+    Gap { expected: u64, received: u64 },
+    /// The publisher's connection ended; it reconnects on its own
+    Disconnected,
+}
+
+/// Decoded payload of a frame, by its envelope's message type
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Book(Box<OrderBookState>),
+    Delta(Box<BookMessage>),
+    Bootstrap(BootstrapFrame),
+    VolumeProfile(VolumeProfile),
+    Anomaly(MarketAnomaly),
+    Bbo(BboChanged),
+    Control(ControlMessage),
+    Order(Box<OrderEvent>),
+    Account(AccountPosition),
+    Status(StatusEvent),
+    Heartbeat(BookHeartbeat),
+    /// Book state in the wire format's own schema (Protobuf, FlatBuffers)
+    Encoded(Vec<u8>),
+}
+
+/// Stream of messages from the publisher connecting to a socket
+pub struct Subscriber {
+    messages: mpsc::Receiver<PublishedMessage>,
+    reader: JoinHandle<()>,
+}
+
+impl Subscriber {
+    /// Listen for the publisher on `path`, replacing a stale socket file,
+    /// and decode payloads as `wire_format` (the publisher's
+    /// `WIRE_FORMAT`)
+    pub fn bind(path: impl AsRef<Path>, wire_format: WireFormat) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        info!(path = %path.display(), "Waiting for the publisher");
+        let (tx, messages) = mpsc::channel(BUFFER);
+        let reader = tokio::spawn(accept(listener, wire_format, tx));
+        Ok(Self { messages, reader })
+    }
+}
+
+impl Stream for Subscriber {
+    type Item = PublishedMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.messages.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Tracks envelope sequence numbers across connections
+#[derive(Debug, Default)]
+struct Sequence {
+    last: Option<u64>,
+}
+
+impl Sequence {
+    /// The missed range, if `seq` skips ahead of the last one
+    fn observe(&mut self, seq: u64) -> Option<(u64, u64)> {
+        let gap = self
+            .last
+            .filter(|last| seq > last + 1)
+            .map(|last| (last + 1, seq));
+        self.last = Some(seq);
+        gap
+    }
+}
+
+/// Accept the publisher and read its frames, connection after connection,
+/// until the stream is dropped
+async fn accept(
+    listener: UnixListener,
+    wire_format: WireFormat,
+    messages: mpsc::Sender<PublishedMessage>,
+) {
+    let mut sequence = Sequence::default();
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!(error = %e, "Failed to accept the publisher");
+                continue;
+            }
+        };
+        if messages.send(PublishedMessage::Connected).await.is_err() {
+            return;
+        }
+        if let Err(e) = read(stream, wire_format, &mut sequence, &messages).await {
+            debug!(error = %e, "Publisher connection ended");
+        }
+        if messages.send(PublishedMessage::Disconnected).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn read(
+    mut stream: UnixStream,
+    wire_format: WireFormat,
+    sequence: &mut Sequence,
+    messages: &mpsc::Sender<PublishedMessage>,
+) -> Result<()> {
+    loop {
+        let len = stream.read_u32().await? as usize;
+        if len > MAX_FRAME {
+            return Err(MarketDataError::IpcError(format!(
+                "Frame of {} bytes is too large",
+                len
+            )));
+        }
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).await?;
+
+        let (envelope, payload) = Envelope::decode(&body)?;
+        if let Some((expected, received)) = sequence.observe(envelope.seq) {
+            let gap = PublishedMessage::Gap { expected, received };
+            if messages.send(gap).await.is_err() {
+                return Ok(());
+            }
+        }
+        let payload = match decode(&envelope, payload, wire_format) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!(error = %e, seq = envelope.seq, "Failed to decode frame, skipping it");
+                continue;
+            }
+        };
+        let frame = PublishedMessage::Frame { envelope, payload };
+        if messages.send(frame).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Decompress and deserialize a frame's payload
+fn decode(envelope: &Envelope, payload: &[u8], wire_format: WireFormat) -> Result<Payload> {
+    let data = compression::decompress(envelope.compression, payload)?;
+    let data = data.as_slice();
+    Ok(match envelope.message_type {
+        MessageType::Book
+            if matches!(wire_format, WireFormat::Protobuf | WireFormat::FlatBuffers) =>
+        {
+            Payload::Encoded(data.to_vec())
+        }
+        MessageType::Book => Payload::Book(Box::new(decode_frame(data, wire_format)?)),
+        MessageType::Delta => Payload::Delta(Box::new(decode_frame(data, wire_format)?)),
+        MessageType::Bootstrap => Payload::Bootstrap(decode_frame(data, wire_format)?),
+        MessageType::VolumeProfile => Payload::VolumeProfile(decode_frame(data, wire_format)?),
+        MessageType::Anomaly => Payload::Anomaly(decode_frame(data, wire_format)?),
+        MessageType::Bbo => Payload::Bbo(decode_frame(data, wire_format)?),
+        MessageType::Control => Payload::Control(decode_frame(data, wire_format)?),
+        MessageType::Order => Payload::Order(Box::new(decode_frame(data, wire_format)?)),
+        MessageType::Account => Payload::Account(decode_frame(data, wire_format)?),
+        MessageType::Status => Payload::Status(decode_frame(data, wire_format)?),
+        MessageType::Heartbeat => Payload::Heartbeat(decode_frame(data, wire_format)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Compression;
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    fn frame(seq: u64, status: &StatusEvent) -> Vec<u8> {
+        let payload = rmp_serde::to_vec(status).unwrap();
+        let payload = compression::compress(Compression::Lz4, &payload).unwrap();
+        Envelope {
+            seq,
+            sent_at_us: 0,
+            message_type: MessageType::Status,
+            compression: Compression::Lz4,
+            symbol: String::new(),
+        }
+        .frame(&payload)
+    }
+
+    #[tokio::test]
+    async fn test_decodes_frames_and_reports_gaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ipc.sock");
+        let mut subscriber = Subscriber::bind(&path, WireFormat::MessagePack).unwrap();
+
+        let connected = |connection_id| StatusEvent::Connected {
+            connection_id,
+            timestamp: 0,
+        };
+        let mut publisher = UnixStream::connect(&path).await.unwrap();
+        publisher.write_all(&frame(1, &connected(1))).await.unwrap();
+        publisher.write_all(&frame(4, &connected(2))).await.unwrap();
+        drop(publisher);
+
+        assert!(matches!(
+            subscriber.next().await,
+            Some(PublishedMessage::Connected)
+        ));
+        match subscriber.next().await {
+            Some(PublishedMessage::Frame {
+                envelope,
+                payload: Payload::Status(status),
+            }) => {
+                assert_eq!(envelope.seq, 1);
+                assert_eq!(status, connected(1));
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+        assert!(matches!(
+            subscriber.next().await,
+            Some(PublishedMessage::Gap {
+                expected: 2,
+                received: 4
+            })
+        ));
+        assert!(matches!(
+            subscriber.next().await,
+            Some(PublishedMessage::Frame { .. })
+        ));
+        assert!(matches!(
+            subscriber.next().await,
+            Some(PublishedMessage::Disconnected)
+        ));
+    }
+}
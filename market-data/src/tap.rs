@@ -0,0 +1,153 @@
+//! Raw message tap
+//!
+//! Forwards every text frame of the primary WebSocket connection, unparsed
+//! and stamped with its receive time, to `RAW_TAP`: a file, or a TCP or
+//! Unix socket listener. Downstream systems that want the exchange's raw
+//! messages read them here instead of opening connections of their own.
+//! Lines are those of a recording's frames (`{"kind":"message",
+//! "received_at_us":..,"raw":..}`), so tools reading recordings read the
+//! tap too.
+//!
+//! The tap never holds up the feed: frames are handed to a writer task
+//! through a queue of `RAW_TAP_CAPACITY`, and dropped (counted in
+//! `raw_tap_dropped_total`) when it is full. A socket sink that fails is
+//! reconnected, losing the frames meanwhile.
+
+use prometheus::{IntCounter, Opts};
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::TapSink;
+use crate::recording::Recorded;
+
+/// Delay before reopening a sink that failed
+const REOPEN_DELAY: Duration = Duration::from_secs(1);
+
+static DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::with_opts(Opts::new(
+        "raw_tap_dropped_total",
+        "Raw frames dropped because the tap's queue was full or its sink was down",
+    ))
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+type Sink = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// Handle feeding the tap's writer task
+#[derive(Debug, Clone)]
+pub struct RawTap {
+    frames: mpsc::Sender<Recorded>,
+}
+
+impl RawTap {
+    /// Start writing to `sink`, buffering up to `capacity` frames
+    pub fn spawn(sink: TapSink, capacity: usize) -> Self {
+        LazyLock::force(&DROPPED);
+        let (frames, rx) = mpsc::channel(capacity.max(1));
+        tokio::spawn(run(sink, rx));
+        Self { frames }
+    }
+
+    /// Forward a raw frame received at `received_at_us`, or drop it when
+    /// the writer is behind
+    pub fn send(&self, raw: &str, received_at_us: u64) {
+        let frame = Recorded::Message {
+            received_at_us,
+            raw: raw.to_string(),
+        };
+        if self.frames.try_send(frame).is_err() {
+            DROPPED.inc();
+        }
+    }
+}
+
+async fn open(sink: &TapSink) -> std::io::Result<Sink> {
+    Ok(match sink {
+        TapSink::File(path) => Box::pin(
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?,
+        ),
+        TapSink::Tcp(addr) => {
+            let stream = tokio::net::TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            Box::pin(stream)
+        }
+        TapSink::Unix(path) => Box::pin(tokio::net::UnixStream::connect(path).await?),
+    })
+}
+
+/// Write frames to the sink until every `RawTap` is dropped
+async fn run(sink: TapSink, mut frames: mpsc::Receiver<Recorded>) {
+    loop {
+        let out = match open(&sink).await {
+            Ok(out) => out,
+            Err(e) => {
+                warn!(error = %e, sink = ?sink, "Failed to open raw tap sink, retrying");
+                tokio::time::sleep(REOPEN_DELAY).await;
+                // Frames arriving while the sink is down are lost
+                while frames.try_recv().is_ok() {
+                    DROPPED.inc();
+                }
+                continue;
+            }
+        };
+        info!(sink = ?sink, "Raw tap sink opened");
+        match write(BufWriter::new(out), &mut frames).await {
+            Ok(()) => return,
+            Err(e) => warn!(error = %e, sink = ?sink, "Raw tap sink failed, reopening"),
+        }
+    }
+}
+
+/// Write frames as lines, flushing whenever the queue runs empty
+async fn write(
+    mut out: BufWriter<Sink>,
+    frames: &mut mpsc::Receiver<Recorded>,
+) -> std::io::Result<()> {
+    while let Some(frame) = frames.recv().await {
+        let mut line = serde_json::to_vec(&frame)?;
+        line.push(b'\n');
+        out.write_all(&line).await?;
+        if frames.is_empty() {
+            out.flush().await?;
+        }
+    }
+    out.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+
+    #[tokio::test]
+    async fn test_frames_stream_to_a_socket_as_recording_lines() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let tap = RawTap::spawn(TapSink::Tcp(addr), 16);
+        tap.send(r#"{"e":"trade"}"#, 42);
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut lines = tokio::io::BufReader::new(stream).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        match serde_json::from_str(&line).unwrap() {
+            Recorded::Message {
+                received_at_us,
+                raw,
+            } => {
+                assert_eq!(received_at_us, 42);
+                assert_eq!(raw, r#"{"e":"trade"}"#);
+            }
+            other => panic!("Unexpected line {:?}", other),
+        }
+    }
+}
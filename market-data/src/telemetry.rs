@@ -0,0 +1,73 @@
+//! OpenTelemetry tracing export
+//!
+//! With the `otel` feature and `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans
+//! for connecting, snapshot fetches, message processing and publishing are
+//! exported over OTLP/gRPC, so the handler shows up next to its consumers
+//! in a distributed tracing backend. Per-message spans are debug level:
+//! they are exported but don't reach the log output unless `RUST_LOG` asks
+//! for them.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::filter::{filter_fn, FilterFn};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// OTLP span exporter pipeline
+pub struct Telemetry {
+    provider: TracerProvider,
+    tracer: Tracer,
+}
+
+impl Telemetry {
+    /// Start exporting batches of spans to `endpoint`, e.g.
+    /// `http://localhost:4317`
+    pub fn init(endpoint: &str, service_name: &str) -> anyhow::Result<Self> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                service_name.to_string(),
+            )]))
+            .build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        Ok(Self { provider, tracer })
+    }
+
+    /// Layer forwarding spans (down to debug) and their info events to the
+    /// exporter
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.tracer.clone())
+            .with_filter(export_filter())
+    }
+
+    /// Export the spans still buffered and stop
+    pub fn shutdown(&self) {
+        if let Err(e) = self.provider.shutdown() {
+            tracing::warn!(error = %e, "Failed to flush spans");
+        }
+    }
+}
+
+fn export_filter() -> FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+    filter_fn(|metadata| {
+        let max = if metadata.is_span() {
+            Level::DEBUG
+        } else {
+            Level::INFO
+        };
+        *metadata.level() <= max
+    })
+}
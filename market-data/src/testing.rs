@@ -0,0 +1,468 @@
+//! In-process mock of the exchange for integration tests
+//!
+//! [`MockExchange`] serves the combined-stream WebSocket and the REST depth
+//! endpoint on loopback ports. Each WebSocket connection plays the next
+//! scripted [`Session`], so tests can stage gaps, out-of-order update ids
+//! and disconnects; depth snapshots are served per symbol in the order they
+//! were queued, the last one repeating.
+//!
+//! ```ignore
+//! let exchange = MockExchange::start().await?;
+//! exchange.snapshot("BTCUSDT", 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+//! exchange.session(Session::new().depth("BTCUSDT", 101, 101, &[("100.5", "2.0")], &[]));
+//! let (state, resyncs) = testing::app_state(exchange.config(&["BTCUSDT"]), Clock::wall()).await?;
+//! let mut manager = WebSocketManager::new(state.clone()).with_resyncs(resyncs);
+//! ```
+//!
+//! Built with the `testing` feature, which the crate's own integration
+//! tests turn on.
+
+use futures_util::{SinkExt, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::analytics::TradeAnalytics;
+use crate::clock::ClockSkew;
+use crate::config::Config;
+use crate::dead_man::DeadManSwitch;
+use crate::degradation::Degradation;
+use crate::endpoints::Endpoints;
+use crate::error::Result;
+use crate::health::Heartbeat;
+use crate::latency::LatencyTracker;
+use crate::orderbook::OrderBookManager;
+use crate::pipeline::Books;
+use crate::publisher::Publisher;
+use crate::rest::RestClient;
+use crate::time::Clock;
+use crate::volume_profile::VolumeProfileTracker;
+use crate::websocket::{SubscriptionProgress, Tls};
+use crate::AppState;
+
+/// Price and quantity of a level, as the exchange sends them
+pub type Level<'a> = (&'a str, &'a str);
+
+/// One step of a scripted connection
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Send a text frame
+    Send(String),
+    /// Wait, still answering the client
+    Pause(Duration),
+    /// Drop the connection without a close handshake
+    Disconnect,
+}
+
+/// Script of one WebSocket connection; once played out the connection
+/// stays open, answering SUBSCRIBE requests and pings
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    steps: Vec<Step>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `raw` as is
+    pub fn send(mut self, raw: impl Into<String>) -> Self {
+        self.steps.push(Step::Send(raw.into()));
+        self
+    }
+
+    /// Send a diff depth update covering update ids `first..=last`
+    pub fn depth(
+        self,
+        symbol: &str,
+        first: u64,
+        last: u64,
+        bids: &[Level<'_>],
+        asks: &[Level<'_>],
+    ) -> Self {
+        let data = serde_json::json!({
+            "e": "depthUpdate",
+            "E": now_millis(),
+            "s": symbol,
+            "U": first,
+            "u": last,
+            "b": bids,
+            "a": asks,
+        });
+        self.send(combined(
+            &format!("{}@depth@100ms", symbol.to_lowercase()),
+            data,
+        ))
+    }
+
+    /// Send a trade; `buyer_maker` marks a sell-initiated trade
+    pub fn trade(self, symbol: &str, id: u64, level: Level<'_>, buyer_maker: bool) -> Self {
+        let now = now_millis();
+        let data = serde_json::json!({
+            "e": "trade",
+            "E": now,
+            "s": symbol,
+            "t": id,
+            "p": level.0,
+            "q": level.1,
+            "b": id,
+            "a": id,
+            "T": now,
+            "m": buyer_maker,
+        });
+        self.send(combined(&format!("{}@trade", symbol.to_lowercase()), data))
+    }
+
+    pub fn pause(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Pause(duration));
+        self
+    }
+
+    pub fn disconnect(mut self) -> Self {
+        self.steps.push(Step::Disconnect);
+        self
+    }
+}
+
+/// State shared by the mock's connections
+#[derive(Default)]
+struct Shared {
+    /// Scripts of the connections to come
+    sessions: Mutex<VecDeque<Session>>,
+    /// Depth snapshot bodies to serve per symbol
+    snapshots: Mutex<HashMap<String, VecDeque<String>>>,
+    snapshot_requests: Mutex<HashMap<String, usize>>,
+    /// Request targets of the WebSocket connections so far
+    connections: Mutex<Vec<String>>,
+    subscribe_requests: AtomicUsize,
+}
+
+/// Mock exchange listening on loopback; stops when dropped
+pub struct MockExchange {
+    ws_addr: SocketAddr,
+    rest_addr: SocketAddr,
+    shared: Arc<Shared>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockExchange {
+    /// Listen on free loopback ports
+    pub async fn start() -> std::io::Result<Self> {
+        let ws = TcpListener::bind("127.0.0.1:0").await?;
+        let rest = TcpListener::bind("127.0.0.1:0").await?;
+        let shared = Arc::new(Shared::default());
+        Ok(Self {
+            ws_addr: ws.local_addr()?,
+            rest_addr: rest.local_addr()?,
+            tasks: vec![
+                tokio::spawn(accept(ws, shared.clone(), serve_ws)),
+                tokio::spawn(accept(rest, shared.clone(), serve_rest)),
+            ],
+            shared,
+        })
+    }
+
+    /// Base WebSocket endpoint, for `WS_ENDPOINT`
+    pub fn ws_endpoint(&self) -> String {
+        format!("ws://{}", self.ws_addr)
+    }
+
+    /// Base REST endpoint, for `REST_ENDPOINT`
+    pub fn rest_endpoint(&self) -> String {
+        format!("http://{}/api/v3", self.rest_addr)
+    }
+
+    /// Default configuration streaming `symbols` from this mock, with
+    /// reconnects quick enough for tests
+    pub fn config(&self, symbols: &[&str]) -> Config {
+        Config {
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            ws_endpoint: self.ws_endpoint(),
+            rest_endpoint: self.rest_endpoint(),
+            reconnect_delay_ms: 10,
+            exchange_info_enabled: false,
+            ..Config::default()
+        }
+    }
+
+    /// Script the next WebSocket connection not scripted yet
+    pub fn session(&self, session: Session) {
+        self.shared.sessions.lock().unwrap().push_back(session);
+    }
+
+    /// Queue a depth snapshot of `symbol`
+    pub fn snapshot(
+        &self,
+        symbol: &str,
+        last_update_id: u64,
+        bids: &[Level<'_>],
+        asks: &[Level<'_>],
+    ) {
+        let body = serde_json::json!({
+            "lastUpdateId": last_update_id,
+            "bids": bids,
+            "asks": asks,
+        });
+        self.shared
+            .snapshots
+            .lock()
+            .unwrap()
+            .entry(symbol.to_string())
+            .or_default()
+            .push_back(body.to_string());
+    }
+
+    /// Depth snapshots of `symbol` requested so far
+    pub fn snapshot_requests(&self, symbol: &str) -> usize {
+        let requests = self.shared.snapshot_requests.lock().unwrap();
+        requests.get(symbol).copied().unwrap_or(0)
+    }
+
+    /// WebSocket connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.shared.connections.lock().unwrap().len()
+    }
+
+    /// Request target (`/stream?streams=...`) of each connection so far
+    pub fn connection_targets(&self) -> Vec<String> {
+        self.shared.connections.lock().unwrap().clone()
+    }
+
+    /// SUBSCRIBE and UNSUBSCRIBE requests received so far
+    pub fn subscribe_requests(&self) -> usize {
+        self.shared.subscribe_requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Shared state of a feed on `config` with its book tasks running, and the
+/// resync requests of those tasks for [`WebSocketManager::with_resyncs`]
+///
+/// The feed's timers run on `time`: with [`Clock::manual`], reconnect
+/// backoff, snapshot retries and the staleness watchdog wait until the test
+/// advances it. Nothing is published: the publisher's connection tasks
+/// aren't started.
+///
+/// [`WebSocketManager::with_resyncs`]: crate::WebSocketManager::with_resyncs
+pub async fn app_state(
+    config: Config,
+    time: Clock,
+) -> Result<(Arc<AppState>, mpsc::UnboundedReceiver<String>)> {
+    let config = Arc::new(config);
+    let manager = OrderBookManager::with_depth(config.depth_levels)
+        .with_overflow_levels(config.overflow_levels)
+        .with_crossed_policy(config.crossed_book_policy);
+    let (books, book_tasks) = Books::new(manager, &config.symbols);
+    let state = Arc::new(AppState {
+        books,
+        analytics: Arc::new(RwLock::new(TradeAnalytics::default())),
+        volume_profile: Arc::new(RwLock::new(VolumeProfileTracker::new(
+            config.volume_profile_window_secs.max(1) * 1000,
+            config.volume_profile_bucket_bps,
+        ))),
+        publisher: Arc::new(Publisher::new(&config).await?.with_clock(time.clone())),
+        subscriptions: Arc::new(SubscriptionProgress::default()),
+        degradation: Arc::new(Degradation::new(config.degradation_policy())),
+        dead_man: DeadManSwitch::new(config.dead_man_policy()),
+        latency: LatencyTracker::new(Duration::from_secs(config.latency_window_secs.max(1))),
+        heartbeat: Heartbeat::default(),
+        rest: RestClient::from_config(&config),
+        clock: ClockSkew::default(),
+        time,
+        ws_endpoints: Endpoints::new("ws", config.ws_endpoints(), config.endpoint_failover_after),
+        tls: Tls::from_config(&config)?,
+        config,
+    });
+    let resyncs = book_tasks.spawn(state.clone());
+    Ok((state, resyncs))
+}
+
+/// Hand each connection on `listener` to `serve`
+async fn accept<F, Fut>(listener: TcpListener, shared: Arc<Shared>, serve: F)
+where
+    F: Fn(TcpStream, Arc<Shared>) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(serve(stream, shared.clone()));
+    }
+}
+
+/// Play the next session on a WebSocket connection
+async fn serve_ws(stream: TcpStream, shared: Arc<Shared>) {
+    let mut target = String::new();
+    // The callback's error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let handshake = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response| {
+        target = request.uri().to_string();
+        Ok::<Response, ErrorResponse>(response)
+    });
+    let Ok(mut ws) = handshake.await else {
+        return;
+    };
+    // The bare endpoint sends payloads without the combined-stream wrapper
+    let bare = target.starts_with("/ws");
+    shared.connections.lock().unwrap().push(target);
+    let session = shared
+        .sessions
+        .lock()
+        .unwrap()
+        .pop_front()
+        .unwrap_or_default();
+
+    for step in session.steps {
+        let open = match step {
+            Step::Send(text) if bare => ws.send(Message::Text(unwrap(text))).await.is_ok(),
+            Step::Send(text) => ws.send(Message::Text(text)).await.is_ok(),
+            Step::Pause(duration) => answer(&mut ws, &shared, Some(duration)).await,
+            Step::Disconnect => return,
+        };
+        if !open {
+            return;
+        }
+    }
+    answer(&mut ws, &shared, None).await;
+}
+
+/// Answer the client's control requests for `duration`, or until it
+/// disconnects; false once it did
+async fn answer(
+    ws: &mut WebSocketStream<TcpStream>,
+    shared: &Shared,
+    duration: Option<Duration>,
+) -> bool {
+    let deadline = duration.map(|d| tokio::time::Instant::now() + d);
+    loop {
+        let next = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, ws.next()).await {
+                Ok(next) => next,
+                Err(_) => return true,
+            },
+            None => ws.next().await,
+        };
+        let text = match next {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return false,
+            // Pings are answered by tungstenite itself
+            Some(Ok(_)) => continue,
+        };
+        let Ok(request) = serde_json::from_str::<serde_json::Value>(&text) else {
+            continue;
+        };
+        if matches!(
+            request["method"].as_str(),
+            Some("SUBSCRIBE" | "UNSUBSCRIBE")
+        ) {
+            shared.subscribe_requests.fetch_add(1, Ordering::Relaxed);
+            let reply = serde_json::json!({ "result": null, "id": request["id"] });
+            if ws.send(Message::Text(reply.to_string())).await.is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+/// Answer one plain HTTP request: depth snapshots, 404 otherwise
+async fn serve_rest(mut stream: TcpStream, shared: Arc<Shared>) {
+    let mut reader = BufReader::new(&mut stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    // Skip the headers; requests are GETs without a body
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line.trim_end().is_empty() => break,
+            Ok(_) => {}
+        }
+    }
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, body) = route(target, &shared);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Status line and body answering a REST request for `target`
+fn route(target: &str, shared: &Shared) -> (&'static str, String) {
+    let Ok(url) = reqwest::Url::parse(&format!("http://mock{}", target)) else {
+        return ("400 Bad Request", String::new());
+    };
+    if !url.path().ends_with("/depth") {
+        return (
+            "404 Not Found",
+            r#"{"code":-1,"msg":"Not found"}"#.to_string(),
+        );
+    }
+    let symbol = url
+        .query_pairs()
+        .find(|(key, _)| key == "symbol")
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default();
+    *shared
+        .snapshot_requests
+        .lock()
+        .unwrap()
+        .entry(symbol.clone())
+        .or_default() += 1;
+
+    let mut snapshots = shared.snapshots.lock().unwrap();
+    let Some(queue) = snapshots.get_mut(&symbol) else {
+        return (
+            "400 Bad Request",
+            r#"{"code":-1121,"msg":"Invalid symbol."}"#.to_string(),
+        );
+    };
+    let body = match queue.len() {
+        0 => String::new(),
+        1 => queue[0].clone(),
+        _ => queue.pop_front().unwrap_or_default(),
+    };
+    ("200 OK", body)
+}
+
+/// Combined-stream message of `stream` carrying `data`
+fn combined(stream: &str, data: serde_json::Value) -> String {
+    format!(r#"{{"stream":"{}","data":{}}}"#, stream, data)
+}
+
+/// Payload of a combined-stream message; anything else as is
+fn unwrap(text: String) -> String {
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(message) if message.get("stream").is_some() => message["data"].to_string(),
+        _ => text,
+    }
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
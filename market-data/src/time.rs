@@ -0,0 +1,157 @@
+//! Time source of the feed's timers
+//!
+//! The WebSocket manager, the staleness watchdog, alignment and conflation
+//! read the time and wait on deadlines through a [`Clock`]. In production
+//! it's the wall clock. Tests use a manual clock that stands still until
+//! advanced, and a replay can advance one to each recorded receive time
+//! ([`Clock::advance_to_micros`]), so staleness, throttling and reconnect
+//! backoff play out the same on every run, without waiting in real time.
+//!
+//! Connection keepalive and the receive stamps of socket reads stay on the
+//! wall clock: they measure the network, not the feed.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Wall clock, or a manual one shared by its clones
+#[derive(Debug, Clone, Default)]
+pub struct Clock {
+    manual: Option<Arc<Manual>>,
+}
+
+#[derive(Debug)]
+struct Manual {
+    /// `Instant` the manual clock started at
+    origin: Instant,
+    /// Unix time it started at (microseconds)
+    epoch_us: u64,
+    /// Time advanced since (microseconds); sleepers wait on it
+    elapsed_us: watch::Sender<u64>,
+}
+
+impl Clock {
+    /// The system clock
+    pub fn wall() -> Self {
+        Self::default()
+    }
+
+    /// A clock reading `epoch_us` microseconds since the Unix epoch until
+    /// advanced
+    pub fn manual(epoch_us: u64) -> Self {
+        Self {
+            manual: Some(Arc::new(Manual {
+                origin: Instant::now(),
+                epoch_us,
+                elapsed_us: watch::channel(0).0,
+            })),
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        self.manual.is_some()
+    }
+
+    /// Monotonic time, for deadlines
+    pub fn now(&self) -> Instant {
+        match &self.manual {
+            Some(manual) => manual.origin + Duration::from_micros(*manual.elapsed_us.borrow()),
+            None => Instant::now(),
+        }
+    }
+
+    /// Time since the Unix epoch in microseconds
+    pub fn now_micros(&self) -> u64 {
+        match &self.manual {
+            Some(manual) => manual.epoch_us + *manual.elapsed_us.borrow(),
+            None => chrono::Utc::now().timestamp_micros() as u64,
+        }
+    }
+
+    /// Time since the Unix epoch in milliseconds
+    pub fn now_millis(&self) -> u64 {
+        self.now_micros() / 1000
+    }
+
+    /// Move a manual clock forward, waking the sleeps it passes; no effect
+    /// on the wall clock
+    pub fn advance(&self, by: Duration) {
+        if let Some(manual) = &self.manual {
+            let by = by.as_micros() as u64;
+            manual.elapsed_us.send_modify(|elapsed| *elapsed += by);
+        }
+    }
+
+    /// Move a manual clock forward to `epoch_us`, if that's ahead of it
+    pub fn advance_to_micros(&self, epoch_us: u64) {
+        if let Some(manual) = &self.manual {
+            let target = epoch_us.saturating_sub(manual.epoch_us);
+            manual.elapsed_us.send_if_modified(|elapsed| {
+                let ahead = target > *elapsed;
+                if ahead {
+                    *elapsed = target;
+                }
+                ahead
+            });
+        }
+    }
+
+    /// Sleep for `duration` from now, as of the call rather than the first
+    /// poll
+    pub fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + '_ {
+        self.sleep_until(self.now() + duration)
+    }
+
+    /// Sleep until the clock reads `deadline`
+    pub async fn sleep_until(&self, deadline: Instant) {
+        match &self.manual {
+            Some(manual) => {
+                let target = deadline
+                    .saturating_duration_since(manual.origin)
+                    .as_micros() as u64;
+                let mut elapsed = manual.elapsed_us.subscribe();
+                let _ = elapsed.wait_for(|elapsed| *elapsed >= target).await;
+            }
+            None => tokio::time::sleep_until(deadline.into()).await,
+        }
+    }
+
+    /// Sleep until the deadline, or forever when there is none
+    pub async fn sleep_until_deadline(&self, deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => self.sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_wakes_sleeps_only_when_advanced() {
+        let clock = Clock::manual(1_700_000_000_000_000);
+        let sleeping = tokio::spawn({
+            let clock = clock.clone();
+            let wake_at = clock.now() + Duration::from_secs(5);
+            async move { clock.sleep_until(wake_at).await }
+        });
+
+        clock.advance(Duration::from_secs(4));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!sleeping.is_finished());
+        assert_eq!(clock.now_millis(), 1_700_000_004_000);
+
+        clock.advance_to_micros(1_700_000_005_000_000);
+        tokio::time::timeout(Duration::from_secs(1), sleeping)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Never backwards
+        clock.advance_to_micros(1_700_000_001_000_000);
+        assert_eq!(clock.now_micros(), 1_700_000_005_000_000);
+    }
+}
@@ -0,0 +1,290 @@
+//! Rolling-window trade metrics
+//!
+//! Keeps the trades of the last `window` per symbol and maintains running
+//! aggregates over them: trade counts, buy/sell and signed volume, average
+//! trade size, the share of trades where the buyer was the maker, and trade
+//! intensity. Unlike the day-anchored `analytics`, the window slides with
+//! the trade stream, so the numbers describe current activity. The latest
+//! `TradeMetrics` are attached to each published book state.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use crate::event::Trade;
+use crate::orderbook::Side;
+
+/// Trade activity over a rolling window
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeMetrics {
+    /// Window length in milliseconds
+    pub window_ms: u64,
+
+    /// Trades in the window
+    pub trade_count: u64,
+
+    /// Trades where the buyer was the taker
+    pub buy_count: u64,
+
+    /// Trades where the seller was the taker
+    pub sell_count: u64,
+
+    /// Taker buy volume (base asset)
+    pub buy_volume: Decimal,
+
+    /// Taker sell volume (base asset)
+    pub sell_volume: Decimal,
+
+    /// Taker buy minus taker sell volume
+    pub signed_volume: Decimal,
+
+    /// Mean trade size (base asset)
+    pub avg_trade_size: Option<Decimal>,
+
+    /// Share of trades where the buyer was the maker (taker sells)
+    pub buyer_maker_ratio: Option<Decimal>,
+
+    /// Trades per second over the window
+    pub trades_per_sec: Decimal,
+
+    /// Analytics were shed under load within the window, so the numbers
+    /// are short of the trades that went uncounted
+    pub partial: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WindowTrade {
+    time: u64,
+    quantity: Decimal,
+    taker_buy: bool,
+}
+
+/// Rolling aggregates for one symbol
+#[derive(Debug, Default)]
+struct TradeWindow {
+    trades: VecDeque<WindowTrade>,
+    buy_count: u64,
+    buy_volume: Decimal,
+    sell_volume: Decimal,
+}
+
+impl TradeWindow {
+    fn push(&mut self, trade: WindowTrade) {
+        if trade.taker_buy {
+            self.buy_count += 1;
+            self.buy_volume += trade.quantity;
+        } else {
+            self.sell_volume += trade.quantity;
+        }
+        self.trades.push_back(trade);
+    }
+
+    /// Drop trades at or before `cutoff`
+    fn evict(&mut self, cutoff: u64) {
+        while let Some(trade) = self.trades.front().filter(|t| t.time <= cutoff).copied() {
+            self.trades.pop_front();
+            if trade.taker_buy {
+                self.buy_count -= 1;
+                self.buy_volume -= trade.quantity;
+            } else {
+                self.sell_volume -= trade.quantity;
+            }
+        }
+    }
+
+    fn metrics(&self, window_ms: u64, partial: bool) -> TradeMetrics {
+        let trade_count = self.trades.len() as u64;
+        let sell_count = trade_count - self.buy_count;
+        let count = Decimal::from(trade_count);
+        let ratio = |value: Decimal| (trade_count > 0).then(|| value / count);
+
+        TradeMetrics {
+            window_ms,
+            trade_count,
+            buy_count: self.buy_count,
+            sell_count,
+            buy_volume: self.buy_volume,
+            sell_volume: self.sell_volume,
+            signed_volume: self.buy_volume - self.sell_volume,
+            avg_trade_size: ratio(self.buy_volume + self.sell_volume),
+            buyer_maker_ratio: ratio(Decimal::from(sell_count)),
+            trades_per_sec: count * Decimal::from(1000) / Decimal::from(window_ms.max(1)),
+            partial,
+        }
+    }
+}
+
+/// Rolling trade metrics for all symbols
+#[derive(Debug)]
+pub struct TradeMetricsTracker {
+    window_ms: u64,
+    /// Window overrides per symbol
+    symbol_windows: HashMap<String, u64>,
+    symbols: HashMap<String, TradeWindow>,
+    /// Analytics sheds seen, and when trades were last counted again
+    /// after one (exchange time, milliseconds)
+    sheds_seen: u64,
+    gap_at_ms: Option<u64>,
+}
+
+impl TradeMetricsTracker {
+    /// Create a tracker aggregating the last `window_ms` of trades
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window_ms: window_ms.max(1),
+            symbol_windows: HashMap::new(),
+            symbols: HashMap::new(),
+            sheds_seen: 0,
+            gap_at_ms: None,
+        }
+    }
+
+    /// Aggregate these symbols' trades over their own windows (ms)
+    pub fn with_symbol_windows(mut self, windows: HashMap<String, u64>) -> Self {
+        self.symbol_windows = windows
+            .into_iter()
+            .map(|(symbol, window_ms)| (symbol, window_ms.max(1)))
+            .collect();
+        self
+    }
+
+    /// Window of a symbol's metrics (ms)
+    pub fn window_ms(&self, symbol: &str) -> u64 {
+        window_for(&self.symbol_windows, self.window_ms, symbol)
+    }
+
+    /// Add a trade to its symbol's window
+    pub fn on_trade(&mut self, trade: &Trade) {
+        let window_ms = self.window_ms(&trade.symbol);
+        let window = self.symbols.entry(trade.symbol.clone()).or_default();
+        // Bid-side takers are buyers lifting the ask
+        window.push(WindowTrade {
+            time: trade.trade_time,
+            quantity: trade.quantity,
+            taker_buy: trade.taker_side == Side::Bid,
+        });
+        window.evict(trade.trade_time.saturating_sub(window_ms));
+    }
+
+    /// Catch up with `sheds`, the times analytics have been shed so far;
+    /// if it grew, trades before `now_ms` went uncounted and windows
+    /// reaching back past it are `partial`
+    pub fn note_sheds(&mut self, sheds: u64, now_ms: u64) {
+        if sheds != self.sheds_seen {
+            self.sheds_seen = sheds;
+            self.gap_at_ms = Some(now_ms);
+        }
+    }
+
+    /// Metrics for a symbol over the window ending at `now_ms` (exchange
+    /// time); `None` before its first trade
+    pub fn metrics(&mut self, symbol: &str, now_ms: u64) -> Option<TradeMetrics> {
+        let window_ms = self.window_ms(symbol);
+        let partial = self.partial(window_ms, now_ms);
+        let window = self.symbols.get_mut(symbol)?;
+        window.evict(now_ms.saturating_sub(window_ms));
+        Some(window.metrics(window_ms, partial))
+    }
+
+    /// Metrics for all symbols over the window ending at `now_ms`
+    pub fn all(&mut self, now_ms: u64) -> HashMap<String, TradeMetrics> {
+        let gap_at_ms = self.gap_at_ms;
+        self.symbols
+            .iter_mut()
+            .map(|(symbol, window)| {
+                let window_ms = window_for(&self.symbol_windows, self.window_ms, symbol);
+                let partial = gap_at_ms.is_some_and(|at| now_ms.saturating_sub(window_ms) < at);
+                window.evict(now_ms.saturating_sub(window_ms));
+                (symbol.clone(), window.metrics(window_ms, partial))
+            })
+            .collect()
+    }
+
+    /// Whether the window ending at `now_ms` reaches back past a gap
+    fn partial(&self, window_ms: u64, now_ms: u64) -> bool {
+        self.gap_at_ms
+            .is_some_and(|at| now_ms.saturating_sub(window_ms) < at)
+    }
+}
+
+fn window_for(overrides: &HashMap<String, u64>, default_ms: u64, symbol: &str) -> u64 {
+    overrides.get(symbol).copied().unwrap_or(default_ms)
+}
+
+impl Default for TradeMetricsTracker {
+    fn default() -> Self {
+        Self::new(60_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use rust_decimal_macros::dec;
+
+    fn trade(trade_time: u64, quantity: Decimal, taker_side: Side) -> Trade {
+        Trade {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            event_time: trade_time,
+            trade_id: trade_time,
+            price: dec!(100),
+            quantity,
+            taker_side,
+            trade_time,
+        }
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let mut tracker = TradeMetricsTracker::new(1_000);
+        tracker.on_trade(&trade(1_000, dec!(2), Side::Bid));
+        tracker.on_trade(&trade(1_500, dec!(1), Side::Ask));
+        tracker.on_trade(&trade(1_800, dec!(3), Side::Ask));
+
+        let metrics = tracker.metrics("BTCUSDT", 1_900).unwrap();
+        assert_eq!(metrics.trade_count, 3);
+        assert_eq!(metrics.signed_volume, dec!(-2));
+        assert_eq!(metrics.avg_trade_size, Some(dec!(2)));
+        assert_eq!(metrics.trades_per_sec, dec!(3));
+
+        // The first trade leaves the window
+        let metrics = tracker.metrics("BTCUSDT", 2_000).unwrap();
+        assert_eq!(metrics.trade_count, 2);
+        assert_eq!(metrics.buy_volume, Decimal::ZERO);
+        assert_eq!(metrics.buyer_maker_ratio, Some(dec!(1)));
+
+        let metrics = tracker.metrics("BTCUSDT", 10_000).unwrap();
+        assert_eq!(metrics.trade_count, 0);
+        assert_eq!(metrics.avg_trade_size, None);
+        assert!(tracker.metrics("ETHUSDT", 10_000).is_none());
+    }
+
+    #[test]
+    fn test_symbol_window_override() {
+        let mut tracker = TradeMetricsTracker::new(1_000)
+            .with_symbol_windows(HashMap::from([("BTCUSDT".to_string(), 5_000)]));
+        tracker.on_trade(&trade(1_000, dec!(1), Side::Bid));
+
+        let metrics = tracker.metrics("BTCUSDT", 4_000).unwrap();
+        assert_eq!(metrics.window_ms, 5_000);
+        assert_eq!(metrics.trade_count, 1);
+        assert_eq!(tracker.window_ms("ETHUSDT"), 1_000);
+    }
+
+    #[test]
+    fn test_windows_spanning_a_gap_are_partial() {
+        let mut tracker = TradeMetricsTracker::new(1_000);
+        tracker.on_trade(&trade(1_000, dec!(1), Side::Bid));
+        assert!(!tracker.metrics("BTCUSDT", 1_500).unwrap().partial);
+
+        // Analytics resume at 3_000 after being shed
+        tracker.note_sheds(1, 3_000);
+        tracker.note_sheds(1, 3_200);
+        tracker.on_trade(&trade(3_000, dec!(1), Side::Bid));
+        assert!(tracker.metrics("BTCUSDT", 3_500).unwrap().partial);
+        assert!(tracker.all(3_999)["BTCUSDT"].partial);
+        assert!(!tracker.metrics("BTCUSDT", 4_000).unwrap().partial);
+    }
+}
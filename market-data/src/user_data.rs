@@ -0,0 +1,526 @@
+//! Authenticated user data stream
+//!
+//! With `BINANCE_API_KEY` set, the handler also follows the account's user
+//! data stream. A listenKey is created over REST and kept alive every
+//! `USER_DATA_KEEPALIVE_SECS` (Binance expires it after 60 minutes without
+//! a keepalive), and the stream at `<WS_ENDPOINT>/<listenKey>` is read for
+//! `executionReport` (order updates and fills) and `outboundAccountPosition`
+//! (balance changes) events. With `BINANCE_API_SECRET` also set, each
+//! stream starts with a signed `account` snapshot published as an
+//! [`AccountPosition`] of every balance. Events are normalized into [`OrderEvent`]s and
+//! [`AccountPosition`]s and published on the IPC socket. A failed keepalive,
+//! a `listenKeyExpired` event or a dropped connection starts over with a new
+//! key after a backoff; the key is closed on shutdown.
+
+use futures_util::StreamExt;
+use prometheus::{IntCounterVec, Opts};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::{info, warn};
+
+use crate::error::{MarketDataError, Result};
+use crate::event::Venue;
+use crate::orderbook::Side;
+use crate::rest::Auth;
+use crate::shutdown::Shutdown;
+use crate::AppState;
+
+/// REST path of listenKey management
+const LISTEN_KEY_PATH: &str = "userDataStream";
+
+/// Weight of each listenKey request
+const LISTEN_KEY_WEIGHT: u32 = 2;
+
+/// REST path of the account snapshot
+const ACCOUNT_PATH: &str = "account";
+
+/// Weight of an account snapshot
+const ACCOUNT_WEIGHT: u32 = 20;
+
+/// Longest wait before starting over
+const MAX_BACKOFF_MS: u64 = 60_000;
+
+static EVENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "user_data_events_total",
+            "User data stream events received, by event type",
+        ),
+        &["event"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Update of one of the account's orders
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub venue: Venue,
+    pub symbol: String,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub side: Side,
+    /// `LIMIT`, `MARKET`, `STOP_LOSS_LIMIT`...
+    pub order_type: String,
+    /// `GTC`, `IOC` or `FOK`
+    pub time_in_force: String,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    /// What happened: `NEW`, `CANCELED`, `REPLACED`, `REJECTED`, `TRADE` or
+    /// `EXPIRED`
+    pub execution_type: String,
+    /// Order status after the event: `NEW`, `PARTIALLY_FILLED`, `FILLED`,
+    /// `CANCELED`, `REJECTED` or `EXPIRED`
+    pub status: String,
+    /// Why the order was rejected, `NONE` otherwise
+    pub reject_reason: String,
+    pub cumulative_filled: Decimal,
+    /// The fill, when the event is one
+    pub fill: Option<Fill>,
+    /// Matching engine time (milliseconds)
+    pub transaction_time: u64,
+}
+
+/// Execution of part of an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade_id: u64,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub commission: Decimal,
+    pub commission_asset: Option<String>,
+    /// Whether the order rested on the book
+    pub is_maker: bool,
+}
+
+/// Balances that changed in an account update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountPosition {
+    pub venue: Venue,
+    /// Exchange event time (milliseconds)
+    pub event_time: u64,
+    /// Time of the last account update (milliseconds)
+    pub last_update_time: u64,
+    pub balances: Vec<Balance>,
+}
+
+/// Balance of one asset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    pub asset: String,
+    pub free: Decimal,
+    pub locked: Decimal,
+}
+
+/// Event on the user data stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserEvent {
+    Order(Box<OrderEvent>),
+    Account(AccountPosition),
+    /// The listenKey expired; the stream ends
+    ListenKeyExpired,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "e")]
+enum RawEvent {
+    #[serde(rename = "executionReport")]
+    ExecutionReport(Box<ExecutionReport>),
+    #[serde(rename = "outboundAccountPosition")]
+    AccountPosition(RawAccountPosition),
+    #[serde(rename = "listenKeyExpired")]
+    ListenKeyExpired,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecutionReport {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "c")]
+    client_order_id: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "o")]
+    order_type: String,
+    #[serde(rename = "f")]
+    time_in_force: String,
+    #[serde(rename = "q")]
+    quantity: Decimal,
+    #[serde(rename = "p")]
+    price: Decimal,
+    #[serde(rename = "x")]
+    execution_type: String,
+    #[serde(rename = "X")]
+    status: String,
+    #[serde(rename = "r")]
+    reject_reason: String,
+    #[serde(rename = "i")]
+    order_id: u64,
+    #[serde(rename = "l")]
+    last_quantity: Decimal,
+    #[serde(rename = "z")]
+    cumulative_filled: Decimal,
+    #[serde(rename = "L")]
+    last_price: Decimal,
+    #[serde(rename = "n")]
+    commission: Decimal,
+    #[serde(rename = "N")]
+    commission_asset: Option<String>,
+    #[serde(rename = "T")]
+    transaction_time: u64,
+    /// -1 unless the event is a fill
+    #[serde(rename = "t")]
+    trade_id: i64,
+    #[serde(rename = "m")]
+    is_maker: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAccountPosition {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "u")]
+    last_update_time: u64,
+    #[serde(rename = "B")]
+    balances: Vec<RawBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAccount {
+    #[serde(rename = "updateTime")]
+    update_time: u64,
+    balances: Vec<RawBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawBalance {
+    #[serde(rename = "a", alias = "asset")]
+    asset: String,
+    #[serde(rename = "f", alias = "free")]
+    free: Decimal,
+    #[serde(rename = "l", alias = "locked")]
+    locked: Decimal,
+}
+
+impl From<ExecutionReport> for OrderEvent {
+    fn from(report: ExecutionReport) -> Self {
+        let fill = (report.execution_type == "TRADE").then(|| Fill {
+            trade_id: report.trade_id.max(0) as u64,
+            price: report.last_price,
+            quantity: report.last_quantity,
+            commission: report.commission,
+            commission_asset: report.commission_asset,
+            is_maker: report.is_maker,
+        });
+        OrderEvent {
+            venue: Venue::Binance,
+            symbol: report.symbol,
+            event_time: report.event_time,
+            order_id: report.order_id,
+            client_order_id: report.client_order_id,
+            side: if report.side == "SELL" {
+                Side::Ask
+            } else {
+                Side::Bid
+            },
+            order_type: report.order_type,
+            time_in_force: report.time_in_force,
+            price: report.price,
+            quantity: report.quantity,
+            execution_type: report.execution_type,
+            status: report.status,
+            reject_reason: report.reject_reason,
+            cumulative_filled: report.cumulative_filled,
+            fill,
+            transaction_time: report.transaction_time,
+        }
+    }
+}
+
+impl From<RawAccountPosition> for AccountPosition {
+    fn from(position: RawAccountPosition) -> Self {
+        AccountPosition {
+            venue: Venue::Binance,
+            event_time: position.event_time,
+            last_update_time: position.last_update_time,
+            balances: position.balances.into_iter().map(Balance::from).collect(),
+        }
+    }
+}
+
+impl From<RawBalance> for Balance {
+    fn from(balance: RawBalance) -> Self {
+        Balance {
+            asset: balance.asset,
+            free: balance.free,
+            locked: balance.locked,
+        }
+    }
+}
+
+impl From<RawAccount> for AccountPosition {
+    fn from(account: RawAccount) -> Self {
+        AccountPosition {
+            venue: Venue::Binance,
+            event_time: account.update_time,
+            last_update_time: account.update_time,
+            balances: account
+                .balances
+                .into_iter()
+                .filter(|balance| !balance.free.is_zero() || !balance.locked.is_zero())
+                .map(Balance::from)
+                .collect(),
+        }
+    }
+}
+
+/// Parse a user data stream message; events the handler doesn't use have
+/// none
+pub fn parse(raw: &str) -> Result<Option<UserEvent>> {
+    Ok(match serde_json::from_str(raw)? {
+        RawEvent::ExecutionReport(report) => Some(UserEvent::Order(Box::new((*report).into()))),
+        RawEvent::AccountPosition(position) => Some(UserEvent::Account(position.into())),
+        RawEvent::ListenKeyExpired => Some(UserEvent::ListenKeyExpired),
+        RawEvent::Other => None,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct ListenKeyResponse {
+    #[serde(rename = "listenKey")]
+    listen_key: String,
+}
+
+/// Follow the user data stream of the account the REST client's credentials
+/// belong to until shutdown
+pub async fn run(state: Arc<AppState>, shutdown: Shutdown) {
+    LazyLock::force(&EVENTS);
+    let mut failures = 0;
+    loop {
+        let started = match create_listen_key(&state).await {
+            Ok(listen_key) => {
+                let result = tokio::select! {
+                    result = follow(&state, &listen_key) => result,
+                    _ = shutdown.wait() => {
+                        close_listen_key(&state, &listen_key).await;
+                        return;
+                    }
+                };
+                if let Err(e) = result {
+                    warn!(error = %e, "User data stream stopped");
+                }
+                true
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to create listenKey");
+                false
+            }
+        };
+
+        failures = if started { 0 } else { failures + 1 };
+        let delay = state
+            .config
+            .reconnect_delay_ms
+            .saturating_mul(1 << failures.min(6))
+            .min(MAX_BACKOFF_MS);
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(delay)) => {}
+            _ = shutdown.wait() => return,
+        }
+    }
+}
+
+async fn create_listen_key(state: &AppState) -> Result<String> {
+    let body = state
+        .rest
+        .request(
+            Method::POST,
+            LISTEN_KEY_PATH,
+            &[],
+            LISTEN_KEY_WEIGHT,
+            Auth::ApiKey,
+        )
+        .await?;
+    let response: ListenKeyResponse = serde_json::from_str(&body)?;
+    Ok(response.listen_key)
+}
+
+async fn keep_alive(state: &AppState, listen_key: &str) -> Result<()> {
+    state
+        .rest
+        .request(
+            Method::PUT,
+            LISTEN_KEY_PATH,
+            &[("listenKey", listen_key)],
+            LISTEN_KEY_WEIGHT,
+            Auth::ApiKey,
+        )
+        .await?;
+    Ok(())
+}
+
+async fn close_listen_key(state: &AppState, listen_key: &str) {
+    let result = state
+        .rest
+        .request(
+            Method::DELETE,
+            LISTEN_KEY_PATH,
+            &[("listenKey", listen_key)],
+            LISTEN_KEY_WEIGHT,
+            Auth::ApiKey,
+        )
+        .await;
+    if let Err(e) = result {
+        warn!(error = %e, "Failed to close listenKey");
+    }
+}
+
+/// Balances of the account, from a signed snapshot
+async fn fetch_account(state: &AppState) -> Result<AccountPosition> {
+    let body = state
+        .rest
+        .request(Method::GET, ACCOUNT_PATH, &[], ACCOUNT_WEIGHT, Auth::Signed)
+        .await?;
+    let account: RawAccount = serde_json::from_str(&body)?;
+    Ok(account.into())
+}
+
+/// Read the stream of `listen_key`, keeping the key alive, until it ends
+async fn follow(state: &AppState, listen_key: &str) -> Result<()> {
+    let url = format!("{}/{}", state.config.ws_endpoint, listen_key);
+    let (mut stream, _) = state.tls.connect(&url).await?;
+    info!("User data stream connected");
+
+    // Balances changed before the stream started are only in a snapshot
+    if state.rest.credentials().is_some_and(|c| c.can_sign()) {
+        match fetch_account(state).await {
+            Ok(position) => {
+                if let Err(e) = state.publisher.publish_account(&position).await {
+                    warn!(error = %e, "Failed to publish account snapshot");
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to fetch account snapshot"),
+        }
+    }
+
+    let period = Duration::from_secs(state.config.user_data_keepalive_secs.max(1));
+    let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = keepalive.tick() => {
+                keep_alive(state, listen_key).await?;
+                continue;
+            }
+        };
+        let text = match message {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | None => {
+                return Err(MarketDataError::WebSocketConnection(
+                    "User data stream closed".to_string(),
+                ))
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+        };
+
+        let event = match parse(&text) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(error = %e, "Failed to parse user data event");
+                continue;
+            }
+        };
+        let result = match &event {
+            UserEvent::Order(order) => {
+                EVENTS.with_label_values(&["order"]).inc();
+                state.publisher.publish_order(order).await
+            }
+            UserEvent::Account(position) => {
+                EVENTS.with_label_values(&["account"]).inc();
+                state.publisher.publish_account(position).await
+            }
+            UserEvent::ListenKeyExpired => {
+                EVENTS.with_label_values(&["listen_key_expired"]).inc();
+                return Err(MarketDataError::WebSocketConnection(
+                    "listenKey expired".to_string(),
+                ));
+            }
+        };
+        if let Err(e) = result {
+            warn!(error = %e, "Failed to publish user data event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_user_events() {
+        let fill = r#"{"e":"executionReport","E":1499405658658,"s":"ETHBTC","c":"mUvoqJxFIILMdfAW5iGSOW","S":"SELL","o":"LIMIT","f":"GTC","q":"1.00000000","p":"0.10264410","P":"0.00000000","F":"0.00000000","g":-1,"C":"","x":"TRADE","X":"PARTIALLY_FILLED","r":"NONE","i":4293153,"l":"0.40000000","z":"0.40000000","L":"0.10264400","n":"0.00004106","N":"BTC","T":1499405658657,"t":1234,"I":8641984,"w":false,"m":true,"M":false,"O":1499405658657,"Z":"0.04105760","Y":"0.04105760","Q":"0.00000000"}"#;
+        let Some(UserEvent::Order(order)) = parse(fill).unwrap() else {
+            panic!("Expected an order event");
+        };
+        assert_eq!(order.side, Side::Ask);
+        assert_eq!(order.status, "PARTIALLY_FILLED");
+        assert_eq!(order.cumulative_filled, dec!(0.4));
+        assert_eq!(
+            order.fill,
+            Some(Fill {
+                trade_id: 1234,
+                price: dec!(0.102644),
+                quantity: dec!(0.4),
+                commission: dec!(0.00004106),
+                commission_asset: Some("BTC".to_string()),
+                is_maker: true,
+            })
+        );
+
+        // Order updates other than fills carry none
+        let new = fill
+            .replace(r#""x":"TRADE""#, r#""x":"NEW""#)
+            .replace(r#""N":"BTC""#, r#""N":null"#)
+            .replace(r#""t":1234"#, r#""t":-1"#);
+        let Some(UserEvent::Order(order)) = parse(&new).unwrap() else {
+            panic!("Expected an order event");
+        };
+        assert!(order.fill.is_none());
+
+        let account = r#"{"e":"outboundAccountPosition","E":1564034571105,"u":1564034571073,"B":[{"a":"ETH","f":"10000.000000","l":"0.000000"}]}"#;
+        let Some(UserEvent::Account(position)) = parse(account).unwrap() else {
+            panic!("Expected an account event");
+        };
+        assert_eq!(position.balances[0].asset, "ETH");
+        assert_eq!(position.balances[0].free, dec!(10000));
+
+        let snapshot = r#"{"makerCommission":15,"updateTime":123456789,"accountType":"SPOT","balances":[{"asset":"BTC","free":"4723846.89208129","locked":"0.00000000"},{"asset":"LTC","free":"0.00000000","locked":"0.00000000"}],"permissions":["SPOT"]}"#;
+        let position = AccountPosition::from(serde_json::from_str::<RawAccount>(snapshot).unwrap());
+        assert_eq!(position.last_update_time, 123456789);
+        assert_eq!(position.balances.len(), 1);
+        assert_eq!(position.balances[0].free, dec!(4723846.89208129));
+
+        assert_eq!(
+            parse(r#"{"e":"listenKeyExpired","E":1576653824250,"listenKey":"abc"}"#).unwrap(),
+            Some(UserEvent::ListenKeyExpired)
+        );
+        assert_eq!(
+            parse(r#"{"e":"balanceUpdate","E":1,"a":"BTC","d":"1","T":1}"#).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Volume profile and liquidity heatmap
+//!
+//! Accumulates, per symbol and price bucket, the volume traded and the
+//! liquidity resting in the book over tumbling windows aligned to multiples
+//! of the window length. Bucket width is `bucket_bps` of the first price
+//! seen in a window, rounded to two significant figures so bucket bounds
+//! stay readable. Resting liquidity is sampled from published states at
+//! most once a second and reported as the average over the samples.
+//!
+//! The tracker is fed from the publisher's live feed; `run` also sends the
+//! in-progress profiles over IPC periodically for visualization tools, and
+//! `GET /volume-profile/:symbol` serves the current and previous windows.
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::event::Trade;
+use crate::orderbook::{Level, OrderBookState, Side};
+use crate::publisher::Publisher;
+
+/// Minimum spacing of resting liquidity samples per symbol
+const SAMPLE_INTERVAL_MS: u64 = 1000;
+
+/// Activity in one price bucket
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileBucket {
+    /// Lower bound of the bucket
+    pub price: Decimal,
+    /// Taker buy volume traded in the bucket
+    pub buy_volume: Decimal,
+    /// Taker sell volume traded in the bucket
+    pub sell_volume: Decimal,
+    /// Average bid quantity resting in the bucket
+    pub bid_liquidity: Decimal,
+    /// Average ask quantity resting in the bucket
+    pub ask_liquidity: Decimal,
+}
+
+/// Volume and liquidity by price over one window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeProfile {
+    pub symbol: String,
+    /// Window start (milliseconds since epoch)
+    pub window_start: u64,
+    pub window_ms: u64,
+    /// Price width of each bucket
+    pub bucket_size: Decimal,
+    /// Whether the window has ended
+    pub complete: bool,
+    /// Buckets with any activity, ascending by price
+    pub buckets: Vec<ProfileBucket>,
+}
+
+#[derive(Debug, Default)]
+struct BucketTotals {
+    buy_volume: Decimal,
+    sell_volume: Decimal,
+    bid_sum: Decimal,
+    ask_sum: Decimal,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: u64,
+    bucket_size: Decimal,
+    buckets: BTreeMap<i64, BucketTotals>,
+    samples: u64,
+    last_sample: Option<u64>,
+}
+
+impl Window {
+    fn bucket(&mut self, price: Decimal) -> Option<&mut BucketTotals> {
+        let index = (price / self.bucket_size).floor().to_i64()?;
+        Some(self.buckets.entry(index).or_default())
+    }
+
+    fn profile(&self, symbol: &str, window_ms: u64, complete: bool) -> VolumeProfile {
+        let samples = Decimal::from(self.samples.max(1));
+        VolumeProfile {
+            symbol: symbol.to_string(),
+            window_start: self.start,
+            window_ms,
+            bucket_size: self.bucket_size,
+            complete,
+            buckets: self
+                .buckets
+                .iter()
+                .map(|(index, totals)| ProfileBucket {
+                    price: Decimal::from(*index) * self.bucket_size,
+                    buy_volume: totals.buy_volume,
+                    sell_volume: totals.sell_volume,
+                    bid_liquidity: totals.bid_sum / samples,
+                    ask_liquidity: totals.ask_sum / samples,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SymbolProfile {
+    current: Option<Window>,
+    previous: Option<VolumeProfile>,
+}
+
+/// Volume profiles for all symbols
+#[derive(Debug)]
+pub struct VolumeProfileTracker {
+    window_ms: u64,
+    bucket_bps: Decimal,
+    symbols: HashMap<String, SymbolProfile>,
+}
+
+impl VolumeProfileTracker {
+    /// Create a tracker with `window_ms` windows and buckets `bucket_bps`
+    /// of price wide
+    pub fn new(window_ms: u64, bucket_bps: Decimal) -> Self {
+        Self {
+            window_ms: window_ms.max(1),
+            bucket_bps,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Add a trade's volume to its bucket
+    pub fn on_trade(&mut self, trade: &Trade) {
+        let Some(window) = self.window(&trade.symbol, trade.trade_time, trade.price) else {
+            return;
+        };
+        if let Some(bucket) = window.bucket(trade.price) {
+            // Bid-side takers are buyers lifting the ask
+            match trade.taker_side {
+                Side::Bid => bucket.buy_volume += trade.quantity,
+                Side::Ask => bucket.sell_volume += trade.quantity,
+            }
+        }
+    }
+
+    /// Sample a state's resting liquidity, unless sampled within the last
+    /// second
+    pub fn on_state(&mut self, state: &OrderBookState) {
+        let Some(price) = state.metrics.mid_price else {
+            return;
+        };
+        let Some(window) = self.window(&state.symbol, state.timestamp, price) else {
+            return;
+        };
+        if window
+            .last_sample
+            .is_some_and(|last| state.timestamp < last + SAMPLE_INTERVAL_MS)
+        {
+            return;
+        }
+        window.last_sample = Some(state.timestamp);
+        window.samples += 1;
+
+        let sides: [(&[Level], Side); 2] = [(&state.bids, Side::Bid), (&state.asks, Side::Ask)];
+        for (levels, side) in sides {
+            for level in levels {
+                if let Some(bucket) = window.bucket(level.price) {
+                    match side {
+                        Side::Bid => bucket.bid_sum += level.quantity,
+                        Side::Ask => bucket.ask_sum += level.quantity,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Close windows that ended by `now_ms`
+    pub fn roll(&mut self, now_ms: u64) {
+        for (symbol, profile) in self.symbols.iter_mut() {
+            if profile
+                .current
+                .as_ref()
+                .is_some_and(|w| now_ms >= w.start + self.window_ms)
+            {
+                Self::close(symbol, profile, self.window_ms);
+            }
+        }
+    }
+
+    /// In-progress profile of a symbol
+    pub fn current(&self, symbol: &str) -> Option<VolumeProfile> {
+        let window = self.symbols.get(symbol)?.current.as_ref()?;
+        Some(window.profile(symbol, self.window_ms, false))
+    }
+
+    /// Profile of a symbol's last completed window
+    pub fn previous(&self, symbol: &str) -> Option<&VolumeProfile> {
+        self.symbols.get(symbol)?.previous.as_ref()
+    }
+
+    /// In-progress profiles of all symbols
+    pub fn all_current(&self) -> Vec<VolumeProfile> {
+        self.symbols
+            .iter()
+            .filter_map(|(symbol, profile)| {
+                let window = profile.current.as_ref()?;
+                Some(window.profile(symbol, self.window_ms, false))
+            })
+            .collect()
+    }
+
+    /// The window containing `time`, opened at `price` if new
+    fn window(&mut self, symbol: &str, time: u64, price: Decimal) -> Option<&mut Window> {
+        let window_ms = self.window_ms;
+        let profile = self.symbols.entry(symbol.to_string()).or_default();
+        let start = time - time % window_ms;
+        match &profile.current {
+            // Late events for a closed window are dropped
+            Some(window) if start < window.start => return None,
+            Some(window) if start > window.start => Self::close(symbol, profile, window_ms),
+            _ => {}
+        }
+
+        if profile.current.is_none() {
+            if profile
+                .previous
+                .as_ref()
+                .is_some_and(|p| start <= p.window_start)
+            {
+                return None;
+            }
+            let bucket_size = (price * self.bucket_bps / Decimal::from(10000)).round_sf(2)?;
+            if bucket_size <= Decimal::ZERO {
+                return None;
+            }
+            profile.current = Some(Window {
+                start,
+                bucket_size,
+                buckets: BTreeMap::new(),
+                samples: 0,
+                last_sample: None,
+            });
+        }
+        profile.current.as_mut()
+    }
+
+    fn close(symbol: &str, profile: &mut SymbolProfile, window_ms: u64) {
+        if let Some(window) = profile.current.take() {
+            profile.previous = Some(window.profile(symbol, window_ms, true));
+        }
+    }
+}
+
+/// Feed `tracker` from the publisher's live feed and publish in-progress
+/// profiles over IPC every `publish_interval`
+pub async fn run(
+    tracker: Arc<RwLock<VolumeProfileTracker>>,
+    publisher: Arc<Publisher>,
+    publish_interval: Duration,
+) {
+    info!("Volume profile tracking enabled");
+    let mut book_rx = publisher.live().subscribe_books();
+    let mut trade_rx = publisher.live().subscribe_trades();
+    let mut ticker = tokio::time::interval(publish_interval);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            state = book_rx.recv() => match state {
+                Ok(state) => tracker.write().await.on_state(&state),
+                // Liquidity is sampled anyway
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            trade = trade_rx.recv() => match trade {
+                Ok(trade) => tracker.write().await.on_trade(&trade),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Volume profile lagging, skipped trades");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = ticker.tick() => {
+                let profiles = {
+                    let mut tracker = tracker.write().await;
+                    tracker.roll(chrono::Utc::now().timestamp_millis() as u64);
+                    tracker.all_current()
+                };
+                for profile in &profiles {
+                    if let Err(e) = publisher.publish_profile(profile).await {
+                        warn!(error = %e, symbol = %profile.symbol, "Failed to publish volume profile");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use crate::orderbook::OrderBookMetrics;
+    use rust_decimal_macros::dec;
+
+    fn trade(trade_time: u64, price: Decimal, taker_side: Side) -> Trade {
+        Trade {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            event_time: trade_time,
+            trade_id: trade_time,
+            price,
+            quantity: dec!(1),
+            taker_side,
+            trade_time,
+        }
+    }
+
+    #[test]
+    fn test_buckets_and_windows() {
+        // 10 bps of 50000 = 50 wide buckets, 60s windows
+        let mut tracker = VolumeProfileTracker::new(60_000, dec!(10));
+        tracker.on_trade(&trade(1_000, dec!(50010), Side::Bid));
+        tracker.on_trade(&trade(2_000, dec!(50040), Side::Ask));
+        tracker.on_trade(&trade(3_000, dec!(49990), Side::Ask));
+
+        let state = |timestamp| OrderBookState {
+            symbol: "BTCUSDT".to_string(),
+            timestamp,
+            last_update_id: 1,
+            bids: vec![Level {
+                price: dec!(49995),
+                quantity: dec!(4),
+            }],
+            asks: vec![],
+            metrics: OrderBookMetrics {
+                mid_price: Some(dec!(50000)),
+                ..Default::default()
+            },
+            provenance: None,
+            trade_metrics: None,
+            instrument: None,
+            checksum: None,
+        };
+        tracker.on_state(&state(4_000));
+        tracker.on_state(&state(4_500)); // within the sample interval
+
+        let profile = tracker.current("BTCUSDT").unwrap();
+        assert_eq!(profile.bucket_size, dec!(50));
+        assert_eq!(profile.buckets.len(), 2);
+        assert_eq!(profile.buckets[0].price, dec!(49950));
+        assert_eq!(profile.buckets[0].sell_volume, dec!(1));
+        assert_eq!(profile.buckets[0].bid_liquidity, dec!(4));
+        assert_eq!(profile.buckets[1].buy_volume, dec!(1));
+        assert_eq!(profile.buckets[1].sell_volume, dec!(1));
+
+        // A trade in the next window closes this one
+        tracker.on_trade(&trade(61_000, dec!(50000), Side::Bid));
+        let previous = tracker.previous("BTCUSDT").unwrap();
+        assert!(previous.complete);
+        assert_eq!(previous.buckets.len(), 2);
+        assert_eq!(tracker.current("BTCUSDT").unwrap().buckets.len(), 1);
+    }
+}
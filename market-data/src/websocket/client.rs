@@ -2,86 +2,385 @@
 //!
 //! Handles connection, subscription, and message reception.
 
+use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::protocol::Message,
-    MaybeTlsStream, WebSocketStream,
-};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::Message;
 use tracing::{debug, error, info, warn};
 
+use super::intake::{Frame, Intake, IntakeQueue};
+use super::keepalive::{Keepalive, KeepaliveFrame, KeepalivePolicy};
+use super::subscription::{
+    PendingSubscriptions, SubscriptionProgress, SubscriptionStatus, MAX_STREAMS_PER_CONNECTION,
+    MAX_SUBSCRIBE_RETRIES, MAX_URL_STREAMS,
+};
+use super::tls::{Tls, WsStream};
+use crate::config::{DepthUpdateSpeed, IntakePolicy, StreamEndpoint};
 use crate::error::{MarketDataError, Result};
-
-type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+use crate::parser::{ExchangeError, SubscriptionAck};
 
 /// WebSocket client for a single connection
 pub struct WebSocketClient {
-    stream: Option<WsStream>,
+    /// Write side of the connection
+    sink: Option<SplitSink<WsStream, Message>>,
+    /// Read side: frames queued by the connection's reader task
+    intake: Option<Intake>,
+    /// Connection label of the intake queue's metrics
+    feed: &'static str,
+    intake_capacity: usize,
+    intake_policy: IntakePolicy,
+    /// Socket receive time of the message `recv` returned last
+    received_at_us: u64,
     endpoint: String,
-    symbols: Vec<String>,
+    tls: Tls,
+    /// Symbols with their depth stream update speed
+    symbols: Vec<(String, DepthUpdateSpeed)>,
+    /// Streams per SUBSCRIBE request; 0 puts all streams in the URL
+    batch_size: usize,
+    /// Delay between SUBSCRIBE requests
+    subscribe_interval: Duration,
+    stream_endpoint: StreamEndpoint,
+    /// Unconfirmed SUBSCRIBE requests on the current connection
+    pending: PendingSubscriptions,
+    /// A request rejected past its retries, failing the connection
+    rejected: Option<String>,
+    progress: Arc<SubscriptionProgress>,
+    /// Pings and pongs owed to the server
+    keepalive: Keepalive,
 }
 
 impl WebSocketClient {
     /// Create a new WebSocket client
-    pub fn new(endpoint: &str, symbols: Vec<String>) -> Self {
+    pub fn new(
+        endpoint: &str,
+        symbols: Vec<(String, DepthUpdateSpeed)>,
+        progress: Arc<SubscriptionProgress>,
+    ) -> Self {
         Self {
-            stream: None,
+            sink: None,
+            intake: None,
+            feed: "primary",
+            intake_capacity: 4096,
+            intake_policy: IntakePolicy::default(),
+            received_at_us: 0,
             endpoint: endpoint.to_string(),
+            tls: Tls::default(),
             symbols,
+            batch_size: 0,
+            subscribe_interval: Duration::ZERO,
+            stream_endpoint: StreamEndpoint::default(),
+            pending: PendingSubscriptions::default(),
+            rejected: None,
+            progress,
+            keepalive: Keepalive::new(KeepalivePolicy::default(), Instant::now()),
         }
     }
 
+    /// Subscribe after connecting, `batch_size` streams per request, one
+    /// request every `interval`
+    pub fn with_batched_subscribe(mut self, batch_size: usize, interval: Duration) -> Self {
+        self.batch_size = batch_size;
+        self.subscribe_interval = interval;
+        self
+    }
+
+    /// Read streams from `endpoint`; the bare `/ws` endpoint always
+    /// subscribes after connecting
+    pub fn with_stream_endpoint(mut self, endpoint: StreamEndpoint) -> Self {
+        self.stream_endpoint = endpoint;
+        self
+    }
+
+    /// Secure connections with `tls`
+    pub fn with_tls(mut self, tls: Tls) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Queue up to `capacity` frames between the socket and `recv`,
+    /// labelled `feed` in metrics, applying `policy` when full
+    pub fn with_intake(
+        mut self,
+        feed: &'static str,
+        capacity: usize,
+        policy: IntakePolicy,
+    ) -> Self {
+        self.feed = feed;
+        self.intake_capacity = capacity;
+        self.intake_policy = policy;
+        self
+    }
+
+    /// Keep connections alive per `policy`
+    pub fn with_keepalive(mut self, policy: KeepalivePolicy) -> Self {
+        self.keepalive = Keepalive::new(policy, Instant::now());
+        self
+    }
+
+    /// Report subscription progress to `progress` from now on, carrying the
+    /// current progress over (its `connected` flag is left as is)
+    pub fn set_progress(&mut self, progress: Arc<SubscriptionProgress>) {
+        let status = self.progress.status();
+        progress.update(|current| {
+            *current = SubscriptionStatus {
+                connected: current.connected,
+                ..status
+            }
+        });
+        self.progress = progress;
+    }
+
+    /// Connect to `endpoint` from the next `connect` on
+    pub fn set_endpoint(&mut self, endpoint: &str) {
+        self.endpoint = endpoint.to_string();
+    }
+
     /// Connect to the WebSocket endpoint
     pub async fn connect(&mut self) -> Result<()> {
-        // Build the combined stream URL
         let streams: Vec<String> = self
             .symbols
             .iter()
-            .flat_map(|s| {
+            .flat_map(|(s, speed)| {
                 let s_lower = s.to_lowercase();
                 vec![
-                    format!("{}@depth@100ms", s_lower),
+                    format!("{}{}", s_lower, speed.stream_suffix()),
                     format!("{}@trade", s_lower),
                 ]
             })
             .collect();
 
-        let url = format!("{}/stream?streams={}", self.endpoint, streams.join("/"));
+        if streams.len() > MAX_STREAMS_PER_CONNECTION {
+            warn!(
+                streams = streams.len(),
+                limit = MAX_STREAMS_PER_CONNECTION,
+                "More streams than Binance allows per connection"
+            );
+        }
+
+        // Too many streams for the URL are subscribed once connected, as
+        // are those of the bare endpoint
+        let batch_size = match self.batch_size {
+            0 if self.stream_endpoint == StreamEndpoint::Raw => MAX_URL_STREAMS,
+            0 if streams.len() > MAX_URL_STREAMS => {
+                info!(
+                    streams = streams.len(),
+                    limit = MAX_URL_STREAMS,
+                    "Too many streams for a combined-stream URL, subscribing after connecting"
+                );
+                MAX_URL_STREAMS
+            }
+            batch_size => batch_size,
+        };
+
+        // Build the combined stream URL, or subscribe once connected
+        let url = match self.stream_endpoint {
+            StreamEndpoint::Raw => format!("{}/ws", self.endpoint),
+            StreamEndpoint::Combined if batch_size == 0 => {
+                format!("{}/stream?streams={}", self.endpoint, streams.join("/"))
+            }
+            StreamEndpoint::Combined => format!("{}/stream", self.endpoint),
+        };
 
         info!(url = %url, "Connecting to Binance WebSocket");
 
-        let (ws_stream, response) = connect_async(&url).await.map_err(|e| {
-            MarketDataError::WebSocketConnection(format!("Failed to connect: {}", e))
-        })?;
+        let (ws_stream, response) = self.tls.connect(&url).await?;
 
         info!(status = ?response.status(), "WebSocket connected");
-        self.stream = Some(ws_stream);
+        let (sink, stream) = ws_stream.split();
+        self.sink = Some(sink);
+        self.intake = Some(Intake::spawn(
+            stream,
+            IntakeQueue::new(self.feed, self.intake_capacity, self.intake_policy),
+        ));
+        self.keepalive.reset(Instant::now());
+        self.pending = PendingSubscriptions::default();
+        self.rejected = None;
+
+        if batch_size == 0 {
+            self.progress.update(|status| {
+                *status = SubscriptionStatus {
+                    mode: "url",
+                    total_streams: streams.len(),
+                    requested_streams: streams.len(),
+                    confirmed_streams: streams.len(),
+                    ..Default::default()
+                }
+            });
+            return Ok(());
+        }
+
+        self.subscribe(&streams, batch_size).await
+    }
+
+    /// Send paced SUBSCRIBE batches; their replies arrive through `recv` and
+    /// are settled with `on_ack` and `on_error`
+    async fn subscribe(&mut self, streams: &[String], batch_size: usize) -> Result<()> {
+        let requests = self.pending.requests(streams, batch_size);
+        self.progress.update(|status| {
+            *status = SubscriptionStatus {
+                mode: "batched",
+                total_streams: streams.len(),
+                pending_requests: requests.len(),
+                ..Default::default()
+            }
+        });
+        info!(
+            streams = streams.len(),
+            requests = requests.len(),
+            "Subscribing in batches"
+        );
 
+        for (i, (count, request)) in requests.into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(self.subscribe_interval).await;
+            }
+            let stream = self
+                .sink
+                .as_mut()
+                .ok_or_else(|| MarketDataError::WebSocketConnection("Not connected".to_string()))?;
+            stream
+                .send(Message::Text(request))
+                .await
+                .map_err(|e| MarketDataError::WebSocketMessage(e.to_string()))?;
+            self.progress
+                .update(|status| status.requested_streams += count);
+        }
+
+        self.pending.sent_all(Instant::now());
         Ok(())
     }
 
-    /// Receive the next message
-    pub async fn recv(&mut self) -> Result<Option<String>> {
+    /// Unsubscribe and resubscribe one symbol's depth stream on the live
+    /// connection
+    pub async fn resubscribe_depth(&mut self, symbol: &str) -> Result<()> {
+        let Some((_, speed)) = self.symbols.iter().find(|(s, _)| s == symbol) else {
+            return Ok(());
+        };
+        let streams = [format!(
+            "{}{}",
+            symbol.to_lowercase(),
+            speed.stream_suffix()
+        )];
+        for method in ["UNSUBSCRIBE", "SUBSCRIBE"] {
+            let request = self.pending.control(method, &streams);
+            self.send_control(request).await?;
+        }
+        self.pending.sent_all(Instant::now());
+        Ok(())
+    }
+
+    async fn send_control(&mut self, request: String) -> Result<()> {
         let stream = self
-            .stream
+            .sink
             .as_mut()
             .ok_or_else(|| MarketDataError::WebSocketConnection("Not connected".to_string()))?;
+        stream
+            .send(Message::Text(request))
+            .await
+            .map_err(|e| MarketDataError::WebSocketMessage(e.to_string()))
+    }
+
+    /// Fail once a request was rejected past its retries or SUBSCRIBE
+    /// confirmations are overdue
+    pub fn check_subscriptions(&self) -> Result<()> {
+        if let Some(error) = &self.rejected {
+            return Err(MarketDataError::WebSocketMessage(error.clone()));
+        }
+        if !self.pending.overdue(Instant::now()) {
+            return Ok(());
+        }
+        let error = format!(
+            "{} SUBSCRIBE requests unconfirmed after {:?}",
+            self.pending.len(),
+            super::subscription::CONFIRM_TIMEOUT
+        );
+        self.progress
+            .update(|status| status.last_error = Some(error.clone()));
+        Err(MarketDataError::WebSocketMessage(error))
+    }
+
+    /// Settle a confirmed control request
+    pub fn on_ack(&mut self, ack: &SubscriptionAck) {
+        let confirmed = self.pending.confirm(ack.id);
+        let pending = self.pending.len();
+        self.progress.update(|status| {
+            status.pending_requests = pending;
+            status.confirmed_streams += confirmed;
+        });
+
+        if confirmed > 0 && self.pending.is_empty() {
+            info!("All stream subscriptions confirmed");
+        }
+    }
+
+    /// Settle a rejected control request, sending a rejected SUBSCRIBE
+    /// again until it runs out of retries
+    pub async fn on_error(&mut self, error: &ExchangeError) -> Result<()> {
+        self.progress
+            .update(|status| status.last_error = Some(error.to_string()));
+        let Some(request) = error.id.and_then(|id| self.pending.reject(id)) else {
+            return Ok(());
+        };
+        if request.method != "SUBSCRIBE" || request.retries >= MAX_SUBSCRIBE_RETRIES {
+            self.rejected = Some(format!(
+                "{} of {} streams rejected: {}",
+                request.method,
+                request.streams.len(),
+                error
+            ));
+            return Ok(());
+        }
+
+        warn!(
+            streams = request.streams.len(),
+            retries = request.retries + 1,
+            "Resubscribing rejected streams"
+        );
+        let request = self.pending.retry(request);
+        self.send_control(request).await?;
+        self.pending.sent_all(Instant::now());
+        Ok(())
+    }
+
+    /// Receive the next message from the intake queue
+    pub async fn recv(&mut self) -> Result<Option<String>> {
+        let intake = self
+            .intake
+            .as_ref()
+            .ok_or_else(|| MarketDataError::WebSocketConnection("Not connected".to_string()))?;
+
+        let frame = intake.next().await;
+        self.on_frame(frame).await
+    }
 
-        match stream.next().await {
+    /// Receive the next message if one is already queued, without waiting
+    pub async fn try_recv(&mut self) -> Option<Result<Option<String>>> {
+        let frame = self.intake.as_ref()?.try_next()?;
+        Some(self.on_frame(frame).await)
+    }
+
+    async fn on_frame(&mut self, frame: Frame) -> Result<Option<String>> {
+        self.received_at_us = frame.received_at_us;
+        let message = frame.message;
+        if let Some(Ok(_)) = &message {
+            self.keepalive.received(Instant::now());
+        }
+        match message {
             Some(Ok(Message::Text(text))) => {
                 debug!(len = text.len(), "Received text message");
                 Ok(Some(text))
             }
             Some(Ok(Message::Binary(data))) => {
-                // Convert binary to text if needed
-                let text = String::from_utf8_lossy(&data).to_string();
+                // Take over the frame's buffer; only invalid UTF-8 is copied
+                let text = String::from_utf8(data)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
                 Ok(Some(text))
             }
             Some(Ok(Message::Ping(data))) => {
                 debug!("Received ping, sending pong");
-                if let Some(stream) = self.stream.as_mut() {
-                    let _ = stream.send(Message::Pong(data)).await;
+                if let Some(sink) = self.sink.as_mut() {
+                    let _ = sink.send(Message::Pong(data)).await;
                 }
                 Ok(None)
             }
@@ -91,7 +390,7 @@ impl WebSocketClient {
             }
             Some(Ok(Message::Close(frame))) => {
                 warn!(frame = ?frame, "Received close frame");
-                self.stream = None;
+                self.disconnect();
                 Err(MarketDataError::WebSocketConnection(
                     "Connection closed".to_string(),
                 ))
@@ -99,12 +398,12 @@ impl WebSocketClient {
             Some(Ok(Message::Frame(_))) => Ok(None),
             Some(Err(e)) => {
                 error!(error = %e, "WebSocket error");
-                self.stream = None;
+                self.disconnect();
                 Err(MarketDataError::WebSocketMessage(e.to_string()))
             }
             None => {
                 warn!("WebSocket stream ended");
-                self.stream = None;
+                self.disconnect();
                 Err(MarketDataError::WebSocketConnection(
                     "Stream ended".to_string(),
                 ))
@@ -112,10 +411,34 @@ impl WebSocketClient {
         }
     }
 
+    /// When a keepalive frame is next due, if ever; call `keep_alive` then
+    pub fn keepalive_deadline(&self) -> Option<Instant> {
+        self.keepalive.deadline()
+    }
+
+    /// Send the keepalive frames due now
+    pub async fn keep_alive(&mut self) -> Result<()> {
+        for frame in self.keepalive.due(Instant::now()) {
+            let Some(stream) = self.sink.as_mut() else {
+                return Ok(());
+            };
+            let message = match frame {
+                KeepaliveFrame::Ping => Message::Ping(vec![]),
+                KeepaliveFrame::Pong => Message::Pong(vec![]),
+            };
+            debug!(frame = ?frame, "Sending keepalive");
+            stream
+                .send(message)
+                .await
+                .map_err(|e| MarketDataError::WebSocketMessage(e.to_string()))?;
+        }
+        Ok(())
+    }
+
     /// Send a ping to keep connection alive
     #[allow(dead_code)]
     pub async fn ping(&mut self) -> Result<()> {
-        if let Some(stream) = self.stream.as_mut() {
+        if let Some(stream) = self.sink.as_mut() {
             stream
                 .send(Message::Ping(vec![]))
                 .await
@@ -124,17 +447,34 @@ impl WebSocketClient {
         Ok(())
     }
 
+    /// Whether connected with every subscription confirmed
+    pub fn is_subscribed(&self) -> bool {
+        self.sink.is_some() && self.pending.is_empty()
+    }
+
     /// Check if connected
     #[allow(dead_code)]
     pub fn is_connected(&self) -> bool {
-        self.stream.is_some()
+        self.sink.is_some()
+    }
+
+    /// Socket receive time of the message `recv` returned last
+    /// (microseconds since epoch), before any wait in the intake queue
+    pub fn received_at_us(&self) -> u64 {
+        self.received_at_us
     }
 
     /// Close the connection
-    #[allow(dead_code)]
     pub async fn close(&mut self) {
-        if let Some(mut stream) = self.stream.take() {
-            let _ = stream.close(None).await;
+        if let Some(mut sink) = self.sink.take() {
+            let _ = sink.close().await;
         }
+        self.intake = None;
+    }
+
+    /// Drop both sides of a connection that ended
+    fn disconnect(&mut self) {
+        self.sink = None;
+        self.intake = None;
     }
 }
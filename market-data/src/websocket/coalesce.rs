@@ -0,0 +1,145 @@
+//! Coalescing of queued depth diffs
+//!
+//! When processing falls behind, several diffs of a symbol wait in the
+//! intake queue at once. Applying and publishing each of them serializes
+//! intermediate book states nobody will read; instead the manager drains
+//! what is queued and folds consecutive diffs of a symbol into one, so the
+//! book takes all their levels in a single update and publishes its final
+//! state once. Only contiguous diffs are folded, so a sequence gap still
+//! reaches the book and triggers its resync. Folded diffs are counted in
+//! `websocket_coalesced_updates_total`.
+
+use prometheus::{IntCounterVec, Opts};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::event::{DepthDelta, PriceLevel};
+use crate::pipeline::Delta;
+
+/// Frames drained from the intake queue per batch at most
+pub const MAX_BATCH: usize = 256;
+
+static COALESCED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "websocket_coalesced_updates_total",
+            "Queued depth diffs folded into a later one instead of being published on their own",
+        ),
+        &["symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Diffs of one batch, folded per symbol
+#[derive(Debug, Default)]
+pub struct Coalescer {
+    pending: HashMap<String, Delta>,
+}
+
+impl Coalescer {
+    /// Fold `delta` into the symbol's pending diff; returns the pending
+    /// diff when the two are not contiguous, to be applied first
+    pub fn push(&mut self, delta: Delta) -> Option<Delta> {
+        let Some(pending) = self.pending.get_mut(&delta.update.symbol) else {
+            self.pending.insert(delta.update.symbol.clone(), delta);
+            return None;
+        };
+        if delta.update.first_update_id != pending.update.final_update_id + 1 {
+            return self.pending.insert(delta.update.symbol.clone(), delta);
+        }
+        COALESCED.with_label_values(&[&delta.update.symbol]).inc();
+        merge(&mut pending.update, delta.update);
+        // Latency still counts from the oldest diff's arrival and release
+        pending.parsed_at_us = delta.parsed_at_us;
+        pending.connection_id = delta.connection_id;
+        pending.shard = delta.shard;
+        None
+    }
+
+    /// Take the pending diffs
+    pub fn drain(&mut self) -> impl Iterator<Item = Delta> + '_ {
+        self.pending.drain().map(|(_, delta)| delta)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Extend `into` by the contiguous diff `next`; a level of `next` replaces
+/// the one at the same price
+fn merge(into: &mut DepthDelta, next: DepthDelta) {
+    into.event_time = next.event_time;
+    into.final_update_id = next.final_update_id;
+    merge_levels(&mut into.bids, next.bids);
+    merge_levels(&mut into.asks, next.asks);
+}
+
+fn merge_levels(into: &mut Vec<PriceLevel>, next: Vec<PriceLevel>) {
+    for level in next {
+        match into.iter_mut().find(|l| l.price == level.price) {
+            Some(existing) => existing.quantity = level.quantity,
+            None => into.push(level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Venue;
+    use rust_decimal_macros::dec;
+
+    fn delta(first: u64, last: u64, bids: Vec<PriceLevel>) -> Delta {
+        Delta {
+            update: DepthDelta {
+                venue: Venue::Binance,
+                symbol: "BTCUSDT".to_string(),
+                event_time: last,
+                first_update_id: first,
+                final_update_id: last,
+                bids,
+                asks: vec![],
+            },
+            received_at_us: first,
+            parsed_at_us: last,
+            released_at_us: last,
+            connection_id: 1,
+            shard: 0,
+        }
+    }
+
+    #[test]
+    fn test_folds_contiguous_diffs_only() {
+        let level = |price, quantity| PriceLevel { price, quantity };
+        let mut coalescer = Coalescer::default();
+        assert!(coalescer
+            .push(delta(101, 102, vec![level(dec!(100), dec!(1))]))
+            .is_none());
+        assert!(coalescer
+            .push(delta(
+                103,
+                105,
+                vec![level(dec!(100), dec!(0)), level(dec!(99), dec!(2))]
+            ))
+            .is_none());
+
+        // A gap hands back the folded diff to apply before the new one
+        let folded = coalescer.push(delta(107, 107, vec![])).unwrap();
+        assert_eq!(folded.update.first_update_id, 101);
+        assert_eq!(folded.update.final_update_id, 105);
+        assert_eq!(folded.update.event_time, 105);
+        assert_eq!(folded.received_at_us, 101);
+        assert_eq!(
+            folded.update.bids,
+            [level(dec!(100), dec!(0)), level(dec!(99), dec!(2))]
+        );
+
+        let rest: Vec<_> = coalescer.drain().collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].update.first_update_id, 107);
+        assert!(coalescer.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+//! Bounded intake queue between the socket and message processing
+//!
+//! Each connection's frames are read off the socket by a task of their own
+//! and queued here, stamped with their receive time, so slow book or
+//! publish work no longer leaves them in the kernel's receive buffer. The
+//! queue holds `WS_INTAKE_CAPACITY` frames; its depth is exported as
+//! `websocket_intake_queue_depth`. When it is full, `WS_INTAKE_POLICY`
+//! decides: `block` waits for room, letting backpressure reach TCP, while
+//! `keep_latest` replaces a queued depth update of the same stream, or of
+//! the same symbol on the bare `/ws` endpoint, with the new one (counted in
+//! `websocket_intake_shed_total`). A shed diff leaves a
+//! sequence gap that makes the book resync from a snapshot; frames other
+//! than depth updates are never shed.
+
+use futures_util::stream::SplitStream;
+use futures_util::StreamExt;
+use prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts};
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use super::now_micros;
+use super::tls::WsStream;
+use crate::config::IntakePolicy;
+
+static DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "websocket_intake_queue_depth",
+            "Frames read off the socket and waiting to be processed, by connection",
+        ),
+        &["feed"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(gauge.clone()));
+    gauge
+});
+
+static SHED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "websocket_intake_shed_total",
+            "Depth updates replaced by a newer one of the same stream in a full intake queue",
+        ),
+        &["feed", "symbol"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// A frame as read off the socket; `None` when the stream ended
+pub struct Frame {
+    pub message: Option<Result<Message, WsError>>,
+    /// Local wall-clock receive time (microseconds since epoch)
+    pub received_at_us: u64,
+}
+
+impl Frame {
+    /// Combined stream name of a depth update, e.g. `btcusdt@depth@100ms`,
+    /// or the symbol of a bare one
+    fn depth_stream(&self) -> Option<&str> {
+        let Some(Ok(Message::Text(text))) = &self.message else {
+            return None;
+        };
+        if let Some(rest) = text.strip_prefix(r#"{"stream":""#) {
+            let stream = &rest[..rest.find('"')?];
+            return stream.contains("@depth").then_some(stream);
+        }
+        if !text.starts_with(r#"{"e":"depthUpdate""#) {
+            return None;
+        }
+        let rest = &text[text.find(r#""s":""#)? + 5..];
+        Some(&rest[..rest.find('"')?])
+    }
+}
+
+/// Bounded queue of frames awaiting processing
+pub struct IntakeQueue {
+    frames: Mutex<VecDeque<Frame>>,
+    capacity: usize,
+    policy: IntakePolicy,
+    feed: &'static str,
+    /// Signalled when a frame is queued
+    ready: Notify,
+    /// Signalled when a frame is taken
+    space: Notify,
+    depth_metric: IntGauge,
+}
+
+impl IntakeQueue {
+    /// Create a queue of up to `capacity` frames, labelled `feed` in
+    /// metrics
+    pub fn new(feed: &'static str, capacity: usize, policy: IntakePolicy) -> Self {
+        LazyLock::force(&SHED);
+        Self {
+            frames: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+            capacity: capacity.max(1),
+            policy,
+            feed,
+            ready: Notify::new(),
+            space: Notify::new(),
+            depth_metric: DEPTH.with_label_values(&[feed]),
+        }
+    }
+
+    /// Queue a frame, applying the policy when full
+    pub async fn push(&self, frame: Frame) {
+        loop {
+            {
+                let mut frames = self.frames.lock().unwrap();
+                if frames.len() < self.capacity {
+                    frames.push_back(frame);
+                    self.depth_metric.set(frames.len() as i64);
+                    drop(frames);
+                    self.ready.notify_one();
+                    return;
+                }
+                if self.policy == IntakePolicy::KeepLatest {
+                    if let Some(stream) = frame.depth_stream() {
+                        let queued = frames
+                            .iter()
+                            .position(|queued| queued.depth_stream() == Some(stream));
+                        if let Some(index) = queued {
+                            let symbol = stream.split('@').next().unwrap_or_default();
+                            SHED.with_label_values(&[self.feed, &symbol.to_uppercase()])
+                                .inc();
+                            frames.remove(index);
+                            frames.push_back(frame);
+                            drop(frames);
+                            self.ready.notify_one();
+                            return;
+                        }
+                    }
+                }
+            }
+            self.space.notified().await;
+        }
+    }
+
+    /// Take the oldest frame, waiting until one is queued
+    pub async fn pop(&self) -> Frame {
+        loop {
+            let frame = {
+                let mut frames = self.frames.lock().unwrap();
+                let frame = frames.pop_front();
+                self.depth_metric.set(frames.len() as i64);
+                frame
+            };
+            if let Some(frame) = frame {
+                self.space.notify_one();
+                return frame;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Take the oldest frame if one is queued
+    pub fn try_pop(&self) -> Option<Frame> {
+        let frame = {
+            let mut frames = self.frames.lock().unwrap();
+            let frame = frames.pop_front();
+            self.depth_metric.set(frames.len() as i64);
+            frame
+        };
+        if frame.is_some() {
+            self.space.notify_one();
+        }
+        frame
+    }
+
+    /// Number of queued frames
+    pub fn len(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A connection's read side: the queue and the task filling it; dropping
+/// it stops the task
+pub struct Intake {
+    queue: Arc<IntakeQueue>,
+    reader: JoinHandle<()>,
+}
+
+impl Intake {
+    /// Read `stream` into `queue` until it ends or fails
+    pub fn spawn(stream: SplitStream<WsStream>, queue: IntakeQueue) -> Self {
+        let queue = Arc::new(queue);
+        let reader = tokio::spawn(read(stream, queue.clone()));
+        Self { queue, reader }
+    }
+
+    /// Next frame, waiting for one
+    pub async fn next(&self) -> Frame {
+        self.queue.pop().await
+    }
+
+    /// Next frame if one is queued
+    pub fn try_next(&self) -> Option<Frame> {
+        self.queue.try_pop()
+    }
+}
+
+impl Drop for Intake {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.queue.depth_metric.set(0);
+    }
+}
+
+async fn read(mut stream: SplitStream<WsStream>, queue: Arc<IntakeQueue>) {
+    loop {
+        let message = stream.next().await;
+        let ended = !matches!(message, Some(Ok(_)));
+        queue
+            .push(Frame {
+                message,
+                received_at_us: now_micros(),
+            })
+            .await;
+        if ended {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(raw: &str) -> Frame {
+        Frame {
+            message: Some(Ok(Message::Text(raw.to_string()))),
+            received_at_us: 0,
+        }
+    }
+
+    fn depth(symbol: &str, id: u64) -> Frame {
+        text(&format!(
+            r#"{{"stream":"{}@depth@100ms","data":{{"u":{}}}}}"#,
+            symbol, id
+        ))
+    }
+
+    fn raw(frame: &Frame) -> &str {
+        match &frame.message {
+            Some(Ok(Message::Text(text))) => text,
+            _ => "",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keep_latest_sheds_queued_depth_of_same_stream() {
+        let queue = IntakeQueue::new("test", 3, IntakePolicy::KeepLatest);
+        queue.push(depth("btcusdt", 1)).await;
+        queue
+            .push(text(r#"{"stream":"btcusdt@trade","data":{}}"#))
+            .await;
+        queue.push(depth("ethusdt", 1)).await;
+
+        // Full: the newer BTCUSDT diff replaces the queued one, at the back
+        queue.push(depth("btcusdt", 2)).await;
+        assert_eq!(queue.len(), 3);
+        assert!(raw(&queue.pop().await).contains("@trade"));
+        assert!(raw(&queue.pop().await).contains("ethusdt"));
+        assert!(raw(&queue.pop().await).contains(r#""u":2"#));
+        assert!(queue.is_empty());
+
+        // Bare payloads of the `/ws` endpoint are matched by symbol
+        let bare = |symbol: &str, id: u64| {
+            text(&format!(
+                r#"{{"e":"depthUpdate","E":1,"s":"{}","u":{}}}"#,
+                symbol, id
+            ))
+        };
+        let queue = IntakeQueue::new("test", 2, IntakePolicy::KeepLatest);
+        queue.push(bare("BTCUSDT", 1)).await;
+        queue.push(bare("ETHUSDT", 1)).await;
+        queue.push(bare("BTCUSDT", 2)).await;
+        assert!(raw(&queue.pop().await).contains("ETHUSDT"));
+        assert!(raw(&queue.pop().await).contains(r#""u":2"#));
+
+        // Blocking waits for room instead
+        let queue = Arc::new(IntakeQueue::new("test", 1, IntakePolicy::Block));
+        queue.push(depth("btcusdt", 1)).await;
+        let pusher = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(depth("btcusdt", 2)).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!pusher.is_finished());
+        assert!(raw(&queue.pop().await).contains(r#""u":1"#));
+        pusher.await.unwrap();
+        assert!(raw(&queue.pop().await).contains(r#""u":2"#));
+    }
+}
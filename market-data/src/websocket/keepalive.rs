@@ -0,0 +1,173 @@
+//! Ping/pong keepalive policy per exchange
+//!
+//! Venues disagree on who keeps a connection alive. Binance pings the
+//! client and drops it when no pong comes back within 10 minutes; pongs
+//! are answered as pings arrive and unsolicited pongs are allowed. Other
+//! venues expect the client to ping every N seconds. A [`KeepalivePolicy`]
+//! states what a connection owes its server, starting from the venue's
+//! defaults with `WS_PING_INTERVAL_SECS`, `WS_PONG_INTERVAL_SECS` and
+//! `WS_IDLE_PING_SECS` overriding them; [`Keepalive`] tracks when the next
+//! frame is due on one connection.
+
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::event::Venue;
+
+/// What a connection sends to keep its server satisfied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepalivePolicy {
+    /// Ping the server this often regardless of traffic
+    pub ping_interval: Option<Duration>,
+    /// Send an unsolicited pong this often
+    pub pong_interval: Option<Duration>,
+    /// Ping to probe the connection after this long without any frame
+    pub idle_ping: Option<Duration>,
+}
+
+impl KeepalivePolicy {
+    /// Defaults of `venue`'s servers
+    pub fn for_venue(venue: Venue) -> Self {
+        match venue {
+            // The server pings; pongs answer them as they arrive
+            Venue::Binance => Self {
+                ping_interval: None,
+                pong_interval: None,
+                idle_ping: Some(Duration::from_secs(45)),
+            },
+        }
+    }
+
+    /// The venue's defaults with the configured overrides (0 turns one off)
+    pub fn from_config(config: &Config) -> Self {
+        let defaults = Self::for_venue(Venue::Binance);
+        let secs = |value: Option<u64>, default| match value {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default,
+        };
+        Self {
+            ping_interval: secs(config.ws_ping_interval_secs, defaults.ping_interval),
+            pong_interval: secs(config.ws_pong_interval_secs, defaults.pong_interval),
+            idle_ping: secs(config.ws_idle_ping_secs, defaults.idle_ping),
+        }
+    }
+}
+
+impl Default for KeepalivePolicy {
+    fn default() -> Self {
+        Self::for_venue(Venue::default())
+    }
+}
+
+/// A keepalive frame to send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepaliveFrame {
+    Ping,
+    Pong,
+}
+
+/// Keepalive timers of one connection
+#[derive(Debug, Clone)]
+pub struct Keepalive {
+    policy: KeepalivePolicy,
+    last_received: Instant,
+    last_ping: Instant,
+    last_pong: Instant,
+}
+
+impl Keepalive {
+    pub fn new(policy: KeepalivePolicy, now: Instant) -> Self {
+        Self {
+            policy,
+            last_received: now,
+            last_ping: now,
+            last_pong: now,
+        }
+    }
+
+    /// Start over on a new connection
+    pub fn reset(&mut self, now: Instant) {
+        *self = Self::new(self.policy, now);
+    }
+
+    /// A frame of any kind arrived
+    pub fn received(&mut self, now: Instant) {
+        self.last_received = now;
+    }
+
+    /// When the next ping is due, if ever
+    fn ping_due(&self) -> Option<Instant> {
+        let idle = self
+            .policy
+            .idle_ping
+            .map(|idle| self.last_received.max(self.last_ping) + idle);
+        let interval = self.policy.ping_interval.map(|i| self.last_ping + i);
+        idle.into_iter().chain(interval).min()
+    }
+
+    fn pong_due(&self) -> Option<Instant> {
+        self.policy.pong_interval.map(|i| self.last_pong + i)
+    }
+
+    /// When a frame is next due, if ever
+    pub fn deadline(&self) -> Option<Instant> {
+        self.ping_due().into_iter().chain(self.pong_due()).min()
+    }
+
+    /// Frames due at `now`, counted as sent
+    pub fn due(&mut self, now: Instant) -> Vec<KeepaliveFrame> {
+        let mut frames = Vec::new();
+        if self.ping_due().is_some_and(|due| due <= now) {
+            self.last_ping = now;
+            frames.push(KeepaliveFrame::Ping);
+        }
+        if self.pong_due().is_some_and(|due| due <= now) {
+            self.last_pong = now;
+            frames.push(KeepaliveFrame::Pong);
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_due_per_policy() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+
+        // Binance: probe only after silence
+        let mut binance = Keepalive::new(KeepalivePolicy::for_venue(Venue::Binance), start);
+        binance.received(start + secs(30));
+        assert_eq!(binance.deadline(), Some(start + secs(75)));
+        assert!(binance.due(start + secs(74)).is_empty());
+        assert_eq!(binance.due(start + secs(75)), [KeepaliveFrame::Ping]);
+        // Still silent: probe again one idle period after the last probe
+        assert_eq!(binance.deadline(), Some(start + secs(120)));
+
+        // A venue wanting client pings every 20s, plus unsolicited pongs
+        let policy = KeepalivePolicy {
+            ping_interval: Some(secs(20)),
+            pong_interval: Some(secs(60)),
+            idle_ping: None,
+        };
+        let mut other = Keepalive::new(policy, start);
+        other.received(start + secs(19));
+        assert_eq!(other.deadline(), Some(start + secs(20)));
+        assert_eq!(other.due(start + secs(20)), [KeepaliveFrame::Ping]);
+        assert_eq!(
+            other.due(start + secs(60)),
+            [KeepaliveFrame::Ping, KeepaliveFrame::Pong]
+        );
+
+        let off = KeepalivePolicy {
+            ping_interval: None,
+            pong_interval: None,
+            idle_ping: None,
+        };
+        assert_eq!(Keepalive::new(off, start).deadline(), None);
+    }
+}
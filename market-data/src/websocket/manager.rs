@@ -2,20 +2,40 @@
 //!
 //! Handles reconnection logic and message dispatch.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::{interval, sleep, timeout};
-use tracing::{error, info, warn};
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep_until};
+use tracing::{debug, error, info, instrument, warn, Instrument};
 
-use super::WebSocketClient;
+use super::coalesce::{self, Coalescer};
+use super::redundant::{self, Arbiter, Feed};
+use super::rotation::{self, Standby, StandbyEvent};
+use super::SubscriptionProgress;
+use super::{
+    AlignmentBuffer, InboundMessage, KeepalivePolicy, LoadedSnapshot, SnapshotLoader,
+    StalenessWatchdog, Tls, WebSocketClient,
+};
+use crate::config::Config;
 use crate::error::Result;
-use crate::parser::{OrderBookSnapshot, ParsedMessage};
+use crate::event::{BookSnapshot, MarketEvent};
+use crate::latency::Stage;
+use crate::metrics;
+use crate::parser::{self, OrderBookSnapshot, ParsedMessage};
+use crate::pipeline::{self, Delta};
+use crate::publisher::StatusEvent;
+use crate::rest::{depth_weight, RestClient};
+use crate::shutdown::Shutdown;
+use crate::tap::RawTap;
 use crate::AppState;
 
 /// Maximum backoff delay in milliseconds (60 seconds)
 const MAX_BACKOFF_MS: u64 = 60_000;
 /// Cooldown period after which reconnect attempts are reset (5 minutes)
 const RECONNECT_COOLDOWN_SECS: u64 = 300;
+/// How often the staleness watchdog checks the books
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
 
 /// Manages WebSocket connections with automatic reconnection
 pub struct WebSocketManager {
@@ -23,29 +43,124 @@ pub struct WebSocketManager {
     client: WebSocketClient,
     reconnect_attempts: u32,
     last_successful_connection: Option<Instant>,
+    /// Optional event-time reordering of trades and depth diffs
+    alignment: Option<AlignmentBuffer>,
+    /// Incremented on every successful connect, stamped into provenance
+    connection_id: u64,
+    /// Shard index of this connection
+    shard: u32,
+    /// Stops `run` when triggered
+    shutdown: Shutdown,
+    /// Recovers books that go silent while others update, when enabled
+    watchdog: Option<StalenessWatchdog>,
+    /// Snapshot fetches in flight and the diffs held for them
+    snapshots: SnapshotLoader,
+    /// Consecutive failed snapshot fetches per symbol, for its backoff
+    snapshot_failures: HashMap<String, u32>,
+    /// Symbols whose book task discarded the book and wants a snapshot
+    resyncs: Option<mpsc::UnboundedReceiver<String>>,
+    /// Parsed messages of the redundant connection, when configured
+    redundant: Option<mpsc::Receiver<InboundMessage>>,
+    /// Drops the second copy of each message across the two connections
+    arbiter: Option<Arbiter>,
+    /// Whether depth diffs are being folded while a batch of queued frames
+    /// is processed
+    coalescing: bool,
+    coalescer: Coalescer,
+    /// Receives the primary connection's raw frames, when configured
+    tap: Option<RawTap>,
 }
 
 impl WebSocketManager {
     /// Create a new WebSocket manager
     pub fn new(state: Arc<AppState>) -> Self {
-        let client = WebSocketClient::new(&state.config.ws_endpoint, state.config.symbols.clone());
+        let client = client(
+            &state.config,
+            &state.tls,
+            state.subscriptions.clone(),
+            Feed::Primary,
+        );
+        let alignment = (state.config.alignment_max_delay_ms > 0).then(|| {
+            AlignmentBuffer::new(Duration::from_millis(state.config.alignment_max_delay_ms))
+        });
+        let watchdog = (state.config.stale_book_secs > 0)
+            .then(|| StalenessWatchdog::new(state.config.stale_book_secs * 1000));
+        let snapshots =
+            SnapshotLoader::new(state.config.snapshot_concurrency).with_clock(state.time.clone());
 
         Self {
             state,
             client,
             reconnect_attempts: 0,
             last_successful_connection: None,
+            alignment,
+            connection_id: 0,
+            shard: 0,
+            shutdown: Shutdown::new(),
+            watchdog,
+            snapshots,
+            snapshot_failures: HashMap::new(),
+            resyncs: None,
+            redundant: None,
+            arbiter: None,
+            coalescing: false,
+            coalescer: Coalescer::default(),
+            tap: None,
         }
     }
 
-    /// Run the WebSocket manager - runs indefinitely with automatic reconnection
+    /// Resync the books whose tasks ask for it on `resyncs`; see
+    /// `BookTasks::spawn`
+    pub fn with_resyncs(mut self, resyncs: mpsc::UnboundedReceiver<String>) -> Self {
+        self.resyncs = Some(resyncs);
+        self
+    }
+
+    /// Forward every raw frame of the primary connection to `tap`
+    pub fn with_tap(mut self, tap: RawTap) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    /// Return from `run` once `shutdown` is triggered
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Run the WebSocket manager - runs with automatic reconnection until
+    /// shutdown is triggered
     pub async fn run(&mut self) -> Result<()> {
         info!("Starting WebSocket manager with infinite retry");
+        let shutdown = self.shutdown.clone();
+        // One status log for all connections
+        let status_log = tokio::spawn(log_status(self.state.clone()));
+
+        // Second connection to another endpoint for the same streams
+        if let Some(endpoint) = self.state.config.redundant_ws_endpoint.clone() {
+            if self.redundant.is_none() {
+                info!(endpoint = %endpoint, "Starting redundant feed");
+                self.redundant = Some(redundant::spawn(
+                    self.state.config.clone(),
+                    self.state.tls.clone(),
+                    endpoint,
+                    shutdown.clone(),
+                ));
+                self.arbiter = Some(Arbiter::default());
+            }
+        }
 
         loop {
+            self.state.heartbeat.beat(self.state.time.now_millis());
+
             // Reset reconnect attempts if we've been stable for a while
             if let Some(last_success) = self.last_successful_connection {
-                if last_success.elapsed() > Duration::from_secs(RECONNECT_COOLDOWN_SECS)
+                let stable_for = self
+                    .state
+                    .time
+                    .now()
+                    .saturating_duration_since(last_success);
+                if stable_for > Duration::from_secs(RECONNECT_COOLDOWN_SECS)
                     && self.reconnect_attempts > 0
                 {
                     info!(
@@ -56,171 +171,649 @@ impl WebSocketManager {
                 }
             }
 
-            match self.connect_and_process().await {
+            let connection_id = self.connection_id;
+            let result = tokio::select! {
+                biased;
+                result = self.connect_and_process() => result,
+                _ = shutdown.wait() => Ok(()),
+            };
+
+            self.state
+                .subscriptions
+                .update(|status| status.connected = false);
+            if self.connection_id != connection_id {
+                let reason = match &result {
+                    Ok(()) if shutdown.is_triggered() => "shutdown".to_string(),
+                    Ok(()) => "closed".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                let status = StatusEvent::Disconnected {
+                    connection_id: self.connection_id,
+                    reason,
+                    timestamp: self.state.time.now_millis(),
+                };
+                pipeline::publish_status(&self.state, status).await;
+            }
+
+            // Hand out anything still held for alignment before resyncing
+            if let Some(alignment) = self.alignment.as_mut() {
+                for msg in alignment.drain() {
+                    if let Err(e) = self.dispatch(msg).await {
+                        warn!(error = %e, "Failed to process buffered message");
+                    }
+                }
+            }
+            // Snapshots are fetched anew after reconnecting
+            self.snapshots.clear();
+            self.snapshot_failures.clear();
+
+            if shutdown.is_triggered() {
+                self.client.close().await;
+                status_log.abort();
+                info!("WebSocket manager stopped");
+                return Ok(());
+            }
+
+            let delay = match result {
                 Ok(()) => {
                     info!("WebSocket processing completed normally, reconnecting...");
                     // Brief pause before reconnecting after normal completion
-                    sleep(Duration::from_secs(1)).await;
+                    Duration::from_secs(1)
                 }
                 Err(e) => {
                     error!(error = %e, "WebSocket error");
                     self.reconnect_attempts += 1;
+                    metrics::reconnected();
+                    self.state.ws_endpoints.failed();
 
-                    // Calculate delay with exponential backoff, capped at MAX_BACKOFF_MS
-                    let base_delay = self.state.config.reconnect_delay_ms
-                        * 2u64.pow(self.reconnect_attempts.min(6));
-                    let delay = Duration::from_millis(base_delay.min(MAX_BACKOFF_MS));
+                    let delay = backoff(
+                        self.state.config.reconnect_delay_ms,
+                        self.reconnect_attempts,
+                    );
 
                     warn!(
                         attempt = self.reconnect_attempts,
                         delay_secs = delay.as_secs(),
                         "Reconnecting after error..."
                     );
-                    sleep(delay).await;
+                    delay
                 }
+            };
+            tokio::select! {
+                _ = self.state.time.sleep(delay) => {}
+                _ = shutdown.wait() => {}
             }
         }
     }
 
     /// Connect and process messages
     async fn connect_and_process(&mut self) -> Result<()> {
-        // Connect to WebSocket
-        self.client.connect().await?;
+        // Connect to the endpoint currently preferred
+        self.client.set_endpoint(self.state.ws_endpoints.current());
+        self.client
+            .connect()
+            .instrument(tracing::info_span!(
+                "connect",
+                connection = self.connection_id + 1
+            ))
+            .await?;
 
         // Mark successful connection
-        self.last_successful_connection = Some(Instant::now());
+        self.state
+            .subscriptions
+            .update(|status| status.connected = true);
+        self.connection_id += 1;
+        self.last_successful_connection = Some(self.state.time.now());
         self.reconnect_attempts = 0;
         info!("WebSocket connected successfully, resetting reconnect counter");
+        let status = StatusEvent::Connected {
+            connection_id: self.connection_id,
+            timestamp: self.state.time.now_millis(),
+        };
+        pipeline::publish_status(&self.state, status).await;
 
-        // Fetch initial snapshots for all symbols
-        self.fetch_snapshots().await?;
-
-        // Start health check and status logging task
-        let health_state = self.state.clone();
-        tokio::spawn(async move {
-            let mut health_interval = interval(Duration::from_secs(30));
-            loop {
-                health_interval.tick().await;
-                // Health check logging
-                let books = health_state.orderbook_manager.read().await;
-                let symbols = books.symbols();
-                for symbol in symbols {
-                    if let Some(state) = books.get_state(&symbol) {
-                        if let Some(mid) = state.metrics.mid_price {
-                            info!(
-                                symbol = %symbol,
-                                mid_price = %mid,
-                                spread_bps = ?state.metrics.spread_bps,
-                                imbalance = ?state.metrics.imbalance,
-                                "Order book status"
-                            );
-                        }
-                    }
-                }
-            }
-        });
+        // Fetch initial snapshots in the background; each book starts
+        // once its own snapshot loads
+        self.start_snapshots();
 
-        // Process messages with keepalive
-        let mut last_message = Instant::now();
-        let keepalive_timeout = Duration::from_secs(30);
-        let recv_timeout = Duration::from_secs(45);
+        let mut watchdog_at = self.state.time.now() + WATCHDOG_INTERVAL;
+        let mut check_stale = false;
+        // Whether the endpoint delivered data on this connection yet
+        let mut delivering = false;
+        // Replace the connection before the exchange's lifetime limit
+        let rotate_after = Duration::from_secs(self.state.config.ws_rotate_after_secs);
+        let mut rotate_at = (!rotate_after.is_zero()).then(|| self.state.time.now() + rotate_after);
+        let mut standby: Option<Standby> = None;
 
         loop {
-            // Use timeout to detect stale connections
-            match timeout(recv_timeout, self.client.recv()).await {
-                Ok(Ok(Some(text))) => {
-                    last_message = Instant::now();
-                    if let Err(e) = self.process_message(&text).await {
-                        warn!(error = %e, "Failed to process message");
+            self.state.heartbeat.beat(self.state.time.now_millis());
+            self.client.check_subscriptions()?;
+
+            let flush_at = self
+                .alignment
+                .as_ref()
+                .and_then(AlignmentBuffer::next_deadline);
+            let keepalive_at = self.client.keepalive_deadline();
+
+            // Wake early to release aligned messages and send the pings
+            // and pongs the keepalive policy asks for
+            let received = tokio::select! {
+                received = self.client.recv() => Some(received),
+                _ = sleep_until_deadline(keepalive_at) => {
+                    if let Err(e) = self.client.keep_alive().await {
+                        warn!(error = %e, "Failed to send keepalive, reconnecting");
+                        return Err(crate::error::MarketDataError::ConnectionTimeout);
                     }
+                    continue;
                 }
-                Ok(Ok(None)) => {
-                    // Ping/pong or other non-data message
-                    // Send keepalive if no data received for a while
-                    if last_message.elapsed() > keepalive_timeout {
-                        if let Err(e) = self.client.ping().await {
-                            warn!(error = %e, "Failed to send keepalive ping");
+                loaded = self.snapshots.next() => {
+                    self.on_snapshot(loaded).await;
+                    continue;
+                }
+                Some(symbol) = next_resync(&mut self.resyncs) => {
+                    self.start_snapshot(&symbol, Duration::ZERO);
+                    continue;
+                }
+                Some(inbound) = next_redundant(&mut self.redundant) => {
+                    if let Err(e) = self.accept(inbound, Feed::Redundant).await {
+                        warn!(error = %e, "Failed to process redundant feed message");
+                    }
+                    continue;
+                }
+                _ = self.state.time.sleep_until_deadline(rotate_at), if standby.is_none() => {
+                    info!(
+                        connection = self.connection_id,
+                        "Rotating to a new connection ahead of the exchange's lifetime limit"
+                    );
+                    // Both connections deliver the same messages meanwhile
+                    self.arbiter.get_or_insert_with(Arbiter::default);
+                    standby = Some(Standby::spawn(
+                        &self.state.config,
+                        &self.state.tls,
+                        self.state.ws_endpoints.current(),
+                        rotation::OVERLAP,
+                    ));
+                    continue;
+                }
+                event = next_standby(&mut standby) => {
+                    match event {
+                        StandbyEvent::Message(inbound) => {
+                            if let Err(e) = self.accept(inbound, Feed::Standby).await {
+                                warn!(error = %e, "Failed to process standby connection message");
+                            }
+                        }
+                        StandbyEvent::Ready(result) => {
+                            standby = None;
+                            match result {
+                                Ok(ws) => {
+                                    self.take_over(*ws).await;
+                                    rotate_at = Some(self.state.time.now() + rotate_after);
+                                }
+                                Err(e) => {
+                                    rotation::rotated(false);
+                                    warn!(
+                                        error = %e,
+                                        retry_secs = rotation::RETRY_AFTER.as_secs(),
+                                        "Standby connection failed to warm up, keeping the current one"
+                                    );
+                                    rotate_at = Some(self.state.time.now() + rotation::RETRY_AFTER);
+                                }
+                            }
                         }
                     }
                     continue;
                 }
-                Ok(Err(e)) => {
-                    // WebSocket error
-                    return Err(e);
+                _ = self.state.time.sleep_until_deadline(flush_at) => None,
+                _ = self.state.time.sleep_until(watchdog_at), if self.watchdog.is_some() => {
+                    watchdog_at = self.state.time.now() + WATCHDOG_INTERVAL;
+                    check_stale = true;
+                    None
                 }
-                Err(_) => {
-                    // Timeout - connection might be stale
-                    warn!(
-                        last_message_secs = last_message.elapsed().as_secs(),
-                        "No message received within timeout, sending keepalive"
-                    );
-                    if let Err(e) = self.client.ping().await {
-                        warn!(error = %e, "Failed to send keepalive ping, reconnecting");
-                        return Err(crate::error::MarketDataError::ConnectionTimeout);
+            };
+
+            let Some(received) = received else {
+                self.flush_aligned().await;
+                if std::mem::take(&mut check_stale) {
+                    self.recover_stale().await?;
+                }
+                continue;
+            };
+
+            match received {
+                Ok(Some(text)) => {
+                    let received_at_us = self.client.received_at_us();
+                    if !std::mem::replace(&mut delivering, true) {
+                        self.state.ws_endpoints.succeeded();
                     }
+                    // Diffs queued behind this message are folded with it
+                    self.coalescing = self.state.config.coalesce_depth_updates;
+                    if let Err(e) = self.process_message(&text, received_at_us).await {
+                        warn!(error = %e, "Failed to process message");
+                    }
+                    let drained = self.drain_queued().await;
+                    self.flush_coalesced().await;
+                    drained?;
                 }
+                // Ping/pong or other non-data message
+                Ok(None) => continue,
+                // WebSocket error
+                Err(e) => return Err(e),
             }
         }
     }
 
-    /// Fetch order book snapshots from REST API
-    async fn fetch_snapshots(&self) -> Result<()> {
-        let client = reqwest::Client::new();
+    /// Process the messages already queued on the connection, up to a
+    /// batch, while coalescing
+    async fn drain_queued(&mut self) -> Result<()> {
+        if !self.coalescing {
+            return Ok(());
+        }
+        for _ in 1..coalesce::MAX_BATCH {
+            match self.client.try_recv().await {
+                Some(Ok(Some(text))) => {
+                    let received_at_us = self.client.received_at_us();
+                    if let Err(e) = self.process_message(&text, received_at_us).await {
+                        warn!(error = %e, "Failed to process message");
+                    }
+                }
+                Some(Ok(None)) => {}
+                Some(Err(e)) => return Err(e),
+                None => break,
+            }
+        }
+        Ok(())
+    }
 
-        for symbol in &self.state.config.symbols {
-            let url = format!(
-                "{}/depth?symbol={}&limit={}",
-                self.state.config.rest_endpoint, symbol, self.state.config.depth_levels
+    /// Apply the folded diffs and stop coalescing
+    async fn flush_coalesced(&mut self) {
+        self.coalescing = false;
+        self.apply_coalesced().await;
+    }
+
+    async fn apply_coalesced(&mut self) {
+        let deltas: Vec<Delta> = self.coalescer.drain().collect();
+        for delta in deltas {
+            self.state.books.apply(delta).await;
+        }
+    }
+
+    /// Switch to a warmed-up standby connection and close the current one
+    ///
+    /// The arbiter stays on: the old connection may have run ahead of the
+    /// new one, whose next messages can still be duplicates.
+    async fn take_over(&mut self, mut ws: WebSocketClient) {
+        ws.set_progress(self.state.subscriptions.clone());
+        let mut old = std::mem::replace(&mut self.client, ws);
+        old.close().await;
+        self.connection_id += 1;
+        self.last_successful_connection = Some(self.state.time.now());
+        rotation::rotated(true);
+        info!(
+            connection = self.connection_id,
+            "Standby connection took over, previous one closed"
+        );
+    }
+
+    /// Resubscribe and resync books the watchdog finds silent
+    async fn recover_stale(&mut self) -> Result<()> {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return Ok(());
+        };
+        let update_times = self.state.books.update_times();
+        let staleness = watchdog.check(&update_times, self.state.time.now_millis());
+        for (symbol, _) in &update_times {
+            metrics::set_stale(symbol, staleness.stale.contains(symbol));
+        }
+
+        for symbol in staleness.recover {
+            warn!(
+                symbol = %symbol,
+                threshold_secs = self.state.config.stale_book_secs,
+                "Book went silent while others update, resubscribing and resyncing"
             );
+            metrics::stale_recovery(&symbol);
+            self.client.resubscribe_depth(&symbol).await?;
+            self.start_snapshot(&symbol, Duration::ZERO);
+        }
+        Ok(())
+    }
 
-            info!(symbol = %symbol, url = %url, "Fetching order book snapshot");
+    /// Start fetching order book snapshots from the REST API,
+    /// `SNAPSHOT_CONCURRENCY` at a time
+    ///
+    /// Books resumed from disk are skipped: the diff stream either
+    /// continues them or reveals a gap, which triggers the fetch.
+    fn start_snapshots(&mut self) {
+        let symbols = self.state.config.symbols.clone();
+        for symbol in &symbols {
+            if self.state.books.is_resumed(symbol) {
+                info!(symbol = %symbol, "Resuming saved book, skipping snapshot");
+                continue;
+            }
+            self.start_snapshot(symbol, Duration::ZERO);
+        }
+    }
+
+    /// Start (re)syncing one book: fetch its snapshot after `delay`,
+    /// holding its diffs meanwhile
+    fn start_snapshot(&mut self, symbol: &str, delay: Duration) {
+        let state = self.state.clone();
+        let owned = symbol.to_string();
+        self.snapshots.start(symbol, delay, async move {
+            fetch_snapshot(&state.rest, &state.config, &owned).await
+        });
+    }
+
+    /// Initialize a book from its loaded snapshot and apply the diffs held
+    /// while it loaded; a failed fetch is retried for that symbol alone
+    async fn on_snapshot(&mut self, loaded: LoadedSnapshot) {
+        let snapshot = match loaded.result {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                let failures = self
+                    .snapshot_failures
+                    .entry(loaded.symbol.clone())
+                    .or_default();
+                *failures += 1;
+                let delay = backoff(self.state.config.reconnect_delay_ms, *failures);
+                metrics::snapshot_failed(&loaded.symbol);
+                warn!(
+                    error = %e,
+                    symbol = %loaded.symbol,
+                    attempt = *failures,
+                    delay_ms = delay.as_millis() as u64,
+                    "Snapshot fetch failed, retrying this symbol"
+                );
+                self.start_snapshot(&loaded.symbol, delay);
+                return;
+            }
+        };
+        self.snapshot_failures.remove(&loaded.symbol);
+        self.state.books.init(snapshot).await;
+        info!(
+            symbol = %loaded.symbol,
+            held_diffs = loaded.held.len(),
+            "Order book initialized"
+        );
 
-            let response = client
-                .get(&url)
-                .send()
-                .await?
-                .json::<OrderBookSnapshot>()
-                .await?;
+        for msg in loaded.held {
+            if let Err(e) = self.handle_message(msg).await {
+                warn!(error = %e, "Failed to process held message");
+            }
+        }
+    }
 
-            let mut manager = self.state.orderbook_manager.write().await;
-            manager.init_book(symbol, response);
+    /// Process a single WebSocket message
+    #[instrument(level = "debug", skip_all, fields(len = raw.len()))]
+    async fn process_message(&mut self, raw: &str, received_at_us: u64) -> Result<()> {
+        if let Some(tap) = &self.tap {
+            tap.send(raw, received_at_us);
+        }
+        let event = match parser::parse_event(raw)? {
+            Some(event) => event,
+            None => return self.on_control(raw).await,
+        };
+        let stream = match &event {
+            MarketEvent::DepthDelta(_) => "depth",
+            MarketEvent::Trade(_) => "trade",
+            _ => "other",
+        };
+        metrics::message_received(event.symbol().unwrap_or_default(), stream);
+        let inbound = InboundMessage {
+            event,
+            received_at_us,
+            parsed_at_us: self.state.time.now_micros(),
+        };
+        self.accept(inbound, Feed::Primary).await
+    }
 
-            info!(symbol = %symbol, "Order book initialized");
+    /// Pass a parsed message on, unless the other connection already did
+    async fn accept(&mut self, inbound: InboundMessage, feed: Feed) -> Result<()> {
+        if let Some(arbiter) = self.arbiter.as_mut() {
+            if !arbiter.accept(&inbound.event, feed) {
+                return Ok(());
+            }
         }
 
+        let Some(alignment) = self.alignment.as_mut() else {
+            return self.dispatch(inbound).await;
+        };
+
+        if let Some(passthrough) = alignment.push(inbound, self.state.time.now()) {
+            self.dispatch(passthrough).await?;
+        }
+        self.flush_aligned().await;
+
         Ok(())
     }
 
-    /// Process a single WebSocket message
-    async fn process_message(&self, raw: &str) -> Result<()> {
-        let parsed = ParsedMessage::parse(raw)?;
-
-        match parsed {
-            ParsedMessage::DepthUpdate(update) => {
-                let mut manager = self.state.orderbook_manager.write().await;
-                if manager.apply_update(&update) {
-                    // Publish updated state
-                    if let Some(state) = manager.get_state(&update.symbol) {
-                        drop(manager); // Release lock before publishing
-                        self.state.publisher.publish(&state).await?;
-                    }
+    /// Handle a message that isn't market data: settle control replies and
+    /// report exchange errors, resubscribing streams whose SUBSCRIBE was
+    /// rejected
+    async fn on_control(&mut self, raw: &str) -> Result<()> {
+        match ParsedMessage::parse_control(raw) {
+            Some(ParsedMessage::SubscriptionAck(ack)) => {
+                debug!(id = ack.id, "Control request confirmed");
+                metrics::subscription_acked();
+                self.client.on_ack(&ack);
+            }
+            Some(ParsedMessage::ExchangeError(error)) => {
+                warn!(
+                    id = ?error.id,
+                    code = error.code,
+                    msg = %error.msg,
+                    "Exchange error"
+                );
+                metrics::exchange_error(error.code);
+                self.client.on_error(&error).await?;
+            }
+            _ => {
+                tracing::trace!(msg = %raw, "Unknown message type");
+                metrics::message_received("", "other");
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle aligned messages whose hold delay has elapsed
+    async fn flush_aligned(&mut self) {
+        let ready = match self.alignment.as_mut() {
+            Some(alignment) => alignment.pop_ready(self.state.time.now()),
+            None => return,
+        };
+
+        for msg in ready {
+            if let Err(e) = self.dispatch(msg).await {
+                warn!(error = %e, "Failed to process aligned message");
+            }
+        }
+    }
+
+    /// Handle a message, unless it is a diff held for a loading snapshot
+    async fn dispatch(&mut self, inbound: InboundMessage) -> Result<()> {
+        match self.snapshots.hold(inbound) {
+            Some(inbound) => self.handle_message(inbound).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Hand a normalized event to its book, or apply and publish a trade
+    #[instrument(level = "debug", skip_all, fields(symbol = inbound.event.symbol()))]
+    async fn handle_message(&mut self, inbound: InboundMessage) -> Result<()> {
+        match inbound.event {
+            MarketEvent::DepthDelta(update) => {
+                let delta = Delta {
+                    update,
+                    received_at_us: inbound.received_at_us,
+                    parsed_at_us: inbound.parsed_at_us,
+                    released_at_us: self.state.time.now_micros(),
+                    connection_id: self.connection_id,
+                    shard: self.shard,
+                };
+                if !self.coalescing {
+                    self.state.books.apply(delta).await;
+                } else if let Some(earlier) = self.coalescer.push(delta) {
+                    self.state.books.apply(earlier).await;
                 }
             }
-            ParsedMessage::Trade(trade) => {
-                // For now, we just log trades
-                // In a full implementation, you might want to publish these too
+            MarketEvent::BookSnapshot(snapshot) => {
+                // Diffs folded so far predate the snapshot
+                self.apply_coalesced().await;
+                self.state.books.init(snapshot).await;
+            }
+            MarketEvent::Trade(trade) => {
                 tracing::trace!(
                     symbol = %trade.symbol,
                     price = %trade.price,
                     qty = %trade.quantity,
                     "Trade received"
                 );
+                if self.state.degradation.analytics_enabled() {
+                    let sheds = self.state.degradation.analytics_sheds();
+                    let mut analytics = self.state.analytics.write().await;
+                    analytics.note_sheds(sheds, trade.trade_time);
+                    analytics.on_trade(&trade);
+                    drop(analytics);
+                    self.state.books.trade(trade.clone()).await;
+                }
+                self.state.publisher.publish_trade(&trade).await?;
             }
-            ParsedMessage::Unknown(msg) => {
-                tracing::trace!(msg = %msg, "Unknown message type");
+            other => {
+                tracing::trace!(event = ?other, "Unhandled market event");
             }
         }
 
         Ok(())
     }
 }
+
+/// WebSocket client subscribed to the configured symbols, as connection
+/// `feed`
+pub(crate) fn client(
+    config: &Config,
+    tls: &Tls,
+    progress: Arc<SubscriptionProgress>,
+    feed: Feed,
+) -> WebSocketClient {
+    let symbols = config
+        .symbols
+        .iter()
+        .map(|s| (s.clone(), config.depth_update_speed_for(s)))
+        .collect();
+    WebSocketClient::new(&config.ws_endpoint, symbols, progress)
+        .with_tls(tls.clone())
+        .with_keepalive(KeepalivePolicy::from_config(config))
+        .with_intake(
+            feed.name(),
+            config.ws_intake_capacity,
+            config.ws_intake_policy,
+        )
+        .with_batched_subscribe(
+            config.subscribe_batch_size,
+            Duration::from_millis(config.subscribe_interval_ms),
+        )
+        .with_stream_endpoint(config.ws_stream_endpoint)
+}
+
+/// Fetch a symbol's order book snapshot from the REST API
+#[instrument(skip(rest, config))]
+pub async fn fetch_snapshot(
+    rest: &RestClient,
+    config: &Config,
+    symbol: &str,
+) -> Result<BookSnapshot> {
+    // Fetch beyond the visible depth so the overflow buffer starts populated
+    let limit = config.depth_levels_for(symbol) + config.overflow_levels;
+
+    info!(symbol = %symbol, limit, "Fetching order book snapshot");
+
+    let body = rest
+        .get(
+            "depth",
+            &[("symbol", symbol), ("limit", &limit.to_string())],
+            depth_weight(limit),
+        )
+        .await?;
+    let response: OrderBookSnapshot = serde_json::from_str(&body)?;
+    Ok(response.into_event(symbol))
+}
+
+/// Current wall-clock time in microseconds since the Unix epoch
+pub(crate) fn now_micros() -> u64 {
+    chrono::Utc::now().timestamp_micros() as u64
+}
+
+/// Log every book's status and the latency summary every 30s
+async fn log_status(state: Arc<AppState>) {
+    let mut health_interval = interval(Duration::from_secs(30));
+    loop {
+        health_interval.tick().await;
+        // Health check logging
+        for book in state.books.states() {
+            if let Some(mid) = book.metrics.mid_price {
+                info!(
+                    symbol = %book.symbol,
+                    mid_price = %mid,
+                    spread_bps = ?book.metrics.spread_bps,
+                    imbalance = ?book.metrics.imbalance,
+                    "Order book status"
+                );
+            }
+        }
+
+        // Latency of the last complete window
+        for (symbol, stages) in state.latency.matrix().symbols {
+            let p99 = |stage| stages.get(&stage).map(|s| s.p99_us).unwrap_or(0);
+            info!(
+                symbol = %symbol,
+                updates = stages.get(&Stage::Total).map(|s| s.count).unwrap_or(0),
+                exchange_p99_us = p99(Stage::Exchange),
+                parse_p99_us = p99(Stage::Parse),
+                apply_p99_us = p99(Stage::Apply),
+                publish_p99_us = p99(Stage::Publish),
+                total_p99_us = p99(Stage::Total),
+                "Latency summary"
+            );
+        }
+    }
+}
+
+/// Exponential backoff from `base_ms` for the `attempt`th retry, capped at
+/// `MAX_BACKOFF_MS`
+fn backoff(base_ms: u64, attempt: u32) -> Duration {
+    Duration::from_millis((base_ms * 2u64.pow(attempt.min(6))).min(MAX_BACKOFF_MS))
+}
+
+/// Next resync request, or never without a receiver
+async fn next_resync(resyncs: &mut Option<mpsc::UnboundedReceiver<String>>) -> Option<String> {
+    match resyncs {
+        Some(resyncs) => resyncs.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Next message of the redundant connection, or never without one
+async fn next_redundant(
+    redundant: &mut Option<mpsc::Receiver<InboundMessage>>,
+) -> Option<InboundMessage> {
+    match redundant {
+        Some(redundant) => redundant.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Next event of the standby connection, or never without one
+async fn next_standby(standby: &mut Option<Standby>) -> StandbyEvent {
+    match standby {
+        Some(standby) => standby.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Sleep until the deadline, or forever when there is none
+pub(super) async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
@@ -1,7 +1,38 @@
 //! WebSocket module for Binance connection management
 
+mod alignment;
 mod client;
+mod coalesce;
+mod intake;
+mod keepalive;
 mod manager;
+pub mod redundant;
+mod rotation;
+mod snapshots;
+pub mod subscription;
+mod tls;
+mod watchdog;
 
+pub use alignment::AlignmentBuffer;
 pub use client::WebSocketClient;
-pub use manager::WebSocketManager;
+pub use coalesce::Coalescer;
+pub use intake::{Frame, Intake, IntakeQueue};
+pub use keepalive::{Keepalive, KeepaliveFrame, KeepalivePolicy};
+pub(crate) use manager::{client, now_micros};
+pub use manager::{fetch_snapshot, WebSocketManager};
+pub use snapshots::{LoadedSnapshot, SnapshotLoader};
+pub use subscription::{SubscriptionProgress, SubscriptionStatus};
+pub use tls::Tls;
+pub use watchdog::{Staleness, StalenessWatchdog};
+
+use crate::event::MarketEvent;
+
+/// A normalized event with its local receive timestamp
+#[derive(Debug, Clone)]
+pub struct InboundMessage {
+    pub event: MarketEvent,
+    /// Local wall-clock receive time (microseconds since epoch)
+    pub received_at_us: u64,
+    /// Local wall-clock time parsing finished (microseconds since epoch)
+    pub parsed_at_us: u64,
+}
@@ -0,0 +1,267 @@
+//! Redundant feed over a second connection, arbitrated by update ID
+//!
+//! With `REDUNDANT_WS_ENDPOINT` set, a second connection to another
+//! endpoint subscribes to the same streams. Its messages are parsed on
+//! their own task and merged into the manager's loop, where the
+//! [`Arbiter`] lets through the first copy of each depth diff (by final
+//! update ID) and trade (by trade ID) and drops the later duplicate, so a
+//! stall on either TCP connection leaves no gap in the data.
+
+use prometheus::{IntCounterVec, Opts};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use super::manager::sleep_until_deadline;
+use super::{client, now_micros, InboundMessage, SubscriptionProgress, Tls, WebSocketClient};
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+use crate::event::MarketEvent;
+use crate::parser::{self, ParsedMessage};
+use crate::shutdown::Shutdown;
+
+/// Messages buffered from the redundant connection before it waits on the
+/// manager
+const CHANNEL_CAPACITY: usize = 4096;
+
+static ARBITRATED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "websocket_arbitrated_messages_total",
+            "Market data messages of the redundant feeds, by connection (primary, redundant or standby) and result (won or duplicate)",
+        ),
+        &["feed", "result"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Connection a message arrived on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feed {
+    Primary,
+    Redundant,
+    /// A new connection warming up to replace the primary (see `rotation`)
+    Standby,
+}
+
+impl Feed {
+    /// Lower-case name, as used in metric labels
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feed::Primary => "primary",
+            Feed::Redundant => "redundant",
+            Feed::Standby => "standby",
+        }
+    }
+}
+
+/// Last depth update and trade passed on per symbol
+#[derive(Debug, Default)]
+pub struct Arbiter {
+    depth: HashMap<String, u64>,
+    trades: HashMap<String, u64>,
+}
+
+impl Arbiter {
+    /// Whether `event` from `feed` is the first copy seen; later copies
+    /// (the same or older IDs) are duplicates to drop
+    pub fn accept(&mut self, event: &MarketEvent, feed: Feed) -> bool {
+        let (last_ids, symbol, id) = match event {
+            MarketEvent::DepthDelta(update) => {
+                (&mut self.depth, &update.symbol, update.final_update_id)
+            }
+            MarketEvent::Trade(trade) => (&mut self.trades, &trade.symbol, trade.trade_id),
+            _ => return true,
+        };
+        let won = match last_ids.get_mut(symbol) {
+            Some(last) if id <= *last => false,
+            Some(last) => {
+                *last = id;
+                true
+            }
+            None => {
+                last_ids.insert(symbol.clone(), id);
+                true
+            }
+        };
+        let result = if won { "won" } else { "duplicate" };
+        ARBITRATED.with_label_values(&[feed.name(), result]).inc();
+        won
+    }
+}
+
+/// Receiver of the redundant connection's messages, fed until shutdown
+pub fn spawn(
+    config: Arc<Config>,
+    tls: Tls,
+    endpoint: String,
+    shutdown: Shutdown,
+) -> mpsc::Receiver<InboundMessage> {
+    LazyLock::force(&ARBITRATED);
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = run(&config, &tls, &endpoint, tx) => {}
+            _ = shutdown.wait() => {}
+        }
+    });
+    rx
+}
+
+/// Keep the redundant connection up, reconnecting after a backoff, until
+/// the manager stops receiving
+async fn run(config: &Config, tls: &Tls, endpoint: &str, tx: mpsc::Sender<InboundMessage>) {
+    let mut failures = 0;
+    loop {
+        let result = follow(config, tls, endpoint, &tx, &mut failures).await;
+        if tx.is_closed() {
+            return;
+        }
+        failures += 1;
+        let delay = Duration::from_millis(
+            (config.reconnect_delay_ms * 2u64.pow(failures.min(6))).min(60_000),
+        );
+        if let Err(e) = result {
+            warn!(
+                error = %e,
+                endpoint = %endpoint,
+                delay_ms = delay.as_millis() as u64,
+                "Redundant feed disconnected, reconnecting"
+            );
+        }
+        sleep(delay).await;
+    }
+}
+
+/// Read one connection, passing its market data on parsed
+async fn follow(
+    config: &Config,
+    tls: &Tls,
+    endpoint: &str,
+    tx: &mpsc::Sender<InboundMessage>,
+    failures: &mut u32,
+) -> Result<()> {
+    let mut ws = client(
+        config,
+        tls,
+        Arc::new(SubscriptionProgress::default()),
+        Feed::Redundant,
+    );
+    ws.set_endpoint(endpoint);
+    ws.connect().await?;
+    info!(endpoint = %endpoint, "Redundant feed connected");
+
+    loop {
+        ws.check_subscriptions()?;
+        let Some(inbound) = read(&mut ws, Feed::Redundant).await? else {
+            continue;
+        };
+        *failures = 0;
+        if tx.send(inbound).await.is_err() {
+            ws.close().await;
+            return Err(MarketDataError::WebSocketConnection(
+                "Manager stopped".to_string(),
+            ));
+        }
+    }
+}
+
+/// Next market data message of a connection read off the manager's loop,
+/// settling control replies and sending keepalives in between
+pub(super) async fn read(ws: &mut WebSocketClient, feed: Feed) -> Result<Option<InboundMessage>> {
+    let keepalive_at = ws.keepalive_deadline();
+    let raw = tokio::select! {
+        received = ws.recv() => match received? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        },
+        _ = sleep_until_deadline(keepalive_at) => {
+            ws.keep_alive().await?;
+            return Ok(None);
+        }
+    };
+    let received_at_us = ws.received_at_us();
+
+    let event = match parser::parse_event(&raw) {
+        Ok(Some(event)) => event,
+        Ok(None) => {
+            match ParsedMessage::parse_control(&raw) {
+                Some(ParsedMessage::SubscriptionAck(ack)) => ws.on_ack(&ack),
+                Some(ParsedMessage::ExchangeError(error)) => {
+                    warn!(feed = feed.name(), code = error.code, msg = %error.msg, "Exchange error");
+                    ws.on_error(&error).await?;
+                }
+                _ => debug!(feed = feed.name(), "Unknown message"),
+            }
+            return Ok(None);
+        }
+        Err(e) => {
+            warn!(feed = feed.name(), error = %e, "Failed to parse message");
+            return Ok(None);
+        }
+    };
+    Ok(Some(InboundMessage {
+        event,
+        received_at_us,
+        parsed_at_us: now_micros(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{DepthDelta, Trade, Venue};
+    use crate::orderbook::Side;
+    use rust_decimal_macros::dec;
+
+    fn depth(symbol: &str, first: u64, last: u64) -> MarketEvent {
+        MarketEvent::DepthDelta(DepthDelta {
+            venue: Venue::Binance,
+            event_time: 1,
+            symbol: symbol.to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            bids: vec![],
+            asks: vec![],
+        })
+    }
+
+    fn trade(trade_id: u64) -> MarketEvent {
+        MarketEvent::Trade(Trade {
+            venue: Venue::Binance,
+            symbol: "BTCUSDT".to_string(),
+            event_time: 1,
+            trade_id,
+            price: dec!(100),
+            quantity: dec!(1),
+            taker_side: Side::Bid,
+            trade_time: 1,
+        })
+    }
+
+    #[test]
+    fn test_first_copy_wins() {
+        let mut arbiter = Arbiter::default();
+
+        assert!(arbiter.accept(&depth("BTCUSDT", 1, 10), Feed::Primary));
+        assert!(!arbiter.accept(&depth("BTCUSDT", 1, 10), Feed::Redundant));
+
+        // The primary stalls and the redundant feed moves ahead
+        assert!(arbiter.accept(&depth("BTCUSDT", 11, 20), Feed::Redundant));
+        assert!(arbiter.accept(&depth("BTCUSDT", 21, 30), Feed::Redundant));
+        assert!(!arbiter.accept(&depth("BTCUSDT", 11, 20), Feed::Primary));
+        assert!(!arbiter.accept(&depth("BTCUSDT", 21, 30), Feed::Primary));
+        assert!(arbiter.accept(&depth("BTCUSDT", 31, 40), Feed::Primary));
+
+        // Symbols and streams are arbitrated apart
+        assert!(arbiter.accept(&depth("ETHUSDT", 1, 5), Feed::Redundant));
+        assert!(arbiter.accept(&trade(7), Feed::Primary));
+        assert!(!arbiter.accept(&trade(7), Feed::Redundant));
+        assert!(arbiter.accept(&trade(8), Feed::Redundant));
+    }
+}
@@ -0,0 +1,188 @@
+//! Proactive rotation ahead of Binance's 24-hour connection limit
+//!
+//! Binance closes every WebSocket connection after 24 hours. Rather than
+//! meet that as an error and a backoff, the manager opens a standby
+//! connection `WS_ROTATE_AFTER_SECS` into the current one's life. The
+//! standby subscribes to the same streams on its own task and, once all
+//! its subscriptions are confirmed, overlaps the current connection for
+//! `OVERLAP`; its messages meanwhile go through the [`Arbiter`] like a
+//! redundant feed's. Then it takes over and the old connection is closed,
+//! so the books see no gap. A standby that fails to warm up is retried
+//! after `RETRY_AFTER`, well ahead of the exchange's limit.
+//!
+//! [`Arbiter`]: super::redundant::Arbiter
+
+use prometheus::{IntCounterVec, Opts};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::sleep_until;
+use tracing::info;
+
+use super::redundant::{read, Feed};
+use super::{client, InboundMessage, SubscriptionProgress, Tls, WebSocketClient};
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+
+/// How long the standby overlaps the current connection once subscribed
+pub const OVERLAP: Duration = Duration::from_secs(5);
+
+/// Wait before trying again after a standby failed to warm up
+pub const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Messages buffered from the standby before it waits on the manager
+const CHANNEL_CAPACITY: usize = 4096;
+
+static ROTATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "websocket_rotations_total",
+            "Proactive connection rotations, by result (ok or failed)",
+        ),
+        &["result"],
+    )
+    .unwrap();
+    let _ = prometheus::register(Box::new(counter.clone()));
+    counter
+});
+
+/// Count a finished rotation attempt
+pub fn rotated(ok: bool) {
+    let result = if ok { "ok" } else { "failed" };
+    ROTATIONS.with_label_values(&[result]).inc();
+}
+
+/// What the standby connection has for the manager
+pub enum StandbyEvent {
+    /// A market data message received while warming up
+    Message(InboundMessage),
+    /// The warmed-up connection, ready to take over, once every message
+    /// before it was handed out
+    Ready(Result<Box<WebSocketClient>>),
+}
+
+/// A connection warming up on its own task to replace the current one;
+/// dropping it abandons the connection
+pub struct Standby {
+    messages: mpsc::Receiver<InboundMessage>,
+    task: JoinHandle<Result<Box<WebSocketClient>>>,
+}
+
+impl Standby {
+    /// Connect to `endpoint` and warm up in the background, overlapping
+    /// for `overlap` once subscribed
+    pub fn spawn(config: &Config, tls: &Tls, endpoint: &str, overlap: Duration) -> Self {
+        LazyLock::force(&ROTATIONS);
+        let mut ws = client(
+            config,
+            tls,
+            Arc::new(SubscriptionProgress::default()),
+            Feed::Standby,
+        );
+        ws.set_endpoint(endpoint);
+        let (tx, messages) = mpsc::channel(CHANNEL_CAPACITY);
+        Self {
+            messages,
+            task: tokio::spawn(warm_up(ws, tx, overlap)),
+        }
+    }
+
+    /// Next message, or the connection once warm and drained
+    pub async fn next(&mut self) -> StandbyEvent {
+        if let Some(inbound) = self.messages.recv().await {
+            return StandbyEvent::Message(inbound);
+        }
+        let result = (&mut self.task).await.unwrap_or_else(|e| {
+            Err(MarketDataError::WebSocketConnection(format!(
+                "Standby task failed: {}",
+                e
+            )))
+        });
+        StandbyEvent::Ready(result)
+    }
+}
+
+impl Drop for Standby {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Connect, subscribe and pass messages on until every subscription is
+/// confirmed and `overlap` has passed since
+async fn warm_up(
+    mut ws: WebSocketClient,
+    tx: mpsc::Sender<InboundMessage>,
+    overlap: Duration,
+) -> Result<Box<WebSocketClient>> {
+    ws.connect().await?;
+    let mut warm_at = None;
+
+    loop {
+        ws.check_subscriptions()?;
+        if ws.is_subscribed() && warm_at.is_none() {
+            warm_at = Some(Instant::now() + overlap);
+        }
+        let read = tokio::select! {
+            read = read(&mut ws, Feed::Standby) => read?,
+            _ = sleep_until(warm_at.unwrap_or_else(Instant::now).into()), if warm_at.is_some() => {
+                info!("Standby connection subscribed and overlapping, ready to take over");
+                return Ok(Box::new(ws));
+            }
+        };
+        if let Some(inbound) = read {
+            if tx.send(inbound).await.is_err() {
+                ws.close().await;
+                return Err(MarketDataError::WebSocketConnection(
+                    "Manager stopped".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    const TRADE: &str = r#"{"stream":"btcusdt@trade","data":{"e":"trade","E":1,"s":"BTCUSDT","t":7,"p":"100.5","q":"0.1","b":1,"a":2,"T":1,"m":true}}"#;
+
+    #[tokio::test]
+    async fn test_standby_hands_out_messages_then_itself() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            for _ in 0..3 {
+                ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+            }
+            while ws.next().await.is_some() {}
+        });
+
+        let config = Config {
+            symbols: vec!["BTCUSDT".to_string()],
+            ..Config::default()
+        };
+        let mut standby = Standby::spawn(
+            &config,
+            &Tls::default(),
+            &endpoint,
+            Duration::from_millis(200),
+        );
+
+        let mut messages = 0;
+        let ws = loop {
+            match standby.next().await {
+                StandbyEvent::Message(_) => messages += 1,
+                StandbyEvent::Ready(result) => break result.unwrap(),
+            }
+        };
+        assert_eq!(messages, 3);
+        assert!(ws.is_subscribed());
+    }
+}
@@ -0,0 +1,208 @@
+//! Concurrent snapshot loading
+//!
+//! Snapshots are fetched a few at a time in the background while the
+//! connection keeps streaming, so a long symbol list no longer holds every
+//! book back until the last snapshot arrives. Depth diffs of a symbol whose
+//! snapshot is still in flight are held here and handed back with it; the
+//! book then skips those the snapshot already covers and applies the rest,
+//! as Binance's sync procedure prescribes.
+//!
+//! Each symbol syncs independently: a failed fetch is retried for that
+//! symbol alone, after a backoff, while the other books keep streaming, and
+//! a book discarded on a sequence gap or crossing is resynced the same way.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::{Id, JoinSet};
+
+use super::InboundMessage;
+use crate::error::{MarketDataError, Result};
+use crate::event::{BookSnapshot, MarketEvent};
+use crate::time::Clock;
+
+/// Diffs held per pending symbol; the oldest are dropped beyond this, which
+/// a snapshot fetched that much later covers anyway
+const MAX_HELD_DIFFS: usize = 10_000;
+
+/// A finished snapshot fetch
+#[derive(Debug)]
+pub struct LoadedSnapshot {
+    pub symbol: String,
+    pub result: Result<BookSnapshot>,
+    /// Depth diffs received while the fetch was in flight, oldest first
+    pub held: Vec<InboundMessage>,
+}
+
+/// Snapshot fetches in flight, limited to a number at a time
+#[derive(Debug)]
+pub struct SnapshotLoader {
+    tasks: JoinSet<Result<BookSnapshot>>,
+    symbols: HashMap<Id, String>,
+    permits: Arc<Semaphore>,
+    held: HashMap<String, VecDeque<InboundMessage>>,
+    /// Times the retry delays
+    clock: Clock,
+}
+
+impl SnapshotLoader {
+    /// Run at most `concurrency` fetches at once
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            tasks: JoinSet::new(),
+            symbols: HashMap::new(),
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            held: HashMap::new(),
+            clock: Clock::wall(),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Queue `fetch` for `symbol` to run after `delay` and hold its depth
+    /// diffs until it finishes; ignored if a fetch for it is already pending
+    pub fn start<F>(&mut self, symbol: &str, delay: Duration, fetch: F)
+    where
+        F: Future<Output = Result<BookSnapshot>> + Send + 'static,
+    {
+        if self.held.contains_key(symbol) {
+            return;
+        }
+        self.held.insert(symbol.to_string(), VecDeque::new());
+        let permits = self.permits.clone();
+        let clock = self.clock.clone();
+        let handle = self.tasks.spawn(async move {
+            if !delay.is_zero() {
+                clock.sleep(delay).await;
+            }
+            let _permit = permits.acquire_owned().await;
+            fetch.await
+        });
+        self.symbols.insert(handle.id(), symbol.to_string());
+    }
+
+    /// Whether a fetch for `symbol` is pending
+    pub fn is_pending(&self, symbol: &str) -> bool {
+        self.held.contains_key(symbol)
+    }
+
+    /// Whether no fetch is pending
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Hold `msg` if it is a depth diff of a pending symbol, or give it back
+    pub fn hold(&mut self, msg: InboundMessage) -> Option<InboundMessage> {
+        let MarketEvent::DepthDelta(update) = &msg.event else {
+            return Some(msg);
+        };
+        let Some(held) = self.held.get_mut(&update.symbol) else {
+            return Some(msg);
+        };
+        if held.len() == MAX_HELD_DIFFS {
+            held.pop_front();
+        }
+        held.push_back(msg);
+        None
+    }
+
+    /// Wait for the next fetch to finish; pending forever when none is
+    /// pending. Cancel safe.
+    pub async fn next(&mut self) -> LoadedSnapshot {
+        let Some(joined) = self.tasks.join_next_with_id().await else {
+            return std::future::pending().await;
+        };
+        let (id, result) = match joined {
+            Ok((id, result)) => (id, result),
+            Err(e) => (
+                e.id(),
+                Err(MarketDataError::RestApiError(format!(
+                    "Snapshot fetch failed: {}",
+                    e
+                ))),
+            ),
+        };
+        let symbol = self.symbols.remove(&id).unwrap_or_default();
+        let held = self.held.remove(&symbol).unwrap_or_default().into();
+        LoadedSnapshot {
+            symbol,
+            result,
+            held,
+        }
+    }
+
+    /// Abandon every pending fetch and the diffs held for it
+    pub fn clear(&mut self) {
+        self.tasks.abort_all();
+        self.tasks.detach_all();
+        self.symbols.clear();
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{DepthDelta, Venue};
+
+    fn snapshot(symbol: &str) -> BookSnapshot {
+        BookSnapshot {
+            venue: Venue::Binance,
+            symbol: symbol.to_string(),
+            last_update_id: 100,
+            bids: vec![],
+            asks: vec![],
+        }
+    }
+
+    fn diff(symbol: &str, id: u64) -> InboundMessage {
+        InboundMessage {
+            event: MarketEvent::DepthDelta(DepthDelta {
+                venue: Venue::Binance,
+                event_time: 0,
+                symbol: symbol.to_string(),
+                first_update_id: id,
+                final_update_id: id,
+                bids: vec![],
+                asks: vec![],
+            }),
+            received_at_us: 0,
+            parsed_at_us: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_holds_diffs_until_snapshot_loads() {
+        let mut loader = SnapshotLoader::new(1);
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        loader.start("BTCUSDT", Duration::ZERO, async move {
+            let _ = released.await;
+            Ok(snapshot("BTCUSDT"))
+        });
+        loader.start("ETHUSDT", Duration::ZERO, async { Ok(snapshot("ETHUSDT")) });
+        assert!(loader.is_pending("BTCUSDT"));
+
+        // Diffs of pending symbols are held, others pass through
+        assert!(loader.hold(diff("BTCUSDT", 101)).is_none());
+        assert!(loader.hold(diff("BTCUSDT", 102)).is_none());
+        assert!(loader.hold(diff("SOLUSDT", 1)).is_some());
+
+        // One fetch at a time: ETHUSDT waits for BTCUSDT's permit
+        release.send(()).unwrap();
+        let first = loader.next().await;
+        assert_eq!(first.symbol, "BTCUSDT");
+        assert_eq!(first.held.len(), 2);
+        assert!(!loader.is_pending("BTCUSDT"));
+        assert!(loader.hold(diff("BTCUSDT", 103)).is_some());
+
+        let second = loader.next().await;
+        assert_eq!(second.symbol, "ETHUSDT");
+        assert!(second.held.is_empty());
+        assert!(loader.is_empty());
+    }
+}
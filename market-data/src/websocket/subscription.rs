@@ -0,0 +1,210 @@
+//! Batched runtime subscriptions
+//!
+//! Large symbol sets don't fit in a combined-stream URL, so streams can
+//! instead be subscribed after connecting with `SUBSCRIBE` requests. Streams
+//! are split into batches, each sent with its own request id and paced to
+//! stay under Binance's incoming message rate; every id must be confirmed
+//! (`{"result": null, "id": N}`) before the deadline or the connection is
+//! treated as failed. A rejected SUBSCRIBE is sent again a few times before
+//! the connection is given up on.
+//!
+//! A combined-stream URL naming more than `MAX_URL_STREAMS` streams grows
+//! past what the exchange accepts, so a connection configured for URL
+//! streams subscribes after connecting instead when there are more.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Binance limit on streams per connection
+pub const MAX_STREAMS_PER_CONNECTION: usize = 1024;
+
+/// Most streams named in a combined-stream URL
+pub const MAX_URL_STREAMS: usize = 200;
+
+/// How long to wait for all confirmations after the last request
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Times a rejected SUBSCRIBE is sent again
+pub const MAX_SUBSCRIBE_RETRIES: u32 = 3;
+
+#[derive(Serialize)]
+struct SubscribeRequest<'a> {
+    method: &'static str,
+    params: &'a [String],
+    id: u64,
+}
+
+/// An unconfirmed control request
+#[derive(Debug, Clone)]
+pub struct PendingRequest {
+    /// `SUBSCRIBE` or `UNSUBSCRIBE`
+    pub method: &'static str,
+    pub streams: Vec<String>,
+    /// Streams it adds to the confirmed count
+    pub new_streams: usize,
+    /// Times it was sent again after a rejection
+    pub retries: u32,
+}
+
+/// Outstanding SUBSCRIBE requests on one connection
+#[derive(Debug, Default)]
+pub struct PendingSubscriptions {
+    next_id: u64,
+    pending: HashMap<u64, PendingRequest>,
+    deadline: Option<Instant>,
+}
+
+impl PendingSubscriptions {
+    /// Split streams into requests of at most `batch_size` streams;
+    /// returns each request's stream count and payload
+    pub fn requests(&mut self, streams: &[String], batch_size: usize) -> Vec<(usize, String)> {
+        streams
+            .chunks(batch_size.clamp(1, MAX_STREAMS_PER_CONNECTION))
+            .map(|batch| {
+                let payload = self.send(PendingRequest {
+                    method: "SUBSCRIBE",
+                    streams: batch.to_vec(),
+                    new_streams: batch.len(),
+                    retries: 0,
+                });
+                (batch.len(), payload)
+            })
+            .collect()
+    }
+
+    /// One `method` request (`SUBSCRIBE` or `UNSUBSCRIBE`) for streams
+    /// already counted as subscribed; it must be confirmed like a batch
+    pub fn control(&mut self, method: &'static str, streams: &[String]) -> String {
+        self.send(PendingRequest {
+            method,
+            streams: streams.to_vec(),
+            new_streams: 0,
+            retries: 0,
+        })
+    }
+
+    /// A rejected request sent again under a new id
+    pub fn retry(&mut self, rejected: PendingRequest) -> String {
+        self.send(PendingRequest {
+            retries: rejected.retries + 1,
+            ..rejected
+        })
+    }
+
+    /// Track `request` under the next id and return its payload
+    fn send(&mut self, request: PendingRequest) -> String {
+        self.next_id += 1;
+        let payload = serde_json::to_string(&SubscribeRequest {
+            method: request.method,
+            params: &request.streams,
+            id: self.next_id,
+        })
+        .unwrap_or_default();
+        self.pending.insert(self.next_id, request);
+        payload
+    }
+
+    /// Start the confirmation deadline once every request is sent
+    pub fn sent_all(&mut self, now: Instant) {
+        self.deadline = Some(now + CONFIRM_TIMEOUT);
+    }
+
+    /// Settle a confirmed request; returns the number of streams it added
+    pub fn confirm(&mut self, id: u64) -> usize {
+        self.pending
+            .remove(&id)
+            .map_or(0, |request| request.new_streams)
+    }
+
+    /// Settle a rejected request, returning it if it was pending
+    pub fn reject(&mut self, id: u64) -> Option<PendingRequest> {
+        self.pending.remove(&id)
+    }
+
+    /// Whether confirmations are still outstanding past the deadline
+    pub fn overdue(&self, now: Instant) -> bool {
+        !self.pending.is_empty() && self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Number of unconfirmed requests
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Subscription progress of the current connection
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SubscriptionStatus {
+    /// Whether the WebSocket is currently connected
+    pub connected: bool,
+    /// `url` (combined-stream URL) or `batched` (SUBSCRIBE requests)
+    pub mode: &'static str,
+    pub total_streams: usize,
+    pub requested_streams: usize,
+    pub confirmed_streams: usize,
+    pub pending_requests: usize,
+    /// Last rejection or timeout, cleared on reconnect
+    pub last_error: Option<String>,
+}
+
+/// Shared view of subscription progress for the status endpoint
+#[derive(Debug, Default)]
+pub struct SubscriptionProgress(Mutex<SubscriptionStatus>);
+
+impl SubscriptionProgress {
+    /// Current progress
+    pub fn status(&self) -> SubscriptionStatus {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Update progress in place
+    pub fn update(&self, f: impl FnOnce(&mut SubscriptionStatus)) {
+        f(&mut self.0.lock().unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_confirmed_by_id() {
+        let streams: Vec<String> = (0..5).map(|i| format!("sym{}@trade", i)).collect();
+        let mut pending = PendingSubscriptions::default();
+
+        let requests = pending.requests(&streams, 2);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[2],
+            (
+                1,
+                r#"{"method":"SUBSCRIBE","params":["sym4@trade"],"id":3}"#.to_string()
+            )
+        );
+
+        let now = Instant::now();
+        pending.sent_all(now);
+
+        assert_eq!(pending.confirm(1), 2);
+        assert_eq!(pending.confirm(1), 0);
+
+        // A rejected batch is resent under a new id, still adding its streams
+        let rejected = pending.reject(2).unwrap();
+        assert_eq!(rejected.streams, ["sym2@trade", "sym3@trade"]);
+        assert_eq!(
+            pending.retry(rejected),
+            r#"{"method":"SUBSCRIBE","params":["sym2@trade","sym3@trade"],"id":4}"#
+        );
+        assert!(pending.reject(2).is_none());
+        assert_eq!(pending.reject(4).unwrap().retries, 1);
+
+        assert!(!pending.overdue(now));
+        assert!(pending.overdue(now + CONFIRM_TIMEOUT));
+    }
+}
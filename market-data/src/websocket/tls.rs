@@ -0,0 +1,235 @@
+//! TLS for the WebSocket connections
+//!
+//! The backend is chosen at build time: the `native-tls` feature (the
+//! default: the platform's TLS library and trust store) or `rustls` (pure
+//! Rust with the bundled webpki roots), which takes precedence when both
+//! are compiled in. `TLS_CA_FILE` pins the trusted roots to the
+//! certificates of a PEM bundle instead of the built-in ones, and
+//! `TLS_HANDSHAKE_TIMEOUT_MS` bounds connecting: the TCP connect, the TLS
+//! handshake and the WebSocket upgrade. The TCP socket is opened here with
+//! the configured options (see `socket`). Built with neither backend
+//! (e.g. `--features client`), only plain `ws://` URLs connect.
+
+use reqwest::Url;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::{self, handshake::client::Response};
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+use tokio_tungstenite::Connector;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::config::Config;
+use crate::error::{MarketDataError, Result};
+use crate::socket::TcpOptions;
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Without a TLS backend there is nothing to configure
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+#[derive(Clone)]
+enum Connector {}
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// How WebSocket connections are opened and secured
+#[derive(Clone)]
+pub struct Tls {
+    /// `None` leaves the backend's defaults
+    connector: Option<Connector>,
+    handshake_timeout: Duration,
+    tcp: TcpOptions,
+}
+
+impl Default for Tls {
+    fn default() -> Self {
+        Self {
+            connector: None,
+            handshake_timeout: Duration::from_secs(10),
+            tcp: TcpOptions::default(),
+        }
+    }
+}
+
+impl Tls {
+    /// TLS settings of `config`, reading the pinned roots if any
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let bundle = config
+            .tls_ca_file
+            .as_deref()
+            .map(|path| {
+                std::fs::read_to_string(path).map_err(|e| {
+                    MarketDataError::ConfigError(format!(
+                        "Failed to read TLS_CA_FILE {}: {}",
+                        path, e
+                    ))
+                })
+            })
+            .transpose()?;
+        let roots = bundle.as_deref().map(pem_blocks);
+        if roots.as_ref().is_some_and(Vec::is_empty) {
+            return Err(MarketDataError::ConfigError(
+                "TLS_CA_FILE holds no PEM certificates".to_string(),
+            ));
+        }
+        Ok(Self {
+            connector: connector(roots.as_deref())?,
+            handshake_timeout: Duration::from_millis(config.tls_handshake_timeout_ms),
+            tcp: TcpOptions::from_config(config),
+        })
+    }
+
+    /// Name of the compiled-in backend in use
+    pub fn backend() -> &'static str {
+        if cfg!(feature = "rustls") {
+            "rustls"
+        } else if cfg!(feature = "native-tls") {
+            "native-tls"
+        } else {
+            "none"
+        }
+    }
+
+    /// Open a WebSocket connection to `url` within the handshake timeout
+    pub async fn connect(&self, url: &str) -> Result<(WsStream, Response)> {
+        let parsed = Url::parse(url).map_err(|e| {
+            MarketDataError::WebSocketConnection(format!("Invalid URL {}: {}", url, e))
+        })?;
+        let host = parsed.host_str().ok_or_else(|| {
+            MarketDataError::WebSocketConnection(format!("URL {} has no host", url))
+        })?;
+        let port = parsed.port_or_known_default().unwrap_or(443);
+
+        timeout(self.handshake_timeout, async {
+            let stream = self.tcp.connect(host, port).await?;
+            self.handshake(url, stream).await.map_err(|e| {
+                MarketDataError::WebSocketConnection(format!("Failed to connect: {}", e))
+            })
+        })
+        .await
+        .map_err(|_| MarketDataError::ConnectionTimeout)?
+    }
+
+    /// WebSocket (and TLS) handshake over a connected socket
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    async fn handshake(
+        &self,
+        url: &str,
+        stream: TcpStream,
+    ) -> std::result::Result<(WsStream, Response), tungstenite::Error> {
+        tokio_tungstenite::client_async_tls_with_config(url, stream, None, self.connector.clone())
+            .await
+    }
+
+    /// WebSocket handshake over a connected socket; `wss://` needs a backend
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    async fn handshake(
+        &self,
+        url: &str,
+        stream: TcpStream,
+    ) -> std::result::Result<(WsStream, Response), tungstenite::Error> {
+        if let Some(connector) = &self.connector {
+            match *connector {}
+        }
+        if url.starts_with("wss://") {
+            return Err(tungstenite::Error::Url(
+                tungstenite::error::UrlError::TlsFeatureNotEnabled,
+            ));
+        }
+        tokio_tungstenite::client_async(url, MaybeTlsStream::Plain(stream)).await
+    }
+}
+
+/// PEM certificate blocks of a bundle, markers included
+fn pem_blocks(bundle: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = bundle;
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let Some(len) = rest[start..].find(PEM_END) else {
+            break;
+        };
+        let end = start + len + PEM_END.len();
+        blocks.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    blocks
+}
+
+/// rustls connector trusting `roots`, or the webpki roots without any
+#[cfg(feature = "rustls")]
+fn connector(roots: Option<&[&str]>) -> Result<Option<Connector>> {
+    use std::sync::Arc;
+
+    let invalid = |e: &dyn std::fmt::Display| {
+        MarketDataError::ConfigError(format!("Invalid TLS_CA_FILE certificate: {}", e))
+    };
+    let mut store = rustls::RootCertStore::empty();
+    match roots {
+        Some(blocks) => {
+            for block in blocks {
+                for cert in rustls_pemfile::certs(&mut block.as_bytes()) {
+                    store
+                        .add(cert.map_err(|e| invalid(&e))?)
+                        .map_err(|e| invalid(&e))?;
+                }
+            }
+        }
+        None => store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(store)
+        .with_no_client_auth();
+    Ok(Some(Connector::Rustls(Arc::new(config))))
+}
+
+/// native-tls connector trusting only `roots`, or the platform's defaults
+/// without any
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+fn connector(roots: Option<&[&str]>) -> Result<Option<Connector>> {
+    let invalid = |e: native_tls::Error| {
+        MarketDataError::ConfigError(format!("Invalid TLS_CA_FILE certificate: {}", e))
+    };
+    let Some(blocks) = roots else {
+        return Ok(None);
+    };
+    let mut builder = native_tls::TlsConnector::builder();
+    builder.disable_built_in_roots(true);
+    for block in blocks {
+        builder.add_root_certificate(
+            native_tls::Certificate::from_pem(block.as_bytes()).map_err(invalid)?,
+        );
+    }
+    Ok(Some(Connector::NativeTls(
+        builder.build().map_err(invalid)?,
+    )))
+}
+
+/// No connector to build; pinned roots need a backend to apply them
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+fn connector(roots: Option<&[&str]>) -> Result<Option<Connector>> {
+    match roots {
+        Some(_) => Err(MarketDataError::ConfigError(
+            "TLS_CA_FILE needs the native-tls or rustls feature".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pem_blocks() {
+        let bundle = format!(
+            "# Root A\n{}\nAAAA\n{}\n\n# Root B\n{}\nBBBB\n{}\n{}\ntruncated",
+            PEM_BEGIN, PEM_END, PEM_BEGIN, PEM_END, PEM_BEGIN
+        );
+        let blocks = pem_blocks(&bundle);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].starts_with(PEM_BEGIN) && blocks[0].ends_with(PEM_END));
+        assert!(blocks[1].contains("BBBB"));
+        assert!(pem_blocks("no certificates").is_empty());
+    }
+}
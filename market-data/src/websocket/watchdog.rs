@@ -0,0 +1,98 @@
+//! Per-symbol staleness watchdog
+//!
+//! Binance occasionally stops delivering one stream of a combined
+//! connection while the others keep flowing, which the connection-level
+//! keepalive never notices. The watchdog compares each book's last update
+//! time with the threshold and picks out symbols that went silent while
+//! another book is still fresh; the manager then resubscribes their depth
+//! stream and resyncs them from a snapshot. When every book is silent the
+//! connection itself is at fault and is left to the receive timeout.
+
+use std::collections::HashMap;
+
+/// Picks out books to recover
+#[derive(Debug)]
+pub struct StalenessWatchdog {
+    threshold_ms: u64,
+    /// When each symbol was last recovered, so a quiet market isn't
+    /// resubscribed more than once per threshold
+    recovered_at: HashMap<String, u64>,
+}
+
+impl StalenessWatchdog {
+    /// Flag books not updated for `threshold_ms`
+    pub fn new(threshold_ms: u64) -> Self {
+        Self {
+            threshold_ms,
+            recovered_at: HashMap::new(),
+        }
+    }
+
+    /// Stale symbols among `update_times` (symbol, last update ms) as of
+    /// `now_ms`, with those that should be recovered now
+    pub fn check(&mut self, update_times: &[(String, u64)], now_ms: u64) -> Staleness {
+        let is_stale = |updated_at: u64| now_ms.saturating_sub(updated_at) > self.threshold_ms;
+        let stale: Vec<String> = update_times
+            .iter()
+            .filter(|(_, updated_at)| is_stale(*updated_at))
+            .map(|(symbol, _)| symbol.clone())
+            .collect();
+        let others_flowing = stale.len() < update_times.len();
+
+        let mut recover = Vec::new();
+        if others_flowing {
+            for symbol in &stale {
+                let due = self
+                    .recovered_at
+                    .get(symbol)
+                    .is_none_or(|at| now_ms.saturating_sub(*at) > self.threshold_ms);
+                if due {
+                    self.recovered_at.insert(symbol.clone(), now_ms);
+                    recover.push(symbol.clone());
+                }
+            }
+        }
+        Staleness { stale, recover }
+    }
+}
+
+/// Result of a watchdog check
+#[derive(Debug, Default, PartialEq)]
+pub struct Staleness {
+    /// Books past the threshold
+    pub stale: Vec<String>,
+    /// Stale books to resubscribe and resync now
+    pub recover: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(entries: &[(&str, u64)]) -> Vec<(String, u64)> {
+        entries
+            .iter()
+            .map(|(symbol, at)| (symbol.to_string(), *at))
+            .collect()
+    }
+
+    #[test]
+    fn test_recovers_silent_symbol_while_others_flow() {
+        let mut watchdog = StalenessWatchdog::new(10_000);
+
+        let result = watchdog.check(&times(&[("BTCUSDT", 19_000), ("ETHUSDT", 5_000)]), 20_000);
+        assert_eq!(result.stale, ["ETHUSDT"]);
+        assert_eq!(result.recover, ["ETHUSDT"]);
+
+        // Not again until another threshold has passed
+        let result = watchdog.check(&times(&[("BTCUSDT", 25_000), ("ETHUSDT", 5_000)]), 25_000);
+        assert!(result.recover.is_empty());
+        let result = watchdog.check(&times(&[("BTCUSDT", 31_000), ("ETHUSDT", 5_000)]), 31_000);
+        assert_eq!(result.recover, ["ETHUSDT"]);
+
+        // Everything silent is a connection problem, not the watchdog's
+        let result = watchdog.check(&times(&[("BTCUSDT", 1_000), ("ETHUSDT", 1_000)]), 60_000);
+        assert_eq!(result.stale.len(), 2);
+        assert!(result.recover.is_empty());
+    }
+}
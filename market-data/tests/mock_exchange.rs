@@ -0,0 +1,248 @@
+//! End-to-end tests of the WebSocket manager against the mock exchange
+
+#![cfg(feature = "runtime")]
+
+use orp_flow_market_data::config::StreamEndpoint;
+use orp_flow_market_data::shutdown::Shutdown;
+use orp_flow_market_data::testing::{self, MockExchange, Session};
+use orp_flow_market_data::time::Clock;
+use orp_flow_market_data::{AppState, Config, WebSocketManager};
+use rust_decimal_macros::dec;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const SYMBOL: &str = "BTCUSDT";
+
+/// Run a manager against `exchange` until the returned shutdown triggers
+async fn run_feed(exchange: &MockExchange) -> (Arc<AppState>, Shutdown, JoinHandle<()>) {
+    run_feed_on(exchange.config(&[SYMBOL]), Clock::wall()).await
+}
+
+async fn run_feed_on(config: Config, time: Clock) -> (Arc<AppState>, Shutdown, JoinHandle<()>) {
+    let (state, resyncs) = testing::app_state(config, time).await.unwrap();
+    let shutdown = Shutdown::new();
+    let mut manager = WebSocketManager::new(state.clone())
+        .with_shutdown(shutdown.clone())
+        .with_resyncs(resyncs);
+    let task = tokio::spawn(async move { manager.run().await.unwrap() });
+    (state, shutdown, task)
+}
+
+/// Wait until the book reaches `last_update_id`
+async fn book_reaches(state: &AppState, last_update_id: u64) {
+    let reached = async {
+        while state
+            .books
+            .state(SYMBOL)
+            .is_none_or(|book| book.last_update_id != last_update_id)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    if tokio::time::timeout(Duration::from_secs(10), reached)
+        .await
+        .is_err()
+    {
+        let at = state.books.state(SYMBOL).map(|book| book.last_update_id);
+        panic!("book at {:?}, expected {}", at, last_update_id);
+    }
+}
+
+async fn stop(shutdown: Shutdown, task: JoinHandle<()>) {
+    shutdown.trigger();
+    tokio::time::timeout(Duration::from_secs(5), task)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_book_follows_snapshot_and_diffs() {
+    let exchange = MockExchange::start().await.unwrap();
+    exchange.snapshot(SYMBOL, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    exchange.session(
+        Session::new()
+            // Predates the snapshot
+            .depth(SYMBOL, 90, 99, &[("99.0", "5.0")], &[])
+            .depth(SYMBOL, 98, 102, &[("100.5", "2.0")], &[])
+            // Out of order: already covered
+            .depth(SYMBOL, 101, 101, &[("100.7", "9.0")], &[])
+            .depth(SYMBOL, 103, 104, &[], &[("101.0", "0"), ("101.5", "3.0")])
+            .trade(SYMBOL, 1, ("100.9", "0.5"), false),
+    );
+
+    let (state, shutdown, task) = run_feed(&exchange).await;
+    book_reaches(&state, 104).await;
+
+    let book = state.books.state(SYMBOL).unwrap();
+    assert_eq!(book.bids[0].price, dec!(100.5));
+    assert_eq!(book.bids.len(), 2);
+    assert_eq!(book.asks[0].price, dec!(101.5));
+    assert_eq!(exchange.snapshot_requests(SYMBOL), 1);
+    assert_eq!(
+        exchange.connection_targets(),
+        ["/stream?streams=btcusdt@depth@100ms/btcusdt@trade"]
+    );
+
+    // The trade reaches the rolling metrics kept by the book's task
+    let counted = async {
+        loop {
+            let now_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            if let Some(metrics) = state.books.trade_metrics(SYMBOL, now_ms).await {
+                break metrics;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    let metrics = tokio::time::timeout(Duration::from_secs(10), counted)
+        .await
+        .unwrap();
+    assert_eq!(metrics.buy_count, 1);
+    assert_eq!(metrics.buy_volume, dec!(0.5));
+    stop(shutdown, task).await;
+}
+
+#[tokio::test]
+async fn test_streams_beyond_the_url_limit_are_subscribed_after_connecting() {
+    let exchange = MockExchange::start().await.unwrap();
+    let symbols: Vec<String> = std::iter::once(SYMBOL.to_string())
+        .chain((1..=100).map(|i| format!("SYM{}USDT", i)))
+        .collect();
+    for symbol in &symbols {
+        exchange.snapshot(symbol, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    }
+    exchange.session(Session::new().depth(SYMBOL, 101, 102, &[("100.5", "2.0")], &[]));
+
+    let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
+    let (state, shutdown, task) = run_feed_on(exchange.config(&symbols), Clock::wall()).await;
+    book_reaches(&state, 102).await;
+
+    // 202 streams, in batches of 200
+    assert_eq!(exchange.connection_targets(), ["/stream"]);
+    assert_eq!(exchange.subscribe_requests(), 2);
+    assert_eq!(state.subscriptions.status().mode, "batched");
+    stop(shutdown, task).await;
+}
+
+#[tokio::test]
+async fn test_bare_endpoint_subscribes_with_acknowledged_requests() {
+    let exchange = MockExchange::start().await.unwrap();
+    exchange.snapshot(SYMBOL, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    exchange.session(
+        Session::new()
+            .depth(SYMBOL, 101, 102, &[("100.5", "2.0")], &[])
+            .trade(SYMBOL, 1, ("100.9", "0.5"), false)
+            .depth(SYMBOL, 103, 103, &[], &[("101.5", "3.0")]),
+    );
+
+    let mut config = exchange.config(&[SYMBOL]);
+    config.ws_stream_endpoint = StreamEndpoint::Raw;
+    let (state, shutdown, task) = run_feed_on(config, Clock::wall()).await;
+    book_reaches(&state, 103).await;
+
+    assert_eq!(exchange.connection_targets(), ["/ws"]);
+    assert_eq!(exchange.subscribe_requests(), 1);
+    let subscriptions = state.subscriptions.status();
+    assert_eq!(subscriptions.confirmed_streams, 2);
+    assert_eq!(subscriptions.pending_requests, 0);
+    stop(shutdown, task).await;
+}
+
+#[tokio::test]
+async fn test_gap_resyncs_from_a_new_snapshot() {
+    let exchange = MockExchange::start().await.unwrap();
+    exchange.snapshot(SYMBOL, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    exchange.snapshot(SYMBOL, 200, &[("99.0", "4.0")], &[("102.0", "4.0")]);
+    exchange.session(
+        Session::new()
+            .depth(SYMBOL, 101, 101, &[("100.5", "2.0")], &[])
+            // Updates 102..=149 never arrive
+            .pause(Duration::from_millis(200))
+            .depth(SYMBOL, 150, 151, &[("100.6", "1.0")], &[])
+            .pause(Duration::from_millis(300))
+            .depth(SYMBOL, 201, 201, &[("99.5", "1.0")], &[]),
+    );
+
+    let (state, shutdown, task) = run_feed(&exchange).await;
+    book_reaches(&state, 201).await;
+
+    let book = state.books.state(SYMBOL).unwrap();
+    assert_eq!(book.bids[0].price, dec!(99.5));
+    assert_eq!(book.asks[0].price, dec!(102.0));
+    assert_eq!(exchange.snapshot_requests(SYMBOL), 2);
+    assert_eq!(exchange.connections(), 1);
+    stop(shutdown, task).await;
+}
+
+#[tokio::test]
+async fn test_reconnects_and_resyncs_after_disconnect() {
+    let exchange = MockExchange::start().await.unwrap();
+    exchange.snapshot(SYMBOL, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    exchange.snapshot(SYMBOL, 300, &[("98.0", "1.0")], &[("103.0", "1.0")]);
+    exchange.session(
+        Session::new()
+            .pause(Duration::from_millis(200))
+            .depth(SYMBOL, 101, 101, &[("100.5", "2.0")], &[])
+            .pause(Duration::from_millis(100))
+            .disconnect(),
+    );
+    exchange.session(Session::new().pause(Duration::from_millis(200)).depth(
+        SYMBOL,
+        301,
+        302,
+        &[],
+        &[("102.5", "1.0")],
+    ));
+
+    let (state, shutdown, task) = run_feed(&exchange).await;
+    book_reaches(&state, 101).await;
+    book_reaches(&state, 302).await;
+
+    let book = state.books.state(SYMBOL).unwrap();
+    assert_eq!(book.bids[0].price, dec!(98.0));
+    assert_eq!(book.asks[0].price, dec!(102.5));
+    assert_eq!(exchange.connections(), 2);
+    assert_eq!(exchange.snapshot_requests(SYMBOL), 2);
+    stop(shutdown, task).await;
+}
+
+#[tokio::test]
+async fn test_reconnect_backoff_waits_on_the_feed_clock() {
+    let exchange = MockExchange::start().await.unwrap();
+    exchange.snapshot(SYMBOL, 100, &[("100.0", "1.0")], &[("101.0", "1.0")]);
+    exchange.snapshot(SYMBOL, 300, &[("98.0", "1.0")], &[("103.0", "1.0")]);
+    exchange.session(
+        Session::new()
+            .pause(Duration::from_millis(200))
+            .depth(SYMBOL, 101, 101, &[("100.5", "2.0")], &[])
+            .disconnect(),
+    );
+    exchange.session(Session::new().pause(Duration::from_millis(200)).depth(
+        SYMBOL,
+        301,
+        301,
+        &[],
+        &[],
+    ));
+
+    let time = Clock::manual(1_700_000_000_000_000);
+    let config = Config {
+        reconnect_delay_ms: 1_000,
+        ..exchange.config(&[SYMBOL])
+    };
+    let (state, shutdown, task) = run_feed_on(config, time.clone()).await;
+    book_reaches(&state, 101).await;
+
+    // However long the test waits, the backoff hasn't elapsed on the clock
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(exchange.connections(), 1);
+
+    time.advance(Duration::from_secs(60));
+    book_reaches(&state, 301).await;
+    assert_eq!(exchange.connections(), 2);
+    stop(shutdown, task).await;
+}
@@ -0,0 +1,343 @@
+//! Property tests of `OrderBook` against a naive reference model
+//!
+//! Random snapshot and diff sequences, with stale and gapped update ids
+//! mixed in, are applied both to the book and to a pair of `BTreeMap`s
+//! that set and delete levels with no depth limit. While initialized, the
+//! book must hold the model's best levels up to its depth (or, without an
+//! overflow buffer, a thinning subset of the model's levels), sorted, with
+//! matching depth totals, and never crossed.
+
+use orp_flow_market_data::event::{BookSnapshot, DepthDelta, PriceLevel, Venue};
+use orp_flow_market_data::orderbook::{CrossedBookPolicy, Level, OrderBook};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+const SYMBOL: &str = "BTCUSDT";
+
+/// Prices in ticks of 0.1 and quantities in lots of 0.5; a zero quantity
+/// deletes a level
+type Levels = Vec<(u32, u32)>;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Snapshot {
+        bids: Levels,
+        asks: Levels,
+    },
+    /// `start` picks the update's ids: 0 stale, 1 a gap, otherwise
+    /// continuing the sequence, overlapping it by up to two ids
+    Update {
+        start: u8,
+        len: u64,
+        bids: Levels,
+        asks: Levels,
+    },
+}
+
+fn price(ticks: u32) -> Decimal {
+    Decimal::new(ticks as i64, 1)
+}
+
+fn quantity(lots: u32) -> Decimal {
+    Decimal::new(lots as i64 * 5, 1)
+}
+
+fn price_levels(levels: &Levels) -> Vec<PriceLevel> {
+    levels
+        .iter()
+        .map(|&(ticks, lots)| PriceLevel {
+            price: price(ticks),
+            quantity: quantity(lots),
+        })
+        .collect()
+}
+
+/// Ops with bids priced in `bid_ticks` and asks in `ask_ticks`, always
+/// starting with a snapshot
+fn ops(
+    bid_ticks: std::ops::Range<u32>,
+    ask_ticks: std::ops::Range<u32>,
+) -> impl Strategy<Value = Vec<Op>> {
+    let side =
+        |ticks: std::ops::Range<u32>, max: usize| prop::collection::vec((ticks, 0u32..6), 0..max);
+    let snapshot = (side(bid_ticks.clone(), 16), side(ask_ticks.clone(), 16))
+        .prop_map(|(bids, asks)| Op::Snapshot { bids, asks });
+    let update = (0u8..12, 0u64..3, side(bid_ticks, 5), side(ask_ticks, 5)).prop_map(
+        |(start, len, bids, asks)| Op::Update {
+            start,
+            len,
+            bids,
+            asks,
+        },
+    );
+    (
+        snapshot.clone(),
+        prop::collection::vec(prop_oneof![1 => snapshot, 12 => update], 1..60),
+    )
+        .prop_map(|(first, rest)| std::iter::once(first).chain(rest).collect())
+}
+
+/// Every level ever set, with no depth limit
+#[derive(Debug, Default)]
+struct Reference {
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    last_update_id: u64,
+    initialized: bool,
+}
+
+impl Reference {
+    fn init(&mut self, snapshot: &BookSnapshot) {
+        let side = |levels: &[PriceLevel]| {
+            levels
+                .iter()
+                .filter(|level| level.quantity > Decimal::ZERO)
+                .map(|level| (level.price, level.quantity))
+                .collect()
+        };
+        self.bids = side(&snapshot.bids);
+        self.asks = side(&snapshot.asks);
+        self.last_update_id = snapshot.last_update_id;
+        self.initialized = true;
+    }
+
+    /// Whether the update applies; `None` for a gap, after which the book
+    /// awaits a snapshot
+    fn apply(&mut self, update: &DepthDelta) -> Option<bool> {
+        if !self.initialized || update.final_update_id <= self.last_update_id {
+            return Some(false);
+        }
+        if update.first_update_id > self.last_update_id + 1 {
+            self.initialized = false;
+            return None;
+        }
+        for (levels, side) in [
+            (&update.bids, &mut self.bids),
+            (&update.asks, &mut self.asks),
+        ] {
+            for level in levels {
+                if level.quantity.is_zero() {
+                    side.remove(&level.price);
+                } else {
+                    side.insert(level.price, level.quantity);
+                }
+            }
+        }
+        self.last_update_id = update.final_update_id;
+        Some(true)
+    }
+
+    /// Best `depth` bids, from the best down
+    fn best_bids(&self, depth: usize) -> Vec<(Decimal, Decimal)> {
+        self.bids
+            .iter()
+            .rev()
+            .take(depth)
+            .map(|(&p, &q)| (p, q))
+            .collect()
+    }
+
+    /// Best `depth` asks, from the best up
+    fn best_asks(&self, depth: usize) -> Vec<(Decimal, Decimal)> {
+        self.asks
+            .iter()
+            .take(depth)
+            .map(|(&p, &q)| (p, q))
+            .collect()
+    }
+}
+
+fn pairs(levels: &[Level]) -> Vec<(Decimal, Decimal)> {
+    levels.iter().map(|l| (l.price, l.quantity)).collect()
+}
+
+/// Sorted sides of positive levels, not crossed, with depth totals and
+/// level counts matching the levels
+fn check_invariants(book: &OrderBook) -> Result<(), TestCaseError> {
+    let state = book.state();
+    prop_assert!(state.bids.windows(2).all(|w| w[0].price > w[1].price));
+    prop_assert!(state.asks.windows(2).all(|w| w[0].price < w[1].price));
+    let levels = state.bids.iter().chain(&state.asks);
+    prop_assert!(levels.into_iter().all(|l| l.quantity > Decimal::ZERO));
+    if let (Some(bid), Some(ask)) = (state.bids.first(), state.asks.first()) {
+        prop_assert!(
+            bid.price < ask.price,
+            "crossed: {} >= {}",
+            bid.price,
+            ask.price
+        );
+    }
+    let total = |levels: &[Level]| levels.iter().map(|l| l.quantity).sum::<Decimal>();
+    prop_assert_eq!(state.metrics.bid_depth, Some(total(&state.bids)));
+    prop_assert_eq!(state.metrics.ask_depth, Some(total(&state.asks)));
+    prop_assert_eq!(state.metrics.bid_levels, state.bids.len());
+    prop_assert_eq!(state.metrics.ask_levels, state.asks.len());
+    Ok(())
+}
+
+/// Whether every level of `levels` is in `side` with the same quantity
+fn known(levels: &[Level], side: &BTreeMap<Decimal, Decimal>) -> bool {
+    levels
+        .iter()
+        .all(|l| side.get(&l.price) == Some(&l.quantity))
+}
+
+/// Apply `ops` to `book` and the reference, comparing after each; a book
+/// that `thins` need only hold levels the reference knows
+fn run(mut book: OrderBook, depth: usize, thins: bool, ops: &[Op]) -> Result<(), TestCaseError> {
+    let mut reference = Reference::default();
+    let mut last_id = 0u64;
+    for op in ops {
+        match op {
+            Op::Snapshot { bids, asks } => {
+                last_id += 10;
+                let snapshot = BookSnapshot {
+                    venue: Venue::Binance,
+                    symbol: SYMBOL.to_string(),
+                    last_update_id: last_id,
+                    bids: price_levels(bids),
+                    asks: price_levels(asks),
+                };
+                book.init_snapshot(&snapshot);
+                reference.init(&snapshot);
+            }
+            Op::Update {
+                start,
+                len,
+                bids,
+                asks,
+            } => {
+                let next = reference.last_update_id + 1;
+                let first = match start {
+                    0 => next.saturating_sub(3),
+                    1 => next + 1,
+                    n => next.saturating_sub((*n as u64) % 3),
+                };
+                let final_id = match start {
+                    0 => next - 1,
+                    _ => (first + len).max(next),
+                };
+                let update = DepthDelta {
+                    venue: Venue::Binance,
+                    symbol: SYMBOL.to_string(),
+                    event_time: final_id,
+                    first_update_id: first,
+                    final_update_id: final_id,
+                    bids: price_levels(bids),
+                    asks: price_levels(asks),
+                };
+                last_id = last_id.max(final_id);
+                match (book.apply_update(&update), reference.apply(&update)) {
+                    (Ok(applied), Some(expected)) => prop_assert_eq!(applied, expected),
+                    (Err(_), None) => {}
+                    // The book ran out of known levels for its depth
+                    (Err(_), Some(true)) if !thins && !book.is_initialized() => {
+                        reference.initialized = false;
+                    }
+                    (result, expected) => {
+                        prop_assert!(false, "book {:?}, reference {:?}", result, expected)
+                    }
+                }
+            }
+        }
+
+        prop_assert_eq!(book.is_initialized(), reference.initialized);
+        if reference.initialized {
+            let state = book.state();
+            prop_assert_eq!(state.last_update_id, reference.last_update_id);
+            if thins {
+                prop_assert!(state.bids.len() <= depth && state.asks.len() <= depth);
+                prop_assert!(known(&state.bids, &reference.bids));
+                prop_assert!(known(&state.asks, &reference.asks));
+            } else {
+                prop_assert_eq!(pairs(&state.bids), reference.best_bids(depth));
+                prop_assert_eq!(pairs(&state.asks), reference.best_asks(depth));
+            }
+            check_invariants(&book)?;
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    /// A book deeper than any generated side holds exactly the model
+    #[test]
+    fn test_deep_book_matches_reference(ops in ops(1..200, 200..400)) {
+        run(OrderBook::new(SYMBOL, 1000), 1000, false, &ops)?;
+    }
+
+    /// A shallow book holds the model's best levels, or gives up for a
+    /// resync once it no longer knows enough of them
+    #[test]
+    fn test_shallow_book_holds_best_reference_levels(
+        depth in 1usize..6,
+        overflow in 1usize..4,
+        ops in ops(1..30, 30..60),
+    ) {
+        let book = OrderBook::new(SYMBOL, depth).with_overflow_levels(overflow);
+        run(book, depth, false, &ops)?;
+    }
+
+    /// A shallow book without an overflow buffer thins rather than
+    /// resyncing, holding only levels the model knows
+    #[test]
+    fn test_shallow_book_without_overflow_thins(
+        depth in 1usize..6,
+        ops in ops(1..30, 30..60),
+    ) {
+        run(OrderBook::new(SYMBOL, depth), depth, true, &ops)?;
+    }
+
+    /// Crossing updates never leave a book crossed, whatever its policy
+    #[test]
+    fn test_crossing_updates_never_leave_book_crossed(
+        policy in prop_oneof![
+            Just(CrossedBookPolicy::Reject),
+            Just(CrossedBookPolicy::Trim),
+            Just(CrossedBookPolicy::Resync),
+        ],
+        ops in ops(1..40, 20..60),
+    ) {
+        let mut book = OrderBook::new(SYMBOL, 1000).with_crossed_policy(policy);
+        let mut last_id = 0;
+        for op in &ops {
+            match op {
+                Op::Snapshot { bids, asks } => {
+                    // Snapshots are uncrossed: drop bids at or above the best ask
+                    let best_ask = asks.iter().filter(|(_, lots)| *lots > 0).map(|(t, _)| *t).min();
+                    let bids: Levels = bids
+                        .iter()
+                        .copied()
+                        .filter(|(ticks, _)| best_ask.is_none_or(|ask| *ticks < ask))
+                        .collect();
+                    last_id += 10;
+                    book.init_snapshot(&BookSnapshot {
+                        venue: Venue::Binance,
+                        symbol: SYMBOL.to_string(),
+                        last_update_id: last_id,
+                        bids: price_levels(&bids),
+                        asks: price_levels(asks),
+                    });
+                }
+                Op::Update { len, bids, asks, .. } => {
+                    let first = book.last_update_id() + 1;
+                    let update = DepthDelta {
+                        venue: Venue::Binance,
+                        symbol: SYMBOL.to_string(),
+                        event_time: first,
+                        first_update_id: first,
+                        final_update_id: first + len,
+                        bids: price_levels(bids),
+                        asks: price_levels(asks),
+                    };
+                    let _ = book.apply_update(&update);
+                    last_id = last_id.max(first + len);
+                }
+            }
+            if book.is_initialized() {
+                check_invariants(&book)?;
+            }
+        }
+    }
+}
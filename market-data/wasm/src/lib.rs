@@ -0,0 +1,188 @@
+//! WebAssembly build of the parser and order book
+//!
+//! Lets browser dashboards keep books client-side with the handler's own
+//! logic, built with `wasm-pack build --target web` in this directory. The
+//! core crate is used without its `runtime` feature, leaving the parser
+//! and order book.
+//!
+//! ```js
+//! import init, { Book } from "./pkg/orp_flow_wasm.js";
+//! await init();
+//! const book = new Book("BTCUSDT", 100);
+//! const ws = new WebSocket("ws://localhost:8080/ws?symbols=BTCUSDT");
+//! ws.onmessage = (e) => {
+//!   if (book.applyFrame(e.data)) render(book.bestBid(), book.bestAsk());
+//! };
+//! ```
+//!
+//! A book follows the re-broadcast `/ws` endpoint's `book` frames, or the
+//! exchange's own REST snapshot and diff depth updates. Results that are
+//! structures (`state`, `parseEvent`) are returned as JSON strings.
+
+use orp_flow_market_data::event::{BookSnapshot, MarketEvent, PriceLevel, Venue};
+use orp_flow_market_data::orderbook::{Level, OrderBook, OrderBookState};
+use orp_flow_market_data::parser::{self, OrderBookSnapshot};
+use rust_decimal::prelude::ToPrimitive;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+/// Frame of the `/ws` re-broadcast endpoint
+#[derive(Deserialize)]
+struct Frame {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+fn levels(levels: &[Level]) -> Vec<PriceLevel> {
+    levels
+        .iter()
+        .map(|level| PriceLevel {
+            price: level.price,
+            quantity: level.quantity,
+        })
+        .collect()
+}
+
+fn pair(level: Option<Level>) -> Option<Vec<f64>> {
+    level.map(|level| {
+        vec![
+            level.price.to_f64().unwrap_or(f64::NAN),
+            level.quantity.to_f64().unwrap_or(f64::NAN),
+        ]
+    })
+}
+
+/// Order book of one symbol
+#[wasm_bindgen]
+pub struct Book {
+    book: OrderBook,
+    symbol: String,
+}
+
+#[wasm_bindgen]
+impl Book {
+    /// Empty book keeping up to `max_depth` levels per side
+    #[wasm_bindgen(constructor)]
+    pub fn new(symbol: &str, max_depth: usize) -> Book {
+        Book {
+            book: OrderBook::new(symbol, max_depth),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    /// Take the state of a `/ws` `book` frame for this symbol; false for
+    /// other frames
+    #[wasm_bindgen(js_name = applyFrame)]
+    pub fn apply_frame(&mut self, json: &str) -> Result<bool, JsError> {
+        self.try_apply_frame(json).map_err(|e| JsError::new(&e))
+    }
+
+    /// (Re)initialize from a REST depth snapshot
+    #[wasm_bindgen(js_name = applySnapshot)]
+    pub fn apply_snapshot(&mut self, json: &str) -> Result<(), JsError> {
+        self.try_apply_snapshot(json).map_err(|e| JsError::new(&e))
+    }
+
+    /// Apply a diff depth update; false if stale or awaiting a snapshot.
+    /// Throws on a sequence gap, after which the book awaits a snapshot.
+    #[wasm_bindgen(js_name = applyUpdate)]
+    pub fn apply_update(&mut self, json: &str) -> Result<bool, JsError> {
+        self.try_apply_update(json).map_err(|e| JsError::new(&e))
+    }
+
+    /// `[price, quantity]` of the best bid, if any
+    #[wasm_bindgen(js_name = bestBid)]
+    pub fn best_bid(&self) -> Option<Vec<f64>> {
+        pair(self.book.bbo().best_bid)
+    }
+
+    /// `[price, quantity]` of the best ask, if any
+    #[wasm_bindgen(js_name = bestAsk)]
+    pub fn best_ask(&self) -> Option<Vec<f64>> {
+        pair(self.book.bbo().best_ask)
+    }
+
+    #[wasm_bindgen(js_name = midPrice)]
+    pub fn mid_price(&self) -> Option<f64> {
+        self.book.mid_price().and_then(|mid| mid.to_f64())
+    }
+
+    /// The book's levels and metrics, as `OrderBookState` JSON
+    pub fn state(&self) -> String {
+        serde_json::to_string(&self.book.state()).unwrap_or_default()
+    }
+}
+
+impl Book {
+    fn try_apply_frame(&mut self, json: &str) -> Result<bool, String> {
+        let frame: Frame = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if frame.kind != "book" {
+            return Ok(false);
+        }
+        let state: OrderBookState =
+            serde_json::from_value(frame.data).map_err(|e| e.to_string())?;
+        if state.symbol != self.symbol {
+            return Ok(false);
+        }
+        self.book.init_snapshot(&BookSnapshot {
+            venue: Venue::Binance,
+            symbol: state.symbol,
+            last_update_id: state.last_update_id,
+            bids: levels(&state.bids),
+            asks: levels(&state.asks),
+        });
+        Ok(true)
+    }
+
+    fn try_apply_snapshot(&mut self, json: &str) -> Result<(), String> {
+        let snapshot: OrderBookSnapshot = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        self.book.init_snapshot(&snapshot.into_event(&self.symbol));
+        Ok(())
+    }
+
+    fn try_apply_update(&mut self, json: &str) -> Result<bool, String> {
+        match parser::parse_event(json).map_err(|e| e.to_string())? {
+            Some(MarketEvent::DepthDelta(delta)) if delta.symbol == self.symbol => {
+                self.book.apply_update(&delta).map_err(|e| e.to_string())
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Normalize a raw exchange message into a `MarketEvent`, as JSON;
+/// undefined for messages that aren't events
+#[wasm_bindgen(js_name = parseEvent)]
+pub fn parse_event(raw: &str) -> Result<Option<String>, JsError> {
+    let event = parser::parse_event(raw).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(event.and_then(|event| serde_json::to_string(&event).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_book_follows_ws_frames_and_depth_updates() {
+        let mut book = Book::new("BTCUSDT", 100);
+        let frame = r#"{"type":"book","data":{"symbol":"BTCUSDT","timestamp":0,
+            "last_update_id":100,"bids":[{"price":"100","quantity":"1"}],
+            "asks":[{"price":"101","quantity":"2"}],"metrics":{"bid_depth":"1",
+            "ask_depth":"2","bid_levels":1,"ask_levels":1}}}"#;
+        assert_eq!(book.try_apply_frame(frame), Ok(true));
+        assert_eq!(
+            book.try_apply_frame(r#"{"type":"subscriptions","data":["BTCUSDT"]}"#),
+            Ok(false)
+        );
+        assert_eq!(book.best_bid(), Some(vec![100.0, 1.0]));
+
+        let update =
+            r#"{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":101,"u":101,"b":[],"a":[["100.5","1"]]}"#;
+        assert_eq!(book.try_apply_update(update), Ok(true));
+        assert_eq!(book.best_ask(), Some(vec![100.5, 1.0]));
+        assert_eq!(book.mid_price(), Some(100.25));
+        assert!(book.state().contains(r#""last_update_id":101"#));
+    }
+}